@@ -0,0 +1,17 @@
+use std::process::Command;
+
+fn main() {
+    // Unlike the binaries' build scripts this one does not insist on git: a
+    // source tarball should still build, and report the hash as unknown.
+    let hash = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .unwrap_or_else(|| "unknown".into());
+    println!("cargo:rustc-env=ARCADE_BUILD_HASH={hash}");
+    println!("cargo:rustc-env=ARCADE_BUILD_TARGET={}", std::env::var("TARGET").unwrap());
+    println!("cargo:rustc-env=ARCADE_BUILD_PROFILE={}", std::env::var("PROFILE").unwrap());
+}
@@ -0,0 +1,169 @@
+//! What a binary was built from, reported the same way everywhere.
+//!
+//! Support questions arrive from the launcher, the games, the relay and the
+//! ops tools alike. Each of them builds a [`BuildInfo`] with [`build_info!`],
+//! adds what only it knows (the libraries and protocols it speaks, its
+//! enabled features, the backends it found at runtime) and prints it for
+//! `--version` or shows it on its about screen. The build hash, target and
+//! profile come from this crate's build script, so every binary built from
+//! the same checkout reports the same hash.
+
+use std::fmt;
+
+/// The git commit the workspace was built from, or `unknown` outside a checkout.
+pub const BUILD_HASH: &str = env!("ARCADE_BUILD_HASH");
+/// The target triple, e.g. `x86_64-unknown-linux-gnu`.
+pub const TARGET: &str = env!("ARCADE_BUILD_TARGET");
+/// `debug` or `release`.
+pub const PROFILE: &str = env!("ARCADE_BUILD_PROFILE");
+
+/// A [`BuildInfo`] for the crate this is expanded in.
+#[macro_export]
+macro_rules! build_info {
+    () => {
+        $crate::BuildInfo::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+    };
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    /// Workspace libraries built in, as (name, version).
+    pub crates: Vec<(&'static str, &'static str)>,
+    /// Wire and file formats spoken, as (name, version).
+    pub protocols: Vec<(&'static str, String)>,
+    /// Cargo features that are on.
+    pub features: Vec<&'static str>,
+    /// What was found at runtime, as (kind, description), e.g. the GPU API.
+    pub backends: Vec<(&'static str, String)>,
+}
+
+impl BuildInfo {
+    pub fn new(name: &'static str, version: &'static str) -> Self {
+        Self {
+            name,
+            version,
+            crates: Vec::new(),
+            protocols: Vec::new(),
+            features: Vec::new(),
+            backends: Vec::new(),
+        }
+    }
+
+    pub fn with_crate(mut self, name: &'static str, version: &'static str) -> Self {
+        self.crates.push((name, version));
+        self
+    }
+
+    pub fn with_protocol(mut self, name: &'static str, version: impl fmt::Display) -> Self {
+        self.protocols.push((name, version.to_string()));
+        self
+    }
+
+    /// Record `feature` if it is `enabled`; pass `cfg!(feature = "...")`.
+    pub fn with_feature(mut self, feature: &'static str, enabled: bool) -> Self {
+        if enabled {
+            self.features.push(feature);
+        }
+        self
+    }
+
+    pub fn with_backend(mut self, kind: &'static str, description: impl Into<String>) -> Self {
+        self.add_backend(kind, description);
+        self
+    }
+
+    /// For backends only known once the app is running. Replaces any earlier
+    /// entry of the same kind.
+    pub fn add_backend(&mut self, kind: &'static str, description: impl Into<String>) {
+        let description = description.into();
+        match self.backends.iter_mut().find(|(existing, _)| *existing == kind) {
+            Some(entry) => entry.1 = description,
+            None => self.backends.push((kind, description)),
+        }
+    }
+
+    /// One line for logs and window titles, e.g. `relay 0.1.0 (3f2a9c1d)`.
+    pub fn summary(&self) -> String {
+        format!("{} {} ({})", self.name, self.version, short_hash())
+    }
+}
+
+/// The first 8 characters of [`BUILD_HASH`].
+pub fn short_hash() -> &'static str {
+    BUILD_HASH.get(..8).unwrap_or(BUILD_HASH)
+}
+
+/// The full report, one fact per line. Empty sections other than features
+/// are left out.
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} {}", self.name, self.version)?;
+        write!(f, "build:     {BUILD_HASH} ({PROFILE}, {TARGET})")?;
+        let pairs = |pairs: Vec<String>| pairs.join(", ");
+        if !self.crates.is_empty() {
+            let crates = self.crates.iter().map(|(name, version)| format!("{name} {version}"));
+            write!(f, "\ncrates:    {}", pairs(crates.collect()))?;
+        }
+        if !self.protocols.is_empty() {
+            let protocols = self
+                .protocols
+                .iter()
+                .map(|(name, version)| format!("{name} {version}"));
+            write!(f, "\nprotocols: {}", pairs(protocols.collect()))?;
+        }
+        let features = if self.features.is_empty() {
+            "none".to_string()
+        } else {
+            self.features.join(", ")
+        };
+        write!(f, "\nfeatures:  {features}")?;
+        if !self.backends.is_empty() {
+            let backends = self.backends.iter().map(|(kind, found)| format!("{kind} {found}"));
+            write!(f, "\nbackends:  {}", pairs(backends.collect()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_lists_everything_recorded() {
+        // given a binary with a library, a protocol, a feature and a backend
+        let info = BuildInfo::new("relay", "0.1.0")
+            .with_crate("prototype-relay", "0.1.0")
+            .with_protocol("lockstep", 1)
+            .with_feature("compression", true)
+            .with_feature("order-check", false)
+            .with_backend("gpu", "Vulkan");
+
+        // when it is printed
+        let report = info.to_string();
+
+        // then each appears on its own line, and disabled features do not
+        assert!(report.starts_with("relay 0.1.0\nbuild:     "));
+        assert!(report.contains("\ncrates:    prototype-relay 0.1.0"));
+        assert!(report.contains("\nprotocols: lockstep 1"));
+        assert!(report.contains("\nfeatures:  compression"));
+        assert!(report.contains("\nbackends:  gpu Vulkan"));
+        assert!(!report.contains("order-check"));
+    }
+
+    #[test]
+    fn later_backend_of_a_kind_replaces_the_earlier() {
+        let mut info = BuildInfo::new("arcade", "0.1.0").with_backend("gpu", "detecting");
+        info.add_backend("gpu", "Metal");
+        assert_eq!(info.backends, vec![("gpu", "Metal".to_string())]);
+    }
+
+    #[test]
+    fn macro_names_the_calling_crate() {
+        let info = build_info!();
+        assert_eq!(info.name, "arcade-buildinfo");
+        assert!(info.summary().starts_with("arcade-buildinfo 0.1.0 ("));
+    }
+}
@@ -0,0 +1,25 @@
+//! Shared core for deterministic games.
+//!
+//! Lockstep games must produce bit-identical state on every machine. Bevy does
+//! not guarantee that a query yields entities in the same order on every run,
+//! so any system that folds over several entities (multi-ball, particles that
+//! feed back into gameplay) can drift between peers. This crate provides:
+//! - [`StableId`], a per-entity id assigned in simulation order
+//! - [`in_stable_order`] to iterate query results sorted by that id
+//! - [`fold_order_checked`], which with the `order-check` feature re-runs a
+//!   fold in reverse order and panics if the result depends on the order
+//! - [`snapshot`], for saving a match's core state to disk and resuming it
+
+pub mod ordering;
+pub mod snapshot;
+
+use arcade_buildinfo::BuildInfo;
+
+pub use ordering::{StableId, StableIdAllocator, fold_order_checked, in_stable_order};
+pub use snapshot::Snapshot;
+
+/// This crate's part of a game's version report.
+pub fn describe_build(info: BuildInfo) -> BuildInfo {
+    info.with_crate("arcade-core", env!("CARGO_PKG_VERSION"))
+        .with_feature("order-check", cfg!(feature = "order-check"))
+}
@@ -0,0 +1,102 @@
+//! Stable entity ordering for deterministic simulation.
+
+use std::fmt::Debug;
+
+use bevy::prelude::*;
+
+/// Simulation-assigned identity. Unlike `Entity`, it is allocated in the same
+/// order on every peer, so sorting by it gives every peer the same order.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StableId(pub u32);
+
+/// Hands out [`StableId`]s. Spawn simulation entities only from deterministic
+/// systems so every peer allocates the same ids.
+#[derive(Resource, Debug, Default)]
+pub struct StableIdAllocator {
+    next: u32,
+}
+
+impl StableIdAllocator {
+    pub fn allocate(&mut self) -> StableId {
+        let id = StableId(self.next);
+        self.next += 1;
+        id
+    }
+}
+
+/// Collect query results and sort them by [`StableId`].
+///
+/// ```ignore
+/// for (_, (transform, velocity)) in in_stable_order(balls.iter().map(|(id, t, v)| (id, (t, v)))) {
+///     // same order on every peer
+/// }
+/// ```
+pub fn in_stable_order<'a, T>(items: impl IntoIterator<Item = (&'a StableId, T)>) -> Vec<(StableId, T)> {
+    let mut sorted: Vec<(StableId, T)> = items.into_iter().map(|(id, item)| (*id, item)).collect();
+    sorted.sort_by_key(|(id, _)| *id);
+    sorted
+}
+
+/// Fold over `items` in order. With the `order-check` feature, also fold in
+/// reverse order and panic if the two results differ — the fold is then
+/// order-dependent and only deterministic if `items` is in a stable order.
+pub fn fold_order_checked<T, S, F>(items: &[T], init: S, fold: F) -> S
+where
+    S: Clone + PartialEq + Debug,
+    F: Fn(S, &T) -> S,
+{
+    #[cfg(feature = "order-check")]
+    {
+        let forward = items.iter().fold(init.clone(), &fold);
+        let reverse = items.iter().rev().fold(init, &fold);
+        assert_eq!(
+            forward, reverse,
+            "order-dependent state change: folding {} items forward and in reverse disagree",
+            items.len()
+        );
+        forward
+    }
+    #[cfg(not(feature = "order-check"))]
+    {
+        items.iter().fold(init, fold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocator_counts_up() {
+        let mut allocator = StableIdAllocator::default();
+        assert_eq!(allocator.allocate(), StableId(0));
+        assert_eq!(allocator.allocate(), StableId(1));
+    }
+
+    #[test]
+    fn in_stable_order_sorts_by_id() {
+        // given items in arbitrary order
+        let ids = [StableId(2), StableId(0), StableId(1)];
+        let items = ids.iter().zip(["c", "a", "b"]);
+
+        // when sorted
+        let sorted: Vec<&str> = in_stable_order(items).into_iter().map(|(_, item)| item).collect();
+
+        // then they follow the ids
+        assert_eq!(sorted, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn commutative_fold_passes() {
+        let total = fold_order_checked(&[1, 2, 3], 0, |sum, x| sum + x);
+        assert_eq!(total, 6);
+    }
+
+    #[cfg(feature = "order-check")]
+    #[test]
+    #[should_panic(expected = "order-dependent")]
+    fn order_dependent_fold_panics() {
+        // "last writer wins" depends on order
+        fold_order_checked(&[1, 2, 3], 0, |_, x| *x);
+    }
+}
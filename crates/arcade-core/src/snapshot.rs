@@ -0,0 +1,148 @@
+//! Saving and restoring a game's core state.
+//!
+//! A game describes everything needed to pick a match back up as one
+//! serializable struct and implements [`Snapshot`] for it. [`save`] writes it
+//! to disk and [`load`] reads it back. A snapshot from another version of the
+//! game's state is ignored rather than misread: the game just starts fresh.
+//!
+//! Writes go to a temporary file that is then renamed over the old one, so
+//! quitting mid-write never leaves a half-written save behind.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// A game's complete core state at one moment.
+pub trait Snapshot: Serialize + DeserializeOwned {
+    /// Names the save file, e.g. `pong`.
+    const GAME: &'static str;
+    /// Bump whenever the struct's layout changes; older saves are then ignored.
+    const VERSION: u32;
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    game: String,
+    version: u32,
+    state: T,
+}
+
+/// Where `T`'s save lives under `dir`.
+pub fn path_in<T: Snapshot>(dir: &Path) -> PathBuf {
+    dir.join(format!("{}.snapshot", T::GAME))
+}
+
+/// The per-user directory saves go in, next to the arcade's config.
+pub fn default_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("seans-arcade")
+        .join("saves")
+}
+
+pub fn save<T: Snapshot>(dir: &Path, state: &T) -> io::Result<()> {
+    let envelope = Envelope {
+        game: T::GAME.to_string(),
+        version: T::VERSION,
+        state,
+    };
+    let bytes = postcard::to_allocvec(&envelope).map_err(io::Error::other)?;
+    std::fs::create_dir_all(dir)?;
+    let path = path_in::<T>(dir);
+    let partial = path.with_extension("snapshot.partial");
+    std::fs::write(&partial, bytes)?;
+    std::fs::rename(&partial, &path)
+}
+
+/// The saved state, if there is one this version of the game can read.
+pub fn load<T: Snapshot>(dir: &Path) -> Option<T> {
+    let bytes = std::fs::read(path_in::<T>(dir)).ok()?;
+    let envelope: Envelope<T> = postcard::from_bytes(&bytes).ok()?;
+    (envelope.game == T::GAME && envelope.version == T::VERSION).then_some(envelope.state)
+}
+
+/// Whether a save exists that [`load`] would return.
+pub fn exists<T: Snapshot>(dir: &Path) -> bool {
+    load::<T>(dir).is_some()
+}
+
+/// Delete the save, e.g. once the match it holds has been finished or
+/// abandoned. Deleting a save that does not exist is not an error.
+pub fn discard<T: Snapshot>(dir: &Path) -> io::Result<()> {
+    match std::fs::remove_file(path_in::<T>(dir)) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Match {
+        score: [u32; 2],
+        ball: (f32, f32),
+    }
+
+    impl Snapshot for Match {
+        const GAME: &'static str = "test-match";
+        const VERSION: u32 = 1;
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct NewerMatch {
+        score: [u32; 2],
+        ball: (f32, f32),
+    }
+
+    impl Snapshot for NewerMatch {
+        const GAME: &'static str = "test-match";
+        const VERSION: u32 = 2;
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("snapshot_test_{name}_{}", std::process::id()))
+    }
+
+    #[test]
+    fn saved_state_loads_back() {
+        // given a saved match
+        let dir = temp_dir("round_trip");
+        let state = Match { score: [3, 5], ball: (1.5, -2.0) };
+        save(&dir, &state).unwrap();
+
+        // when it is loaded
+        let loaded: Option<Match> = load(&dir);
+
+        // then it is the same match
+        assert_eq!(loaded, Some(state));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn other_versions_are_ignored() {
+        // given a save from an older layout
+        let dir = temp_dir("version");
+        save(&dir, &Match { score: [1, 0], ball: (0.0, 0.0) }).unwrap();
+
+        // when the newer layout looks for it
+        let loaded: Option<NewerMatch> = load(&dir);
+
+        // then there is nothing to resume
+        assert!(loaded.is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn discarded_save_is_gone() {
+        let dir = temp_dir("discard");
+        save(&dir, &Match { score: [0, 0], ball: (0.0, 0.0) }).unwrap();
+        discard::<Match>(&dir).unwrap();
+        assert!(!exists::<Match>(&dir));
+        discard::<Match>(&dir).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,354 @@
+//! Reading each player's devices into the actions a game acts on.
+//!
+//! [`InputAssignments`] says which devices each player holds and how they
+//! are bound. Every frame [`read_actions`] reads those devices and leaves
+//! the result in [`ActionState`], so game systems ask "is player 1
+//! serving?" instead of which key or pad that is this week.
+
+use bevy::prelude::*;
+
+use crate::bindings::{ActionMap, Binding};
+
+/// What a game reads from a player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Up is positive, in [-1, 1]: the Up and Down bindings plus the left
+    /// stick.
+    MoveY,
+    Serve,
+    Pause,
+}
+
+/// Somewhere input comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Device {
+    Keyboard,
+    /// The nth connected Bevy gamepad, counting from 0 in `Entity` order,
+    /// as net_pong numbers them.
+    Gamepad(usize),
+    /// XInput's controller slot, as [`XInputPads`] has it.
+    XInput(usize),
+    /// SDL's controller slot, as [`SdlPads`] has it.
+    Sdl(usize),
+}
+
+/// Where the players' pads are read from, chosen at runtime. Games offer
+/// the [`available`](PadBackend::available) ones and assign players the
+/// chosen one's [`device`](PadBackend::device)s.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PadBackend {
+    /// Bevy's gamepads, from gilrs.
+    #[default]
+    Bevy,
+    /// XInput, with the `xinput` feature's `XInputPlugin`.
+    XInput,
+    /// SDL2, with the `sdl` feature's `SdlPlugin`.
+    Sdl,
+}
+
+impl PadBackend {
+    /// The backends this build can read, Bevy's first.
+    pub fn available() -> Vec<PadBackend> {
+        let mut available = vec![PadBackend::Bevy];
+        if cfg!(feature = "xinput") {
+            available.push(PadBackend::XInput);
+        }
+        if cfg!(feature = "sdl") {
+            available.push(PadBackend::Sdl);
+        }
+        available
+    }
+
+    /// The next of [`available`](PadBackend::available), round to the first.
+    pub fn next(self) -> PadBackend {
+        let available = Self::available();
+        let at = available.iter().position(|&backend| backend == self);
+        available[at.map_or(0, |at| (at + 1) % available.len())]
+    }
+
+    /// This backend's `pad`th pad, counting from 0.
+    pub fn device(self, pad: usize) -> Device {
+        match self {
+            PadBackend::Bevy => Device::Gamepad(pad),
+            PadBackend::XInput => Device::XInput(pad),
+            PadBackend::Sdl => Device::Sdl(pad),
+        }
+    }
+}
+
+/// One player's devices and bindings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerAssignment {
+    pub devices: Vec<Device>,
+    pub map: ActionMap,
+}
+
+impl Default for PlayerAssignment {
+    /// The keyboard and the first gamepad, as one player.
+    fn default() -> Self {
+        Self {
+            devices: vec![Device::Keyboard, Device::Gamepad(0)],
+            map: ActionMap::default(),
+        }
+    }
+}
+
+/// Who plays with what, by player number from 0.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct InputAssignments(pub Vec<PlayerAssignment>);
+
+impl Default for InputAssignments {
+    fn default() -> Self {
+        Self(vec![PlayerAssignment::default()])
+    }
+}
+
+/// XInput has four controller slots.
+pub const XINPUT_SLOTS: usize = 4;
+
+/// What the last XInput poll saw, written by whatever polls it. Bevy's
+/// gamepads come from gilrs instead, which mixes up two identical pads;
+/// see `docs/research/gilrs-dual-gamepad-bug.md`.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct XInputPads(pub [PadState; XINPUT_SLOTS]);
+
+/// SDL keeps as many controller slots as XInput here; four is plenty.
+pub const SDL_SLOTS: usize = 4;
+
+/// What the last SDL poll saw, written by the `sdl` feature's `SdlPlugin`.
+/// SDL numbers identical pads apart on every platform.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct SdlPads(pub [PadState; SDL_SLOTS]);
+
+/// One XInput or SDL controller, in Bevy's button names. Sticks are in
+/// [-1, 1] with up positive, triggers in [0, 1].
+#[derive(Debug, Clone, Default)]
+pub struct PadState {
+    pub connected: bool,
+    pub left_stick: Vec2,
+    pub right_stick: Vec2,
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+    pub pressed: Vec<GamepadButton>,
+}
+
+impl PadState {
+    pub fn pressed(&self, button: GamepadButton) -> bool {
+        self.connected && self.pressed.contains(&button)
+    }
+}
+
+/// Every player's actions this frame.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct ActionState(Vec<PlayerActions>);
+
+impl ActionState {
+    /// Player `player`'s actions, all at rest if nobody is assigned there.
+    pub fn player(&self, player: usize) -> PlayerActions {
+        self.0.get(player).copied().unwrap_or_default()
+    }
+}
+
+/// One player's actions this frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PlayerActions {
+    move_y: f32,
+    serve: bool,
+    pause: bool,
+    serve_just_pressed: bool,
+    pause_just_pressed: bool,
+}
+
+impl PlayerActions {
+    /// How far the action is pushed, in [-1, 1]; 1 or 0 for a button.
+    pub fn value(&self, action: Action) -> f32 {
+        match action {
+            Action::MoveY => self.move_y,
+            _ => f32::from(u8::from(self.pressed(action))),
+        }
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        match action {
+            Action::MoveY => self.move_y != 0.0,
+            Action::Serve => self.serve,
+            Action::Pause => self.pause,
+        }
+    }
+
+    /// Pressed this frame and not the last. Never for MoveY.
+    pub fn just_pressed(&self, action: Action) -> bool {
+        match action {
+            Action::MoveY => false,
+            Action::Serve => self.serve_just_pressed,
+            Action::Pause => self.pause_just_pressed,
+        }
+    }
+}
+
+/// What one device says about one player's bindings.
+#[derive(Default)]
+struct Reading {
+    up: bool,
+    down: bool,
+    serve: bool,
+    pause: bool,
+    stick_y: f32,
+}
+
+impl Reading {
+    fn from_buttons(map: &ActionMap, pressed: impl Fn(&Binding) -> bool, stick_y: f32) -> Self {
+        Self {
+            up: pressed(&map.up),
+            down: pressed(&map.down),
+            serve: pressed(&map.serve),
+            pause: pressed(&map.pause),
+            stick_y,
+        }
+    }
+
+    fn from_pad(map: &ActionMap, pad: &PadState) -> Option<Self> {
+        pad.connected.then(|| {
+            Self::from_buttons(map, |binding| pad.pressed(binding.button), pad.left_stick.y)
+        })
+    }
+}
+
+/// Read every assigned device into [`ActionState`]. Runs in `PreUpdate`,
+/// in [`ActionSystems`](crate::ActionSystems).
+pub fn read_actions(
+    assignments: Res<InputAssignments>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<(Entity, &Gamepad)>,
+    xinput: Res<XInputPads>,
+    sdl: Res<SdlPads>,
+    mut state: ResMut<ActionState>,
+) {
+    let mut connected: Vec<_> = gamepads.iter().collect();
+    connected.sort_by_key(|(entity, _)| *entity);
+    let read = |device: Device, map: &ActionMap| match device {
+        Device::Keyboard => Some(Reading::from_buttons(
+            map,
+            |binding| keyboard.any_pressed(binding.keys.iter().copied()),
+            0.0,
+        )),
+        Device::Gamepad(n) => connected.get(n).map(|(_, gamepad)| {
+            Reading::from_buttons(
+                map,
+                |binding| gamepad.pressed(binding.button),
+                gamepad.left_stick().y,
+            )
+        }),
+        Device::XInput(slot) => xinput
+            .0
+            .get(slot)
+            .and_then(|pad| Reading::from_pad(map, pad)),
+        Device::Sdl(slot) => sdl.0.get(slot).and_then(|pad| Reading::from_pad(map, pad)),
+    };
+
+    let previous = std::mem::take(&mut state.0);
+    for (player, assignment) in assignments.0.iter().enumerate() {
+        let readings: Vec<Reading> = assignment
+            .devices
+            .iter()
+            .filter_map(|&device| read(device, &assignment.map))
+            .collect();
+        let up = readings.iter().any(|reading| reading.up);
+        let down = readings.iter().any(|reading| reading.down);
+        let stick: f32 = readings.iter().map(|reading| reading.stick_y).sum();
+        let serve = readings.iter().any(|reading| reading.serve);
+        let pause = readings.iter().any(|reading| reading.pause);
+        let before = previous.get(player).copied().unwrap_or_default();
+        state.0.push(PlayerActions {
+            move_y: (f32::from(i8::from(up) - i8::from(down)) + stick).clamp(-1.0, 1.0),
+            serve,
+            pause,
+            serve_just_pressed: serve && !before.serve,
+            pause_just_pressed: pause && !before.pause,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ArcadeInputPlugin;
+
+    fn app(assignments: InputAssignments) -> App {
+        let mut app = App::new();
+        app.init_resource::<ButtonInput<KeyCode>>()
+            .insert_resource(assignments)
+            .add_plugins(ArcadeInputPlugin);
+        app
+    }
+
+    #[test]
+    fn players_read_only_their_own_devices() {
+        // given one player on the keyboard and another on XInput pad 1
+        let mut app = app(InputAssignments(vec![
+            PlayerAssignment {
+                devices: vec![Device::Keyboard],
+                map: ActionMap::default(),
+            },
+            PlayerAssignment {
+                devices: vec![Device::XInput(1)],
+                map: ActionMap::default(),
+            },
+        ]));
+
+        // when W is held and pad 1 presses South with its stick half down
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::KeyW);
+        app.world_mut().resource_mut::<XInputPads>().0[1] = PadState {
+            connected: true,
+            left_stick: Vec2::new(0.0, -0.5),
+            pressed: vec![GamepadButton::South],
+            ..default()
+        };
+        app.update();
+
+        // then each moves and serves by their own device alone
+        let actions = app.world().resource::<ActionState>();
+        assert_eq!(actions.player(0).value(Action::MoveY), 1.0);
+        assert!(!actions.player(0).pressed(Action::Serve));
+        assert_eq!(actions.player(1).value(Action::MoveY), -0.5);
+        assert!(actions.player(1).just_pressed(Action::Serve));
+    }
+
+    #[test]
+    fn next_visits_every_available_backend_once() {
+        // given the backends this build can read
+        let available = PadBackend::available();
+
+        // when they are stepped through from Bevy's
+        let mut visited = vec![PadBackend::Bevy];
+        while visited.len() <= available.len() {
+            visited.push(visited.last().unwrap().next());
+        }
+
+        // then each comes up once before Bevy's again
+        assert_eq!(visited[..available.len()], available[..]);
+        assert_eq!(visited[available.len()], PadBackend::Bevy);
+    }
+
+    #[test]
+    fn a_held_button_is_just_pressed_only_once() {
+        // given a player holding Serve for two frames
+        let mut app = app(InputAssignments::default());
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::Space);
+        app.update();
+        let first = app.world().resource::<ActionState>().player(0);
+
+        // when the next frame reads it again
+        app.update();
+        let second = app.world().resource::<ActionState>().player(0);
+
+        // then only the first frame saw it go down
+        assert!(first.just_pressed(Action::Serve));
+        assert!(second.pressed(Action::Serve));
+        assert!(!second.just_pressed(Action::Serve));
+    }
+}
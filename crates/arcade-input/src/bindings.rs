@@ -0,0 +1,147 @@
+//! Which keys and buttons a player has bound to each control.
+//!
+//! An [`ActionMap`] is plain data, saved as TOML, so a game's controls page
+//! can rebind one [`Control`] at a time and write the result back.
+
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// What a player can bind keys and a button to. Up and Down together make
+/// [`Action::MoveY`](crate::Action::MoveY).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Control {
+    Up,
+    Down,
+    /// Starts the next point or game.
+    Serve,
+    /// Opens the pause menu.
+    Pause,
+}
+
+impl Control {
+    pub const ALL: [Control; 4] = [Control::Up, Control::Down, Control::Serve, Control::Pause];
+}
+
+/// The keys and gamepad button for one control.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    pub keys: Vec<KeyCode>,
+    pub button: GamepadButton,
+}
+
+impl Binding {
+    pub fn new(keys: &[KeyCode], button: GamepadButton) -> Self {
+        Self {
+            keys: keys.to_vec(),
+            button,
+        }
+    }
+
+    /// "W / ArrowUp / pad DPadUp".
+    pub fn describe(&self) -> String {
+        let keys = self.keys.iter().map(|key| {
+            let name = format!("{key:?}");
+            match name
+                .strip_prefix("Key")
+                .or_else(|| name.strip_prefix("Digit"))
+            {
+                Some(short) => short.to_string(),
+                None => name,
+            }
+        });
+        keys.chain([format!("pad {:?}", self.button)])
+            .collect::<Vec<_>>()
+            .join(" / ")
+    }
+}
+
+/// One player's bindings for every control. The left stick always moves
+/// them too.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionMap {
+    pub up: Binding,
+    pub down: Binding,
+    pub serve: Binding,
+    /// Saved maps from before Pause could be bound have none.
+    #[serde(default = "default_pause")]
+    pub pause: Binding,
+}
+
+fn default_pause() -> Binding {
+    Binding::new(&[KeyCode::Escape], GamepadButton::Start)
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        Self {
+            up: Binding::new(&[KeyCode::KeyW, KeyCode::ArrowUp], GamepadButton::DPadUp),
+            down: Binding::new(
+                &[KeyCode::KeyS, KeyCode::ArrowDown],
+                GamepadButton::DPadDown,
+            ),
+            serve: Binding::new(&[KeyCode::Enter, KeyCode::Space], GamepadButton::South),
+            pause: default_pause(),
+        }
+    }
+}
+
+impl ActionMap {
+    /// The map saved at `path`, or the defaults if there is none that can
+    /// be read.
+    pub fn load(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+                warn!("ignoring unreadable {}: {e}", path.display());
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) {
+        let contents = toml::to_string_pretty(self).expect("failed to serialize controls");
+        if let Err(e) = std::fs::write(path, contents) {
+            warn!("failed to write {}: {e}", path.display());
+        }
+    }
+
+    pub fn get(&self, control: Control) -> &Binding {
+        match control {
+            Control::Up => &self.up,
+            Control::Down => &self.down,
+            Control::Serve => &self.serve,
+            Control::Pause => &self.pause,
+        }
+    }
+
+    pub fn get_mut(&mut self, control: Control) -> &mut Binding {
+        match control {
+            Control::Up => &mut self.up,
+            Control::Down => &mut self.down,
+            Control::Serve => &mut self.serve,
+            Control::Pause => &mut self.pause,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_map_saved_before_pause_existed_still_loads() {
+        // given the controls net_pong saved with only up, down and serve
+        let mut saved = toml::Table::try_from(ActionMap::default()).unwrap();
+        saved.remove("pause");
+        let contents = toml::to_string(&saved).unwrap();
+
+        // when it is read back
+        let map: ActionMap = toml::from_str(&contents).unwrap();
+
+        // then Pause has its default binding
+        assert_eq!(map.pause, default_pause());
+        assert_eq!(map.serve, ActionMap::default().serve);
+    }
+}
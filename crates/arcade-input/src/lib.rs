@@ -0,0 +1,64 @@
+//! Named actions from whatever each player holds.
+//!
+//! Games read [`Action`]s (MoveY, Serve, Pause) per player from
+//! [`ActionState`] instead of asking the keyboard and the first gamepad
+//! directly. Which devices a player has, and how they are bound, is an
+//! [`InputAssignments`] entry: the keyboard, a Bevy gamepad, or an XInput
+//! slot, each read through the player's [`ActionMap`].
+//! - [`bindings`], the rebindable [`Control`]s and their TOML form
+//! - [`actions`], devices, assignments and the per-frame [`read_actions`]
+//! - [`rumble`], the [`Rumble`] message that shakes a player's pads
+//!
+//! Players assigned an XInput slot read it from [`XInputPads`]. With the
+//! `xinput` feature, `XInputPlugin` fills that from `win_xinput`; otherwise
+//! whatever polls XInput writes what it saw there. The `sdl` feature's
+//! `SdlPlugin` does the same for SDL2's controllers into [`SdlPads`], which
+//! tells identical pads apart off Windows too. [`PadBackend`] picks between
+//! them, and Bevy's own gamepads, at runtime.
+
+pub mod actions;
+pub mod bindings;
+pub mod rumble;
+#[cfg(feature = "sdl")]
+pub mod sdl;
+#[cfg(feature = "xinput")]
+pub mod xinput;
+
+use bevy::input::InputSystems;
+use bevy::input::gamepad::GamepadRumbleRequest;
+use bevy::prelude::*;
+
+pub use actions::{
+    Action, ActionState, Device, InputAssignments, PadBackend, PadState, PlayerActions,
+    PlayerAssignment, SDL_SLOTS, SdlPads, XINPUT_SLOTS, XInputPads, read_actions,
+};
+pub use bindings::{ActionMap, Binding, Control};
+pub use rumble::Rumble;
+#[cfg(feature = "sdl")]
+pub use sdl::SdlPlugin;
+#[cfg(feature = "xinput")]
+pub use xinput::{XInputConnection, XInputPlugin};
+
+/// Fills [`ActionState`] every frame, in `PreUpdate`, and passes
+/// [`Rumble`]s on to Bevy gamepads in `PostUpdate`.
+pub struct ArcadeInputPlugin;
+
+impl Plugin for ArcadeInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputAssignments>()
+            .init_resource::<XInputPads>()
+            .init_resource::<SdlPads>()
+            .init_resource::<ActionState>()
+            .add_message::<Rumble>()
+            .add_message::<GamepadRumbleRequest>()
+            .add_systems(
+                PreUpdate,
+                read_actions.in_set(ActionSystems).after(InputSystems),
+            )
+            .add_systems(PostUpdate, rumble::rumble_gamepads);
+    }
+}
+
+/// [`read_actions`], for systems that read [`ActionState`] in `PreUpdate`.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ActionSystems;
@@ -0,0 +1,100 @@
+//! Shaking a player's gamepads.
+//!
+//! A game writes a [`Rumble`] for a player, and every pad assigned to them
+//! in [`InputAssignments`] shakes: Bevy gamepads through Bevy's own rumble,
+//! XInput slots through `XInputSetState` when the `xinput` feature polls
+//! them, and SDL slots through SDL with the `sdl` feature. The keyboard just
+//! ignores it.
+
+use std::time::Duration;
+
+use bevy::input::gamepad::{GamepadRumbleIntensity, GamepadRumbleRequest};
+use bevy::prelude::*;
+
+use crate::actions::{Device, InputAssignments};
+
+/// Shake player `player`'s pads, each motor from 0 (still) to 1, for
+/// `duration`. Rumbles that overlap add up on Bevy gamepads; on an XInput
+/// or SDL pad the latest one wins.
+#[derive(Message, Debug, Clone, Copy, PartialEq)]
+pub struct Rumble {
+    pub player: usize,
+    /// The heavy, low-frequency motor: thuds.
+    pub strong: f32,
+    /// The light, high-frequency motor: buzzes.
+    pub weak: f32,
+    pub duration: Duration,
+}
+
+/// Pass each [`Rumble`] on to the player's Bevy gamepads.
+pub fn rumble_gamepads(
+    mut rumbles: MessageReader<Rumble>,
+    assignments: Res<InputAssignments>,
+    gamepads: Query<Entity, With<Gamepad>>,
+    mut requests: MessageWriter<GamepadRumbleRequest>,
+) {
+    let mut connected: Vec<Entity> = gamepads.iter().collect();
+    connected.sort();
+    for rumble in rumbles.read() {
+        let Some(assignment) = assignments.0.get(rumble.player) else {
+            continue;
+        };
+        for device in &assignment.devices {
+            if let Device::Gamepad(n) = *device
+                && let Some(&gamepad) = connected.get(n)
+            {
+                requests.write(GamepadRumbleRequest::Add {
+                    gamepad,
+                    intensity: GamepadRumbleIntensity {
+                        strong_motor: rumble.strong,
+                        weak_motor: rumble.weak,
+                    },
+                    duration: rumble.duration,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ArcadeInputPlugin, PlayerAssignment};
+
+    #[test]
+    fn a_rumble_reaches_only_that_players_gamepad() {
+        // given two players on gamepads 0 and 1
+        let mut app = App::new();
+        app.init_resource::<ButtonInput<KeyCode>>()
+            .insert_resource(InputAssignments(
+                (0..2)
+                    .map(|n| PlayerAssignment {
+                        devices: vec![Device::Keyboard, Device::Gamepad(n)],
+                        ..default()
+                    })
+                    .collect(),
+            ))
+            .add_plugins(ArcadeInputPlugin);
+        let mut pads: Vec<Entity> = (0..2)
+            .map(|_| app.world_mut().spawn(Gamepad::default()).id())
+            .collect();
+        pads.sort();
+
+        // when player 1 is told to rumble
+        app.world_mut().write_message(Rumble {
+            player: 1,
+            strong: 0.8,
+            weak: 0.2,
+            duration: Duration::from_millis(100),
+        });
+        app.update();
+
+        // then only the second pad is asked to
+        let requests = app.world().resource::<Messages<GamepadRumbleRequest>>();
+        let asked: Vec<Entity> = requests
+            .iter_current_update_messages()
+            .map(GamepadRumbleRequest::gamepad)
+            .collect();
+        assert_eq!(asked, vec![pads[1]]);
+    }
+}
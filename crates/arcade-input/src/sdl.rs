@@ -0,0 +1,188 @@
+//! Filling [`SdlPads`] from SDL2's game controllers, and rumbling them.
+//! Needs the `sdl` feature, and the SDL2 library where the game runs.
+//!
+//! SDL gives each controller an instance id that lasts while it is plugged
+//! in; here each one also takes the first free slot when it arrives and
+//! keeps it until it leaves, so two identical pads stay apart.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::{EventPump, GameControllerSubsystem, Sdl};
+
+use crate::actions::{Device, InputAssignments, PadState, SDL_SLOTS, SdlPads};
+use crate::rumble::Rumble;
+
+/// Polls SDL2 every frame into [`SdlPads`], ahead of
+/// [`ActionSystems`](crate::ActionSystems). [`Rumble`]s for players on an
+/// SDL slot shake that pad. If SDL will not start, says so once and leaves
+/// the pads disconnected.
+pub struct SdlPlugin;
+
+impl Plugin for SdlPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SdlPads>()
+            .add_message::<Rumble>()
+            .add_systems(
+                PreUpdate,
+                poll_sdl
+                    .before(crate::ActionSystems)
+                    .after(bevy::input::InputSystems),
+            )
+            .add_systems(PostUpdate, rumble_sdl_pads);
+        match SdlSource::start() {
+            Ok(source) => {
+                app.insert_non_send_resource(source);
+            }
+            Err(e) => warn!("SDL2 is unavailable; SDL pads stay disconnected: {e}"),
+        }
+    }
+}
+
+/// SDL, kept on the main thread as it requires.
+struct SdlSource {
+    _sdl: Sdl,
+    subsystem: GameControllerSubsystem,
+    events: EventPump,
+    controllers: [Option<GameController>; SDL_SLOTS],
+}
+
+impl SdlSource {
+    fn start() -> Result<Self, String> {
+        let sdl = sdl2::init()?;
+        let subsystem = sdl.game_controller()?;
+        let events = sdl.event_pump()?;
+        Ok(Self {
+            _sdl: sdl,
+            subsystem,
+            events,
+            controllers: Default::default(),
+        })
+    }
+
+    /// Open the controller SDL knows as joystick `index`, into the first
+    /// free slot.
+    fn open(&mut self, index: u32) {
+        let Some(slot) = self.controllers.iter().position(Option::is_none) else {
+            warn!("SDL pad ignored: all {SDL_SLOTS} slots are taken");
+            return;
+        };
+        match self.subsystem.open(index) {
+            Ok(controller) => {
+                info!("SDL pad {} connected: {}", slot + 1, controller.name());
+                self.controllers[slot] = Some(controller);
+            }
+            Err(e) => warn!("failed to open SDL pad: {e}"),
+        }
+    }
+
+    fn close(&mut self, instance_id: u32) {
+        for (slot, controller) in self.controllers.iter_mut().enumerate() {
+            if controller
+                .as_ref()
+                .is_some_and(|controller| controller.instance_id() == instance_id)
+            {
+                info!("SDL pad {} disconnected", slot + 1);
+                *controller = None;
+            }
+        }
+    }
+}
+
+fn poll_sdl(source: Option<NonSendMut<SdlSource>>, mut pads: ResMut<SdlPads>) {
+    let Some(mut source) = source else {
+        return;
+    };
+    // Pads already plugged in arrive as events too, on the first poll.
+    let events: Vec<Event> = source.events.poll_iter().collect();
+    for event in events {
+        match event {
+            Event::ControllerDeviceAdded { which, .. } => source.open(which),
+            Event::ControllerDeviceRemoved { which, .. } => source.close(which),
+            _ => {}
+        }
+    }
+    for (pad, controller) in pads.0.iter_mut().zip(&source.controllers) {
+        *pad = controller
+            .as_ref()
+            .map_or_else(PadState::default, pad_state);
+    }
+}
+
+/// A controller as [`read_actions`](crate::read_actions) reads it.
+fn pad_state(controller: &GameController) -> PadState {
+    let axis = |axis: Axis| {
+        let value = controller.axis(axis);
+        if value >= 0 {
+            f32::from(value) / 32767.0
+        } else {
+            f32::from(value) / 32768.0
+        }
+    };
+    PadState {
+        connected: true,
+        // SDL's sticks point down for positive Y.
+        left_stick: Vec2::new(axis(Axis::LeftX), -axis(Axis::LeftY)),
+        right_stick: Vec2::new(axis(Axis::RightX), -axis(Axis::RightY)),
+        left_trigger: axis(Axis::TriggerLeft).max(0.0),
+        right_trigger: axis(Axis::TriggerRight).max(0.0),
+        pressed: BUTTONS
+            .iter()
+            .filter(|(button, _)| controller.button(*button))
+            .map(|&(_, name)| name)
+            .collect(),
+    }
+}
+
+/// SDL's buttons by their Bevy names. SDL, like Bevy, names the face
+/// buttons by an Xbox pad's layout.
+const BUTTONS: [(Button, GamepadButton); 15] = [
+    (Button::A, GamepadButton::South),
+    (Button::B, GamepadButton::East),
+    (Button::X, GamepadButton::West),
+    (Button::Y, GamepadButton::North),
+    (Button::Back, GamepadButton::Select),
+    (Button::Guide, GamepadButton::Mode),
+    (Button::Start, GamepadButton::Start),
+    (Button::LeftStick, GamepadButton::LeftThumb),
+    (Button::RightStick, GamepadButton::RightThumb),
+    (Button::LeftShoulder, GamepadButton::LeftTrigger),
+    (Button::RightShoulder, GamepadButton::RightTrigger),
+    (Button::DPadUp, GamepadButton::DPadUp),
+    (Button::DPadDown, GamepadButton::DPadDown),
+    (Button::DPadLeft, GamepadButton::DPadLeft),
+    (Button::DPadRight, GamepadButton::DPadRight),
+];
+
+/// Start the motors of SDL pads whose players were sent a [`Rumble`]. SDL
+/// stops them itself when the time is up.
+fn rumble_sdl_pads(
+    mut rumbles: MessageReader<Rumble>,
+    assignments: Res<InputAssignments>,
+    source: Option<NonSendMut<SdlSource>>,
+) {
+    let Some(mut source) = source else {
+        rumbles.clear();
+        return;
+    };
+    let speed = |fraction: f32| (fraction.clamp(0.0, 1.0) * f32::from(u16::MAX)) as u16;
+    for rumble in rumbles.read() {
+        let Some(assignment) = assignments.0.get(rumble.player) else {
+            continue;
+        };
+        for device in &assignment.devices {
+            if let Device::Sdl(slot) = *device
+                && let Some(Some(controller)) = source.controllers.get_mut(slot)
+            {
+                let millis = rumble.duration.min(Duration::from_secs(60)).as_millis() as u32;
+                if let Err(e) =
+                    controller.set_rumble(speed(rumble.strong), speed(rumble.weak), millis)
+                {
+                    debug!("SDL pad {} cannot rumble: {e}", slot + 1);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Filling [`XInputPads`] from XInput itself, for games that want pads to
+//! keep their slots, and rumbling them. Needs the `xinput` feature; off
+//! Windows the pads just stay disconnected.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use win_xinput::{Button, ConnectionEvent, SLOTS, XInput};
+
+use crate::actions::{Device, InputAssignments, PadState, XInputPads};
+use crate::rumble::Rumble;
+
+/// Polls XInput every frame into [`XInputPads`], ahead of
+/// [`ActionSystems`](crate::ActionSystems), and says when pads come and go
+/// with [`XInputConnection`]. [`Rumble`]s for players on an XInput slot
+/// shake that pad.
+pub struct XInputPlugin;
+
+impl Plugin for XInputPlugin {
+    fn build(&self, app: &mut App) {
+        let xinput = XInput::load();
+        if !xinput.available() {
+            warn!("XInput is unavailable; XInput pads stay disconnected");
+        }
+        app.insert_resource(XInputSource {
+            xinput,
+            rumbling_until: [None; SLOTS],
+        })
+        .init_resource::<XInputPads>()
+        .add_message::<XInputConnection>()
+        .add_message::<Rumble>()
+        .add_systems(
+            PreUpdate,
+            poll_xinput
+                .before(crate::ActionSystems)
+                .after(bevy::input::InputSystems),
+        )
+        .add_systems(PostUpdate, rumble_xinput_pads);
+    }
+}
+
+/// A pad arrived in, or left, an XInput slot.
+#[derive(Message, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XInputConnection(pub ConnectionEvent);
+
+#[derive(Resource)]
+struct XInputSource {
+    xinput: XInput,
+    /// When each slot's motors are due to stop, by `Time::elapsed`. XInput
+    /// runs them until told otherwise.
+    rumbling_until: [Option<Duration>; SLOTS],
+}
+
+fn poll_xinput(
+    mut source: ResMut<XInputSource>,
+    mut pads: ResMut<XInputPads>,
+    mut connections: MessageWriter<XInputConnection>,
+) {
+    for event in source.xinput.poll() {
+        match event {
+            ConnectionEvent::Connected(slot) => info!("XInput pad {} connected", slot + 1),
+            ConnectionEvent::Disconnected(slot) => info!("XInput pad {} disconnected", slot + 1),
+        }
+        connections.write(XInputConnection(event));
+    }
+    for (slot, pad) in pads.0.iter_mut().enumerate() {
+        *pad = match source.xinput.pad(slot) {
+            Some(reading) => PadState {
+                connected: true,
+                left_stick: Vec2::from(reading.left_stick),
+                right_stick: Vec2::from(reading.right_stick),
+                left_trigger: reading.left_trigger,
+                right_trigger: reading.right_trigger,
+                pressed: reading.pressed_buttons().map(gamepad_button).collect(),
+            },
+            None => PadState::default(),
+        };
+    }
+}
+
+/// Bevy's name for an XInput button.
+pub fn gamepad_button(button: Button) -> GamepadButton {
+    match button {
+        Button::DPadUp => GamepadButton::DPadUp,
+        Button::DPadDown => GamepadButton::DPadDown,
+        Button::DPadLeft => GamepadButton::DPadLeft,
+        Button::DPadRight => GamepadButton::DPadRight,
+        Button::Start => GamepadButton::Start,
+        Button::Back => GamepadButton::Select,
+        Button::LeftThumb => GamepadButton::LeftThumb,
+        Button::RightThumb => GamepadButton::RightThumb,
+        Button::LeftShoulder => GamepadButton::LeftTrigger,
+        Button::RightShoulder => GamepadButton::RightTrigger,
+        Button::A => GamepadButton::South,
+        Button::B => GamepadButton::East,
+        Button::X => GamepadButton::West,
+        Button::Y => GamepadButton::North,
+    }
+}
+
+/// Start the motors of XInput pads whose players were sent a [`Rumble`],
+/// and stop any whose rumble has run its course.
+fn rumble_xinput_pads(
+    mut rumbles: MessageReader<Rumble>,
+    assignments: Res<InputAssignments>,
+    time: Res<Time>,
+    mut source: ResMut<XInputSource>,
+) {
+    let now = time.elapsed();
+    for rumble in rumbles.read() {
+        let Some(assignment) = assignments.0.get(rumble.player) else {
+            continue;
+        };
+        for device in &assignment.devices {
+            if let Device::XInput(slot) = *device
+                && source.xinput.vibrate(slot, rumble.strong, rumble.weak)
+            {
+                source.rumbling_until[slot] = Some(now + rumble.duration);
+            }
+        }
+    }
+    let XInputSource {
+        xinput,
+        rumbling_until,
+    } = &mut *source;
+    for (slot, until) in rumbling_until.iter_mut().enumerate() {
+        if until.is_some_and(|until| until <= now) {
+            xinput.vibrate(slot, 0.0, 0.0);
+            *until = None;
+        }
+    }
+}
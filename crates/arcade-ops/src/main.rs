@@ -59,7 +59,9 @@ fn main() {
         "data" => cmd_data(&bucket, &positional[1..]),
         // Meta
         "update" => cmd_update(),
-        "version" => println!("arcade-ops {}", env!("GIT_COMMIT_HASH")),
+        "version" => {
+            println!("{}", protocol::describe_build(arcade_buildinfo::build_info!()));
+        }
         other => {
             eprintln!("Unknown command: {other}");
             print_usage();
@@ -121,7 +123,7 @@ fn print_usage() {
     eprintln!("  data prune                     Delete data for inactive versions");
     eprintln!();
     eprintln!("  update                         Download latest arcade-ops binary");
-    eprintln!("  version                        Print arcade-ops version");
+    eprintln!("  version                        Print build, protocol versions and features");
     eprintln!();
     eprintln!("Environment:");
     eprintln!("  ARCADE_OPS_BUCKET              S3 bucket (default: {DEFAULT_BUCKET})");
@@ -0,0 +1,65 @@
+use bevy::prelude::*;
+
+/// Named colors shared across games, so a "neon cyan" paddle looks the same
+/// everywhere it appears.
+pub mod palette {
+    use bevy::prelude::Color;
+
+    pub const BORDER_GRAY: Color = Color::srgb(0.3, 0.3, 0.3);
+
+    pub const VIVID_BLUE: Color = Color::srgb_u8(0x15, 0x2e, 0xff);
+    pub const RADIOACTIVE_GREEN: Color = Color::srgb_u8(0x2c, 0xfa, 0x1f);
+    pub const RED_WINE: Color = Color::srgba_u8(140, 0, 52, 255);
+
+    // Above 1.0 so they bloom under HDR.
+    pub const NEON_CYAN: Color = Color::linear_rgb(0.0, 4.0, 4.0);
+    pub const NEON_MAGENTA: Color = Color::linear_rgb(4.0, 0.0, 4.0);
+    pub const NEON_HOT_PINK: Color = Color::linear_rgb(5.0, 0.5, 2.0);
+    pub const NEON_GREEN: Color = Color::linear_rgb(0.0, 3.0, 0.0);
+    pub const DIM_BLUE: Color = Color::linear_rgb(0.3, 0.1, 0.8);
+    pub const DIM_PURPLE: Color = Color::linear_rgb(0.2, 0.1, 0.5);
+}
+
+/// `color` with its alpha replaced. Works in linear space, so HDR values
+/// above 1.0 survive.
+pub fn with_alpha(color: Color, alpha: f32) -> Color {
+    let linear = LinearRgba::from(color);
+    Color::linear_rgba(linear.red, linear.green, linear.blue, alpha)
+}
+
+/// Blend from `a` to `b` in linear space; `t` is clamped to 0..=1.
+pub fn lerp_color(a: Color, b: Color, t: f32) -> Color {
+    let a_lin = LinearRgba::from(a);
+    let b_lin = LinearRgba::from(b);
+    let t_clamped = t.clamp(0.0, 1.0);
+
+    Color::linear_rgba(
+        a_lin.red + (b_lin.red - a_lin.red) * t_clamped,
+        a_lin.green + (b_lin.green - a_lin.green) * t_clamped,
+        a_lin.blue + (b_lin.blue - a_lin.blue) * t_clamped,
+        a_lin.alpha + (b_lin.alpha - a_lin.alpha) * t_clamped,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_alpha_keeps_hdr_channels() {
+        let faded = LinearRgba::from(with_alpha(palette::NEON_CYAN, 0.25));
+        assert_eq!(faded, LinearRgba::new(0.0, 4.0, 4.0, 0.25));
+    }
+
+    #[test]
+    fn lerp_color_clamps_past_the_ends() {
+        let black = Color::linear_rgb(0.0, 0.0, 0.0);
+        let white = Color::linear_rgb(1.0, 1.0, 1.0);
+        assert_eq!(
+            LinearRgba::from(lerp_color(black, white, 0.5)),
+            LinearRgba::new(0.5, 0.5, 0.5, 1.0)
+        );
+        assert_eq!(LinearRgba::from(lerp_color(black, white, 2.0)), LinearRgba::from(white));
+        assert_eq!(LinearRgba::from(lerp_color(black, white, -1.0)), LinearRgba::from(black));
+    }
+}
@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+
+/// Movement in world units per second.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Velocity(pub Vec2);
+
+/// A countdown for short-lived entities such as trails and particles.
+#[derive(Component, Debug, Clone)]
+pub struct Lifetime(pub Timer);
+
+impl Lifetime {
+    pub fn from_seconds(secs: f32) -> Self {
+        Self(Timer::from_seconds(secs, TimerMode::Once))
+    }
+
+    pub fn tick(&mut self, delta: std::time::Duration) {
+        self.0.tick(delta);
+    }
+
+    /// Share of the lifetime still to go: 1.0 when spawned, 0.0 when expired.
+    /// Handy for fading and shrinking.
+    pub fn remaining(&self) -> f32 {
+        1.0 - self.0.fraction()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.0.is_finished()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn lifetime_counts_down_to_finished() {
+        // given a one-second lifetime
+        let mut lifetime = Lifetime::from_seconds(1.0);
+
+        // when a quarter, then the rest, of it passes
+        lifetime.tick(Duration::from_millis(250));
+        let early = lifetime.remaining();
+        lifetime.tick(Duration::from_millis(800));
+
+        // then it reports what is left, and finishes once it runs out
+        assert!((early - 0.75).abs() < 1e-6);
+        assert_eq!(lifetime.remaining(), 0.0);
+        assert!(lifetime.is_finished());
+    }
+}
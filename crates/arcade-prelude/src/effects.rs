@@ -0,0 +1,284 @@
+use bevy::prelude::*;
+
+use crate::color::with_alpha;
+use crate::components::{Lifetime, Velocity};
+use crate::sets::{EffectSet, EffectSetsPlugin};
+
+const TRAIL_LIFETIME_SECS: f32 = 0.3;
+const TRAIL_SIZE_FACTOR: f32 = 0.8;
+const TRAIL_ALPHA: f32 = 0.5;
+
+const PARTICLE_LIFETIME_SECS: f32 = 0.4;
+const PARTICLE_SIZE: f32 = 5.0;
+const PARTICLE_SPEED: f32 = 300.0;
+/// Particles draw over everything else in the arena.
+const PARTICLE_Z: f32 = 2.0;
+
+const MAX_SHAKE_OFFSET: f32 = 8.0;
+const TRAUMA_DECAY_RATE: f32 = 3.0;
+
+const GRID_COLOR: Color = Color::linear_rgba(0.1, 0.05, 0.2, 0.3);
+const GRID_SPACING: f32 = 50.0;
+const GRID_LINE_THICKNESS: f32 = 1.0;
+
+/// A fading, shrinking copy left behind by something moving.
+#[derive(Component)]
+pub struct Trail {
+    initial_size: f32,
+}
+
+/// A spark flying out of a hit, fading and shrinking as it goes.
+#[derive(Component)]
+pub struct Particle {
+    initial_size: f32,
+}
+
+/// How hard the camera is shaking. Trauma from 0.0 to 1.0 wears off over
+/// a third of a second; the offset grows with its square.
+#[derive(Resource, Default)]
+pub struct ScreenShake {
+    trauma: f32,
+    frame: u32,
+}
+
+impl ScreenShake {
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.0);
+    }
+}
+
+/// Ages trails and particles and shakes 2D cameras, in the [`EffectSet`]s.
+/// Games spawn the effects themselves, from their own events.
+pub struct EffectsPlugin;
+
+impl Plugin for EffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(EffectSetsPlugin)
+            .init_resource::<ScreenShake>()
+            .add_systems(
+                Update,
+                (update_trails, update_particles).in_set(EffectSet::Update),
+            )
+            .add_systems(Update, apply_screen_shake.in_set(EffectSet::Camera));
+    }
+}
+
+/// Leave a trail behind something `size` across at `translation`, drawn at
+/// the same depth.
+pub fn spawn_trail(commands: &mut Commands, translation: Vec3, size: f32, color: Color) {
+    let trail_size = size * TRAIL_SIZE_FACTOR;
+    commands.spawn((
+        Trail {
+            initial_size: trail_size,
+        },
+        Lifetime::from_seconds(TRAIL_LIFETIME_SECS),
+        Sprite {
+            color: with_alpha(color, TRAIL_ALPHA),
+            custom_size: Some(Vec2::splat(trail_size)),
+            ..default()
+        },
+        Transform::from_translation(translation),
+    ));
+}
+
+/// `count` particles fanned evenly over `spread` radians around
+/// `center_angle`, flying out from `position`.
+pub fn spawn_particles(
+    commands: &mut Commands,
+    position: Vec2,
+    count: usize,
+    center_angle: f32,
+    spread: f32,
+    color: Color,
+) {
+    let half_spread = spread / 2.0;
+
+    for i in 0..count {
+        let fraction = if count <= 1 {
+            0.5
+        } else {
+            i as f32 / (count - 1) as f32
+        };
+        let angle = center_angle - half_spread + spread * fraction;
+        let direction = Vec2::new(angle.cos(), angle.sin());
+        let velocity = direction * PARTICLE_SPEED;
+
+        commands.spawn((
+            Particle {
+                initial_size: PARTICLE_SIZE,
+            },
+            Lifetime::from_seconds(PARTICLE_LIFETIME_SECS),
+            Velocity(velocity),
+            Sprite {
+                color,
+                custom_size: Some(Vec2::splat(PARTICLE_SIZE)),
+                ..default()
+            },
+            Transform::from_translation(position.extend(PARTICLE_Z)),
+        ));
+    }
+}
+
+/// A faint grid over an `arena` centered on the origin, at depth `z`.
+pub fn spawn_background_grid(commands: &mut Commands, arena: Vec2, z: f32) {
+    let half_w = arena.x / 2.0;
+    let half_h = arena.y / 2.0;
+
+    // Vertical lines
+    let mut x = -half_w;
+    while x <= half_w {
+        commands.spawn((
+            Sprite {
+                color: GRID_COLOR,
+                custom_size: Some(Vec2::new(GRID_LINE_THICKNESS, arena.y)),
+                ..default()
+            },
+            Transform::from_xyz(x, 0.0, z),
+        ));
+        x += GRID_SPACING;
+    }
+
+    // Horizontal lines
+    let mut y = -half_h;
+    while y <= half_h {
+        commands.spawn((
+            Sprite {
+                color: GRID_COLOR,
+                custom_size: Some(Vec2::new(arena.x, GRID_LINE_THICKNESS)),
+                ..default()
+            },
+            Transform::from_xyz(0.0, y, z),
+        ));
+        y += GRID_SPACING;
+    }
+}
+
+fn update_trails(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut trails: Query<(Entity, &Trail, &mut Lifetime, &mut Sprite, &mut Transform)>,
+) {
+    for (entity, trail, mut lifetime, mut sprite, mut transform) in &mut trails {
+        lifetime.tick(time.delta());
+
+        let remaining = lifetime.remaining();
+
+        if let Some(size) = &mut sprite.custom_size {
+            *size = Vec2::splat(trail.initial_size * remaining);
+        }
+        sprite.color = with_alpha(sprite.color, TRAIL_ALPHA * remaining);
+        transform.scale = Vec3::splat(remaining.max(0.01));
+
+        if lifetime.is_finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut particles: Query<(
+        Entity,
+        &Particle,
+        &mut Lifetime,
+        &Velocity,
+        &mut Sprite,
+        &mut Transform,
+    )>,
+) {
+    let dt = time.delta_secs();
+
+    for (entity, particle, mut lifetime, velocity, mut sprite, mut transform) in &mut particles {
+        lifetime.tick(time.delta());
+        transform.translation += velocity.0.extend(0.0) * dt;
+
+        let remaining = lifetime.remaining();
+        let size = particle.initial_size * remaining;
+        sprite.custom_size = Some(Vec2::splat(size.max(0.1)));
+        sprite.color = with_alpha(sprite.color, remaining);
+
+        if lifetime.is_finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn apply_screen_shake(
+    time: Res<Time>,
+    mut shake: ResMut<ScreenShake>,
+    mut camera: Query<&mut Transform, With<Camera2d>>,
+) {
+    shake.frame = shake.frame.wrapping_add(1);
+    let dt = time.delta_secs();
+
+    shake.trauma = (shake.trauma - TRAUMA_DECAY_RATE * dt).max(0.0);
+
+    for mut transform in &mut camera {
+        if shake.trauma > 0.001 {
+            let intensity = shake.trauma * shake.trauma;
+            let frame = shake.frame as f32;
+
+            // Pseudo-random offset using sin of frame * primes
+            transform.translation.x = (frame * 97.0).sin() * MAX_SHAKE_OFFSET * intensity;
+            transform.translation.y = (frame * 53.0).sin() * MAX_SHAKE_OFFSET * intensity;
+        } else {
+            transform.translation.x = 0.0;
+            transform.translation.y = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn particles_fan_out_evenly_across_the_spread() {
+        // given an app
+        let mut app = App::new();
+
+        // when three particles are sprayed over a half turn centered on +x
+        app.world_mut()
+            .run_system_once(|mut commands: Commands| {
+                spawn_particles(
+                    &mut commands,
+                    Vec2::ZERO,
+                    3,
+                    0.0,
+                    std::f32::consts::PI,
+                    Color::WHITE,
+                );
+            })
+            .unwrap();
+
+        // then they fly straight down, right, and up
+        let mut velocities: Vec<Vec2> = app
+            .world_mut()
+            .query::<&Velocity>()
+            .iter(app.world())
+            .map(|velocity| velocity.0 / PARTICLE_SPEED)
+            .collect();
+        velocities.sort_by(|a, b| a.y.total_cmp(&b.y));
+        let expected = [Vec2::NEG_Y, Vec2::X, Vec2::Y];
+        for (velocity, expected) in velocities.iter().zip(expected) {
+            assert!(velocity.abs_diff_eq(expected, 1e-6), "{velocity} != {expected}");
+        }
+        assert_eq!(velocities.len(), 3);
+    }
+
+    #[test]
+    fn trauma_tops_out_at_one() {
+        // given a camera already shaking
+        let mut shake = ScreenShake::default();
+        shake.add_trauma(0.5);
+
+        // when more trauma piles on than it can take
+        shake.add_trauma(0.8);
+
+        // then it shakes at most fully
+        assert_eq!(shake.trauma, 1.0);
+    }
+}
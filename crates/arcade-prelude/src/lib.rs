@@ -0,0 +1,28 @@
+//! Components, helpers, and system-set labels shared by the games and examples.
+//!
+//! Every pong variant and ball toy used to carry its own copy of `Velocity`,
+//! `spawn_border`, and the color helpers, and the copies had started to drift.
+//! They live here now:
+//! - [`Velocity`] and [`Lifetime`] components
+//! - [`palette`] constants and the [`with_alpha`] / [`lerp_color`] helpers
+//! - [`spawn_border`] and [`spawn_arena_walls`] for rectangular playfields
+//! - [`EffectSet`] for ordering cosmetic effects after the frame's gameplay
+//! - [`EffectsPlugin`] with [`spawn_trail`], [`spawn_particles`],
+//!   [`ScreenShake`] and [`spawn_background_grid`], the neon pongs' effects
+//!
+//! Glob-import it next to Bevy's prelude: `use arcade_prelude::*;`
+
+pub mod color;
+pub mod components;
+pub mod effects;
+pub mod sets;
+pub mod spawn;
+
+pub use color::{lerp_color, palette, with_alpha};
+pub use components::{Lifetime, Velocity};
+pub use effects::{
+    EffectsPlugin, Particle, ScreenShake, Trail, spawn_background_grid, spawn_particles,
+    spawn_trail,
+};
+pub use sets::{EffectSet, EffectSetsPlugin};
+pub use spawn::{spawn_arena_walls, spawn_border};
@@ -0,0 +1,33 @@
+use bevy::prelude::*;
+
+/// Cosmetic effects in `Update`: react to this frame's gameplay events, then
+/// age what is already on screen, then move the camera.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EffectSet {
+    /// Spawn trails, particles, and flashes from gameplay events.
+    Spawn,
+    /// Tick lifetimes, fade, shrink, and despawn.
+    Update,
+    /// Screen shake and other camera moves, after everything is placed.
+    Camera,
+}
+
+/// Orders the [`EffectSet`]s. Adding it from several plugins is fine.
+pub struct EffectSetsPlugin;
+
+impl Plugin for EffectSetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(
+            Update,
+            (
+                EffectSet::Spawn,
+                EffectSet::Update.after(EffectSet::Spawn),
+                EffectSet::Camera.after(EffectSet::Update),
+            ),
+        );
+    }
+
+    fn is_unique(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,30 @@
+use bevy::prelude::*;
+
+/// A solid rectangle of `color` centered on `position`.
+pub fn spawn_border(commands: &mut Commands, position: Vec3, width: f32, height: f32, color: Color) {
+    commands.spawn((
+        Sprite {
+            color,
+            custom_size: Some(Vec2::new(width, height)),
+            ..default()
+        },
+        Transform::from_translation(position),
+    ));
+}
+
+/// Four walls of `thickness` centered on the edges of a `width` x `height`
+/// arena around the origin.
+pub fn spawn_arena_walls(
+    commands: &mut Commands,
+    width: f32,
+    height: f32,
+    thickness: f32,
+    color: Color,
+) {
+    let half_width = width / 2.0;
+    let half_height = height / 2.0;
+    spawn_border(commands, Vec3::new(0.0, half_height, 0.0), width, thickness, color);
+    spawn_border(commands, Vec3::new(0.0, -half_height, 0.0), width, thickness, color);
+    spawn_border(commands, Vec3::new(-half_width, 0.0, 0.0), thickness, height, color);
+    spawn_border(commands, Vec3::new(half_width, 0.0, 0.0), thickness, height, color);
+}
@@ -0,0 +1,127 @@
+//! The about screen: what this binary was built from and what it found.
+//!
+//! Insert an [`AboutInfo`] and add [`AboutPlugin`]; F1 or any gamepad's
+//! Select button toggles an overlay with the same report `--version` prints.
+//! The plugin fills in the backends it can see for itself: connected
+//! gamepads, and with the `render` feature the GPU adapter. Binaries add any
+//! others (an audio host, say) to the [`BuildInfo`] before inserting it.
+
+use arcade_buildinfo::BuildInfo;
+use bevy::prelude::*;
+
+const OVERLAY_COLOR: Color = Color::srgba(0.0, 0.0, 0.0, 0.9);
+const TEXT_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
+const ABOUT_FONT_SIZE: f32 = 16.0;
+
+pub struct AboutPlugin;
+
+impl Plugin for AboutPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (record_gamepads, toggle_about_screen, refresh_about_screen).chain(),
+        );
+        #[cfg(feature = "render")]
+        app.add_systems(Update, record_gpu.before(refresh_about_screen));
+    }
+}
+
+/// The report the about screen shows.
+#[derive(Resource, Debug, Clone)]
+pub struct AboutInfo(pub BuildInfo);
+
+#[derive(Component)]
+struct AboutScreen;
+
+#[derive(Component)]
+struct AboutText;
+
+/// Describe `count` connected gamepads the way the report lists backends.
+fn gamepads_description(count: usize) -> String {
+    match count {
+        0 => "none connected".to_string(),
+        n => format!("{n} connected"),
+    }
+}
+
+/// Set a backend only when it changed, so the screen is not rebuilt every frame.
+fn set_backend(info: &mut ResMut<AboutInfo>, kind: &'static str, description: String) {
+    let current = info.0.backends.iter().find(|(existing, _)| *existing == kind);
+    if current.is_none_or(|(_, found)| *found != description) {
+        info.0.add_backend(kind, description);
+    }
+}
+
+fn record_gamepads(mut info: ResMut<AboutInfo>, gamepads: Query<&Gamepad>) {
+    set_backend(&mut info, "gamepads", gamepads_description(gamepads.iter().count()));
+}
+
+#[cfg(feature = "render")]
+fn record_gpu(
+    mut info: ResMut<AboutInfo>,
+    adapter: Option<Res<bevy::render::renderer::RenderAdapterInfo>>,
+) {
+    if let Some(adapter) = adapter {
+        set_backend(&mut info, "gpu", format!("{:?} on {}", adapter.backend, adapter.name));
+    }
+}
+
+fn toggle_about_screen(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    info: Res<AboutInfo>,
+    screen: Query<Entity, With<AboutScreen>>,
+) {
+    let pressed = keyboard.just_pressed(KeyCode::F1)
+        || gamepads.iter().any(|gamepad| gamepad.just_pressed(GamepadButton::Select));
+    if !pressed {
+        return;
+    }
+    if let Ok(screen) = screen.single() {
+        commands.entity(screen).despawn();
+        return;
+    }
+    commands
+        .spawn((
+            AboutScreen,
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                padding: UiRect::all(Val::Px(20.0)),
+                ..default()
+            },
+            BackgroundColor(OVERLAY_COLOR),
+            GlobalZIndex(200),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                AboutText,
+                Text::new(info.0.to_string()),
+                TextFont::from_font_size(ABOUT_FONT_SIZE),
+                TextColor(TEXT_COLOR),
+            ));
+        });
+}
+
+fn refresh_about_screen(info: Res<AboutInfo>, mut text: Query<&mut Text, With<AboutText>>) {
+    if !info.is_changed() {
+        return;
+    }
+    for mut text in &mut text {
+        text.0 = info.0.to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gamepad_count_reads_naturally() {
+        assert_eq!(gamepads_description(0), "none connected");
+        assert_eq!(gamepads_description(1), "1 connected");
+        assert_eq!(gamepads_description(3), "3 connected");
+    }
+}
@@ -0,0 +1,288 @@
+//! An on-screen keyboard as plain data.
+//!
+//! The keyboard is a grid of [`Key`]s with a cursor. Moving the cursor and
+//! pressing the key under it is all a player can do, so a d-pad is enough.
+//! Rows may differ in length; moving up or down keeps the column where it
+//! can and otherwise lands on the row's last key.
+//!
+//! What the grid offers depends on the [`Charset`]: a player name gets
+//! letters only, a relay address gets a number pad. Keys that could only
+//! produce rejected input are simply not on the keyboard.
+
+/// Which characters a keyboard offers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    /// Letters only, e.g. a player name.
+    Letters,
+    /// Lowercase letters, digits, and `-`, e.g. a room name.
+    RoomCode,
+    /// Digits, `.` and `:`, e.g. `192.168.1.20:7700`.
+    Address,
+    /// Letters with shift, digits, space, and common symbols, e.g. a secret.
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Space,
+    /// Toggles upper case for letter keys.
+    Shift,
+    Backspace,
+    /// Submits the text.
+    Done,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OnScreenKeyboard {
+    rows: Vec<Vec<Key>>,
+    /// (row, column)
+    cursor: (usize, usize),
+    text: String,
+    max_len: usize,
+    shifted: bool,
+}
+
+fn char_row(chars: &str) -> Vec<Key> {
+    chars.chars().map(Key::Char).collect()
+}
+
+fn layout(charset: Charset) -> Vec<Vec<Key>> {
+    match charset {
+        Charset::Letters => vec![
+            char_row("abcdefghi"),
+            char_row("jklmnopqr"),
+            char_row("stuvwxyz"),
+            vec![Key::Shift, Key::Backspace, Key::Done],
+        ],
+        Charset::RoomCode => vec![
+            char_row("1234567890"),
+            char_row("abcdefghij"),
+            char_row("klmnopqrst"),
+            char_row("uvwxyz-"),
+            vec![Key::Backspace, Key::Done],
+        ],
+        Charset::Address => vec![
+            char_row("123"),
+            char_row("456"),
+            char_row("789"),
+            char_row(".0:"),
+            vec![Key::Backspace, Key::Done],
+        ],
+        Charset::Text => vec![
+            char_row("1234567890"),
+            char_row("abcdefghij"),
+            char_row("klmnopqrst"),
+            char_row("uvwxyz-_.@"),
+            char_row("!?#$%&*+/:"),
+            vec![Key::Shift, Key::Space, Key::Backspace, Key::Done],
+        ],
+    }
+}
+
+impl OnScreenKeyboard {
+    pub fn new(charset: Charset, max_len: usize) -> Self {
+        Self {
+            rows: layout(charset),
+            cursor: (0, 0),
+            text: String::new(),
+            max_len,
+            shifted: matches!(charset, Charset::Letters),
+        }
+    }
+
+    /// Start with `text` already typed, e.g. the current value being edited.
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.text = text.chars().take(self.max_len).collect();
+        // A name starts with a capital; after that, lower case.
+        if !self.text.is_empty() && self.shifted {
+            self.shifted = false;
+        }
+        self
+    }
+
+    pub fn rows(&self) -> &[Vec<Key>] {
+        &self.rows
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        self.cursor
+    }
+
+    pub fn selected(&self) -> Key {
+        self.rows[self.cursor.0][self.cursor.1]
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.text.chars().count() >= self.max_len
+    }
+
+    pub fn shifted(&self) -> bool {
+        self.shifted
+    }
+
+    /// How `key` is shown, taking shift into account.
+    pub fn label(&self, key: Key) -> String {
+        match key {
+            Key::Char(c) if self.shifted => c.to_ascii_uppercase().to_string(),
+            Key::Char(c) => c.to_string(),
+            Key::Space => "space".into(),
+            Key::Shift => "shift".into(),
+            Key::Backspace => "del".into(),
+            Key::Done => "done".into(),
+        }
+    }
+
+    /// Move the cursor one key, wrapping at the edges.
+    pub fn navigate(&mut self, direction: NavDirection) {
+        let (row, column) = self.cursor;
+        let row_count = self.rows.len();
+        let row_len = self.rows[row].len();
+        self.cursor = match direction {
+            NavDirection::Left => (row, (column + row_len - 1) % row_len),
+            NavDirection::Right => (row, (column + 1) % row_len),
+            NavDirection::Up => self.clamped(row.checked_sub(1).unwrap_or(row_count - 1), column),
+            NavDirection::Down => self.clamped((row + 1) % row_count, column),
+        };
+    }
+
+    fn clamped(&self, row: usize, column: usize) -> (usize, usize) {
+        (row, column.min(self.rows[row].len() - 1))
+    }
+
+    /// Press the key under the cursor. Returns the text when that key is
+    /// [`Key::Done`].
+    pub fn press(&mut self) -> Option<String> {
+        match self.selected() {
+            Key::Char(c) => {
+                let c = if self.shifted { c.to_ascii_uppercase() } else { c };
+                self.type_char(c);
+                // Shift applies to one letter, like a phone keyboard.
+                if c.is_ascii_alphabetic() {
+                    self.shifted = false;
+                }
+            }
+            Key::Space => self.type_char(' '),
+            Key::Shift => self.shifted = !self.shifted,
+            Key::Backspace => self.backspace(),
+            Key::Done => return Some(self.text.clone()),
+        }
+        None
+    }
+
+    pub fn backspace(&mut self) {
+        self.text.pop();
+    }
+
+    /// Type a space if this keyboard has a space key.
+    pub fn space(&mut self) {
+        if self.rows.iter().flatten().any(|key| *key == Key::Space) {
+            self.type_char(' ');
+        }
+    }
+
+    pub fn toggle_shift(&mut self) {
+        if self.rows.iter().flatten().any(|key| *key == Key::Shift) {
+            self.shifted = !self.shifted;
+        }
+    }
+
+    fn type_char(&mut self, c: char) {
+        if !self.is_full() {
+            self.text.push(c);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Move the cursor onto `target`, then press it.
+    fn type_key(keyboard: &mut OnScreenKeyboard, target: Key) -> Option<String> {
+        let (row, column) = keyboard
+            .rows()
+            .iter()
+            .enumerate()
+            .find_map(|(row, keys)| keys.iter().position(|key| *key == target).map(|c| (row, c)))
+            .expect("key is on the keyboard");
+        while keyboard.cursor().0 != row {
+            keyboard.navigate(NavDirection::Down);
+        }
+        while keyboard.cursor().1 != column {
+            keyboard.navigate(NavDirection::Right);
+        }
+        keyboard.press()
+    }
+
+    #[test]
+    fn names_start_with_a_capital() {
+        // given a letters keyboard
+        let mut keyboard = OnScreenKeyboard::new(Charset::Letters, 20);
+
+        // when a name is typed and submitted
+        for c in "bob".chars() {
+            type_key(&mut keyboard, Key::Char(c));
+        }
+        let submitted = type_key(&mut keyboard, Key::Done);
+
+        // then only the first letter was shifted
+        assert_eq!(submitted.as_deref(), Some("Bob"));
+    }
+
+    #[test]
+    fn cursor_wraps_and_clamps_to_shorter_rows() {
+        // given the cursor on the last key of the first address row
+        let mut keyboard = OnScreenKeyboard::new(Charset::Address, 21);
+        keyboard.navigate(NavDirection::Left);
+        assert_eq!(keyboard.selected(), Key::Char('3'));
+
+        // when it moves up past the top
+        keyboard.navigate(NavDirection::Up);
+
+        // then it wraps to the bottom row, clamped to that row's length
+        assert_eq!(keyboard.selected(), Key::Done);
+    }
+
+    #[test]
+    fn text_stops_at_the_maximum_length() {
+        let mut keyboard = OnScreenKeyboard::new(Charset::RoomCode, 3);
+        for _ in 0..5 {
+            keyboard.press();
+        }
+        assert_eq!(keyboard.text(), "111");
+        assert!(keyboard.is_full());
+    }
+
+    #[test]
+    fn backspace_and_space() {
+        // given a text keyboard with something typed
+        let mut keyboard = OnScreenKeyboard::new(Charset::Text, 32).with_text("ab");
+
+        // when a character is deleted and a space added
+        keyboard.backspace();
+        keyboard.space();
+
+        // then the text reflects both
+        assert_eq!(keyboard.text(), "a ");
+    }
+
+    #[test]
+    fn keyboards_without_space_ignore_it() {
+        let mut keyboard = OnScreenKeyboard::new(Charset::RoomCode, 32);
+        keyboard.space();
+        assert_eq!(keyboard.text(), "");
+    }
+}
@@ -0,0 +1,21 @@
+//! UI widgets shared by the arcade's screens.
+//!
+//! Everything here must work from the couch: a gamepad is the only input
+//! device these widgets assume.
+//! - [`keyboard::OnScreenKeyboard`], an IO-free grid keyboard: layout, cursor,
+//!   and the text typed so far
+//! - [`text_entry::TextEntryPlugin`], which puts that keyboard on screen,
+//!   drives it with the d-pad or left stick, and reports the result as a
+//!   [`text_entry::TextEntrySubmitted`] or [`text_entry::TextEntryCancelled`]
+//! - [`about::AboutPlugin`], an about screen toggled with F1 or Select that
+//!   shows the binary's build report
+
+pub mod about;
+pub mod keyboard;
+pub mod text_entry;
+
+pub use about::{AboutInfo, AboutPlugin};
+pub use keyboard::{Charset, Key, NavDirection, OnScreenKeyboard};
+pub use text_entry::{
+    TextEntry, TextEntryCancelled, TextEntryPlugin, TextEntrySubmitted, spawn_text_entry,
+};
@@ -0,0 +1,238 @@
+//! The on-screen keyboard as a Bevy widget.
+//!
+//! [`spawn_text_entry`] puts an [`OnScreenKeyboard`] on screen as an overlay;
+//! any connected gamepad drives it:
+//! - d-pad or left stick: move (the stick repeats while held)
+//! - South: press the selected key
+//! - East: delete, or cancel when nothing is typed
+//! - West: space, North: shift, Start: submit
+//!
+//! The widget despawns itself once it sends a [`TextEntrySubmitted`] or
+//! [`TextEntryCancelled`]. Callers match on the entity to know which prompt
+//! the answer belongs to.
+
+use bevy::prelude::*;
+
+use crate::keyboard::{Key, NavDirection, OnScreenKeyboard};
+
+/// Left stick deflection that counts as a direction.
+const STICK_THRESHOLD: f32 = 0.5;
+/// Hold the stick this long before the cursor starts repeating.
+const REPEAT_DELAY: f32 = 0.4;
+const REPEAT_INTERVAL: f32 = 0.1;
+
+const OVERLAY_COLOR: Color = Color::srgba(0.0, 0.0, 0.0, 0.85);
+const PROMPT_COLOR: Color = Color::srgb(1.0, 1.0, 0.3);
+const KEY_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
+
+pub struct TextEntryPlugin;
+
+impl Plugin for TextEntryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<TextEntrySubmitted>()
+            .add_message::<TextEntryCancelled>()
+            .add_systems(Update, (drive_text_entry, render_text_entry).chain());
+    }
+}
+
+#[derive(Component)]
+pub struct TextEntry {
+    pub keyboard: OnScreenKeyboard,
+    pub prompt: String,
+    /// The stick direction being held and when it next repeats.
+    held: Option<(NavDirection, f32)>,
+}
+
+#[derive(Message, Debug, Clone, PartialEq)]
+pub struct TextEntrySubmitted {
+    pub entity: Entity,
+    pub text: String,
+}
+
+#[derive(Message, Debug, Clone, PartialEq)]
+pub struct TextEntryCancelled {
+    pub entity: Entity,
+}
+
+#[derive(Component)]
+struct TextEntryLine;
+
+#[derive(Component)]
+struct TextEntryGrid;
+
+/// Open a text entry overlay. Returns the widget's entity, which is also
+/// the `entity` of the message it eventually sends.
+pub fn spawn_text_entry(
+    commands: &mut Commands,
+    prompt: impl Into<String>,
+    keyboard: OnScreenKeyboard,
+) -> Entity {
+    commands
+        .spawn((
+            TextEntry {
+                keyboard,
+                prompt: prompt.into(),
+                held: None,
+            },
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(16.0),
+                ..default()
+            },
+            BackgroundColor(OVERLAY_COLOR),
+            GlobalZIndex(100),
+        ))
+        .with_children(|overlay| {
+            overlay.spawn((
+                TextEntryLine,
+                Text::new(""),
+                TextFont::from_font_size(28.0),
+                TextColor(PROMPT_COLOR),
+            ));
+            overlay.spawn((
+                TextEntryGrid,
+                Text::new(""),
+                TextFont::from_font_size(24.0),
+                TextColor(KEY_COLOR),
+                TextLayout::new_with_justify(Justify::Center),
+            ));
+        })
+        .id()
+}
+
+fn dpad_direction(gamepad: &Gamepad) -> Option<NavDirection> {
+    [
+        (GamepadButton::DPadUp, NavDirection::Up),
+        (GamepadButton::DPadDown, NavDirection::Down),
+        (GamepadButton::DPadLeft, NavDirection::Left),
+        (GamepadButton::DPadRight, NavDirection::Right),
+    ]
+    .into_iter()
+    .find_map(|(button, direction)| gamepad.just_pressed(button).then_some(direction))
+}
+
+fn stick_direction(gamepad: &Gamepad) -> Option<NavDirection> {
+    let stick = gamepad.left_stick();
+    if stick.length() < STICK_THRESHOLD {
+        None
+    } else if stick.x.abs() > stick.y.abs() {
+        Some(if stick.x > 0.0 { NavDirection::Right } else { NavDirection::Left })
+    } else {
+        Some(if stick.y > 0.0 { NavDirection::Up } else { NavDirection::Down })
+    }
+}
+
+fn drive_text_entry(
+    mut commands: Commands,
+    time: Res<Time>,
+    gamepads: Query<&Gamepad>,
+    mut entries: Query<(Entity, &mut TextEntry)>,
+    mut submitted: MessageWriter<TextEntrySubmitted>,
+    mut cancelled: MessageWriter<TextEntryCancelled>,
+) {
+    // Only the most recently opened entry takes input.
+    let Some((entity, mut entry)) = entries.iter_mut().max_by_key(|(entity, _)| *entity) else {
+        return;
+    };
+
+    let stick = gamepads.iter().find_map(stick_direction);
+    let mut moves: Vec<NavDirection> = gamepads.iter().filter_map(dpad_direction).collect();
+    match (stick, entry.held) {
+        (Some(direction), Some((held, next))) if direction == held => {
+            let next = next - time.delta_secs();
+            if next <= 0.0 {
+                moves.push(direction);
+                entry.held = Some((direction, next + REPEAT_INTERVAL));
+            } else {
+                entry.held = Some((direction, next));
+            }
+        }
+        (Some(direction), _) => {
+            moves.push(direction);
+            entry.held = Some((direction, REPEAT_DELAY));
+        }
+        (None, _) => entry.held = None,
+    }
+
+    for direction in moves {
+        entry.keyboard.navigate(direction);
+    }
+
+    for gamepad in &gamepads {
+        let text = if gamepad.just_pressed(GamepadButton::Start) {
+            Some(entry.keyboard.text().to_string())
+        } else if gamepad.just_pressed(GamepadButton::South) {
+            entry.keyboard.press()
+        } else {
+            if gamepad.just_pressed(GamepadButton::East) {
+                if entry.keyboard.text().is_empty() {
+                    cancelled.write(TextEntryCancelled { entity });
+                    commands.entity(entity).despawn();
+                    return;
+                }
+                entry.keyboard.backspace();
+            }
+            if gamepad.just_pressed(GamepadButton::West) {
+                entry.keyboard.space();
+            }
+            if gamepad.just_pressed(GamepadButton::North) {
+                entry.keyboard.toggle_shift();
+            }
+            None
+        };
+        if let Some(text) = text {
+            submitted.write(TextEntrySubmitted { entity, text });
+            commands.entity(entity).despawn();
+            return;
+        }
+    }
+}
+
+fn render_text_entry(
+    entries: Query<(&TextEntry, &Children), Changed<TextEntry>>,
+    mut lines: Query<&mut Text, (With<TextEntryLine>, Without<TextEntryGrid>)>,
+    mut grids: Query<&mut Text, (With<TextEntryGrid>, Without<TextEntryLine>)>,
+) {
+    for (entry, children) in &entries {
+        let keyboard = &entry.keyboard;
+        for child in children.iter() {
+            if let Ok(mut line) = lines.get_mut(child) {
+                **line = format!("{} {}_", entry.prompt, keyboard.text());
+            }
+            if let Ok(mut grid) = grids.get_mut(child) {
+                **grid = keyboard
+                    .rows()
+                    .iter()
+                    .enumerate()
+                    .map(|(row, keys)| {
+                        keys.iter()
+                            .enumerate()
+                            .map(|(column, key)| key_cell(keyboard, *key, (row, column)))
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+            }
+        }
+    }
+}
+
+fn key_cell(keyboard: &OnScreenKeyboard, key: Key, at: (usize, usize)) -> String {
+    let label = keyboard.label(key);
+    let label = if key == Key::Shift && keyboard.shifted() {
+        label.to_uppercase()
+    } else {
+        label
+    };
+    if at == keyboard.cursor() {
+        format!("[{label}]")
+    } else {
+        format!(" {label} ")
+    }
+}
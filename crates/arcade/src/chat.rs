@@ -3,6 +3,10 @@
 //! Reuses the scroll/scrollbar system from `examples/chat_prototype.rs`.
 //! Messages come from the network instead of being local-only.
 
+use arcade_ui::{
+    Charset, OnScreenKeyboard, TextEntryCancelled, TextEntryPlugin, TextEntrySubmitted,
+    spawn_text_entry,
+};
 use bevy::input::keyboard::KeyboardInput;
 use bevy::input::mouse::MouseWheel;
 use bevy::prelude::*;
@@ -10,6 +14,7 @@ use bevy::ui::UiGlobalTransform;
 use protocol::{ChatPayload, RelayMessage, deserialize};
 
 use crate::fraktur::to_fraktur;
+use crate::kiosk::KioskIdleTimeout;
 
 use crate::config::{generate_identity_secret, save_config};
 use crate::net::{
@@ -45,14 +50,22 @@ pub struct ChatPlugin;
 
 impl Plugin for ChatPlugin {
     fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<TextEntryPlugin>() {
+            app.add_plugins(TextEntryPlugin);
+        }
         app.init_resource::<ChatState>()
             .init_resource::<ScrollbarDragState>()
             .add_message::<TextSubmitted>()
+            .add_message::<KioskIdleTimeout>()
             .add_systems(Startup, setup_ui)
             .add_systems(
                 Update,
                 (
-                    (read_text_input, handle_text_submit).chain(),
+                    (
+                        (read_text_input, open_gamepad_keyboard, forward_gamepad_text),
+                        handle_text_submit,
+                    )
+                        .chain(),
                     (process_incoming_messages, spawn_new_messages)
                         .chain()
                         .after(ReceiveSet),
@@ -62,6 +75,7 @@ impl Plugin for ChatPlugin {
                     update_input_display,
                     update_status_bar,
                     auto_scroll_on_new_message,
+                    reset_on_kiosk_idle,
                 ),
             );
     }
@@ -74,6 +88,8 @@ struct ChatState {
     spawned_count: usize,
     input_mode: InputMode,
     welcomed: bool,
+    /// The on-screen keyboard, while a gamepad player is typing.
+    gamepad_keyboard: Option<Entity>,
 }
 
 #[derive(Debug, Default, PartialEq, Eq)]
@@ -290,6 +306,54 @@ fn read_text_input(
     }
 }
 
+/// Input layer for the couch: Start on any gamepad opens an on-screen
+/// keyboard suited to what the current input mode is asking for.
+fn open_gamepad_keyboard(
+    mut commands: Commands,
+    gamepads: Query<&Gamepad>,
+    mut chat: ResMut<ChatState>,
+) {
+    if chat.gamepad_keyboard.is_some()
+        || !gamepads.iter().any(|gamepad| gamepad.just_pressed(GamepadButton::Start))
+    {
+        return;
+    }
+    let (prompt, keyboard) = match chat.input_mode {
+        InputMode::NameEntry => ("name>", OnScreenKeyboard::new(Charset::Letters, 20)),
+        InputMode::RelaySecretEntry => ("relay>", OnScreenKeyboard::new(Charset::Text, 64)),
+        InputMode::SecretEntry => ("secret>", OnScreenKeyboard::new(Charset::Text, 64)),
+        InputMode::Chat => (">", OnScreenKeyboard::new(Charset::Text, 200)),
+    };
+    let keyboard = keyboard.with_text(&chat.input_buffer);
+    chat.gamepad_keyboard = Some(spawn_text_entry(&mut commands, prompt, keyboard));
+}
+
+/// Hands text typed on the on-screen keyboard to the same submit path as
+/// the physical keyboard. A cancelled keyboard just closes.
+fn forward_gamepad_text(
+    mut entered: MessageReader<TextEntrySubmitted>,
+    mut closed: MessageReader<TextEntryCancelled>,
+    mut chat: ResMut<ChatState>,
+    mut submit: MessageWriter<TextSubmitted>,
+) {
+    for cancelled in closed.read() {
+        if chat.gamepad_keyboard == Some(cancelled.entity) {
+            chat.gamepad_keyboard = None;
+        }
+    }
+    for entry in entered.read() {
+        if chat.gamepad_keyboard != Some(entry.entity) {
+            continue;
+        }
+        chat.gamepad_keyboard = None;
+        chat.input_buffer.clear();
+        let trimmed = entry.text.trim();
+        if !trimmed.is_empty() {
+            submit.write(TextSubmitted(trimmed.to_string()));
+        }
+    }
+}
+
 /// Simulation layer: dispatches submitted text based on current input mode.
 fn handle_text_submit(
     mut events: MessageReader<TextSubmitted>,
@@ -817,6 +881,15 @@ fn auto_scroll_on_new_message(
     scroll.y = max_scroll(content_h, viewport_h);
 }
 
+/// Kiosk idle timeout: drop any half-typed input and snap back to the latest messages.
+fn reset_on_kiosk_idle(mut events: MessageReader<KioskIdleTimeout>, mut chat: ResMut<ChatState>) {
+    if events.read().count() == 0 {
+        return;
+    }
+    chat.input_buffer.clear();
+    chat.set_changed();
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,156 @@
+//! Kiosk mode for unattended arcade-cabinet deployments.
+//!
+//! `--kiosk` turns the client into something that can be left alone:
+//! - a watchdog parent process re-launches the game whenever it crashes
+//! - the window is borderless fullscreen, always on top, with the cursor hidden
+//!   and confined, which keeps most OS shortcuts from stealing focus
+//! - after a period with no input the UI returns to its home state
+//!
+//! Usage: `cargo run -p arcade -- --kiosk`
+
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use bevy::input::gamepad::GamepadButtonChangedEvent;
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::mouse::MouseButtonInput;
+use bevy::prelude::*;
+use bevy::window::{CursorGrabMode, CursorOptions, MonitorSelection, WindowLevel, WindowMode};
+
+/// Set on the child process so it knows a watchdog is supervising it.
+const SUPERVISED_ENV: &str = "ARCADE_KIOSK_SUPERVISED";
+const RESTART_DELAY: Duration = Duration::from_secs(2);
+/// A child that crashes this soon after launch counts toward the crash loop limit.
+const QUICK_CRASH_WINDOW: Duration = Duration::from_secs(10);
+const MAX_QUICK_CRASHES: u32 = 5;
+const IDLE_TIMEOUT_SECS: f32 = 120.0;
+
+pub fn kiosk_from_args() -> bool {
+    std::env::args().any(|arg| arg == "--kiosk")
+}
+
+/// True when this process was launched by the kiosk watchdog.
+pub fn is_supervised() -> bool {
+    std::env::var_os(SUPERVISED_ENV).is_some()
+}
+
+/// Run the game as a child process and re-launch it whenever it crashes.
+///
+/// Returns only in the child. The watchdog parent exits when the child exits
+/// cleanly, or gives up after the game crashes repeatedly right after launch.
+pub fn supervise() {
+    if is_supervised() {
+        return;
+    }
+
+    let exe = std::env::current_exe().expect("cannot determine current executable");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut quick_crashes = 0;
+
+    loop {
+        let started = Instant::now();
+        let status = Command::new(&exe)
+            .args(&args)
+            .env(SUPERVISED_ENV, "1")
+            .status();
+
+        match status {
+            Ok(status) if status.success() => std::process::exit(0),
+            Ok(status) => println!("kiosk: game exited with {status}, restarting"),
+            Err(e) => println!("kiosk: failed to launch game: {e}"),
+        }
+
+        if started.elapsed() < QUICK_CRASH_WINDOW {
+            quick_crashes += 1;
+            if quick_crashes >= MAX_QUICK_CRASHES {
+                println!("kiosk: game crashed {quick_crashes} times right after launch, giving up");
+                std::process::exit(1);
+            }
+        } else {
+            quick_crashes = 0;
+        }
+
+        std::thread::sleep(RESTART_DELAY);
+    }
+}
+
+/// Window settings for kiosk mode, applied on top of the normal window.
+pub fn kiosk_window(window: Window) -> Window {
+    Window {
+        mode: WindowMode::BorderlessFullscreen(MonitorSelection::Primary),
+        window_level: WindowLevel::AlwaysOnTop,
+        decorations: false,
+        ..window
+    }
+}
+
+pub fn kiosk_cursor_options() -> CursorOptions {
+    CursorOptions {
+        visible: false,
+        grab_mode: CursorGrabMode::Confined,
+        ..default()
+    }
+}
+
+/// Sent when no input has arrived for `IDLE_TIMEOUT_SECS`. UI plugins listen
+/// for this and reset themselves to their home state.
+#[derive(Message)]
+pub struct KioskIdleTimeout;
+
+pub struct KioskPlugin;
+
+impl Plugin for KioskPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(IdleTimer(Timer::from_seconds(IDLE_TIMEOUT_SECS, TimerMode::Once)))
+            .add_message::<KioskIdleTimeout>()
+            .add_systems(Update, track_idle);
+    }
+}
+
+#[derive(Resource)]
+struct IdleTimer(Timer);
+
+fn track_idle(
+    time: Res<Time>,
+    mut timer: ResMut<IdleTimer>,
+    mut keys: MessageReader<KeyboardInput>,
+    mut mouse: MessageReader<MouseButtonInput>,
+    mut gamepad: MessageReader<GamepadButtonChangedEvent>,
+    mut idle: MessageWriter<KioskIdleTimeout>,
+) {
+    let had_input = keys.read().count() + mouse.read().count() + gamepad.read().count() > 0;
+    if had_input {
+        timer.0.reset();
+        return;
+    }
+
+    timer.0.tick(time.delta());
+    if timer.0.just_finished() {
+        println!("kiosk: idle for {IDLE_TIMEOUT_SECS}s, returning to home screen");
+        idle.write(KioskIdleTimeout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kiosk_window_is_fullscreen_and_on_top() {
+        let window = kiosk_window(Window {
+            title: "test".into(),
+            ..default()
+        });
+        assert_eq!(window.mode, WindowMode::BorderlessFullscreen(MonitorSelection::Primary));
+        assert_eq!(window.window_level, WindowLevel::AlwaysOnTop);
+        assert!(!window.decorations);
+        assert_eq!(window.title, "test");
+    }
+
+    #[test]
+    fn kiosk_cursor_is_hidden() {
+        let cursor = kiosk_cursor_options();
+        assert!(!cursor.visible);
+        assert_eq!(cursor.grab_mode, CursorGrabMode::Confined);
+    }
+}
@@ -1,14 +1,21 @@
 //! Sean's Arcade — chat client.
 //!
-//! Usage: `cargo run -p arcade [-- --data-dir local/alice]`
+//! Usage: `cargo run -p arcade [-- --data-dir local/alice] [--kiosk] [--version]`
+//!
+//! `--version` prints the build, protocol versions and features, and exits.
+//! F1 or a gamepad's Select button shows the same report in the client,
+//! along with the GPU, audio host and gamepads in use.
 
 mod assets;
 mod chat;
 mod config;
 mod fraktur;
+mod kiosk;
 mod net;
+mod selfcheck;
 mod version;
 
+use arcade_ui::{AboutInfo, AboutPlugin};
 use bevy::log::LogPlugin;
 use bevy::prelude::*;
 use bevy::window::WindowResolution;
@@ -25,35 +32,72 @@ fn log_layer(_app: &mut App) -> Option<bevy::log::BoxedLayer> {
 }
 
 fn main() {
+    let build = protocol::describe_build(arcade_buildinfo::build_info!())
+        .with_backend("audio", cpal::default_host().id().name());
+    if std::env::args().any(|arg| arg == "--version") {
+        println!("{build}");
+        return;
+    }
+    let kiosk = kiosk::kiosk_from_args();
     version::cleanup_old_binary();
     let version_status = version::check_version();
     if let version::VersionStatus::UpdateAvailable { .. } = &version_status {
-        version::auto_update();
-        // If auto_update returns, it failed — continue with current version
+        // A supervised child must not replace itself: the watchdog would lose track of it.
+        if !kiosk::is_supervised() {
+            version::auto_update();
+            // If auto_update returns, it failed — continue with current version
+        }
+    }
+    if kiosk {
+        kiosk::supervise();
     }
 
     let data_dir = config::data_dir_from_args();
     let assets_dir = assets::sync_assets(&data_dir);
+    let self_check = selfcheck::run_checks(&data_dir, &assets_dir);
+    let checks_failed = self_check.has_failures();
 
-    App::new()
-        .add_plugins(DefaultPlugins
-            .set(WindowPlugin {
-                primary_window: Some(Window {
-                    title: format!("Sean's Arcade {}", &env!("GIT_COMMIT_HASH")[..8]),
-                    resolution: WindowResolution::new(600, 500),
-                    ..default()
-                }),
-                ..default()
-            })
+    let window = Window {
+        title: format!("Sean's Arcade {}", &env!("GIT_COMMIT_HASH")[..8]),
+        resolution: WindowResolution::new(600, 500),
+        ..default()
+    };
+    let window_plugin = if kiosk {
+        WindowPlugin {
+            primary_window: Some(kiosk::kiosk_window(window)),
+            primary_cursor_options: Some(kiosk::kiosk_cursor_options()),
+            ..default()
+        }
+    } else {
+        WindowPlugin {
+            primary_window: Some(window),
+            ..default()
+        }
+    };
+
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins
+            .set(window_plugin)
             .set(LogPlugin {
                 custom_layer: log_layer,
                 ..default()
             })
         )
-        .insert_resource(version_status)
+        .insert_resource(self_check)
+        .add_plugins(selfcheck::SelfCheckPlugin { kiosk })
+        .insert_resource(AboutInfo(build))
+        .add_plugins(AboutPlugin);
+    if checks_failed {
+        app.run();
+        return;
+    }
+    app.insert_resource(version_status)
         .insert_resource(assets::AssetsDir(assets_dir))
         .add_plugins(version::VersionPlugin)
         .add_plugins(net::NetPlugin)
-        .add_plugins(chat::ChatPlugin)
-        .run();
+        .add_plugins(chat::ChatPlugin);
+    if kiosk {
+        app.add_plugins(kiosk::KioskPlugin);
+    }
+    app.run();
 }
@@ -0,0 +1,469 @@
+//! Startup self-check screen.
+//!
+//! Before the chat UI starts, run a handful of quick checks (assets present,
+//! audio device, input source, settings readable, relay answering) and show
+//! the results with a remediation hint for anything that is off.
+//! A clean run dismisses itself after a moment; a warning waits for Enter or
+//! a gamepad's South or Start button, except on a kiosk, which may have no
+//! one at it to press them and goes on by itself after a while. If any check
+//! fails outright, the rest of the client never starts — it would only crash
+//! later with a less helpful message.
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::Stopwatch;
+use protocol::{ClientMessage, RelayMessage, deserialize, serialize};
+
+/// Files the client cannot start without, relative to the assets directory.
+pub const REQUIRED_ASSETS: &[&str] = &["fonts/NotoSansMath-Regular.ttf"];
+const AUTO_DISMISS_SECS: f32 = 1.5;
+/// How long a kiosk shows warnings before going on without anyone's say.
+const KIOSK_WARNING_SECS: f32 = 10.0;
+/// How long to wait for the relay to answer, across every try.
+const RELAY_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+/// Hellos to send before giving up, in case one is lost.
+const RELAY_PROBE_ATTEMPTS: u32 = 3;
+/// No relay is run with this secret, so the probe's Hello is turned away
+/// with `RejectSecret` rather than joining the chat.
+const RELAY_PROBE_SECRET: &str = "\0self-check";
+const CHECK_FONT_SIZE: f32 = 18.0;
+const PASS_COLOR: Color = Color::srgb(0.5, 1.0, 0.5);
+const WARN_COLOR: Color = Color::srgb(1.0, 1.0, 0.5);
+const FAIL_COLOR: Color = Color::srgb(1.0, 0.4, 0.4);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    /// The client can run, but something will not work as expected.
+    Warn,
+    /// The client cannot run.
+    Fail,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What the user can do about it. Empty when the check passed.
+    pub hint: String,
+}
+
+impl CheckResult {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Pass, detail: detail.into(), hint: String::new() }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Warn, detail: detail.into(), hint: hint.into() }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Fail, detail: detail.into(), hint: hint.into() }
+    }
+}
+
+/// Results of the checks that can run before the app starts.
+#[derive(Resource, Debug, Clone)]
+pub struct SelfCheckReport(pub Vec<CheckResult>);
+
+impl SelfCheckReport {
+    pub fn has_failures(&self) -> bool {
+        self.0.iter().any(|r| r.status == CheckStatus::Fail)
+    }
+}
+
+/// Run every check that does not need the Bevy app.
+pub fn run_checks(data_dir: &Path, assets_dir: &Path) -> SelfCheckReport {
+    let settings = check_settings(data_dir);
+    let relay = match crate::config::load_config(data_dir).relay_address.as_str() {
+        "" => None,
+        address => Some(check_relay(address)),
+    };
+    let mut results = vec![check_assets(assets_dir), check_audio(), settings];
+    results.extend(relay);
+    SelfCheckReport(results)
+}
+
+pub fn check_assets(assets_dir: &Path) -> CheckResult {
+    let missing: Vec<&str> = REQUIRED_ASSETS
+        .iter()
+        .copied()
+        .filter(|file| !assets_dir.join(file).is_file())
+        .collect();
+    if missing.is_empty() {
+        CheckResult::pass("assets", format!("{} required file(s) present", REQUIRED_ASSETS.len()))
+    } else {
+        CheckResult::fail(
+            "assets",
+            format!("missing {}", missing.join(", ")),
+            format!(
+                "connect to the internet and restart, or delete {} to force a re-download",
+                assets_dir.join("assets-manifest.json").display()
+            ),
+        )
+    }
+}
+
+pub fn check_settings(data_dir: &Path) -> CheckResult {
+    let path = data_dir.join("config.toml");
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return CheckResult::pass("settings", "no config yet (first launch)");
+        }
+        Err(e) => {
+            return CheckResult::fail(
+                "settings",
+                format!("cannot read {}: {e}", path.display()),
+                "check the file's permissions",
+            );
+        }
+    };
+    match toml::from_str::<crate::config::Config>(&contents) {
+        Ok(_) => CheckResult::pass("settings", "config.toml readable"),
+        Err(e) => CheckResult::warn(
+            "settings",
+            format!("config.toml is malformed: {}", e.message()),
+            format!("fix or delete {}; defaults will be used", path.display()),
+        ),
+    }
+}
+
+pub fn check_relay(address: &str) -> CheckResult {
+    probe_relay(address, RELAY_PROBE_TIMEOUT)
+}
+
+fn probe_relay(address: &str, timeout: Duration) -> CheckResult {
+    let relay = match address.to_socket_addrs().map(|mut addrs| addrs.next()) {
+        Ok(Some(relay)) => relay,
+        Ok(None) => {
+            return CheckResult::warn(
+                "relay",
+                format!("{address} resolved to no addresses"),
+                "check relay_address in config.toml",
+            );
+        }
+        Err(e) => {
+            return CheckResult::warn(
+                "relay",
+                format!("cannot resolve {address}: {e}"),
+                "check your network connection, or relay_address in config.toml",
+            );
+        }
+    };
+    match ask_relay(relay, timeout) {
+        Ok(true) => CheckResult::pass("relay", format!("{address} answers")),
+        Ok(false) => CheckResult::warn(
+            "relay",
+            format!("no answer from {address} within {}s", timeout.as_secs_f32()),
+            "check the relay is running, and relay_address in config.toml",
+        ),
+        Err(e) => CheckResult::warn(
+            "relay",
+            format!("cannot reach {address}: {e}"),
+            "check your network connection, or relay_address in config.toml",
+        ),
+    }
+}
+
+/// Say Hello and wait for any reply. The Hello carries a secret the relay
+/// refuses, so nothing is joined or registered; should it be let in all the
+/// same, it says goodbye at once.
+fn ask_relay(relay: SocketAddr, timeout: Duration) -> io::Result<bool> {
+    let local: SocketAddr = if relay.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.connect(relay)?;
+    socket.set_read_timeout(Some(timeout / RELAY_PROBE_ATTEMPTS))?;
+    let hello = serialize(&ClientMessage::Hello {
+        commit_hash: env!("GIT_COMMIT_HASH").to_string(),
+        relay_secret: RELAY_PROBE_SECRET.to_string(),
+        identity_name: String::new(),
+        identity_secret: String::new(),
+        new_identity_secret: None,
+    });
+    let mut buf = [0u8; 2048];
+    for _ in 0..RELAY_PROBE_ATTEMPTS {
+        socket.send(&hello)?;
+        let len = match socket.recv(&mut buf) {
+            Ok(len) => len,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        match deserialize::<RelayMessage>(&buf[..len]) {
+            Some(RelayMessage::Welcome { .. }) => {
+                socket.send(&serialize(&ClientMessage::Disconnect))?;
+                return Ok(true);
+            }
+            Some(_) => return Ok(true),
+            None => continue,
+        }
+    }
+    Ok(false)
+}
+
+fn check_audio() -> CheckResult {
+    use cpal::traits::{DeviceTrait, HostTrait};
+    match cpal::default_host().default_output_device() {
+        Some(device) => {
+            let name = device.name().unwrap_or_else(|_| "unnamed device".into());
+            CheckResult::pass("audio", format!("output device: {name}"))
+        }
+        None => CheckResult::warn(
+            "audio",
+            "no audio output device",
+            "plug in speakers or headphones; the game will run muted",
+        ),
+    }
+}
+
+/// Input sources are only known once Bevy has enumerated gamepads, so this
+/// check runs inside the app. Keyboards cannot be enumerated portably; a
+/// desktop is assumed to have one, and a keyboard is all the client needs.
+fn check_input(gamepad_count: usize) -> CheckResult {
+    if gamepad_count > 0 {
+        CheckResult::pass("input", format!("keyboard + {gamepad_count} gamepad(s)"))
+    } else {
+        CheckResult::pass("input", "keyboard only")
+    }
+}
+
+/// Shows the self-check screen. When the report contains a failure, this is
+/// the only plugin the client runs.
+pub struct SelfCheckPlugin {
+    /// Running as a kiosk: warnings go away by themselves.
+    pub kiosk: bool,
+}
+
+impl Plugin for SelfCheckPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(CheckScreen { shown_for: Stopwatch::new(), kiosk: self.kiosk })
+            .add_systems(Startup, spawn_check_screen)
+            .add_systems(Update, (add_input_check, dismiss_check_screen).chain());
+    }
+}
+
+#[derive(Component)]
+struct SelfCheckScreen;
+
+#[derive(Component)]
+struct SelfCheckList;
+
+#[derive(Resource)]
+struct CheckScreen {
+    shown_for: Stopwatch,
+    kiosk: bool,
+}
+
+#[derive(Resource)]
+struct InputChecked;
+
+fn spawn_check_screen(mut commands: Commands, report: Res<SelfCheckReport>) {
+    // On failure nothing else runs, so nothing else spawns a camera.
+    if report.has_failures() {
+        commands.spawn(Camera2d);
+    }
+
+    let title = if report.has_failures() { "Self-check failed" } else { "Self-check" };
+    commands
+        .spawn((
+            SelfCheckScreen,
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(20.0)),
+                row_gap: Val::Px(6.0),
+                ..default()
+            },
+            BackgroundColor(Color::BLACK),
+            GlobalZIndex(100),
+        ))
+        .with_children(|parent| {
+            parent.spawn((Text::new(title), TextFont::from_font_size(CHECK_FONT_SIZE * 1.5)));
+            parent
+                .spawn((
+                    SelfCheckList,
+                    Node { flex_direction: FlexDirection::Column, row_gap: Val::Px(6.0), ..default() },
+                ))
+                .with_children(|list| {
+                    for result in &report.0 {
+                        spawn_result_line(list, result);
+                    }
+                });
+        });
+}
+
+fn spawn_result_line(parent: &mut ChildSpawnerCommands, result: &CheckResult) {
+    let (label, color) = match result.status {
+        CheckStatus::Pass => ("PASS", PASS_COLOR),
+        CheckStatus::Warn => ("WARN", WARN_COLOR),
+        CheckStatus::Fail => ("FAIL", FAIL_COLOR),
+    };
+    let mut line = format!("{label}  {}: {}", result.name, result.detail);
+    if !result.hint.is_empty() {
+        line.push_str(&format!("\n      -> {}", result.hint));
+    }
+    parent.spawn((Text::new(line), TextFont::from_font_size(CHECK_FONT_SIZE), TextColor(color)));
+}
+
+fn add_input_check(
+    mut commands: Commands,
+    mut report: ResMut<SelfCheckReport>,
+    gamepads: Query<&Gamepad>,
+    list: Query<Entity, With<SelfCheckList>>,
+    checked: Option<Res<InputChecked>>,
+) {
+    if checked.is_some() {
+        return;
+    }
+    let Ok(list) = list.single() else {
+        return;
+    };
+    let result = check_input(gamepads.iter().count());
+    commands.entity(list).with_children(|parent| spawn_result_line(parent, &result));
+    report.0.push(result);
+    commands.insert_resource(InputChecked);
+}
+
+fn dismiss_check_screen(
+    mut commands: Commands,
+    report: Res<SelfCheckReport>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    time: Res<Time>,
+    mut check_screen: ResMut<CheckScreen>,
+    screen: Query<Entity, With<SelfCheckScreen>>,
+) {
+    let Ok(screen) = screen.single() else {
+        return;
+    };
+    if report.has_failures() {
+        return;
+    }
+    let shown_for = check_screen.shown_for.tick(time.delta()).elapsed_secs();
+    let all_passed = report.0.iter().all(|r| r.status == CheckStatus::Pass);
+    let wait = if all_passed {
+        Some(AUTO_DISMISS_SECS)
+    } else if check_screen.kiosk {
+        Some(KIOSK_WARNING_SECS)
+    } else {
+        None
+    };
+    let pressed = keyboard.just_pressed(KeyCode::Enter)
+        || gamepads.iter().any(|gamepad| {
+            gamepad.just_pressed(GamepadButton::South) || gamepad.just_pressed(GamepadButton::Start)
+        });
+    let dismissed = pressed || wait.is_some_and(|wait| shown_for >= wait);
+    if dismissed {
+        commands.entity(screen).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("arcade_selfcheck_{}_{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn missing_assets_fail() {
+        let dir = temp_dir("missing_assets");
+        let result = check_assets(&dir);
+        assert_eq!(result.status, CheckStatus::Fail);
+        assert!(result.detail.contains("NotoSansMath-Regular.ttf"));
+        assert!(!result.hint.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn present_assets_pass() {
+        let dir = temp_dir("present_assets");
+        for file in REQUIRED_ASSETS {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"font").unwrap();
+        }
+        assert_eq!(check_assets(&dir).status, CheckStatus::Pass);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_settings_pass_as_first_launch() {
+        let dir = temp_dir("missing_settings");
+        assert_eq!(check_settings(&dir).status, CheckStatus::Pass);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn malformed_settings_warn() {
+        let dir = temp_dir("malformed_settings");
+        std::fs::write(dir.join("config.toml"), "identity_name = [").unwrap();
+        let result = check_settings(&dir);
+        assert_eq!(result.status, CheckStatus::Warn);
+        assert!(result.hint.contains("config.toml"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn relay_that_answers_passes() {
+        // given a relay that turns every Hello away
+        let relay = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = relay.local_addr().unwrap().to_string();
+        let answering = std::thread::spawn(move || {
+            let mut buf = [0u8; 2048];
+            let (len, from) = relay.recv_from(&mut buf).unwrap();
+            let hello = deserialize::<ClientMessage>(&buf[..len]);
+            assert!(matches!(hello, Some(ClientMessage::Hello { .. })));
+            relay.send_to(&serialize(&RelayMessage::RejectSecret), from).unwrap();
+        });
+
+        // when it is probed
+        let result = probe_relay(&address, Duration::from_secs(5));
+
+        // then the check passes
+        answering.join().unwrap();
+        assert_eq!(result.status, CheckStatus::Pass);
+    }
+
+    #[test]
+    fn silent_relay_warns() {
+        // given an address that never answers
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = silent.local_addr().unwrap().to_string();
+
+        // when it is probed
+        let result = probe_relay(&address, Duration::from_millis(150));
+
+        // then the check warns
+        assert_eq!(result.status, CheckStatus::Warn);
+        assert!(result.hint.contains("relay is running"));
+    }
+
+    #[test]
+    fn garbage_relay_address_warns() {
+        assert_eq!(check_relay("not an address").status, CheckStatus::Warn);
+    }
+
+    #[test]
+    fn keyboard_alone_passes() {
+        assert_eq!(check_input(0).status, CheckStatus::Pass);
+        assert_eq!(check_input(1).status, CheckStatus::Pass);
+    }
+}
@@ -0,0 +1,320 @@
+//! Rollback-friendly sound scheduling.
+//!
+//! With rollback netcode a game re-simulates ticks it has already shown. If
+//! sounds are fired straight from simulation events, every resimulation
+//! replays them: a paddle hit heard once on the predicted tick is heard again
+//! each time that tick is re-run. Worse, a prediction that turns out wrong
+//! leaves behind sounds for events that never happened.
+//!
+//! [`SoundScheduler`] sits between the simulation and the audio output:
+//! - a sound is identified by `(tick, event id)`; requesting it again is a no-op
+//! - rolling back to a tick puts every sound from that tick onward on probation
+//! - sounds re-requested during resimulation are kept; the rest are cancelled
+//! - confirming a tick forgets everything up to it, since it can no longer roll back
+//!
+//! The scheduler is IO-free and returns [`AudioCommand`]s. [`RollbackAudioPlugin`]
+//! executes them with Bevy audio.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use bevy::prelude::*;
+
+pub type Tick = u32;
+
+/// Identifies a sound-producing event within a tick. Derive it from what
+/// happened (e.g. entity id and event kind), not from a counter, so that
+/// resimulating the same event produces the same id.
+pub type EventId = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SoundKey {
+    pub tick: Tick,
+    pub event: EventId,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioCommand<S> {
+    Play { key: SoundKey, sound: S },
+    Stop { key: SoundKey },
+}
+
+#[derive(Debug)]
+pub struct SoundScheduler<S> {
+    /// Sounds that have been played and may still be rolled back, by tick.
+    played: BTreeMap<Tick, HashSet<EventId>>,
+    /// Sounds awaiting re-request after a rollback.
+    on_probation: HashSet<SoundKey>,
+    /// Last tick that has been confirmed and can no longer be rolled back.
+    confirmed: Option<Tick>,
+    _sound: std::marker::PhantomData<S>,
+}
+
+impl<S> Default for SoundScheduler<S> {
+    fn default() -> Self {
+        Self {
+            played: BTreeMap::new(),
+            on_probation: HashSet::new(),
+            confirmed: None,
+            _sound: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<S> SoundScheduler<S> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The simulation produced a sound. Returns `Play` only the first time a
+    /// given `(tick, event)` is requested.
+    pub fn request(&mut self, tick: Tick, event: EventId, sound: S) -> Option<AudioCommand<S>> {
+        if self.confirmed.is_some_and(|confirmed| tick <= confirmed) {
+            return None;
+        }
+        let key = SoundKey { tick, event };
+        // Still playing from before the rollback; track it again so a later
+        // rollback over this tick can cancel it.
+        let kept = self.on_probation.remove(&key);
+        let newly_played = self.played.entry(tick).or_default().insert(event);
+        (newly_played && !kept).then_some(AudioCommand::Play { key, sound })
+    }
+
+    /// The simulation is about to re-run from `tick`. Sounds from `tick`
+    /// onward must be requested again or they will be cancelled.
+    pub fn rollback_to(&mut self, tick: Tick) {
+        for (&played_tick, events) in self.played.range_mut(tick..) {
+            for &event in events.iter() {
+                self.on_probation.insert(SoundKey { tick: played_tick, event });
+            }
+            events.clear();
+        }
+    }
+
+    /// Resimulation has caught up through `tick`. Any sound from a tick up to
+    /// and including it that was not requested again did not really happen.
+    pub fn resimulated_through(&mut self, tick: Tick) -> Vec<AudioCommand<S>> {
+        let mut cancelled: Vec<SoundKey> =
+            self.on_probation.iter().copied().filter(|key| key.tick <= tick).collect();
+        cancelled.sort_by_key(|key| (key.tick, key.event));
+        for key in &cancelled {
+            self.on_probation.remove(key);
+        }
+        cancelled.into_iter().map(|key| AudioCommand::Stop { key }).collect()
+    }
+
+    /// Inputs through `tick` are final; forget sounds that can no longer roll back.
+    pub fn confirm(&mut self, tick: Tick) {
+        self.confirmed = Some(self.confirmed.map_or(tick, |confirmed| confirmed.max(tick)));
+        self.played = self.played.split_off(&(tick + 1));
+        self.on_probation.retain(|key| key.tick > tick);
+    }
+}
+
+// ---- Bevy integration -------------------------------------------------------
+
+/// Resource games use to request sounds from simulation systems.
+#[derive(Resource, Default)]
+pub struct RollbackAudio {
+    scheduler: SoundScheduler<Handle<AudioSource>>,
+    pending: Vec<AudioCommand<Handle<AudioSource>>>,
+}
+
+impl RollbackAudio {
+    pub fn play(&mut self, tick: Tick, event: EventId, sound: Handle<AudioSource>) {
+        self.pending.extend(self.scheduler.request(tick, event, sound));
+    }
+
+    pub fn rollback_to(&mut self, tick: Tick) {
+        self.scheduler.rollback_to(tick);
+    }
+
+    pub fn resimulated_through(&mut self, tick: Tick) {
+        let stops = self.scheduler.resimulated_through(tick);
+        self.pending.extend(stops);
+    }
+
+    pub fn confirm(&mut self, tick: Tick) {
+        self.scheduler.confirm(tick);
+    }
+}
+
+/// Marks a playing sound with the key it was scheduled under, so it can be stopped.
+#[derive(Component)]
+pub struct ScheduledSound(pub SoundKey);
+
+pub struct RollbackAudioPlugin;
+
+impl Plugin for RollbackAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RollbackAudio>()
+            .add_systems(PostUpdate, execute_audio_commands);
+    }
+}
+
+fn execute_audio_commands(
+    mut commands: Commands,
+    mut audio: ResMut<RollbackAudio>,
+    playing: Query<(Entity, &ScheduledSound)>,
+) {
+    if audio.pending.is_empty() {
+        return;
+    }
+    let mut by_key: HashMap<SoundKey, Entity> =
+        playing.iter().map(|(entity, scheduled)| (scheduled.0, entity)).collect();
+
+    for command in audio.pending.drain(..) {
+        match command {
+            AudioCommand::Play { key, sound } => {
+                let entity = commands
+                    .spawn((AudioPlayer(sound), PlaybackSettings::DESPAWN, ScheduledSound(key)))
+                    .id();
+                by_key.insert(key, entity);
+            }
+            AudioCommand::Stop { key } => {
+                if let Some(entity) = by_key.remove(&key) {
+                    commands.entity(entity).despawn();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(tick: Tick, event: EventId) -> SoundKey {
+        SoundKey { tick, event }
+    }
+
+    #[test]
+    fn first_request_plays() {
+        // given an empty scheduler
+        let mut scheduler = SoundScheduler::new();
+
+        // when a sound is requested
+        let command = scheduler.request(5, 1, "hit");
+
+        // then it plays
+        assert_eq!(command, Some(AudioCommand::Play { key: key(5, 1), sound: "hit" }));
+    }
+
+    #[test]
+    fn duplicate_request_is_ignored() {
+        // given a sound that already played
+        let mut scheduler = SoundScheduler::new();
+        scheduler.request(5, 1, "hit");
+
+        // when the same tick and event are requested again
+        let command = scheduler.request(5, 1, "hit");
+
+        // then nothing new plays
+        assert_eq!(command, None);
+    }
+
+    #[test]
+    fn resimulated_sound_is_not_replayed() {
+        // given a sound played on tick 5, then a rollback to tick 3
+        let mut scheduler = SoundScheduler::new();
+        scheduler.request(5, 1, "hit");
+        scheduler.rollback_to(3);
+
+        // when resimulation produces the same sound
+        let command = scheduler.request(5, 1, "hit");
+        let stops = scheduler.resimulated_through(6);
+
+        // then it neither replays nor gets cancelled
+        assert_eq!(command, None);
+        assert!(stops.is_empty());
+    }
+
+    #[test]
+    fn sound_kept_through_one_rollback_is_tracked_through_the_next() {
+        // given a sound played on tick 5 and produced again after a rollback
+        let mut scheduler = SoundScheduler::new();
+        scheduler.request(5, 1, "hit");
+        scheduler.rollback_to(3);
+        scheduler.request(5, 1, "hit");
+
+        // when a second rollback over tick 5 produces it again
+        scheduler.rollback_to(3);
+        let command = scheduler.request(5, 1, "hit");
+
+        // then it still does not replay
+        assert_eq!(command, None);
+    }
+
+    #[test]
+    fn sound_kept_through_one_rollback_is_cancelled_by_the_next() {
+        // given a sound played on tick 5 and produced again after a rollback
+        let mut scheduler = SoundScheduler::new();
+        scheduler.request(5, 1, "hit");
+        scheduler.rollback_to(3);
+        scheduler.request(5, 1, "hit");
+
+        // when a second rollback over tick 5 does not produce it
+        scheduler.rollback_to(3);
+        let stops = scheduler.resimulated_through(6);
+
+        // then it is stopped
+        assert_eq!(stops, vec![AudioCommand::Stop { key: key(5, 1) }]);
+    }
+
+    #[test]
+    fn mispredicted_sound_is_cancelled() {
+        // given a sound played on tick 5, then a rollback to tick 3
+        let mut scheduler = SoundScheduler::new();
+        scheduler.request(5, 1, "hit");
+        scheduler.rollback_to(3);
+
+        // when resimulation does not produce it
+        let stops = scheduler.resimulated_through(6);
+
+        // then it is stopped
+        assert_eq!(stops, vec![AudioCommand::Stop { key: key(5, 1) }]);
+    }
+
+    #[test]
+    fn sounds_before_rollback_tick_are_untouched() {
+        // given sounds on ticks 2 and 5, then a rollback to tick 3
+        let mut scheduler = SoundScheduler::new();
+        scheduler.request(2, 1, "hit");
+        scheduler.request(5, 1, "hit");
+        scheduler.rollback_to(3);
+
+        // when resimulation catches up without re-requesting either
+        let stops = scheduler.resimulated_through(6);
+
+        // then only the tick 5 sound is cancelled, and tick 2 still dedups
+        assert_eq!(stops, vec![AudioCommand::Stop { key: key(5, 1) }]);
+        assert_eq!(scheduler.request(2, 1, "hit"), None);
+    }
+
+    #[test]
+    fn new_sound_during_resimulation_plays() {
+        // given a rollback with nothing previously played on tick 4
+        let mut scheduler = SoundScheduler::new();
+        scheduler.rollback_to(3);
+
+        // when resimulation produces a new sound
+        let command = scheduler.request(4, 7, "score");
+
+        // then it plays
+        assert_eq!(command, Some(AudioCommand::Play { key: key(4, 7), sound: "score" }));
+    }
+
+    #[test]
+    fn confirmed_ticks_are_forgotten() {
+        // given sounds on ticks 2 and 5
+        let mut scheduler = SoundScheduler::new();
+        scheduler.request(2, 1, "hit");
+        scheduler.request(5, 1, "hit");
+
+        // when tick 3 is confirmed
+        scheduler.confirm(3);
+
+        // then only tick 5 is still tracked, and late requests for confirmed ticks are ignored
+        assert_eq!(scheduler.played.keys().copied().collect::<Vec<_>>(), vec![5]);
+        assert_eq!(scheduler.request(2, 9, "hit"), None);
+    }
+}
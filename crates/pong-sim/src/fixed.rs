@@ -0,0 +1,216 @@
+//! Fixed-point numbers for the simulation.
+//!
+//! Both clients have to compute bit-identical states from the same inputs,
+//! and `f32` does not promise that across compilers, CPUs and optimisation
+//! levels. [`Fx`] is a Q16.16 number: an `i32` counting 1/65536ths, so every
+//! operation is integer arithmetic and comes out the same everywhere. The
+//! simulation keeps its state in these, and only the renderer converts back
+//! to `f32`.
+
+use std::fmt;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub};
+
+use serde::{Deserialize, Serialize};
+
+const FRACTION_BITS: u32 = 16;
+const ONE_RAW: i32 = 1 << FRACTION_BITS;
+
+/// A Q16.16 fixed-point number, from about -32768 to 32768 in steps of
+/// 1/65536.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Fx(i32);
+
+impl Fx {
+    pub const ZERO: Fx = Fx(0);
+    pub const ONE: Fx = Fx(ONE_RAW);
+
+    /// `x`, rounded toward zero to the nearest 1/65536. The same `f32` always
+    /// converts to the same `Fx`, so inputs that arrive as `f32` are safe to
+    /// bring into the simulation this way.
+    pub const fn from_f32(x: f32) -> Self {
+        Fx((x * ONE_RAW as f32) as i32)
+    }
+
+    /// For drawing; never feed the result back into the simulation.
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / ONE_RAW as f32
+    }
+
+    /// The raw count of 1/65536ths, for hashing.
+    pub const fn to_bits(self) -> i32 {
+        self.0
+    }
+
+    /// The exact value, for logs.
+    pub fn to_f64(self) -> f64 {
+        f64::from(self.0) / f64::from(ONE_RAW)
+    }
+
+    pub const fn abs(self) -> Self {
+        Fx(self.0.abs())
+    }
+
+    pub const fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    pub const fn is_positive(self) -> bool {
+        self.0 > 0
+    }
+}
+
+impl fmt::Debug for Fx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_f64())
+    }
+}
+
+impl Add for Fx {
+    type Output = Fx;
+
+    fn add(self, other: Fx) -> Fx {
+        Fx(self.0.wrapping_add(other.0))
+    }
+}
+
+impl AddAssign for Fx {
+    fn add_assign(&mut self, other: Fx) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Fx {
+    type Output = Fx;
+
+    fn sub(self, other: Fx) -> Fx {
+        Fx(self.0.wrapping_sub(other.0))
+    }
+}
+
+impl Neg for Fx {
+    type Output = Fx;
+
+    fn neg(self) -> Fx {
+        Fx(self.0.wrapping_neg())
+    }
+}
+
+impl Mul for Fx {
+    type Output = Fx;
+
+    fn mul(self, other: Fx) -> Fx {
+        Fx(((i64::from(self.0) * i64::from(other.0)) >> FRACTION_BITS) as i32)
+    }
+}
+
+impl Div for Fx {
+    type Output = Fx;
+
+    /// Panics on division by zero, like integer division.
+    fn div(self, other: Fx) -> Fx {
+        Fx(((i64::from(self.0) << FRACTION_BITS) / i64::from(other.0)) as i32)
+    }
+}
+
+impl Div<i32> for Fx {
+    type Output = Fx;
+
+    fn div(self, n: i32) -> Fx {
+        Fx(self.0 / n)
+    }
+}
+
+/// A 2D vector of [`Fx`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FxVec2 {
+    pub x: Fx,
+    pub y: Fx,
+}
+
+impl FxVec2 {
+    pub const ZERO: FxVec2 = FxVec2::new(Fx::ZERO, Fx::ZERO);
+
+    pub const fn new(x: Fx, y: Fx) -> Self {
+        Self { x, y }
+    }
+
+    /// The length, rounded down. Squares in 64 bits, so it does not overflow
+    /// for any vector that fits.
+    pub fn length(self) -> Fx {
+        let x = i64::from(self.x.0);
+        let y = i64::from(self.y.0);
+        Fx(((x * x + y * y) as u64).isqrt() as i32)
+    }
+
+    /// This direction, `length` long; zero stays zero.
+    pub fn with_length(self, length: Fx) -> Self {
+        let current = i64::from(self.length().0);
+        if current == 0 {
+            return Self::ZERO;
+        }
+        let scale = |c: Fx| Fx((i64::from(c.0) * i64::from(length.0) / current) as i32);
+        Self::new(scale(self.x), scale(self.y))
+    }
+
+    /// For drawing; never feed the result back into the simulation.
+    pub fn to_vec2(self) -> bevy::math::Vec2 {
+        bevy::math::Vec2::new(self.x.to_f32(), self.y.to_f32())
+    }
+}
+
+impl Add for FxVec2 {
+    type Output = FxVec2;
+
+    fn add(self, other: FxVec2) -> FxVec2 {
+        FxVec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl AddAssign for FxVec2 {
+    fn add_assign(&mut self, other: FxVec2) {
+        *self = *self + other;
+    }
+}
+
+impl Mul<Fx> for FxVec2 {
+    type Output = FxVec2;
+
+    fn mul(self, scale: Fx) -> FxVec2 {
+        FxVec2::new(self.x * scale, self.y * scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_is_exact_where_it_can_be() {
+        let half = Fx::ONE / 2;
+        assert_eq!(Fx::from_f32(3.0) * half, Fx::from_f32(1.5));
+        assert_eq!(Fx::from_f32(3.0) / Fx::from_f32(2.0), Fx::from_f32(1.5));
+        assert_eq!(Fx::from_f32(-7.0) + Fx::from_f32(2.0), Fx::from_f32(-5.0));
+        assert_eq!(-Fx::from_f32(0.25), Fx::from_f32(-0.25));
+        assert_eq!(Fx::ONE / 64, Fx::from_f32(0.015625));
+    }
+
+    #[test]
+    fn vectors_keep_their_direction_when_resized() {
+        // given a 3-4-5 triangle
+        let v = FxVec2::new(Fx::from_f32(3.0), Fx::from_f32(4.0));
+
+        // when measured and stretched
+        let longer = v.with_length(Fx::from_f32(10.0));
+
+        // then the length is exact and the direction kept
+        assert_eq!(v.length(), Fx::from_f32(5.0));
+        assert_eq!(longer, FxVec2::new(Fx::from_f32(6.0), Fx::from_f32(8.0)));
+        assert_eq!(FxVec2::ZERO.with_length(Fx::ONE), FxVec2::ZERO);
+    }
+
+    #[test]
+    fn speeds_past_the_square_root_of_the_range_do_not_overflow() {
+        let v = FxVec2::new(Fx::from_f32(3000.0), Fx::from_f32(4000.0));
+        assert_eq!(v.length(), Fx::from_f32(5000.0));
+    }
+}
@@ -0,0 +1,556 @@
+//! The Pong simulation shared by `examples/pong.rs`, `examples/neon_pong.rs`
+//! and net_pong.
+//!
+//! A match is a [`PongState`], for two players or for four in a square
+//! arena with a paddle on every side. [`PongState::step`] moves it on by one tick
+//! from the players' paddle inputs and returns what happened in that tick as
+//! [`SimEvent`]s, for effects and sound. The state is kept in [`fixed`] point,
+//! so the same inputs give bit-identical states on every machine, which a
+//! lockstep game needs and a local one gets for free. This crate provides:
+//! - the arena and gameplay constants every frontend draws with
+//! - [`PongState`] and its deterministic `step(inputs, dt)`, and
+//!   `step_traced` to follow a tick phase by phase
+//! - [`SimEvent`], the paddle hits, wall bounces and points of a tick
+//! - [`Paddle`] and [`Ball`], markers for the entities a frontend draws them as
+//!
+//! Frontends own everything else: input, drawing, saving and networking.
+
+pub mod fixed;
+
+use arcade_buildinfo::BuildInfo;
+use bevy::math::Vec2;
+use bevy::prelude::{Component, Message, Resource};
+use serde::{Deserialize, Serialize};
+
+use crate::fixed::{Fx, FxVec2};
+
+pub const ARENA_WIDTH: f32 = 800.0;
+pub const ARENA_HEIGHT: f32 = 500.0;
+pub const PADDLE_WIDTH: f32 = 15.0;
+pub const PADDLE_HEIGHT: f32 = 80.0;
+pub const PADDLE_X_OFFSET: f32 = 30.0;
+pub const PADDLE_SPEED: f32 = 400.0;
+pub const BALL_SIZE: f32 = 12.0;
+pub const BALL_INITIAL_SPEED: f32 = 300.0;
+pub const BALL_SPEED_INCREASE: f32 = 25.0;
+pub const PADDLE_HIT_ANGLE_FACTOR: f32 = 0.5;
+/// Players in the classic game.
+pub const PLAYER_COUNT: usize = 2;
+/// Players in the square arena, one on each side, and the most a match has.
+pub const MAX_PLAYERS: usize = 4;
+/// The square arena is as tall as the classic one.
+pub const SQUARE_ARENA_SIZE: f32 = ARENA_HEIGHT;
+
+// The same, in the fixed point the simulation runs in. Every one of them is
+// exact.
+const SIM_MAX_PADDLE_OFFSET: Fx = Fx::from_f32((ARENA_HEIGHT - PADDLE_HEIGHT) / 2.0);
+const SIM_MAX_BALL_Y: Fx = Fx::from_f32((ARENA_HEIGHT - BALL_SIZE) / 2.0);
+const SIM_SCORE_BOUNDARY_X: Fx = Fx::from_f32(ARENA_WIDTH / 2.0 + BALL_SIZE);
+const SIM_SQUARE_SCORE_BOUNDARY: Fx = Fx::from_f32(SQUARE_ARENA_SIZE / 2.0 + BALL_SIZE);
+const SIM_PADDLE_HALF_WIDTH: Fx = Fx::from_f32(PADDLE_WIDTH / 2.0);
+const SIM_PADDLE_HALF_HEIGHT: Fx = Fx::from_f32(PADDLE_HEIGHT / 2.0);
+const SIM_BALL_HALF_SIZE: Fx = Fx::from_f32(BALL_SIZE / 2.0);
+const SIM_PADDLE_SPEED: Fx = Fx::from_f32(PADDLE_SPEED);
+const SIM_BALL_INITIAL_SPEED: Fx = Fx::from_f32(BALL_INITIAL_SPEED);
+const SIM_BALL_SPEED_INCREASE: Fx = Fx::from_f32(BALL_SPEED_INCREASE);
+const SIM_PADDLE_HIT_ANGLE_FACTOR: Fx = Fx::from_f32(PADDLE_HIT_ANGLE_FACTOR);
+
+/// A player's paddle, as a frontend draws it.
+#[derive(Component)]
+pub struct Paddle {
+    pub player_index: usize,
+}
+
+/// The ball, as a frontend draws it.
+#[derive(Component)]
+pub struct Ball;
+
+/// Something that happened during a tick, for effects and sound. Positions
+/// are where the ball was when it happened.
+#[derive(Message, Debug, Clone, Copy, PartialEq)]
+pub enum SimEvent {
+    PaddleHit { position: Vec2, player_index: usize },
+    WallBounce { position: Vec2 },
+    Score { player_index: usize },
+}
+
+/// Everything a tick can change.
+///
+/// Players 0 and 1 play from the left and right, and in the square arena
+/// players 2 and 3 from the bottom and top. Slots past the match's players
+/// stay at zero.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PongState {
+    /// [`PLAYER_COUNT`], or [`MAX_PLAYERS`] in the square arena.
+    pub players: u8,
+    /// How far each paddle is from the middle of its side: up for the left
+    /// and right paddles, right for the bottom and top ones.
+    pub paddle: [Fx; MAX_PLAYERS],
+    pub ball: FxVec2,
+    pub ball_velocity: FxVec2,
+    pub score: [u32; MAX_PLAYERS],
+    /// Points scored so far, which decides which way the next serve drifts.
+    pub resets: u32,
+    /// Who hit the ball last since it was served. In the square arena, they
+    /// score when it goes out past someone else.
+    pub last_hit: Option<u8>,
+}
+
+impl Default for PongState {
+    /// The classic two-player game.
+    fn default() -> Self {
+        Self::new(PLAYER_COUNT)
+    }
+}
+
+impl PongState {
+    /// Paddles centred and the ball served toward the right, for
+    /// [`PLAYER_COUNT`] or [`MAX_PLAYERS`] players.
+    pub fn new(players: usize) -> Self {
+        assert!(
+            players == PLAYER_COUNT || players == MAX_PLAYERS,
+            "Pong is for {PLAYER_COUNT} or {MAX_PLAYERS} players, not {players}"
+        );
+        Self {
+            players: players as u8,
+            paddle: [Fx::ZERO; MAX_PLAYERS],
+            ball: FxVec2::ZERO,
+            ball_velocity: serve_velocity(Fx::ONE, Fx::ONE),
+            score: [0; MAX_PLAYERS],
+            resets: 0,
+            last_hit: None,
+        }
+    }
+
+    pub fn players(&self) -> usize {
+        usize::from(self.players)
+    }
+
+    /// Whether this is the four-player game, where every side is a goal.
+    pub fn is_square(&self) -> bool {
+        self.players() == MAX_PLAYERS
+    }
+
+    /// The arena's width and height.
+    pub fn arena_size(&self) -> Vec2 {
+        if self.is_square() {
+            Vec2::splat(SQUARE_ARENA_SIZE)
+        } else {
+            Vec2::new(ARENA_WIDTH, ARENA_HEIGHT)
+        }
+    }
+
+    /// Simulate `dt` seconds with each player pushing their paddle at
+    /// `inputs[player_index]`, from -1 (down, or left) to 1 (up, or right).
+    /// Players with no input stand still.
+    pub fn step(&mut self, inputs: &[f32], dt: Fx) -> Vec<SimEvent> {
+        self.step_traced(inputs, dt, |_, _| {})
+    }
+
+    /// [`step`](Self::step), handing `trace` the name of each phase of the
+    /// tick and the state it left, to find where two copies of a match that
+    /// should agree part ways.
+    pub fn step_traced(
+        &mut self,
+        inputs: &[f32],
+        dt: Fx,
+        mut trace: impl FnMut(&'static str, &PongState),
+    ) -> Vec<SimEvent> {
+        let mut events = Vec::new();
+        self.move_paddles(inputs, dt);
+        trace("move_paddles", self);
+        self.ball += self.ball_velocity * dt;
+        trace("move_ball", self);
+        // The square arena has goals where the walls would be.
+        if !self.is_square() {
+            self.ball_wall_bounce(&mut events);
+            trace("ball_wall_bounce", self);
+        }
+        self.ball_paddle_bounce(inputs, &mut events);
+        trace("ball_paddle_bounce", self);
+        if self.is_square() {
+            self.check_square_scoring(&mut events);
+            trace("check_square_scoring", self);
+        } else {
+            self.check_scoring(&mut events);
+            trace("check_scoring", self);
+        }
+        events
+    }
+
+    /// Where to draw a player's paddle.
+    pub fn paddle_position(&self, player_index: usize) -> Vec2 {
+        let across = self.paddle_across(player_index);
+        let along = self.paddle[player_index].to_f32();
+        if paddle_is_horizontal(player_index) {
+            Vec2::new(along, across)
+        } else {
+            Vec2::new(across, along)
+        }
+    }
+
+    /// Where to draw the ball.
+    pub fn ball_position(&self) -> Vec2 {
+        self.ball.to_vec2()
+    }
+
+    /// Whether the ball is heading for a player's side of the arena.
+    pub fn ball_approaching(&self, player_index: usize) -> bool {
+        let velocity = in_paddle_frame(player_index, self.ball_velocity);
+        velocity.x.is_negative() == player_index.is_multiple_of(2)
+    }
+
+    /// How far the ball is from the middle of a player's side, measured the
+    /// way their paddle moves.
+    pub fn ball_along_side(&self, player_index: usize) -> f32 {
+        in_paddle_frame(player_index, self.ball).y.to_f32()
+    }
+
+    /// How far a player's paddle is from the middle of the arena, toward its
+    /// own side: negative for the left and bottom ones.
+    fn paddle_across(&self, player_index: usize) -> f32 {
+        let half = self.arena_size() / 2.0;
+        let distance = if paddle_is_horizontal(player_index) {
+            half.y
+        } else {
+            half.x
+        } - PADDLE_X_OFFSET;
+        if player_index.is_multiple_of(2) {
+            -distance
+        } else {
+            distance
+        }
+    }
+
+    fn move_paddles(&mut self, inputs: &[f32], dt: Fx) {
+        let players = self.players();
+        for (player_index, offset) in self.paddle.iter_mut().enumerate().take(players) {
+            let movement = sim_movement(inputs, player_index);
+            *offset = (*offset + movement * SIM_PADDLE_SPEED * dt)
+                .clamp(-SIM_MAX_PADDLE_OFFSET, SIM_MAX_PADDLE_OFFSET);
+        }
+    }
+
+    fn ball_wall_bounce(&mut self, events: &mut Vec<SimEvent>) {
+        let y = self.ball.y;
+        if (y >= SIM_MAX_BALL_Y && self.ball_velocity.y.is_positive())
+            || (y <= -SIM_MAX_BALL_Y && self.ball_velocity.y.is_negative())
+        {
+            self.ball_velocity.y = -self.ball_velocity.y;
+            events.push(SimEvent::WallBounce {
+                position: self.ball.to_vec2(),
+            });
+        }
+    }
+
+    fn ball_paddle_bounce(&mut self, inputs: &[f32], events: &mut Vec<SimEvent>) {
+        // A ball can touch two paddles in one tick only in degenerate cases;
+        // going in player order keeps even those the same everywhere.
+        for player_index in 0..self.players() {
+            // x across to the paddle, y along it.
+            let ball = in_paddle_frame(player_index, self.ball);
+            let mut velocity = in_paddle_frame(player_index, self.ball_velocity);
+            let paddle_x = Fx::from_f32(self.paddle_across(player_index));
+            let overlap_x = (ball.x - paddle_x).abs() < SIM_PADDLE_HALF_WIDTH + SIM_BALL_HALF_SIZE;
+            let overlap_y = (ball.y - self.paddle[player_index]).abs()
+                < SIM_PADDLE_HALF_HEIGHT + SIM_BALL_HALF_SIZE;
+
+            if !overlap_x || !overlap_y {
+                continue;
+            }
+
+            let ball_moving_toward_paddle = if paddle_x.is_negative() {
+                velocity.x.is_negative()
+            } else {
+                velocity.x.is_positive()
+            };
+
+            if !ball_moving_toward_paddle {
+                continue;
+            }
+
+            velocity.x = -velocity.x;
+
+            let paddle_movement = sim_movement(inputs, player_index);
+            velocity.y += paddle_movement * SIM_PADDLE_SPEED * SIM_PADDLE_HIT_ANGLE_FACTOR;
+
+            let new_speed = velocity.length() + SIM_BALL_SPEED_INCREASE;
+            self.ball_velocity = in_paddle_frame(player_index, velocity.with_length(new_speed));
+            self.last_hit = Some(player_index as u8);
+
+            events.push(SimEvent::PaddleHit {
+                position: self.ball.to_vec2(),
+                player_index,
+            });
+        }
+    }
+
+    fn check_scoring(&mut self, events: &mut Vec<SimEvent>) {
+        let x = self.ball.x;
+        let scorer = if x < -SIM_SCORE_BOUNDARY_X {
+            1
+        } else if x > SIM_SCORE_BOUNDARY_X {
+            0
+        } else {
+            return;
+        };
+
+        self.score[scorer] += 1;
+        self.resets += 1;
+        self.last_hit = None;
+        events.push(SimEvent::Score {
+            player_index: scorer,
+        });
+
+        self.ball = FxVec2::ZERO;
+
+        let direction_x = if scorer == 0 { -Fx::ONE } else { Fx::ONE };
+        let direction_y = if self.resets.is_multiple_of(2) {
+            Fx::ONE
+        } else {
+            -Fx::ONE
+        };
+        self.ball_velocity = serve_velocity(direction_x, direction_y);
+    }
+
+    /// In the square arena the ball going out past a paddle scores for
+    /// whoever hit it last, unless that is the player who missed it or
+    /// nobody has hit it since the serve. Either way it is served again
+    /// toward the player who missed it.
+    fn check_square_scoring(&mut self, events: &mut Vec<SimEvent>) {
+        let (x, y) = (self.ball.x, self.ball.y);
+        let missed_by = if x < -SIM_SQUARE_SCORE_BOUNDARY {
+            0
+        } else if x > SIM_SQUARE_SCORE_BOUNDARY {
+            1
+        } else if y < -SIM_SQUARE_SCORE_BOUNDARY {
+            2
+        } else if y > SIM_SQUARE_SCORE_BOUNDARY {
+            3
+        } else {
+            return;
+        };
+
+        let scorer = self
+            .last_hit
+            .map(usize::from)
+            .filter(|&hitter| hitter != missed_by);
+        if let Some(scorer) = scorer {
+            self.score[scorer] += 1;
+            events.push(SimEvent::Score {
+                player_index: scorer,
+            });
+        }
+        self.resets += 1;
+        self.last_hit = None;
+
+        self.ball = FxVec2::ZERO;
+
+        let toward = if missed_by.is_multiple_of(2) {
+            -Fx::ONE
+        } else {
+            Fx::ONE
+        };
+        let drift = if self.resets.is_multiple_of(2) {
+            Fx::ONE
+        } else {
+            -Fx::ONE
+        };
+        self.ball_velocity = in_paddle_frame(missed_by, serve_velocity(toward, drift));
+    }
+}
+
+/// Whether a player's paddle lies along the bottom or top of the square
+/// arena, moving left and right, rather than up and down at a side.
+pub fn paddle_is_horizontal(player_index: usize) -> bool {
+    player_index >= PLAYER_COUNT
+}
+
+/// How big to draw a player's paddle.
+pub fn paddle_size(player_index: usize) -> Vec2 {
+    if paddle_is_horizontal(player_index) {
+        Vec2::new(PADDLE_HEIGHT, PADDLE_WIDTH)
+    } else {
+        Vec2::new(PADDLE_WIDTH, PADDLE_HEIGHT)
+    }
+}
+
+/// `v` with x across to a player's paddle and y along it: as it is for the
+/// left and right paddles, swapped for the bottom and top ones. Swapping
+/// twice gives `v` back.
+fn in_paddle_frame(player_index: usize, v: FxVec2) -> FxVec2 {
+    if paddle_is_horizontal(player_index) {
+        FxVec2::new(v.y, v.x)
+    } else {
+        v
+    }
+}
+
+/// Where a player's paddle sits across the two-player arena; paddles only
+/// move up and down there.
+pub fn paddle_x(player_index: usize) -> f32 {
+    let x = ARENA_WIDTH / 2.0 - PADDLE_X_OFFSET;
+    if player_index == 0 { -x } else { x }
+}
+
+/// A player's paddle direction as the simulation takes it. Converting the
+/// same `f32` always gives the same `Fx`, and the clamp keeps a bad input
+/// from moving a paddle faster than a good one.
+fn sim_movement(inputs: &[f32], player_index: usize) -> Fx {
+    let input = inputs.get(player_index).copied().unwrap_or(0.0);
+    Fx::from_f32(input).clamp(-Fx::ONE, Fx::ONE)
+}
+
+/// A ball served toward `direction_x`, drifting half as fast toward
+/// `direction_y`.
+fn serve_velocity(direction_x: Fx, direction_y: Fx) -> FxVec2 {
+    FxVec2::new(direction_x, direction_y / 2).with_length(SIM_BALL_INITIAL_SPEED)
+}
+
+/// This crate's part of a game's version report.
+pub fn describe_build(info: BuildInfo) -> BuildInfo {
+    info.with_crate("pong-sim", env!("CARGO_PKG_VERSION"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: Fx = Fx::from_f32(1.0 / 64.0);
+
+    fn play(state: &mut PongState, ticks: usize, inputs: [f32; PLAYER_COUNT]) -> Vec<SimEvent> {
+        (0..ticks).flat_map(|_| state.step(&inputs, DT)).collect()
+    }
+
+    #[test]
+    fn same_inputs_give_the_same_match() {
+        // given two matches from the start
+        let mut a = PongState::default();
+        let mut b = PongState::default();
+
+        // when both play the same long run of inputs
+        let events_a = play(&mut a, 2000, [0.7, -0.3]);
+        let events_b = play(&mut b, 2000, [0.7, -0.3]);
+
+        // then they end bit for bit the same, having seen the same things
+        assert_eq!(a, b);
+        assert_eq!(events_a, events_b);
+    }
+
+    #[test]
+    fn paddles_stop_at_the_walls() {
+        // given a match
+        let mut state = PongState::default();
+
+        // when one player holds up and the other holds down far past the wall
+        play(&mut state, 200, [1.0, -1.0]);
+
+        // then each paddle is flush with its wall
+        assert_eq!(
+            state.paddle,
+            [
+                SIM_MAX_PADDLE_OFFSET,
+                -SIM_MAX_PADDLE_OFFSET,
+                Fx::ZERO,
+                Fx::ZERO
+            ]
+        );
+    }
+
+    #[test]
+    fn a_missed_ball_scores_and_serves_toward_the_scorer() {
+        // given a ball about to leave past the right paddle
+        let mut state = PongState {
+            paddle: [Fx::ZERO, SIM_MAX_PADDLE_OFFSET, Fx::ZERO, Fx::ZERO],
+            ball: FxVec2::new(SIM_SCORE_BOUNDARY_X, Fx::ZERO),
+            ..PongState::default()
+        };
+
+        // when the next tick moves it out
+        let events = state.step(&[0.0, 0.0], DT);
+
+        // then the left player scores and the ball restarts from the middle
+        assert_eq!(events, vec![SimEvent::Score { player_index: 0 }]);
+        assert_eq!(state.score, [1, 0, 0, 0]);
+        assert_eq!(state.resets, 1);
+        assert_eq!(state.ball, FxVec2::ZERO);
+        assert!(state.ball_velocity.x.is_negative());
+    }
+
+    #[test]
+    fn a_paddle_sends_the_ball_back_faster() {
+        // given a ball just short of the right paddle, heading into it
+        let mut state = PongState {
+            ball: FxVec2::new(Fx::from_f32(paddle_x(1)) - SIM_PADDLE_HALF_WIDTH, Fx::ZERO),
+            ..PongState::default()
+        };
+        let speed = state.ball_velocity.length();
+
+        // when the next tick brings them together
+        let events = state.step(&[0.0, 0.0], DT);
+
+        // then the ball heads back left, faster, and the hit is reported
+        let hit = SimEvent::PaddleHit {
+            position: state.ball_position(),
+            player_index: 1,
+        };
+        assert_eq!(events, vec![hit]);
+        assert!(state.ball_velocity.x.is_negative());
+        assert!(state.ball_velocity.length() > speed);
+    }
+
+    #[test]
+    fn a_traced_step_ends_where_a_plain_one_does() {
+        // given two copies of a match in play
+        let mut plain = PongState::default();
+        play(&mut plain, 100, [0.5, -0.5]);
+        let mut traced = plain;
+
+        // when one steps plainly and the other with a trace
+        let events = plain.step(&[1.0, 0.0], DT);
+        let mut phases = Vec::new();
+        let traced_events = traced.step_traced(&[1.0, 0.0], DT, |phase, state| {
+            phases.push((phase, *state));
+        });
+
+        // then they agree, and the trace ends on the finished state
+        assert_eq!(traced, plain);
+        assert_eq!(traced_events, events);
+        let names: Vec<_> = phases.iter().map(|(phase, _)| *phase).collect();
+        assert_eq!(
+            names,
+            [
+                "move_paddles",
+                "move_ball",
+                "ball_wall_bounce",
+                "ball_paddle_bounce",
+                "check_scoring"
+            ]
+        );
+        assert_eq!(phases.last().map(|(_, state)| *state), Some(plain));
+    }
+
+    #[test]
+    fn in_the_square_arena_a_miss_scores_for_the_last_hitter() {
+        // given a four-player match where the left player hit the ball last,
+        // and it is about to leave past the bottom paddle
+        let mut state = PongState {
+            ball: FxVec2::new(Fx::ZERO, -SIM_SQUARE_SCORE_BOUNDARY),
+            ball_velocity: FxVec2::new(Fx::ZERO, -SIM_BALL_INITIAL_SPEED),
+            last_hit: Some(0),
+            ..PongState::new(MAX_PLAYERS)
+        };
+
+        // when the next tick moves it out
+        let events = state.step(&[0.0; MAX_PLAYERS], DT);
+
+        // then the left player scores, and the ball is served from the
+        // middle toward the bottom player
+        assert_eq!(events, vec![SimEvent::Score { player_index: 0 }]);
+        assert_eq!(state.score, [1, 0, 0, 0]);
+        assert_eq!(state.last_hit, None);
+        assert_eq!(state.ball, FxVec2::ZERO);
+        assert!(state.ball_velocity.y.is_negative());
+        assert!(state.ball_approaching(2));
+    }
+}
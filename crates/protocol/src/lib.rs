@@ -4,6 +4,7 @@
 //! Messages are serialized with `postcard` (compact, serde-based, no framing
 //! needed since UDP is message-oriented).
 
+use arcade_buildinfo::BuildInfo;
 use serde::{Deserialize, Serialize};
 
 // Re-export the derive macro so downstream crates use `protocol::HasSchema`.
@@ -170,6 +171,10 @@ pub enum AdminCommand {
 // decode decisions, but helps operators understand format differences across
 // versions without needing the source code.
 
+/// Bumped when the payload format changes in a way the fingerprint alone does
+/// not explain.
+pub const SCHEMA_VERSION: u32 = 1;
+
 /// Schema for the postcard-serialized payloads. Written to
 /// `admin/versions/<hash>/schema.json` on relay startup.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -208,7 +213,7 @@ pub fn current_payload_schema(commit_hash: &str) -> PayloadSchema {
     ];
     let fingerprint = schema_fingerprint(&types);
     PayloadSchema {
-        schema_version: 1,
+        schema_version: SCHEMA_VERSION,
         commit_hash: commit_hash.to_string(),
         types,
         fingerprint,
@@ -217,6 +222,14 @@ pub fn current_payload_schema(commit_hash: &str) -> PayloadSchema {
 
 /// FNV-1a hash of the canonical JSON representation of the schema types.
 /// Deterministic and stable across platforms without external dependencies.
+/// This crate's part of a `--version` report: the schema version and the
+/// fingerprint of the message types compiled in.
+pub fn describe_build(info: BuildInfo) -> BuildInfo {
+    let fingerprint = current_payload_schema(arcade_buildinfo::BUILD_HASH).fingerprint;
+    info.with_crate("protocol", env!("CARGO_PKG_VERSION"))
+        .with_protocol("chat", format!("{SCHEMA_VERSION} ({fingerprint})"))
+}
+
 fn schema_fingerprint(types: &[SchemaType]) -> String {
     let json = serde_json::to_string(types).expect("schema serialization should not fail");
     let mut hash: u64 = 0xcbf29ce484222325;
@@ -6,6 +6,8 @@
 //! with the same commit hash. Payloads are treated as opaque bytes.
 //!
 //! Usage: `RELAY_SECRET=test cargo run -p relay [-- --data-dir local/relay --bind 0.0.0.0:7700]`
+//!
+//! `--version` prints the build, protocol versions and features, and exits.
 
 mod identity;
 mod logging;
@@ -849,7 +851,12 @@ mod tests {
 }
 
 fn main() {
-    println!("relay {}", env!("GIT_COMMIT_HASH"));
+    let build = protocol::describe_build(arcade_buildinfo::build_info!());
+    if std::env::args().any(|arg| arg == "--version") {
+        println!("{build}");
+        return;
+    }
+    println!("{}", build.summary());
 
     let bind_addr = bind_address_from_args();
     let socket = UdpSocket::bind(&bind_addr)
@@ -0,0 +1,23 @@
+//! Retro sound effects built from code instead of shipped as files.
+//!
+//! A [`Tone`] is one note: an oscillator [`Waveform`], a pitch that may
+//! [`Sweep`] from one frequency to another, and an [`Adsr`] envelope. It
+//! renders to a [`Sound`], plain mono samples that can be strung together,
+//! mixed, written out as a WAV file, or (with the `bevy` feature) handed to
+//! Bevy's audio as an `AudioSource` while the game runs.
+//! - [`tone`], oscillators, sweeps, envelopes and the [`Tone`] that holds them
+//! - [`sound`], rendered samples: sequencing, mixing and WAV export
+//! - [`presets`], the pong effects `generate_sounds` used to write by hand
+//!
+//! Everything renders the same samples on every run; even the noise is
+//! drawn from a fixed seed.
+
+pub mod presets;
+pub mod sound;
+pub mod tone;
+
+pub use sound::Sound;
+pub use tone::{Adsr, Sweep, Tone, Waveform};
+
+/// CD quality, and what `generate_sounds` always wrote.
+pub const SAMPLE_RATE: u32 = 44_100;
@@ -0,0 +1,45 @@
+//! Ready-made effects for the pong games, at [`SAMPLE_RATE`].
+
+use crate::{Adsr, SAMPLE_RATE, Sound, Tone, Waveform};
+
+/// Short high-pitched ping for a paddle hit: 880 Hz, 80 ms, fading out.
+pub fn hit() -> Sound {
+    Tone::new(Waveform::Sine, 880.0, 0.08)
+        .envelope(Adsr::pluck(0.08))
+        .render(SAMPLE_RATE)
+}
+
+/// Short mid-pitched blip for a wall bounce: 440 Hz, 60 ms, fading out.
+pub fn bounce() -> Sound {
+    Tone::new(Waveform::Sine, 440.0, 0.06)
+        .envelope(Adsr::pluck(0.06))
+        .render(SAMPLE_RATE)
+}
+
+/// Descending two-tone fanfare for a point: 660 Hz then 440 Hz.
+pub fn score() -> Sound {
+    let high = Tone::new(Waveform::Sine, 660.0, 0.1).envelope(Adsr {
+        release: 0.0,
+        ..Adsr::FLAT
+    });
+    let low = Tone::new(Waveform::Sine, 440.0, 0.15).envelope(Adsr::pluck(0.15));
+    high.render(SAMPLE_RATE).then(&low.render(SAMPLE_RATE))
+}
+
+/// Falling square-wave zap with a burst of noise under it, for a miss.
+pub fn miss() -> Sound {
+    let zap = Tone::new(Waveform::SQUARE, 600.0, 0.25)
+        .sweep_to(120.0)
+        .envelope(Adsr {
+            attack: 0.005,
+            decay: 0.1,
+            sustain: 0.6,
+            release: 0.1,
+        })
+        .volume(0.3);
+    let crackle = Tone::new(Waveform::Noise, 4000.0, 0.08)
+        .envelope(Adsr::pluck(0.08))
+        .volume(0.2);
+    zap.render(SAMPLE_RATE)
+        .mix(1.0, &crackle.render(SAMPLE_RATE), 1.0, 0.0)
+}
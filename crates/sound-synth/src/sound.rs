@@ -0,0 +1,154 @@
+//! Rendered samples, ready to combine, save or play.
+
+use std::io;
+use std::path::Path;
+
+/// Mono samples in [-1, 1] at `sample_rate` samples a second.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sound {
+    sample_rate: u32,
+    samples: Vec<f32>,
+}
+
+impl Sound {
+    pub fn new(sample_rate: u32, samples: Vec<f32>) -> Self {
+        Self {
+            sample_rate,
+            samples,
+        }
+    }
+
+    /// `seconds` of nothing, to leave a gap with [`then`](Sound::then).
+    pub fn silence(sample_rate: u32, seconds: f32) -> Self {
+        Self::new(
+            sample_rate,
+            vec![0.0; (seconds * sample_rate as f32) as usize],
+        )
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    /// In seconds.
+    pub fn duration(&self) -> f32 {
+        self.samples.len() as f32 / self.sample_rate as f32
+    }
+
+    /// This sound followed by `next`.
+    pub fn then(mut self, next: &Sound) -> Self {
+        assert_eq!(self.sample_rate, next.sample_rate, "sample rates differ");
+        self.samples.extend_from_slice(&next.samples);
+        self
+    }
+
+    /// `other` played over this sound, starting `offset` seconds in, each
+    /// scaled by its gain. The result is as long as whichever ends later,
+    /// and clipped to [-1, 1].
+    pub fn mix(mut self, gain: f32, other: &Sound, other_gain: f32, offset: f32) -> Self {
+        assert_eq!(self.sample_rate, other.sample_rate, "sample rates differ");
+        let start = (offset * self.sample_rate as f32) as usize;
+        let length = self.samples.len().max(start + other.samples.len());
+        self.samples.resize(length, 0.0);
+        for sample in &mut self.samples {
+            *sample *= gain;
+        }
+        for (sample, added) in self.samples[start..].iter_mut().zip(&other.samples) {
+            *sample += added * other_gain;
+        }
+        for sample in &mut self.samples {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+        self
+    }
+
+    /// A 16-bit PCM WAV file of the sound.
+    pub fn to_wav(&self) -> Vec<u8> {
+        let channels = 1u16;
+        let bits_per_sample = 16u16;
+        let byte_rate = self.sample_rate * u32::from(channels) * u32::from(bits_per_sample) / 8;
+        let block_align = channels * bits_per_sample / 8;
+        let data_size = (self.samples.len() * 2) as u32;
+        let file_size = 36 + data_size;
+
+        let mut bytes = Vec::with_capacity(44 + data_size as usize);
+
+        // RIFF header
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&file_size.to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+
+        // fmt chunk
+        bytes.extend_from_slice(b"fmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes()); // chunk size
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM format
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&self.sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&byte_rate.to_le_bytes());
+        bytes.extend_from_slice(&block_align.to_le_bytes());
+        bytes.extend_from_slice(&bits_per_sample.to_le_bytes());
+
+        // data chunk
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_size.to_le_bytes());
+        for sample in &self.samples {
+            let pcm = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+            bytes.extend_from_slice(&pcm.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn write_wav(&self, path: &Path) -> io::Result<()> {
+        std::fs::write(path, self.to_wav())
+    }
+
+    /// The sound as something Bevy can play. Add it to
+    /// `Assets<AudioSource>` once and play the handle as often as needed.
+    #[cfg(feature = "bevy")]
+    pub fn audio_source(&self) -> bevy::audio::AudioSource {
+        bevy::audio::AudioSource {
+            bytes: self.to_wav().into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_wav_file_holds_every_sample_after_its_header() {
+        // given a tenth of a second of sound
+        let sound = Sound::new(1000, vec![0.5; 100]);
+
+        // when it is written as WAV
+        let wav = sound.to_wav();
+
+        // then the header says how much follows, and it all follows
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(wav.len(), 44 + 200);
+        assert_eq!(u32::from_le_bytes(wav[40..44].try_into().unwrap()), 200);
+        assert_eq!(i16::from_le_bytes([wav[44], wav[45]]), i16::MAX / 2);
+    }
+
+    #[test]
+    fn mixing_lengthens_and_clips() {
+        // given a short loud sound and another that starts as it ends
+        let first = Sound::new(10, vec![0.8; 10]);
+        let second = Sound::new(10, vec![0.8; 10]);
+
+        // when the second is mixed in half a second into the first
+        let mixed = first.mix(1.0, &second, 1.0, 0.5);
+
+        // then they overlap in the middle, clipped, and the second runs on
+        assert_eq!(mixed.samples().len(), 15);
+        assert_eq!(mixed.samples()[0], 0.8);
+        assert_eq!(mixed.samples()[7], 1.0);
+        assert_eq!(mixed.samples()[14], 0.8);
+    }
+}
@@ -0,0 +1,261 @@
+//! One note, described rather than recorded.
+
+use std::f32::consts::TAU;
+
+use crate::Sound;
+
+/// The shape of one cycle of the oscillator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Waveform {
+    Sine,
+    /// High for `duty` of each cycle, in (0, 1); 0.5 is the classic square.
+    Square {
+        duty: f32,
+    },
+    /// Rises across the cycle and drops back.
+    Saw,
+    Triangle,
+    /// White noise, one fresh value per cycle, so the frequency sets how
+    /// hissy or rumbly it is.
+    Noise,
+}
+
+impl Waveform {
+    /// A plain square wave.
+    pub const SQUARE: Waveform = Waveform::Square { duty: 0.5 };
+
+    /// The wave at `phase` through a cycle, in [0, 1), from -1 to 1.
+    /// Noise takes its value from `noise` instead.
+    fn sample(self, phase: f32, noise: f32) -> f32 {
+        match self {
+            Waveform::Sine => (phase * TAU).sin(),
+            Waveform::Square { duty } => {
+                if phase < duty {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Saw => 2.0 * phase - 1.0,
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Noise => noise,
+        }
+    }
+}
+
+/// A pitch moving from `from` to `to` Hz over the length of the tone. It
+/// moves evenly in octaves, as the ear hears it, not in Hz.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sweep {
+    pub from: f32,
+    pub to: f32,
+}
+
+impl Sweep {
+    /// One pitch throughout.
+    pub fn steady(frequency: f32) -> Self {
+        Self {
+            from: frequency,
+            to: frequency,
+        }
+    }
+
+    /// The frequency `progress` of the way through, in [0, 1].
+    pub fn at(&self, progress: f32) -> f32 {
+        self.from * (self.to / self.from).powf(progress.clamp(0.0, 1.0))
+    }
+}
+
+/// How loud a note is over time: it rises to full over `attack`, falls to
+/// `sustain` over `decay`, holds there until the note is let go, and fades
+/// to silence over `release`. Times are in seconds; `sustain` is in [0, 1].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adsr {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Adsr {
+    /// Full volume from start to end, with just enough attack and release
+    /// not to click.
+    pub const FLAT: Adsr = Adsr {
+        attack: 0.005,
+        decay: 0.0,
+        sustain: 1.0,
+        release: 0.005,
+    };
+
+    /// Straight to full volume and evenly down to nothing over `length`: a
+    /// struck sound, like a ping.
+    pub fn pluck(length: f32) -> Self {
+        Self {
+            attack: 0.0,
+            decay: length,
+            sustain: 0.0,
+            release: 0.0,
+        }
+    }
+
+    /// The level `t` seconds in, for a note let go after `held` seconds.
+    pub fn level(&self, t: f32, held: f32) -> f32 {
+        let holding = |t: f32| {
+            if t < self.attack {
+                t / self.attack
+            } else if t < self.attack + self.decay {
+                1.0 - (1.0 - self.sustain) * (t - self.attack) / self.decay
+            } else {
+                self.sustain
+            }
+        };
+        if t < held {
+            holding(t)
+        } else if self.release > 0.0 {
+            holding(held) * (1.0 - (t - held) / self.release).max(0.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// One note: a waveform at a (perhaps sweeping) pitch, shaped by an
+/// envelope. Held for `duration` seconds, then released.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    pub waveform: Waveform,
+    pub pitch: Sweep,
+    pub envelope: Adsr,
+    pub duration: f32,
+    /// Peak level, in [0, 1].
+    pub volume: f32,
+}
+
+impl Tone {
+    /// `waveform` at `frequency` Hz for `duration` seconds, at half volume
+    /// with a [`FLAT`](Adsr::FLAT) envelope.
+    pub fn new(waveform: Waveform, frequency: f32, duration: f32) -> Self {
+        Self {
+            waveform,
+            pitch: Sweep::steady(frequency),
+            envelope: Adsr::FLAT,
+            duration,
+            volume: 0.5,
+        }
+    }
+
+    /// Slide the pitch to `frequency` Hz by the end of the note.
+    pub fn sweep_to(mut self, frequency: f32) -> Self {
+        self.pitch.to = frequency;
+        self
+    }
+
+    pub fn envelope(mut self, envelope: Adsr) -> Self {
+        self.envelope = envelope;
+        self
+    }
+
+    pub fn volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    /// The note, including its release, at `sample_rate` samples a second.
+    pub fn render(&self, sample_rate: u32) -> Sound {
+        let rate = sample_rate as f32;
+        let length = ((self.duration + self.envelope.release) * rate) as usize;
+        let mut phase = 0.0_f32;
+        let mut noise = Noise::default();
+        let mut noise_value = noise.next();
+        let samples = (0..length)
+            .map(|i| {
+                let t = i as f32 / rate;
+                let wave = self.waveform.sample(phase, noise_value);
+                // Step the phase by this instant's frequency, so a sweep
+                // glides instead of jumping about.
+                phase += self.pitch.at(t / self.duration) / rate;
+                if phase >= 1.0 {
+                    phase -= phase.floor();
+                    noise_value = noise.next();
+                }
+                wave * self.volume * self.envelope.level(t, self.duration)
+            })
+            .collect();
+        Sound::new(sample_rate, samples)
+    }
+}
+
+/// xorshift32 from a fixed seed, so noise is the same on every run.
+struct Noise(u32);
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self(0x9e37_79b9)
+    }
+}
+
+impl Noise {
+    /// The next value, in [-1, 1].
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_envelope_rises_holds_and_fades() {
+        // given a note with every stage a tenth of a second long
+        let envelope = Adsr {
+            attack: 0.1,
+            decay: 0.1,
+            sustain: 0.5,
+            release: 0.1,
+        };
+
+        // when it is held for half a second
+        let level = |t| envelope.level(t, 0.5);
+
+        // then it passes through each stage in turn
+        assert_eq!(level(0.05), 0.5);
+        assert_eq!(level(0.1), 1.0);
+        assert!((level(0.15) - 0.75).abs() < 1e-6);
+        assert_eq!(level(0.3), 0.5);
+        assert!((level(0.55) - 0.25).abs() < 1e-6);
+        assert_eq!(level(0.7), 0.0);
+    }
+
+    #[test]
+    fn a_sweep_moves_evenly_in_octaves() {
+        // given a two-octave sweep down
+        let sweep = Sweep {
+            from: 880.0,
+            to: 220.0,
+        };
+
+        // then halfway through it is one octave down
+        assert_eq!(sweep.at(0.0), 880.0);
+        assert!((sweep.at(0.5) - 440.0).abs() < 1e-3);
+        assert_eq!(sweep.at(1.0), 220.0);
+    }
+
+    #[test]
+    fn a_tone_renders_the_same_samples_every_time() {
+        // given a noise burst
+        let tone = Tone::new(Waveform::Noise, 2000.0, 0.05).envelope(Adsr::pluck(0.05));
+
+        // when it is rendered twice
+        let first = tone.render(crate::SAMPLE_RATE);
+        let second = tone.render(crate::SAMPLE_RATE);
+
+        // then both are the same, as long as asked, and within full scale
+        assert_eq!(first, second);
+        assert_eq!(first.samples().len(), 2205);
+        assert!(first.samples().iter().all(|s| s.abs() <= 0.5));
+    }
+}
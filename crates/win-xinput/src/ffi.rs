@@ -0,0 +1,113 @@
+//! The XInput calls we make, loaded at runtime so a machine without
+//! XInput still starts.
+
+use std::ffi::c_void;
+
+use crate::PadReading;
+
+#[repr(C)]
+pub(crate) struct XInputGamepad {
+    buttons: u16,
+    left_trigger: u8,
+    right_trigger: u8,
+    thumb_lx: i16,
+    thumb_ly: i16,
+    thumb_rx: i16,
+    thumb_ry: i16,
+}
+
+#[repr(C)]
+pub(crate) struct XInputState {
+    packet_number: u32,
+    gamepad: XInputGamepad,
+}
+
+#[repr(C)]
+pub(crate) struct XInputVibration {
+    left_motor_speed: u16,
+    right_motor_speed: u16,
+}
+
+const ERROR_SUCCESS: u32 = 0;
+
+pub(crate) type GetStateFn = unsafe extern "system" fn(u32, *mut XInputState) -> u32;
+pub(crate) type SetStateFn = unsafe extern "system" fn(u32, *mut XInputVibration) -> u32;
+
+/// The calls, from one DLL.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Api {
+    pub(crate) get_state: GetStateFn,
+    pub(crate) set_state: SetStateFn,
+}
+
+#[link(name = "kernel32")]
+unsafe extern "system" {
+    fn LoadLibraryA(name: *const u8) -> *mut c_void;
+    fn GetProcAddress(module: *mut c_void, name: *const u8) -> *mut c_void;
+}
+
+/// `XInputGetState` and `XInputSetState` from the newest XInput DLL that
+/// has both.
+pub(crate) fn load() -> Option<Api> {
+    for dll in [b"xinput1_4.dll\0" as &[u8], b"xinput9_1_0.dll\0"] {
+        // SAFETY: both names are NUL-terminated.
+        let module = unsafe { LoadLibraryA(dll.as_ptr()) };
+        if module.is_null() {
+            continue;
+        }
+        // SAFETY: as above; the module was just loaded and is never freed.
+        let get_state = unsafe { GetProcAddress(module, b"XInputGetState\0".as_ptr()) };
+        let set_state = unsafe { GetProcAddress(module, b"XInputSetState\0".as_ptr()) };
+        if !get_state.is_null() && !set_state.is_null() {
+            // SAFETY: both have these signatures in every XInput DLL.
+            return Some(unsafe {
+                Api {
+                    get_state: std::mem::transmute::<*mut c_void, GetStateFn>(get_state),
+                    set_state: std::mem::transmute::<*mut c_void, SetStateFn>(set_state),
+                }
+            });
+        }
+    }
+    None
+}
+
+/// What the controller in `slot` reports, or `None` if there is none.
+pub(crate) fn read(api: Api, slot: usize) -> Option<PadReading> {
+    let mut state = std::mem::MaybeUninit::<XInputState>::uninit();
+    // SAFETY: `state` is a writable XINPUT_STATE; XInput fills it on success.
+    let result = unsafe { (api.get_state)(slot as u32, state.as_mut_ptr()) };
+    if result != ERROR_SUCCESS {
+        return None;
+    }
+    // SAFETY: filled, as the call succeeded.
+    let state = unsafe { state.assume_init() };
+    let pad = &state.gamepad;
+    Some(PadReading {
+        buttons: pad.buttons,
+        left_trigger: f32::from(pad.left_trigger) / 255.0,
+        right_trigger: f32::from(pad.right_trigger) / 255.0,
+        left_stick: (normalize_thumb(pad.thumb_lx), normalize_thumb(pad.thumb_ly)),
+        right_stick: (normalize_thumb(pad.thumb_rx), normalize_thumb(pad.thumb_ry)),
+    })
+}
+
+/// Run the motors of the controller in `slot` at these speeds, in [0, 1],
+/// until told otherwise. False if there is no controller there.
+pub(crate) fn vibrate(api: Api, slot: usize, left: f32, right: f32) -> bool {
+    let speed = |fraction: f32| (fraction.clamp(0.0, 1.0) * f32::from(u16::MAX)) as u16;
+    let mut vibration = XInputVibration {
+        left_motor_speed: speed(left),
+        right_motor_speed: speed(right),
+    };
+    // SAFETY: `vibration` is a valid XINPUT_VIBRATION for the call.
+    unsafe { (api.set_state)(slot as u32, &mut vibration) == ERROR_SUCCESS }
+}
+
+/// A thumbstick axis in [-1, 1]; the negative side has one more step.
+fn normalize_thumb(value: i16) -> f32 {
+    if value >= 0 {
+        value as f32 / 32767.0
+    } else {
+        value as f32 / 32768.0
+    }
+}
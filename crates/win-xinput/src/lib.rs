@@ -0,0 +1,217 @@
+//! Xbox-style controllers read straight from Windows' XInput.
+//!
+//! Bevy's gamepads come from gilrs, which mixes up two identical pads; see
+//! `docs/research/gilrs-dual-gamepad-bug.md`. XInput numbers its four
+//! controller slots itself, so a pad stays in its slot for as long as it is
+//! plugged in. [`XInput::poll`] reads every slot and reports which pads came
+//! and went since the last poll; [`XInput::pad`] is what a slot held then.
+//! [`XInput::vibrate`] runs a pad's rumble motors until told to stop.
+//!
+//! XInput is loaded from whichever of its DLLs Windows has. Elsewhere, or if
+//! neither DLL loads, [`XInput::load`] still succeeds but is not
+//! [`available`](XInput::available), and every slot stays empty.
+
+#[cfg(target_os = "windows")]
+mod ffi;
+
+/// XInput has four controller slots.
+pub const SLOTS: usize = 4;
+
+/// A button on an XInput controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    Start,
+    Back,
+    LeftThumb,
+    RightThumb,
+    LeftShoulder,
+    RightShoulder,
+    A,
+    B,
+    X,
+    Y,
+}
+
+impl Button {
+    pub const ALL: [Button; 14] = [
+        Button::DPadUp,
+        Button::DPadDown,
+        Button::DPadLeft,
+        Button::DPadRight,
+        Button::Start,
+        Button::Back,
+        Button::LeftThumb,
+        Button::RightThumb,
+        Button::LeftShoulder,
+        Button::RightShoulder,
+        Button::A,
+        Button::B,
+        Button::X,
+        Button::Y,
+    ];
+
+    /// Its bit in `XINPUT_GAMEPAD::wButtons`.
+    fn mask(self) -> u16 {
+        match self {
+            Button::DPadUp => 0x0001,
+            Button::DPadDown => 0x0002,
+            Button::DPadLeft => 0x0004,
+            Button::DPadRight => 0x0008,
+            Button::Start => 0x0010,
+            Button::Back => 0x0020,
+            Button::LeftThumb => 0x0040,
+            Button::RightThumb => 0x0080,
+            Button::LeftShoulder => 0x0100,
+            Button::RightShoulder => 0x0200,
+            Button::A => 0x1000,
+            Button::B => 0x2000,
+            Button::X => 0x4000,
+            Button::Y => 0x8000,
+        }
+    }
+}
+
+/// What one connected controller reported, scaled to floats.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PadReading {
+    /// Raw `wButtons`; ask [`pressed`](PadReading::pressed).
+    pub buttons: u16,
+    /// In [0, 1].
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+    /// (x, y) in [-1, 1], up positive.
+    pub left_stick: (f32, f32),
+    pub right_stick: (f32, f32),
+}
+
+impl PadReading {
+    pub fn pressed(&self, button: Button) -> bool {
+        self.buttons & button.mask() != 0
+    }
+
+    /// Every button held, in [`Button::ALL`] order.
+    pub fn pressed_buttons(&self) -> impl Iterator<Item = Button> + '_ {
+        Button::ALL
+            .into_iter()
+            .filter(|&button| self.pressed(button))
+    }
+}
+
+/// A pad arriving in or leaving a slot, as seen between two polls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    Connected(usize),
+    Disconnected(usize),
+}
+
+/// The XInput library, if one loaded, and what each slot held at the last
+/// poll.
+#[derive(Debug, Default)]
+pub struct XInput {
+    #[cfg(target_os = "windows")]
+    api: Option<ffi::Api>,
+    pads: [Option<PadReading>; SLOTS],
+}
+
+impl XInput {
+    /// Load XInput. Check [`available`](XInput::available) to learn whether
+    /// it did.
+    pub fn load() -> Self {
+        Self {
+            #[cfg(target_os = "windows")]
+            api: ffi::load(),
+            pads: [None; SLOTS],
+        }
+    }
+
+    /// Whether polling can ever find a pad.
+    pub fn available(&self) -> bool {
+        #[cfg(target_os = "windows")]
+        return self.api.is_some();
+        #[cfg(not(target_os = "windows"))]
+        return false;
+    }
+
+    /// Read every slot, and say which pads connected or disconnected since
+    /// the last poll.
+    pub fn poll(&mut self) -> Vec<ConnectionEvent> {
+        let readings: [Option<PadReading>; SLOTS] = std::array::from_fn(|slot| self.read(slot));
+        self.update(readings)
+    }
+
+    /// What `slot` held at the last poll, or `None` if it was empty.
+    pub fn pad(&self, slot: usize) -> Option<&PadReading> {
+        self.pads.get(slot).and_then(Option::as_ref)
+    }
+
+    /// Run `slot`'s strong (left, low-frequency) and weak (right) motors at
+    /// these speeds in [0, 1] until the next call; zeros stop them. False
+    /// if there is no pad there to rumble.
+    #[cfg(target_os = "windows")]
+    pub fn vibrate(&self, slot: usize, strong: f32, weak: f32) -> bool {
+        slot < SLOTS
+            && self
+                .api
+                .is_some_and(|api| ffi::vibrate(api, slot, strong, weak))
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    pub fn vibrate(&self, _slot: usize, _strong: f32, _weak: f32) -> bool {
+        false
+    }
+
+    #[cfg(target_os = "windows")]
+    fn read(&self, slot: usize) -> Option<PadReading> {
+        ffi::read(self.api?, slot)
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn read(&self, _slot: usize) -> Option<PadReading> {
+        None
+    }
+
+    fn update(&mut self, readings: [Option<PadReading>; SLOTS]) -> Vec<ConnectionEvent> {
+        let mut events = Vec::new();
+        for (slot, (was, now)) in self.pads.iter().zip(&readings).enumerate() {
+            match (was, now) {
+                (None, Some(_)) => events.push(ConnectionEvent::Connected(slot)),
+                (Some(_), None) => events.push(ConnectionEvent::Disconnected(slot)),
+                _ => {}
+            }
+        }
+        self.pads = readings;
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_pad_is_reported_once_when_it_comes_and_once_when_it_goes() {
+        // given no pads
+        let mut xinput = XInput::default();
+        let pad = PadReading {
+            buttons: Button::A.mask() | Button::Start.mask(),
+            ..PadReading::default()
+        };
+
+        // when one arrives in slot 2, stays a poll, and leaves
+        let arrived = xinput.update([None, None, Some(pad), None]);
+        let stayed = xinput.update([None, None, Some(pad), None]);
+        let pressed: Vec<Button> = xinput.pad(2).unwrap().pressed_buttons().collect();
+        let left = xinput.update([None; SLOTS]);
+
+        // then only its coming and going are events
+        assert_eq!(arrived, vec![ConnectionEvent::Connected(2)]);
+        assert_eq!(stayed, vec![]);
+        assert_eq!(pressed, vec![Button::Start, Button::A]);
+        assert_eq!(left, vec![ConnectionEvent::Disconnected(2)]);
+        assert_eq!(xinput.pad(2), None);
+    }
+}
@@ -4,6 +4,7 @@
 
 use std::collections::VecDeque;
 
+use arcade_prelude::Velocity;
 use bevy::diagnostic::{DiagnosticsStore, EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin};
 use bevy::asset::RenderAssetUsages;
 use bevy::prelude::*;
@@ -124,9 +125,6 @@ struct Ball;
 #[derive(Component)]
 struct TrailDot;
 
-#[derive(Component)]
-struct Velocity(Vec2);
-
 #[derive(Component)]
 struct TrailHistory(VecDeque<Vec2>);
 
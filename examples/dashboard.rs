@@ -5,13 +5,36 @@
 //! Shows gamepad input (2 controllers), mouse state, and window info
 //! side by side. Useful for diagnosing input and display configuration.
 //!
-//! Gamepad input is read directly via XInput FFI, bypassing Bevy's
-//! gilrs-based gamepad system. See `docs/gilrs-dual-gamepad-bug.md`
-//! for why.
-
+//! The keyboard panel can show ANSI, ISO or JIS layouts at full size,
+//! tenkeyless or 60% (Ctrl+L / Ctrl+K). The choice is saved to
+//! `dashboard.toml`. Keys relabel themselves with what the OS reports once
+//! pressed, so non-US keyboards show their own legends.
+//!
+//! Each panel is a type implementing `DashboardPanel`, registered from its
+//! plugin with `app.add_dashboard_panel::<P>()`. Adding a panel (network,
+//! audio, battery, game debug) does not touch `setup_ui`.
+//!
+//! Gamepad input is read directly from XInput through `win_xinput`,
+//! bypassing Bevy's gilrs-based gamepad system. See
+//! `docs/gilrs-dual-gamepad-bug.md` for why. Built with `--features sdl`,
+//! Ctrl+G switches to SDL2's controllers and back, which tell identical
+//! pads apart on every platform. Each pad is also handed to `arcade_input` as one player, and
+//! its panel shows the actions that player would send a game.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use arcade_input::{
+    Action, ActionState, ArcadeInputPlugin, InputAssignments, PadBackend, PadState, PlayerActions,
+    PlayerAssignment, SdlPads, XInputPads, XInputPlugin,
+};
+use bevy::input::ButtonState;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::ecs::system::{StaticSystemParam, SystemParam, SystemParamItem};
 use bevy::input::mouse::MouseWheel;
 use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
+use serde::{Deserialize, Serialize};
 
 fn main() {
     App::new()
@@ -27,25 +50,38 @@ struct DualInputDisplayPlugin;
 
 impl Plugin for DualInputDisplayPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins((
-            DualGamepadInputPlugin,
-            MouseInputPlugin,
-            WindowInfoPlugin,
-            DualInputDisplayUiPlugin,
-        ));
+        app.configure_sets(Update, (DashboardSet::Read, DashboardSet::Render).chain())
+            .add_plugins((
+                DualGamepadInputPlugin,
+                MouseInputPlugin,
+                WindowInfoPlugin,
+                KeyboardInputPlugin,
+                DualInputDisplayUiPlugin,
+            ));
     }
 }
 
 // ---------------------------------------------------------------------------
-// Gamepad input plugin: polls XInput directly, bypassing gilrs
+// Gamepad input plugin: reads XInput (or SDL2) directly, bypassing gilrs
 // ---------------------------------------------------------------------------
 
 struct DualGamepadInputPlugin;
 
 impl Plugin for DualGamepadInputPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<DualGamepadInputState>()
-            .add_systems(Update, read_gamepad_input);
+        app.add_plugins((ArcadeInputPlugin, XInputPlugin))
+            .insert_resource(PadBackend::XInput)
+            .init_resource::<DualGamepadInputState>()
+            .add_systems(
+                Update,
+                (switch_pad_backend, assign_pads, read_gamepad_input)
+                    .chain()
+                    .in_set(DashboardSet::Read),
+            )
+            .add_dashboard_panel::<GamepadPanel<0>>()
+            .add_dashboard_panel::<GamepadPanel<1>>();
+        #[cfg(feature = "sdl")]
+        app.add_plugins(arcade_input::SdlPlugin);
     }
 }
 
@@ -82,159 +118,184 @@ struct GamepadButtonStates {
     dpad_right: bool,
 }
 
-// -- XInput FFI --------------------------------------------------------------
-
-#[repr(C)]
-struct XInputGamepad {
-    buttons: u16,
-    left_trigger: u8,
-    right_trigger: u8,
-    thumb_lx: i16,
-    thumb_ly: i16,
-    thumb_rx: i16,
-    thumb_ry: i16,
+/// Ctrl+G moves to the next backend this build has, other than gilrs.
+fn switch_pad_backend(keys: Res<ButtonInput<KeyCode>>, mut backend: ResMut<PadBackend>) {
+    if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+        || !keys.just_pressed(KeyCode::KeyG)
+    {
+        return;
+    }
+    let mut next = backend.next();
+    if next == PadBackend::Bevy {
+        next = next.next();
+    }
+    *backend = next;
 }
 
-#[repr(C)]
-struct XInputState {
-    packet_number: u32,
-    gamepad: XInputGamepad,
+/// Each pad is one player, so its panel can show what a game would read.
+fn assign_pads(backend: Res<PadBackend>, mut assignments: ResMut<InputAssignments>) {
+    if !backend.is_changed() {
+        return;
+    }
+    assignments.0 = (0..GAMEPAD_COUNT)
+        .map(|pad| PlayerAssignment {
+            devices: vec![backend.device(pad)],
+            ..default()
+        })
+        .collect();
 }
 
-const XINPUT_GAMEPAD_DPAD_UP: u16 = 0x0001;
-const XINPUT_GAMEPAD_DPAD_DOWN: u16 = 0x0002;
-const XINPUT_GAMEPAD_DPAD_LEFT: u16 = 0x0004;
-const XINPUT_GAMEPAD_DPAD_RIGHT: u16 = 0x0008;
-const XINPUT_GAMEPAD_START: u16 = 0x0010;
-const XINPUT_GAMEPAD_BACK: u16 = 0x0020;
-const XINPUT_GAMEPAD_LEFT_SHOULDER: u16 = 0x0100;
-const XINPUT_GAMEPAD_RIGHT_SHOULDER: u16 = 0x0200;
-const XINPUT_GAMEPAD_A: u16 = 0x1000;
-const XINPUT_GAMEPAD_B: u16 = 0x2000;
-const XINPUT_GAMEPAD_X: u16 = 0x4000;
-const XINPUT_GAMEPAD_Y: u16 = 0x8000;
+fn read_gamepad_input(
+    backend: Res<PadBackend>,
+    xinput: Res<XInputPads>,
+    sdl: Res<SdlPads>,
+    mut state: ResMut<DualGamepadInputState>,
+) {
+    let pads = match *backend {
+        PadBackend::Sdl => &sdl.0,
+        _ => &xinput.0,
+    };
+    for (slot, pad) in state.gamepads.iter_mut().zip(pads) {
+        *slot = SingleGamepadState::from_pad(pad);
+    }
+}
 
-const ERROR_SUCCESS: u32 = 0;
+impl SingleGamepadState {
+    fn from_pad(pad: &PadState) -> Self {
+        if !pad.connected {
+            return Self::default();
+        }
+        let btn = |button: GamepadButton| pad.pressed(button);
+        Self {
+            connected: true,
+            left_stick: pad.left_stick,
+            right_stick: pad.right_stick,
+            left_trigger: pad.left_trigger,
+            right_trigger: pad.right_trigger,
+            buttons: GamepadButtonStates {
+                south: btn(GamepadButton::South),
+                east: btn(GamepadButton::East),
+                north: btn(GamepadButton::North),
+                west: btn(GamepadButton::West),
+                left_bumper: btn(GamepadButton::LeftTrigger),
+                right_bumper: btn(GamepadButton::RightTrigger),
+                start: btn(GamepadButton::Start),
+                select: btn(GamepadButton::Select),
+                dpad_up: btn(GamepadButton::DPadUp),
+                dpad_down: btn(GamepadButton::DPadDown),
+                dpad_left: btn(GamepadButton::DPadLeft),
+                dpad_right: btn(GamepadButton::DPadRight),
+            },
+        }
+    }
+}
 
-type XInputGetStateFn = unsafe extern "system" fn(u32, *mut XInputState) -> u32;
+// ---------------------------------------------------------------------------
+// Panel API: panels describe themselves; the UI plugin lays them out
+// ---------------------------------------------------------------------------
 
-fn load_xinput() -> Option<XInputGetStateFn> {
-    use std::ffi::CString;
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+enum DashboardSet {
+    /// Systems that poll devices into state resources.
+    Read,
+    /// Panel updates, after every read.
+    Render,
+}
 
-    #[link(name = "kernel32")]
-    unsafe extern "system" {
-        fn LoadLibraryA(name: *const u8) -> *mut std::ffi::c_void;
-        fn GetProcAddress(
-            module: *mut std::ffi::c_void,
-            name: *const u8,
-        ) -> *mut std::ffi::c_void;
-    }
+/// How a panel wants to be laid out.
+#[derive(Clone, Copy)]
+struct PanelLayout {
+    /// Panels appear in ascending order; ties keep registration order.
+    order: i32,
+    font_size: f32,
+}
 
-    for dll in &[b"xinput1_4.dll\0" as &[u8], b"xinput9_1_0.dll\0"] {
-        let module = unsafe { LoadLibraryA(dll.as_ptr()) };
-        if module.is_null() {
-            continue;
-        }
-        let proc_name = CString::new("XInputGetState").unwrap();
-        let proc = unsafe { GetProcAddress(module, proc_name.as_ptr() as *const u8) };
-        if !proc.is_null() {
-            return Some(unsafe { std::mem::transmute(proc) });
+impl Default for PanelLayout {
+    fn default() -> Self {
+        Self {
+            order: 0,
+            font_size: FONT_SIZE,
         }
     }
-    None
 }
 
-fn normalize_thumb(value: i16) -> f32 {
-    if value >= 0 {
-        value as f32 / 32767.0
-    } else {
-        value as f32 / 32768.0
+/// A diagnostic panel: a bordered box of monospace text re-rendered every
+/// frame from whatever `Param` reads.
+trait DashboardPanel: Send + Sync + 'static {
+    /// Resources or queries the panel renders from, with `'static`
+    /// lifetimes (e.g. `Res<'static, MouseInputState>`).
+    type Param: SystemParam;
+
+    /// Shown until the first render.
+    fn title() -> String;
+
+    fn layout() -> PanelLayout {
+        PanelLayout::default()
     }
+
+    fn render(param: &mut SystemParamItem<'_, '_, Self::Param>) -> String;
 }
 
-// -- Bevy system -------------------------------------------------------------
+/// Marks the text entity of panel `P`.
+#[derive(Component)]
+struct PanelText<P: DashboardPanel>(PhantomData<P>);
 
-fn read_gamepad_input(
-    mut state: ResMut<DualGamepadInputState>,
-    mut xinput_fn: Local<Option<Option<XInputGetStateFn>>>,
-) {
-    let get_state = match *xinput_fn {
-        Some(Some(f)) => f,
-        Some(None) => return, // already failed to load
-        None => {
-            let loaded = load_xinput();
-            if loaded.is_none() {
-                warn!("Failed to load XInput DLL — gamepad input unavailable");
-            }
-            *xinput_fn = Some(loaded);
-            match loaded {
-                Some(f) => f,
-                None => return,
-            }
-        }
-    };
+struct PanelSpec {
+    title: String,
+    layout: PanelLayout,
+    insert_marker: fn(&mut EntityCommands),
+}
 
-    for (index, slot) in state.gamepads.iter_mut().enumerate() {
-        let mut xinput_state = std::mem::MaybeUninit::<XInputState>::uninit();
-        let result = unsafe { get_state(index as u32, xinput_state.as_mut_ptr()) };
+/// Every registered panel, in registration order.
+#[derive(Resource, Default)]
+struct DashboardPanels(Vec<PanelSpec>);
 
-        if result != ERROR_SUCCESS {
-            *slot = SingleGamepadState::default();
-            continue;
-        }
+trait DashboardAppExt {
+    fn add_dashboard_panel<P: DashboardPanel>(&mut self) -> &mut Self;
+}
 
-        let xs = unsafe { xinput_state.assume_init() };
-        let gp = &xs.gamepad;
-        let btn = |mask: u16| gp.buttons & mask != 0;
-
-        slot.connected = true;
-        slot.left_stick = Vec2::new(normalize_thumb(gp.thumb_lx), normalize_thumb(gp.thumb_ly));
-        slot.right_stick = Vec2::new(normalize_thumb(gp.thumb_rx), normalize_thumb(gp.thumb_ry));
-        slot.left_trigger = gp.left_trigger as f32 / 255.0;
-        slot.right_trigger = gp.right_trigger as f32 / 255.0;
-
-        slot.buttons = GamepadButtonStates {
-            south: btn(XINPUT_GAMEPAD_A),
-            east: btn(XINPUT_GAMEPAD_B),
-            north: btn(XINPUT_GAMEPAD_Y),
-            west: btn(XINPUT_GAMEPAD_X),
-            left_bumper: btn(XINPUT_GAMEPAD_LEFT_SHOULDER),
-            right_bumper: btn(XINPUT_GAMEPAD_RIGHT_SHOULDER),
-            start: btn(XINPUT_GAMEPAD_START),
-            select: btn(XINPUT_GAMEPAD_BACK),
-            dpad_up: btn(XINPUT_GAMEPAD_DPAD_UP),
-            dpad_down: btn(XINPUT_GAMEPAD_DPAD_DOWN),
-            dpad_left: btn(XINPUT_GAMEPAD_DPAD_LEFT),
-            dpad_right: btn(XINPUT_GAMEPAD_DPAD_RIGHT),
-        };
+impl DashboardAppExt for App {
+    fn add_dashboard_panel<P: DashboardPanel>(&mut self) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<DashboardPanels>()
+            .0
+            .push(PanelSpec {
+                title: P::title(),
+                layout: P::layout(),
+                insert_marker: |entity| {
+                    entity.insert(PanelText::<P>(PhantomData));
+                },
+            });
+        self.add_systems(Update, update_panel::<P>.in_set(DashboardSet::Render))
+    }
+}
+
+fn update_panel<P: DashboardPanel>(
+    mut param: StaticSystemParam<P::Param>,
+    mut query: Query<&mut Text, With<PanelText<P>>>,
+) {
+    if query.is_empty() {
+        return;
+    }
+    let rendered = P::render(&mut *param);
+    for mut text in &mut query {
+        **text = rendered.clone();
     }
 }
 
 // ---------------------------------------------------------------------------
-// Display UI plugin: reads input state resources and renders text
+// Display UI plugin: spawns a box for every registered panel
 // ---------------------------------------------------------------------------
 
 struct DualInputDisplayUiPlugin;
 
 impl Plugin for DualInputDisplayUiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_ui).add_systems(
-            Update,
-            (
-                update_display.after(read_gamepad_input),
-                update_mouse_display.after(read_mouse_input),
-                update_window_display.after(read_window_info),
-                update_keyboard_display,
-            ),
-        );
+        app.init_resource::<DashboardPanels>()
+            .add_systems(Startup, setup_ui);
     }
 }
 
-#[derive(Component)]
-struct GamepadDisplayText {
-    index: usize,
-}
-
 const FONT_SIZE: f32 = 20.0;
 const KEYBOARD_FONT_SIZE: f32 = 14.0;
 const GAP: f32 = 10.0;
@@ -251,17 +312,11 @@ fn panel_node() -> Node {
     }
 }
 
-fn setup_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn setup_ui(mut commands: Commands, asset_server: Res<AssetServer>, panels: Res<DashboardPanels>) {
     commands.spawn(Camera2d);
 
     let mono_font: Handle<Font> = asset_server.load("local/fonts/FiraMono-Regular.ttf");
 
-    let text_font = TextFont {
-        font: mono_font.clone(),
-        font_size: FONT_SIZE,
-        ..default()
-    };
-
     let flex_root = commands
         .spawn(Node {
             display: Display::Flex,
@@ -275,108 +330,99 @@ fn setup_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
         })
         .id();
 
-    for index in 0..GAMEPAD_COUNT {
-        let gamepad_number = index + 1;
-        let header = format!("Gamepad {gamepad_number}: No gamepad detected");
+    let mut ordered: Vec<&PanelSpec> = panels.0.iter().collect();
+    ordered.sort_by_key(|spec| spec.layout.order);
 
+    for spec in ordered {
         let panel = commands
             .spawn((panel_node(), BorderColor::all(BORDER_COLOR)))
             .id();
-        let text = commands
-            .spawn((
-                Text::new(header),
-                text_font.clone(),
-                TextColor::WHITE,
-                GamepadDisplayText { index },
-            ))
-            .id();
+        let mut text = commands.spawn((
+            Text::new(spec.title.clone()),
+            TextFont {
+                font: mono_font.clone(),
+                font_size: spec.layout.font_size,
+                ..default()
+            },
+            TextColor::WHITE,
+        ));
+        (spec.insert_marker)(&mut text);
+        let text = text.id();
         commands.entity(panel).add_child(text);
         commands.entity(flex_root).add_child(panel);
     }
+}
 
-    let mouse_panel = commands
-        .spawn((panel_node(), BorderColor::all(BORDER_COLOR)))
-        .id();
-    let mouse_text = commands
-        .spawn((
-            Text::new("Mouse\n\nWaiting for input..."),
-            text_font.clone(),
-            TextColor::WHITE,
-            MouseDisplayText,
-        ))
-        .id();
-    commands.entity(mouse_panel).add_child(mouse_text);
-    commands.entity(flex_root).add_child(mouse_panel);
+// ---------------------------------------------------------------------------
+// Gamepad panels
+// ---------------------------------------------------------------------------
 
-    let window_panel = commands
-        .spawn((panel_node(), BorderColor::all(BORDER_COLOR)))
-        .id();
-    let window_text = commands
-        .spawn((
-            Text::new("Window\n\nLoading..."),
-            text_font.clone(),
-            TextColor::WHITE,
-            WindowDisplayText,
-        ))
-        .id();
-    commands.entity(window_panel).add_child(window_text);
-    commands.entity(flex_root).add_child(window_panel);
+/// One controller slot of the chosen backend.
+struct GamepadPanel<const INDEX: usize>;
 
-    let keyboard_font = TextFont {
-        font: mono_font,
-        font_size: KEYBOARD_FONT_SIZE,
-        ..default()
-    };
+impl<const INDEX: usize> DashboardPanel for GamepadPanel<INDEX> {
+    type Param = (
+        Res<'static, DualGamepadInputState>,
+        Res<'static, ActionState>,
+        Res<'static, PadBackend>,
+    );
 
-    let keyboard_panel = commands
-        .spawn((panel_node(), BorderColor::all(BORDER_COLOR)))
-        .id();
-    let keyboard_text = commands
-        .spawn((
-            Text::new("Keyboard\n\nLoading..."),
-            keyboard_font,
-            TextColor::WHITE,
-            KeyboardDisplayText,
-        ))
-        .id();
-    commands.entity(keyboard_panel).add_child(keyboard_text);
-    commands.entity(flex_root).add_child(keyboard_panel);
-}
+    fn title() -> String {
+        format!("Gamepad {}: No gamepad detected", INDEX + 1)
+    }
 
-fn update_display(
-    gamepad_state: Res<DualGamepadInputState>,
-    mut query: Query<(&mut Text, &GamepadDisplayText)>,
-) {
-    for (mut text, display_marker) in &mut query {
-        let pad = &gamepad_state.gamepads[display_marker.index];
-        let gamepad_number = display_marker.index + 1;
+    fn layout() -> PanelLayout {
+        PanelLayout {
+            order: INDEX as i32,
+            ..default()
+        }
+    }
+
+    fn render(
+        (gamepad_state, actions, backend): &mut SystemParamItem<'_, '_, Self::Param>,
+    ) -> String {
+        let pad = &gamepad_state.gamepads[INDEX];
+        let gamepad_number = INDEX + 1;
+        let backend = **backend;
 
         if !pad.connected {
-            **text = format!(
-                "Gamepad {gamepad_number}\n\nNo gamepad detected\n\nConnect a controller to see input values."
+            return format!(
+                "Gamepad {gamepad_number} ({backend:?})\n\nNo gamepad detected\n\nConnect a controller to see input values."
             );
-            continue;
         }
 
-        let display = format_gamepad_display(gamepad_number, pad);
-        **text = display;
+        format_gamepad_display(gamepad_number, backend, pad, actions.player(INDEX))
     }
 }
 
-fn format_gamepad_display(gamepad_number: usize, state: &SingleGamepadState) -> String {
+fn format_gamepad_display(
+    gamepad_number: usize,
+    backend: PadBackend,
+    state: &SingleGamepadState,
+    actions: PlayerActions,
+) -> String {
     let left = state.left_stick;
     let right = state.right_stick;
     let buttons = &state.buttons;
+    let held = |name: &str, pressed: bool| {
+        if pressed {
+            format!("[{name}]")
+        } else {
+            format!(" {name} ")
+        }
+    };
 
     format!(
-        "Gamepad {gamepad_number}\n\
+        "Gamepad {gamepad_number} ({backend:?})\n\
          \n\
          Left Stick   X: {:>6.3}  Y: {:>6.3}\n\
          Right Stick  X: {:>6.3}  Y: {:>6.3}\n\
          Triggers     L: {:>5.3}   R: {:>5.3}\n\
          \n\
          Buttons\n\
-         {}",
+         {}\n\
+         \n\
+         Actions      MoveY: {:>6.3}  {}  {}",
         left.x,
         left.y,
         right.x,
@@ -384,6 +430,9 @@ fn format_gamepad_display(gamepad_number: usize, state: &SingleGamepadState) ->
         state.left_trigger,
         state.right_trigger,
         format_buttons(buttons),
+        actions.value(Action::MoveY),
+        held("Serve", actions.pressed(Action::Serve)),
+        held("Pause", actions.pressed(Action::Pause)),
     )
 }
 
@@ -396,7 +445,8 @@ struct MouseInputPlugin;
 impl Plugin for MouseInputPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<MouseInputState>()
-            .add_systems(Update, read_mouse_input);
+            .add_systems(Update, read_mouse_input.in_set(DashboardSet::Read))
+            .add_dashboard_panel::<MousePanel>();
     }
 }
 
@@ -437,8 +487,26 @@ fn read_mouse_input(
     state.scroll_delta = scroll;
 }
 
-#[derive(Component)]
-struct MouseDisplayText;
+struct MousePanel;
+
+impl DashboardPanel for MousePanel {
+    type Param = Res<'static, MouseInputState>;
+
+    fn title() -> String {
+        "Mouse\n\nWaiting for input...".into()
+    }
+
+    fn layout() -> PanelLayout {
+        PanelLayout {
+            order: 10,
+            ..default()
+        }
+    }
+
+    fn render(mouse_state: &mut SystemParamItem<'_, '_, Self::Param>) -> String {
+        format_mouse_display(mouse_state)
+    }
+}
 
 fn format_mouse_display(state: &MouseInputState) -> String {
     let format_pos = |pos: Option<Vec2>| match pos {
@@ -474,15 +542,6 @@ fn format_mouse_display(state: &MouseInputState) -> String {
     )
 }
 
-fn update_mouse_display(
-    mouse_state: Res<MouseInputState>,
-    mut query: Query<&mut Text, With<MouseDisplayText>>,
-) {
-    for mut text in &mut query {
-        **text = format_mouse_display(&mouse_state);
-    }
-}
-
 // ---------------------------------------------------------------------------
 // Window info plugin: reads window properties into a resource
 // ---------------------------------------------------------------------------
@@ -492,7 +551,8 @@ struct WindowInfoPlugin;
 impl Plugin for WindowInfoPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<WindowInfoState>()
-            .add_systems(Update, read_window_info);
+            .add_systems(Update, read_window_info.in_set(DashboardSet::Read))
+            .add_dashboard_panel::<WindowPanel>();
     }
 }
 
@@ -527,8 +587,26 @@ fn read_window_info(
     state.scale_factor = window.scale_factor();
 }
 
-#[derive(Component)]
-struct WindowDisplayText;
+struct WindowPanel;
+
+impl DashboardPanel for WindowPanel {
+    type Param = Res<'static, WindowInfoState>;
+
+    fn title() -> String {
+        "Window\n\nLoading...".into()
+    }
+
+    fn layout() -> PanelLayout {
+        PanelLayout {
+            order: 20,
+            ..default()
+        }
+    }
+
+    fn render(window_state: &mut SystemParamItem<'_, '_, Self::Param>) -> String {
+        format_window_display(window_state)
+    }
+}
 
 fn format_window_display(state: &WindowInfoState) -> String {
     let position = match state.position {
@@ -556,15 +634,6 @@ fn format_window_display(state: &WindowInfoState) -> String {
     )
 }
 
-fn update_window_display(
-    window_state: Res<WindowInfoState>,
-    mut query: Query<&mut Text, With<WindowDisplayText>>,
-) {
-    for mut text in &mut query {
-        **text = format_window_display(&window_state);
-    }
-}
-
 // ---------------------------------------------------------------------------
 // Gamepad display formatting helpers
 // ---------------------------------------------------------------------------
@@ -610,11 +679,158 @@ fn format_buttons(buttons: &GamepadButtonStates) -> String {
 }
 
 // ---------------------------------------------------------------------------
-// Keyboard display
+// Keyboard plugin: layout settings, OS key labels, and the keyboard panel
 // ---------------------------------------------------------------------------
 
-#[derive(Component)]
-struct KeyboardDisplayText;
+struct KeyboardInputPlugin;
+
+impl Plugin for KeyboardInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(KeyboardSettings::load())
+            .init_resource::<LogicalKeyLabels>()
+            .add_systems(
+                Update,
+                (cycle_keyboard_settings, learn_logical_key_labels).in_set(DashboardSet::Read),
+            )
+            .add_dashboard_panel::<KeyboardPanel>();
+    }
+}
+
+struct KeyboardPanel;
+
+impl DashboardPanel for KeyboardPanel {
+    type Param = (
+        Res<'static, ButtonInput<KeyCode>>,
+        Res<'static, KeyboardSettings>,
+        Res<'static, LogicalKeyLabels>,
+    );
+
+    fn title() -> String {
+        "Keyboard\n\nLoading...".into()
+    }
+
+    fn layout() -> PanelLayout {
+        PanelLayout {
+            order: 30,
+            font_size: KEYBOARD_FONT_SIZE,
+        }
+    }
+
+    fn render((keys, settings, labels): &mut SystemParamItem<'_, '_, Self::Param>) -> String {
+        format_keyboard_display(keys, **settings, labels)
+    }
+}
+
+/// Where the dashboard keeps its settings, relative to the working directory.
+const SETTINGS_FILENAME: &str = "dashboard.toml";
+
+/// Physical key arrangement. Changes which keys exist and where Enter sits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+enum PhysicalLayout {
+    /// US: wide left Shift, one-row Enter, Backslash above Enter.
+    #[default]
+    Ansi,
+    /// Europe: short left Shift with an extra key, two-row Enter.
+    Iso,
+    /// Japan: ISO-style Enter plus Yen, Ro, and IME keys around Space.
+    Jis,
+}
+
+impl PhysicalLayout {
+    fn next(self) -> Self {
+        match self {
+            PhysicalLayout::Ansi => PhysicalLayout::Iso,
+            PhysicalLayout::Iso => PhysicalLayout::Jis,
+            PhysicalLayout::Jis => PhysicalLayout::Ansi,
+        }
+    }
+}
+
+/// Which blocks of keys the keyboard has.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+enum KeyboardSize {
+    #[default]
+    FullSize,
+    /// No numpad.
+    Tenkeyless,
+    /// Main block only: no function row, navigation cluster, or numpad.
+    SixtyPercent,
+}
+
+impl KeyboardSize {
+    fn next(self) -> Self {
+        match self {
+            KeyboardSize::FullSize => KeyboardSize::Tenkeyless,
+            KeyboardSize::Tenkeyless => KeyboardSize::SixtyPercent,
+            KeyboardSize::SixtyPercent => KeyboardSize::FullSize,
+        }
+    }
+}
+
+/// The keyboard the user picked, saved to [`SETTINGS_FILENAME`].
+/// Ctrl+L cycles the layout and Ctrl+K the size.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct KeyboardSettings {
+    layout: PhysicalLayout,
+    size: KeyboardSize,
+}
+
+impl KeyboardSettings {
+    fn load() -> Self {
+        match std::fs::read_to_string(SETTINGS_FILENAME) {
+            Ok(text) => toml::from_str(&text).unwrap_or_else(|e| {
+                eprintln!("Failed to parse {SETTINGS_FILENAME}: {e} — using defaults");
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn save(&self) {
+        let text = toml::to_string_pretty(self).expect("failed to serialize settings");
+        if let Err(e) = std::fs::write(SETTINGS_FILENAME, text) {
+            eprintln!("Could not write {SETTINGS_FILENAME}: {e}");
+        }
+    }
+}
+
+/// Labels the OS reported for keys that have been pressed, so a French user
+/// sees `A` where US labels say `Q`. Keys not yet pressed keep their US label.
+#[derive(Resource, Default)]
+struct LogicalKeyLabels(HashMap<KeyCode, String>);
+
+fn cycle_keyboard_settings(keys: Res<ButtonInput<KeyCode>>, mut settings: ResMut<KeyboardSettings>) {
+    if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    let before = *settings;
+    if keys.just_pressed(KeyCode::KeyL) {
+        settings.layout = settings.layout.next();
+    }
+    if keys.just_pressed(KeyCode::KeyK) {
+        settings.size = settings.size.next();
+    }
+    if *settings != before {
+        settings.save();
+    }
+}
+
+fn learn_logical_key_labels(
+    mut events: MessageReader<KeyboardInput>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut labels: ResMut<LogicalKeyLabels>,
+) {
+    // Shift and AltGr change the character, not the key's legend.
+    let modified = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight, KeyCode::AltRight]);
+    for event in events.read() {
+        if modified || event.state != ButtonState::Pressed {
+            continue;
+        }
+        if let Key::Character(text) = &event.logical_key {
+            labels.0.insert(event.key_code, text.to_uppercase());
+        }
+    }
+}
 
 /// Render a single key: `[label]` when pressed, ` label ` when unpressed.
 /// Empty label produces a spacer gap.
@@ -643,13 +859,30 @@ fn key(label: &str, pressed: bool, width: usize) -> String {
     }
 }
 
-/// Returns the keyboard layout as rows of (label, KeyCode, display_width) tuples.
-/// An empty label indicates a spacer gap (KeyCode is ignored).
-fn keyboard_rows() -> Vec<Vec<(&'static str, KeyCode, u8)>> {
+/// (label, KeyCode, display_width). An empty label is a spacer gap
+/// (KeyCode is ignored).
+type KeySpec = (&'static str, KeyCode, u8);
+
+/// One row of the keyboard, split into the blocks that come and go with
+/// [`KeyboardSize`]. Blocks are padded so they line up across rows.
+#[derive(Default)]
+struct KeyboardRow {
+    main: Vec<KeySpec>,
+    nav: Vec<KeySpec>,
+    numpad: Vec<KeySpec>,
+}
+
+const BLOCK_GAP: usize = 2;
+/// Three 5-wide keys.
+const NAV_WIDTH: usize = 15;
+
+/// Returns the keyboard as rows of key blocks for the chosen layout and size.
+fn keyboard_rows(settings: KeyboardSettings) -> Vec<KeyboardRow> {
     use KeyCode::*;
-    vec![
-        // Function key row + PrtSc/ScrLk/Pause + numpad top
-        vec![
+    let layout = settings.layout;
+
+    let function_row = KeyboardRow {
+        main: vec![
             ("Esc", Escape, 5),
             ("", Escape, 2), // spacer
             ("F1", F1, 4),
@@ -666,107 +899,136 @@ fn keyboard_rows() -> Vec<Vec<(&'static str, KeyCode, u8)>> {
             ("F10", F10, 5),
             ("F11", F11, 5),
             ("F12", F12, 5),
-            ("", Escape, 2), // spacer
-            ("PrS", PrintScreen, 5),
-            ("SLk", ScrollLock, 5),
-            ("Pau", Pause, 5),
         ],
-        // Number row + nav cluster + numpad row 1
-        vec![
-            ("`", Backquote, 4),
-            ("1", Digit1, 4),
-            ("2", Digit2, 4),
-            ("3", Digit3, 4),
-            ("4", Digit4, 4),
-            ("5", Digit5, 4),
-            ("6", Digit6, 4),
-            ("7", Digit7, 4),
-            ("8", Digit8, 4),
-            ("9", Digit9, 4),
-            ("0", Digit0, 4),
-            ("-", Minus, 4),
-            ("=", Equal, 4),
-            ("Bksp", Backspace, 6),
-            ("", Escape, 2), // spacer
-            ("Ins", Insert, 5),
-            ("Hom", Home, 5),
-            ("PUp", PageUp, 5),
-            ("", Escape, 2), // spacer
+        nav: vec![("PrS", PrintScreen, 5), ("SLk", ScrollLock, 5), ("Pau", Pause, 5)],
+        numpad: vec![],
+    };
+
+    // A 60% board has no Escape key of its own; it shares the backquote key.
+    let mut number_main = if settings.size == KeyboardSize::SixtyPercent {
+        vec![("Esc", Escape, 5)]
+    } else {
+        vec![("`", Backquote, 4)]
+    };
+    number_main.extend([
+        ("1", Digit1, 4),
+        ("2", Digit2, 4),
+        ("3", Digit3, 4),
+        ("4", Digit4, 4),
+        ("5", Digit5, 4),
+        ("6", Digit6, 4),
+        ("7", Digit7, 4),
+        ("8", Digit8, 4),
+        ("9", Digit9, 4),
+        ("0", Digit0, 4),
+        ("-", Minus, 4),
+        ("=", Equal, 4),
+    ]);
+    match layout {
+        PhysicalLayout::Jis => number_main.extend([("\u{a5}", IntlYen, 4), ("BS", Backspace, 4)]),
+        PhysicalLayout::Ansi | PhysicalLayout::Iso => number_main.push(("Bksp", Backspace, 6)),
+    }
+    let number_row = KeyboardRow {
+        main: number_main,
+        nav: vec![("Ins", Insert, 5), ("Hom", Home, 5), ("PUp", PageUp, 5)],
+        numpad: vec![
             ("NLk", NumLock, 4),
             ("N/", NumpadDivide, 4),
             ("N*", NumpadMultiply, 4),
             ("N-", NumpadSubtract, 4),
         ],
-        // QWERTY row + nav cluster + numpad row 2
-        vec![
-            ("Tab", Tab, 5),
-            ("Q", KeyQ, 4),
-            ("W", KeyW, 4),
-            ("E", KeyE, 4),
-            ("R", KeyR, 4),
-            ("T", KeyT, 4),
-            ("Y", KeyY, 4),
-            ("U", KeyU, 4),
-            ("I", KeyI, 4),
-            ("O", KeyO, 4),
-            ("P", KeyP, 4),
-            ("[", BracketLeft, 4),
-            ("]", BracketRight, 4),
-            ("\\", Backslash, 5),
-            ("", Escape, 2), // spacer
-            ("Del", Delete, 5),
-            ("End", End, 5),
-            ("PDn", PageDown, 5),
-            ("", Escape, 2), // spacer
+    };
+
+    let mut top_main = vec![
+        ("Tab", Tab, 5),
+        ("Q", KeyQ, 4),
+        ("W", KeyW, 4),
+        ("E", KeyE, 4),
+        ("R", KeyR, 4),
+        ("T", KeyT, 4),
+        ("Y", KeyY, 4),
+        ("U", KeyU, 4),
+        ("I", KeyI, 4),
+        ("O", KeyO, 4),
+        ("P", KeyP, 4),
+        ("[", BracketLeft, 4),
+        ("]", BracketRight, 4),
+    ];
+    match layout {
+        PhysicalLayout::Ansi => top_main.push(("\\", Backslash, 5)),
+        // Upper half of the two-row Enter.
+        PhysicalLayout::Iso | PhysicalLayout::Jis => top_main.push(("\u{21b5}", Enter, 5)),
+    }
+    let top_row = KeyboardRow {
+        main: top_main,
+        nav: vec![("Del", Delete, 5), ("End", End, 5), ("PDn", PageDown, 5)],
+        numpad: vec![
             ("N7", Numpad7, 4),
             ("N8", Numpad8, 4),
             ("N9", Numpad9, 4),
             ("N+", NumpadAdd, 4),
         ],
-        // Home row + numpad row 3
-        vec![
-            ("Caps", CapsLock, 6),
-            ("A", KeyA, 4),
-            ("S", KeyS, 4),
-            ("D", KeyD, 4),
-            ("F", KeyF, 4),
-            ("G", KeyG, 4),
-            ("H", KeyH, 4),
-            ("J", KeyJ, 4),
-            ("K", KeyK, 4),
-            ("L", KeyL, 4),
-            (";", Semicolon, 4),
-            ("'", Quote, 4),
-            ("Entr", Enter, 6),
-            ("", Escape, 21), // spacer past nav cluster (56 + 21 = 77)
-            ("N4", Numpad4, 4),
-            ("N5", Numpad5, 4),
-            ("N6", Numpad6, 4),
-        ],
-        // Shift row + arrows + numpad row 4
-        vec![
-            ("Shft", ShiftLeft, 7),
-            ("Z", KeyZ, 4),
-            ("X", KeyX, 4),
-            ("C", KeyC, 4),
-            ("V", KeyV, 4),
-            ("B", KeyB, 4),
-            ("N", KeyN, 4),
-            ("M", KeyM, 4),
-            (",", Comma, 4),
-            (".", Period, 4),
-            ("/", Slash, 4),
-            ("Shft", ShiftRight, 7),
-            ("", Escape, 10), // spacer (54 + 10 = 64, aligns Up above Down)
-            ("\u{2191}", ArrowUp, 4),
-            ("", Escape, 9), // spacer (68 + 9 = 77)
+    };
+
+    let mut home_main = vec![
+        ("Caps", CapsLock, 6),
+        ("A", KeyA, 4),
+        ("S", KeyS, 4),
+        ("D", KeyD, 4),
+        ("F", KeyF, 4),
+        ("G", KeyG, 4),
+        ("H", KeyH, 4),
+        ("J", KeyJ, 4),
+        ("K", KeyK, 4),
+        ("L", KeyL, 4),
+        (";", Semicolon, 4),
+        ("'", Quote, 4),
+    ];
+    match layout {
+        PhysicalLayout::Ansi => home_main.push(("Entr", Enter, 6)),
+        // Backslash moves beside the lower half of the Enter key.
+        PhysicalLayout::Iso => home_main.extend([("#", Backslash, 4), ("\u{21b5}", Enter, 4)]),
+        PhysicalLayout::Jis => home_main.extend([("]", Backslash, 4), ("\u{21b5}", Enter, 4)]),
+    }
+    let home_row = KeyboardRow {
+        main: home_main,
+        nav: vec![],
+        numpad: vec![("N4", Numpad4, 4), ("N5", Numpad5, 4), ("N6", Numpad6, 4)],
+    };
+
+    let mut shift_main = match layout {
+        PhysicalLayout::Ansi | PhysicalLayout::Jis => vec![("Shft", ShiftLeft, 7)],
+        PhysicalLayout::Iso => vec![("Sh", ShiftLeft, 4), ("\\", IntlBackslash, 4)],
+    };
+    shift_main.extend([
+        ("Z", KeyZ, 4),
+        ("X", KeyX, 4),
+        ("C", KeyC, 4),
+        ("V", KeyV, 4),
+        ("B", KeyB, 4),
+        ("N", KeyN, 4),
+        ("M", KeyM, 4),
+        (",", Comma, 4),
+        (".", Period, 4),
+        ("/", Slash, 4),
+    ]);
+    match layout {
+        PhysicalLayout::Jis => shift_main.extend([("\\", IntlRo, 4), ("Sh", ShiftRight, 4)]),
+        PhysicalLayout::Ansi | PhysicalLayout::Iso => shift_main.push(("Shft", ShiftRight, 7)),
+    }
+    let shift_row = KeyboardRow {
+        main: shift_main,
+        nav: vec![("", Escape, 5), ("\u{2191}", ArrowUp, 5)], // spacer aligns Up above Down
+        numpad: vec![
             ("N1", Numpad1, 4),
             ("N2", Numpad2, 4),
             ("N3", Numpad3, 4),
             ("NEn", NumpadEnter, 4),
         ],
-        // Bottom row + arrows + numpad row 5
-        vec![
+    };
+
+    let bottom_main = match layout {
+        PhysicalLayout::Ansi | PhysicalLayout::Iso => vec![
             ("Ctrl", ControlLeft, 6),
             ("Sup", SuperLeft, 5),
             ("Alt", AltLeft, 5),
@@ -775,44 +1037,92 @@ fn keyboard_rows() -> Vec<Vec<(&'static str, KeyCode, u8)>> {
             ("Sup", SuperRight, 5),
             ("Menu", ContextMenu, 6),
             ("Ctrl", ControlRight, 6),
-            ("", Escape, 7), // spacer (53 + 7 = 60, aligns with nav cluster)
-            ("\u{2190}", ArrowLeft, 4),
-            ("\u{2193}", ArrowDown, 4),
-            ("\u{2192}", ArrowRight, 4),
-            ("", Escape, 5), // spacer (72 + 5 = 77)
-            ("N0", Numpad0, 8),
-            ("N.", NumpadDecimal, 4),
         ],
-    ]
+        // IME keys squeeze the space bar.
+        PhysicalLayout::Jis => vec![
+            ("Ctrl", ControlLeft, 6),
+            ("Sup", SuperLeft, 5),
+            ("Alt", AltLeft, 5),
+            ("MHn", NonConvert, 5),
+            ("Space", Space, 9),
+            ("Hen", Convert, 5),
+            ("Kan", KanaMode, 5),
+            ("Alt", AltRight, 5),
+            ("Menu", ContextMenu, 6),
+            ("Ctrl", ControlRight, 6),
+        ],
+    };
+    let bottom_row = KeyboardRow {
+        main: bottom_main,
+        nav: vec![("\u{2190}", ArrowLeft, 5), ("\u{2193}", ArrowDown, 5), ("\u{2192}", ArrowRight, 5)],
+        numpad: vec![("N0", Numpad0, 8), ("N.", NumpadDecimal, 4)],
+    };
+
+    let mut rows = Vec::with_capacity(6);
+    if settings.size != KeyboardSize::SixtyPercent {
+        rows.push(function_row);
+    }
+    rows.extend([number_row, top_row, home_row, shift_row, bottom_row]);
+    for row in &mut rows {
+        if settings.size != KeyboardSize::FullSize {
+            row.numpad.clear();
+        }
+        if settings.size == KeyboardSize::SixtyPercent {
+            row.nav.clear();
+        }
+    }
+    rows
 }
 
-fn format_keyboard_display(keys: &ButtonInput<KeyCode>) -> String {
-    let rows = keyboard_rows();
+fn block_width(block: &[KeySpec]) -> usize {
+    block.iter().map(|&(_, _, width)| width as usize).sum()
+}
+
+fn format_block(block: &[KeySpec], keys: &ButtonInput<KeyCode>, labels: &LogicalKeyLabels) -> String {
+    let mut text = String::new();
+    for &(label, code, width) in block {
+        let (label, pressed) = if label.is_empty() {
+            (label, false)
+        } else {
+            let label = labels.0.get(&code).map_or(label, String::as_str);
+            (label, keys.pressed(code))
+        };
+        text.push_str(&key(label, pressed, width as usize));
+    }
+    text
+}
+
+fn format_keyboard_display(
+    keys: &ButtonInput<KeyCode>,
+    settings: KeyboardSettings,
+    labels: &LogicalKeyLabels,
+) -> String {
+    let rows = keyboard_rows(settings);
+    let main_width = rows.iter().map(|row| block_width(&row.main)).max().unwrap_or(0);
+    let has_nav = rows.iter().any(|row| !row.nav.is_empty());
+    let has_numpad = rows.iter().any(|row| !row.numpad.is_empty());
+
     let mut lines = Vec::with_capacity(rows.len() + 2);
-    lines.push("Keyboard".to_string());
+    lines.push(format!(
+        "Keyboard ({:?}, {:?})  Ctrl+L layout  Ctrl+K size",
+        settings.layout, settings.size
+    ));
     lines.push(String::new());
 
     for row in &rows {
-        let mut line = String::new();
-        for &(label, code, width) in row {
-            let pressed = if label.is_empty() {
-                false
-            } else {
-                keys.pressed(code)
-            };
-            line.push_str(&key(label, pressed, width as usize));
+        let mut line = format_block(&row.main, keys, labels);
+        line.push_str(&" ".repeat(main_width - block_width(&row.main)));
+        if has_nav {
+            line.push_str(&" ".repeat(BLOCK_GAP));
+            line.push_str(&format_block(&row.nav, keys, labels));
+            line.push_str(&" ".repeat(NAV_WIDTH - block_width(&row.nav)));
         }
-        lines.push(line);
+        if has_numpad {
+            line.push_str(&" ".repeat(BLOCK_GAP));
+            line.push_str(&format_block(&row.numpad, keys, labels));
+        }
+        lines.push(line.trim_end().to_string());
     }
 
     lines.join("\n")
 }
-
-fn update_keyboard_display(
-    keys: Res<ButtonInput<KeyCode>>,
-    mut query: Query<&mut Text, With<KeyboardDisplayText>>,
-) {
-    for mut text in &mut query {
-        **text = format_keyboard_display(&keys);
-    }
-}
@@ -2,16 +2,33 @@
 //!
 //! Run with: `cargo run --example neon_pong`
 //!
-//! Connect two gamepads and use the left stick Y-axis to move paddles.
-//! Unconnected paddles simply stay still.
+//! Connect two gamepads and use the left stick Y-axis (or the D-pad) to
+//! move paddles. Unconnected paddles simply stay still. A pad rumbles when
+//! its paddle hits the ball, and harder when a point is lost.
 //!
-//! Sound files must be generated once before first run:
-//!   `cargo run --example generate_sounds`
-//! The game works fine without them (just silent, with asset warnings).
+//! Pads come from Bevy's gamepads at first. F2 switches to XInput, then to
+//! SDL2 when built with `--features sdl`, for when Bevy mixes up two
+//! identical pads (see `docs/research/gilrs-dual-gamepad-bug.md`).
+//!
+//! Its sound effects are synthesized at startup by `sound_synth`, so there
+//! are no sound files to generate or ship.
+
+use std::time::Duration;
 
+use arcade_input::{
+    Action, ActionState, ActionSystems, ArcadeInputPlugin, InputAssignments, PadBackend,
+    PlayerAssignment, Rumble, XInputPlugin,
+};
+use arcade_prelude::*;
 use bevy::core_pipeline::tonemapping::{DebandDither, Tonemapping};
 use bevy::post_process::bloom::Bloom;
 use bevy::prelude::*;
+use pong_sim::fixed::Fx;
+use sound_synth::presets;
+use pong_sim::{
+    ARENA_HEIGHT, ARENA_WIDTH, BALL_SIZE, Ball, PADDLE_HEIGHT, PADDLE_WIDTH, PADDLE_X_OFFSET,
+    PLAYER_COUNT, Paddle, PongState, SimEvent, paddle_x,
+};
 
 fn main() {
     App::new()
@@ -38,243 +55,110 @@ impl Plugin for NeonPongPlugin {
 }
 
 // ---------------------------------------------------------------------------
-// Arena and gameplay constants
-// ---------------------------------------------------------------------------
-
-const ARENA_WIDTH: f32 = 800.0;
-const ARENA_HEIGHT: f32 = 500.0;
-const PADDLE_WIDTH: f32 = 15.0;
-const PADDLE_HEIGHT: f32 = 80.0;
-const PADDLE_X_OFFSET: f32 = 30.0;
-const PADDLE_SPEED: f32 = 400.0;
-const BALL_SIZE: f32 = 12.0;
-const BALL_INITIAL_SPEED: f32 = 300.0;
-const BALL_SPEED_INCREASE: f32 = 25.0;
-const PADDLE_HIT_ANGLE_FACTOR: f32 = 0.5;
-const PLAYER_COUNT: usize = 2;
-
-// ---------------------------------------------------------------------------
-// Input plugin (identical to pong.rs)
+// Input plugin, rumble included (identical to pong.rs)
 // ---------------------------------------------------------------------------
 
 struct PongInputPlugin;
 
 impl Plugin for PongInputPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<PaddleInput>()
-            .add_systems(Update, read_paddle_input);
+        app.add_plugins((ArcadeInputPlugin, XInputPlugin))
+            .init_resource::<PadBackend>()
+            .init_resource::<PaddleInput>()
+            .add_systems(PreUpdate, assign_pads.before(ActionSystems))
+            .add_systems(
+                Update,
+                (switch_pad_backend, read_paddle_input, rumble_on_events),
+            );
+        #[cfg(feature = "sdl")]
+        app.add_plugins(arcade_input::SdlPlugin);
     }
 }
 
-#[derive(Resource, Default)]
-struct PaddleInput {
-    movement: [f32; PLAYER_COUNT],
+fn switch_pad_backend(keys: Res<ButtonInput<KeyCode>>, mut backend: ResMut<PadBackend>) {
+    if keys.just_pressed(KeyCode::F2) {
+        *backend = backend.next();
+        info!("reading pads from {:?}", *backend);
+    }
 }
 
-fn read_paddle_input(gamepads: Query<&Gamepad>, mut input: ResMut<PaddleInput>) {
-    let mut gamepad_iter = gamepads.iter();
-
-    for slot in &mut input.movement {
-        let Some(gamepad) = gamepad_iter.next() else {
-            *slot = 0.0;
-            continue;
-        };
-        *slot = gamepad.left_stick().y;
+/// Player n holds the backend's nth pad.
+fn assign_pads(backend: Res<PadBackend>, mut assignments: ResMut<InputAssignments>) {
+    if !backend.is_changed() {
+        return;
     }
+    assignments.0 = (0..PLAYER_COUNT)
+        .map(|pad| PlayerAssignment {
+            devices: vec![backend.device(pad)],
+            ..default()
+        })
+        .collect();
 }
 
-// ---------------------------------------------------------------------------
-// Cross-domain events (game -> effects/audio)
-// ---------------------------------------------------------------------------
+#[derive(Resource, Default)]
+struct PaddleInput {
+    movement: [f32; PLAYER_COUNT],
+}
 
-#[derive(Message)]
-struct PaddleHitEvent {
-    ball_position: Vec3,
+fn read_paddle_input(actions: Res<ActionState>, mut input: ResMut<PaddleInput>) {
+    for (player, slot) in input.movement.iter_mut().enumerate() {
+        *slot = actions.player(player).value(Action::MoveY);
+    }
 }
 
-#[derive(Message)]
-struct WallBounceEvent {
-    ball_position: Vec3,
+/// A buzz for the hitter, and a thud for whoever let a point in.
+fn rumble_on_events(mut events: MessageReader<SimEvent>, mut rumbles: MessageWriter<Rumble>) {
+    for event in events.read() {
+        match *event {
+            SimEvent::PaddleHit { player_index, .. } => {
+                rumbles.write(Rumble {
+                    player: player_index,
+                    strong: 0.0,
+                    weak: HIT_RUMBLE,
+                    duration: HIT_RUMBLE_DURATION,
+                });
+            }
+            SimEvent::Score { player_index } => {
+                let conceded = (0..PLAYER_COUNT).filter(|&player| player != player_index);
+                rumbles.write_batch(conceded.map(|player| Rumble {
+                    player,
+                    strong: SCORE_RUMBLE,
+                    weak: 0.0,
+                    duration: SCORE_RUMBLE_DURATION,
+                }));
+            }
+            SimEvent::WallBounce { .. } => {}
+        }
+    }
 }
 
-#[derive(Message)]
-struct ScoreEvent;
+const HIT_RUMBLE: f32 = 0.5;
+const HIT_RUMBLE_DURATION: Duration = Duration::from_millis(80);
+const SCORE_RUMBLE: f32 = 0.9;
+const SCORE_RUMBLE_DURATION: Duration = Duration::from_millis(300);
 
 // ---------------------------------------------------------------------------
-// Game plugin (pong.rs logic + event emission)
+// Game plugin: steps the shared simulation (see pong_sim), whose SimEvents
+// drive the effects and audio
 // ---------------------------------------------------------------------------
 
 struct NeonPongGamePlugin;
 
 impl Plugin for NeonPongGamePlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<Score>()
-            .init_resource::<BallResetCounter>()
-            .add_message::<PaddleHitEvent>()
-            .add_message::<WallBounceEvent>()
-            .add_message::<ScoreEvent>()
-            .add_systems(
-                FixedUpdate,
-                (
-                    move_paddles,
-                    move_ball,
-                    ball_wall_bounce,
-                    ball_paddle_bounce,
-                    check_scoring,
-                )
-                    .chain(),
-            );
+        app.init_resource::<PongState>()
+            .add_message::<SimEvent>()
+            .add_systems(FixedUpdate, step_pong);
     }
 }
 
-#[derive(Component)]
-struct Paddle {
-    player_index: usize,
-}
-
-#[derive(Component)]
-struct Ball;
-
-#[derive(Component)]
-struct Velocity(Vec2);
-
-#[derive(Resource, Default)]
-struct Score {
-    points: [u32; PLAYER_COUNT],
-}
-
-#[derive(Resource, Default)]
-struct BallResetCounter(u32);
-
-fn move_paddles(
+fn step_pong(
     input: Res<PaddleInput>,
     time: Res<Time>,
-    mut paddles: Query<(&mut Transform, &Paddle)>,
-) {
-    let dt = time.delta_secs();
-    let max_paddle_y = (ARENA_HEIGHT - PADDLE_HEIGHT) / 2.0;
-
-    for (mut transform, paddle) in &mut paddles {
-        let movement = input.movement[paddle.player_index];
-        transform.translation.y += movement * PADDLE_SPEED * dt;
-        transform.translation.y = transform.translation.y.clamp(-max_paddle_y, max_paddle_y);
-    }
-}
-
-fn move_ball(time: Res<Time>, mut ball: Query<(&mut Transform, &Velocity), With<Ball>>) {
-    let dt = time.delta_secs();
-    for (mut transform, velocity) in &mut ball {
-        transform.translation += velocity.0.extend(0.0) * dt;
-    }
-}
-
-fn ball_wall_bounce(
-    mut ball: Query<(&Transform, &mut Velocity), With<Ball>>,
-    mut bounce_events: MessageWriter<WallBounceEvent>,
-) {
-    let max_ball_y = (ARENA_HEIGHT - BALL_SIZE) / 2.0;
-
-    for (transform, mut velocity) in &mut ball {
-        let y = transform.translation.y;
-        if (y >= max_ball_y && velocity.0.y > 0.0)
-            || (y <= -max_ball_y && velocity.0.y < 0.0)
-        {
-            velocity.0.y = -velocity.0.y;
-            bounce_events.write(WallBounceEvent {
-                ball_position: transform.translation,
-            });
-        }
-    }
-}
-
-fn ball_paddle_bounce(
-    mut ball_query: Query<(&Transform, &mut Velocity), With<Ball>>,
-    paddle_query: Query<(&Transform, &Paddle), Without<Ball>>,
-    input: Res<PaddleInput>,
-    mut hit_events: MessageWriter<PaddleHitEvent>,
+    mut state: ResMut<PongState>,
+    mut events: MessageWriter<SimEvent>,
 ) {
-    let paddle_half_w = PADDLE_WIDTH / 2.0;
-    let paddle_half_h = PADDLE_HEIGHT / 2.0;
-    let ball_half = BALL_SIZE / 2.0;
-
-    for (ball_transform, mut ball_velocity) in &mut ball_query {
-        let ball_pos = ball_transform.translation;
-
-        for (paddle_transform, paddle) in &paddle_query {
-            let paddle_pos = paddle_transform.translation;
-
-            let overlap_x = (ball_pos.x - paddle_pos.x).abs() < paddle_half_w + ball_half;
-            let overlap_y = (ball_pos.y - paddle_pos.y).abs() < paddle_half_h + ball_half;
-
-            if !overlap_x || !overlap_y {
-                continue;
-            }
-
-            let ball_moving_toward_paddle = if paddle_pos.x < 0.0 {
-                ball_velocity.0.x < 0.0
-            } else {
-                ball_velocity.0.x > 0.0
-            };
-
-            if !ball_moving_toward_paddle {
-                continue;
-            }
-
-            ball_velocity.0.x = -ball_velocity.0.x;
-
-            let paddle_movement = input.movement[paddle.player_index];
-            ball_velocity.0.y += paddle_movement * PADDLE_SPEED * PADDLE_HIT_ANGLE_FACTOR;
-
-            let current_speed = ball_velocity.0.length();
-            let new_speed = current_speed + BALL_SPEED_INCREASE;
-            ball_velocity.0 = ball_velocity.0.normalize() * new_speed;
-
-            hit_events.write(PaddleHitEvent {
-                ball_position: ball_pos,
-            });
-        }
-    }
-}
-
-fn check_scoring(
-    mut ball_query: Query<(&mut Transform, &mut Velocity), With<Ball>>,
-    mut score: ResMut<Score>,
-    mut reset_counter: ResMut<BallResetCounter>,
-    mut score_events: MessageWriter<ScoreEvent>,
-) {
-    let score_boundary_x = ARENA_WIDTH / 2.0 + BALL_SIZE;
-
-    for (mut transform, mut velocity) in &mut ball_query {
-        let x = transform.translation.x;
-
-        let scoring_player = if x < -score_boundary_x {
-            Some(1)
-        } else if x > score_boundary_x {
-            Some(0)
-        } else {
-            None
-        };
-
-        let Some(scorer) = scoring_player else {
-            continue;
-        };
-
-        score.points[scorer] += 1;
-        reset_counter.0 += 1;
-
-        transform.translation = Vec3::ZERO;
-
-        let direction_x = if scorer == 0 { -1.0 } else { 1.0 };
-        let direction_y = if reset_counter.0.is_multiple_of(2) {
-            1.0
-        } else {
-            -1.0
-        };
-        let direction = Vec2::new(direction_x, direction_y * 0.5).normalize();
-        velocity.0 = direction * BALL_INITIAL_SPEED;
-
-        score_events.write(ScoreEvent);
-    }
+    events.write_batch(state.step(&input.movement, Fx::from_f32(time.delta_secs())));
 }
 
 // ---------------------------------------------------------------------------
@@ -287,7 +171,7 @@ impl Plugin for NeonRenderPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(ClearColor(Color::linear_rgb(0.02, 0.01, 0.05)))
             .add_systems(Startup, setup_neon_pong)
-            .add_systems(Update, update_score_display);
+            .add_systems(Update, (draw_pong, update_score_display));
     }
 }
 
@@ -299,18 +183,15 @@ const SCORE_TOP_MARGIN: f32 = 20.0;
 const BORDER_THICKNESS: f32 = 4.0;
 
 // Neon HDR colors (values > 1.0 trigger bloom)
-const COLOR_CYAN_PADDLE: Color = Color::linear_rgb(0.0, 4.0, 4.0);
-const COLOR_MAGENTA_PADDLE: Color = Color::linear_rgb(4.0, 0.0, 4.0);
-const COLOR_HOT_PINK_BALL: Color = Color::linear_rgb(5.0, 0.5, 2.0);
-const COLOR_DIM_BLUE_BORDER: Color = Color::linear_rgb(0.3, 0.1, 0.8);
-const COLOR_DIM_PURPLE_CENTER: Color = Color::linear_rgb(0.2, 0.1, 0.5);
-const COLOR_NEON_GREEN_SCORE: Color = Color::linear_rgb(0.0, 3.0, 0.0);
-const COLOR_GRID: Color = Color::linear_rgba(0.1, 0.05, 0.2, 0.3);
+const COLOR_CYAN_PADDLE: Color = palette::NEON_CYAN;
+const COLOR_MAGENTA_PADDLE: Color = palette::NEON_MAGENTA;
+const COLOR_HOT_PINK_BALL: Color = palette::NEON_HOT_PINK;
+const COLOR_DIM_BLUE_BORDER: Color = palette::DIM_BLUE;
+const COLOR_DIM_PURPLE_CENTER: Color = palette::DIM_PURPLE;
+const COLOR_NEON_GREEN_SCORE: Color = palette::NEON_GREEN;
 
 const CENTER_LINE_DASH_COUNT: usize = 15;
 const CENTER_LINE_DASH_WIDTH: f32 = 4.0;
-const GRID_SPACING: f32 = 50.0;
-const GRID_LINE_THICKNESS: f32 = 1.0;
 
 fn setup_neon_pong(mut commands: Commands) {
     // HDR camera with bloom
@@ -322,32 +203,15 @@ fn setup_neon_pong(mut commands: Commands) {
     ));
 
     // Background grid
-    spawn_background_grid(&mut commands);
+    spawn_background_grid(&mut commands, Vec2::new(ARENA_WIDTH, ARENA_HEIGHT), -0.1);
 
     // Arena borders
-    spawn_border(
+    spawn_arena_walls(
         &mut commands,
-        Vec3::new(0.0, ARENA_HEIGHT / 2.0, 0.0),
         ARENA_WIDTH,
-        BORDER_THICKNESS,
-    );
-    spawn_border(
-        &mut commands,
-        Vec3::new(0.0, -ARENA_HEIGHT / 2.0, 0.0),
-        ARENA_WIDTH,
-        BORDER_THICKNESS,
-    );
-    spawn_border(
-        &mut commands,
-        Vec3::new(-ARENA_WIDTH / 2.0, 0.0, 0.0),
-        BORDER_THICKNESS,
         ARENA_HEIGHT,
-    );
-    spawn_border(
-        &mut commands,
-        Vec3::new(ARENA_WIDTH / 2.0, 0.0, 0.0),
         BORDER_THICKNESS,
-        ARENA_HEIGHT,
+        COLOR_DIM_BLUE_BORDER,
     );
 
     // Center line (dashed)
@@ -366,19 +230,12 @@ fn setup_neon_pong(mut commands: Commands) {
     }
 
     // Paddles
-    let left_paddle_x = -(ARENA_WIDTH / 2.0 - PADDLE_X_OFFSET);
-    let right_paddle_x = ARENA_WIDTH / 2.0 - PADDLE_X_OFFSET;
-
-    spawn_neon_paddle(&mut commands, left_paddle_x, 0, COLOR_CYAN_PADDLE);
-    spawn_neon_paddle(&mut commands, right_paddle_x, 1, COLOR_MAGENTA_PADDLE);
+    spawn_neon_paddle(&mut commands, 0, COLOR_CYAN_PADDLE);
+    spawn_neon_paddle(&mut commands, 1, COLOR_MAGENTA_PADDLE);
 
     // Ball
-    let initial_direction = Vec2::new(1.0, 0.5).normalize();
-    let initial_velocity = initial_direction * BALL_INITIAL_SPEED;
-
     commands.spawn((
         Ball,
-        Velocity(initial_velocity),
         Sprite {
             color: COLOR_HOT_PINK_BALL,
             custom_size: Some(Vec2::splat(BALL_SIZE)),
@@ -402,52 +259,7 @@ fn setup_neon_pong(mut commands: Commands) {
     ));
 }
 
-fn spawn_background_grid(commands: &mut Commands) {
-    let half_w = ARENA_WIDTH / 2.0;
-    let half_h = ARENA_HEIGHT / 2.0;
-    let z_depth = -0.1;
-
-    // Vertical lines
-    let mut x = -half_w;
-    while x <= half_w {
-        commands.spawn((
-            Sprite {
-                color: COLOR_GRID,
-                custom_size: Some(Vec2::new(GRID_LINE_THICKNESS, ARENA_HEIGHT)),
-                ..default()
-            },
-            Transform::from_xyz(x, 0.0, z_depth),
-        ));
-        x += GRID_SPACING;
-    }
-
-    // Horizontal lines
-    let mut y = -half_h;
-    while y <= half_h {
-        commands.spawn((
-            Sprite {
-                color: COLOR_GRID,
-                custom_size: Some(Vec2::new(ARENA_WIDTH, GRID_LINE_THICKNESS)),
-                ..default()
-            },
-            Transform::from_xyz(0.0, y, z_depth),
-        ));
-        y += GRID_SPACING;
-    }
-}
-
-fn spawn_border(commands: &mut Commands, position: Vec3, width: f32, height: f32) {
-    commands.spawn((
-        Sprite {
-            color: COLOR_DIM_BLUE_BORDER,
-            custom_size: Some(Vec2::new(width, height)),
-            ..default()
-        },
-        Transform::from_translation(position),
-    ));
-}
-
-fn spawn_neon_paddle(commands: &mut Commands, x: f32, player_index: usize, color: Color) {
+fn spawn_neon_paddle(commands: &mut Commands, player_index: usize, color: Color) {
     commands.spawn((
         Paddle { player_index },
         PaddleFlash {
@@ -460,16 +272,33 @@ fn spawn_neon_paddle(commands: &mut Commands, x: f32, player_index: usize, color
             custom_size: Some(Vec2::new(PADDLE_WIDTH, PADDLE_HEIGHT)),
             ..default()
         },
-        Transform::from_xyz(x, 0.0, 0.5),
+        Transform::from_xyz(paddle_x(player_index), 0.0, 0.5),
     ));
 }
 
-fn update_score_display(score: Res<Score>, mut query: Query<&mut Text, With<ScoreText>>) {
-    if !score.is_changed() {
+/// Move the sprites to where the simulation has the paddles and ball,
+/// keeping their depth.
+fn draw_pong(
+    state: Res<PongState>,
+    mut paddles: Query<(&Paddle, &mut Transform), Without<Ball>>,
+    mut ball: Query<&mut Transform, With<Ball>>,
+) {
+    for (paddle, mut transform) in &mut paddles {
+        let z = transform.translation.z;
+        transform.translation = state.paddle_position(paddle.player_index).extend(z);
+    }
+    for mut transform in &mut ball {
+        let z = transform.translation.z;
+        transform.translation = state.ball_position().extend(z);
+    }
+}
+
+fn update_score_display(state: Res<PongState>, mut query: Query<&mut Text, With<ScoreText>>) {
+    if !state.is_changed() {
         return;
     }
     for mut text in &mut query {
-        **text = format!("{}  :  {}", score.points[0], score.points[1]);
+        **text = format!("{}  :  {}", state.score[0], state.score[1]);
     }
 }
 
@@ -479,25 +308,9 @@ fn update_score_display(score: Res<Score>, mut query: Query<&mut Text, With<Scor
 
 struct NeonEffectsPlugin;
 
-#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
-enum NeonEffectSet {
-    Spawn,
-    Update,
-    Camera,
-}
-
 impl Plugin for NeonEffectsPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<ScreenShake>()
-            .init_resource::<FrameCount>()
-            .configure_sets(
-                Update,
-                (
-                    NeonEffectSet::Spawn,
-                    NeonEffectSet::Update.after(NeonEffectSet::Spawn),
-                    NeonEffectSet::Camera.after(NeonEffectSet::Update),
-                ),
-            )
+        app.add_plugins(EffectsPlugin)
             .add_systems(
                 Update,
                 (
@@ -506,31 +319,12 @@ impl Plugin for NeonEffectsPlugin {
                     handle_wall_bounce_effects,
                     handle_score_effects,
                 )
-                    .in_set(NeonEffectSet::Spawn),
+                    .in_set(EffectSet::Spawn),
             )
-            .add_systems(
-                Update,
-                (update_paddle_flash, update_trails, update_particles)
-                    .in_set(NeonEffectSet::Update),
-            )
-            .add_systems(
-                Update,
-                apply_screen_shake.in_set(NeonEffectSet::Camera),
-            );
+            .add_systems(Update, update_paddle_flash.in_set(EffectSet::Update));
     }
 }
 
-#[derive(Component)]
-struct Trail {
-    lifetime: Timer,
-}
-
-#[derive(Component)]
-struct Particle {
-    lifetime: Timer,
-    initial_size: f32,
-}
-
 #[derive(Component)]
 struct PaddleFlash {
     timer: Timer,
@@ -538,22 +332,6 @@ struct PaddleFlash {
     flash_color: Color,
 }
 
-#[derive(Resource, Default)]
-struct ScreenShake {
-    trauma: f32,
-}
-
-#[derive(Resource, Default)]
-struct FrameCount(u32);
-
-const TRAIL_LIFETIME_SECS: f32 = 0.3;
-const TRAIL_SIZE_FACTOR: f32 = 0.8;
-const TRAIL_ALPHA: f32 = 0.5;
-
-const PARTICLE_LIFETIME_SECS: f32 = 0.4;
-const PARTICLE_SIZE: f32 = 5.0;
-const PARTICLE_SPEED: f32 = 300.0;
-
 const PADDLE_HIT_PARTICLE_COUNT: usize = 10;
 const WALL_BOUNCE_PARTICLE_COUNT: usize = 5;
 const SCORE_PARTICLE_COUNT: usize = 18;
@@ -561,9 +339,6 @@ const SCORE_PARTICLE_COUNT: usize = 18;
 const PADDLE_HIT_TRAUMA: f32 = 0.3;
 const SCORE_TRAUMA: f32 = 0.5;
 
-const MAX_SHAKE_OFFSET: f32 = 8.0;
-const TRAUMA_DECAY_RATE: f32 = 3.0;
-
 const FLASH_DURATION_SECS: f32 = 0.1;
 
 // --- Ball trail ---
@@ -573,44 +348,8 @@ fn spawn_ball_trail(
     ball_query: Query<(&Transform, &Sprite), With<Ball>>,
 ) {
     for (transform, sprite) in &ball_query {
-        let trail_size = BALL_SIZE * TRAIL_SIZE_FACTOR;
-        let trail_color = with_alpha(sprite.color, TRAIL_ALPHA);
-
-        commands.spawn((
-            Trail {
-                lifetime: Timer::from_seconds(TRAIL_LIFETIME_SECS, TimerMode::Once),
-            },
-            Sprite {
-                color: trail_color,
-                custom_size: Some(Vec2::splat(trail_size)),
-                ..default()
-            },
-            Transform::from_translation(transform.translation.with_z(0.5)),
-        ));
-    }
-}
-
-fn update_trails(
-    mut commands: Commands,
-    time: Res<Time>,
-    mut trails: Query<(Entity, &mut Trail, &mut Sprite, &mut Transform)>,
-) {
-    for (entity, mut trail, mut sprite, mut transform) in &mut trails {
-        trail.lifetime.tick(time.delta());
-
-        let progress = trail.lifetime.fraction();
-        let remaining = 1.0 - progress;
-
-        if let Some(size) = &mut sprite.custom_size {
-            let base = BALL_SIZE * TRAIL_SIZE_FACTOR;
-            *size = Vec2::splat(base * remaining);
-        }
-        sprite.color = with_alpha(sprite.color, TRAIL_ALPHA * remaining);
-        transform.scale = Vec3::splat(remaining.max(0.01));
-
-        if trail.lifetime.is_finished() {
-            commands.entity(entity).despawn();
-        }
+        let translation = transform.translation.with_z(0.5);
+        spawn_trail(&mut commands, translation, BALL_SIZE, sprite.color);
     }
 }
 
@@ -618,20 +357,24 @@ fn update_trails(
 
 fn handle_paddle_hit_effects(
     mut commands: Commands,
-    mut events: MessageReader<PaddleHitEvent>,
+    mut events: MessageReader<SimEvent>,
     mut shake: ResMut<ScreenShake>,
     paddles: Query<(&Transform, &Paddle)>,
     mut flash_query: Query<(&mut PaddleFlash, &Paddle)>,
 ) {
     for event in events.read() {
-        shake.trauma = (shake.trauma + PADDLE_HIT_TRAUMA).min(1.0);
+        let SimEvent::PaddleHit { position, .. } = *event else {
+            continue;
+        };
+        let ball_position = position.extend(0.0);
+        shake.add_trauma(PADDLE_HIT_TRAUMA);
 
         // Determine spray direction (away from the paddle that was hit)
-        let spray_center_angle = closest_paddle_spray_angle(&paddles, event.ball_position);
+        let spray_center_angle = closest_paddle_spray_angle(&paddles, ball_position);
 
         spawn_particles(
             &mut commands,
-            event.ball_position,
+            position,
             PADDLE_HIT_PARTICLE_COUNT,
             spray_center_angle,
             std::f32::consts::PI,
@@ -640,12 +383,7 @@ fn handle_paddle_hit_effects(
 
         // Flash the closest paddle
         for (mut flash, paddle) in &mut flash_query {
-            let paddle_x = if paddle.player_index == 0 {
-                -(ARENA_WIDTH / 2.0 - PADDLE_X_OFFSET)
-            } else {
-                ARENA_WIDTH / 2.0 - PADDLE_X_OFFSET
-            };
-            let distance = (event.ball_position.x - paddle_x).abs();
+            let distance = (ball_position.x - paddle_x(paddle.player_index)).abs();
             let close_threshold = PADDLE_X_OFFSET + PADDLE_WIDTH;
             if distance < close_threshold {
                 flash.timer = Timer::from_seconds(FLASH_DURATION_SECS, TimerMode::Once);
@@ -677,13 +415,13 @@ fn closest_paddle_spray_angle(
     }
 }
 
-fn handle_wall_bounce_effects(
-    mut commands: Commands,
-    mut events: MessageReader<WallBounceEvent>,
-) {
+fn handle_wall_bounce_effects(mut commands: Commands, mut events: MessageReader<SimEvent>) {
     for event in events.read() {
+        let SimEvent::WallBounce { position } = *event else {
+            continue;
+        };
         // Spray downward if at top wall, upward if at bottom wall
-        let spray_angle = if event.ball_position.y > 0.0 {
+        let spray_angle = if position.y > 0.0 {
             -std::f32::consts::FRAC_PI_2 // spray downward
         } else {
             std::f32::consts::FRAC_PI_2 // spray upward
@@ -691,7 +429,7 @@ fn handle_wall_bounce_effects(
 
         spawn_particles(
             &mut commands,
-            event.ball_position,
+            position,
             WALL_BOUNCE_PARTICLE_COUNT,
             spray_angle,
             std::f32::consts::PI,
@@ -702,16 +440,16 @@ fn handle_wall_bounce_effects(
 
 fn handle_score_effects(
     mut commands: Commands,
-    mut events: MessageReader<ScoreEvent>,
+    mut events: MessageReader<SimEvent>,
     mut shake: ResMut<ScreenShake>,
 ) {
-    for _event in events.read() {
-        shake.trauma = (shake.trauma + SCORE_TRAUMA).min(1.0);
+    for _ in events.read().filter(|event| matches!(event, SimEvent::Score { .. })) {
+        shake.add_trauma(SCORE_TRAUMA);
 
         let full_circle = std::f32::consts::TAU;
         spawn_particles(
             &mut commands,
-            Vec3::ZERO,
+            Vec2::ZERO,
             SCORE_PARTICLE_COUNT,
             0.0,
             full_circle,
@@ -720,64 +458,6 @@ fn handle_score_effects(
     }
 }
 
-fn spawn_particles(
-    commands: &mut Commands,
-    position: Vec3,
-    count: usize,
-    center_angle: f32,
-    spread: f32,
-    color: Color,
-) {
-    let half_spread = spread / 2.0;
-
-    for i in 0..count {
-        let fraction = if count <= 1 {
-            0.5
-        } else {
-            i as f32 / (count - 1) as f32
-        };
-        let angle = center_angle - half_spread + spread * fraction;
-        let direction = Vec2::new(angle.cos(), angle.sin());
-        let velocity = direction * PARTICLE_SPEED;
-
-        commands.spawn((
-            Particle {
-                lifetime: Timer::from_seconds(PARTICLE_LIFETIME_SECS, TimerMode::Once),
-                initial_size: PARTICLE_SIZE,
-            },
-            Velocity(velocity),
-            Sprite {
-                color,
-                custom_size: Some(Vec2::splat(PARTICLE_SIZE)),
-                ..default()
-            },
-            Transform::from_translation(position.with_z(2.0)),
-        ));
-    }
-}
-
-fn update_particles(
-    mut commands: Commands,
-    time: Res<Time>,
-    mut particles: Query<(Entity, &mut Particle, &Velocity, &mut Sprite, &mut Transform)>,
-) {
-    let dt = time.delta_secs();
-
-    for (entity, mut particle, velocity, mut sprite, mut transform) in &mut particles {
-        particle.lifetime.tick(time.delta());
-        transform.translation += velocity.0.extend(0.0) * dt;
-
-        let remaining = 1.0 - particle.lifetime.fraction();
-        let size = particle.initial_size * remaining;
-        sprite.custom_size = Some(Vec2::splat(size.max(0.1)));
-        sprite.color = with_alpha(sprite.color, remaining);
-
-        if particle.lifetime.is_finished() {
-            commands.entity(entity).despawn();
-        }
-    }
-}
-
 // --- Paddle flash ---
 
 fn update_paddle_flash(time: Res<Time>, mut query: Query<(&mut PaddleFlash, &mut Sprite)>) {
@@ -793,37 +473,6 @@ fn update_paddle_flash(time: Res<Time>, mut query: Query<(&mut PaddleFlash, &mut
     }
 }
 
-// --- Screen shake ---
-
-fn apply_screen_shake(
-    time: Res<Time>,
-    mut shake: ResMut<ScreenShake>,
-    mut frame_count: ResMut<FrameCount>,
-    mut camera: Query<&mut Transform, With<Camera2d>>,
-) {
-    frame_count.0 = frame_count.0.wrapping_add(1);
-    let dt = time.delta_secs();
-
-    shake.trauma = (shake.trauma - TRAUMA_DECAY_RATE * dt).max(0.0);
-
-    for mut transform in &mut camera {
-        if shake.trauma > 0.001 {
-            let intensity = shake.trauma * shake.trauma;
-            let frame = frame_count.0 as f32;
-
-            // Deterministic pseudo-random offset using sin of frame * primes
-            let offset_x = (frame * 97.0).sin() * MAX_SHAKE_OFFSET * intensity;
-            let offset_y = (frame * 53.0).sin() * MAX_SHAKE_OFFSET * intensity;
-
-            transform.translation.x = offset_x;
-            transform.translation.y = offset_y;
-        } else {
-            transform.translation.x = 0.0;
-            transform.translation.y = 0.0;
-        }
-    }
-}
-
 // ---------------------------------------------------------------------------
 // Audio plugin: plays sounds on events
 // ---------------------------------------------------------------------------
@@ -832,10 +481,10 @@ struct NeonAudioPlugin;
 
 impl Plugin for NeonAudioPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, load_sound_assets)
+        app.add_systems(Startup, synthesize_sounds)
             .add_systems(
                 Update,
-                (play_hit_sound, play_bounce_sound, play_score_sound),
+                play_event_sounds,
             );
     }
 }
@@ -847,71 +496,26 @@ struct SoundAssets {
     score: Handle<AudioSource>,
 }
 
-fn load_sound_assets(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn synthesize_sounds(mut commands: Commands, mut sources: ResMut<Assets<AudioSource>>) {
+    let mut add = |sound: sound_synth::Sound| sources.add(sound.audio_source());
     commands.insert_resource(SoundAssets {
-        hit: asset_server.load("local/sounds/hit.wav"),
-        bounce: asset_server.load("local/sounds/bounce.wav"),
-        score: asset_server.load("local/sounds/score.wav"),
+        hit: add(presets::hit()),
+        bounce: add(presets::bounce()),
+        score: add(presets::score()),
     });
 }
 
-fn play_hit_sound(
-    mut commands: Commands,
-    mut events: MessageReader<PaddleHitEvent>,
-    sounds: Res<SoundAssets>,
-) {
-    for _event in events.read() {
-        commands.spawn((
-            AudioPlayer::new(sounds.hit.clone()),
-            PlaybackSettings::DESPAWN,
-        ));
-    }
-}
-
-fn play_bounce_sound(
-    mut commands: Commands,
-    mut events: MessageReader<WallBounceEvent>,
-    sounds: Res<SoundAssets>,
-) {
-    for _event in events.read() {
-        commands.spawn((
-            AudioPlayer::new(sounds.bounce.clone()),
-            PlaybackSettings::DESPAWN,
-        ));
-    }
-}
-
-fn play_score_sound(
+fn play_event_sounds(
     mut commands: Commands,
-    mut events: MessageReader<ScoreEvent>,
+    mut events: MessageReader<SimEvent>,
     sounds: Res<SoundAssets>,
 ) {
-    for _event in events.read() {
-        commands.spawn((
-            AudioPlayer::new(sounds.score.clone()),
-            PlaybackSettings::DESPAWN,
-        ));
+    for event in events.read() {
+        let sound = match event {
+            SimEvent::PaddleHit { .. } => &sounds.hit,
+            SimEvent::WallBounce { .. } => &sounds.bounce,
+            SimEvent::Score { .. } => &sounds.score,
+        };
+        commands.spawn((AudioPlayer::new(sound.clone()), PlaybackSettings::DESPAWN));
     }
 }
-
-// ---------------------------------------------------------------------------
-// Color helpers
-// ---------------------------------------------------------------------------
-
-fn with_alpha(color: Color, alpha: f32) -> Color {
-    let linear = LinearRgba::from(color);
-    Color::linear_rgba(linear.red, linear.green, linear.blue, alpha)
-}
-
-fn lerp_color(a: Color, b: Color, t: f32) -> Color {
-    let a_lin = LinearRgba::from(a);
-    let b_lin = LinearRgba::from(b);
-    let t_clamped = t.clamp(0.0, 1.0);
-
-    Color::linear_rgba(
-        a_lin.red + (b_lin.red - a_lin.red) * t_clamped,
-        a_lin.green + (b_lin.green - a_lin.green) * t_clamped,
-        a_lin.blue + (b_lin.blue - a_lin.blue) * t_clamped,
-        a_lin.alpha + (b_lin.alpha - a_lin.alpha) * t_clamped,
-    )
-}
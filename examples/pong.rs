@@ -2,10 +2,33 @@
 //!
 //! Run with: `cargo run --example pong`
 //!
-//! Connect two gamepads and use the left stick Y-axis to move paddles.
-//! Unconnected paddles simply stay still.
-
+//! Connect two gamepads and use the left stick Y-axis (or the D-pad) to
+//! move paddles. Unconnected paddles simply stay still. A pad rumbles when
+//! its paddle hits the ball, and harder when a point is lost.
+//!
+//! Pads come from Bevy's gamepads at first. F2 switches to XInput, then to
+//! SDL2 when built with `--features sdl`, for when Bevy mixes up two
+//! identical pads (see `docs/research/gilrs-dual-gamepad-bug.md`).
+//!
+//! Quitting saves the match. The next run offers to resume it: South (or
+//! Enter) resumes, East (or Escape) starts a new match.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use arcade_core::snapshot::{self, Snapshot};
+use arcade_input::{
+    Action, ActionState, ActionSystems, ArcadeInputPlugin, InputAssignments, PadBackend,
+    PlayerAssignment, Rumble, XInputPlugin,
+};
+use arcade_prelude::*;
 use bevy::prelude::*;
+use pong_sim::fixed::Fx;
+use pong_sim::{
+    ARENA_HEIGHT, ARENA_WIDTH, BALL_SIZE, Ball, PADDLE_HEIGHT, PADDLE_WIDTH, PLAYER_COUNT, Paddle,
+    PongState, SimEvent, paddle_x,
+};
+use serde::{Deserialize, Serialize};
 
 fn main() {
     App::new()
@@ -21,209 +44,233 @@ struct PongPlugin;
 
 impl Plugin for PongPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins((PongInputPlugin, PongGamePlugin, PongRenderPlugin));
+        app.add_plugins((PongInputPlugin, PongGamePlugin, PongSavePlugin, PongRenderPlugin));
     }
 }
 
 // ---------------------------------------------------------------------------
-// Arena and gameplay constants
-// ---------------------------------------------------------------------------
-
-const ARENA_WIDTH: f32 = 800.0;
-const ARENA_HEIGHT: f32 = 500.0;
-const PADDLE_WIDTH: f32 = 15.0;
-const PADDLE_HEIGHT: f32 = 80.0;
-const PADDLE_X_OFFSET: f32 = 30.0;
-const PADDLE_SPEED: f32 = 400.0;
-const BALL_SIZE: f32 = 12.0;
-const BALL_INITIAL_SPEED: f32 = 300.0;
-const BALL_SPEED_INCREASE: f32 = 25.0;
-const PADDLE_HIT_ANGLE_FACTOR: f32 = 0.5;
-const PLAYER_COUNT: usize = 2;
-
-// ---------------------------------------------------------------------------
-// Input plugin: reads gamepads into paddle movement intent
+// Input plugin: reads gamepads into paddle movement intent, and rumbles them
+// on the simulation's events
 // ---------------------------------------------------------------------------
 
 struct PongInputPlugin;
 
 impl Plugin for PongInputPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<PaddleInput>()
-            .add_systems(Update, read_paddle_input);
+        app.add_plugins((ArcadeInputPlugin, XInputPlugin))
+            .init_resource::<PadBackend>()
+            .init_resource::<PaddleInput>()
+            .add_systems(PreUpdate, assign_pads.before(ActionSystems))
+            .add_systems(
+                Update,
+                (switch_pad_backend, read_paddle_input, rumble_on_events),
+            );
+        #[cfg(feature = "sdl")]
+        app.add_plugins(arcade_input::SdlPlugin);
+    }
+}
+
+fn switch_pad_backend(keys: Res<ButtonInput<KeyCode>>, mut backend: ResMut<PadBackend>) {
+    if keys.just_pressed(KeyCode::F2) {
+        *backend = backend.next();
+        info!("reading pads from {:?}", *backend);
     }
 }
 
+/// Player n holds the backend's nth pad.
+fn assign_pads(backend: Res<PadBackend>, mut assignments: ResMut<InputAssignments>) {
+    if !backend.is_changed() {
+        return;
+    }
+    assignments.0 = (0..PLAYER_COUNT)
+        .map(|pad| PlayerAssignment {
+            devices: vec![backend.device(pad)],
+            ..default()
+        })
+        .collect();
+}
+
 #[derive(Resource, Default)]
 struct PaddleInput {
     movement: [f32; PLAYER_COUNT],
 }
 
-fn read_paddle_input(gamepads: Query<&Gamepad>, mut input: ResMut<PaddleInput>) {
-    let mut gamepad_iter = gamepads.iter();
+fn read_paddle_input(actions: Res<ActionState>, mut input: ResMut<PaddleInput>) {
+    for (player, slot) in input.movement.iter_mut().enumerate() {
+        *slot = actions.player(player).value(Action::MoveY);
+    }
+}
 
-    for slot in &mut input.movement {
-        let Some(gamepad) = gamepad_iter.next() else {
-            *slot = 0.0;
-            continue;
-        };
-        *slot = gamepad.left_stick().y;
+/// A buzz for the hitter, and a thud for whoever let a point in.
+fn rumble_on_events(mut events: MessageReader<SimEvent>, mut rumbles: MessageWriter<Rumble>) {
+    for event in events.read() {
+        match *event {
+            SimEvent::PaddleHit { player_index, .. } => {
+                rumbles.write(Rumble {
+                    player: player_index,
+                    strong: 0.0,
+                    weak: HIT_RUMBLE,
+                    duration: HIT_RUMBLE_DURATION,
+                });
+            }
+            SimEvent::Score { player_index } => {
+                let conceded = (0..PLAYER_COUNT).filter(|&player| player != player_index);
+                rumbles.write_batch(conceded.map(|player| Rumble {
+                    player,
+                    strong: SCORE_RUMBLE,
+                    weak: 0.0,
+                    duration: SCORE_RUMBLE_DURATION,
+                }));
+            }
+            SimEvent::WallBounce { .. } => {}
+        }
     }
 }
 
+const HIT_RUMBLE: f32 = 0.5;
+const HIT_RUMBLE_DURATION: Duration = Duration::from_millis(80);
+const SCORE_RUMBLE: f32 = 0.9;
+const SCORE_RUMBLE_DURATION: Duration = Duration::from_millis(300);
+
 // ---------------------------------------------------------------------------
-// Game plugin: ball physics, collisions, scoring
+// Game plugin: steps the shared simulation (see pong_sim)
 // ---------------------------------------------------------------------------
 
 struct PongGamePlugin;
 
 impl Plugin for PongGamePlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<Score>()
-            .init_resource::<BallResetCounter>()
-            .add_systems(
-                FixedUpdate,
-                (
-                    move_paddles,
-                    move_ball,
-                    ball_wall_bounce,
-                    ball_paddle_bounce,
-                    check_scoring,
-                )
-                    .chain(),
-            );
+        app.init_resource::<PongState>()
+            .add_message::<SimEvent>()
+            .add_systems(FixedUpdate, step_pong.run_if(match_in_progress));
     }
 }
 
-#[derive(Component)]
-struct Paddle {
-    player_index: usize,
-}
-
-#[derive(Component)]
-struct Ball;
-
-#[derive(Component)]
-struct Velocity(Vec2);
-
-#[derive(Resource, Default)]
-struct Score {
-    points: [u32; PLAYER_COUNT],
-}
-
-#[derive(Resource, Default)]
-struct BallResetCounter(u32);
-
-fn move_paddles(
+fn step_pong(
     input: Res<PaddleInput>,
     time: Res<Time>,
-    mut paddles: Query<(&mut Transform, &Paddle)>,
+    mut state: ResMut<PongState>,
+    mut events: MessageWriter<SimEvent>,
 ) {
-    let dt = time.delta_secs();
-    let max_paddle_y = (ARENA_HEIGHT - PADDLE_HEIGHT) / 2.0;
-
-    for (mut transform, paddle) in &mut paddles {
-        let movement = input.movement[paddle.player_index];
-        transform.translation.y += movement * PADDLE_SPEED * dt;
-        transform.translation.y = transform.translation.y.clamp(-max_paddle_y, max_paddle_y);
-    }
+    events.write_batch(state.step(&input.movement, Fx::from_f32(time.delta_secs())));
 }
 
-fn move_ball(time: Res<Time>, mut ball: Query<(&mut Transform, &Velocity), With<Ball>>) {
-    let dt = time.delta_secs();
-    for (mut transform, velocity) in &mut ball {
-        transform.translation += velocity.0.extend(0.0) * dt;
-    }
-}
+// ---------------------------------------------------------------------------
+// Save plugin: snapshot the match on quit, offer to resume it on start
+// ---------------------------------------------------------------------------
 
-fn ball_wall_bounce(mut ball: Query<(&Transform, &mut Velocity), With<Ball>>) {
-    let max_ball_y = (ARENA_HEIGHT - BALL_SIZE) / 2.0;
+struct PongSavePlugin;
 
-    for (transform, mut velocity) in &mut ball {
-        let y = transform.translation.y;
-        if (y >= max_ball_y && velocity.0.y > 0.0)
-            || (y <= -max_ball_y && velocity.0.y < 0.0)
-        {
-            velocity.0.y = -velocity.0.y;
-        }
+impl Plugin for PongSavePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SaveDir(snapshot::default_dir()))
+            .init_resource::<ResumeOffer>()
+            .add_systems(Startup, offer_saved_match.after(setup_pong))
+            .add_systems(Update, answer_resume_prompt.run_if(resume_offered))
+            .add_systems(Last, save_on_exit);
     }
 }
 
-fn ball_paddle_bounce(
-    mut ball_query: Query<(&Transform, &mut Velocity), With<Ball>>,
-    paddle_query: Query<(&Transform, &Paddle), Without<Ball>>,
-    input: Res<PaddleInput>,
-) {
-    let paddle_half_w = PADDLE_WIDTH / 2.0;
-    let paddle_half_h = PADDLE_HEIGHT / 2.0;
-    let ball_half = BALL_SIZE / 2.0;
-
-    for (ball_transform, mut ball_velocity) in &mut ball_query {
-        let ball_pos = ball_transform.translation;
+/// Everything needed to pick a match back up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PongSnapshot {
+    state: PongState,
+}
 
-        for (paddle_transform, paddle) in &paddle_query {
-            let paddle_pos = paddle_transform.translation;
+impl Snapshot for PongSnapshot {
+    const GAME: &'static str = "pong";
+    const VERSION: u32 = 3;
+}
 
-            let overlap_x = (ball_pos.x - paddle_pos.x).abs() < paddle_half_w + ball_half;
-            let overlap_y = (ball_pos.y - paddle_pos.y).abs() < paddle_half_h + ball_half;
+#[derive(Resource)]
+struct SaveDir(PathBuf);
 
-            if !overlap_x || !overlap_y {
-                continue;
-            }
+/// A saved match the players have not yet chosen to resume or abandon. The
+/// game holds still until they answer.
+#[derive(Resource, Default)]
+struct ResumeOffer(Option<PongSnapshot>);
 
-            let ball_moving_toward_paddle = if paddle_pos.x < 0.0 {
-                ball_velocity.0.x < 0.0
-            } else {
-                ball_velocity.0.x > 0.0
-            };
+#[derive(Component)]
+struct ResumePrompt;
 
-            if !ball_moving_toward_paddle {
-                continue;
-            }
+const PROMPT_FONT_SIZE: f32 = 28.0;
 
-            ball_velocity.0.x = -ball_velocity.0.x;
+fn match_in_progress(offer: Res<ResumeOffer>) -> bool {
+    offer.0.is_none()
+}
 
-            let paddle_movement = input.movement[paddle.player_index];
-            ball_velocity.0.y += paddle_movement * PADDLE_SPEED * PADDLE_HIT_ANGLE_FACTOR;
+fn resume_offered(offer: Res<ResumeOffer>) -> bool {
+    offer.0.is_some()
+}
 
-            let current_speed = ball_velocity.0.length();
-            let new_speed = current_speed + BALL_SPEED_INCREASE;
-            ball_velocity.0 = ball_velocity.0.normalize() * new_speed;
-        }
-    }
+fn offer_saved_match(mut commands: Commands, dir: Res<SaveDir>, mut offer: ResMut<ResumeOffer>) {
+    let Some(saved) = snapshot::load::<PongSnapshot>(&dir.0) else {
+        return;
+    };
+    commands.spawn((
+        ResumePrompt,
+        Text::new(format!(
+            "Resume last match ({} : {})?\nSouth / Enter: resume    East / Esc: new match",
+            saved.state.score[0], saved.state.score[1]
+        )),
+        TextFont::from_font_size(PROMPT_FONT_SIZE),
+        TextColor::WHITE,
+        TextLayout::new_with_justify(Justify::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(SCORE_TOP_MARGIN),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+    ));
+    offer.0 = Some(saved);
 }
 
-fn check_scoring(
-    mut ball_query: Query<(&mut Transform, &mut Velocity), With<Ball>>,
-    mut score: ResMut<Score>,
-    mut reset_counter: ResMut<BallResetCounter>,
+fn answer_resume_prompt(
+    mut commands: Commands,
+    gamepads: Query<&Gamepad>,
+    keys: Res<ButtonInput<KeyCode>>,
+    dir: Res<SaveDir>,
+    mut offer: ResMut<ResumeOffer>,
+    mut state: ResMut<PongState>,
+    prompt: Query<Entity, With<ResumePrompt>>,
 ) {
-    let score_boundary_x = ARENA_WIDTH / 2.0 + BALL_SIZE;
-
-    for (mut transform, mut velocity) in &mut ball_query {
-        let x = transform.translation.x;
-
-        let scoring_player = if x < -score_boundary_x {
-            Some(1)
-        } else if x > score_boundary_x {
-            Some(0)
-        } else {
-            None
-        };
-
-        let Some(scorer) = scoring_player else {
-            continue;
-        };
-
-        score.points[scorer] += 1;
-        reset_counter.0 += 1;
+    let pressed = |button: GamepadButton| gamepads.iter().any(|gamepad| gamepad.just_pressed(button));
+    let resume = pressed(GamepadButton::South) || keys.just_pressed(KeyCode::Enter);
+    let start_over = pressed(GamepadButton::East) || keys.just_pressed(KeyCode::Escape);
+    if !resume && !start_over {
+        return;
+    }
 
-        transform.translation = Vec3::ZERO;
+    let Some(saved) = offer.0.take() else {
+        return;
+    };
+    if resume {
+        *state = saved.state;
+    } else if let Err(e) = snapshot::discard::<PongSnapshot>(&dir.0) {
+        warn!("could not delete saved match: {e}");
+    }
+    for entity in &prompt {
+        commands.entity(entity).despawn();
+    }
+}
 
-        let direction_x = if scorer == 0 { -1.0 } else { 1.0 };
-        let direction_y = if reset_counter.0.is_multiple_of(2) { 1.0 } else { -1.0 };
-        let direction = Vec2::new(direction_x, direction_y * 0.5).normalize();
-        velocity.0 = direction * BALL_INITIAL_SPEED;
+/// Save the match as the app quits. A match nobody resumed is left as it was
+/// on disk, so quitting at the prompt does not lose it.
+fn save_on_exit(
+    mut exits: MessageReader<AppExit>,
+    dir: Res<SaveDir>,
+    offer: Res<ResumeOffer>,
+    state: Res<PongState>,
+) {
+    if exits.read().last().is_none() || offer.0.is_some() {
+        return;
+    }
+    let saved = PongSnapshot { state: *state };
+    match snapshot::save(&dir.0, &saved) {
+        Ok(()) => info!("saved match to {}", dir.0.display()),
+        Err(e) => warn!("could not save match: {e}"),
     }
 }
 
@@ -236,7 +283,7 @@ struct PongRenderPlugin;
 impl Plugin for PongRenderPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, setup_pong)
-            .add_systems(Update, update_score_display);
+            .add_systems(Update, (draw_pong, update_score_display));
     }
 }
 
@@ -246,7 +293,7 @@ struct ScoreText;
 const SCORE_FONT_SIZE: f32 = 48.0;
 const SCORE_TOP_MARGIN: f32 = 20.0;
 const BORDER_THICKNESS: f32 = 4.0;
-const BORDER_COLOR: Color = Color::srgb(0.3, 0.3, 0.3);
+const BORDER_COLOR: Color = palette::BORDER_GRAY;
 const PADDLE_COLOR: Color = Color::WHITE;
 const BALL_COLOR: Color = Color::WHITE;
 const CENTER_LINE_DASH_COUNT: usize = 15;
@@ -256,10 +303,7 @@ fn setup_pong(mut commands: Commands) {
     commands.spawn(Camera2d);
 
     // Arena borders
-    spawn_border(&mut commands, Vec3::new(0.0, ARENA_HEIGHT / 2.0, 0.0), ARENA_WIDTH, BORDER_THICKNESS);
-    spawn_border(&mut commands, Vec3::new(0.0, -ARENA_HEIGHT / 2.0, 0.0), ARENA_WIDTH, BORDER_THICKNESS);
-    spawn_border(&mut commands, Vec3::new(-ARENA_WIDTH / 2.0, 0.0, 0.0), BORDER_THICKNESS, ARENA_HEIGHT);
-    spawn_border(&mut commands, Vec3::new(ARENA_WIDTH / 2.0, 0.0, 0.0), BORDER_THICKNESS, ARENA_HEIGHT);
+    spawn_arena_walls(&mut commands, ARENA_WIDTH, ARENA_HEIGHT, BORDER_THICKNESS, BORDER_COLOR);
 
     // Center line (dashed)
     let dash_spacing = ARENA_HEIGHT / CENTER_LINE_DASH_COUNT as f32;
@@ -277,19 +321,13 @@ fn setup_pong(mut commands: Commands) {
     }
 
     // Paddles
-    let left_paddle_x = -(ARENA_WIDTH / 2.0 - PADDLE_X_OFFSET);
-    let right_paddle_x = ARENA_WIDTH / 2.0 - PADDLE_X_OFFSET;
-
-    spawn_paddle(&mut commands, left_paddle_x, 0);
-    spawn_paddle(&mut commands, right_paddle_x, 1);
+    for player_index in 0..PLAYER_COUNT {
+        spawn_paddle(&mut commands, player_index);
+    }
 
     // Ball
-    let initial_direction = Vec2::new(1.0, 0.5).normalize();
-    let initial_velocity = initial_direction * BALL_INITIAL_SPEED;
-
     commands.spawn((
         Ball,
-        Velocity(initial_velocity),
         Sprite {
             color: BALL_COLOR,
             custom_size: Some(Vec2::splat(BALL_SIZE)),
@@ -313,18 +351,7 @@ fn setup_pong(mut commands: Commands) {
     ));
 }
 
-fn spawn_border(commands: &mut Commands, position: Vec3, width: f32, height: f32) {
-    commands.spawn((
-        Sprite {
-            color: BORDER_COLOR,
-            custom_size: Some(Vec2::new(width, height)),
-            ..default()
-        },
-        Transform::from_translation(position),
-    ));
-}
-
-fn spawn_paddle(commands: &mut Commands, x: f32, player_index: usize) {
+fn spawn_paddle(commands: &mut Commands, player_index: usize) {
     commands.spawn((
         Paddle { player_index },
         Sprite {
@@ -332,15 +359,29 @@ fn spawn_paddle(commands: &mut Commands, x: f32, player_index: usize) {
             custom_size: Some(Vec2::new(PADDLE_WIDTH, PADDLE_HEIGHT)),
             ..default()
         },
-        Transform::from_xyz(x, 0.0, 0.0),
+        Transform::from_xyz(paddle_x(player_index), 0.0, 0.0),
     ));
 }
 
-fn update_score_display(score: Res<Score>, mut query: Query<&mut Text, With<ScoreText>>) {
-    if !score.is_changed() {
+/// Move the sprites to where the simulation has the paddles and ball.
+fn draw_pong(
+    state: Res<PongState>,
+    mut paddles: Query<(&Paddle, &mut Transform), Without<Ball>>,
+    mut ball: Query<&mut Transform, With<Ball>>,
+) {
+    for (paddle, mut transform) in &mut paddles {
+        transform.translation = state.paddle_position(paddle.player_index).extend(0.0);
+    }
+    for mut transform in &mut ball {
+        transform.translation = state.ball_position().extend(0.0);
+    }
+}
+
+fn update_score_display(state: Res<PongState>, mut query: Query<&mut Text, With<ScoreText>>) {
+    if !state.is_changed() {
         return;
     }
     for mut text in &mut query {
-        **text = format!("{}  :  {}", score.points[0], score.points[1]);
+        **text = format!("{}  :  {}", state.score[0], state.score[1]);
     }
 }
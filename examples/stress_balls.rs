@@ -2,6 +2,7 @@
 //!
 //! Run with: `cargo run --example stress_balls --release`
 
+use arcade_prelude::Velocity;
 use bevy::diagnostic::{DiagnosticsStore, EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin};
 use bevy::ecs::batching::BatchingStrategy;
 use bevy::prelude::*;
@@ -76,9 +77,6 @@ impl StressConfig {
 #[derive(Component)]
 struct Ball;
 
-#[derive(Component)]
-struct Velocity(Vec2);
-
 #[derive(Resource)]
 struct WhitePixel(Handle<Image>);
 
@@ -0,0 +1,61 @@
+//! Scaffolding for lockstep games played through the relay.
+//!
+//! A game that can step its whole state from every player's input, and
+//! nothing else, implements [`LockstepSim`] for that state. [`Session`] then
+//! does the rest of what net_pong does by hand: Hello until seated, local
+//! input sent a few ticks ahead, each tick played once every player's input
+//! for it has arrived, acks, and a checksum now and then to catch a desync,
+//! which is mended from slot 0's snapshot. A session never touches a socket;
+//! it takes datagrams in and hands datagrams out, so tests can run it
+//! against a relay in memory.
+//!
+//! [`LockstepPlugin`] drives a session over UDP in a Bevy app: the game
+//! writes [`LocalInput`] each frame and reads its state from [`Lockstep`].
+//! Adding another networked game means writing its simulation and its
+//! drawing, not another connection layer.
+
+pub mod plugin;
+pub mod session;
+
+use arcade_buildinfo::BuildInfo;
+use prototype_relay::LockstepInput;
+
+pub use plugin::{LocalInput, Lockstep, LockstepPlugin};
+pub use session::{CHECKSUM_INTERVAL, MatchEnd, Phase, Session, SessionConfig};
+
+/// A game state that every player steps identically, given the same inputs.
+///
+/// Nothing outside the state may feed into it: no clock, no thread-local
+/// random numbers, no iteration order that can differ between machines. The
+/// same seed, player count and inputs must give the same [`checksum`] on
+/// every machine, tick after tick.
+///
+/// [`checksum`]: LockstepSim::checksum
+pub trait LockstepSim: Send + Sync + 'static {
+    /// One player's input for one tick.
+    type Input: LockstepInput + Send + Sync;
+
+    /// The state before tick 0 of a match for `players`, with whatever
+    /// randomness it needs drawn from `seed`.
+    fn init(seed: u64, players: usize) -> Self;
+
+    /// Play one tick. `inputs` has one per player, by slot; a player who
+    /// missed the relay's deadline has the default input.
+    fn step(&mut self, inputs: &[Self::Input]);
+
+    /// A hash of the whole state, compared between players to catch a
+    /// desync. Must not depend on anything but the state.
+    fn checksum(&self) -> u64;
+
+    /// The whole state, for a player whose own has gone wrong.
+    fn snapshot(&self) -> Vec<u8>;
+
+    /// Carry on from `snapshot`. Returns false, leaving the state alone, if
+    /// it is not one [`snapshot`](LockstepSim::snapshot) wrote.
+    fn restore(&mut self, snapshot: &[u8]) -> bool;
+}
+
+/// This crate's part of a game's version report.
+pub fn describe_build(info: BuildInfo) -> BuildInfo {
+    info.with_crate("prototype-lockstep", env!("CARGO_PKG_VERSION"))
+}
@@ -0,0 +1,151 @@
+//! A [`Session`] over UDP, ticking in `FixedUpdate`.
+//!
+//! Each fixed step sends [`LocalInput`] for whatever ticks are due and
+//! plays the next tick if its inputs are in, so the simulation runs at the
+//! tick rate and waits, rather than guesses, when the relay is behind.
+//! Datagrams are read in `PreUpdate` and sent in `PostUpdate`.
+
+use std::marker::PhantomData;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::LockstepSim;
+use crate::session::{Phase, Session, SessionConfig};
+
+/// How often Hello goes out until the relay seats us.
+const HELLO_RETRY: Duration = Duration::from_millis(500);
+/// How long the match may wait on the relay before our unplayed inputs go
+/// out again, in case one was lost.
+const RESEND_AFTER: Duration = Duration::from_millis(250);
+
+/// Plays `S` through the relay at `relay`, `tick_rate` ticks a second.
+pub struct LockstepPlugin<S> {
+    pub relay: SocketAddr,
+    pub tick_rate: u32,
+    pub config: SessionConfig,
+    sim: PhantomData<fn() -> S>,
+}
+
+impl<S: LockstepSim> LockstepPlugin<S> {
+    pub fn new(relay: SocketAddr, tick_rate: u32, config: SessionConfig) -> Self {
+        Self {
+            relay,
+            tick_rate,
+            config,
+            sim: PhantomData,
+        }
+    }
+}
+
+impl<S: LockstepSim> Plugin for LockstepPlugin<S> {
+    fn build(&self, app: &mut App) {
+        let socket = UdpSocket::bind("0.0.0.0:0").expect("failed to bind local UDP socket");
+        socket
+            .set_nonblocking(true)
+            .expect("failed to set non-blocking");
+        app.insert_resource(RelaySocket {
+            socket,
+            relay: self.relay,
+        })
+        .insert_resource(Lockstep(Session::<S>::new(self.config.clone())))
+        .init_resource::<LocalInput<S>>()
+        .insert_resource(Time::<Fixed>::from_hz(f64::from(self.tick_rate)))
+        .add_systems(PreUpdate, receive_datagrams::<S>)
+        .add_systems(Update, say_hello::<S>)
+        .add_systems(FixedUpdate, play_tick::<S>)
+        .add_systems(PostUpdate, send_datagrams::<S>)
+        .add_systems(Last, say_goodbye_on_exit::<S>);
+    }
+}
+
+/// The match, and the simulation in it. Read it to draw the game.
+#[derive(Resource, Deref, DerefMut)]
+pub struct Lockstep<S: LockstepSim>(pub Session<S>);
+
+/// Our input, as the game last set it. Sent for each tick as it comes due.
+#[derive(Resource)]
+pub struct LocalInput<S: LockstepSim>(pub S::Input);
+
+impl<S: LockstepSim> Default for LocalInput<S> {
+    fn default() -> Self {
+        Self(S::Input::default())
+    }
+}
+
+#[derive(Resource)]
+struct RelaySocket {
+    socket: UdpSocket,
+    relay: SocketAddr,
+}
+
+fn receive_datagrams<S: LockstepSim>(net: Res<RelaySocket>, mut lockstep: ResMut<Lockstep<S>>) {
+    let mut buf = [0u8; 2048];
+    while let Ok((len, from)) = net.socket.recv_from(&mut buf) {
+        if from == net.relay {
+            lockstep.receive(&buf[..len]);
+        }
+    }
+}
+
+fn say_hello<S: LockstepSim>(
+    time: Res<Time>,
+    mut lockstep: ResMut<Lockstep<S>>,
+    mut since: Local<Option<Duration>>,
+) {
+    if !matches!(lockstep.phase(), Phase::Connecting | Phase::Queued(_)) {
+        return;
+    }
+    let waited = since.map_or(HELLO_RETRY, |since| since + time.delta());
+    if waited < HELLO_RETRY {
+        *since = Some(waited);
+        return;
+    }
+    *since = Some(Duration::ZERO);
+    lockstep.hello();
+}
+
+/// Send our input for every tick now due, then play one tick if everyone's
+/// input for it is in. Unplayed inputs go out again when the relay has kept
+/// us waiting for `RESEND_AFTER`.
+fn play_tick<S: LockstepSim>(
+    time: Res<Time>,
+    input: Res<LocalInput<S>>,
+    mut lockstep: ResMut<Lockstep<S>>,
+    mut waiting: Local<Duration>,
+) {
+    while lockstep.wants_input() {
+        lockstep.send_input(input.0.clone());
+    }
+    if lockstep.play() || *lockstep.phase() != Phase::Playing {
+        *waiting = Duration::ZERO;
+        return;
+    }
+    *waiting += time.delta();
+    if *waiting >= RESEND_AFTER {
+        *waiting = Duration::ZERO;
+        lockstep.resend_inputs();
+    }
+}
+
+fn send_datagrams<S: LockstepSim>(net: Res<RelaySocket>, mut lockstep: ResMut<Lockstep<S>>) {
+    for datagram in lockstep.take_outgoing() {
+        let _ = net.socket.send_to(&datagram, net.relay);
+    }
+}
+
+/// Free our slot right away rather than leave the relay to time us out.
+fn say_goodbye_on_exit<S: LockstepSim>(
+    mut exits: MessageReader<AppExit>,
+    net: Res<RelaySocket>,
+    mut lockstep: ResMut<Lockstep<S>>,
+) {
+    if exits.read().next().is_none() {
+        return;
+    }
+    lockstep.goodbye();
+    for datagram in lockstep.take_outgoing() {
+        let _ = net.socket.send_to(&datagram, net.relay);
+    }
+}
@@ -0,0 +1,361 @@
+//! One player's side of a lockstep match, without the socket.
+//!
+//! [`Session::receive`] takes a datagram from the relay, and every message
+//! for the relay waits in [`Session::take_outgoing`] until the caller sends
+//! it. What happens in between is all bookkeeping: which slot we are in,
+//! which ticks have every player's input, and whose checksums agree.
+
+use std::collections::BTreeMap;
+
+use prototype_relay::sequence::{SequenceCounter, SequenceFilter, Sequenced};
+use prototype_relay::{
+    AbortReason, ClientMessage, Payload, PlayerSlot, RejectReason, RelayMessage, SPECTATOR_SLOT,
+    Tick, deserialize, serialize,
+};
+
+use crate::LockstepSim;
+
+/// Ticks between checksums sent to the other players.
+pub const CHECKSUM_INTERVAL: Tick = 32;
+/// Our own checksums kept for comparing with late ones from the others.
+const CHECKSUMS_KEPT: usize = 16;
+
+type SimClientMessage<S> = ClientMessage<Payload<<S as LockstepSim>::Input>>;
+type SimRelayMessage<S> = RelayMessage<Payload<<S as LockstepSim>::Input>>;
+
+/// What a session needs to know before saying Hello.
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    /// Names the game, and anything besides the seed and player count that
+    /// changes how it plays (a score limit, a tick rate). The relay only
+    /// seats players with the same one together.
+    pub game_id: String,
+    /// Shown to the other players.
+    pub name: String,
+    /// A standing room to join, or `None` for the unnamed match.
+    pub room: Option<String>,
+    /// Players per match; the relay needs the same.
+    pub players: usize,
+    /// Handed to [`LockstepSim::init`]; everyone in a match needs the same.
+    pub seed: u64,
+    /// Ticks between reading local input and playing it. Must not exceed
+    /// the relay's `--input-window`, or it drops the input.
+    pub input_delay: Tick,
+}
+
+impl SessionConfig {
+    /// The game id Hello carries: the player count and seed are part of the
+    /// game, as far as pairing players goes.
+    pub fn relay_game_id(&self) -> String {
+        format!(
+            "{}-{}-players-seed-{:x}",
+            self.game_id, self.players, self.seed
+        )
+    }
+}
+
+/// Where a session is in its match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Phase {
+    /// Saying Hello; the relay has not seated us yet.
+    Connecting,
+    /// Every slot is taken and we are this far down the relay's queue.
+    Queued(u32),
+    /// Seated, and waiting for the other players.
+    Waiting,
+    Playing,
+    /// Nothing more will be played.
+    Over(MatchEnd),
+}
+
+/// Why a match is over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchEnd {
+    Rejected(RejectReason),
+    /// The player in this slot said Goodbye or went quiet.
+    PlayerLeft(PlayerSlot),
+    Aborted {
+        by_slot: PlayerSlot,
+        reason: AbortReason,
+    },
+    /// The relay shut down.
+    RelayClosed,
+    /// We said Goodbye.
+    Left,
+}
+
+/// One player's lockstep match with the simulation it plays.
+pub struct Session<S: LockstepSim> {
+    config: SessionConfig,
+    sim: S,
+    phase: Phase,
+    slot: Option<PlayerSlot>,
+    /// The next tick to play.
+    tick: Tick,
+    /// The next tick to send local input for.
+    next_input: Tick,
+    /// Every player's input by tick, until the tick is played.
+    inputs: BTreeMap<Tick, Vec<S::Input>>,
+    /// Local input sent for ticks not played yet, to send again if lost.
+    sent: BTreeMap<Tick, S::Input>,
+    /// Our checksum after each of the last few checked ticks.
+    ours: BTreeMap<Tick, u64>,
+    /// The others' checksums for ticks we have not played yet.
+    theirs: BTreeMap<Tick, Vec<(PlayerSlot, u64)>>,
+    /// The first tick whose checksums disagreed, until a snapshot mends it.
+    desync: Option<Tick>,
+    outbound: SequenceCounter,
+    inbound: SequenceFilter,
+    outgoing: Vec<Vec<u8>>,
+}
+
+impl<S: LockstepSim> Session<S> {
+    pub fn new(config: SessionConfig) -> Self {
+        let sim = S::init(config.seed, config.players);
+        Self {
+            config,
+            sim,
+            phase: Phase::Connecting,
+            slot: None,
+            tick: 0,
+            next_input: 0,
+            inputs: BTreeMap::new(),
+            sent: BTreeMap::new(),
+            ours: BTreeMap::new(),
+            theirs: BTreeMap::new(),
+            desync: None,
+            outbound: SequenceCounter::default(),
+            inbound: SequenceFilter::new(),
+            outgoing: Vec::new(),
+        }
+    }
+
+    /// The state as of the start of [`tick`](Session::tick).
+    pub fn sim(&self) -> &S {
+        &self.sim
+    }
+
+    pub fn phase(&self) -> &Phase {
+        &self.phase
+    }
+
+    /// Our slot, once the relay has seated us.
+    pub fn slot(&self) -> Option<PlayerSlot> {
+        self.slot
+    }
+
+    /// The next tick to play.
+    pub fn tick(&self) -> Tick {
+        self.tick
+    }
+
+    /// The first tick whose checksums disagreed, while we are out of sync.
+    pub fn desync(&self) -> Option<Tick> {
+        self.desync
+    }
+
+    /// Datagrams for the relay, oldest first, since the last call.
+    pub fn take_outgoing(&mut self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    fn send(&mut self, message: SimClientMessage<S>) {
+        self.outgoing.push(serialize(&self.outbound.wrap(message)));
+    }
+
+    /// Ask to be seated, or to keep our place in the queue. Repeat until
+    /// the phase moves on; the relay answers every one.
+    pub fn hello(&mut self) {
+        if matches!(self.phase, Phase::Connecting | Phase::Queued(_)) {
+            let hello = ClientMessage::Hello {
+                game_id: self.config.relay_game_id(),
+                name: self.config.name.clone(),
+                room: self.config.room.clone(),
+            };
+            self.send(hello);
+        }
+    }
+
+    /// Leave the match, freeing our slot right away.
+    pub fn goodbye(&mut self) {
+        if !matches!(self.phase, Phase::Over(_)) {
+            self.send(ClientMessage::Goodbye);
+            self.phase = Phase::Over(MatchEnd::Left);
+        }
+    }
+
+    /// Whether local input is due for another tick before the next one is
+    /// played.
+    pub fn wants_input(&self) -> bool {
+        self.phase == Phase::Playing && self.next_input <= self.tick + self.config.input_delay
+    }
+
+    /// Send `input` as ours for the next tick that needs it.
+    pub fn send_input(&mut self, input: S::Input) {
+        let tick = self.next_input;
+        self.next_input += 1;
+        self.sent.insert(tick, input.clone());
+        self.send(ClientMessage::Input {
+            tick,
+            payload: Payload(input),
+        });
+    }
+
+    /// Send every input not yet played again, in case one was lost. For
+    /// when the match has waited on the relay for a while.
+    pub fn resend_inputs(&mut self) {
+        let Some((&first_tick, _)) = self.sent.first_key_value() else {
+            return;
+        };
+        let payloads = self.sent.values().cloned().map(Payload).collect();
+        self.send(ClientMessage::InputBatch {
+            first_tick,
+            payloads,
+        });
+    }
+
+    /// Play the next tick if every player's input for it has arrived, and
+    /// ack it. Returns whether it was played.
+    pub fn play(&mut self) -> bool {
+        if self.phase != Phase::Playing {
+            return false;
+        }
+        let Some(inputs) = self.inputs.remove(&self.tick) else {
+            return false;
+        };
+        self.sim.step(&inputs);
+        let tick = self.tick;
+        self.tick += 1;
+        self.sent.remove(&tick);
+        self.send(ClientMessage::AckTick { tick });
+        if (tick + 1).is_multiple_of(CHECKSUM_INTERVAL) {
+            self.check(tick);
+        }
+        true
+    }
+
+    /// Send our checksum after `tick`, and compare it with any the others
+    /// got there with first.
+    fn check(&mut self, tick: Tick) {
+        let hash = self.sim.checksum();
+        self.send(ClientMessage::StateHash { tick, hash });
+        self.ours.insert(tick, hash);
+        while self.ours.len() > CHECKSUMS_KEPT {
+            self.ours.pop_first();
+        }
+        for (_, theirs) in self.theirs.remove(&tick).unwrap_or_default() {
+            self.compare(tick, theirs);
+        }
+        self.theirs.retain(|&checked, _| checked > tick);
+    }
+
+    /// A checksum that does not match ours starts a desync, which slot 0's
+    /// snapshot mends.
+    fn compare(&mut self, tick: Tick, theirs: u64) {
+        if self.ours.get(&tick).is_none_or(|&ours| ours == theirs) || self.desync.is_some() {
+            return;
+        }
+        self.desync = Some(tick);
+        // Everyone takes slot 0's state, so slot 0 has nothing to ask for.
+        if self.slot != Some(0) {
+            self.send(ClientMessage::RequestSnapshot);
+        }
+    }
+
+    /// Take in one datagram from the relay. Duplicates, stale ones and
+    /// anything unreadable are dropped.
+    pub fn receive(&mut self, datagram: &[u8]) {
+        let Some(sequenced) = deserialize::<Sequenced<SimRelayMessage<S>>>(datagram) else {
+            return;
+        };
+        if self.inbound.accept(sequenced.seq).accepted() {
+            self.handle(sequenced.message);
+        }
+    }
+
+    fn handle(&mut self, message: SimRelayMessage<S>) {
+        match message {
+            RelayMessage::Welcome { player_slot } => {
+                // Welcome can arrive after GameStart.
+                if self.slot.is_none() {
+                    self.slot = Some(player_slot);
+                }
+                if matches!(self.phase, Phase::Connecting | Phase::Queued(_)) {
+                    self.phase = Phase::Waiting;
+                }
+            }
+            RelayMessage::QueuePosition { position } => {
+                if matches!(self.phase, Phase::Connecting | Phase::Queued(_)) {
+                    self.phase = Phase::Queued(position);
+                }
+            }
+            RelayMessage::GameStart => {
+                if matches!(
+                    self.phase,
+                    Phase::Connecting | Phase::Queued(_) | Phase::Waiting
+                ) {
+                    self.phase = Phase::Playing;
+                }
+            }
+            RelayMessage::TickInputs { tick, inputs } => {
+                if tick < self.tick {
+                    // A retransmission: our ack was lost.
+                    self.send(ClientMessage::AckTick { tick });
+                    return;
+                }
+                let mut inputs: Vec<S::Input> =
+                    inputs.into_iter().map(|Payload(input)| input).collect();
+                inputs.resize(self.config.players, S::Input::default());
+                self.inputs.insert(tick, inputs);
+            }
+            RelayMessage::Rejected { reason } => {
+                self.phase = Phase::Over(MatchEnd::Rejected(reason));
+            }
+            RelayMessage::PlayerLeft { slot } if self.phase == Phase::Playing => {
+                self.phase = Phase::Over(MatchEnd::PlayerLeft(slot));
+            }
+            RelayMessage::MatchAborted { by_slot, reason } => {
+                self.phase = Phase::Over(MatchEnd::Aborted { by_slot, reason });
+            }
+            RelayMessage::ServerShutdown => {
+                self.phase = Phase::Over(MatchEnd::RelayClosed);
+            }
+            RelayMessage::StateHash { slot, tick, hash } => {
+                if tick < self.tick {
+                    self.compare(tick, hash);
+                } else {
+                    self.theirs.entry(tick).or_default().push((slot, hash));
+                }
+            }
+            RelayMessage::SnapshotRequested { slot }
+                if slot != SPECTATOR_SLOT && self.phase == Phase::Playing =>
+            {
+                let state = self.sim.snapshot();
+                let tick = self.tick;
+                self.send(ClientMessage::Snapshot { tick, state });
+            }
+            RelayMessage::Snapshot { slot, tick, state } if slot == 0 && self.desync.is_some() => {
+                self.resync(tick, &state);
+            }
+            _ => {}
+        }
+    }
+
+    /// Carry on from slot 0's state at the start of `tick`, asking the relay
+    /// again for any inputs from there that were played already.
+    fn resync(&mut self, tick: Tick, state: &[u8]) {
+        if !self.sim.restore(state) {
+            return;
+        }
+        for missed in tick..self.tick {
+            self.send(ClientMessage::RequestTick { tick: missed });
+        }
+        self.inputs.retain(|&buffered, _| buffered >= tick);
+        self.sent.retain(|&sent, _| sent >= tick);
+        self.tick = tick;
+        self.next_input = self.next_input.max(tick);
+        self.ours.clear();
+        self.theirs.clear();
+        self.desync = None;
+    }
+}
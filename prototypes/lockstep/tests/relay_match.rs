@@ -0,0 +1,298 @@
+//! Two [`Session`]s playing a toy simulation through a relay in memory.
+//!
+//! The relay is the real one, fed datagrams directly as in the relay's own
+//! headless match test, so every run is the same. The simulation is just
+//! enough of a game to notice a wrong input: a random walk per player, with
+//! the randomness drawn from the seed.
+
+use std::cell::Cell;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use prototype_lockstep::{CHECKSUM_INTERVAL, LockstepSim, Phase, Session, SessionConfig};
+use prototype_relay::metrics::Metrics;
+use prototype_relay::server::{DispatchEvent, RelayCore, Settings};
+use prototype_relay::test_support::{self, ManualClock, MemorySocket};
+use prototype_relay::{LockstepInput, Tick, deserialize, serialize};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+const PLAYERS: usize = 2;
+const TICKS: Tick = 200;
+
+thread_local! {
+    /// Set to throw off the next step taken on this thread, as a
+    /// determinism bug would.
+    static GLITCH: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Which way a player pushes their walker this tick.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+struct Push(i8);
+
+impl LockstepInput for Push {}
+
+/// Each player's walker, nudged by their push and by a shared random
+/// stream.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Walk {
+    rng: u64,
+    positions: Vec<i64>,
+}
+
+impl Walk {
+    fn next_random(&mut self) -> i64 {
+        // xorshift64
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng % 5) as i64 - 2
+    }
+}
+
+impl LockstepSim for Walk {
+    type Input = Push;
+
+    fn init(seed: u64, players: usize) -> Self {
+        Walk {
+            rng: seed | 1,
+            positions: vec![0; players],
+        }
+    }
+
+    fn step(&mut self, inputs: &[Push]) {
+        if GLITCH.take() {
+            self.positions[0] += 1000;
+        }
+        for (slot, push) in inputs.iter().enumerate() {
+            let drift = self.next_random();
+            self.positions[slot] += i64::from(push.0) + drift;
+        }
+    }
+
+    fn checksum(&self) -> u64 {
+        serialize(self)
+            .into_iter()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            })
+    }
+
+    fn snapshot(&self) -> Vec<u8> {
+        serialize(self)
+    }
+
+    fn restore(&mut self, snapshot: &[u8]) -> bool {
+        match deserialize::<Walk>(snapshot) {
+            Some(walk) => {
+                *self = walk;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+struct Player {
+    addr: SocketAddr,
+    session: Session<Walk>,
+    /// The checksum after each tick played.
+    checksums: Vec<u64>,
+}
+
+struct Harness {
+    core: RelayCore,
+    reports: mpsc::UnboundedReceiver<DispatchEvent>,
+    socket: Arc<MemorySocket>,
+    players: [Player; PLAYERS],
+}
+
+impl Harness {
+    fn new(test: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("lockstep_{test}_{}", std::process::id()));
+        let settings = Settings {
+            room_capacity: 1,
+            // Nothing is ever re-sent on a timer, and the clock never moves
+            // to refill the rate limit.
+            tick_timeout: Duration::from_secs(3600),
+            input_history: 64,
+            rate_limit: 100_000,
+            rate_burst: 100_000,
+            max_pending: 8,
+            ..test_support::settings(PLAYERS)
+        };
+        let socket = Arc::new(MemorySocket::default());
+        let (core, reports) = RelayCore::new(
+            settings,
+            dir.join("rooms.toml"),
+            socket.clone(),
+            Arc::new(ManualClock::new()),
+            None,
+            Arc::new(Metrics::new()),
+        );
+        let player = |port: u16, name: &str| Player {
+            addr: test_support::addr(port),
+            session: Session::new(SessionConfig {
+                game_id: "walk".into(),
+                name: name.into(),
+                room: None,
+                players: PLAYERS,
+                seed: 7,
+                input_delay: 2,
+            }),
+            checksums: Vec::new(),
+        };
+        Self {
+            core,
+            reports,
+            socket,
+            players: [player(1, "alice"), player(2, "bob")],
+        }
+    }
+
+    /// Deliver everything player `index` has to send, and wait until all
+    /// it set off has been sent.
+    async fn deliver(&mut self, index: usize) {
+        let from = self.players[index].addr;
+        for datagram in self.players[index].session.take_outgoing() {
+            self.core.handle_datagram(&datagram, from);
+        }
+        for _ in 0..8 {
+            tokio::task::yield_now().await;
+            while let Ok(event) = self.reports.try_recv() {
+                self.core.handle_dispatch_event(event);
+            }
+        }
+        self.core.flush();
+    }
+
+    /// Let every player read what the relay sent them.
+    fn receive(&mut self) {
+        for player in &mut self.players {
+            for datagram in self.socket.take_datagrams(player.addr) {
+                player.session.receive(&datagram);
+            }
+        }
+    }
+
+    async fn start(&mut self) {
+        for index in 0..PLAYERS {
+            self.players[index].session.hello();
+            self.deliver(index).await;
+        }
+        self.receive();
+    }
+
+    /// One round: each player sends what input is due, pushing one way on
+    /// even ticks and the other on odd ones, then plays what it can.
+    async fn round(&mut self) {
+        for index in 0..PLAYERS {
+            let session = &mut self.players[index].session;
+            while session.wants_input() {
+                let push = if session.tick().is_multiple_of(2) { 1 } else { -1 };
+                session.send_input(Push(push * (index as i8 + 1)));
+            }
+            self.deliver(index).await;
+        }
+        self.receive();
+        for index in 0..PLAYERS {
+            let player = &mut self.players[index];
+            while player.session.play() {
+                player.checksums.push(player.session.sim().checksum());
+            }
+            self.deliver(index).await;
+        }
+        self.receive();
+    }
+
+    /// A round in which player `index` plays one tick wrong, as a
+    /// determinism bug would, and nobody else plays at all.
+    async fn glitch(&mut self, index: usize) {
+        loop {
+            for sender in 0..PLAYERS {
+                let session = &mut self.players[sender].session;
+                while session.wants_input() {
+                    session.send_input(Push(0));
+                }
+                self.deliver(sender).await;
+            }
+            self.receive();
+            GLITCH.set(true);
+            let played = self.players[index].session.play();
+            GLITCH.set(false);
+            if played {
+                self.deliver(index).await;
+                return;
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn both_players_reach_the_same_state_on_every_tick() {
+    // given two sessions the relay has started a match for
+    let mut harness = Harness::new("agree");
+    harness.start().await;
+    assert!(
+        harness
+            .players
+            .iter()
+            .all(|player| *player.session.phase() == Phase::Playing)
+    );
+
+    // when they play a couple of hundred ticks
+    while harness
+        .players
+        .iter()
+        .any(|player| player.checksums.len() < TICKS as usize)
+    {
+        harness.round().await;
+    }
+
+    // then they agreed after every tick, and never saw a desync
+    let [alice, bob] = &harness.players;
+    let parted = alice
+        .checksums
+        .iter()
+        .zip(&bob.checksums)
+        .position(|(a, b)| a != b);
+    assert_eq!(parted, None, "the players' states parted on this tick");
+    assert_eq!(alice.session.desync(), None);
+    assert_eq!(bob.session.desync(), None);
+}
+
+#[tokio::test]
+async fn a_desync_is_mended_from_slot_zeros_snapshot() {
+    // given a match under way
+    let mut harness = Harness::new("mend");
+    harness.start().await;
+    while harness.players[1].session.tick() < 10 {
+        harness.round().await;
+    }
+    let bob = harness
+        .players
+        .iter()
+        .position(|player| player.session.slot() == Some(1))
+        .expect("somebody is in slot 1");
+
+    // when slot 1's next tick goes wrong
+    harness.glitch(bob).await;
+
+    // and the match plays on past the next checksum or two
+    let until = harness.players[bob].session.tick() + 3 * CHECKSUM_INTERVAL;
+    let mut desynced = false;
+    while harness.players[bob].session.tick() < until {
+        harness.round().await;
+        desynced |= harness.players[bob].session.desync().is_some();
+    }
+
+    // then it noticed, took slot 0's state, and agrees again
+    assert!(desynced, "the desync went unnoticed");
+    while harness.players[0].session.tick() != harness.players[1].session.tick() {
+        harness.round().await;
+    }
+    let [first, second] = &harness.players;
+    assert_eq!(second.session.desync(), None);
+    assert_eq!(first.session.sim(), second.session.sim());
+}
@@ -0,0 +1,129 @@
+//! Command-line options for `net_pong` and `net_pong_bot`.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use clap::Parser;
+use pong_sim::{MAX_PLAYERS, PLAYER_COUNT};
+use prototype_relay::netsim::NetConditions;
+
+use crate::constants::{
+    DEFAULT_OPPONENT_WAIT_SECS, DEFAULT_RELAY, DEFAULT_SCORE_LIMIT, DEFAULT_TICK_RATE_HZ,
+};
+
+/// Command-line options.
+#[derive(Parser, Debug)]
+#[command(name = "net_pong", about = "Networked Pong over a lockstep relay")]
+pub struct Args {
+    /// The relay to play through; without it (or `--play`), the start menu
+    /// asks.
+    #[arg(long, value_name = "ADDR")]
+    pub relay: Option<SocketAddr>,
+    /// Your name as the other player sees it; `USER` (or `USERNAME`) if not given.
+    #[arg(long)]
+    pub name: Option<String>,
+    /// Join this standing room instead of the unnamed match, or with
+    /// `--spectate`, watch the match in it.
+    #[arg(long)]
+    pub room: Option<String>,
+    /// Ticks per second. Everyone in a match needs the same rate.
+    #[arg(
+        long,
+        value_name = "HZ",
+        default_value_t = DEFAULT_TICK_RATE_HZ,
+        value_parser = clap::value_parser!(u32).range(1..=1000),
+    )]
+    pub tick_rate: u32,
+    /// Points to win; 0 plays forever.
+    #[arg(long, value_name = "POINTS", default_value_t = DEFAULT_SCORE_LIMIT)]
+    pub score_limit: u32,
+    /// Players per match: 2, or 4 in a square arena. The relay needs the
+    /// same `--players`.
+    #[arg(long, value_name = "N", default_value_t = PLAYER_COUNT, value_parser = player_count)]
+    pub players: usize,
+    /// Seconds to wait for an opponent before offering to retry or quit; 0
+    /// waits forever.
+    #[arg(long, value_name = "SECS", default_value_t = DEFAULT_OPPONENT_WAIT_SECS)]
+    pub opponent_wait: u64,
+    /// Write the match to this file on exit, to play back with `--play`.
+    #[arg(long, value_name = "PATH")]
+    pub record: Option<PathBuf>,
+    /// Play back a file written by `--record` instead of connecting.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["record", "spectate"])]
+    pub play: Option<PathBuf>,
+    /// Watch a match instead of playing in one.
+    #[arg(long)]
+    pub spectate: bool,
+    /// Draw your own paddle moving ahead of the simulation.
+    #[arg(long)]
+    pub local_echo: bool,
+    /// Which connected gamepad, counting from 1, drives your paddle.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(1..))]
+    pub gamepad: Option<u8>,
+    /// Bloom, trails, particles, screen shake and sound.
+    #[arg(long)]
+    pub neon: bool,
+    /// Ticks of inputs and state to keep for post-mortems.
+    #[arg(long, value_name = "TICKS")]
+    pub tick_log: Option<usize>,
+    /// Input datagrams per second to send at most.
+    #[arg(long, value_name = "HZ", conflicts_with = "spectate")]
+    pub packet_rate: Option<u32>,
+    /// Play ahead on guessed input instead of waiting for the relay.
+    #[arg(long, conflicts_with = "spectate")]
+    pub rollback: bool,
+    /// Delay and drop outgoing datagrams, e.g. `latency=80,jitter=20,loss=2%`.
+    #[arg(long, value_name = "CONDITIONS")]
+    pub simulate_net: Option<NetConditions>,
+    /// Step a second, local copy of the simulation alongside and report
+    /// where it first parts ways with the one being played.
+    #[arg(long)]
+    pub verify: bool,
+    /// Print the build, protocol versions and features, and exit.
+    #[arg(long)]
+    pub version: bool,
+}
+
+/// Command-line options for `net_pong_bot`.
+#[derive(Parser, Debug)]
+#[command(
+    name = "net_pong_bot",
+    about = "A headless net_pong player that follows the ball"
+)]
+pub struct BotArgs {
+    /// The relay to play through.
+    #[arg(long, value_name = "ADDR", default_value = DEFAULT_RELAY)]
+    pub relay: SocketAddr,
+    /// The bot's name as the other player sees it.
+    #[arg(long, default_value = "bot")]
+    pub name: String,
+    /// Join this standing room instead of the unnamed match.
+    #[arg(long)]
+    pub room: Option<String>,
+    /// Ticks per second; must match the other player's.
+    #[arg(
+        long,
+        value_name = "HZ",
+        default_value_t = DEFAULT_TICK_RATE_HZ,
+        value_parser = clap::value_parser!(u32).range(1..=1000),
+    )]
+    pub tick_rate: u32,
+    /// Points to win; 0 plays forever. Must match the other player's.
+    #[arg(long, value_name = "POINTS", default_value_t = DEFAULT_SCORE_LIMIT)]
+    pub score_limit: u32,
+    /// Players per match, 2 or 4; must match the other players' and the
+    /// relay's.
+    #[arg(long, value_name = "N", default_value_t = PLAYER_COUNT, value_parser = player_count)]
+    pub players: usize,
+}
+
+/// `--players`: the counts Pong has an arena for.
+fn player_count(arg: &str) -> Result<usize, String> {
+    match arg.parse() {
+        Ok(players @ (PLAYER_COUNT | MAX_PLAYERS)) => Ok(players),
+        _ => Err(format!(
+            "Pong is for {PLAYER_COUNT} or {MAX_PLAYERS} players"
+        )),
+    }
+}
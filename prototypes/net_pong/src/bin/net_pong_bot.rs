@@ -0,0 +1,8 @@
+//! A net_pong player with no window, for soak-testing the relay and for
+//! practice. See the library's docs.
+
+use clap::Parser;
+
+fn main() {
+    net_pong::run_bot(net_pong::BotArgs::parse());
+}
@@ -0,0 +1,94 @@
+//! Bot plugin: `net_pong_bot` steers toward the ball instead of reading input.
+//!
+//! The `net_pong_bot` binary plays without a window: `cargo run -p net_pong
+//! --bin net_pong_bot -- [--relay <addr>] [--name <name>] [--room <name>]
+//! [--tick-rate <hz>] [--score-limit <points>]`. It runs the same simulation
+//! and steers its paddle toward the ball, and asks for a rematch as soon as
+//! a match is won, so it can keep a relay busy for hours or give a single
+//! player someone to practice against.
+
+use bevy::prelude::*;
+use pong_sim::{PADDLE_HEIGHT, PongState};
+use prototype_relay::ClientMessage;
+
+use crate::connection::{
+    ConfirmedTick, FreshMatch, LocalPlayerSlot, NetSocket, is_playing, lost_the_match,
+    receive_relay_messages, start_fresh_match,
+};
+use crate::input::{LocalMovement, read_and_send_local_input};
+use crate::match_over::{Rematch, confirmed_winner, repeat_rematch_request};
+use crate::shared::MatchWinner;
+
+/// Frames `net_pong_bot` runs per tick, so it never waits long to play one.
+pub const BOT_FRAMES_PER_TICK: u32 = 4;
+/// How far from the paddle's centre, as a fraction of its half height, the
+/// ball can be before the bot moves.
+const BOT_DEAD_ZONE: f32 = 0.25;
+
+pub struct NetPongBotPlugin;
+
+impl Plugin for NetPongBotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Bot>().add_systems(
+            Update,
+            (
+                follow_ball.before(read_and_send_local_input),
+                ask_for_every_rematch
+                    .run_if(is_playing)
+                    .after(receive_relay_messages)
+                    .before(repeat_rematch_request),
+                ask_for_every_fresh_match
+                    .run_if(lost_the_match)
+                    .before(start_fresh_match),
+            ),
+        );
+    }
+}
+
+/// Present when the paddle is steered by `NetPongBotPlugin`.
+#[derive(Resource, Default)]
+pub struct Bot;
+
+/// Move toward the ball while it comes our way, and back to the middle
+/// while it goes away.
+fn follow_ball(
+    local_slot: Res<LocalPlayerSlot>,
+    state: Res<PongState>,
+    mut movement: ResMut<LocalMovement>,
+) {
+    let slot = usize::from(local_slot.0);
+    if slot >= state.players() {
+        return;
+    }
+    let target = if state.ball_approaching(slot) {
+        state.ball_along_side(slot)
+    } else {
+        0.0
+    };
+    let offset = (target - state.paddle[slot].to_f32()) / (PADDLE_HEIGHT / 2.0);
+    movement.0 = if offset.abs() < BOT_DEAD_ZONE {
+        0.0
+    } else {
+        offset.clamp(-1.0, 1.0)
+    };
+}
+
+/// Always up for another game.
+fn ask_for_every_rematch(
+    winner: Res<MatchWinner>,
+    confirmed: Res<ConfirmedTick>,
+    mut rematch: ResMut<Rematch>,
+    mut net: ResMut<NetSocket>,
+) {
+    if rematch.asked || confirmed_winner(&winner, &confirmed).is_none() {
+        return;
+    }
+    println!("net_pong: asking for a rematch");
+    rematch.asked = true;
+    net.send(&ClientMessage::Rematch);
+}
+
+/// And for a new one when the relay loses the last.
+fn ask_for_every_fresh_match(mut fresh: ResMut<FreshMatch>) {
+    fresh.0 = true;
+}
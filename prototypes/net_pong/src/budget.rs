@@ -0,0 +1,98 @@
+//! Packet budget plugin: fewer, fuller datagrams for tethered connections.
+//!
+//! `--packet-rate` is for tethered mobile connections, where the cost of each
+//! datagram matters more than its size. It caps our input datagrams at about
+//! `<hz>` per second by sending up to `MAX_INPUT_BATCH` ticks of input at
+//! once, ahead of time, with one ack for every tick confirmed since the last
+//! batch: local input takes effect at least one tick less than that late. A
+//! corner indicator shows the cap and the actual send rate.
+
+use bevy::prelude::*;
+
+use crate::connection::NetSocket;
+use crate::shared::PongInput;
+
+/// The most ticks of input sent at once. The relay holds input up to its
+/// `--input-window` (default 3) ticks ahead, so a batch may span 4.
+const MAX_INPUT_BATCH: u32 = 4;
+const BUDGET_FONT_SIZE: f32 = 16.0;
+const BUDGET_MARGIN: f32 = 10.0;
+
+/// `--packet-rate`: how many ticks of input go in each datagram.
+#[derive(Resource)]
+pub struct PacketBudget {
+    /// The requested datagrams per second.
+    hz: u32,
+    /// Ticks per batch, each an input datagram and an ack. Local input is
+    /// delayed one tick less.
+    pub batch: u32,
+    /// Inputs read but not yet sent, for consecutive ticks.
+    pub pending: Vec<PongInput>,
+    meter: Timer,
+    packets_at_last_reading: u64,
+}
+
+impl PacketBudget {
+    pub fn new(hz: u32, tick_rate: u32) -> Self {
+        Self {
+            hz,
+            batch: (2 * tick_rate)
+                .div_ceil(hz.max(1))
+                .clamp(1, MAX_INPUT_BATCH),
+            pending: Vec::new(),
+            meter: Timer::from_seconds(1.0, TimerMode::Repeating),
+            packets_at_last_reading: 0,
+        }
+    }
+}
+
+#[derive(Component)]
+struct PacketBudgetText;
+
+pub struct NetPongPacketBudgetPlugin;
+
+impl Plugin for NetPongPacketBudgetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_packet_budget_indicator)
+            .add_systems(Update, update_packet_budget_indicator);
+    }
+}
+
+fn spawn_packet_budget_indicator(mut commands: Commands, budget: Res<PacketBudget>) {
+    commands.spawn((
+        PacketBudgetText,
+        Text::new(format!("Hotspot: {} packets/s cap", budget.hz)),
+        TextFont::from_font_size(BUDGET_FONT_SIZE),
+        TextColor(Color::srgb(0.5, 0.8, 1.0)),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(BUDGET_MARGIN),
+            right: Val::Px(BUDGET_MARGIN),
+            ..default()
+        },
+    ));
+}
+
+/// Once a second, show how many datagrams we actually sent.
+fn update_packet_budget_indicator(
+    time: Res<Time>,
+    net: Option<Res<NetSocket>>,
+    mut budget: ResMut<PacketBudget>,
+    mut query: Query<&mut Text, With<PacketBudgetText>>,
+) {
+    let Some(net) = net else {
+        return;
+    };
+    budget.meter.tick(time.delta());
+    if !budget.meter.just_finished() {
+        return;
+    }
+    let sent = net.packets_sent - budget.packets_at_last_reading;
+    budget.packets_at_last_reading = net.packets_sent;
+    for mut text in &mut query {
+        **text = format!(
+            "Hotspot: {sent}/{} packets/s, {} ticks per batch",
+            budget.hz, budget.batch
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! Career plugin: wins and losses against each opponent, kept between sessions.
+//!
+//! Every match played to a win is counted against the opponent's name in
+//! `CAREER_PATH` in the working directory. The waiting screen shows the
+//! wins and losses so far, and the match-over screen adds the record
+//! against this opponent. A match that ends any other way is not counted,
+//! and spectating or playing a replay counts nothing.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::connection::{
+    ConfirmedTick, ConnectionState, LocalPlayerSlot, PlayerRoster, is_spectating,
+};
+use crate::match_over::{confirmed_winner, start_rematch};
+use crate::shared::MatchWinner;
+
+/// Where the career record is kept, in the working directory.
+const CAREER_PATH: &str = "net_pong-career.toml";
+const CAREER_FONT_SIZE: f32 = 20.0;
+
+pub struct NetPongCareerPlugin;
+
+impl Plugin for NetPongCareerPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Career::load(Path::new(CAREER_PATH)))
+            .add_systems(Startup, spawn_career_text)
+            .add_systems(
+                Update,
+                (record_match_result, update_career_text)
+                    .chain()
+                    .run_if(not(is_spectating))
+                    .after(start_rematch),
+            );
+    }
+}
+
+/// Every finished match we played, by the name of the player we played.
+#[derive(Resource, Debug, Default, Serialize, Deserialize)]
+struct Career {
+    opponents: BTreeMap<String, Record>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Record {
+    wins: u32,
+    losses: u32,
+}
+
+impl std::fmt::Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} won, {} lost", self.wins, self.losses)
+    }
+}
+
+impl Career {
+    fn load(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!("net_pong: ignoring unreadable {}: {e}", path.display());
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn save(&self, path: &Path) {
+        let contents = toml::to_string_pretty(self).expect("failed to serialize career");
+        if let Err(e) = std::fs::write(path, contents) {
+            eprintln!("net_pong: failed to write {}: {e}", path.display());
+        }
+    }
+
+    fn against(&self, opponent: &str) -> Record {
+        self.opponents.get(opponent).copied().unwrap_or_default()
+    }
+
+    fn total(&self) -> Record {
+        self.opponents
+            .values()
+            .fold(Record::default(), |total, record| Record {
+                wins: total.wins + record.wins,
+                losses: total.losses + record.losses,
+            })
+    }
+}
+
+#[derive(Component)]
+struct CareerText;
+
+fn spawn_career_text(mut commands: Commands) {
+    commands.spawn((
+        CareerText,
+        Text::new(""),
+        TextFont::from_font_size(CAREER_FONT_SIZE),
+        TextColor(Color::srgb(0.7, 0.7, 0.7)),
+        Visibility::Hidden,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(65.0),
+            left: Val::Percent(50.0),
+            ..default()
+        },
+    ));
+}
+
+/// The name of whoever we are playing, once the relay has told us.
+fn opponent_name(roster: &PlayerRoster, local_slot: &LocalPlayerSlot) -> Option<String> {
+    roster
+        .names
+        .iter()
+        .enumerate()
+        .filter(|(slot, _)| *slot != local_slot.0 as usize)
+        .find_map(|(_, name)| name.clone())
+}
+
+/// Count a match once its win is confirmed, and write the record out
+/// straight away so a crash or a closed window does not lose it.
+fn record_match_result(
+    state: Res<ConnectionState>,
+    winner: Res<MatchWinner>,
+    confirmed: Res<ConfirmedTick>,
+    roster: Res<PlayerRoster>,
+    local_slot: Res<LocalPlayerSlot>,
+    mut career: ResMut<Career>,
+    mut counted: Local<bool>,
+) {
+    let Some(slot) = confirmed_winner(&winner, &confirmed) else {
+        *counted = false;
+        return;
+    };
+    if *counted || *state != ConnectionState::Playing {
+        return;
+    }
+    *counted = true;
+    let Some(opponent) = opponent_name(&roster, &local_slot) else {
+        return;
+    };
+    let record = career.opponents.entry(opponent).or_default();
+    if slot == local_slot.0 {
+        record.wins += 1;
+    } else {
+        record.losses += 1;
+    }
+    career.save(Path::new(CAREER_PATH));
+}
+
+/// The career so far while we wait for an opponent, and the record against
+/// this one under the winner once a match is over.
+fn update_career_text(
+    career: Res<Career>,
+    state: Res<ConnectionState>,
+    winner: Res<MatchWinner>,
+    confirmed: Res<ConfirmedTick>,
+    roster: Res<PlayerRoster>,
+    local_slot: Res<LocalPlayerSlot>,
+    mut query: Query<(&mut Text, &mut Visibility), With<CareerText>>,
+) {
+    let total = format!("Career: {}", career.total());
+    let content = match *state {
+        ConnectionState::WaitingForOpponent => Some(total),
+        ConnectionState::Playing if confirmed_winner(&winner, &confirmed).is_some() => {
+            match opponent_name(&roster, &local_slot) {
+                Some(opponent) => Some(format!(
+                    "Against {opponent}: {}\n{total}",
+                    career.against(&opponent)
+                )),
+                None => Some(total),
+            }
+        }
+        _ => None,
+    };
+    for (mut text, mut visibility) in &mut query {
+        match &content {
+            Some(content) => {
+                if **text != *content {
+                    **text = content.clone();
+                }
+                visibility.set_if_neq(Visibility::Visible);
+            }
+            None => {
+                visibility.set_if_neq(Visibility::Hidden);
+            }
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! Chat plugin: a line of text to the others, over the arena.
+//!
+//! T opens a chat line over the arena while seated in a match or waiting
+//! for one: type, then Enter sends it to the other player (and anyone
+//! watching) through the relay, or Escape closes it. While it is open the
+//! keys are the chat's, so the paddle stands still and Escape does not
+//! open the menu; they are the game's again once it closes. Lines fade
+//! after `CHAT_LINE_SHOWN`, and at most `CHAT_RATE_LIMIT` go out in any
+//! `CHAT_RATE_WINDOW`. Spectators read along but cannot chat.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use bevy::input::InputSystems;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+use prototype_relay::{ClientMessage, MAX_CHAT_LEN};
+
+use crate::connection::{ConnectionState, NetSocket, PlayerName, is_spectating};
+use crate::pause_menu::PauseMenu;
+
+const CHAT_KEY: KeyCode = KeyCode::KeyT;
+/// Lines kept on screen, oldest dropped first.
+const CHAT_HISTORY: usize = 6;
+/// How long a line stays up while the chat is closed.
+const CHAT_LINE_SHOWN: Duration = Duration::from_secs(10);
+/// At most `CHAT_RATE_LIMIT` lines go out in any `CHAT_RATE_WINDOW`.
+const CHAT_RATE_LIMIT: usize = 3;
+const CHAT_RATE_WINDOW: Duration = Duration::from_secs(5);
+const CHAT_FONT_SIZE: f32 = 18.0;
+const CHAT_MARGIN: f32 = 20.0;
+const CHAT_BOTTOM_MARGIN: f32 = 50.0;
+const CHAT_BACKGROUND: Color = Color::srgba(0.0, 0.0, 0.0, 0.6);
+
+pub struct NetPongChatPlugin;
+
+impl Plugin for NetPongChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Chat>()
+            .add_systems(Startup, spawn_chat_overlay)
+            .add_systems(
+                PreUpdate,
+                type_chat.after(InputSystems).run_if(not(is_spectating)),
+            )
+            .add_systems(Update, update_chat_overlay);
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Chat {
+    /// The input line is open and takes every key.
+    pub typing: bool,
+    draft: String,
+    /// The last `CHAT_HISTORY` lines, ours included, oldest first.
+    lines: VecDeque<ChatLine>,
+    /// When each line we sent in the last `CHAT_RATE_WINDOW` went out.
+    sent: VecDeque<Instant>,
+    /// The last Enter was refused by the rate limit.
+    held_back: bool,
+}
+
+struct ChatLine {
+    from: String,
+    text: String,
+    at: Instant,
+}
+
+impl Chat {
+    pub fn push(&mut self, from: String, text: &str) {
+        let text: String = text.chars().filter(|c| !c.is_control()).collect();
+        println!("net_pong: {from}: {text}");
+        self.lines.push_back(ChatLine {
+            from,
+            text,
+            at: Instant::now(),
+        });
+        while self.lines.len() > CHAT_HISTORY {
+            self.lines.pop_front();
+        }
+    }
+
+    fn type_text(&mut self, text: &str) {
+        let room = MAX_CHAT_LEN.saturating_sub(self.draft.chars().count());
+        let typed = text.chars().filter(|c| !c.is_control());
+        self.draft.extend(typed.take(room));
+    }
+
+    /// Send the draft and close, unless that would be one line too many
+    /// for the rate limit; then it waits for another Enter.
+    fn send(&mut self, net: &mut NetSocket, name: &str) {
+        let text = self.draft.trim().to_string();
+        if text.is_empty() {
+            self.close();
+            return;
+        }
+        let now = Instant::now();
+        while self
+            .sent
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= CHAT_RATE_WINDOW)
+        {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= CHAT_RATE_LIMIT {
+            self.held_back = true;
+            return;
+        }
+        self.sent.push_back(now);
+        net.send(&ClientMessage::Chat { text: text.clone() });
+        self.push(name.to_string(), &text);
+        self.close();
+    }
+
+    fn close(&mut self) {
+        self.typing = false;
+        self.held_back = false;
+        self.draft.clear();
+    }
+}
+
+/// `CHAT_KEY` opens the input line while we are seated and the menu is
+/// closed. Then every key is for the chat: Enter sends, Escape closes, and
+/// the keys are taken out of `ButtonInput` before anything in `Update`
+/// sees them, so typing neither steers nor opens the menu. The paddle is
+/// ours again as soon as the line closes.
+fn type_chat(
+    mut keyboard: ResMut<ButtonInput<KeyCode>>,
+    mut typed: MessageReader<KeyboardInput>,
+    mut chat: ResMut<Chat>,
+    state: Res<ConnectionState>,
+    menu: Res<PauseMenu>,
+    mut net: Option<ResMut<NetSocket>>,
+    name: Res<PlayerName>,
+) {
+    if !chat.typing {
+        typed.clear();
+        let seated = matches!(
+            *state,
+            ConnectionState::WaitingForOpponent | ConnectionState::Playing
+        );
+        if seated && !menu.open && net.is_some() && keyboard.clear_just_pressed(CHAT_KEY) {
+            chat.typing = true;
+        }
+        return;
+    }
+    for event in typed.read() {
+        if !event.state.is_pressed() || !chat.typing {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Enter => {
+                if let Some(net) = &mut net {
+                    chat.send(net, &name.0);
+                }
+            }
+            Key::Escape => chat.close(),
+            Key::Backspace => {
+                chat.draft.pop();
+            }
+            Key::Space => chat.type_text(" "),
+            Key::Character(text) => chat.type_text(text),
+            _ => {}
+        }
+    }
+    let pressed: Vec<KeyCode> = keyboard.get_just_pressed().copied().collect();
+    for key in pressed {
+        keyboard.clear_just_pressed(key);
+    }
+}
+
+#[derive(Component)]
+struct ChatText;
+
+fn spawn_chat_overlay(mut commands: Commands) {
+    commands.spawn((
+        ChatText,
+        Text::new(""),
+        TextFont::from_font_size(CHAT_FONT_SIZE),
+        TextColor(Color::srgb(0.9, 0.9, 0.9)),
+        BackgroundColor(CHAT_BACKGROUND),
+        GlobalZIndex(50),
+        Visibility::Hidden,
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(CHAT_BOTTOM_MARGIN),
+            left: Val::Px(CHAT_MARGIN),
+            max_width: Val::Percent(60.0),
+            padding: UiRect::all(Val::Px(6.0)),
+            ..default()
+        },
+    ));
+}
+
+/// Recent lines, every kept line and the draft while typing, or nothing.
+fn update_chat_overlay(
+    chat: Res<Chat>,
+    mut query: Query<(&mut Text, &mut Visibility), With<ChatText>>,
+) {
+    let now = Instant::now();
+    let mut lines: Vec<String> = chat
+        .lines
+        .iter()
+        .filter(|line| chat.typing || now.duration_since(line.at) < CHAT_LINE_SHOWN)
+        .map(|line| format!("{}: {}", line.from, line.text))
+        .collect();
+    if chat.typing {
+        let hint = if chat.held_back {
+            "  (too fast; Enter again in a moment)"
+        } else {
+            ""
+        };
+        lines.push(format!("> {}_{hint}", chat.draft));
+    }
+    let content = lines.join("\n");
+    for (mut text, mut visibility) in &mut query {
+        if **text != content {
+            **text = content.clone();
+        }
+        if content.is_empty() {
+            visibility.set_if_neq(Visibility::Hidden);
+        } else {
+            visibility.set_if_neq(Visibility::Visible);
+        }
+    }
+}
@@ -0,0 +1,1291 @@
+//! Connection plugin: UDP setup, handshake, message receive.
+//!
+//! `--spectate` watches a match on the relay instead of playing in one: the
+//! one in `--room`, or else the longest running. The relay sends a
+//! spectator every tick's inputs, and one of the players a request for a
+//! snapshot to start from, and the spectator plays the match on the same
+//! simulation with nothing to steer. Spectators never hold the players up. `--rollback` and
+//! `--packet-rate` do not apply to them.
+//!
+//! Hello goes out every `HELLO_RETRY_FIRST` until the match starts, and the
+//! relay answers each one. While it does not, the wait doubles each time,
+//! up to `HELLO_RETRY_MAX`. If the socket fails or the relay goes quiet for
+//! `RELAY_SILENCE_LIMIT` before the match starts, the game says it is
+//! reconnecting and starts saying Hello again.
+//!
+//! If nobody joins within `--opponent-wait` seconds (two minutes unless
+//! given; 0 waits forever), the game leaves the waiting room and says "No
+//! opponent found", offering Retry, which waits as long again, or Quit. The
+//! bot waits for as long as it takes.
+//!
+//! `--room` joins a standing room on the relay instead of the unnamed match.
+//! In a standing room a lost connection is not the end of the match: if the
+//! socket fails, the relay goes quiet for `RELAY_SILENCE_LIMIT` or shuts down,
+//! or the opponent drops out, the game pauses on "Reconnecting..." and asks
+//! the relay to resume the match where it left off, backing off like Hello,
+//! for up to `RECONNECT_GIVE_UP`. Without a room the relay cannot resume
+//! anything, so the match just ends with "Connection lost".
+//!
+//! A relay that restarted mid-match knows nothing of it. If it seats us in
+//! another slot than ours, or starts a match while ours is under way or
+//! being resumed, net_pong leaves that seat and says the relay lost the
+//! match. There, as after "Connection lost", Serve forgets the old match and
+//! asks for a new one from tick 0; bots ask straight away.
+//!
+//! `--simulate-net latency=80,jitter=20,loss=2%` holds back what we send by
+//! 80 ms, give or take 20, and drops 2% of it, to try the game on a bad
+//! network without finding one. It only touches our side; give the relay
+//! the same option to make the other direction bad too.
+//!
+//! Set `RELAY_SESSION_SECRET` to the relay's shared secret when it runs in
+//! authenticated mode.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use pong_sim::MAX_PLAYERS;
+use prototype_relay::auth::{HANDSHAKE_NONCE, SessionAuth};
+use prototype_relay::netsim::{DelayLine, NetConditions, NetSim};
+use prototype_relay::sequence::{SequenceCounter, SequenceFilter, Sequenced};
+use prototype_relay::{
+    AbortReason, ClientMessage, ConfigValue, Payload, RejectReason, RelayMessage, SPECTATOR_SLOT,
+    Tick, config_keys, deserialize, serialize,
+};
+
+use crate::budget::PacketBudget;
+use crate::chat::Chat;
+use crate::constants::{
+    DELAY_SETTLE_REPORTS, HELLO_RETRY_FIRST, HELLO_RETRY_MAX, MAX_INPUT_DELAY, RECONNECT_GIVE_UP,
+    RELAY_SILENCE_LIMIT, SENT_INPUT_HISTORY,
+};
+use crate::desync::DesyncCheck;
+use crate::game::TickEvents;
+use crate::input::ControlBindings;
+use crate::match_over::Rematch;
+use crate::net_stats::MatchNetStats;
+use crate::pause_menu::{MatchPause, MenuInput, PauseMenu, PauseRequest};
+use crate::replay::ReplayRecorder;
+use crate::resync::{Resync, load_snapshot};
+use crate::rollback::GameSnapshot;
+use crate::shared::{
+    GameId, MatchWinner, PaddleInput, PlayerCount, PongClientMessage, PongInput, PongRelayMessage,
+    PongTickLog, Score, TickRate,
+};
+
+pub struct NetPongConnectionPlugin;
+
+impl Plugin for NetPongConnectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ConnectionState::Connecting)
+            .insert_resource(SimulationTick(0))
+            .insert_resource(ConfirmedTick(0))
+            .insert_resource(TickReady(false))
+            .insert_resource(NextInputTick(0))
+            .init_resource::<InputBuffer>()
+            .init_resource::<HelloBackoff>()
+            .insert_resource(KeepAliveTimer(Timer::from_seconds(
+                0.5,
+                TimerMode::Repeating,
+            )))
+            .insert_resource(LocalPlayerSlot(0))
+            .init_resource::<RelayConfig>()
+            .init_resource::<PlayerRoster>()
+            .init_resource::<RelayRtt>()
+            .init_resource::<FreshMatch>()
+            .init_resource::<NoOpponentMenu>()
+            .add_systems(Startup, setup_network)
+            .add_systems(
+                Update,
+                (
+                    send_hello.run_if(is_in_lobby.or(is_playing.and(is_spectating))),
+                    send_resume.run_if(is_reconnecting),
+                    detect_lost_relay.run_if(is_connected),
+                    give_up_waiting_for_opponent,
+                    resend_pending_input
+                        .run_if(is_playing.or(is_reconnecting))
+                        .run_if(not(need_to_send)),
+                    report_score.run_if(is_playing).run_if(not(is_spectating)),
+                    receive_relay_messages,
+                    adapt_input_delay
+                        .run_if(resource_exists::<AdaptiveDelay>)
+                        .after(receive_relay_messages),
+                    release_held_datagrams,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    ask_for_fresh_match.run_if(lost_the_match),
+                    navigate_no_opponent_menu.run_if(found_no_opponent),
+                    start_fresh_match,
+                )
+                    .chain()
+                    .after(receive_relay_messages),
+            )
+            .add_systems(Last, send_goodbye_on_exit);
+    }
+}
+
+#[derive(Resource)]
+pub struct RelayAddress(pub SocketAddr);
+
+/// The standing room to join, if any.
+#[derive(Resource)]
+pub struct RoomName(pub Option<String>);
+
+/// `--name`, or `player_name`.
+#[derive(Resource)]
+pub struct PlayerName(pub String);
+
+/// `--spectate`: watching a match, the one in `room` if given, rather than
+/// playing. Only there for spectators.
+#[derive(Resource)]
+pub struct Spectate {
+    pub room: Option<String>,
+}
+
+/// `--simulate-net`: the network to pretend we are on, if any.
+#[derive(Resource)]
+pub struct SimulatedNet(pub Option<NetConditions>);
+
+#[derive(Resource)]
+pub struct NetSocket {
+    socket: UdpSocket,
+    pub relay_addr: SocketAddr,
+    auth: Option<SessionAuth>,
+    /// The relay's session nonce, learned from its first authenticated reply.
+    session_nonce: u64,
+    outbound: SequenceCounter,
+    pub inbound: SequenceFilter,
+    /// TickInputs that arrived for a tick we had not sent input for.
+    ignored_tick_inputs: u64,
+    /// Datagrams sent, for the packet budget indicator and the HUD.
+    pub packets_sent: u64,
+    /// Datagrams received and accepted, for the HUD.
+    pub packets_received: u64,
+    /// The current match's traffic, for the stats panel.
+    pub stats: MatchNetStats,
+    /// Every input sent lately, by tick, to re-send after a loss or resume.
+    pub sent_inputs: BTreeMap<Tick, PongInput>,
+    /// When the relay last sent anything we could use.
+    last_heard: Instant,
+    /// When the connection was lost, while we try to resume.
+    pub reconnecting_since: Option<Instant>,
+    /// Datagrams held back by `--simulate-net` until they are due.
+    held: Option<DelayLine<Vec<u8>>>,
+}
+
+impl NetSocket {
+    pub fn send(&mut self, msg: &PongClientMessage) {
+        let bytes = serialize(&self.outbound.wrap(msg));
+        let datagram = match &self.auth {
+            Some(auth) => auth.seal(self.session_nonce, &bytes),
+            None => bytes,
+        };
+        self.stats.packets_sent += 1;
+        self.stats.bytes_sent += datagram.len() as u64;
+        match &mut self.held {
+            Some(held) => held.push(Instant::now(), datagram),
+            None => {
+                let _ = self.socket.send_to(&datagram, self.relay_addr);
+            }
+        }
+        self.packets_sent += 1;
+    }
+
+    /// Send whatever `--simulate-net` has held back long enough, or all of
+    /// it with `everything`, as when quitting.
+    fn release_held(&mut self, everything: bool) {
+        let Some(held) = &mut self.held else {
+            return;
+        };
+        let due: Vec<_> = if everything {
+            held.drain().collect()
+        } else {
+            let now = Instant::now();
+            std::iter::from_fn(|| held.pop_due(now)).collect()
+        };
+        for datagram in due {
+            let _ = self.socket.send_to(&datagram, self.relay_addr);
+        }
+    }
+
+    /// Send inputs for consecutive ticks from `first_tick` in one datagram.
+    pub fn send_inputs(&mut self, first_tick: Tick, inputs: &[PongInput]) {
+        let msg = match inputs {
+            [input] => ClientMessage::Input {
+                tick: first_tick,
+                payload: Payload(*input),
+            },
+            _ => ClientMessage::InputBatch {
+                first_tick,
+                payloads: inputs.iter().copied().map(Payload).collect(),
+            },
+        };
+        self.send(&msg);
+        for (tick, input) in (first_tick..).zip(inputs) {
+            self.sent_inputs.insert(tick, *input);
+        }
+        while self.sent_inputs.len() > SENT_INPUT_HISTORY {
+            self.sent_inputs.pop_first();
+        }
+    }
+
+    /// Stand still until our first input takes effect, `delay` ticks in.
+    pub fn send_opening_inputs(&mut self, delay: Tick) {
+        if delay > 0 {
+            self.send_inputs(0, &vec![PongInput::default(); delay as usize]);
+        }
+    }
+
+    /// What we sent for `first_tick` onwards, up to the first tick not sent.
+    pub fn sent_from(&self, first_tick: Tick) -> Vec<PongInput> {
+        (first_tick..)
+            .map_while(|tick| self.sent_inputs.get(&tick).copied())
+            .collect()
+    }
+
+    /// Send again what we sent for `first_tick` onwards.
+    fn resend_inputs_from(&mut self, first_tick: Tick) {
+        let inputs = self.sent_from(first_tick);
+        // Nothing sent for that tick yet means the input system will.
+        if !inputs.is_empty() {
+            self.stats.resends += 1;
+            self.send_inputs(first_tick, &inputs);
+        }
+    }
+
+    /// Forget the relay's session, which may not survive whatever cut us
+    /// off. Our own sequence numbers carry on, so a relay that still has us
+    /// seated takes what we send next.
+    fn start_over(&mut self) {
+        self.session_nonce = HANDSHAKE_NONCE;
+        self.inbound = SequenceFilter::new();
+        self.reconnecting_since = Some(Instant::now());
+    }
+
+    /// Decode a datagram from the relay.
+    fn receive(&mut self, datagram: &[u8]) -> Result<PongRelayMessage, Dropped> {
+        let bytes = self.open(datagram).ok_or(Dropped::Unauthenticated)?;
+        let sequenced =
+            deserialize::<Sequenced<PongRelayMessage>>(bytes).ok_or(Dropped::Malformed)?;
+        self.inbound
+            .accept(sequenced.seq)
+            .accepted()
+            .then_some(sequenced.message)
+            .ok_or(Dropped::Sequence)
+    }
+
+    fn open<'a>(&mut self, datagram: &'a [u8]) -> Option<&'a [u8]> {
+        let Some(auth) = &self.auth else {
+            return Some(datagram);
+        };
+        let (nonce, message) = auth.open(datagram)?;
+        if self.session_nonce == HANDSHAKE_NONCE {
+            self.session_nonce = nonce;
+        }
+        (nonce == self.session_nonce).then_some(message)
+    }
+}
+
+/// Why a datagram from the relay was dropped.
+pub enum Dropped {
+    Unauthenticated,
+    Malformed,
+    /// Duplicate or too old to use.
+    Sequence,
+}
+
+#[derive(Resource, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The start menu is up and nothing has been sent yet.
+    StartMenu,
+    Connecting,
+    /// The relay is full; we keep saying Hello and wait to be admitted.
+    Queued(u32),
+    WaitingForOpponent,
+    /// Nobody joined within `--opponent-wait`; we left the waiting room and
+    /// offer to retry or quit.
+    NoOpponent,
+    Playing,
+    Rejected(RejectReason),
+    /// The other player said Goodbye; the match is over.
+    OpponentLeft,
+    /// The other player ended the match early and said why.
+    OpponentAborted(AbortReason),
+    /// The relay shut down; nothing more will arrive.
+    RelayClosed,
+    /// The connection was lost mid-match; asking the relay to resume it.
+    Reconnecting,
+    /// The connection was lost and the match could not be resumed.
+    ConnectionLost,
+    /// The relay restarted and started over without our match; Serve asks
+    /// it for a new one.
+    RelayRestarted,
+    /// The relay seats this many players a match, not `--players`.
+    WrongPlayerCount(usize),
+    /// `--play`: no relay, just a recorded match.
+    Replaying,
+    /// The recorded match has run out of ticks.
+    ReplayFinished,
+}
+
+impl ConnectionState {
+    /// The relay has answered and has not gone quiet since.
+    fn is_connected(&self) -> bool {
+        matches!(
+            self,
+            ConnectionState::Queued(_)
+                | ConnectionState::WaitingForOpponent
+                | ConnectionState::Playing
+        )
+    }
+}
+
+#[derive(Resource)]
+pub struct SimulationTick(pub Tick);
+
+/// Ticks before this one have been played with the relay's inputs. In
+/// lockstep that is every tick played; with rollback, the simulation runs
+/// ahead of it on guesses.
+#[derive(Resource)]
+pub struct ConfirmedTick(pub Tick);
+
+#[derive(Resource)]
+pub struct TickReady(pub bool);
+
+/// The first tick we have not read local input for.
+#[derive(Resource)]
+pub struct NextInputTick(pub Tick);
+
+/// Ticks between reading local input and playing it: `INPUT_DELAY` to
+/// start, as `AdaptiveDelay` sets it after that, and never less than a
+/// packet budget's batch needs.
+#[derive(Resource)]
+pub struct InputDelay(pub Tick);
+
+/// Present unless `--rollback`: `InputDelay` follows the round trip.
+#[derive(Resource, Default)]
+pub struct AdaptiveDelay {
+    /// Reports in a row that called for a shorter delay.
+    shorter_reports: u32,
+}
+
+/// Our round trip to the relay, as the relay last measured it. Only changed
+/// by a NetStats report.
+#[derive(Resource, Default)]
+pub struct RelayRtt(pub Option<u32>);
+
+/// Broadcast inputs by tick, from the tick being played until the
+/// simulation gets to them.
+#[derive(Resource, Default)]
+pub struct InputBuffer(pub BTreeMap<Tick, [f32; MAX_PLAYERS]>);
+
+impl InputBuffer {
+    /// Load `tick`'s inputs into `input`, if they have arrived.
+    pub fn load(&self, tick: Tick, input: &mut PaddleInput) -> bool {
+        let Some(movement) = self.0.get(&tick) else {
+            return false;
+        };
+        input.movement = *movement;
+        true
+    }
+
+    /// The first tick from `tick` through `last` that has not arrived.
+    fn first_missing(&self, tick: Tick, last: Tick) -> Option<Tick> {
+        (tick..=last).find(|tick| !self.0.contains_key(tick))
+    }
+}
+
+/// Hello is repeated until the game starts: it gets lost like anything else,
+/// and once seated it tells the relay we have not gone away. It goes every
+/// `HELLO_RETRY_FIRST` while the relay answers; each one it leaves
+/// unanswered doubles the wait, up to `HELLO_RETRY_MAX`. Resume is repeated
+/// the same way.
+#[derive(Resource)]
+struct HelloBackoff {
+    timer: Timer,
+    /// When the last one went out.
+    sent_at: Option<Instant>,
+}
+
+impl Default for HelloBackoff {
+    fn default() -> Self {
+        Self {
+            timer: Timer::new(HELLO_RETRY_FIRST, TimerMode::Repeating),
+            sent_at: None,
+        }
+    }
+}
+
+impl HelloBackoff {
+    /// Whether the next one is due, `delta` after the last check. The wait
+    /// after it depends on whether the relay has been heard from, at
+    /// `last_heard`, since the last one.
+    fn due(&mut self, delta: Duration, last_heard: Instant) -> bool {
+        self.timer.tick(delta);
+        if !self.timer.just_finished() {
+            return false;
+        }
+        let answered = self.sent_at.is_none_or(|sent_at| last_heard >= sent_at);
+        let wait = if answered {
+            HELLO_RETRY_FIRST
+        } else {
+            (self.timer.duration() * 2).min(HELLO_RETRY_MAX)
+        };
+        if wait != self.timer.duration() {
+            if !answered {
+                println!("net_pong: no answer from the relay; trying again in {wait:?}");
+            }
+            self.timer.set_duration(wait);
+        }
+        self.sent_at = Some(Instant::now());
+        true
+    }
+}
+
+#[derive(Resource)]
+struct KeepAliveTimer(Timer);
+
+/// `SPECTATOR_SLOT` when spectating.
+#[derive(Resource)]
+pub struct LocalPlayerSlot(pub u8);
+
+/// Who is in each slot, as announced by the relay.
+#[derive(Resource, Default)]
+pub struct PlayerRoster {
+    pub names: [Option<String>; MAX_PLAYERS],
+}
+
+/// The lockstep bookkeeping that arriving TickInputs feed.
+#[derive(SystemParam)]
+pub struct TickSync<'w> {
+    tick_ready: ResMut<'w, TickReady>,
+    next_input: ResMut<'w, NextInputTick>,
+    delay: Res<'w, InputDelay>,
+    buffer: ResMut<'w, InputBuffer>,
+    input: ResMut<'w, PaddleInput>,
+    sim_tick: Res<'w, SimulationTick>,
+    confirmed: Res<'w, ConfirmedTick>,
+    recorder: Option<ResMut<'w, ReplayRecorder>>,
+    players: Res<'w, PlayerCount>,
+    tick_rate: Res<'w, TickRate>,
+}
+
+/// Settings pushed by the relay via `RelayMessage::Config`.
+#[derive(Resource, Default)]
+pub struct RelayConfig {
+    values: HashMap<String, ConfigValue>,
+    /// Counts down from the most recent shutdown warning.
+    pub shutdown_timer: Option<Timer>,
+}
+
+impl RelayConfig {
+    fn apply(&mut self, entries: Vec<(String, ConfigValue)>) {
+        for (key, value) in entries {
+            if key == config_keys::SHUTDOWN_COUNTDOWN_SECS
+                && let ConfigValue::Int(secs) = value
+            {
+                self.shutdown_timer =
+                    Some(Timer::from_seconds(secs.max(0) as f32, TimerMode::Once));
+            }
+            println!("net_pong: relay config {key} = {value}");
+            self.values.insert(key, value);
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&ConfigValue> {
+        self.values.get(key)
+    }
+}
+
+fn is_in_lobby(state: Res<ConnectionState>) -> bool {
+    matches!(
+        *state,
+        ConnectionState::Connecting
+            | ConnectionState::Queued(_)
+            | ConnectionState::WaitingForOpponent
+    )
+}
+
+fn is_connected(state: Res<ConnectionState>) -> bool {
+    state.is_connected()
+}
+
+pub fn is_playing(state: Res<ConnectionState>) -> bool {
+    *state == ConnectionState::Playing
+}
+
+fn is_reconnecting(state: Res<ConnectionState>) -> bool {
+    *state == ConnectionState::Reconnecting
+}
+
+pub fn is_spectating(spectate: Option<Res<Spectate>>) -> bool {
+    spectate.is_some()
+}
+
+pub fn tick_is_ready(ready: Res<TickReady>) -> bool {
+    ready.0
+}
+
+/// Input is owed up to `delay` ticks ahead, but never for a paused tick.
+pub fn need_to_send(
+    next_input: Res<NextInputTick>,
+    sim_tick: Res<SimulationTick>,
+    delay: Res<InputDelay>,
+    pause: Res<MatchPause>,
+) -> bool {
+    next_input.0 <= sim_tick.0 + delay.0 && pause.at.is_none_or(|at| next_input.0 < at)
+}
+
+/// The input delay that covers a round trip of `rtt_ms` at `tick_rate`,
+/// with a tick to spare.
+fn delay_for_rtt(rtt_ms: u32, tick_rate: u32) -> Tick {
+    (rtt_ms * tick_rate).div_ceil(1000) + 1
+}
+
+/// Fit the input delay to each new round-trip measurement.
+fn adapt_input_delay(
+    rtt: Res<RelayRtt>,
+    tick_rate: Res<TickRate>,
+    budget: Option<Res<PacketBudget>>,
+    mut adaptive: ResMut<AdaptiveDelay>,
+    mut delay: ResMut<InputDelay>,
+) {
+    if !rtt.is_changed() {
+        return;
+    }
+    let Some(rtt_ms) = rtt.0 else {
+        return;
+    };
+    // A batch goes out as its first tick comes up, so it has to reach that
+    // far ahead.
+    let floor = budget.map_or(1, |budget| (budget.batch - 1).max(1));
+    let wanted = delay_for_rtt(rtt_ms, tick_rate.0).clamp(floor, MAX_INPUT_DELAY.max(floor));
+    if wanted < delay.0 {
+        adaptive.shorter_reports += 1;
+        if adaptive.shorter_reports < DELAY_SETTLE_REPORTS {
+            return;
+        }
+    }
+    adaptive.shorter_reports = 0;
+    let current = delay.0;
+    if wanted != current {
+        println!("net_pong: input delay {current} -> {wanted} ticks (RTT {rtt_ms} ms)");
+        delay.0 = wanted;
+    }
+}
+
+fn setup_network(
+    mut commands: Commands,
+    relay_addr: Res<RelayAddress>,
+    simulated: Res<SimulatedNet>,
+) {
+    let socket = UdpSocket::bind("0.0.0.0:0").expect("failed to bind local UDP socket");
+    socket
+        .set_nonblocking(true)
+        .expect("failed to set non-blocking");
+
+    let addr = relay_addr.0;
+    commands.insert_resource(NetSocket {
+        socket,
+        relay_addr: addr,
+        auth: SessionAuth::from_env(),
+        session_nonce: HANDSHAKE_NONCE,
+        outbound: SequenceCounter::default(),
+        inbound: SequenceFilter::new(),
+        ignored_tick_inputs: 0,
+        packets_sent: 0,
+        packets_received: 0,
+        stats: MatchNetStats::default(),
+        sent_inputs: BTreeMap::new(),
+        last_heard: Instant::now(),
+        reconnecting_since: None,
+        held: simulated.0.map(|conditions| {
+            warn!("simulating a bad network on the way out: {conditions}");
+            DelayLine::new(NetSim::new(conditions))
+        }),
+    });
+}
+
+/// A spectator says Spectate instead, and keeps saying it while it watches.
+fn send_hello(
+    mut net: ResMut<NetSocket>,
+    mut backoff: ResMut<HelloBackoff>,
+    room: Res<RoomName>,
+    game_id: Res<GameId>,
+    name: Res<PlayerName>,
+    spectate: Option<Res<Spectate>>,
+    time: Res<Time>,
+) {
+    if !backoff.due(time.delta(), net.last_heard) {
+        return;
+    }
+    match spectate {
+        Some(spectate) => net.send(&ClientMessage::Spectate {
+            game_id: game_id.0.clone(),
+            room: spectate.room.clone(),
+        }),
+        None => net.send(&ClientMessage::Hello {
+            game_id: game_id.0.clone(),
+            name: name.0.clone(),
+            room: room.0.clone(),
+        }),
+    }
+}
+
+/// Ask the relay to give us back our slot in the match we lost, from the
+/// first tick we do not have its inputs for. Repeated, like Hello, until it
+/// is resumed or we give up.
+#[allow(clippy::too_many_arguments)]
+fn send_resume(
+    mut net: ResMut<NetSocket>,
+    mut state: ResMut<ConnectionState>,
+    mut backoff: ResMut<HelloBackoff>,
+    room: Res<RoomName>,
+    game_id: Res<GameId>,
+    name: Res<PlayerName>,
+    local_slot: Res<LocalPlayerSlot>,
+    confirmed: Res<ConfirmedTick>,
+    time: Res<Time>,
+) {
+    if net
+        .reconnecting_since
+        .is_some_and(|since| since.elapsed() > RECONNECT_GIVE_UP)
+    {
+        eprintln!("net_pong: gave up reconnecting");
+        net.send(&ClientMessage::Goodbye);
+        *state = ConnectionState::ConnectionLost;
+        return;
+    }
+    let Some(room) = &room.0 else {
+        return;
+    };
+    if backoff.due(time.delta(), net.last_heard) {
+        net.send(&ClientMessage::Resume {
+            game_id: game_id.0.clone(),
+            name: name.0.clone(),
+            room: room.clone(),
+            slot: local_slot.0,
+            next_tick: confirmed.0,
+        });
+    }
+}
+
+/// Notice a relay that has gone quiet mid-match, or while it has us seated
+/// or queued: it answers every Hello, so it is never quiet for long there
+/// either.
+fn detect_lost_relay(
+    mut net: ResMut<NetSocket>,
+    mut state: ResMut<ConnectionState>,
+    room: Res<RoomName>,
+) {
+    if net.last_heard.elapsed() > RELAY_SILENCE_LIMIT {
+        lose_connection(&mut net, &mut state, &room, "the relay went quiet");
+    }
+}
+
+/// The relay was cut off for `why`. Before the match starts there is
+/// nothing to lose: say Hello until it answers again. Once it has started,
+/// try to resume it in our standing room, or end it if we are not in one.
+fn lose_connection(
+    net: &mut NetSocket,
+    state: &mut ConnectionState,
+    room: &RoomName,
+    why: &str,
+) {
+    if *state != ConnectionState::Playing {
+        eprintln!("net_pong: {why}; connecting again");
+        net.start_over();
+        *state = ConnectionState::Connecting;
+        return;
+    }
+    if room.0.is_none() {
+        eprintln!("net_pong: connection lost: {why}");
+        *state = ConnectionState::ConnectionLost;
+        return;
+    }
+    eprintln!("net_pong: {why}; reconnecting");
+    net.start_over();
+    *state = ConnectionState::Reconnecting;
+}
+
+/// The relay restarted and carried on without the match we were in, so
+/// there is nothing to resume. Give up whatever seat it gave us in its new
+/// one, and wait for the player to ask for a match of their own.
+fn relay_lost_match(net: &mut NetSocket, state: &mut ConnectionState, why: &str) {
+    eprintln!("net_pong: the relay lost the match: {why}");
+    net.send(&ClientMessage::Goodbye);
+    net.reconnecting_since = None;
+    *state = ConnectionState::RelayRestarted;
+}
+
+pub fn lost_the_match(state: Res<ConnectionState>) -> bool {
+    matches!(
+        *state,
+        ConnectionState::RelayRestarted | ConnectionState::ConnectionLost
+    )
+}
+
+/// Serve asked for a new match after the last one was lost.
+#[derive(Resource, Default)]
+pub struct FreshMatch(pub bool);
+
+/// Serve, once the match is lost, asks for a new one.
+fn ask_for_fresh_match(
+    input: MenuInput,
+    bindings: Res<ControlBindings>,
+    menu: Res<PauseMenu>,
+    mut fresh: ResMut<FreshMatch>,
+) {
+    if !menu.open && input.binding_just_pressed(&bindings.serve) {
+        fresh.0 = true;
+    }
+}
+
+/// `--opponent-wait`: how long to wait for an opponent, if not forever.
+#[derive(Resource)]
+pub struct OpponentWait(pub Option<Duration>);
+
+/// Nobody has joined for `OpponentWait`, so free our seat in the waiting
+/// room rather than hold it forever.
+fn give_up_waiting_for_opponent(
+    wait: Res<OpponentWait>,
+    mut net: ResMut<NetSocket>,
+    mut state: ResMut<ConnectionState>,
+    mut menu: ResMut<NoOpponentMenu>,
+    mut waiting_since: Local<Option<Instant>>,
+) {
+    if *state != ConnectionState::WaitingForOpponent {
+        *waiting_since = None;
+        return;
+    }
+    let Some(wait) = wait.0 else {
+        return;
+    };
+    let since = *waiting_since.get_or_insert_with(Instant::now);
+    if since.elapsed() < wait {
+        return;
+    }
+    println!(
+        "net_pong: no opponent after {}s; leaving the waiting room",
+        wait.as_secs()
+    );
+    net.send(&ClientMessage::Goodbye);
+    menu.selected = 0;
+    *state = ConnectionState::NoOpponent;
+}
+
+fn found_no_opponent(state: Res<ConnectionState>) -> bool {
+    *state == ConnectionState::NoOpponent
+}
+
+/// What to do after nobody joined: 0 is Retry, 1 is Quit.
+#[derive(Resource, Default)]
+pub struct NoOpponentMenu {
+    pub selected: usize,
+}
+
+const NO_OPPONENT_MENU_ITEMS: usize = 2;
+
+/// Up and Down choose between Retry and Quit, and Enter (or South) picks.
+/// Retry asks for a match afresh, and waits as long again.
+fn navigate_no_opponent_menu(
+    input: MenuInput,
+    pause_menu: Res<PauseMenu>,
+    mut menu: ResMut<NoOpponentMenu>,
+    mut fresh: ResMut<FreshMatch>,
+    mut exit: MessageWriter<AppExit>,
+) {
+    if pause_menu.open {
+        return;
+    }
+    let items = NO_OPPONENT_MENU_ITEMS;
+    if input.just_pressed(&[KeyCode::ArrowUp, KeyCode::KeyW], GamepadButton::DPadUp) {
+        menu.selected = (menu.selected + items - 1) % items;
+    }
+    if input.just_pressed(
+        &[KeyCode::ArrowDown, KeyCode::KeyS],
+        GamepadButton::DPadDown,
+    ) {
+        menu.selected = (menu.selected + 1) % items;
+    }
+    if !input.just_pressed(&[KeyCode::Enter, KeyCode::Space], GamepadButton::South) {
+        return;
+    }
+    match menu.selected {
+        0 => fresh.0 = true,
+        _ => {
+            exit.write(AppExit::Success);
+        }
+    }
+}
+
+/// Forget the lost match entirely and say Hello as if just started, so the
+/// new match begins at tick 0 with nothing left over.
+pub fn start_fresh_match(world: &mut World) {
+    if !std::mem::take(&mut world.resource_mut::<FreshMatch>().0) {
+        return;
+    }
+    println!("net_pong: asking the relay for a new match");
+    world.resource_mut::<InputBuffer>().0.clear();
+    world.resource_mut::<TickEvents>().0.clear();
+    let players = world.resource::<PlayerCount>().0;
+    load_snapshot(world, 0, &GameSnapshot::new_game(players));
+    world.resource_mut::<NextInputTick>().0 = 0;
+    {
+        let mut net = world.resource_mut::<NetSocket>();
+        net.start_over();
+        net.reconnecting_since = None;
+        net.sent_inputs.clear();
+        net.stats = MatchNetStats::default();
+    }
+    *world.resource_mut::<PlayerRoster>() = PlayerRoster::default();
+    *world.resource_mut::<MatchPause>() = MatchPause::default();
+    *world.resource_mut::<Rematch>() = Rematch::default();
+    *world.resource_mut::<DesyncCheck>() = DesyncCheck::default();
+    *world.resource_mut::<Resync>() = Resync::default();
+    *world.resource_mut::<HelloBackoff>() = HelloBackoff::default();
+    *world.resource_mut::<ConnectionState>() = ConnectionState::Connecting;
+}
+
+/// The name to go by without `--name`: `USER`, or `USERNAME` on Windows.
+pub fn player_name() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "player".into())
+}
+
+/// While waiting on the other player's input, re-send ours now and then. This
+/// recovers an Input the network lost, and keeps the relay from deciding we
+/// went silent. While ticks keep being confirmed ours are getting through,
+/// so the timer starts over with each one. A room can set how long to wait
+/// with `retransmit.interval_ms`.
+fn resend_pending_input(
+    mut net: ResMut<NetSocket>,
+    mut timer: ResMut<KeepAliveTimer>,
+    confirmed: Res<ConfirmedTick>,
+    relay_config: Res<RelayConfig>,
+    time: Res<Time>,
+    mut last_confirmed: Local<Option<Tick>>,
+) {
+    if relay_config.is_changed()
+        && let Some(&ConfigValue::Int(ms)) = relay_config.get(config_keys::RETRANSMIT_INTERVAL_MS)
+    {
+        timer
+            .0
+            .set_duration(Duration::from_millis(ms.clamp(1, 60_000) as u64));
+    }
+    if *last_confirmed != Some(confirmed.0) {
+        *last_confirmed = Some(confirmed.0);
+        timer.0.reset();
+        return;
+    }
+    timer.0.tick(time.delta());
+    if !timer.0.just_finished() {
+        return;
+    }
+    net.resend_inputs_from(confirmed.0);
+}
+
+/// Tell the relay the score each time it changes, for its results log.
+fn report_score(mut net: ResMut<NetSocket>, score: Res<Score>) {
+    if score.is_changed() && !score.is_added() {
+        net.send(&ClientMessage::Scores {
+            scores: score.points.to_vec(),
+        });
+    }
+}
+
+/// Tell the relay we are leaving so it can free our slot right away. Quitting
+/// mid-match is an abort, so the opponent sees why the match ended; once
+/// somebody has won it is just goodbye.
+fn send_goodbye_on_exit(
+    mut exits: MessageReader<AppExit>,
+    net: Option<ResMut<NetSocket>>,
+    state: Option<Res<ConnectionState>>,
+    score: Option<Res<Score>>,
+    winner: Option<Res<MatchWinner>>,
+    spectate: Option<Res<Spectate>>,
+) {
+    if exits.read().next().is_none() {
+        return;
+    }
+    if let Some(mut net) = net {
+        let state = state.as_deref();
+        if matches!(
+            state,
+            Some(
+                ConnectionState::StartMenu
+                    | ConnectionState::NoOpponent
+                    | ConnectionState::RelayClosed
+                    | ConnectionState::Replaying
+                    | ConnectionState::ReplayFinished
+            )
+        ) {
+            return;
+        }
+        let won = winner.is_some_and(|winner| winner.0.is_some());
+        if state == Some(&ConnectionState::Playing) && !won && spectate.is_none() {
+            // Once more, in case the last report was lost.
+            if let Some(score) = score {
+                net.send(&ClientMessage::Scores {
+                    scores: score.points.to_vec(),
+                });
+            }
+            net.send(&ClientMessage::Abort {
+                reason: AbortReason::RageQuit,
+            });
+        } else {
+            net.send(&ClientMessage::Goodbye);
+        }
+        // No frames left to wait for it in.
+        net.release_held(true);
+    }
+}
+
+fn release_held_datagrams(mut net: ResMut<NetSocket>) {
+    net.release_held(false);
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn receive_relay_messages(
+    mut net: ResMut<NetSocket>,
+    mut state: ResMut<ConnectionState>,
+    mut local_slot: ResMut<LocalPlayerSlot>,
+    mut sync: TickSync,
+    mut relay_config: ResMut<RelayConfig>,
+    mut roster: ResMut<PlayerRoster>,
+    mut tick_log: Option<ResMut<PongTickLog>>,
+    budget: Option<Res<PacketBudget>>,
+    room: Res<RoomName>,
+    mut rtt: ResMut<RelayRtt>,
+    mut desync: ResMut<DesyncCheck>,
+    mut resync: ResMut<Resync>,
+    mut pause: ResMut<MatchPause>,
+    mut rematch: ResMut<Rematch>,
+    mut chat: ResMut<Chat>,
+) {
+    let mut buf = [0u8; 1024];
+    loop {
+        let len = match net.socket.recv(&mut buf) {
+            Ok(len) => len,
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+            Err(e) => {
+                eprintln!("net_pong: recv error: {e}");
+                if state.is_connected() {
+                    lose_connection(&mut net, &mut state, &room, "the socket failed");
+                }
+                break;
+            }
+        };
+
+        net.stats.bytes_received += len as u64;
+        let msg = match net.receive(&buf[..len]) {
+            Ok(msg) => msg,
+            Err(dropped) => {
+                net.stats.count_dropped(&dropped);
+                if matches!(dropped, Dropped::Malformed)
+                    && let Some(tick_log) = &mut tick_log
+                {
+                    tick_log.0.dump("malformed message from relay");
+                }
+                continue;
+            }
+        };
+        net.last_heard = Instant::now();
+        net.packets_received += 1;
+        net.stats.packets_received += 1;
+
+        if *state == ConnectionState::Reconnecting
+            && matches!(
+                msg,
+                RelayMessage::TickInputs { .. } | RelayMessage::NetStats { .. }
+            )
+        {
+            // Only seated players hear these: the relay never lost us.
+            println!("net_pong: relay is back; carrying on");
+            net.reconnecting_since = None;
+            *state = ConnectionState::Playing;
+        }
+
+        match msg {
+            RelayMessage::Welcome { player_slot } => {
+                // A player hears Welcome mid-match only by asking to be
+                // seated again, in the slot it had. Any other slot is a
+                // relay that lost the match and seated us afresh.
+                let reseated = match *state {
+                    ConnectionState::Playing => local_slot.0 != SPECTATOR_SLOT,
+                    ConnectionState::Reconnecting => true,
+                    _ => false,
+                };
+                if reseated && player_slot != local_slot.0 {
+                    relay_lost_match(&mut net, &mut state, "it seated us in another slot");
+                    break;
+                }
+                local_slot.0 = player_slot;
+                if matches!(
+                    *state,
+                    ConnectionState::Connecting | ConnectionState::Queued(_)
+                ) {
+                    net.reconnecting_since = None;
+                    *state = ConnectionState::WaitingForOpponent;
+                    println!("net_pong: assigned slot {player_slot}");
+                }
+            }
+            RelayMessage::GameStart => {
+                // A resumed match goes on with Resumed, and one under way
+                // does not start again. The grace second lets through an
+                // answer to a Hello sent just before it started.
+                let restarted = match *state {
+                    ConnectionState::Reconnecting => true,
+                    ConnectionState::Playing => {
+                        local_slot.0 != SPECTATOR_SLOT && sync.confirmed.0 >= sync.tick_rate.0
+                    }
+                    _ => false,
+                };
+                if restarted {
+                    relay_lost_match(&mut net, &mut state, "it started a new match");
+                    break;
+                }
+                if matches!(
+                    *state,
+                    ConnectionState::Connecting
+                        | ConnectionState::Queued(_)
+                        | ConnectionState::WaitingForOpponent
+                ) {
+                    *state = ConnectionState::Playing;
+                    net.stats = MatchNetStats::default();
+                    net.send_opening_inputs(sync.delay.0);
+                    sync.next_input.0 = sync.delay.0;
+                    println!("net_pong: game starting!");
+                }
+            }
+            RelayMessage::TickInputs { tick, inputs } => {
+                // Spectators send no input, and the relay waits on no acks
+                // from them.
+                let watching = local_slot.0 == SPECTATOR_SLOT;
+                if tick < sync.confirmed.0 {
+                    // A retransmission: our earlier ack was lost. Ack again.
+                    net.stats.repeated_ticks += 1;
+                    if !watching {
+                        net.send(&ClientMessage::AckTick { tick });
+                    }
+                    continue;
+                }
+                if tick >= sync.next_input.0 && !watching {
+                    // The relay cannot have this tick without our input.
+                    net.ignored_tick_inputs += 1;
+                    net.stats.ignored_inputs += 1;
+                    eprintln!(
+                        "net_pong: ignored inputs for tick {tick} while on tick {} ({} so far)",
+                        sync.sim_tick.0, net.ignored_tick_inputs
+                    );
+                    if let Some(tick_log) = &mut tick_log {
+                        let reason = format!("inputs for tick {tick} on tick {}", sync.sim_tick.0);
+                        tick_log.0.dump(&reason);
+                    }
+                    continue;
+                }
+                if inputs.len() != sync.players.0 {
+                    eprintln!(
+                        "net_pong: the relay seats {} players a match, not {}; \
+                         start it and every client with the same --players",
+                        inputs.len(),
+                        sync.players.0
+                    );
+                    net.send(&ClientMessage::Goodbye);
+                    *state = ConnectionState::WrongPlayerCount(inputs.len());
+                    break;
+                }
+                let mut movement = [0.0; MAX_PLAYERS];
+                for (i, Payload(player_input)) in inputs.iter().enumerate().take(MAX_PLAYERS) {
+                    movement[i] = player_input.movement;
+                }
+                sync.buffer.0.insert(tick, movement);
+                if let Some(recorder) = &mut sync.recorder {
+                    recorder.record(tick, movement);
+                }
+                match sync.buffer.first_missing(sync.confirmed.0, tick) {
+                    // Until its snapshot arrives a spectator has nowhere to
+                    // play from, so nothing is missing yet.
+                    Some(_) if watching && sync.sim_tick.0 == 0 => {}
+                    // Broadcasts go out in order, so a gap means we missed
+                    // one; ask for it rather than waiting out the relay's
+                    // retransmit timer.
+                    Some(missing) => net.send(&ClientMessage::RequestTick { tick: missing }),
+                    // On a packet budget, the ack goes out with the next batch.
+                    None if budget.is_none() && !watching => {
+                        net.send(&ClientMessage::AckTick { tick });
+                    }
+                    None => {}
+                }
+            }
+            RelayMessage::Rejected {
+                reason: RejectReason::BadRoom,
+            } if *state == ConnectionState::Reconnecting => {
+                // The relay still has the match running; if we are seated in
+                // it, its broadcasts will reach us once the link recovers.
+            }
+            RelayMessage::Rejected { reason } => {
+                if matches!(
+                    *state,
+                    ConnectionState::Connecting
+                        | ConnectionState::Queued(_)
+                        | ConnectionState::Reconnecting
+                ) {
+                    *state = ConnectionState::Rejected(reason);
+                    eprintln!("net_pong: relay rejected us: {reason}");
+                }
+            }
+            RelayMessage::Config { entries } => {
+                relay_config.apply(entries);
+            }
+            RelayMessage::QueuePosition { position } => {
+                if matches!(
+                    *state,
+                    ConnectionState::Connecting | ConnectionState::Queued(_)
+                ) && *state != ConnectionState::Queued(position)
+                {
+                    *state = ConnectionState::Queued(position);
+                    println!("net_pong: queued at position {position}");
+                }
+            }
+            RelayMessage::PlayerJoined { slot, name } => {
+                if let Some(entry) = roster.names.get_mut(slot as usize) {
+                    println!("net_pong: {name} joined in slot {slot}");
+                    *entry = Some(name);
+                }
+            }
+            RelayMessage::PlayerLeft { slot } => {
+                if let Some(entry) = roster.names.get_mut(slot as usize) {
+                    let name = entry.take().unwrap_or_default();
+                    println!("net_pong: {name} left slot {slot}");
+                }
+                if slot != local_slot.0 && *state == ConnectionState::Playing {
+                    // Quitting mid-match is an abort, so they were cut off
+                    // and may come back.
+                    if room.0.is_some() {
+                        lose_connection(&mut net, &mut state, &room, "the opponent dropped out");
+                    } else {
+                        *state = ConnectionState::OpponentLeft;
+                    }
+                }
+            }
+            RelayMessage::MatchAborted { by_slot, reason } => {
+                if let Some(entry) = roster.names.get_mut(by_slot as usize) {
+                    let name = entry.take().unwrap_or_default();
+                    println!("net_pong: {name} in slot {by_slot} ended the match: {reason}");
+                }
+                if by_slot != local_slot.0 && *state == ConnectionState::Playing {
+                    *state = ConnectionState::OpponentAborted(reason);
+                }
+            }
+            RelayMessage::ServerShutdown => {
+                println!("net_pong: relay shut down");
+                if *state == ConnectionState::Playing && room.0.is_some() {
+                    // It may be restarting; its rooms come back with it.
+                    lose_connection(&mut net, &mut state, &room, "the relay shut down");
+                } else if *state != ConnectionState::Reconnecting {
+                    *state = ConnectionState::RelayClosed;
+                }
+            }
+            // Only sent to room owners' admin tools and room browsers.
+            RelayMessage::RoomAdminResult { .. } | RelayMessage::RoomList { .. } => {}
+            RelayMessage::Resumed { tick } => {
+                if *state == ConnectionState::Reconnecting {
+                    println!("net_pong: match resumed at tick {tick}");
+                    net.reconnecting_since = None;
+                    net.resend_inputs_from(tick);
+                    *state = ConnectionState::Playing;
+                }
+            }
+            RelayMessage::NetStats { players } => {
+                rtt.0 = players
+                    .iter()
+                    .find(|stats| stats.slot == local_slot.0)
+                    .and_then(|stats| stats.rtt_ms);
+            }
+            RelayMessage::StateHash { slot, tick, hash } => {
+                desync.theirs.entry(tick).or_default().push((slot, hash));
+            }
+            RelayMessage::StateDump { slot, tick, state } => {
+                // They found it first; they need our state as much as we need
+                // theirs.
+                if desync.start(tick, local_slot.0, &mut net)
+                    && let Some(tick_log) = &mut tick_log
+                {
+                    tick_log.0.dump(&format!("desync at tick {tick}"));
+                }
+                desync.add_state(tick, slot, state);
+            }
+            RelayMessage::SnapshotRequested { slot } => {
+                if slot == SPECTATOR_SLOT {
+                    println!("net_pong: a spectator asked for a snapshot");
+                } else {
+                    println!("net_pong: player {slot} asked for a snapshot");
+                }
+                resync.requested = true;
+            }
+            RelayMessage::Snapshot { slot, tick, state } => {
+                if resync.asked_at.is_some() {
+                    resync.received = Some((slot, tick, state));
+                }
+            }
+            RelayMessage::Paused { by_slot, tick } => {
+                if pause.by_slot.is_none() {
+                    println!("net_pong: player {by_slot} paused the match before tick {tick}");
+                }
+                pause.at = Some(tick);
+                pause.by_slot = Some(by_slot);
+                if matches!(pause.asking, Some(PauseRequest::Pause(_))) {
+                    pause.asking = None;
+                }
+            }
+            RelayMessage::Unpaused { by_slot } => {
+                if pause.at.is_some() {
+                    println!("net_pong: player {by_slot} carried on");
+                }
+                pause.at = None;
+                pause.by_slot = None;
+                if pause.asking == Some(PauseRequest::Unpause) {
+                    pause.asking = None;
+                }
+            }
+            RelayMessage::RematchRequested { slot } => {
+                if !rematch.requested_by.contains(&slot) {
+                    println!("net_pong: player {slot} wants a rematch");
+                    rematch.requested_by.push(slot);
+                }
+            }
+            RelayMessage::RematchStart => {
+                if rematch.asked || local_slot.0 == SPECTATOR_SLOT {
+                    rematch.starting = true;
+                }
+            }
+            RelayMessage::Spectating { tick } => {
+                if *state == ConnectionState::Connecting {
+                    *state = ConnectionState::Playing;
+                    net.stats = MatchNetStats::default();
+                    println!("net_pong: watching from tick {tick}");
+                }
+            }
+            RelayMessage::Chat { slot, text } => {
+                let from = roster
+                    .names
+                    .get(slot as usize)
+                    .cloned()
+                    .flatten()
+                    .unwrap_or_else(|| format!("Player {}", slot + 1));
+                chat.push(from, &text);
+            }
+        }
+    }
+
+    if !sync.tick_ready.0 {
+        sync.tick_ready.0 = sync.buffer.load(sync.sim_tick.0, &mut sync.input);
+    }
+}
@@ -0,0 +1,47 @@
+//! Tuning constants; the arena and gameplay ones are pong_sim's.
+
+use std::time::Duration;
+
+use prototype_relay::Tick;
+
+/// Ticks per second without `--tick-rate`. Every client simulates exactly
+/// `1 / TickRate` seconds per tick, whatever its clock says, and paces
+/// FixedUpdate to match.
+pub const DEFAULT_TICK_RATE_HZ: u32 = 64;
+
+/// The relay to try without `--relay`, and what the start menu offers.
+pub const DEFAULT_RELAY: &str = "127.0.0.1:7700";
+
+/// Sent in Hello, with the score limit, so the relay never pairs us with a
+/// different game.
+pub const GAME_ID: &str = "net_pong";
+/// The arcade's shared assets, as `cargo run -p net_pong` finds them from
+/// this crate.
+pub const ASSET_DIR: &str = "../../assets";
+/// Points to win, without `--score-limit`.
+pub const DEFAULT_SCORE_LIMIT: u32 = 11;
+/// The relay sends NetStats about once a second while a match runs, so this
+/// long without a word means the link is gone.
+pub const RELAY_SILENCE_LIMIT: Duration = Duration::from_secs(3);
+/// How long to keep asking the relay to resume before calling it lost.
+pub const RECONNECT_GIVE_UP: Duration = Duration::from_secs(30);
+/// How long to sit in the waiting room, without `--opponent-wait`.
+pub const DEFAULT_OPPONENT_WAIT_SECS: u64 = 120;
+/// How often Hello (or Resume) goes out while the relay answers it.
+pub const HELLO_RETRY_FIRST: Duration = Duration::from_millis(500);
+/// The longest wait between Hellos the relay has not answered.
+pub const HELLO_RETRY_MAX: Duration = Duration::from_secs(8);
+/// Ticks between reading local input and playing it, until the relay has
+/// measured our round trip. Must not exceed the relay's `--input-window`
+/// (default 3), or it drops the input.
+pub const INPUT_DELAY: Tick = 2;
+/// The most input delay a slow round trip gets: the relay's default
+/// `--input-window`, past which it drops the input.
+pub const MAX_INPUT_DELAY: Tick = 3;
+/// NetStats in a row that must call for a shorter input delay before it is
+/// shortened. A longer one is taken at once.
+pub const DELAY_SETTLE_REPORTS: u32 = 3;
+/// Ticks of our own input kept to re-send after a resume. The relay resumes
+/// from the earliest tick either player still needs, which is never more
+/// than a batch or so behind us.
+pub const SENT_INPUT_HISTORY: usize = 64;
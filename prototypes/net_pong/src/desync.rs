@@ -0,0 +1,227 @@
+//! Desync plugin: state hashes compared between clients.
+//!
+//! Every `STATE_HASH_INTERVAL` ticks each client hashes its game state and
+//! sends the hash to the others through the relay. If a confirmed tick's
+//! hashes differ, the simulations have drifted apart: a "DESYNC at tick N"
+//! banner goes up, the clients swap their states for that tick, and each
+//! writes every state it has to a `net_pong-<millis>.desync` file in the
+//! working directory (and dumps `--tick-log`, if given).
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use bevy::prelude::*;
+use prototype_relay::{ClientMessage, PlayerSlot, Tick};
+
+use crate::connection::{
+    ConfirmedTick, LocalPlayerSlot, NetSocket, SimulationTick, is_playing, is_spectating,
+    receive_relay_messages,
+};
+use crate::constants::DEFAULT_TICK_RATE_HZ;
+use crate::rollback::GameSnapshot;
+use crate::shared::PongTickLog;
+
+/// Ticks between state hashes: twice a second at the default tick rate.
+const STATE_HASH_INTERVAL: Tick = DEFAULT_TICK_RATE_HZ / 2;
+/// Our hashes kept for comparing with the others', which lag a round trip.
+const STATE_HASH_HISTORY: usize = 16;
+const DESYNC_FONT_SIZE: f32 = 40.0;
+
+pub struct NetPongDesyncPlugin;
+
+impl Plugin for NetPongDesyncPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DesyncCheck>()
+            .add_systems(Startup, spawn_desync_banner)
+            .add_systems(
+                Update,
+                (
+                    send_state_hashes
+                        .run_if(is_playing)
+                        .run_if(not(is_spectating)),
+                    check_state_hashes,
+                    show_desync_banner,
+                )
+                    .chain()
+                    .after(receive_relay_messages),
+            );
+    }
+}
+
+/// Our state hashes and everyone else's, until they can be compared.
+#[derive(Resource, Default)]
+pub struct DesyncCheck {
+    /// Our hash and state after each hashed tick, lately. With rollback a
+    /// guessed tick is hashed again when replayed.
+    pub ours: BTreeMap<Tick, (u64, String)>,
+    /// The other players' hashes by tick, until the tick is confirmed here.
+    pub theirs: BTreeMap<Tick, Vec<(PlayerSlot, u64)>>,
+    /// Hashes for ticks before this one have been sent.
+    pub next_to_send: Tick,
+    pub desync: Option<Desync>,
+}
+
+/// The first tick found out of sync, and what each player had after it.
+pub struct Desync {
+    tick: Tick,
+    states: BTreeMap<PlayerSlot, String>,
+    path: PathBuf,
+    /// We sent our state to put it right.
+    pub resynced: bool,
+}
+
+impl DesyncCheck {
+    /// Declare a desync at `tick` and send our state for it to the others.
+    /// Only the first one counts; returns whether this was it.
+    pub fn start(&mut self, tick: Tick, local_slot: PlayerSlot, net: &mut NetSocket) -> bool {
+        if self.desync.is_some() {
+            return false;
+        }
+        eprintln!("net_pong: DESYNC at tick {tick}");
+        let millis = SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis())
+            .unwrap_or_default();
+        self.desync = Some(Desync {
+            tick,
+            states: BTreeMap::new(),
+            path: PathBuf::from(format!("net_pong-{millis}.desync")),
+            resynced: false,
+        });
+        if let Some((_, state)) = self.ours.get(&tick).cloned() {
+            net.send(&ClientMessage::StateDump {
+                tick,
+                state: state.clone(),
+            });
+            self.add_state(tick, local_slot, state);
+        }
+        true
+    }
+
+    /// Add a player's state after the desync tick to the dump file.
+    pub fn add_state(&mut self, tick: Tick, slot: PlayerSlot, state: String) {
+        let Some(desync) = &mut self.desync else {
+            return;
+        };
+        if desync.tick != tick {
+            return;
+        }
+        desync.states.insert(slot, state);
+        let mut text = format!("# net_pong: DESYNC at tick {tick}\n");
+        text += "# each player's state after that tick, by slot\n";
+        for (slot, state) in &desync.states {
+            text += &format!("{slot}: {state}\n");
+        }
+        match std::fs::write(&desync.path, text) {
+            Ok(()) => eprintln!("net_pong: wrote desync states to {}", desync.path.display()),
+            Err(e) => eprintln!(
+                "net_pong: failed to write desync states {}: {e}",
+                desync.path.display()
+            ),
+        }
+    }
+}
+
+/// Hash the state this tick left, every `STATE_HASH_INTERVAL` ticks.
+pub fn record_state_hash(world: &mut World) {
+    let tick = world.resource::<SimulationTick>().0;
+    if !tick.is_multiple_of(STATE_HASH_INTERVAL) {
+        return;
+    }
+    let snapshot = GameSnapshot::capture(world);
+    let mut check = world.resource_mut::<DesyncCheck>();
+    check
+        .ours
+        .insert(tick, (snapshot.hash(), format!("{snapshot:?}")));
+    while check.ours.len() > STATE_HASH_HISTORY {
+        check.ours.pop_first();
+    }
+}
+
+/// Send our hashes once their ticks are confirmed, and so final.
+fn send_state_hashes(
+    mut net: ResMut<NetSocket>,
+    confirmed: Res<ConfirmedTick>,
+    mut check: ResMut<DesyncCheck>,
+) {
+    if check.next_to_send >= confirmed.0 {
+        return;
+    }
+    let ready: Vec<_> = check
+        .ours
+        .range(check.next_to_send..confirmed.0)
+        .map(|(tick, (hash, _))| (*tick, *hash))
+        .collect();
+    for (tick, hash) in ready {
+        net.send(&ClientMessage::StateHash { tick, hash });
+    }
+    check.next_to_send = confirmed.0;
+}
+
+/// Compare the others' hashes with ours for every tick confirmed here.
+pub fn check_state_hashes(
+    mut net: ResMut<NetSocket>,
+    confirmed: Res<ConfirmedTick>,
+    local_slot: Res<LocalPlayerSlot>,
+    mut check: ResMut<DesyncCheck>,
+    tick_log: Option<ResMut<PongTickLog>>,
+) {
+    let Some(oldest) = check.theirs.keys().next().copied() else {
+        return;
+    };
+    if oldest >= confirmed.0 {
+        return;
+    }
+    let pending = check.theirs.split_off(&confirmed.0);
+    let comparable = std::mem::replace(&mut check.theirs, pending);
+    let mismatch = comparable.into_iter().find_map(|(tick, hashes)| {
+        let (ours, _) = check.ours.get(&tick)?;
+        hashes.iter().any(|(_, hash)| hash != ours).then_some(tick)
+    });
+    if let Some(tick) = mismatch
+        && check.start(tick, local_slot.0, &mut net)
+        && let Some(mut tick_log) = tick_log
+    {
+        tick_log.0.dump(&format!("desync at tick {tick}"));
+    }
+}
+
+#[derive(Component)]
+struct DesyncBanner;
+
+fn spawn_desync_banner(mut commands: Commands) {
+    commands.spawn((
+        DesyncBanner,
+        Text::new(""),
+        TextFont::from_font_size(DESYNC_FONT_SIZE),
+        TextColor(Color::srgb(1.0, 0.2, 0.2)),
+        Visibility::Hidden,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(25.0),
+            left: Val::Percent(50.0),
+            ..default()
+        },
+    ));
+}
+
+fn show_desync_banner(
+    check: Res<DesyncCheck>,
+    mut query: Query<(&mut Text, &mut Visibility), With<DesyncBanner>>,
+) {
+    if !check.is_changed() {
+        return;
+    }
+    for (mut text, mut visibility) in &mut query {
+        // A resync clears it.
+        match &check.desync {
+            Some(desync) => {
+                let resynced = if desync.resynced { " (resynced)" } else { "" };
+                **text = format!("DESYNC at tick {}{resynced}", desync.tick);
+                *visibility = Visibility::Visible;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
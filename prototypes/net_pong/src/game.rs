@@ -0,0 +1,198 @@
+//! Game plugin: deterministic simulation (lockstep-gated FixedUpdate).
+//!
+//! `--tick-rate` (default `DEFAULT_TICK_RATE_HZ`) is part of the game id, like
+//! the score limit: players at different rates would play different games.
+//!
+//! `--players 4` plays in pong_sim's square arena, a paddle on every side:
+//! slots 0 and 1 left and right as usual, 2 and 3 along the bottom and top,
+//! moving sideways with the same Up and Down. Missing the ball gives the
+//! point to whoever touched it last. The relay must be started with the
+//! same `--players`; the count is part of the game id too, and a client that
+//! finds the relay seating a different number says so and leaves.
+
+use std::collections::BTreeMap;
+
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use pong_sim::fixed::Fx;
+use pong_sim::{PLAYER_COUNT, PongState, SimEvent};
+use prototype_relay::{PlayerSlot, Tick};
+
+use crate::connection::{
+    ConfirmedTick, InputBuffer, SimulationTick, TickReady, is_playing, tick_is_ready,
+};
+use crate::constants::DEFAULT_TICK_RATE_HZ;
+use crate::desync::record_state_hash;
+use crate::rollback::Rollback;
+use crate::shared::{
+    MatchWinner, PaddleInput, PlayerCount, PongTickLog, Score, ScoreLimit, TickDt, TickRate,
+    TickSnapshot,
+};
+use crate::verify::Ghost;
+
+pub struct NetPongGamePlugin;
+
+impl Plugin for NetPongGamePlugin {
+    fn build(&self, app: &mut App) {
+        let tick_rate = app
+            .world()
+            .get_resource::<TickRate>()
+            .map_or(DEFAULT_TICK_RATE_HZ, |rate| rate.0);
+        let players = app
+            .world()
+            .get_resource::<PlayerCount>()
+            .map_or(PLAYER_COUNT, |players| players.0);
+        app.insert_resource(PongState::new(players))
+            .init_resource::<Score>()
+            .init_resource::<MatchWinner>()
+            .insert_resource(TickDt(Fx::ONE / tick_rate as i32))
+            .insert_resource(Time::<Fixed>::from_hz(f64::from(tick_rate)))
+            .init_resource::<TickEvents>()
+            .add_message::<SimEvent>()
+            .init_schedule(PongTick)
+            .add_systems(
+                PongTick,
+                (
+                    clear_tick_events,
+                    step_simulation.run_if(no_winner_yet),
+                    record_tick_log,
+                    record_state_hash,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                FixedUpdate,
+                (play_tick, post_tick_advance)
+                    .chain()
+                    .run_if(is_playing)
+                    .run_if(tick_is_ready)
+                    .run_if(not(resource_exists::<Rollback>)),
+            )
+            .add_systems(Update, emit_confirmed_events);
+    }
+}
+
+/// One tick of the simulation, for `SimulationTick` with `PaddleInput`.
+/// Rollback runs it several times in a frame to catch up.
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PongTick;
+
+fn play_tick(world: &mut World) {
+    world.run_schedule(PongTick);
+}
+
+/// The `SimEvent`s of each tick played but not yet confirmed. Rollback may
+/// play a tick again with other inputs, so they only go out as messages
+/// once the relay has confirmed the tick.
+#[derive(Resource, Default)]
+pub struct TickEvents(pub BTreeMap<Tick, Vec<SimEvent>>);
+
+/// Where the simulation reports `SimEvent`s, under the tick being played.
+#[derive(SystemParam)]
+struct TickEventLog<'w> {
+    sim_tick: Res<'w, SimulationTick>,
+    events: ResMut<'w, TickEvents>,
+}
+
+impl TickEventLog<'_> {
+    fn push(&mut self, event: SimEvent) {
+        let tick = self.sim_tick.0;
+        self.events.0.entry(tick).or_default().push(event);
+    }
+}
+
+/// A tick played again starts without the events of its last playing.
+pub fn clear_tick_events(sim_tick: Res<SimulationTick>, mut events: ResMut<TickEvents>) {
+    events.0.remove(&sim_tick.0);
+}
+
+/// Send the events of every confirmed tick on to whatever shows them.
+pub fn emit_confirmed_events(
+    confirmed: Res<ConfirmedTick>,
+    mut events: ResMut<TickEvents>,
+    mut writer: MessageWriter<SimEvent>,
+) {
+    let unconfirmed = events.0.split_off(&confirmed.0);
+    for (_, tick_events) in std::mem::replace(&mut events.0, unconfirmed) {
+        writer.write_batch(tick_events);
+    }
+}
+
+/// Once somebody has won, ticks still come and go but nothing moves.
+fn no_winner_yet(winner: Res<MatchWinner>) -> bool {
+    winner.0.is_none()
+}
+
+/// Play one tick, and note who won if a point ended the match. Under
+/// `--verify` the ghost gets each phase of the tick as it is played.
+#[allow(clippy::too_many_arguments)]
+fn step_simulation(
+    input: Res<PaddleInput>,
+    dt: Res<TickDt>,
+    mut state: ResMut<PongState>,
+    mut score: ResMut<Score>,
+    limit: Res<ScoreLimit>,
+    sim_tick: Res<SimulationTick>,
+    mut winner: ResMut<MatchWinner>,
+    mut events: TickEventLog,
+    ghost: Option<ResMut<Ghost>>,
+) {
+    let tick_events = match ghost {
+        Some(mut ghost) => state.step_traced(&input.movement, dt.0, |phase, state| {
+            ghost.played_phases.push((phase, *state));
+        }),
+        None => state.step(&input.movement, dt.0),
+    };
+    for event in tick_events {
+        events.push(event);
+    }
+    score.follow(&state);
+    if let Some(slot) = winning_slot(&state, limit.0) {
+        winner.0 = Some((slot, sim_tick.0));
+    }
+}
+
+/// Ticks stop once somebody wins, so whoever is at the limit got there on
+/// the tick just played.
+pub fn winning_slot(state: &PongState, limit: u32) -> Option<PlayerSlot> {
+    let reached_limit = |points: &u32| limit > 0 && *points >= limit;
+    state
+        .score
+        .iter()
+        .position(reached_limit)
+        .map(|slot| slot as PlayerSlot)
+}
+
+/// Remember this tick's inputs and the state they produced.
+fn record_tick_log(
+    tick_log: Option<ResMut<PongTickLog>>,
+    sim_tick: Res<SimulationTick>,
+    input: Res<PaddleInput>,
+    state: Res<PongState>,
+) {
+    let Some(mut tick_log) = tick_log else {
+        return;
+    };
+    tick_log.0.record(
+        sim_tick.0,
+        TickSnapshot {
+            inputs: input.movement,
+            state: *state,
+        },
+    );
+}
+
+fn post_tick_advance(
+    mut sim_tick: ResMut<SimulationTick>,
+    mut confirmed: ResMut<ConfirmedTick>,
+    mut tick_ready: ResMut<TickReady>,
+    mut buffer: ResMut<InputBuffer>,
+    mut input: ResMut<PaddleInput>,
+) {
+    sim_tick.0 += 1;
+    confirmed.0 = sim_tick.0;
+    buffer.0 = buffer.0.split_off(&sim_tick.0);
+    // The next tick may have arrived already, and can play in this frame.
+    tick_ready.0 = buffer.load(sim_tick.0, &mut input);
+}
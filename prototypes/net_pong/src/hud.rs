@@ -0,0 +1,134 @@
+//! HUD plugin: connection health in a corner, toggled with F3.
+//!
+//! F3 shows how the connection is doing in a corner: the round-trip time the
+//! relay measured for us and the input delay it led to, the tick being
+//! played, how many ticks a second the simulation spent waiting on the other
+//! player, and datagrams each way.
+
+use bevy::prelude::*;
+use prototype_relay::Tick;
+
+use crate::connection::{InputDelay, NetSocket, RelayRtt, SimulationTick, is_playing};
+
+const HUD_FONT_SIZE: f32 = 16.0;
+const HUD_MARGIN: f32 = 10.0;
+
+pub struct NetPongHudPlugin;
+
+impl Plugin for NetPongHudPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NetHud>()
+            .add_systems(Startup, spawn_hud)
+            .add_systems(FixedPostUpdate, count_stalled_steps.run_if(is_playing))
+            .add_systems(Update, (toggle_hud, update_hud).chain());
+    }
+}
+
+/// What the HUD shows, gathered as it happens and totted up once a second.
+#[derive(Resource)]
+pub struct NetHud {
+    pub visible: bool,
+    /// The tick the simulation was on at the last fixed step.
+    last_step_tick: Tick,
+    /// Fixed steps this second on which no tick was played.
+    stalled_steps: u32,
+    meter: Timer,
+    /// Last second's numbers: stalled steps, datagrams sent and received.
+    pub rates: (u32, u64, u64),
+    packets_at_last_reading: (u64, u64),
+}
+
+impl Default for NetHud {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            last_step_tick: 0,
+            stalled_steps: 0,
+            meter: Timer::from_seconds(1.0, TimerMode::Repeating),
+            rates: (0, 0, 0),
+            packets_at_last_reading: (0, 0),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct HudText;
+
+fn spawn_hud(mut commands: Commands) {
+    commands.spawn((
+        HudText,
+        Text::new(""),
+        TextFont::from_font_size(HUD_FONT_SIZE),
+        TextColor(Color::srgb(0.6, 1.0, 0.6)),
+        Visibility::Hidden,
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(HUD_MARGIN),
+            right: Val::Px(HUD_MARGIN),
+            ..default()
+        },
+    ));
+}
+
+/// A fixed step that did not move the simulation on was spent waiting.
+fn count_stalled_steps(sim_tick: Res<SimulationTick>, mut hud: ResMut<NetHud>) {
+    if sim_tick.0 == hud.last_step_tick {
+        hud.stalled_steps += 1;
+    }
+    hud.last_step_tick = sim_tick.0;
+}
+
+/// F3 flips the HUD, as does its entry in the pause menu's settings.
+fn toggle_hud(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut hud: ResMut<NetHud>,
+    mut query: Query<&mut Visibility, With<HudText>>,
+) {
+    if keyboard.just_pressed(KeyCode::F3) {
+        hud.visible = !hud.visible;
+    }
+    let wanted = if hud.visible {
+        Visibility::Visible
+    } else {
+        Visibility::Hidden
+    };
+    for mut visibility in &mut query {
+        visibility.set_if_neq(wanted);
+    }
+}
+
+pub fn update_hud(
+    time: Res<Time>,
+    net: Option<Res<NetSocket>>,
+    sim_tick: Res<SimulationTick>,
+    rtt: Res<RelayRtt>,
+    delay: Res<InputDelay>,
+    mut hud: ResMut<NetHud>,
+    mut query: Query<&mut Text, With<HudText>>,
+) {
+    let Some(net) = net else {
+        return;
+    };
+    hud.meter.tick(time.delta());
+    if hud.meter.just_finished() {
+        let (sent, received) = hud.packets_at_last_reading;
+        hud.rates = (
+            std::mem::take(&mut hud.stalled_steps),
+            net.packets_sent - sent,
+            net.packets_received - received,
+        );
+        hud.packets_at_last_reading = (net.packets_sent, net.packets_received);
+    }
+    if !hud.visible {
+        return;
+    }
+    let rtt = rtt.0.map_or("--".into(), |ms| ms.to_string());
+    let (stalled, sent, received) = hud.rates;
+    for mut text in &mut query {
+        **text = format!(
+            "RTT {rtt} ms, input delay {} ticks\ntick {}\nwaited {stalled} ticks/s\n\
+             sent {sent}/s, received {received}/s",
+            delay.0, sim_tick.0
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Input plugin: local keyboard + gamepad -> send to relay.
+//!
+//! Local input is sent for `INPUT_DELAY` ticks after the one being played,
+//! so by the time the simulation gets there the relay has usually broadcast
+//! it already. Broadcasts wait in a buffer until their tick comes up, and the
+//! simulation only stalls when the round trip takes longer than the delay.
+//! Once the relay reports our round trip, the delay follows it as
+//! `ceil(RTT / tick) + 1`, up to `MAX_INPUT_DELAY`: longer as soon as the
+//! round trip grows, shorter once `DELAY_SETTLE_REPORTS` reports agree.
+
+use std::path::Path;
+
+use arcade_input::{
+    Action, ActionMap, ActionState, ActionSystems, ArcadeInputPlugin, Device, InputAssignments,
+    PlayerAssignment,
+};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use prototype_relay::{ClientMessage, Tick};
+
+use crate::bot::Bot;
+use crate::budget::PacketBudget;
+use crate::chat::Chat;
+use crate::connection::{
+    ConfirmedTick, InputDelay, NetSocket, NextInputTick, SimulationTick, is_playing, is_spectating,
+    need_to_send,
+};
+use crate::shared::{PaddleInput, PongInput};
+
+pub struct NetPongInputPlugin;
+
+impl Plugin for NetPongInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ArcadeInputPlugin)
+            .init_resource::<PaddleInput>()
+            .init_resource::<LocalMovement>()
+            .insert_resource(ControlBindings(ActionMap::load(Path::new(CONTROLS_PATH))))
+            .init_resource::<GamepadChoice>()
+            .add_systems(PreUpdate, assign_local_player.before(ActionSystems))
+            .add_systems(
+                Update,
+                (
+                    list_gamepads,
+                    read_local_movement.run_if(not(resource_exists::<Bot>)),
+                    read_and_send_local_input
+                        .run_if(is_playing)
+                        .run_if(need_to_send)
+                        .run_if(not(is_spectating)),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Where we are steering our paddle: from the keyboard and gamepad, or from
+/// `NetPongBotPlugin`.
+#[derive(Resource, Default)]
+pub struct LocalMovement(pub f32);
+
+/// The one local player holds the keyboard and the chosen gamepad, bound
+/// as the controls page says.
+fn assign_local_player(
+    bindings: Res<ControlBindings>,
+    choice: Res<GamepadChoice>,
+    mut assignments: ResMut<InputAssignments>,
+) {
+    if !(bindings.is_changed() || choice.is_changed()) {
+        return;
+    }
+    let gamepad = Device::Gamepad(choice.0.map_or(0, |n| n - 1));
+    assignments.0 = vec![PlayerAssignment {
+        devices: vec![Device::Keyboard, gamepad],
+        map: bindings.0.clone(),
+    }];
+}
+
+pub fn read_local_movement(
+    actions: Res<ActionState>,
+    chat: Res<Chat>,
+    mut movement: ResMut<LocalMovement>,
+) {
+    // Keys held while typing a chat line are not steering.
+    movement.0 = if chat.typing {
+        0.0
+    } else {
+        actions.player(0).value(Action::MoveY)
+    };
+}
+
+/// Which connected gamepad drives our paddle, counting from 1: `--gamepad`,
+/// or picked on the controls page. `None` takes the first.
+#[derive(Resource, Default)]
+pub struct GamepadChoice(pub Option<usize>);
+
+/// The connected gamepads, numbered, and the one that drives our paddle.
+#[derive(SystemParam)]
+pub struct PaddleGamepad<'w, 's> {
+    gamepads: Query<'w, 's, (Entity, &'static Gamepad, Option<&'static Name>)>,
+    choice: Res<'w, GamepadChoice>,
+}
+
+impl PaddleGamepad<'_, '_> {
+    /// Connected gamepads with their names, in the order they are numbered.
+    fn listed(&self) -> Vec<(&Gamepad, String)> {
+        let mut listed: Vec<_> = self.gamepads.iter().collect();
+        listed.sort_by_key(|(entity, _, _)| *entity);
+        listed
+            .into_iter()
+            .map(|(_, gamepad, name)| {
+                let name = name.map_or_else(|| "Gamepad".to_string(), |name| name.to_string());
+                (gamepad, name)
+            })
+            .collect()
+    }
+
+    pub fn describe(&self) -> String {
+        let listed = self.listed();
+        match self.choice.0 {
+            None => match listed.first() {
+                Some((_, name)) => format!("first connected ({name})"),
+                None => "none connected".to_string(),
+            },
+            Some(n) => match listed.get(n - 1) {
+                Some((_, name)) => format!("{n}. {name}"),
+                None => format!("{n} (not connected)"),
+            },
+        }
+    }
+}
+
+/// Number the gamepads on the console whenever one connects, for
+/// `--gamepad`.
+fn list_gamepads(added: Query<(), Added<Gamepad>>, gamepad: PaddleGamepad) {
+    if added.is_empty() {
+        return;
+    }
+    for (i, (_, name)) in gamepad.listed().iter().enumerate() {
+        println!("net_pong: gamepad {}: {name}", i + 1);
+    }
+}
+
+pub fn read_and_send_local_input(
+    movement: Res<LocalMovement>,
+    mut net: ResMut<NetSocket>,
+    sim_tick: Res<SimulationTick>,
+    delay: Res<InputDelay>,
+    mut next_input: ResMut<NextInputTick>,
+    confirmed: Res<ConfirmedTick>,
+    budget: Option<ResMut<PacketBudget>>,
+) {
+    let input = PongInput {
+        movement: movement.0,
+    };
+    // Input read on tick `t` is for tick `t + delay`. Usually that is one
+    // tick, but the simulation can play several in a frame.
+    let first_tick = next_input.0;
+    next_input.0 = sim_tick.0 + delay.0 + 1;
+    let owed = vec![input; (next_input.0 - first_tick) as usize];
+
+    let Some(mut budget) = budget else {
+        net.send_inputs(first_tick, &owed);
+        return;
+    };
+    // A full batch starts at this tick, padded out with inputs already sent,
+    // so it goes out just as the first is needed. Input is sent ahead of the
+    // broadcasts it follows, so it acks nothing; one AckTick covers every
+    // tick confirmed since the last batch.
+    budget.pending.extend(owed);
+    if budget.pending.len() >= budget.batch as usize {
+        let pending_from = next_input.0 - budget.pending.len() as Tick;
+        let padding = pending_from.saturating_sub(sim_tick.0) as usize;
+        let mut inputs = net.sent_from(sim_tick.0);
+        if inputs.len() < padding {
+            inputs.clear();
+        }
+        inputs.truncate(padding);
+        let first_tick = pending_from - inputs.len() as Tick;
+        inputs.append(&mut budget.pending);
+        net.send_inputs(first_tick, &inputs);
+        if let Some(tick) = confirmed.0.checked_sub(1) {
+            net.send(&ClientMessage::AckTick { tick });
+        }
+    }
+}
+
+/// Where `ControlBindings` are kept between runs.
+pub const CONTROLS_PATH: &str = "net_pong-controls.toml";
+
+/// Which keys and buttons move the paddle, serve and pause, as the controls
+/// page last saved them.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct ControlBindings(ActionMap);
@@ -0,0 +1,272 @@
+//! Networked Pong — deterministic lockstep over UDP.
+//!
+//! Two clients connect to a relay server. Each client sends its local input
+//! for the current tick; the relay broadcasts both inputs back. Both clients
+//! then advance the simulation identically. To make sure of "identically",
+//! the simulation keeps positions and velocities in fixed point (see
+//! `pong_sim::fixed`), never `f32`.
+//!
+//! Usage: `cargo run -p net_pong -- [--relay <addr>] [--name <name>] [--room <name>]
+//! [--tick-rate <hz>] [--score-limit <points>] [--players <n>] [--record <file>] [--play <file>] [--spectate]
+//! [--local-echo] [--gamepad <n>] [--neon] [--tick-log <ticks>] [--packet-rate <hz>] [--rollback]
+//! [--simulate-net <conditions>] [--version]`; `--help` says what each does.
+//!
+//! `--version` prints the build, protocol versions and features, and exits.
+//! In game, F1 or a gamepad's Select button shows the same report along with
+//! the GPU and gamepads in use.
+//!
+//! Each part of the game is a plugin in a module of its own, and the
+//! module says how that part behaves.
+
+mod args;
+mod bot;
+mod budget;
+mod career;
+mod chat;
+mod connection;
+mod constants;
+mod desync;
+mod game;
+mod hud;
+mod input;
+mod local_echo;
+mod match_over;
+mod neon;
+mod net_stats;
+mod pause_menu;
+mod quality;
+mod render;
+mod replay;
+mod resync;
+mod rollback;
+mod shared;
+mod stall;
+mod start_menu;
+mod verify;
+
+use std::time::Duration;
+
+use arcade_ui::{AboutInfo, AboutPlugin};
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use prototype_relay::ticklog::TickLog;
+use prototype_relay::{SPECTATOR_SLOT, deserialize};
+
+use crate::bot::{BOT_FRAMES_PER_TICK, NetPongBotPlugin};
+use crate::budget::{NetPongPacketBudgetPlugin, PacketBudget};
+use crate::career::NetPongCareerPlugin;
+use crate::chat::NetPongChatPlugin;
+use crate::connection::{
+    AdaptiveDelay, ConnectionState, InputDelay, LocalPlayerSlot, NetPongConnectionPlugin,
+    OpponentWait, PlayerName, PlayerRoster, RelayAddress, RoomName, SimulatedNet, Spectate,
+    player_name,
+};
+use crate::constants::{ASSET_DIR, DEFAULT_RELAY, GAME_ID, INPUT_DELAY};
+use crate::desync::NetPongDesyncPlugin;
+use crate::game::NetPongGamePlugin;
+use crate::hud::NetPongHudPlugin;
+use crate::input::{GamepadChoice, NetPongInputPlugin};
+use crate::local_echo::{LocalEcho, NetPongLocalEchoPlugin};
+use crate::match_over::NetPongMatchOverPlugin;
+use crate::neon::NetPongNeonPlugin;
+use crate::net_stats::NetPongNetStatsPlugin;
+use crate::pause_menu::NetPongPauseMenuPlugin;
+use crate::quality::NetPongQualityPlugin;
+use crate::render::NetPongRenderPlugin;
+use crate::replay::{NetPongReplayPlugin, Replay, ReplayPlayback, ReplayRecorder};
+use crate::resync::NetPongResyncPlugin;
+use crate::rollback::{NetPongRollbackPlugin, Rollback};
+use crate::shared::{GameId, PlayerCount, PongTickLog, ScoreLimit, TickRate, game_id};
+use crate::stall::NetPongStallPlugin;
+use crate::start_menu::{NetPongStartMenuPlugin, START_MENU_PLAY, START_MENU_SPECTATE, StartMenu};
+use crate::verify::{Ghost, NetPongVerifyPlugin};
+
+pub use crate::args::{Args, BotArgs};
+
+/// Open the window and play, watch or replay as `args` say.
+pub fn run(args: Args) {
+    let build = prototype_relay::describe_build(pong_sim::describe_build(
+        arcade_core::describe_build(arcade_buildinfo::build_info!()),
+    ));
+    if args.version {
+        println!("{build}");
+        return;
+    }
+
+    let name = args.name.unwrap_or_else(player_name);
+    let start_menu = (args.relay.is_none() && args.play.is_none()).then(|| StartMenu {
+        selected: if args.spectate {
+            START_MENU_SPECTATE
+        } else {
+            START_MENU_PLAY
+        },
+        relay: DEFAULT_RELAY.to_string(),
+        room: args.room.clone().unwrap_or_default(),
+        name: name.clone(),
+        can_spectate: !args.rollback && args.packet_rate.is_none(),
+        problem: None,
+        lan_search: None,
+        lan_games: Vec::new(),
+    });
+    // A spectator names the room it watches, not one it plays in. The start
+    // menu sorts both out once it knows which.
+    let (room, watch) = match (&start_menu, args.spectate) {
+        (Some(_), _) => (None, None),
+        (None, true) => (None, Some(Spectate { room: args.room })),
+        (None, false) => (args.room, None),
+    };
+    let relay = args
+        .relay
+        .unwrap_or_else(|| DEFAULT_RELAY.parse().expect("DEFAULT_RELAY is an address"));
+
+    let replay = args.play.map(|path| {
+        let replay = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| deserialize::<Replay>(&bytes));
+        replay.unwrap_or_else(|| {
+            eprintln!("--play: no replay in {}", path.display());
+            std::process::exit(2);
+        })
+    });
+    let (score_limit, tick_rate, players) = match &replay {
+        Some(replay) => (
+            replay.score_limit,
+            replay.tick_rate,
+            replay.start.state.players(),
+        ),
+        None => (args.score_limit, args.tick_rate, args.players),
+    };
+
+    let assets = AssetPlugin {
+        file_path: ASSET_DIR.to_string(),
+        ..default()
+    };
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(assets))
+        .insert_resource(RelayAddress(relay))
+        .insert_resource(RoomName(room))
+        .insert_resource(PlayerName(name))
+        .insert_resource(SimulatedNet(args.simulate_net))
+        .insert_resource(ScoreLimit(score_limit))
+        .insert_resource(TickRate(tick_rate))
+        .insert_resource(PlayerCount(players))
+        .insert_resource(GameId(game_id(score_limit, tick_rate, players)))
+        .insert_resource(OpponentWait(
+            Some(Duration::from_secs(args.opponent_wait)).filter(|wait| !wait.is_zero()),
+        ))
+        .insert_resource(AboutInfo(build))
+        .add_plugins(AboutPlugin)
+        .insert_resource(LocalEcho {
+            enabled: args.local_echo,
+            predicted: None,
+        })
+        .insert_resource(GamepadChoice(args.gamepad.map(usize::from)))
+        .add_plugins(NetPongPlugin)
+        .add_plugins(NetPongCareerPlugin);
+    if args.neon {
+        app.add_plugins(NetPongNeonPlugin);
+    }
+    if let Some(watch) = watch {
+        app.insert_resource(watch)
+            .insert_resource(LocalPlayerSlot(SPECTATOR_SLOT));
+    }
+    if let Some(start_menu) = start_menu {
+        app.insert_resource(ConnectionState::StartMenu)
+            .insert_resource(start_menu)
+            .add_plugins(NetPongStartMenuPlugin);
+    }
+    if let Some(path) = args.record {
+        app.insert_resource(ReplayRecorder::new(path, players));
+    }
+    if let Some(replay) = replay {
+        app.insert_resource(ConnectionState::Replaying)
+            .insert_resource(PlayerRoster {
+                names: replay.names.clone(),
+            })
+            .insert_resource(ReplayPlayback { replay, played: 0 });
+    }
+    if let Some(ticks) = args.tick_log {
+        app.insert_resource(PongTickLog(TickLog::new(GAME_ID, ticks, ".")));
+    }
+    if args.verify {
+        app.init_resource::<Ghost>()
+            .add_plugins(NetPongVerifyPlugin);
+    }
+    // Rollback hides the round trip instead, and needs the relay's input
+    // window to run ahead in.
+    let delay = if args.rollback { 0 } else { INPUT_DELAY };
+    app.insert_resource(InputDelay(delay));
+    if !args.rollback {
+        app.init_resource::<AdaptiveDelay>();
+    }
+    if let Some(hz) = args.packet_rate {
+        let budget = PacketBudget::new(hz, tick_rate);
+        // A batch goes out as its first tick comes up, so it has to reach
+        // that far ahead.
+        app.insert_resource(InputDelay(delay.max(budget.batch - 1)))
+            .insert_resource(budget)
+            .add_plugins(NetPongPacketBudgetPlugin);
+    }
+    if args.rollback {
+        app.init_resource::<Rollback>()
+            .add_plugins(NetPongRollbackPlugin);
+    }
+    app.run();
+}
+
+/// Play without a window until killed, asking for a rematch after every
+/// match.
+pub fn run_bot(args: BotArgs) {
+    let frame = Duration::from_secs_f64(1.0 / f64::from(BOT_FRAMES_PER_TICK * args.tick_rate));
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(frame)))
+        .add_plugins(InputPlugin)
+        .insert_resource(RelayAddress(args.relay))
+        .insert_resource(RoomName(args.room))
+        .insert_resource(PlayerName(args.name))
+        .insert_resource(SimulatedNet(None))
+        .insert_resource(ScoreLimit(args.score_limit))
+        .insert_resource(TickRate(args.tick_rate))
+        .insert_resource(PlayerCount(args.players))
+        .insert_resource(GameId(game_id(
+            args.score_limit,
+            args.tick_rate,
+            args.players,
+        )))
+        // A bot is there to be found, however long that takes.
+        .insert_resource(OpponentWait(None))
+        .insert_resource(LocalEcho {
+            enabled: false,
+            predicted: None,
+        })
+        .insert_resource(InputDelay(INPUT_DELAY))
+        .init_resource::<AdaptiveDelay>()
+        .add_plugins(NetPongPlugin)
+        .add_plugins(NetPongBotPlugin);
+    app.run();
+}
+
+struct NetPongPlugin;
+
+impl Plugin for NetPongPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            NetPongConnectionPlugin,
+            NetPongInputPlugin,
+            NetPongGamePlugin,
+            NetPongRenderPlugin,
+            NetPongLocalEchoPlugin,
+            NetPongHudPlugin,
+            NetPongQualityPlugin,
+            NetPongStallPlugin,
+            NetPongNetStatsPlugin,
+            NetPongDesyncPlugin,
+            NetPongResyncPlugin,
+            NetPongPauseMenuPlugin,
+            NetPongMatchOverPlugin,
+            NetPongReplayPlugin,
+            NetPongChatPlugin,
+        ));
+    }
+}
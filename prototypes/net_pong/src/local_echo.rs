@@ -0,0 +1,89 @@
+//! Local echo plugin: visual-only prediction of the local paddle.
+//!
+//! `--local-echo` draws your own paddle moving as soon as you press a key,
+//! ahead of the lockstep simulation. The offset is visual only and is pulled
+//! back to the simulated position every tick.
+
+use bevy::prelude::*;
+use pong_sim::{ARENA_HEIGHT, PADDLE_HEIGHT, PADDLE_SPEED, Paddle, paddle_is_horizontal};
+
+use crate::connection::{LocalPlayerSlot, is_playing};
+use crate::input::{LocalMovement, read_local_movement};
+use crate::render::{PaddleSprite, draw_simulation};
+
+/// How far the drawn paddle may run ahead of the simulated one.
+const MAX_ECHO_OFFSET: f32 = PADDLE_HEIGHT;
+
+pub struct NetPongLocalEchoPlugin;
+
+impl Plugin for NetPongLocalEchoPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            update_local_echo
+                .run_if(is_playing)
+                .after(read_local_movement)
+                .after(draw_simulation),
+        );
+    }
+}
+
+#[derive(Resource)]
+pub struct LocalEcho {
+    pub enabled: bool,
+    /// Where the local paddle is drawn along its side; `None` until the
+    /// first frame of play.
+    pub predicted: Option<f32>,
+}
+
+/// Move the predicted paddle with local input every frame, keep it within
+/// `MAX_ECHO_OFFSET` of the simulated paddle, and draw the sprite there.
+fn update_local_echo(
+    movement: Res<LocalMovement>,
+    time: Res<Time>,
+    local_slot: Res<LocalPlayerSlot>,
+    mut echo: ResMut<LocalEcho>,
+    paddles: Query<(&Transform, &Paddle, &Children)>,
+    mut sprites: Query<&mut Transform, (With<PaddleSprite>, Without<Paddle>)>,
+) {
+    if !echo.enabled {
+        if echo.predicted.is_some() {
+            // Turned off from the pause menu: draw the paddle where it is.
+            echo.predicted = None;
+            for (_, _, children) in &paddles {
+                for child in children.iter() {
+                    if let Ok(mut sprite_transform) = sprites.get_mut(child) {
+                        sprite_transform.translation = Vec3::ZERO;
+                    }
+                }
+            }
+        }
+        return;
+    }
+    let max_offset = (ARENA_HEIGHT - PADDLE_HEIGHT) / 2.0;
+
+    for (transform, paddle, children) in &paddles {
+        if paddle.player_index != local_slot.0 as usize {
+            continue;
+        }
+        // The bottom and top paddles of the square arena move sideways.
+        let along = if paddle_is_horizontal(paddle.player_index) {
+            Vec3::X
+        } else {
+            Vec3::Y
+        };
+        let simulated = transform.translation.dot(along);
+        let predicted =
+            echo.predicted.unwrap_or(simulated) + movement.0 * PADDLE_SPEED * time.delta_secs();
+        let predicted = predicted
+            .clamp(simulated - MAX_ECHO_OFFSET, simulated + MAX_ECHO_OFFSET)
+            .clamp(-max_offset, max_offset);
+        echo.predicted = Some(predicted);
+
+        for child in children.iter() {
+            if let Ok(mut sprite_transform) = sprites.get_mut(child) {
+                sprite_transform.translation = along * (predicted - simulated);
+            }
+        }
+    }
+}
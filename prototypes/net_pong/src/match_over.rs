@@ -0,0 +1,211 @@
+//! Match over plugin: the winner, and a rematch once everyone wants one.
+//!
+//! The first player to `--score-limit` points (default `DEFAULT_SCORE_LIMIT`;
+//! 0 plays forever) wins. The limit is part of the game id sent in Hello, so
+//! the relay only pairs players with the same one. The win happens inside the
+//! simulation, so every client sees it on the same tick: play stops there
+//! and "<name> wins" goes up. Serve (Enter, or South on a gamepad) asks the
+//! relay for a rematch; the other player hears about it, and once both have asked
+//! the relay starts the lockstep over from tick 0 and both clients reset.
+
+use bevy::prelude::*;
+use prototype_relay::{ClientMessage, PlayerSlot};
+
+use crate::budget::PacketBudget;
+use crate::connection::{
+    ConfirmedTick, ConnectionState, InputBuffer, InputDelay, NetSocket, NextInputTick,
+    PlayerRoster, Spectate, is_playing, is_spectating, receive_relay_messages,
+};
+use crate::input::ControlBindings;
+use crate::net_stats::MatchNetStats;
+use crate::pause_menu::{MatchPause, MenuInput, PAUSE_REPEAT, PauseMenu, open_or_close_pause_menu};
+use crate::resync::load_snapshot;
+use crate::rollback::GameSnapshot;
+use crate::shared::{MatchWinner, PlayerCount};
+
+const MATCH_OVER_FONT_SIZE: f32 = 40.0;
+
+pub struct NetPongMatchOverPlugin;
+
+impl Plugin for NetPongMatchOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Rematch>()
+            .insert_resource(RematchRepeatTimer(Timer::new(
+                PAUSE_REPEAT,
+                TimerMode::Repeating,
+            )))
+            .add_systems(Startup, spawn_match_over_text)
+            .add_systems(
+                Update,
+                (
+                    ask_for_rematch.run_if(not(is_spectating)),
+                    repeat_rematch_request,
+                    start_rematch,
+                )
+                    .chain()
+                    .run_if(is_playing)
+                    .after(receive_relay_messages)
+                    .before(open_or_close_pause_menu),
+            )
+            .add_systems(Update, update_match_over_text.after(start_rematch));
+    }
+}
+
+/// Our side of the rematch handshake, until the new game starts.
+#[derive(Resource, Default)]
+pub struct Rematch {
+    /// We asked the relay for one, and keep asking until it starts.
+    pub asked: bool,
+    /// The other players who want one too.
+    pub requested_by: Vec<PlayerSlot>,
+    /// Everyone agreed and the relay has started over; so do we.
+    pub starting: bool,
+}
+
+#[derive(Resource)]
+pub struct RematchRepeatTimer(Timer);
+
+#[derive(Component)]
+struct MatchOverText;
+
+fn spawn_match_over_text(mut commands: Commands) {
+    commands.spawn((
+        MatchOverText,
+        Text::new(""),
+        TextFont::from_font_size(MATCH_OVER_FONT_SIZE),
+        TextColor(Color::srgb(1.0, 1.0, 0.5)),
+        Visibility::Hidden,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(40.0),
+            left: Val::Percent(50.0),
+            ..default()
+        },
+    ));
+}
+
+/// Who won, once the winning tick is confirmed. With rollback a guessed tick
+/// can win and then be played again without.
+pub fn confirmed_winner(winner: &MatchWinner, confirmed: &ConfirmedTick) -> Option<PlayerSlot> {
+    winner
+        .0
+        .filter(|(_, tick)| *tick < confirmed.0)
+        .map(|(slot, _)| slot)
+}
+
+/// Serve on the match-over screen asks for a rematch.
+fn ask_for_rematch(
+    input: MenuInput,
+    bindings: Res<ControlBindings>,
+    menu: Res<PauseMenu>,
+    winner: Res<MatchWinner>,
+    confirmed: Res<ConfirmedTick>,
+    mut rematch: ResMut<Rematch>,
+    mut net: ResMut<NetSocket>,
+) {
+    if menu.open || rematch.asked || confirmed_winner(&winner, &confirmed).is_none() {
+        return;
+    }
+    if input.binding_just_pressed(&bindings.serve) {
+        println!("net_pong: asking for a rematch");
+        rematch.asked = true;
+        net.send(&ClientMessage::Rematch);
+    }
+}
+
+/// Ask again every `PAUSE_REPEAT` until the relay starts the rematch.
+pub fn repeat_rematch_request(
+    mut net: ResMut<NetSocket>,
+    rematch: Res<Rematch>,
+    mut timer: ResMut<RematchRepeatTimer>,
+    time: Res<Time>,
+) {
+    timer.0.tick(time.delta());
+    if rematch.asked && timer.0.just_finished() {
+        net.send(&ClientMessage::Rematch);
+    }
+}
+
+/// Everyone agreed: start over from tick 0, as the relay just did, with
+/// nothing left of the last game.
+pub fn start_rematch(world: &mut World) {
+    if !world.resource::<Rematch>().starting {
+        return;
+    }
+    println!("net_pong: rematch starting!");
+    world.resource_mut::<InputBuffer>().0.clear();
+    let players = world.resource::<PlayerCount>().0;
+    load_snapshot(world, 0, &GameSnapshot::new_game(players));
+    let delay = world.resource::<InputDelay>().0;
+    let playing = !world.contains_resource::<Spectate>();
+    {
+        let mut net = world.resource_mut::<NetSocket>();
+        net.sent_inputs.clear();
+        net.stats = MatchNetStats::default();
+        if playing {
+            net.send_opening_inputs(delay);
+        }
+    }
+    world.resource_mut::<NextInputTick>().0 = delay;
+    if let Some(mut budget) = world.get_resource_mut::<PacketBudget>() {
+        budget.pending.clear();
+    }
+    *world.resource_mut::<MatchPause>() = MatchPause::default();
+    *world.resource_mut::<Rematch>() = Rematch::default();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_match_over_text(
+    state: Res<ConnectionState>,
+    winner: Res<MatchWinner>,
+    confirmed: Res<ConfirmedTick>,
+    rematch: Res<Rematch>,
+    roster: Res<PlayerRoster>,
+    spectate: Option<Res<Spectate>>,
+    bindings: Res<ControlBindings>,
+    mut query: Query<(&mut Text, &mut Visibility), With<MatchOverText>>,
+) {
+    let serve = bindings.serve.describe();
+    let name = |slot: PlayerSlot| {
+        roster
+            .names
+            .get(slot as usize)
+            .cloned()
+            .flatten()
+            .unwrap_or_else(|| format!("Player {}", slot + 1))
+    };
+    let content = confirmed_winner(&winner, &confirmed)
+        .filter(|_| {
+            matches!(
+                *state,
+                ConnectionState::Playing | ConnectionState::Replaying
+            )
+        })
+        .map(|slot| {
+            let prompt = if *state == ConnectionState::Replaying {
+                "Replay".to_string()
+            } else if spectate.is_some() {
+                "Waiting for the players...".to_string()
+            } else if rematch.asked {
+                "Waiting for the other player...".to_string()
+            } else if let Some(&other) = rematch.requested_by.first() {
+                format!("{} wants a rematch: {serve} to play again", name(other))
+            } else {
+                format!("{serve} for a rematch")
+            };
+            format!("{} wins!\n\n{prompt}", name(slot))
+        });
+    for (mut text, mut visibility) in &mut query {
+        match &content {
+            Some(content) => {
+                if **text != *content {
+                    **text = content.clone();
+                }
+                visibility.set_if_neq(Visibility::Visible);
+            }
+            None => {
+                visibility.set_if_neq(Visibility::Hidden);
+            }
+        }
+    }
+}
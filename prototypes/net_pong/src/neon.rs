@@ -0,0 +1,282 @@
+//! Neon plugin: `--neon` bloom, trails, particles, screen shake and sound.
+//!
+//! `--neon` dresses the game up as `examples/neon_pong.rs`, with paddle
+//! flashes too, and sounds synthesized at startup by `sound_synth` so there
+//! are no files to ship. The simulation reports its paddle hits, wall
+//! bounces and points as `SimEvent`s, which the effects only read, so
+//! nothing they do reaches the game state. A tick's events go out once the
+//! relay confirms it, so a tick rollback plays again on other inputs is
+//! never seen twice; under `--rollback` its sounds go out sooner (see
+//! `play_predicted_sounds`).
+
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use arcade_prelude::*;
+use audio::{EventId, RollbackAudio, RollbackAudioPlugin};
+use bevy::core_pipeline::tonemapping::{DebandDither, Tonemapping};
+use bevy::post_process::bloom::Bloom;
+use bevy::prelude::*;
+use pong_sim::{BALL_SIZE, Ball, Paddle, PongState, SimEvent};
+use sound_synth::presets;
+
+use crate::connection::{ConfirmedTick, SimulationTick};
+use crate::game::{TickEvents, emit_confirmed_events};
+use crate::render::{PaddleSprite, PongColors, setup_pong};
+use crate::rollback::Rollback;
+
+// Colours from examples/neon_pong.rs; values over 1.0 bloom.
+const NEON_BACKGROUND: Color = Color::linear_rgb(0.02, 0.01, 0.05);
+const NEON_FLASH_COLOR: Color = Color::linear_rgb(10.0, 10.0, 10.0);
+
+const PADDLE_HIT_PARTICLE_COUNT: usize = 10;
+const WALL_BOUNCE_PARTICLE_COUNT: usize = 5;
+const SCORE_PARTICLE_COUNT: usize = 18;
+
+const PADDLE_HIT_TRAUMA: f32 = 0.3;
+const SCORE_TRAUMA: f32 = 0.5;
+
+const FLASH_DURATION_SECS: f32 = 0.1;
+
+/// The look and sound of `examples/neon_pong.rs`. Everything here reads the
+/// simulation and its `SimEvent`s and writes nothing back, so a neon client
+/// plays the same match as a plain one.
+pub struct NetPongNeonPlugin;
+
+impl Plugin for NetPongNeonPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ClearColor(NEON_BACKGROUND))
+            .insert_resource(PongColors {
+                border: palette::DIM_BLUE,
+                center_line: palette::DIM_PURPLE,
+                paddles: [
+                    palette::NEON_CYAN,
+                    palette::NEON_MAGENTA,
+                    palette::NEON_GREEN,
+                    palette::NEON_HOT_PINK,
+                ],
+                ball: palette::NEON_HOT_PINK,
+                score: palette::NEON_GREEN,
+            })
+            .add_plugins((EffectsPlugin, RollbackAudioPlugin))
+            .add_systems(Startup, (setup_neon, synthesize_sounds).after(setup_pong))
+            .add_systems(
+                Update,
+                (
+                    spawn_ball_trail,
+                    spawn_event_effects,
+                    play_event_sounds.run_if(not(resource_exists::<Rollback>)),
+                )
+                    .in_set(EffectSet::Spawn)
+                    .after(emit_confirmed_events),
+            )
+            .add_systems(
+                Update,
+                play_predicted_sounds
+                    .run_if(resource_exists::<Rollback>)
+                    .before(emit_confirmed_events),
+            )
+            .add_systems(Update, update_paddle_flash.in_set(EffectSet::Update));
+    }
+}
+
+/// On a paddle's sprite: white-hot for a moment after it hits the ball.
+#[derive(Component)]
+struct PaddleFlash {
+    player_index: usize,
+    timer: Timer,
+    base_color: Color,
+}
+
+#[derive(Resource)]
+struct SoundAssets {
+    hit: Handle<AudioSource>,
+    bounce: Handle<AudioSource>,
+    score: Handle<AudioSource>,
+}
+
+impl SoundAssets {
+    fn for_event(&self, event: &SimEvent) -> Handle<AudioSource> {
+        match event {
+            SimEvent::PaddleHit { .. } => self.hit.clone(),
+            SimEvent::WallBounce { .. } => self.bounce.clone(),
+            SimEvent::Score { .. } => self.score.clone(),
+        }
+    }
+}
+
+/// Bloom on the camera, a grid behind the arena and a flash on each paddle.
+fn setup_neon(
+    mut commands: Commands,
+    camera: Query<Entity, With<Camera2d>>,
+    paddles: Query<(&Paddle, &Children)>,
+    sprites: Query<&Sprite, With<PaddleSprite>>,
+    state: Res<PongState>,
+) {
+    for camera in &camera {
+        commands.entity(camera).insert((
+            Bloom::OLD_SCHOOL,
+            Tonemapping::TonyMcMapface,
+            DebandDither::Enabled,
+        ));
+    }
+
+    // Behind the ball's trail.
+    spawn_background_grid(&mut commands, state.arena_size(), -0.2);
+
+    for (paddle, children) in &paddles {
+        for child in children.iter() {
+            let Ok(sprite) = sprites.get(child) else {
+                continue;
+            };
+            let mut timer = Timer::from_seconds(FLASH_DURATION_SECS, TimerMode::Once);
+            timer.finish();
+            commands.entity(child).insert(PaddleFlash {
+                player_index: paddle.player_index,
+                timer,
+                base_color: sprite.color,
+            });
+        }
+    }
+}
+
+/// neon_pong's sound effects, synthesized rather than loaded.
+fn synthesize_sounds(mut commands: Commands, mut sources: ResMut<Assets<AudioSource>>) {
+    let mut add = |sound: sound_synth::Sound| sources.add(sound.audio_source());
+    commands.insert_resource(SoundAssets {
+        hit: add(presets::hit()),
+        bounce: add(presets::bounce()),
+        score: add(presets::score()),
+    });
+}
+
+// --- Ball trail ---
+
+fn spawn_ball_trail(mut commands: Commands, ball_query: Query<(&Transform, &Sprite), With<Ball>>) {
+    for (transform, sprite) in &ball_query {
+        let behind = transform.translation.with_z(-0.1);
+        spawn_trail(&mut commands, behind, BALL_SIZE, sprite.color);
+    }
+}
+
+// --- Particle bursts, flashes and shake ---
+
+fn spawn_event_effects(
+    mut commands: Commands,
+    mut events: MessageReader<SimEvent>,
+    mut shake: ResMut<ScreenShake>,
+    mut flashes: Query<&mut PaddleFlash>,
+    colors: Res<PongColors>,
+) {
+    for event in events.read() {
+        match *event {
+            SimEvent::PaddleHit {
+                position,
+                player_index,
+            } => {
+                shake.add_trauma(PADDLE_HIT_TRAUMA);
+
+                // Spray away from the paddle; player 0's is on the left.
+                let spray_angle = if player_index == 0 { 0.0 } else { PI };
+                spawn_particles(
+                    &mut commands,
+                    position,
+                    PADDLE_HIT_PARTICLE_COUNT,
+                    spray_angle,
+                    PI,
+                    colors.ball,
+                );
+
+                for mut flash in &mut flashes {
+                    if flash.player_index == player_index {
+                        flash.timer.reset();
+                    }
+                }
+            }
+            SimEvent::WallBounce { position } => {
+                // Spray downward off the top wall, upward off the bottom one
+                let spray_angle = -FRAC_PI_2.copysign(position.y);
+                spawn_particles(
+                    &mut commands,
+                    position,
+                    WALL_BOUNCE_PARTICLE_COUNT,
+                    spray_angle,
+                    PI,
+                    colors.border,
+                );
+            }
+            SimEvent::Score { player_index } => {
+                shake.add_trauma(SCORE_TRAUMA);
+                spawn_particles(
+                    &mut commands,
+                    Vec2::ZERO,
+                    SCORE_PARTICLE_COUNT,
+                    0.0,
+                    TAU,
+                    colors.paddles[player_index],
+                );
+            }
+        }
+    }
+}
+
+fn update_paddle_flash(time: Res<Time>, mut query: Query<(&mut PaddleFlash, &mut Sprite)>) {
+    for (mut flash, mut sprite) in &mut query {
+        flash.timer.tick(time.delta());
+
+        if flash.timer.is_finished() {
+            sprite.color = flash.base_color;
+        } else {
+            let progress = flash.timer.fraction();
+            sprite.color = lerp_color(NEON_FLASH_COLOR, flash.base_color, progress);
+        }
+    }
+}
+
+// --- Sound ---
+
+fn play_event_sounds(
+    mut commands: Commands,
+    mut events: MessageReader<SimEvent>,
+    sounds: Res<SoundAssets>,
+) {
+    for event in events.read() {
+        commands.spawn((
+            AudioPlayer::new(sounds.for_event(event)),
+            PlaybackSettings::DESPAWN,
+        ));
+    }
+}
+
+/// Under `--rollback`, sound each event as soon as a tick is played, guess
+/// or not, rather than waiting a round trip for the relay to confirm it.
+/// `RollbackAudio` keeps a replayed tick from sounding twice and stops the
+/// sounds of events a replay took back.
+fn play_predicted_sounds(
+    mut audio: ResMut<RollbackAudio>,
+    events: Res<TickEvents>,
+    sim_tick: Res<SimulationTick>,
+    confirmed: Res<ConfirmedTick>,
+    sounds: Res<SoundAssets>,
+) {
+    for (&tick, tick_events) in &events.0 {
+        for event in tick_events {
+            audio.play(tick, sound_event_id(event), sounds.for_event(event));
+        }
+    }
+    if let Some(played) = sim_tick.0.checked_sub(1) {
+        audio.resimulated_through(played);
+    }
+    if let Some(last_confirmed) = confirmed.0.checked_sub(1) {
+        audio.confirm(last_confirmed);
+    }
+}
+
+/// The same event on a replayed tick gets the same id, even if the ball
+/// met the paddle or wall a little way off from where the guess had it.
+fn sound_event_id(event: &SimEvent) -> EventId {
+    match *event {
+        SimEvent::PaddleHit { player_index, .. } => player_index as EventId,
+        SimEvent::WallBounce { position } => 8 + EventId::from(position.y > 0.0),
+        SimEvent::Score { player_index } => 16 + player_index as EventId,
+    }
+}
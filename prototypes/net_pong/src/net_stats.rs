@@ -0,0 +1,201 @@
+//! Net stats plugin: this match's traffic in detail, toggled with F4.
+//!
+//! F4 opens a panel on the current match's traffic: datagrams and bytes
+//! each way, input resends, what was dropped or ignored and why, and the
+//! longest the simulation has waited for a tick. It starts over with each
+//! match and rematch.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use prototype_relay::Tick;
+
+use crate::connection::{Dropped, NetSocket, SimulationTick, is_playing};
+use crate::pause_menu::MatchPause;
+
+const STATS_FONT_SIZE: f32 = 16.0;
+const STATS_MARGIN: f32 = 10.0;
+const STATS_BACKGROUND: Color = Color::srgba(0.0, 0.0, 0.0, 0.7);
+
+pub struct NetPongNetStatsPlugin;
+
+impl Plugin for NetPongNetStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NetStatsPanel>()
+            .add_systems(Startup, spawn_net_stats_panel)
+            .add_systems(FixedPostUpdate, measure_stalls.run_if(is_playing))
+            .add_systems(
+                Update,
+                (toggle_net_stats_panel, update_net_stats_panel).chain(),
+            );
+    }
+}
+
+/// What the socket has seen since the current match started: the relay's
+/// `GameStart` or `Spectating`, or a rematch. A resumed match carries on.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MatchNetStats {
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Times our inputs went out again: while waiting, after a gap, or on
+    /// resuming.
+    pub resends: u64,
+    /// Datagrams that failed authentication.
+    unauthenticated: u64,
+    /// Datagrams that did not decode.
+    malformed: u64,
+    /// Duplicate datagrams, or ones overtaken by a newer one.
+    stale: u64,
+    /// TickInputs for a tick we had sent no input for.
+    pub ignored_inputs: u64,
+    /// TickInputs for a tick already played, re-sent because an ack was lost.
+    pub repeated_ticks: u64,
+    /// The longest the simulation has gone without a tick to play, pauses
+    /// aside.
+    max_stall: Duration,
+}
+
+impl MatchNetStats {
+    pub fn count_dropped(&mut self, why: &Dropped) {
+        match why {
+            Dropped::Unauthenticated => self.unauthenticated += 1,
+            Dropped::Malformed => self.malformed += 1,
+            Dropped::Sequence => self.stale += 1,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct NetStatsPanel {
+    visible: bool,
+    meter: Timer,
+    /// Bytes a second each way over the last second.
+    byte_rates: (u64, u64),
+    bytes_at_last_reading: (u64, u64),
+}
+
+impl Default for NetStatsPanel {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            meter: Timer::from_seconds(1.0, TimerMode::Repeating),
+            byte_rates: (0, 0),
+            bytes_at_last_reading: (0, 0),
+        }
+    }
+}
+
+#[derive(Component)]
+struct NetStatsText;
+
+fn spawn_net_stats_panel(mut commands: Commands) {
+    commands.spawn((
+        NetStatsText,
+        Text::new(""),
+        TextFont::from_font_size(STATS_FONT_SIZE),
+        TextColor(Color::srgb(0.6, 1.0, 0.6)),
+        BackgroundColor(STATS_BACKGROUND),
+        Visibility::Hidden,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(STATS_MARGIN),
+            left: Val::Px(STATS_MARGIN),
+            padding: UiRect::all(Val::Px(6.0)),
+            ..default()
+        },
+    ));
+}
+
+/// Time each run of fixed steps that played no tick, and keep the longest.
+/// A pause is not a stall.
+fn measure_stalls(
+    sim_tick: Res<SimulationTick>,
+    pause: Res<MatchPause>,
+    time: Res<Time<Fixed>>,
+    net: Option<ResMut<NetSocket>>,
+    mut stall: Local<(Tick, Duration)>,
+) {
+    let Some(mut net) = net else {
+        return;
+    };
+    let (last_tick, stalled) = &mut *stall;
+    let paused = pause.at.is_some_and(|at| sim_tick.0 >= at);
+    if sim_tick.0 != *last_tick || paused {
+        *last_tick = sim_tick.0;
+        *stalled = Duration::ZERO;
+        return;
+    }
+    *stalled += time.timestep();
+    if *stalled > net.stats.max_stall {
+        net.stats.max_stall = *stalled;
+    }
+}
+
+fn toggle_net_stats_panel(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut panel: ResMut<NetStatsPanel>,
+    mut query: Query<&mut Visibility, With<NetStatsText>>,
+) {
+    if keyboard.just_pressed(KeyCode::F4) {
+        panel.visible = !panel.visible;
+    }
+    let wanted = if panel.visible {
+        Visibility::Visible
+    } else {
+        Visibility::Hidden
+    };
+    for mut visibility in &mut query {
+        visibility.set_if_neq(wanted);
+    }
+}
+
+fn update_net_stats_panel(
+    time: Res<Time>,
+    net: Option<Res<NetSocket>>,
+    mut panel: ResMut<NetStatsPanel>,
+    mut query: Query<&mut Text, With<NetStatsText>>,
+) {
+    let Some(net) = net else {
+        return;
+    };
+    let stats = net.stats;
+    panel.meter.tick(time.delta());
+    if panel.meter.just_finished() {
+        let (sent, received) = panel.bytes_at_last_reading;
+        // The counts start over with each match.
+        panel.byte_rates = (
+            stats.bytes_sent.saturating_sub(sent),
+            stats.bytes_received.saturating_sub(received),
+        );
+        panel.bytes_at_last_reading = (stats.bytes_sent, stats.bytes_received);
+    }
+    if !panel.visible {
+        return;
+    }
+    let (sent_rate, received_rate) = panel.byte_rates;
+    let content = format!(
+        "This match\n\
+         packets: {} sent, {} received\n\
+         bytes/s: {sent_rate} sent, {received_rate} received\n\
+         input resends: {}\n\
+         dropped: {} unauthenticated, {} malformed, {} stale\n\
+         ignored: {} early inputs, {} repeated ticks\n\
+         longest stall: {} ms",
+        stats.packets_sent,
+        stats.packets_received,
+        stats.resends,
+        stats.unauthenticated,
+        stats.malformed,
+        stats.stale,
+        stats.ignored_inputs,
+        stats.repeated_ticks,
+        stats.max_stall.as_millis(),
+    );
+    for mut text in &mut query {
+        if **text != content {
+            **text = content.clone();
+        }
+    }
+}
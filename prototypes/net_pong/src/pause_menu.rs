@@ -0,0 +1,431 @@
+//! Pause menu plugin: Escape pauses the match for everyone.
+//!
+//! Escape, or Start on a gamepad, opens a menu: Resume, Settings (the
+//! connection HUD, local echo and controls) and Quit. The controls page
+//! rebinds the keys and gamepad button for up, down, serve (which starts a
+//! rematch) and pause; they are saved to `CONTROLS_PATH` in the working
+//! directory. Movement is read through `arcade_input`, with the keyboard and
+//! the chosen gamepad assigned to the one local player.
+//!
+//! Settings also picks which gamepad drives the paddle when several are
+//! connected, as `--gamepad <n>` does from the command line; the connected
+//! pads are numbered from 1, listed by name there and on the console as they
+//! connect. Without a choice the first one does.
+//!
+//! Opening the menu mid-match pauses the match for everyone: we stop sending
+//! input at the next tick and send the relay a `Pause` for it, the relay
+//! tells both clients where the match stops, and both play up to that tick
+//! and wait there. The other player sees who paused, and either player's
+//! Resume carries on. While paused, each client acks its last tick every
+//! `PAUSE_REPEAT` so the relay does not time it out.
+
+use std::path::Path;
+use std::time::Duration;
+
+use arcade_input::{Binding, Control};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use prototype_relay::{ClientMessage, PlayerSlot, SPECTATOR_SLOT, Tick};
+
+use crate::connection::{
+    ConfirmedTick, ConnectionState, LocalPlayerSlot, NetSocket, NextInputTick, PlayerRoster,
+    is_playing,
+};
+use crate::hud::NetHud;
+use crate::input::{CONTROLS_PATH, ControlBindings, GamepadChoice, PaddleGamepad};
+use crate::local_echo::LocalEcho;
+use crate::shared::PongClientMessage;
+use crate::start_menu::is_in_start_menu;
+
+/// How often a pause request or release is repeated until the relay
+/// answers, and how often a paused client reminds the relay it is there.
+pub const PAUSE_REPEAT: Duration = Duration::from_millis(500);
+const PAUSE_FONT_SIZE: f32 = 28.0;
+const MAIN_MENU_ITEMS: usize = 3;
+const SETTINGS_MENU_ITEMS: usize = 4;
+/// One per `Control`, then Gamepad, Reset to defaults and Back.
+const CONTROLS_MENU_ITEMS: usize = Control::ALL.len() + 3;
+
+pub struct NetPongPauseMenuPlugin;
+
+impl Plugin for NetPongPauseMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MatchPause>()
+            .init_resource::<PauseMenu>()
+            .insert_resource(PauseRepeatTimer(Timer::new(
+                PAUSE_REPEAT,
+                TimerMode::Repeating,
+            )))
+            .add_systems(Startup, spawn_pause_text)
+            .add_systems(
+                Update,
+                (
+                    open_or_close_pause_menu.run_if(not(is_in_start_menu)),
+                    navigate_pause_menu,
+                    update_pause_text,
+                )
+                    .chain(),
+            )
+            .add_systems(Update, repeat_pause_messages.run_if(is_playing));
+    }
+}
+
+/// Where the match is paused, as far as the relay has told us.
+#[derive(Resource, Default)]
+pub struct MatchPause {
+    /// Play stops before this tick while paused. Set as soon as we ask to
+    /// pause, and moved if the relay picks a later tick.
+    pub at: Option<Tick>,
+    /// Who paused it, once the relay has said.
+    pub by_slot: Option<PlayerSlot>,
+    /// What we asked the relay for and have not heard back about.
+    pub asking: Option<PauseRequest>,
+}
+
+#[derive(Resource)]
+struct PauseRepeatTimer(Timer);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PauseRequest {
+    Pause(Tick),
+    Unpause,
+}
+
+impl MatchPause {
+    /// Ask the relay for `request` now, and every `PAUSE_REPEAT` until it
+    /// answers.
+    fn ask(&mut self, request: PauseRequest, net: &mut NetSocket) {
+        self.asking = Some(request);
+        net.send(&request.message());
+    }
+}
+
+impl PauseRequest {
+    fn message(self) -> PongClientMessage {
+        match self {
+            PauseRequest::Pause(tick) => ClientMessage::Pause { tick },
+            PauseRequest::Unpause => ClientMessage::Unpause,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct PauseMenu {
+    pub open: bool,
+    page: MenuPage,
+    selected: usize,
+    /// Waiting for the key or button to bind to this control.
+    rebinding: Option<Control>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum MenuPage {
+    #[default]
+    Main,
+    Settings,
+    Controls,
+}
+
+/// Menu buttons from the keyboard or any gamepad.
+#[derive(SystemParam)]
+pub struct MenuInput<'w, 's> {
+    keyboard: Res<'w, ButtonInput<KeyCode>>,
+    gamepads: Query<'w, 's, &'static Gamepad>,
+}
+
+impl MenuInput<'_, '_> {
+    pub fn just_pressed(&self, keys: &[KeyCode], button: GamepadButton) -> bool {
+        self.keyboard.any_just_pressed(keys.iter().copied())
+            || self
+                .gamepads
+                .iter()
+                .any(|gamepad| gamepad.just_pressed(button))
+    }
+
+    pub fn binding_just_pressed(&self, binding: &Binding) -> bool {
+        self.just_pressed(&binding.keys, binding.button)
+    }
+
+    /// A key or button just pressed to rebind an action to, other than the
+    /// ones that close the menu.
+    fn new_binding(&self) -> Option<Result<KeyCode, GamepadButton>> {
+        let key = self
+            .keyboard
+            .get_just_pressed()
+            .find(|&&key| key != KeyCode::Escape);
+        if let Some(&key) = key {
+            return Some(Ok(key));
+        }
+        self.gamepads.iter().find_map(|gamepad| {
+            gamepad
+                .get_just_pressed()
+                .find(|&&button| button != GamepadButton::Start)
+                .map(|&button| Err(button))
+        })
+    }
+}
+
+#[derive(Component)]
+struct PauseText;
+
+fn spawn_pause_text(mut commands: Commands) {
+    commands.spawn((
+        PauseText,
+        Text::new(""),
+        TextFont::from_font_size(PAUSE_FONT_SIZE),
+        TextColor(Color::WHITE),
+        Visibility::Hidden,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(35.0),
+            left: Val::Percent(50.0),
+            ..default()
+        },
+    ));
+}
+
+/// Escape or Start opens the menu, pausing a match in play; while rebinding
+/// it cancels, on the other pages it goes back, and otherwise it resumes.
+#[allow(clippy::too_many_arguments)]
+pub fn open_or_close_pause_menu(
+    input: MenuInput,
+    bindings: Res<ControlBindings>,
+    state: Res<ConnectionState>,
+    next_input: Res<NextInputTick>,
+    mut menu: ResMut<PauseMenu>,
+    mut pause: ResMut<MatchPause>,
+    mut net: Option<ResMut<NetSocket>>,
+    local_slot: Res<LocalPlayerSlot>,
+) {
+    // Escape and Start always work, so rebinding Pause cannot lock anyone
+    // out of the menu; the bound keys are ignored while rebinding them.
+    let bound = menu.rebinding.is_none() && input.binding_just_pressed(&bindings.pause);
+    if !(bound || input.just_pressed(&[KeyCode::Escape], GamepadButton::Start)) {
+        return;
+    }
+    if !menu.open {
+        *menu = PauseMenu {
+            open: true,
+            ..default()
+        };
+        // Spectators have nothing to pause.
+        if *state == ConnectionState::Playing
+            && local_slot.0 != SPECTATOR_SLOT
+            && pause.at.is_none()
+            && let Some(net) = &mut net
+        {
+            pause.at = Some(next_input.0);
+            pause.ask(PauseRequest::Pause(next_input.0), net);
+        }
+    } else if menu.rebinding.is_some() {
+        menu.rebinding = None;
+    } else if menu.page == MenuPage::Controls {
+        menu.page = MenuPage::Settings;
+        menu.selected = 2;
+    } else if menu.page == MenuPage::Settings {
+        menu.page = MenuPage::Main;
+        menu.selected = 1;
+    } else {
+        resume(&mut menu, &mut pause, net.as_deref_mut());
+    }
+}
+
+/// Close the menu, and carry on if the match is paused.
+fn resume(menu: &mut PauseMenu, pause: &mut MatchPause, net: Option<&mut NetSocket>) {
+    menu.open = false;
+    if pause.at.is_some()
+        && let Some(net) = net
+    {
+        pause.ask(PauseRequest::Unpause, net);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn navigate_pause_menu(
+    input: MenuInput,
+    mut menu: ResMut<PauseMenu>,
+    mut pause: ResMut<MatchPause>,
+    mut net: Option<ResMut<NetSocket>>,
+    mut local_echo: ResMut<LocalEcho>,
+    mut hud: ResMut<NetHud>,
+    mut bindings: ResMut<ControlBindings>,
+    mut gamepad_choice: ResMut<GamepadChoice>,
+    mut exit: MessageWriter<AppExit>,
+) {
+    if !menu.open {
+        return;
+    }
+    if let Some(control) = menu.rebinding {
+        let Some(pressed) = input.new_binding() else {
+            return;
+        };
+        let binding = bindings.get_mut(control);
+        match pressed {
+            Ok(key) => binding.keys = vec![key],
+            Err(button) => binding.button = button,
+        }
+        bindings.save(Path::new(CONTROLS_PATH));
+        menu.rebinding = None;
+        return;
+    }
+    let items = match menu.page {
+        MenuPage::Main => MAIN_MENU_ITEMS,
+        MenuPage::Settings => SETTINGS_MENU_ITEMS,
+        MenuPage::Controls => CONTROLS_MENU_ITEMS,
+    };
+    if input.just_pressed(&[KeyCode::ArrowUp, KeyCode::KeyW], GamepadButton::DPadUp) {
+        menu.selected = (menu.selected + items - 1) % items;
+    }
+    if input.just_pressed(
+        &[KeyCode::ArrowDown, KeyCode::KeyS],
+        GamepadButton::DPadDown,
+    ) {
+        menu.selected = (menu.selected + 1) % items;
+    }
+    if !input.just_pressed(&[KeyCode::Enter, KeyCode::Space], GamepadButton::South) {
+        return;
+    }
+    match (menu.page, menu.selected) {
+        (MenuPage::Main, 0) => resume(&mut menu, &mut pause, net.as_deref_mut()),
+        (MenuPage::Main, 1) => {
+            menu.page = MenuPage::Settings;
+            menu.selected = 0;
+        }
+        (MenuPage::Main, _) => {
+            exit.write(AppExit::Success);
+        }
+        (MenuPage::Settings, 0) => {
+            hud.visible = !hud.visible;
+            menu.set_changed();
+        }
+        (MenuPage::Settings, 1) => {
+            local_echo.enabled = !local_echo.enabled;
+            menu.set_changed();
+        }
+        (MenuPage::Settings, 2) => {
+            menu.page = MenuPage::Controls;
+            menu.selected = 0;
+        }
+        (MenuPage::Settings, _) => {
+            menu.page = MenuPage::Main;
+            menu.selected = 1;
+        }
+        (MenuPage::Controls, selected) if selected < Control::ALL.len() => {
+            menu.rebinding = Some(Control::ALL[selected]);
+        }
+        (MenuPage::Controls, selected) if selected == Control::ALL.len() => {
+            // The next one along, then back to the first connected.
+            let connected = input.gamepads.iter().count();
+            gamepad_choice.0 = match gamepad_choice.0 {
+                None if connected > 0 => Some(1),
+                Some(n) if n < connected => Some(n + 1),
+                _ => None,
+            };
+            menu.set_changed();
+        }
+        (MenuPage::Controls, selected) if selected == Control::ALL.len() + 1 => {
+            *bindings = ControlBindings::default();
+            bindings.save(Path::new(CONTROLS_PATH));
+        }
+        (MenuPage::Controls, _) => {
+            menu.page = MenuPage::Settings;
+            menu.selected = 2;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_pause_text(
+    menu: Res<PauseMenu>,
+    pause: Res<MatchPause>,
+    roster: Res<PlayerRoster>,
+    local_echo: Res<LocalEcho>,
+    hud: Res<NetHud>,
+    bindings: Res<ControlBindings>,
+    gamepad: PaddleGamepad,
+    mut query: Query<(&mut Text, &mut Visibility), With<PauseText>>,
+) {
+    if !(menu.is_changed() || pause.is_changed() || roster.is_changed() || bindings.is_changed()) {
+        return;
+    }
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    let content = if menu.open {
+        let (title, items) = match menu.page {
+            MenuPage::Main => {
+                let title = if pause.at.is_some() { "PAUSED" } else { "MENU" };
+                let items = ["Resume", "Settings", "Quit"].map(String::from).to_vec();
+                (title, items)
+            }
+            MenuPage::Settings => (
+                "SETTINGS",
+                vec![
+                    format!("Connection HUD: {}", on_off(hud.visible)),
+                    format!("Local echo: {}", on_off(local_echo.enabled)),
+                    "Controls".to_string(),
+                    "Back".to_string(),
+                ],
+            ),
+            MenuPage::Controls => {
+                let mut items: Vec<String> = Control::ALL
+                    .iter()
+                    .map(|&control| {
+                        let binding = if menu.rebinding == Some(control) {
+                            "press a key or button...".to_string()
+                        } else {
+                            bindings.get(control).describe()
+                        };
+                        format!("{control:?}: {binding}")
+                    })
+                    .collect();
+                items.push(format!("Gamepad: {}", gamepad.describe()));
+                items.extend(["Reset to defaults", "Back"].map(String::from));
+                ("CONTROLS", items)
+            }
+        };
+        let mut content = format!("{title}\n");
+        for (i, item) in items.iter().enumerate() {
+            let marker = if i == menu.selected { ">" } else { " " };
+            content += &format!("\n{marker} {item}");
+        }
+        Some(content)
+    } else {
+        pause.at.map(|_| {
+            let name = pause
+                .by_slot
+                .and_then(|slot| roster.names.get(slot as usize)?.clone())
+                .unwrap_or_else(|| "Somebody".into());
+            format!("{name} paused the game\nEsc for the menu")
+        })
+    };
+    for (mut text, mut visibility) in &mut query {
+        match &content {
+            Some(content) => {
+                **text = content.clone();
+                *visibility = Visibility::Visible;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+/// Repeat an unanswered pause request or release, and while paused, ack
+/// our last tick so the relay does not take the silence for a dropout.
+fn repeat_pause_messages(
+    mut net: ResMut<NetSocket>,
+    pause: Res<MatchPause>,
+    mut timer: ResMut<PauseRepeatTimer>,
+    confirmed: Res<ConfirmedTick>,
+    time: Res<Time>,
+) {
+    timer.0.tick(time.delta());
+    if !timer.0.just_finished() {
+        return;
+    }
+    if let Some(request) = pause.asking {
+        net.send(&request.message());
+    } else if pause.at.is_some()
+        && let Some(tick) = confirmed.0.checked_sub(1)
+    {
+        net.send(&ClientMessage::AckTick { tick });
+    }
+}
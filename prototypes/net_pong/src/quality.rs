@@ -0,0 +1,162 @@
+//! Quality plugin: a green, yellow or red dot by the score.
+//!
+//! A dot left of the score rates the connection once a second, whether or
+//! not the HUD is up: green, yellow or red for the worst of the round trip,
+//! the share of the relay's datagrams lost on the way, and the share of
+//! fixed steps spent waiting for a tick (see `ConnectionQuality`). A hitch
+//! under a green dot is the game's, not the network's.
+
+use bevy::prelude::*;
+
+use crate::connection::{ConnectionState, NetSocket, RelayRtt};
+use crate::hud::{NetHud, update_hud};
+use crate::render::{SCORE_FONT_SIZE, SCORE_TOP_MARGIN};
+use crate::shared::TickRate;
+
+const QUALITY_DOT_SIZE: f32 = 14.0;
+/// Between the dot and the score, which starts at the middle of the window.
+const QUALITY_GAP: f32 = 16.0;
+/// Round trips up to the first are good, up to the second fair.
+const QUALITY_RTT_MS: (u32, u32) = (100, 200);
+/// Shares of the relay's datagrams lost: up to the first is good, up to the
+/// second fair.
+const QUALITY_LOSS: (f32, f32) = (0.02, 0.1);
+/// Shares of a second's fixed steps spent waiting for a tick.
+const QUALITY_STALLS: (f32, f32) = (0.05, 0.2);
+
+pub struct NetPongQualityPlugin;
+
+impl Plugin for NetPongQualityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<QualityMeter>()
+            .add_systems(Startup, spawn_quality_dot)
+            .add_systems(Update, update_quality_dot.after(update_hud));
+    }
+}
+
+/// How the connection has been over the last second, worst last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ConnectionQuality {
+    Good,
+    Fair,
+    Poor,
+}
+
+impl ConnectionQuality {
+    /// The worse of how `value` fares against a `(good, fair)` pair of
+    /// limits.
+    fn of<T: PartialOrd>(value: T, (good, fair): (T, T)) -> Self {
+        if value <= good {
+            Self::Good
+        } else if value <= fair {
+            Self::Fair
+        } else {
+            Self::Poor
+        }
+    }
+
+    /// The worst of the round trip, the loss and the stalls. With no round
+    /// trip measured yet, that one counts as good.
+    fn rate(rtt: Option<u32>, loss: f32, stalls: f32) -> Self {
+        let rtt = rtt.map_or(Self::Good, |ms| Self::of(ms, QUALITY_RTT_MS));
+        rtt.max(Self::of(loss, QUALITY_LOSS))
+            .max(Self::of(stalls, QUALITY_STALLS))
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Self::Good => Color::srgb(0.3, 0.9, 0.3),
+            Self::Fair => Color::srgb(1.0, 0.85, 0.2),
+            Self::Poor => Color::srgb(1.0, 0.3, 0.3),
+        }
+    }
+}
+
+#[derive(Resource)]
+struct QualityMeter {
+    meter: Timer,
+    /// The relay's datagrams that arrived and that went missing, as of the
+    /// last reading.
+    seen_at_last_reading: (u64, u64),
+    quality: Option<ConnectionQuality>,
+}
+
+impl Default for QualityMeter {
+    fn default() -> Self {
+        Self {
+            meter: Timer::from_seconds(1.0, TimerMode::Repeating),
+            seen_at_last_reading: (0, 0),
+            quality: None,
+        }
+    }
+}
+
+#[derive(Component)]
+struct QualityDot;
+
+fn spawn_quality_dot(mut commands: Commands) {
+    commands.spawn((
+        QualityDot,
+        BackgroundColor(ConnectionQuality::Good.color()),
+        Visibility::Hidden,
+        Node {
+            position_type: PositionType::Absolute,
+            // Level with the middle of the score's digits.
+            top: Val::Px(SCORE_TOP_MARGIN + (SCORE_FONT_SIZE - QUALITY_DOT_SIZE) / 2.0),
+            right: Val::Percent(50.0),
+            width: Val::Px(QUALITY_DOT_SIZE),
+            height: Val::Px(QUALITY_DOT_SIZE),
+            margin: UiRect::right(Val::Px(QUALITY_GAP)),
+            border_radius: BorderRadius::MAX,
+            ..default()
+        },
+    ));
+}
+
+/// Rate the last second once it is over, and show the dot only while a
+/// match is being played over the network.
+#[allow(clippy::too_many_arguments)]
+fn update_quality_dot(
+    time: Res<Time>,
+    state: Res<ConnectionState>,
+    net: Option<Res<NetSocket>>,
+    rtt: Res<RelayRtt>,
+    hud: Res<NetHud>,
+    tick_rate: Res<TickRate>,
+    mut meter: ResMut<QualityMeter>,
+    mut query: Query<(&mut BackgroundColor, &mut Visibility), With<QualityDot>>,
+) {
+    let Some(net) = net else {
+        return;
+    };
+    meter.meter.tick(time.delta());
+    if meter.meter.just_finished() {
+        // The filter starts over on a reconnect, so its counts can go down.
+        let stats = net.inbound.stats();
+        let seen = (stats.in_order + stats.reordered, stats.gaps);
+        let (arrived, lost) = meter.seen_at_last_reading;
+        let (arrived, lost) = (seen.0.saturating_sub(arrived), seen.1.saturating_sub(lost));
+        meter.seen_at_last_reading = seen;
+        let loss = if arrived + lost == 0 {
+            0.0
+        } else {
+            lost as f32 / (arrived + lost) as f32
+        };
+        let stalls = hud.rates.0 as f32 / tick_rate.0 as f32;
+        meter.quality = Some(ConnectionQuality::rate(rtt.0, loss, stalls));
+    }
+    let shown = (*state == ConnectionState::Playing)
+        .then_some(meter.quality)
+        .flatten();
+    for (mut color, mut visibility) in &mut query {
+        match shown {
+            Some(quality) => {
+                color.set_if_neq(BackgroundColor(quality.color()));
+                visibility.set_if_neq(Visibility::Visible);
+            }
+            None => {
+                visibility.set_if_neq(Visibility::Hidden);
+            }
+        }
+    }
+}
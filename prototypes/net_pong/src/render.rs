@@ -0,0 +1,435 @@
+//! Render plugin: sprites, score display, connection status.
+//!
+//! Drawing is not tied to ticks: each frame places the paddles and ball
+//! between where the last two fixed steps left them, so a 20 Hz tick rate
+//! still moves smoothly on a 144 Hz display.
+
+use arcade_prelude::*;
+use bevy::prelude::*;
+use pong_sim::{BALL_SIZE, Ball, MAX_PLAYERS, Paddle, PongState, paddle_size};
+
+use crate::connection::{
+    ConnectionState, NetSocket, NoOpponentMenu, OpponentWait, PlayerRoster, RelayConfig, Spectate,
+};
+use crate::input::ControlBindings;
+use crate::shared::{PlayerCount, Score};
+
+pub struct NetPongRenderPlugin;
+
+impl Plugin for NetPongRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PongColors>()
+            .init_resource::<DrawnStates>()
+            .add_systems(Startup, setup_pong)
+            .add_systems(FixedPostUpdate, remember_drawn_state)
+            .add_systems(
+                Update,
+                (
+                    update_score_display,
+                    update_connection_status,
+                    update_player_names,
+                    update_shutdown_warning,
+                    draw_simulation,
+                ),
+            );
+    }
+}
+
+#[derive(Component)]
+struct ScoreText;
+
+#[derive(Component)]
+struct ConnectionStatusText;
+
+#[derive(Component)]
+struct PlayerNamesText;
+
+#[derive(Component)]
+struct ShutdownWarningText;
+
+/// The visible part of a paddle, offset from it by local echo.
+#[derive(Component)]
+pub struct PaddleSprite;
+
+/// What the arena is drawn in. `--neon` brings its own.
+#[derive(Resource, Clone)]
+pub struct PongColors {
+    pub border: Color,
+    pub center_line: Color,
+    pub paddles: [Color; MAX_PLAYERS],
+    pub ball: Color,
+    pub score: Color,
+}
+
+impl Default for PongColors {
+    fn default() -> Self {
+        Self {
+            border: BORDER_COLOR,
+            center_line: BORDER_COLOR,
+            paddles: [PADDLE_COLOR; MAX_PLAYERS],
+            ball: BALL_COLOR,
+            score: Color::WHITE,
+        }
+    }
+}
+
+pub const SCORE_FONT_SIZE: f32 = 48.0;
+pub const SCORE_TOP_MARGIN: f32 = 20.0;
+const BORDER_THICKNESS: f32 = 4.0;
+const BORDER_COLOR: Color = palette::BORDER_GRAY;
+const PADDLE_COLOR: Color = Color::WHITE;
+const BALL_COLOR: Color = Color::WHITE;
+const CENTER_LINE_DASH_COUNT: usize = 15;
+const CENTER_LINE_DASH_WIDTH: f32 = 4.0;
+const STATUS_FONT_SIZE: f32 = 32.0;
+const NAMES_FONT_SIZE: f32 = 20.0;
+const NAMES_TOP_MARGIN: f32 = 76.0;
+const WARNING_FONT_SIZE: f32 = 20.0;
+const WARNING_BOTTOM_MARGIN: f32 = 20.0;
+
+pub fn setup_pong(mut commands: Commands, colors: Res<PongColors>, state: Res<PongState>) {
+    commands.spawn(Camera2d);
+
+    // Arena borders
+    let arena = state.arena_size();
+    spawn_arena_walls(
+        &mut commands,
+        arena.x,
+        arena.y,
+        BORDER_THICKNESS,
+        colors.border,
+    );
+
+    // Center line (dashed); the square arena has no halves to divide.
+    let dashes = if state.is_square() {
+        0
+    } else {
+        CENTER_LINE_DASH_COUNT
+    };
+    let dash_spacing = arena.y / CENTER_LINE_DASH_COUNT as f32;
+    let dash_height = dash_spacing * 0.5;
+    for i in 0..dashes {
+        let y = -arena.y / 2.0 + dash_spacing * (i as f32 + 0.5);
+        commands.spawn((
+            Sprite {
+                color: colors.center_line,
+                custom_size: Some(Vec2::new(CENTER_LINE_DASH_WIDTH, dash_height)),
+                ..default()
+            },
+            Transform::from_xyz(0.0, y, 0.0),
+        ));
+    }
+
+    // Paddles
+    for (player_index, color) in colors.paddles.into_iter().enumerate().take(state.players()) {
+        spawn_paddle(&mut commands, &state, player_index, color);
+    }
+
+    // Ball
+    commands.spawn((
+        Ball,
+        Sprite {
+            color: colors.ball,
+            custom_size: Some(Vec2::splat(BALL_SIZE)),
+            ..default()
+        },
+        Transform::from_xyz(0.0, 0.0, 0.0),
+    ));
+
+    // Score text
+    commands.spawn((
+        ScoreText,
+        Text::new(score_line(&[0; MAX_PLAYERS][..state.players()])),
+        TextFont::from_font_size(SCORE_FONT_SIZE),
+        TextColor(colors.score),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(SCORE_TOP_MARGIN),
+            left: Val::Percent(50.0),
+            ..default()
+        },
+    ));
+
+    // Player names (under the score, filled in as players join)
+    commands.spawn((
+        PlayerNamesText,
+        Text::new(""),
+        TextFont::from_font_size(NAMES_FONT_SIZE),
+        TextColor(Color::srgb(0.7, 0.7, 0.7)),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(NAMES_TOP_MARGIN),
+            left: Val::Percent(50.0),
+            ..default()
+        },
+    ));
+
+    // Connection status text (centered)
+    commands.spawn((
+        ConnectionStatusText,
+        Text::new("Connecting to relay..."),
+        TextFont::from_font_size(STATUS_FONT_SIZE),
+        TextColor(Color::srgb(1.0, 1.0, 0.5)),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(50.0),
+            left: Val::Percent(50.0),
+            ..default()
+        },
+    ));
+
+    // Relay shutdown warning (bottom, hidden until the relay sends one)
+    commands.spawn((
+        ShutdownWarningText,
+        Text::new(""),
+        TextFont::from_font_size(WARNING_FONT_SIZE),
+        TextColor(Color::srgb(1.0, 0.5, 0.3)),
+        Visibility::Hidden,
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(WARNING_BOTTOM_MARGIN),
+            left: Val::Px(WARNING_BOTTOM_MARGIN),
+            ..default()
+        },
+    ));
+}
+
+fn spawn_paddle(commands: &mut Commands, state: &PongState, player_index: usize, color: Color) {
+    // The sprite is a child so local echo can offset it without touching
+    // the simulated Transform.
+    commands
+        .spawn((
+            Paddle { player_index },
+            Transform::from_translation(state.paddle_position(player_index).extend(0.0)),
+            Visibility::default(),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                PaddleSprite,
+                Sprite {
+                    color,
+                    custom_size: Some(paddle_size(player_index)),
+                    ..default()
+                },
+                Transform::default(),
+            ));
+        });
+}
+
+/// The simulation as of the last two fixed steps. A tick lasts several
+/// frames at low tick rates, so frames in between draw a blend of the two
+/// instead of holding still and then jumping.
+#[derive(Resource)]
+pub struct DrawnStates {
+    previous: PongState,
+    current: PongState,
+}
+
+impl FromWorld for DrawnStates {
+    /// Both the state the game starts in, which has the match's players.
+    fn from_world(world: &mut World) -> Self {
+        let state = *world.resource::<PongState>();
+        Self {
+            previous: state,
+            current: state,
+        }
+    }
+}
+
+/// Move the last step's state to `previous`. When the step played no tick,
+/// both are the same and everything holds still until one is played.
+fn remember_drawn_state(state: Res<PongState>, mut drawn: ResMut<DrawnStates>) {
+    drawn.previous = drawn.current;
+    drawn.current = *state;
+}
+
+/// Draw everything the simulation moved, as far between the last two steps
+/// as the frame is between fixed steps.
+pub fn draw_simulation(
+    drawn: Res<DrawnStates>,
+    fixed_time: Res<Time<Fixed>>,
+    mut paddles: Query<(&Paddle, &mut Transform), Without<Ball>>,
+    mut ball: Query<&mut Transform, With<Ball>>,
+) {
+    let DrawnStates { previous, current } = *drawn;
+    let blend = fixed_time.overstep_fraction();
+    for (paddle, mut transform) in &mut paddles {
+        let from = previous.paddle_position(paddle.player_index);
+        let to = current.paddle_position(paddle.player_index);
+        let z = transform.translation.z;
+        transform.translation = from.lerp(to, blend).extend(z);
+    }
+    // A point puts the ball back in the middle; blending would streak it
+    // across the arena.
+    let from = if previous.resets == current.resets {
+        previous.ball_position()
+    } else {
+        current.ball_position()
+    };
+    for mut transform in &mut ball {
+        let z = transform.translation.z;
+        transform.translation = from.lerp(current.ball_position(), blend).extend(z);
+    }
+}
+
+fn update_score_display(
+    score: Res<Score>,
+    players: Res<PlayerCount>,
+    mut query: Query<&mut Text, With<ScoreText>>,
+) {
+    if !score.is_changed() {
+        return;
+    }
+    for mut text in &mut query {
+        **text = score_line(&score.points[..players.0]);
+    }
+}
+
+/// Every player's points, in slot order.
+fn score_line(points: &[u32]) -> String {
+    points
+        .iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join("  :  ")
+}
+
+fn update_connection_status(
+    state: Res<ConnectionState>,
+    spectate: Option<Res<Spectate>>,
+    net: Option<Res<NetSocket>>,
+    bindings: Res<ControlBindings>,
+    no_opponent: Res<NoOpponentMenu>,
+    wait: Res<OpponentWait>,
+    mut query: Query<(&mut Text, &mut Visibility), With<ConnectionStatusText>>,
+) {
+    if !(state.is_changed() || no_opponent.is_changed()) {
+        return;
+    }
+    let reconnecting = net.is_some_and(|net| net.reconnecting_since.is_some());
+    let serve = bindings.serve.describe();
+    let opponent = if spectate.is_some() {
+        "A player"
+    } else {
+        "Opponent"
+    };
+    for (mut text, mut visibility) in &mut query {
+        match *state {
+            ConnectionState::StartMenu => {
+                *visibility = Visibility::Hidden;
+            }
+            ConnectionState::Connecting if spectate.is_some() => {
+                **text = "Looking for a match to watch...".into();
+                *visibility = Visibility::Visible;
+            }
+            ConnectionState::Connecting if reconnecting => {
+                **text = "Relay not answering; reconnecting...".into();
+                *visibility = Visibility::Visible;
+            }
+            ConnectionState::Connecting => {
+                **text = "Connecting to relay...".into();
+                *visibility = Visibility::Visible;
+            }
+            ConnectionState::Queued(position) => {
+                **text = format!("You are #{position} in queue");
+                *visibility = Visibility::Visible;
+            }
+            ConnectionState::WaitingForOpponent => {
+                **text = "Waiting for opponent...".into();
+                *visibility = Visibility::Visible;
+            }
+            ConnectionState::NoOpponent => {
+                let waited = wait.0.unwrap_or_default().as_secs();
+                let marker = |i: usize| if i == no_opponent.selected { ">" } else { " " };
+                **text = format!(
+                    "No opponent found in {waited}s\n{} Retry\n{} Quit",
+                    marker(0),
+                    marker(1)
+                );
+                *visibility = Visibility::Visible;
+            }
+            ConnectionState::Playing => {
+                *visibility = Visibility::Hidden;
+            }
+            ConnectionState::Rejected(reason) => {
+                **text = format!("Rejected by relay: {reason}");
+                *visibility = Visibility::Visible;
+            }
+            ConnectionState::OpponentLeft => {
+                **text = format!("{opponent} left");
+                *visibility = Visibility::Visible;
+            }
+            ConnectionState::OpponentAborted(reason) => {
+                **text = format!("{opponent} ended the match: {reason}");
+                *visibility = Visibility::Visible;
+            }
+            ConnectionState::RelayClosed => {
+                **text = "Relay closed".into();
+                *visibility = Visibility::Visible;
+            }
+            ConnectionState::Reconnecting => {
+                **text = "Reconnecting...".into();
+                *visibility = Visibility::Visible;
+            }
+            ConnectionState::ConnectionLost => {
+                **text = format!("Connection lost\n{serve} for a new match");
+                *visibility = Visibility::Visible;
+            }
+            ConnectionState::RelayRestarted => {
+                **text = format!("The relay restarted and lost the match\n{serve} for a new one");
+                *visibility = Visibility::Visible;
+            }
+            ConnectionState::WrongPlayerCount(players) => {
+                **text = format!("The relay plays {players}-player matches");
+                *visibility = Visibility::Visible;
+            }
+            ConnectionState::Replaying => {
+                *visibility = Visibility::Hidden;
+            }
+            ConnectionState::ReplayFinished => {
+                **text = "End of replay".into();
+                *visibility = Visibility::Visible;
+            }
+        }
+    }
+}
+
+fn update_player_names(
+    roster: Res<PlayerRoster>,
+    players: Res<PlayerCount>,
+    spectate: Option<Res<Spectate>>,
+    mut query: Query<&mut Text, With<PlayerNamesText>>,
+) {
+    if !roster.is_changed() {
+        return;
+    }
+    let names = roster.names[..players.0]
+        .iter()
+        .map(|name| name.as_deref().unwrap_or("..."))
+        .collect::<Vec<_>>()
+        .join("  vs  ");
+    let watching = if spectate.is_some() {
+        "  (watching)"
+    } else {
+        ""
+    };
+    for mut text in &mut query {
+        **text = format!("{names}{watching}");
+    }
+}
+
+fn update_shutdown_warning(
+    time: Res<Time>,
+    mut relay_config: ResMut<RelayConfig>,
+    mut query: Query<(&mut Text, &mut Visibility), With<ShutdownWarningText>>,
+) {
+    let Some(timer) = relay_config.shutdown_timer.as_mut() else {
+        return;
+    };
+    timer.tick(time.delta());
+    let remaining = timer.remaining_secs().ceil() as u32;
+    for (mut text, mut visibility) in &mut query {
+        **text = format!("Relay shutting down in {remaining}s");
+        *visibility = Visibility::Visible;
+    }
+}
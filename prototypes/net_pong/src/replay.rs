@@ -0,0 +1,155 @@
+//! Replay plugin: `--record` keeps the confirmed inputs, `--play` plays them back.
+//!
+//! `--record match.replay` writes the match to a file on exit: the score
+//! limit, the players' names, the state play started from and every tick's
+//! inputs as the relay confirmed them. Loading a snapshot (a resync, a
+//! rematch, or a spectator's start) starts the recording over from there.
+//! `--play match.replay` plays one back offline through the same
+//! simulation, at the same tick rate, without a relay.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use pong_sim::MAX_PLAYERS;
+use prototype_relay::{Tick, serialize};
+use serde::{Deserialize, Serialize};
+
+use crate::connection::{ConfirmedTick, ConnectionState, PlayerRoster, SimulationTick};
+use crate::game::PongTick;
+use crate::resync::load_snapshot;
+use crate::rollback::GameSnapshot;
+use crate::shared::{PaddleInput, ScoreLimit, TickRate};
+
+pub struct NetPongReplayPlugin;
+
+impl Plugin for NetPongReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, play_replay_tick.run_if(is_replaying))
+            .add_systems(Last, write_replay_on_exit);
+    }
+}
+
+/// A recorded match, as `--record` writes it and `--play` reads it. The
+/// relay's GameStart carries nothing itself; what a match starts from is the
+/// score limit, who is playing and the opening state.
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+    pub score_limit: u32,
+    pub tick_rate: u32,
+    pub names: [Option<String>; MAX_PLAYERS],
+    start_tick: Tick,
+    /// The state at the start of `start_tick`.
+    pub start: GameSnapshot,
+    /// Every player's input for each tick from `start_tick` on.
+    inputs: Vec<[f32; MAX_PLAYERS]>,
+}
+
+/// `--record`: the relay's inputs for every tick from the state play started
+/// from, written to `path` on exit.
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    path: PathBuf,
+    start_tick: Tick,
+    start: GameSnapshot,
+    inputs: BTreeMap<Tick, [f32; MAX_PLAYERS]>,
+}
+
+impl ReplayRecorder {
+    pub fn new(path: PathBuf, players: usize) -> Self {
+        Self {
+            path,
+            start_tick: 0,
+            start: GameSnapshot::new_game(players),
+            inputs: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, tick: Tick, inputs: [f32; MAX_PLAYERS]) {
+        if tick >= self.start_tick {
+            self.inputs.insert(tick, inputs);
+        }
+    }
+
+    /// Carry on from a loaded snapshot. The ticks before it no longer lead
+    /// to the state we are in.
+    pub fn start_over(&mut self, tick: Tick, snapshot: &GameSnapshot) {
+        self.start_tick = tick;
+        self.start = snapshot.clone();
+        self.inputs = self.inputs.split_off(&tick);
+    }
+
+    /// What we have, up to the first tick that never arrived.
+    fn replay(
+        &self,
+        score_limit: u32,
+        tick_rate: u32,
+        names: &[Option<String>; MAX_PLAYERS],
+    ) -> Replay {
+        Replay {
+            score_limit,
+            tick_rate,
+            names: names.clone(),
+            start_tick: self.start_tick,
+            start: self.start.clone(),
+            inputs: (self.start_tick..)
+                .map_while(|tick| self.inputs.get(&tick).copied())
+                .collect(),
+        }
+    }
+}
+
+/// `--play`: the recording and how many of its ticks have been played.
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    pub played: usize,
+}
+
+fn is_replaying(state: Res<ConnectionState>) -> bool {
+    *state == ConnectionState::Replaying
+}
+
+/// Play the next recorded tick, starting from the recorded state.
+fn play_replay_tick(world: &mut World) {
+    let playback = world.resource::<ReplayPlayback>();
+    let tick = playback.replay.start_tick + playback.played as Tick;
+    let inputs = playback.replay.inputs.get(playback.played).copied();
+    let start = (playback.played == 0).then(|| playback.replay.start.clone());
+    let Some(inputs) = inputs else {
+        println!("net_pong: end of replay at tick {tick}");
+        *world.resource_mut::<ConnectionState>() = ConnectionState::ReplayFinished;
+        return;
+    };
+    if let Some(start) = start {
+        load_snapshot(world, tick, &start);
+    }
+    world.resource_mut::<PaddleInput>().movement = inputs;
+    world.run_schedule(PongTick);
+    world.resource_mut::<SimulationTick>().0 = tick + 1;
+    world.resource_mut::<ConfirmedTick>().0 = tick + 1;
+    world.resource_mut::<ReplayPlayback>().played += 1;
+}
+
+fn write_replay_on_exit(
+    mut exits: MessageReader<AppExit>,
+    recorder: Option<Res<ReplayRecorder>>,
+    limit: Option<Res<ScoreLimit>>,
+    tick_rate: Option<Res<TickRate>>,
+    roster: Option<Res<PlayerRoster>>,
+) {
+    if exits.read().next().is_none() {
+        return;
+    }
+    let (Some(recorder), Some(limit), Some(tick_rate), Some(roster)) =
+        (recorder, limit, tick_rate, roster)
+    else {
+        return;
+    };
+    let replay = recorder.replay(limit.0, tick_rate.0, &roster.names);
+    let path = recorder.path.display();
+    match std::fs::write(&recorder.path, serialize(&replay)) {
+        Ok(()) => println!("net_pong: wrote {} ticks to {path}", replay.inputs.len()),
+        Err(e) => eprintln!("net_pong: failed to write replay {path}: {e}"),
+    }
+}
@@ -0,0 +1,179 @@
+//! Resync plugin: carry on from another player's state.
+//!
+//! A match that desyncs, or stalls for `RESYNC_STALL_LIMIT`, is not
+//! abandoned. The client asks the others through the relay for a snapshot:
+//! their whole game state at the start of their last confirmed tick. It
+//! loads the first one to arrive and carries on from that tick, asking the
+//! relay again for any inputs it has missed since. After a desync only the
+//! players other than slot 0 ask, so everyone ends up with slot 0's state;
+//! its banner stays up, marked as resynced, and theirs comes down.
+
+use std::time::{Duration, Instant};
+
+use audio::RollbackAudio;
+use bevy::prelude::*;
+use prototype_relay::{ClientMessage, PlayerSlot, SPECTATOR_SLOT, Tick, deserialize, serialize};
+
+use crate::connection::{
+    ConfirmedTick, InputBuffer, LocalPlayerSlot, NetSocket, NextInputTick, SimulationTick,
+    TickReady, is_playing,
+};
+use crate::desync::{DesyncCheck, check_state_hashes};
+use crate::game::TickEvents;
+use crate::pause_menu::MatchPause;
+use crate::replay::ReplayRecorder;
+use crate::rollback::{GameSnapshot, Rollback};
+use crate::shared::PaddleInput;
+use crate::verify::Ghost;
+
+/// How long the simulation may sit on one tick before we ask for a snapshot.
+const RESYNC_STALL_LIMIT: Duration = Duration::from_secs(2);
+/// How long to wait for a snapshot before asking again.
+const RESYNC_RETRY: Duration = Duration::from_secs(1);
+
+pub struct NetPongResyncPlugin;
+
+impl Plugin for NetPongResyncPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Resync>().add_systems(
+            Update,
+            (request_snapshot, resync)
+                .chain()
+                .run_if(is_playing)
+                .after(check_state_hashes),
+        );
+    }
+}
+
+/// Snapshots asked for, to send, and received.
+#[derive(Resource, Default)]
+pub struct Resync {
+    /// When we last asked for a snapshot, while we wait for one.
+    pub asked_at: Option<Instant>,
+    /// Another player asked for ours.
+    pub requested: bool,
+    /// From whom, for which tick, and the state, until it is loaded.
+    pub received: Option<(PlayerSlot, Tick, Vec<u8>)>,
+    /// The tick the simulation was last seen on, and since when.
+    progress: Option<(Tick, Instant)>,
+}
+
+/// Ask for a snapshot when the simulation is stuck, or out of sync with
+/// slot 0, or as a spectator, to start from.
+fn request_snapshot(
+    mut net: ResMut<NetSocket>,
+    sim_tick: Res<SimulationTick>,
+    local_slot: Res<LocalPlayerSlot>,
+    desync: Res<DesyncCheck>,
+    mut resync: ResMut<Resync>,
+    pause: Res<MatchPause>,
+) {
+    // Waiting at a pause is not a stall.
+    if pause.at.is_some() {
+        resync.progress = None;
+        return;
+    }
+    let now = Instant::now();
+    if resync.progress.is_none_or(|(tick, _)| tick != sim_tick.0) {
+        resync.progress = Some((sim_tick.0, now));
+    }
+    let stalled = resync
+        .progress
+        .is_some_and(|(_, since)| now - since >= RESYNC_STALL_LIMIT);
+    let out_of_sync = desync.desync.is_some() && local_slot.0 != 0;
+    let starting_to_watch = local_slot.0 == SPECTATOR_SLOT && sim_tick.0 == 0;
+    let wanted = stalled || out_of_sync || starting_to_watch;
+    if !wanted || resync.asked_at.is_some_and(|at| now - at < RESYNC_RETRY) {
+        return;
+    }
+    if resync.asked_at.is_none() {
+        let why = if starting_to_watch {
+            "watching"
+        } else if out_of_sync {
+            "out of sync"
+        } else {
+            "stalled"
+        };
+        println!(
+            "net_pong: {why} on tick {}; asking for a snapshot",
+            sim_tick.0
+        );
+    }
+    resync.asked_at = Some(now);
+    net.send(&ClientMessage::RequestSnapshot);
+}
+
+/// Answer a request for our snapshot, and load one we asked for.
+fn resync(world: &mut World) {
+    let confirmed = world.resource::<ConfirmedTick>().0;
+    if std::mem::take(&mut world.resource_mut::<Resync>().requested) {
+        // Only confirmed ticks are the same everywhere. In lockstep that is
+        // the state now; with rollback it may be behind a few guesses.
+        let snapshot = if world.resource::<SimulationTick>().0 == confirmed {
+            Some(GameSnapshot::capture(world))
+        } else {
+            world
+                .get_resource::<Rollback>()
+                .and_then(|rollback| rollback.snapshots.get(&confirmed).cloned())
+        };
+        if let Some(snapshot) = snapshot {
+            world
+                .resource_mut::<NetSocket>()
+                .send(&ClientMessage::Snapshot {
+                    tick: confirmed,
+                    state: serialize(&snapshot),
+                });
+            if let Some(desync) = &mut world.resource_mut::<DesyncCheck>().desync {
+                desync.resynced = true;
+            }
+        }
+    }
+
+    let Some((slot, tick, state)) = world.resource_mut::<Resync>().received.take() else {
+        return;
+    };
+    // Going back is only worth it to leave a desync behind.
+    if tick < confirmed && world.resource::<DesyncCheck>().desync.is_none() {
+        return;
+    }
+    let Some(snapshot) = deserialize::<GameSnapshot>(&state) else {
+        eprintln!("net_pong: malformed snapshot from player {slot}");
+        return;
+    };
+    load_snapshot(world, tick, &snapshot);
+    println!("net_pong: resynchronized at tick {tick} from player {slot}");
+}
+
+/// Carry on from `snapshot` as the state at the start of `tick`.
+pub fn load_snapshot(world: &mut World, tick: Tick, snapshot: &GameSnapshot) {
+    snapshot.restore(world);
+    world.resource_mut::<SimulationTick>().0 = tick;
+    world.resource_mut::<ConfirmedTick>().0 = tick;
+    {
+        let mut next_input = world.resource_mut::<NextInputTick>();
+        next_input.0 = next_input.0.max(tick);
+    }
+    world.resource_scope(|world, mut buffer: Mut<InputBuffer>| {
+        buffer.0 = buffer.0.split_off(&tick);
+        let ready = buffer.load(tick, &mut world.resource_mut::<PaddleInput>());
+        world.resource_mut::<TickReady>().0 = ready;
+    });
+    if let Some(mut rollback) = world.get_resource_mut::<Rollback>() {
+        *rollback = Rollback::default();
+    }
+    if let Some(mut audio) = world.get_resource_mut::<RollbackAudio>() {
+        *audio = RollbackAudio::default();
+    }
+    if let Some(mut recorder) = world.get_resource_mut::<ReplayRecorder>() {
+        recorder.start_over(tick, snapshot);
+    }
+    if let Some(mut ghost) = world.get_resource_mut::<Ghost>() {
+        ghost.start_over(tick, snapshot);
+    }
+    world.resource_mut::<TickEvents>().0.clear();
+    *world.resource_mut::<DesyncCheck>() = DesyncCheck {
+        next_to_send: tick,
+        ..default()
+    };
+    *world.resource_mut::<Resync>() = Resync::default();
+}
@@ -0,0 +1,189 @@
+//! Rollback plugin: play ahead on guessed input, replay when the guess was wrong.
+//!
+//! `--rollback` stops waiting on the relay altogether. Each tick is played as
+//! soon as our own input for it is known, guessing that the opponent is
+//! still doing what they last did. The state at the start of every guessed
+//! tick is kept, and when the relay's inputs for a tick differ from the
+//! guess, the game goes back to that tick and plays forward again with the
+//! real inputs. It runs at most `MAX_ROLLBACK_TICKS` ahead of the relay, and
+//! plays local input on the tick it is read rather than `INPUT_DELAY` later.
+
+use std::collections::BTreeMap;
+
+use audio::RollbackAudio;
+use bevy::prelude::*;
+use pong_sim::{MAX_PLAYERS, PongState};
+use prototype_relay::{PlayerSlot, Tick};
+use serde::{Deserialize, Serialize};
+
+use crate::connection::{
+    ConfirmedTick, InputBuffer, InputDelay, LocalPlayerSlot, NetSocket, SimulationTick, is_playing,
+};
+use crate::game::PongTick;
+use crate::shared::{MatchWinner, PaddleInput, Score};
+
+/// How far ahead of the relay's inputs our own may go. The relay drops input
+/// more than its `--input-window` (default 3) ahead of it, and we only play
+/// ticks we have sent input for.
+const MAX_ROLLBACK_TICKS: Tick = 3;
+
+pub struct NetPongRollbackPlugin;
+
+impl Plugin for NetPongRollbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, rollback_tick.run_if(is_playing));
+    }
+}
+
+/// `--rollback`: the ticks played on a guess, until the relay confirms them.
+#[derive(Resource, Default)]
+pub struct Rollback {
+    /// The state at the start of each unconfirmed tick.
+    pub snapshots: BTreeMap<Tick, GameSnapshot>,
+    /// The inputs each unconfirmed tick was played with.
+    played: BTreeMap<Tick, [f32; MAX_PLAYERS]>,
+    /// Every player's input on the last confirmed tick; the guess for the
+    /// others from there on.
+    last_confirmed: [f32; MAX_PLAYERS],
+}
+
+/// Everything a tick can change: Pong's state, and whether the match is
+/// over.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameSnapshot {
+    pub state: PongState,
+    pub winner: Option<(PlayerSlot, Tick)>,
+}
+
+impl GameSnapshot {
+    /// The state before tick 0, as `setup_pong` spawns it.
+    pub fn new_game(players: usize) -> Self {
+        Self {
+            state: PongState::new(players),
+            winner: None,
+        }
+    }
+
+    pub fn capture(world: &mut World) -> Self {
+        Self {
+            state: *world.resource::<PongState>(),
+            winner: world.resource::<MatchWinner>().0,
+        }
+    }
+
+    /// FNV-1a over every field, the same on every machine.
+    pub fn hash(&self) -> u64 {
+        let state = &self.state;
+        let fixed = state
+            .paddle
+            .into_iter()
+            .chain([state.ball.x, state.ball.y])
+            .chain([state.ball_velocity.x, state.ball_velocity.y])
+            .map(|x| x.to_bits() as u32);
+        let winner = match self.winner {
+            Some((slot, tick)) => [u32::from(slot) + 1, tick],
+            None => [0, 0],
+        };
+        let last_hit = state.last_hit.map_or(0, |slot| u32::from(slot) + 1);
+        let counts = state
+            .score
+            .into_iter()
+            .chain([u32::from(state.players), state.resets, last_hit])
+            .chain(winner);
+        fixed
+            .chain(counts)
+            .flat_map(u32::to_le_bytes)
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            })
+    }
+
+    pub fn restore(&self, world: &mut World) {
+        *world.resource_mut::<PongState>() = self.state;
+        // Only touched when it differs, so a replay does not report the score
+        // to the relay again.
+        world.resource_mut::<Score>().follow(&self.state);
+        world.resource_mut::<MatchWinner>().0 = self.winner;
+    }
+}
+
+/// Replay from the first tick the relay's inputs contradict, confirm what
+/// it has sent, then play the next tick if we are not too far ahead.
+fn rollback_tick(world: &mut World) {
+    let sim_tick = world.resource::<SimulationTick>().0;
+    let mut confirmed = world.resource::<ConfirmedTick>().0;
+
+    let wrong_guess = {
+        let buffer = world.resource::<InputBuffer>();
+        let rollback = world.resource::<Rollback>();
+        (confirmed..sim_tick).find(|tick| {
+            buffer
+                .0
+                .get(tick)
+                .is_some_and(|inputs| rollback.played.get(tick) != Some(inputs))
+        })
+    };
+    if let Some(from) = wrong_guess
+        && let Some(snapshot) = world.resource::<Rollback>().snapshots.get(&from).cloned()
+    {
+        snapshot.restore(world);
+        if let Some(mut audio) = world.get_resource_mut::<RollbackAudio>() {
+            audio.rollback_to(from);
+        }
+        for tick in from..sim_tick {
+            play_rollback_tick(world, tick);
+        }
+    }
+
+    world.resource_scope(|world, mut buffer: Mut<InputBuffer>| {
+        let mut rollback = world.resource_mut::<Rollback>();
+        while confirmed < sim_tick
+            && let Some(inputs) = buffer.0.get(&confirmed)
+        {
+            rollback.last_confirmed = *inputs;
+            confirmed += 1;
+        }
+        rollback.snapshots = rollback.snapshots.split_off(&confirmed);
+        rollback.played = rollback.played.split_off(&confirmed);
+        buffer.0 = buffer.0.split_off(&confirmed);
+    });
+    world.resource_mut::<ConfirmedTick>().0 = confirmed;
+
+    // Playing this tick lets the next input read be for `sim_tick + 1 + delay`.
+    let delay = world.resource::<InputDelay>().0;
+    let local_input_sent = world
+        .resource::<NetSocket>()
+        .sent_inputs
+        .contains_key(&sim_tick);
+    if sim_tick + delay < confirmed + MAX_ROLLBACK_TICKS && local_input_sent {
+        play_rollback_tick(world, sim_tick);
+    }
+}
+
+/// Play `tick` with the relay's inputs if they are in, or else our own and
+/// a guess at everyone else's, keeping the state it started from.
+fn play_rollback_tick(world: &mut World, tick: Tick) {
+    let inputs = match world.resource::<InputBuffer>().0.get(&tick) {
+        Some(inputs) => *inputs,
+        None => {
+            let local_slot = world.resource::<LocalPlayerSlot>().0 as usize;
+            let local = world
+                .resource::<NetSocket>()
+                .sent_inputs
+                .get(&tick)
+                .copied();
+            let mut inputs = world.resource::<Rollback>().last_confirmed;
+            inputs[local_slot] = local.unwrap_or_default().movement;
+            inputs
+        }
+    };
+    let snapshot = GameSnapshot::capture(world);
+    let mut rollback = world.resource_mut::<Rollback>();
+    rollback.snapshots.insert(tick, snapshot);
+    rollback.played.insert(tick, inputs);
+
+    world.resource_mut::<SimulationTick>().0 = tick;
+    world.resource_mut::<PaddleInput>().movement = inputs;
+    world.run_schedule(PongTick);
+    world.resource_mut::<SimulationTick>().0 = tick + 1;
+}
@@ -0,0 +1,110 @@
+//! Components and resources the plugins share.
+
+use std::fmt;
+
+use bevy::prelude::*;
+use pong_sim::fixed::Fx;
+use pong_sim::{MAX_PLAYERS, PLAYER_COUNT, PongState};
+use prototype_relay::ticklog::TickLog;
+use prototype_relay::{ClientMessage, LockstepInput, Payload, PlayerSlot, RelayMessage, Tick};
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{DEFAULT_TICK_RATE_HZ, GAME_ID};
+
+/// The score as the HUD and the relay see it: `PongState::score`, changed
+/// only when a point is scored so both can wait for that.
+#[derive(Resource, Default)]
+pub struct Score {
+    pub points: [u32; MAX_PLAYERS],
+}
+
+impl Score {
+    pub fn follow(&mut self, state: &PongState) {
+        if self.points != state.score {
+            self.points = state.score;
+        }
+    }
+}
+
+/// `--score-limit`: points to win, or 0 to play forever.
+#[derive(Resource)]
+pub struct ScoreLimit(pub u32);
+
+/// `--tick-rate`: ticks per second.
+#[derive(Resource)]
+pub struct TickRate(pub u32);
+
+/// `--players`: how many play each match, and so which arena.
+#[derive(Resource)]
+pub struct PlayerCount(pub usize);
+
+/// What we call the game in Hello, Resume and Spectate.
+#[derive(Resource)]
+pub struct GameId(pub String);
+
+/// Clients with different score limits, tick rates or player counts would
+/// play different games from the same inputs, so the relay must not pair
+/// them.
+pub fn game_id(score_limit: u32, tick_rate: u32, players: usize) -> String {
+    let mut id = GAME_ID.to_string();
+    if score_limit != 0 {
+        id += &format!("-first-to-{score_limit}");
+    }
+    if tick_rate != DEFAULT_TICK_RATE_HZ {
+        id += &format!("-at-{tick_rate}hz");
+    }
+    if players != PLAYER_COUNT {
+        id += &format!("-{players}-players");
+    }
+    id
+}
+
+/// The slot that reached the score limit and the tick it happened on. Part
+/// of the simulation, so it rolls back and resyncs with everything else.
+#[derive(Resource, Default)]
+pub struct MatchWinner(pub Option<(PlayerSlot, Tick)>);
+
+/// How much time one tick simulates. The same on every machine, unlike
+/// `Time::delta`, so simulation systems read this instead.
+#[derive(Resource)]
+pub struct TickDt(pub Fx);
+
+#[derive(Resource, Default)]
+pub struct PaddleInput {
+    pub movement: [f32; MAX_PLAYERS],
+}
+
+/// What each client sends the relay every tick.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PongInput {
+    /// Paddle direction in [-1, 1]; positive is up.
+    pub movement: f32,
+}
+
+impl LockstepInput for PongInput {}
+
+/// The last `--tick-log` ticks of inputs and game state, written to the
+/// working directory when the relay sends something malformed, inputs
+/// arrive for a tick we have not sent input for, or the game panics.
+#[derive(Resource)]
+pub struct PongTickLog(pub TickLog<TickSnapshot>);
+
+/// One tick's inputs and the state they produced.
+pub struct TickSnapshot {
+    pub inputs: [f32; MAX_PLAYERS],
+    pub state: PongState,
+}
+
+/// A line of the tick log dump: the inputs of the players in the match, then
+/// the state.
+impl fmt::Debug for TickSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TickSnapshot")
+            .field("inputs", &&self.inputs[..self.state.players()])
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+pub type PongClientMessage = ClientMessage<Payload<PongInput>>;
+pub type PongRelayMessage = RelayMessage<Payload<PongInput>>;
@@ -0,0 +1,161 @@
+//! Stall plugin: a spinner and slower frames while lockstep waits.
+//!
+//! A stall of `STALL_SHOWN_AFTER` or more puts up "Waiting for the other
+//! player" with a spinner, and holds frames `STALLED_FRAME_TIME` apart until
+//! a tick plays: there is nothing new to draw, so there is no point spending
+//! a core on it. Our unconfirmed inputs go out again every half second of
+//! a stall, and not while ticks are being confirmed.
+
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use bevy::winit::{UpdateMode, WinitSettings};
+use pong_sim::PLAYER_COUNT;
+use prototype_relay::Tick;
+
+use crate::connection::{ConnectionState, SimulationTick, Spectate, receive_relay_messages};
+use crate::pause_menu::MatchPause;
+use crate::shared::PlayerCount;
+
+/// How long the simulation may wait for a tick before it counts as a stall.
+/// Shorter waits are ordinary jitter and go by unseen.
+const STALL_SHOWN_AFTER: Duration = Duration::from_millis(250);
+/// The most time between frames during a stall: often enough to turn the
+/// spinner and answer the keyboard, without spinning a core on frames that
+/// draw nothing new.
+const STALLED_FRAME_TIME: Duration = Duration::from_millis(50);
+const SPINNER_FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+const SPINNER_FRAME_SECS: f32 = 0.1;
+const SPINNER_FONT_SIZE: f32 = 24.0;
+/// Below the middle, clear of the ball's serve.
+const SPINNER_TOP: f32 = 62.0;
+
+pub struct NetPongStallPlugin;
+
+impl Plugin for NetPongStallPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Stall>()
+            .add_systems(Startup, spawn_stall_spinner)
+            .add_systems(
+                Update,
+                (watch_for_stalls, update_stall_spinner)
+                    .chain()
+                    .after(receive_relay_messages),
+            );
+    }
+}
+
+/// How long the simulation has been stuck on one tick.
+#[derive(Resource)]
+struct Stall {
+    tick: Tick,
+    since: Instant,
+    /// The frame pacing to go back to once ticks flow again, while slowed.
+    paced_from: Option<WinitSettings>,
+}
+
+impl Default for Stall {
+    fn default() -> Self {
+        Self {
+            tick: 0,
+            since: Instant::now(),
+            paced_from: None,
+        }
+    }
+}
+
+impl Stall {
+    fn is_stalled(&self) -> bool {
+        self.since.elapsed() >= STALL_SHOWN_AFTER
+    }
+}
+
+#[derive(Component)]
+struct StallSpinner;
+
+fn spawn_stall_spinner(mut commands: Commands) {
+    commands.spawn((
+        StallSpinner,
+        Text::new(""),
+        TextFont::from_font_size(SPINNER_FONT_SIZE),
+        TextColor(Color::srgb(1.0, 1.0, 0.5)),
+        Visibility::Hidden,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(SPINNER_TOP),
+            left: Val::Percent(50.0),
+            ..default()
+        },
+    ));
+}
+
+/// Note when the simulation last moved on, and while it has not for
+/// `STALL_SHOWN_AFTER`, hold frames to `STALLED_FRAME_TIME` apart. Only a
+/// match in play stalls: a pause or a match that has not started is
+/// waiting on purpose.
+fn watch_for_stalls(
+    state: Res<ConnectionState>,
+    sim_tick: Res<SimulationTick>,
+    pause: Res<MatchPause>,
+    mut stall: ResMut<Stall>,
+    winit: Option<ResMut<WinitSettings>>,
+) {
+    let waiting = *state == ConnectionState::Playing && pause.at.is_none();
+    if !waiting || sim_tick.0 != stall.tick {
+        stall.tick = sim_tick.0;
+        stall.since = Instant::now();
+    }
+    // Headless bots have no window to pace.
+    let Some(mut winit) = winit else {
+        return;
+    };
+    match (stall.is_stalled(), stall.paced_from.is_some()) {
+        (true, false) => {
+            stall.paced_from = Some(winit.clone());
+            let slow = UpdateMode::reactive_low_power(STALLED_FRAME_TIME);
+            winit.focused_mode = slow;
+            winit.unfocused_mode = slow;
+        }
+        (false, true) => {
+            if let Some(paced_from) = stall.paced_from.take() {
+                *winit = paced_from;
+            }
+        }
+        _ => {}
+    }
+}
+
+/// "Waiting for the other player" with a turning bar, for as long as the
+/// stall lasts.
+fn update_stall_spinner(
+    stall: Res<Stall>,
+    players: Res<PlayerCount>,
+    spectate: Option<Res<Spectate>>,
+    mut query: Query<(&mut Text, &mut Visibility), With<StallSpinner>>,
+) {
+    let content = stall.is_stalled().then(|| {
+        let frame = (stall.since.elapsed().as_secs_f32() / SPINNER_FRAME_SECS) as usize;
+        let spinner = SPINNER_FRAMES[frame % SPINNER_FRAMES.len()];
+        let who = if spectate.is_some() {
+            "the players"
+        } else if players.0 > PLAYER_COUNT {
+            "the other players"
+        } else {
+            "the other player"
+        };
+        format!("{spinner} Waiting for {who}...")
+    });
+    for (mut text, mut visibility) in &mut query {
+        match &content {
+            Some(content) => {
+                if **text != *content {
+                    **text = content.clone();
+                }
+                visibility.set_if_neq(Visibility::Visible);
+            }
+            None => {
+                visibility.set_if_neq(Visibility::Hidden);
+            }
+        }
+    }
+}
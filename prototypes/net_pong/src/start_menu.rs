@@ -0,0 +1,400 @@
+//! Start menu plugin: the relay, room and name, typed in or found on the LAN.
+//!
+//! Without `--relay` (or `--play`) the game opens on a start menu instead of
+//! connecting: type the relay's address (`DEFAULT_RELAY` to begin with), a
+//! room to join or watch (none for the unnamed match) and your name, then
+//! choose Play or Spectate. `--room`, `--name` and `--spectate` fill it in.
+//! Find LAN games broadcasts a discovery probe (see
+//! `prototype_relay::discovery`) and lists, for `LAN_SEARCH_TIME`, each
+//! answering relay's unnamed match and the standing rooms playing this game
+//! or nothing yet, with their players; picking one plays there.
+
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use bevy::input::InputSystems;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+use prototype_relay::discovery::{DISCOVERY_PORT, DiscoveryProbe, DiscoveryReply};
+use prototype_relay::{SPECTATOR_SLOT, deserialize, serialize};
+
+use crate::connection::{
+    ConnectionState, LocalPlayerSlot, NetSocket, PlayerName, RelayAddress, RoomName, Spectate,
+};
+use crate::shared::GameId;
+
+const START_MENU_FONT_SIZE: f32 = 28.0;
+const START_MENU_BACKGROUND: Color = Color::srgba(0.0, 0.0, 0.0, 0.8);
+/// The most characters a start menu field takes.
+const START_MENU_FIELD_LEN: usize = 40;
+/// Relay, Room and Name come first, then the buttons.
+const START_MENU_FIELDS: usize = 3;
+pub const START_MENU_PLAY: usize = START_MENU_FIELDS;
+pub const START_MENU_SPECTATE: usize = START_MENU_FIELDS + 1;
+const START_MENU_FIND_LAN: usize = START_MENU_FIELDS + 2;
+/// The fields, then Play, Spectate, Find LAN games and Quit. The games found
+/// come after those.
+const START_MENU_ITEMS: usize = START_MENU_FIELDS + 4;
+/// How long to listen for relays answering a discovery probe.
+const LAN_SEARCH_TIME: Duration = Duration::from_secs(1);
+
+pub struct NetPongStartMenuPlugin;
+
+impl Plugin for NetPongStartMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_start_menu_text)
+            .add_systems(
+                PreUpdate,
+                navigate_start_menu
+                    .after(InputSystems)
+                    .run_if(is_in_start_menu),
+            )
+            .add_systems(
+                Update,
+                (
+                    listen_for_lan_games.run_if(is_in_start_menu),
+                    update_start_menu_text,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// What has been typed into the start menu so far. Only there when the game
+/// opens on it.
+#[derive(Resource)]
+pub struct StartMenu {
+    pub selected: usize,
+    pub relay: String,
+    /// Empty for the unnamed match.
+    pub room: String,
+    pub name: String,
+    /// Spectating does not go with `--rollback` or `--packet-rate`.
+    pub can_spectate: bool,
+    /// Why the last Play, Spectate or search did not work out.
+    pub problem: Option<String>,
+    /// The socket a discovery probe went out on, while replies may still
+    /// come back to it.
+    pub lan_search: Option<LanSearch>,
+    /// What the last search found, each one an item below Quit.
+    pub lan_games: Vec<LanGame>,
+}
+
+pub struct LanSearch {
+    socket: UdpSocket,
+    until: Instant,
+}
+
+/// A match a relay on the LAN offers: one of its standing rooms, or its
+/// unnamed match.
+pub struct LanGame {
+    relay: SocketAddr,
+    room: Option<String>,
+    /// How it is listed in the menu.
+    description: String,
+}
+
+impl LanGame {
+    /// What `reply`, from `from`, offers that plays `game_id`. A room with
+    /// no match in it yet plays whatever its first player brings.
+    fn from_reply(reply: &DiscoveryReply, from: SocketAddr, game_id: &str) -> Vec<LanGame> {
+        let relay = SocketAddr::new(from.ip(), reply.relay_port);
+        let capacity = reply.players_per_match;
+        let rooms = reply
+            .rooms
+            .iter()
+            .filter(|room| room.game_id.as_deref().is_none_or(|id| id == game_id))
+            .map(|room| LanGame {
+                relay,
+                room: Some(room.name.clone()),
+                description: format!(
+                    "{} at {relay}: {}/{capacity} players",
+                    room.name, room.players
+                ),
+            });
+        let waiting = reply.open_matches.iter().any(|id| id == game_id);
+        let unnamed = LanGame {
+            relay,
+            room: None,
+            description: if waiting {
+                format!("Unnamed match at {relay}: someone waiting")
+            } else {
+                format!("Unnamed match at {relay}: nobody waiting")
+            },
+        };
+        rooms.chain(std::iter::once(unnamed)).collect()
+    }
+}
+
+impl StartMenu {
+    fn items(&self) -> usize {
+        START_MENU_ITEMS + self.lan_games.len()
+    }
+
+    fn select_previous(&mut self) {
+        self.selected = (self.selected + self.items() - 1) % self.items();
+    }
+
+    fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.items();
+    }
+
+    /// The selected field, if a field is selected.
+    fn field_mut(&mut self) -> Option<&mut String> {
+        match self.selected {
+            0 => Some(&mut self.relay),
+            1 => Some(&mut self.room),
+            2 => Some(&mut self.name),
+            _ => None,
+        }
+    }
+
+    fn type_text(&mut self, text: &str) {
+        let Some(field) = self.field_mut() else {
+            return;
+        };
+        let room = START_MENU_FIELD_LEN.saturating_sub(field.chars().count());
+        field.extend(text.chars().filter(|c| !c.is_control()).take(room));
+    }
+
+    /// The relay the address field names, looking up a host name if need be.
+    fn relay_addr(&self) -> Option<SocketAddr> {
+        self.relay.trim().to_socket_addrs().ok()?.next()
+    }
+
+    /// Forget the last search's games and start another.
+    fn search_lan(&mut self) {
+        self.lan_games.clear();
+        self.selected = self.selected.min(START_MENU_ITEMS - 1);
+        match send_discovery_probe() {
+            Ok(socket) => {
+                self.lan_search = Some(LanSearch {
+                    socket,
+                    until: Instant::now() + LAN_SEARCH_TIME,
+                });
+                self.problem = None;
+            }
+            Err(e) => self.problem = Some(format!("Cannot search the LAN: {e}")),
+        }
+    }
+}
+
+/// Broadcast a discovery probe from a socket of its own, which the relays'
+/// replies come back to.
+fn send_discovery_probe() -> std::io::Result<UdpSocket> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_broadcast(true)?;
+    socket.set_nonblocking(true)?;
+    let probe = serialize(&DiscoveryProbe::new());
+    socket.send_to(&probe, (Ipv4Addr::BROADCAST, DISCOVERY_PORT))?;
+    Ok(socket)
+}
+
+/// Add what each relay answering the probe offers, until the search is over.
+fn listen_for_lan_games(mut menu: ResMut<StartMenu>, game_id: Res<GameId>) {
+    let Some(search) = &menu.lan_search else {
+        return;
+    };
+    // Big enough for a relay with a few dozen rooms.
+    let mut buf = [0u8; 8192];
+    let mut found = Vec::new();
+    while let Ok((len, from)) = search.socket.recv_from(&mut buf) {
+        if let Some(reply) = deserialize::<DiscoveryReply>(&buf[..len]) {
+            found.extend(LanGame::from_reply(&reply, from, &game_id.0));
+        }
+    }
+    let over = Instant::now() >= search.until;
+    if !found.is_empty() {
+        menu.lan_games.extend(found);
+    }
+    if over {
+        menu.lan_search = None;
+        if menu.lan_games.is_empty() {
+            menu.problem = Some("No LAN games found".into());
+        }
+    }
+}
+
+pub fn is_in_start_menu(state: Res<ConnectionState>) -> bool {
+    *state == ConnectionState::StartMenu
+}
+
+#[derive(Component)]
+struct StartMenuText;
+
+fn spawn_start_menu_text(mut commands: Commands) {
+    commands.spawn((
+        StartMenuText,
+        Text::new(""),
+        TextFont::from_font_size(START_MENU_FONT_SIZE),
+        TextColor(Color::WHITE),
+        BackgroundColor(START_MENU_BACKGROUND),
+        GlobalZIndex(40),
+        Visibility::Hidden,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(25.0),
+            left: Val::Percent(30.0),
+            padding: UiRect::all(Val::Px(16.0)),
+            ..default()
+        },
+    ));
+}
+
+/// Typing goes into the selected field. Up and Down (Tab too, or the
+/// D-pad) move between the items, and Enter (or South) moves on from a
+/// field or presses a button. Every key is taken out of `ButtonInput`
+/// before anything in `Update` sees it, as the chat does, so typing a name
+/// neither steers nor opens the pause menu.
+#[allow(clippy::too_many_arguments)]
+fn navigate_start_menu(
+    mut keyboard: ResMut<ButtonInput<KeyCode>>,
+    mut typed: MessageReader<KeyboardInput>,
+    gamepads: Query<&Gamepad>,
+    mut menu: ResMut<StartMenu>,
+    mut state: ResMut<ConnectionState>,
+    mut net: ResMut<NetSocket>,
+    mut commands: Commands,
+    mut exit: MessageWriter<AppExit>,
+) {
+    let mut pressed = false;
+    for event in typed.read() {
+        if !event.state.is_pressed() {
+            continue;
+        }
+        match &event.logical_key {
+            Key::ArrowUp => menu.select_previous(),
+            Key::ArrowDown | Key::Tab => menu.select_next(),
+            Key::Enter => pressed = true,
+            Key::Backspace => {
+                if let Some(field) = menu.field_mut() {
+                    field.pop();
+                }
+            }
+            Key::Space => menu.type_text(" "),
+            Key::Character(text) => menu.type_text(text),
+            _ => {}
+        }
+    }
+    for gamepad in &gamepads {
+        if gamepad.just_pressed(GamepadButton::DPadUp) {
+            menu.select_previous();
+        }
+        if gamepad.just_pressed(GamepadButton::DPadDown) {
+            menu.select_next();
+        }
+        pressed |= gamepad.just_pressed(GamepadButton::South);
+    }
+    let keys: Vec<KeyCode> = keyboard.get_just_pressed().copied().collect();
+    for key in keys {
+        keyboard.clear_just_pressed(key);
+    }
+    if !pressed {
+        return;
+    }
+
+    let selected = menu.selected;
+    let spectate = match selected {
+        _ if selected < START_MENU_FIELDS => {
+            menu.selected += 1;
+            return;
+        }
+        START_MENU_PLAY => false,
+        START_MENU_SPECTATE => true,
+        START_MENU_FIND_LAN => {
+            menu.search_lan();
+            return;
+        }
+        _ if selected < START_MENU_ITEMS => {
+            exit.write(AppExit::Success);
+            return;
+        }
+        _ => {
+            // A game found on the LAN: play there.
+            let game = &menu.lan_games[selected - START_MENU_ITEMS];
+            let (relay, room) = (game.relay.to_string(), game.room.clone());
+            menu.relay = relay;
+            menu.room = room.unwrap_or_default();
+            false
+        }
+    };
+    if spectate && !menu.can_spectate {
+        menu.problem = Some("Spectating does not go with --rollback or --packet-rate".into());
+        return;
+    }
+    let Some(addr) = menu.relay_addr() else {
+        let problem = format!("No relay at \"{}\"; try host:port", menu.relay.trim());
+        menu.problem = Some(problem);
+        return;
+    };
+    let room = Some(menu.room.trim().to_string()).filter(|room| !room.is_empty());
+    let name = menu.name.trim();
+    if !name.is_empty() {
+        commands.insert_resource(PlayerName(name.to_string()));
+    }
+    net.relay_addr = addr;
+    commands.insert_resource(RelayAddress(addr));
+    // A spectator names the room it watches, not one it plays in.
+    if spectate {
+        commands.insert_resource(Spectate { room });
+        commands.insert_resource(LocalPlayerSlot(SPECTATOR_SLOT));
+    } else {
+        commands.insert_resource(RoomName(room));
+    }
+    menu.problem = None;
+    *state = ConnectionState::Connecting;
+}
+
+fn update_start_menu_text(
+    menu: Res<StartMenu>,
+    state: Res<ConnectionState>,
+    mut query: Query<(&mut Text, &mut Visibility), With<StartMenuText>>,
+) {
+    if !(menu.is_changed() || state.is_changed()) {
+        return;
+    }
+    let shown = *state == ConnectionState::StartMenu;
+    let field = |label: &str, value: &str, index: usize| {
+        let cursor = if menu.selected == index { "_" } else { "" };
+        format!("{label}: {value}{cursor}")
+    };
+    let room: &str = if menu.room.is_empty() && menu.selected != 1 {
+        "(none)"
+    } else {
+        &menu.room
+    };
+    let items = [
+        field("Relay", &menu.relay, 0),
+        field("Room", room, 1),
+        field("Name", &menu.name, 2),
+        "Play".to_string(),
+        "Spectate".to_string(),
+        "Find LAN games".to_string(),
+        "Quit".to_string(),
+    ];
+    let marker = |i: usize| if i == menu.selected { ">" } else { " " };
+    let mut content = "NET PONG\n".to_string();
+    for (i, item) in items.iter().enumerate() {
+        content += &format!("\n{} {item}", marker(i));
+    }
+    if menu.lan_search.is_some() {
+        content += "\n\nSearching the LAN...";
+    }
+    if !menu.lan_games.is_empty() {
+        content += "\n\nLAN games:";
+        for (i, game) in menu.lan_games.iter().enumerate() {
+            content += &format!("\n{} {}", marker(START_MENU_ITEMS + i), game.description);
+        }
+    }
+    if let Some(problem) = &menu.problem {
+        content += &format!("\n\n{problem}");
+    }
+    for (mut text, mut visibility) in &mut query {
+        **text = content.clone();
+        *visibility = if shown {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
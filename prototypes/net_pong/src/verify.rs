@@ -0,0 +1,168 @@
+//! Verify plugin: `--verify` checks every tick against a local ghost copy.
+//!
+//! `--verify` hunts determinism bugs on one machine, before they show up as
+//! a desync between two. A ghost copy of the game state is stepped alongside
+//! the real one from the same inputs, with nothing else allowed to touch it
+//! but loading a snapshot. The first tick where the two differ is printed to
+//! stderr with both states and where it happened: in which phase of
+//! `PongState::step`, later in the tick, or between ticks.
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use pong_sim::PongState;
+use prototype_relay::Tick;
+
+use crate::connection::{ConfirmedTick, SimulationTick};
+use crate::desync::record_state_hash;
+use crate::game::{PongTick, clear_tick_events, winning_slot};
+use crate::rollback::GameSnapshot;
+use crate::shared::{MatchWinner, PaddleInput, PongTickLog, ScoreLimit, TickDt};
+
+pub struct NetPongVerifyPlugin;
+
+impl Plugin for NetPongVerifyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PongTick,
+            (
+                check_ghost_at_tick_start.before(clear_tick_events),
+                step_ghost.after(record_state_hash),
+            ),
+        );
+    }
+}
+
+/// A second copy of the simulation, stepped by itself from the inputs each
+/// tick is played with. It only follows the played one where that is meant
+/// to jump: a loaded snapshot. Anything else that leaves the two apart is a
+/// determinism bug, and the first place they differ is where to look.
+#[derive(Resource, Default)]
+pub struct Ghost {
+    /// The ghost's state at the start of each tick from the confirmed one
+    /// on; rollback may play any of them again.
+    at_tick: BTreeMap<Tick, GameSnapshot>,
+    /// Each phase of the tick being played, as `step_simulation` left it.
+    pub played_phases: Vec<(&'static str, PongState)>,
+    /// Ticks compared and those found apart, for the report.
+    checked: u64,
+    diverged: u64,
+}
+
+impl Ghost {
+    pub fn start_over(&mut self, tick: Tick, snapshot: &GameSnapshot) {
+        self.at_tick.clear();
+        self.at_tick.insert(tick, snapshot.clone());
+    }
+
+    /// Say where the played simulation parted from the ghost, then carry on
+    /// from the played state so the next report is about a new divergence.
+    fn report(&mut self, tick: Tick, place: &str, ghost: &GameSnapshot, played: &GameSnapshot) {
+        self.diverged += 1;
+        eprintln!(
+            "net_pong: --verify: tick {tick} diverged {place} ({} of {} ticks)
+               ghost:  {ghost:?}
+  played: {played:?}",
+            self.diverged, self.checked
+        );
+    }
+}
+
+/// Nothing outside `PongTick` may change the state between ticks, except
+/// loading a snapshot, which the ghost follows.
+fn check_ghost_at_tick_start(
+    sim_tick: Res<SimulationTick>,
+    state: Res<PongState>,
+    winner: Res<MatchWinner>,
+    mut ghost: ResMut<Ghost>,
+    mut tick_log: Option<ResMut<PongTickLog>>,
+) {
+    let tick = sim_tick.0;
+    let played = GameSnapshot {
+        state: *state,
+        winner: winner.0,
+    };
+    ghost.played_phases.clear();
+    let Some(expected) = ghost.at_tick.get(&tick).cloned() else {
+        // Not reached by the ghost yet: the first tick we see.
+        ghost.at_tick.insert(tick, played);
+        return;
+    };
+    if expected != played {
+        ghost.report(
+            tick,
+            "before it was played, outside PongTick",
+            &expected,
+            &played,
+        );
+        if let Some(tick_log) = &mut tick_log {
+            tick_log
+                .0
+                .dump(&format!("--verify: tick {tick} changed between ticks"));
+        }
+        ghost.at_tick.insert(tick, played);
+    }
+}
+
+/// Play the tick on the ghost from its own state and the same inputs, and
+/// compare the two phase by phase, then as the tick ended.
+#[allow(clippy::too_many_arguments)]
+fn step_ghost(
+    sim_tick: Res<SimulationTick>,
+    confirmed: Res<ConfirmedTick>,
+    input: Res<PaddleInput>,
+    dt: Res<TickDt>,
+    limit: Res<ScoreLimit>,
+    state: Res<PongState>,
+    winner: Res<MatchWinner>,
+    mut ghost: ResMut<Ghost>,
+    mut tick_log: Option<ResMut<PongTickLog>>,
+) {
+    let tick = sim_tick.0;
+    let Some(mut next) = ghost.at_tick.get(&tick).cloned() else {
+        return;
+    };
+    let mut ghost_phases = Vec::new();
+    if next.winner.is_none() {
+        next.state
+            .step_traced(&input.movement, dt.0, |phase, state| {
+                ghost_phases.push((phase, *state));
+            });
+        if let Some(slot) = winning_slot(&next.state, limit.0) {
+            next.winner = Some((slot, tick));
+        }
+    }
+    let played = GameSnapshot {
+        state: *state,
+        winner: winner.0,
+    };
+    ghost.checked += 1;
+
+    let first_apart = ghost_phases
+        .iter()
+        .zip(&ghost.played_phases)
+        .find(|((_, ours), (_, theirs))| ours != theirs)
+        .map(|((phase, _), _)| *phase);
+    let place = match first_apart {
+        Some(phase) => Some(format!("in step_simulation, at PongState's {phase}")),
+        None if ghost_phases.len() != ghost.played_phases.len() => {
+            Some("in step_simulation, which played a different number of phases".into())
+        }
+        None if next.winner != played.winner => {
+            Some("in step_simulation, deciding the winner".into())
+        }
+        None if next != played => Some("after step_simulation, later in PongTick".into()),
+        None => None,
+    };
+    if let Some(place) = place {
+        ghost.report(tick, &place, &next, &played);
+        if let Some(tick_log) = &mut tick_log {
+            tick_log.0.dump(&format!("--verify: tick {tick} diverged"));
+        }
+        next = played;
+    }
+    ghost.at_tick.insert(tick + 1, next);
+    // The confirmed tick is the earliest rollback can play again.
+    let keep = ghost.at_tick.split_off(&confirmed.0);
+    ghost.at_tick = keep;
+}
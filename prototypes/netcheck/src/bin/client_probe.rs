@@ -0,0 +1,69 @@
+//! Client probe: play one netcheck match through the relay and say whether
+//! this connection is good enough for a game night.
+//!
+//! Usage: `cargo run -p netcheck --bin netcheck-client -- <relay_address> [--room <name>]
+//! [--duration <secs>] [--wait <secs>]`
+//!
+//! Pairs with a `netcheck-server` next to the relay, or with a friend's
+//! client probe to test the path between you. Exits 0 on PASS, 1 on FAIL.
+//! Set `RELAY_SESSION_SECRET` when the relay runs in authenticated mode.
+
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::Parser;
+use netcheck::{ProbeConfig, Thresholds, run_match};
+
+#[derive(Parser, Debug)]
+#[command(name = "netcheck-client", about = "Check a connection to the relay")]
+struct Args {
+    /// The relay to test against.
+    relay: SocketAddr,
+    /// Standing room to meet the other probe in.
+    #[arg(long)]
+    room: Option<String>,
+    /// Seconds the match lasts.
+    #[arg(long, default_value_t = 60)]
+    duration: u64,
+    /// Seconds to wait for the other probe before giving up.
+    #[arg(long, default_value_t = 120)]
+    wait: u64,
+    /// Print the build, protocol versions and features, and exit.
+    #[arg(long, exclusive = true)]
+    version: bool,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    if args.version {
+        println!("{}", prototype_relay::describe_build(arcade_buildinfo::build_info!()));
+        return ExitCode::SUCCESS;
+    }
+    let config = ProbeConfig {
+        relay_addr: args.relay,
+        name: "client-probe".into(),
+        room: args.room,
+        duration: Duration::from_secs(args.duration),
+        lobby_timeout: Some(Duration::from_secs(args.wait)),
+        thresholds: Thresholds::default(),
+    };
+    println!(
+        "netcheck-client: playing a {}s match via {}",
+        args.duration, config.relay_addr
+    );
+    match run_match(&config) {
+        Ok(report) => {
+            println!("{}", report.display(&config.thresholds));
+            if report.passed(&config.thresholds) {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(e) => {
+            eprintln!("netcheck-client: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Server probe: run next to the relay and play netcheck matches against
+//! whichever client probes show up, one after another.
+//!
+//! Usage: `cargo run -p netcheck --bin netcheck-server -- [--relay <addr>] [--room <name>]
+//! [--duration <secs>]`
+//!
+//! Each match's report is printed as it finishes. Set `RELAY_SESSION_SECRET`
+//! when the relay runs in authenticated mode.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use clap::Parser;
+use netcheck::{ProbeConfig, Thresholds, run_match};
+
+#[derive(Parser, Debug)]
+#[command(name = "netcheck-server", about = "Play netcheck matches next to the relay")]
+struct Args {
+    /// The relay, usually on this machine.
+    #[arg(long, default_value = "127.0.0.1:7700")]
+    relay: SocketAddr,
+    /// Standing room to meet client probes in.
+    #[arg(long)]
+    room: Option<String>,
+    /// Seconds each match lasts.
+    #[arg(long, default_value_t = 60)]
+    duration: u64,
+    /// Print the build, protocol versions and features, and exit.
+    #[arg(long, exclusive = true)]
+    version: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+    if args.version {
+        println!("{}", prototype_relay::describe_build(arcade_buildinfo::build_info!()));
+        return;
+    }
+    let config = ProbeConfig {
+        relay_addr: args.relay,
+        name: "server-probe".into(),
+        room: args.room,
+        duration: Duration::from_secs(args.duration),
+        lobby_timeout: None,
+        thresholds: Thresholds::default(),
+    };
+    println!("netcheck-server: waiting for client probes via {}", config.relay_addr);
+    loop {
+        match run_match(&config) {
+            Ok(report) => {
+                println!("netcheck-server: match finished");
+                println!("{}", report.display(&config.thresholds));
+            }
+            Err(e) => {
+                eprintln!("netcheck-server: {e}");
+                std::thread::sleep(Duration::from_secs(1));
+            }
+        }
+    }
+}
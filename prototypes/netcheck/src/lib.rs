@@ -0,0 +1,20 @@
+//! WAN smoke test for the lockstep relay.
+//!
+//! Two probes play a scripted synthetic match through a real relay and each
+//! prints a pass/fail report, so two friends can check their setup before a
+//! game night instead of discovering a bad connection mid-match.
+//! - `netcheck-server` runs next to the relay and plays one match after
+//!   another, for as long as it runs
+//! - `netcheck-client` runs on a player's machine, plays one match against
+//!   the server probe (or another client probe), and exits 0 on pass, 1 on fail
+//!
+//! The probes join as game `netcheck`, so the relay only pairs them with each
+//! other. Each tick's input is derived from the slot and tick number, so a
+//! probe can tell a corrupted or misdelivered input from a good one. See
+//! [`report`] for what is measured and where the pass/fail lines are.
+
+pub mod probe;
+pub mod report;
+
+pub use probe::{ProbeConfig, run_match};
+pub use report::{Report, Thresholds};
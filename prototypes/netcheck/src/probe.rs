@@ -0,0 +1,265 @@
+//! One probe's side of a synthetic match.
+//!
+//! The probe behaves like a well-mannered game client: it says Hello until
+//! the match starts, sends an input per tick at 60 Hz, acks every TickInputs,
+//! re-sends an input the relay seems not to have, and says Goodbye at the
+//! end. It only differs in what it does with the inputs: check them and time
+//! them.
+
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use prototype_relay::auth::{HANDSHAKE_NONCE, SessionAuth};
+use prototype_relay::sequence::{SequenceCounter, SequenceFilter, Sequenced};
+use prototype_relay::{ClientMessage, PlayerSlot, RelayMessage, Tick, deserialize, serialize};
+
+use crate::report::{Report, Thresholds};
+
+/// The relay only pairs probes with probes.
+pub const GAME_ID: &str = "netcheck";
+pub const TICK_INTERVAL: Duration = Duration::from_nanos(1_000_000_000 / 60);
+const HELLO_INTERVAL: Duration = Duration::from_millis(500);
+/// Re-send our input if the tick has not completed this long after sending.
+const RESEND_AFTER: Duration = Duration::from_millis(200);
+/// Give up if nothing at all arrives from the relay for this long mid-match.
+const SILENCE_LIMIT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(2);
+
+#[derive(Debug, Clone)]
+pub struct ProbeConfig {
+    pub relay_addr: SocketAddr,
+    /// Shown to the other probe.
+    pub name: String,
+    /// A standing room to meet in; `None` pairs with any waiting probe.
+    pub room: Option<String>,
+    pub duration: Duration,
+    /// How long to wait for an opponent before giving up; `None` waits forever.
+    pub lobby_timeout: Option<Duration>,
+    pub thresholds: Thresholds,
+}
+
+/// The scripted input for `slot` on `tick`. Every probe can compute every
+/// other probe's input, which is how corruption is caught.
+pub fn synthetic_input(slot: PlayerSlot, tick: Tick) -> Vec<u8> {
+    let mut input = Vec::with_capacity(6);
+    input.push(slot);
+    input.extend_from_slice(&tick.to_le_bytes());
+    input.push(slot.wrapping_mul(31) ^ tick.to_le_bytes().iter().fold(0, |a, b| a ^ b));
+    input
+}
+
+struct Connection {
+    socket: UdpSocket,
+    relay_addr: SocketAddr,
+    auth: Option<SessionAuth>,
+    session_nonce: u64,
+    outbound: SequenceCounter,
+    inbound: SequenceFilter,
+}
+
+impl Connection {
+    fn send(&mut self, msg: &ClientMessage) {
+        let bytes = serialize(&self.outbound.wrap(msg));
+        let datagram = match &self.auth {
+            Some(auth) => auth.seal(self.session_nonce, &bytes),
+            None => bytes,
+        };
+        let _ = self.socket.send_to(&datagram, self.relay_addr);
+    }
+
+    /// The next message from the relay, if one arrives before the poll
+    /// interval is up. Datagrams that fail authentication, decoding, or the
+    /// sequence filter are skipped.
+    fn receive(&mut self) -> Option<RelayMessage> {
+        let mut buf = [0u8; 2048];
+        let len = self.socket.recv(&mut buf).ok()?;
+        let mut bytes = &buf[..len];
+        if let Some(auth) = &self.auth {
+            let (nonce, message) = auth.open(bytes)?;
+            if self.session_nonce == HANDSHAKE_NONCE {
+                self.session_nonce = nonce;
+            }
+            if nonce != self.session_nonce {
+                return None;
+            }
+            bytes = message;
+        }
+        let sequenced = deserialize::<Sequenced<RelayMessage>>(bytes)?;
+        self.inbound
+            .accept(sequenced.seq)
+            .accepted()
+            .then_some(sequenced.message)
+    }
+}
+
+/// Progress through the match, from our side.
+struct Playing {
+    slot: PlayerSlot,
+    started: Instant,
+    /// The tick we are waiting on.
+    tick: Tick,
+    /// When we sent (or last re-sent) our input for `tick`; `None` until it is due.
+    sent: Option<Instant>,
+    /// When we first sent it, for the RTT.
+    first_sent: Option<Instant>,
+    last_completed: Instant,
+}
+
+/// Play one synthetic match and report on it. Fails only if the socket cannot
+/// be set up; everything that goes wrong on the network ends up in the report.
+pub fn run_match(config: &ProbeConfig) -> std::io::Result<Report> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_read_timeout(Some(POLL_INTERVAL))?;
+    let mut conn = Connection {
+        socket,
+        relay_addr: config.relay_addr,
+        auth: SessionAuth::from_env(),
+        session_nonce: HANDSHAKE_NONCE,
+        outbound: SequenceCounter::default(),
+        inbound: SequenceFilter::new(),
+    };
+
+    let ticks_expected = (config.duration.as_secs_f64() / TICK_INTERVAL.as_secs_f64()) as u32;
+    let mut report = Report {
+        ticks_expected,
+        ..Default::default()
+    };
+    let opened = Instant::now();
+    let mut slot = None;
+    let mut last_hello: Option<Instant> = None;
+    let mut last_heard = Instant::now();
+    let mut playing: Option<Playing> = None;
+
+    loop {
+        let now = Instant::now();
+        match &mut playing {
+            None => {
+                if last_hello.is_none_or(|sent| now.duration_since(sent) >= HELLO_INTERVAL) {
+                    conn.send(&ClientMessage::Hello {
+                        game_id: GAME_ID.into(),
+                        name: config.name.clone(),
+                        room: config.room.clone(),
+                    });
+                    last_hello = Some(now);
+                }
+                if config
+                    .lobby_timeout
+                    .is_some_and(|limit| now.duration_since(opened) > limit)
+                {
+                    report.ended_early = Some("no opponent showed up".into());
+                    break;
+                }
+            }
+            Some(play) => {
+                if play.tick >= ticks_expected {
+                    break;
+                }
+                if now.duration_since(last_heard) > SILENCE_LIMIT {
+                    report.ended_early = Some("relay went silent".into());
+                    break;
+                }
+                let due = play.started + TICK_INTERVAL * play.tick;
+                let resend = play
+                    .sent
+                    .is_some_and(|sent| now.duration_since(sent) >= RESEND_AFTER);
+                if (play.sent.is_none() && now >= due) || resend {
+                    conn.send(&ClientMessage::Input {
+                        tick: play.tick,
+                        payload: synthetic_input(play.slot, play.tick),
+                    });
+                    if resend {
+                        report.resends += 1;
+                    }
+                    play.sent = Some(now);
+                    play.first_sent.get_or_insert(now);
+                }
+            }
+        }
+
+        let Some(msg) = conn.receive() else {
+            continue;
+        };
+        last_heard = Instant::now();
+        match msg {
+            RelayMessage::Welcome { player_slot } => slot = Some(player_slot),
+            RelayMessage::GameStart => {
+                if playing.is_none()
+                    && let Some(slot) = slot
+                {
+                    let now = Instant::now();
+                    playing = Some(Playing {
+                        slot,
+                        started: now,
+                        tick: 0,
+                        sent: None,
+                        first_sent: None,
+                        last_completed: now,
+                    });
+                }
+            }
+            RelayMessage::TickInputs { tick, inputs } => {
+                let Some(play) = &mut playing else {
+                    continue;
+                };
+                conn.send(&ClientMessage::AckTick { tick });
+                if tick < play.tick {
+                    report.duplicates += 1;
+                    continue;
+                }
+                if tick > play.tick {
+                    // Cannot happen without our input for `tick`; count it as bad data.
+                    report.corrupt_inputs += 1;
+                    continue;
+                }
+                let now = Instant::now();
+                for (other, input) in inputs.iter().enumerate() {
+                    if *input != synthetic_input(other as PlayerSlot, tick) {
+                        report.corrupt_inputs += 1;
+                    }
+                }
+                if let Some(first_sent) = play.first_sent {
+                    report.rtts.push(now.duration_since(first_sent));
+                }
+                if tick > 0 {
+                    report.record_gap(now.duration_since(play.last_completed), &config.thresholds);
+                }
+                report.ticks_completed += 1;
+                play.last_completed = now;
+                play.tick += 1;
+                play.sent = None;
+                play.first_sent = None;
+            }
+            RelayMessage::Rejected { reason } => {
+                report.ended_early = Some(format!("relay rejected us: {reason}"));
+                break;
+            }
+            RelayMessage::PlayerLeft { slot: left } if playing.is_some() && Some(left) != slot => {
+                report.ended_early = Some("the other probe left".into());
+                break;
+            }
+            RelayMessage::MatchAborted { reason, .. } => {
+                report.ended_early = Some(format!("the other probe aborted: {reason}"));
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    conn.send(&ClientMessage::Goodbye);
+    let stats = conn.inbound.stats();
+    report.messages_received = stats.in_order + stats.reordered;
+    report.messages_lost = stats.gaps;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synthetic_inputs_differ_by_slot_and_tick() {
+        assert_eq!(synthetic_input(0, 7), synthetic_input(0, 7));
+        assert_ne!(synthetic_input(0, 7), synthetic_input(1, 7));
+        assert_ne!(synthetic_input(0, 7), synthetic_input(0, 8));
+    }
+}
@@ -0,0 +1,265 @@
+//! What a probe measured during its match, and whether that is good enough.
+//!
+//! - tick RTT: from sending our input for a tick to receiving every player's
+//!   inputs for it. This is the delay lockstep actually feels, including the
+//!   other player's leg.
+//! - loss: sequence numbers from the relay that never arrived, as a share of
+//!   everything the relay sent us.
+//! - stalls: gaps between consecutive ticks longer than
+//!   [`Thresholds::stall`], when the game would visibly freeze.
+//! - corrupt inputs: inputs that differ from what the sender's script says.
+
+use std::fmt;
+use std::time::Duration;
+
+/// Where passing ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    /// Highest acceptable 95th-percentile tick RTT.
+    pub p95_rtt: Duration,
+    /// Highest acceptable loss, as a fraction.
+    pub loss: f64,
+    /// A gap between ticks longer than this is a stall.
+    pub stall: Duration,
+    /// Longest acceptable single stall.
+    pub longest_stall: Duration,
+    /// Lowest acceptable share of scheduled ticks actually played.
+    pub completion: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            p95_rtt: Duration::from_millis(150),
+            loss: 0.02,
+            stall: Duration::from_millis(250),
+            longest_stall: Duration::from_secs(1),
+            completion: 0.95,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub ticks_expected: u32,
+    pub ticks_completed: u32,
+    /// Tick RTT of every completed tick, in completion order.
+    pub rtts: Vec<Duration>,
+    pub messages_received: u64,
+    pub messages_lost: u64,
+    /// Inputs we sent again because the tick did not complete in time.
+    pub resends: u64,
+    /// TickInputs received again for a tick already completed.
+    pub duplicates: u64,
+    pub stalls: u32,
+    pub longest_stall: Duration,
+    pub corrupt_inputs: u64,
+    /// Why the match ended early, if it did.
+    pub ended_early: Option<String>,
+}
+
+/// The `p`th percentile (0–100) of `samples`, nearest-rank.
+pub fn percentile(samples: &[Duration], p: f64) -> Option<Duration> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+impl Report {
+    pub fn loss(&self) -> f64 {
+        let sent = self.messages_received + self.messages_lost;
+        if sent == 0 {
+            0.0
+        } else {
+            self.messages_lost as f64 / sent as f64
+        }
+    }
+
+    pub fn completion(&self) -> f64 {
+        if self.ticks_expected == 0 {
+            0.0
+        } else {
+            f64::from(self.ticks_completed) / f64::from(self.ticks_expected)
+        }
+    }
+
+    /// Note a gap of `gap` between two consecutive ticks.
+    pub fn record_gap(&mut self, gap: Duration, thresholds: &Thresholds) {
+        if gap > thresholds.stall {
+            self.stalls += 1;
+            self.longest_stall = self.longest_stall.max(gap);
+        }
+    }
+
+    /// Every way this run fell short; empty means it passed.
+    pub fn failures(&self, thresholds: &Thresholds) -> Vec<String> {
+        let mut failures = Vec::new();
+        if let Some(reason) = &self.ended_early {
+            failures.push(format!("match ended early: {reason}"));
+        }
+        if self.completion() < thresholds.completion {
+            failures.push(format!(
+                "played {} of {} ticks ({:.0}%, need {:.0}%)",
+                self.ticks_completed,
+                self.ticks_expected,
+                self.completion() * 100.0,
+                thresholds.completion * 100.0
+            ));
+        }
+        match percentile(&self.rtts, 95.0) {
+            Some(p95) if p95 > thresholds.p95_rtt => failures.push(format!(
+                "95th percentile tick RTT {} ms (limit {} ms)",
+                p95.as_millis(),
+                thresholds.p95_rtt.as_millis()
+            )),
+            Some(_) => {}
+            None => failures.push("no ticks completed".into()),
+        }
+        if self.loss() > thresholds.loss {
+            failures.push(format!(
+                "{:.1}% loss (limit {:.1}%)",
+                self.loss() * 100.0,
+                thresholds.loss * 100.0
+            ));
+        }
+        if self.longest_stall > thresholds.longest_stall {
+            failures.push(format!(
+                "stalled for {} ms (limit {} ms)",
+                self.longest_stall.as_millis(),
+                thresholds.longest_stall.as_millis()
+            ));
+        }
+        if self.corrupt_inputs > 0 {
+            failures.push(format!("{} corrupt inputs", self.corrupt_inputs));
+        }
+        failures
+    }
+
+    pub fn passed(&self, thresholds: &Thresholds) -> bool {
+        self.failures(thresholds).is_empty()
+    }
+
+    /// The report as printed, ending in PASS or FAIL.
+    pub fn display<'a>(&'a self, thresholds: &'a Thresholds) -> impl fmt::Display + 'a {
+        ReportDisplay {
+            report: self,
+            thresholds,
+        }
+    }
+}
+
+struct ReportDisplay<'a> {
+    report: &'a Report,
+    thresholds: &'a Thresholds,
+}
+
+impl fmt::Display for ReportDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let report = self.report;
+        let ms = |p: f64| {
+            percentile(&report.rtts, p)
+                .map(|rtt| format!("{} ms", rtt.as_millis()))
+                .unwrap_or_else(|| "-".into())
+        };
+        writeln!(
+            f,
+            "ticks:    {} of {} ({:.1}%)",
+            report.ticks_completed,
+            report.ticks_expected,
+            report.completion() * 100.0
+        )?;
+        writeln!(f, "tick RTT: median {}, p95 {}, max {}", ms(50.0), ms(95.0), ms(100.0))?;
+        writeln!(
+            f,
+            "loss:     {:.2}% ({} of {} messages), {} resends, {} duplicates",
+            report.loss() * 100.0,
+            report.messages_lost,
+            report.messages_received + report.messages_lost,
+            report.resends,
+            report.duplicates
+        )?;
+        writeln!(
+            f,
+            "stalls:   {} over {} ms, longest {} ms",
+            report.stalls,
+            self.thresholds.stall.as_millis(),
+            report.longest_stall.as_millis()
+        )?;
+        let failures = report.failures(self.thresholds);
+        if failures.is_empty() {
+            write!(f, "PASS")
+        } else {
+            for failure in &failures {
+                writeln!(f, "  - {failure}")?;
+            }
+            write!(f, "FAIL")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn healthy() -> Report {
+        Report {
+            ticks_expected: 100,
+            ticks_completed: 100,
+            rtts: (1..=100).map(ms).collect(),
+            messages_received: 1000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn percentile_is_nearest_rank() {
+        let samples: Vec<Duration> = (1..=100).map(ms).collect();
+        assert_eq!(percentile(&samples, 50.0), Some(ms(50)));
+        assert_eq!(percentile(&samples, 95.0), Some(ms(95)));
+        assert_eq!(percentile(&samples, 100.0), Some(ms(100)));
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+
+    #[test]
+    fn healthy_run_passes() {
+        let report = healthy();
+        assert!(report.passed(&Thresholds::default()));
+        assert!(report.display(&Thresholds::default()).to_string().ends_with("PASS"));
+    }
+
+    #[test]
+    fn each_shortfall_is_reported() {
+        // given a run that was slow, lossy, stalled, and cut short
+        let thresholds = Thresholds::default();
+        let mut report = healthy();
+        report.ticks_completed = 50;
+        report.rtts = vec![ms(400); 50];
+        report.messages_lost = 100;
+        report.record_gap(ms(2000), &thresholds);
+        report.corrupt_inputs = 1;
+
+        // when it is judged
+        let failures = report.failures(&thresholds);
+
+        // then every problem is named
+        assert_eq!(failures.len(), 5, "{failures:?}");
+        assert_eq!(report.stalls, 1);
+    }
+
+    #[test]
+    fn short_gaps_are_not_stalls() {
+        let thresholds = Thresholds::default();
+        let mut report = healthy();
+        report.record_gap(ms(100), &thresholds);
+        assert_eq!(report.stalls, 0);
+        assert_eq!(report.longest_stall, Duration::ZERO);
+    }
+}
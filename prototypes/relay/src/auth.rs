@@ -0,0 +1,140 @@
+//! Optional shared-secret authentication for datagrams.
+//!
+//! When the relay and its clients share a secret, every datagram is wrapped as
+//! `[session nonce: u64 LE][HMAC-SHA256 tag: 32 bytes][message bytes]`, where
+//! the tag covers the nonce and the message. Anything without a valid tag is
+//! dropped, so a public relay cannot have its lockstep corrupted by spoofed
+//! packets from hosts that do not know the secret.
+//!
+//! The relay picks a fresh nonce each time it starts and seals everything it
+//! sends with it. A client does not know the nonce until the relay's first
+//! reply, so its Hello is sealed with nonce 0; after that it adopts the
+//! relay's nonce and uses it for everything. Datagrams captured in an earlier
+//! session therefore fail the nonce check in later ones.
+//!
+//! Both sides take the secret from `RELAY_SESSION_SECRET`; without it the
+//! relay accepts unauthenticated datagrams.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const NONCE_LEN: usize = 8;
+const TAG_LEN: usize = 32;
+/// What sealing adds in front of a message.
+pub const SEALED_OVERHEAD: usize = NONCE_LEN + TAG_LEN;
+
+/// Nonce a client uses before it has heard from the relay.
+pub const HANDSHAKE_NONCE: u64 = 0;
+
+/// Environment variable holding the shared secret. Unset means no authentication.
+pub const SECRET_ENV: &str = "RELAY_SESSION_SECRET";
+
+#[derive(Clone)]
+pub struct SessionAuth {
+    key: Vec<u8>,
+}
+
+impl SessionAuth {
+    pub fn new(secret: &[u8]) -> Self {
+        Self { key: secret.to_vec() }
+    }
+
+    /// Read the shared secret from `RELAY_SESSION_SECRET`, if set.
+    pub fn from_env() -> Option<Self> {
+        std::env::var(SECRET_ENV)
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .map(|secret| Self::new(secret.as_bytes()))
+    }
+
+    pub fn seal(&self, nonce: u64, message: &[u8]) -> Vec<u8> {
+        let tag = self.tag(nonce, message);
+        let mut sealed = Vec::with_capacity(NONCE_LEN + TAG_LEN + message.len());
+        sealed.extend_from_slice(&nonce.to_le_bytes());
+        sealed.extend_from_slice(&tag);
+        sealed.extend_from_slice(message);
+        sealed
+    }
+
+    /// Verify a sealed datagram. Returns the nonce it was sealed with and the
+    /// message bytes, or `None` if it is truncated or the tag does not match.
+    pub fn open<'a>(&self, datagram: &'a [u8]) -> Option<(u64, &'a [u8])> {
+        if datagram.len() < SEALED_OVERHEAD {
+            return None;
+        }
+        let (nonce_bytes, rest) = datagram.split_at(NONCE_LEN);
+        let (tag, message) = rest.split_at(TAG_LEN);
+        let nonce = u64::from_le_bytes(nonce_bytes.try_into().ok()?);
+
+        let mut mac = self.mac();
+        mac.update(&nonce.to_le_bytes());
+        mac.update(message);
+        mac.verify_slice(tag).ok()?;
+        Some((nonce, message))
+    }
+
+    fn tag(&self, nonce: u64, message: &[u8]) -> [u8; TAG_LEN] {
+        let mut mac = self.mac();
+        mac.update(&nonce.to_le_bytes());
+        mac.update(message);
+        mac.finalize().into_bytes().into()
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any length")
+    }
+}
+
+/// A nonce for a new relay session. Not cryptographically random; it only
+/// needs to differ between runs.
+pub fn new_session_nonce() -> u64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    let nonce = nanos ^ ((std::process::id() as u64) << 32);
+    if nonce == HANDSHAKE_NONCE { 1 } else { nonce }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_then_open() {
+        let auth = SessionAuth::new(b"secret");
+        let sealed = auth.seal(42, b"hello");
+        assert_eq!(auth.open(&sealed), Some((42, &b"hello"[..])));
+    }
+
+    #[test]
+    fn wrong_secret_is_rejected() {
+        let sealed = SessionAuth::new(b"secret").seal(42, b"hello");
+        assert_eq!(SessionAuth::new(b"other").open(&sealed), None);
+    }
+
+    #[test]
+    fn tampered_message_is_rejected() {
+        let auth = SessionAuth::new(b"secret");
+        let mut sealed = auth.seal(42, b"hello");
+        *sealed.last_mut().unwrap() ^= 1;
+        assert_eq!(auth.open(&sealed), None);
+    }
+
+    #[test]
+    fn tampered_nonce_is_rejected() {
+        let auth = SessionAuth::new(b"secret");
+        let mut sealed = auth.seal(42, b"hello");
+        sealed[0] ^= 1;
+        assert_eq!(auth.open(&sealed), None);
+    }
+
+    #[test]
+    fn truncated_datagram_is_rejected() {
+        let auth = SessionAuth::new(b"secret");
+        let sealed = auth.seal(42, b"");
+        assert_eq!(auth.open(&sealed[..sealed.len() - 1]), None);
+    }
+}
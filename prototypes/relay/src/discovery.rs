@@ -0,0 +1,106 @@
+//! LAN discovery: finding a relay without typing its address.
+//!
+//! A client broadcasts a [`DiscoveryProbe`] to [`DISCOVERY_PORT`] on its
+//! local network. Every relay listening there answers with a
+//! [`DiscoveryReply`]: the port its game socket is on and what is open on it.
+//! The reply's source IP plus [`DiscoveryReply::relay_port`] is the address
+//! to send a Hello to.
+//!
+//! Probes and replies are encoded with [`crate::serialize`] but are never
+//! authenticated: they only say what a relay on the same network is
+//! offering. Probes from outside private address ranges are ignored, so the
+//! responder cannot be used to bounce a larger reply at someone on the
+//! internet.
+
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::rooms::RoomInfo;
+
+/// Where relays listen for probes.
+pub const DISCOVERY_PORT: u16 = 7701;
+
+/// Sent to the broadcast address by a client looking for relays.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiscoveryProbe {
+    /// [`crate::PROTOCOL_VERSION`] of the client; relays speaking another
+    /// version do not answer.
+    pub protocol_version: u32,
+}
+
+impl DiscoveryProbe {
+    pub fn new() -> Self {
+        Self {
+            protocol_version: crate::PROTOCOL_VERSION,
+        }
+    }
+}
+
+impl Default for DiscoveryProbe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A relay's answer to a [`DiscoveryProbe`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiscoveryReply {
+    /// The relay's game port, on the IP the reply came from.
+    pub relay_port: u16,
+    pub players_per_match: u8,
+    /// Standing rooms that are not hidden, in name order.
+    pub rooms: Vec<RoomInfo>,
+    /// Games with an unnamed match waiting for players, one entry per match.
+    pub open_matches: Vec<String>,
+}
+
+/// True for addresses a LAN probe can come from: private, loopback and
+/// link-local ranges, and IPv6 unique-local addresses.
+pub fn is_lan(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_private() || v4.is_loopback() || v4.is_link_local(),
+        IpAddr::V6(v6) => {
+            let unique_local = (v6.segments()[0] & 0xfe00) == 0xfc00;
+            let link_local = (v6.segments()[0] & 0xffc0) == 0xfe80;
+            v6.is_loopback() || unique_local || link_local
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deserialize, serialize};
+
+    #[test]
+    fn probe_and_reply_round_trip() {
+        // given a probe and the reply a relay sends it
+        let probe = DiscoveryProbe::new();
+        let reply = DiscoveryReply {
+            relay_port: 7700,
+            players_per_match: 2,
+            rooms: Vec::new(),
+            open_matches: vec!["net_pong".into()],
+        };
+
+        // when both cross the wire
+        let probe_back: DiscoveryProbe = deserialize(&serialize(&probe)).unwrap();
+        let reply_back: DiscoveryReply = deserialize(&serialize(&reply)).unwrap();
+
+        // then they arrive unchanged
+        assert_eq!(probe_back, probe);
+        assert_eq!(reply_back, reply);
+    }
+
+    #[test]
+    fn only_local_addresses_count_as_lan() {
+        assert!(is_lan("192.168.1.20".parse().unwrap()));
+        assert!(is_lan("10.0.0.5".parse().unwrap()));
+        assert!(is_lan("127.0.0.1".parse().unwrap()));
+        assert!(is_lan("fe80::1".parse().unwrap()));
+        assert!(is_lan("fd12:3456::1".parse().unwrap()));
+        assert!(!is_lan("8.8.8.8".parse().unwrap()));
+        assert!(!is_lan("2001:4860::8888".parse().unwrap()));
+    }
+}
@@ -0,0 +1,274 @@
+//! Fragmentation and reassembly for messages larger than one datagram.
+//!
+//! Receive buffers are sized for small lockstep messages, and datagrams much
+//! larger than the path MTU are fragmented by IP — losing one IP fragment
+//! loses the whole datagram. Games with bigger payloads instead split the
+//! serialized bytes with a [`Fragmenter`] and put them back together with a
+//! [`Reassembler`]. Each fragment carries a small header:
+//!
+//! ```text
+//! message id   u16 LE   same for every fragment of one message
+//! index        u8       0-based position of this fragment
+//! count        u8       total fragments in the message
+//! chunk        ...      up to mtu - HEADER_LEN bytes
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+
+pub const HEADER_LEN: usize = 4;
+/// Fits comfortably in the relay's and clients' receive buffers.
+pub const DEFAULT_MTU: usize = 1024;
+/// Messages still missing fragments; beyond this the oldest is dropped.
+pub const MAX_PENDING: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FragmentError {
+    /// The message needs more than 255 fragments at this MTU.
+    TooLarge { len: usize, mtu: usize },
+    /// The MTU leaves no room for data after the header.
+    MtuTooSmall(usize),
+}
+
+impl std::fmt::Display for FragmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FragmentError::TooLarge { len, mtu } => {
+                write!(f, "{len} bytes needs more than {} fragments at MTU {mtu}", u8::MAX)
+            }
+            FragmentError::MtuTooSmall(mtu) => {
+                write!(f, "MTU {mtu} does not fit the {HEADER_LEN}-byte fragment header")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FragmentError {}
+
+/// Splits messages into fragments of at most `mtu` bytes, numbering each
+/// message so the receiver can tell them apart.
+#[derive(Debug, Clone)]
+pub struct Fragmenter {
+    mtu: usize,
+    next_id: u16,
+}
+
+impl Default for Fragmenter {
+    fn default() -> Self {
+        Self::new(DEFAULT_MTU)
+    }
+}
+
+impl Fragmenter {
+    pub fn new(mtu: usize) -> Self {
+        Self { mtu, next_id: 0 }
+    }
+
+    pub fn split(&mut self, message: &[u8]) -> Result<Vec<Vec<u8>>, FragmentError> {
+        let chunk_len = self
+            .mtu
+            .checked_sub(HEADER_LEN)
+            .filter(|len| *len > 0)
+            .ok_or(FragmentError::MtuTooSmall(self.mtu))?;
+        // An empty message still needs one fragment to arrive at all.
+        let count = message.len().div_ceil(chunk_len).max(1);
+        let count = u8::try_from(count).map_err(|_| FragmentError::TooLarge {
+            len: message.len(),
+            mtu: self.mtu,
+        })?;
+
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+
+        let chunks: Vec<&[u8]> = if message.is_empty() {
+            vec![message]
+        } else {
+            message.chunks(chunk_len).collect()
+        };
+        Ok(chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                let mut fragment = Vec::with_capacity(HEADER_LEN + chunk.len());
+                fragment.extend_from_slice(&id.to_le_bytes());
+                fragment.push(index as u8);
+                fragment.push(count);
+                fragment.extend_from_slice(chunk);
+                fragment
+            })
+            .collect())
+    }
+}
+
+struct Partial {
+    parts: Vec<Option<Vec<u8>>>,
+    received: usize,
+}
+
+/// Collects fragments from one sender and yields each message once all of
+/// its fragments have arrived, in any order. Duplicates are ignored.
+#[derive(Default)]
+pub struct Reassembler {
+    pending: HashMap<u16, Partial>,
+    /// Message ids in arrival order, for evicting the oldest.
+    order: VecDeque<u16>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of messages still waiting for fragments.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Add one fragment. Returns the whole message when this completes it,
+    /// `None` if more fragments are needed or the fragment is malformed.
+    pub fn accept(&mut self, fragment: &[u8]) -> Option<Vec<u8>> {
+        if fragment.len() < HEADER_LEN {
+            return None;
+        }
+        let id = u16::from_le_bytes([fragment[0], fragment[1]]);
+        let index = fragment[2] as usize;
+        let count = fragment[3] as usize;
+        if count == 0 || index >= count {
+            return None;
+        }
+        let chunk = &fragment[HEADER_LEN..];
+
+        if count == 1 {
+            return Some(chunk.to_vec());
+        }
+
+        if !self.pending.contains_key(&id) {
+            if self.pending.len() >= MAX_PENDING
+                && let Some(oldest) = self.order.pop_front()
+            {
+                self.pending.remove(&oldest);
+            }
+            self.pending.insert(
+                id,
+                Partial {
+                    parts: vec![None; count],
+                    received: 0,
+                },
+            );
+            self.order.push_back(id);
+        }
+
+        let partial = self.pending.get_mut(&id)?;
+        if partial.parts.len() != count {
+            // Same id, different shape: an old message whose id wrapped around.
+            return None;
+        }
+        if partial.parts[index].is_none() {
+            partial.parts[index] = Some(chunk.to_vec());
+            partial.received += 1;
+        }
+        if partial.received < count {
+            return None;
+        }
+
+        let partial = self.pending.remove(&id)?;
+        self.order.retain(|pending| *pending != id);
+        Some(partial.parts.into_iter().flatten().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    #[test]
+    fn small_message_is_one_fragment() {
+        // given a message that fits in one fragment
+        let mut fragmenter = Fragmenter::new(64);
+        let fragments = fragmenter.split(&message(10)).unwrap();
+
+        // when it is received
+        let mut reassembler = Reassembler::new();
+
+        // then it comes back whole immediately
+        assert_eq!(fragments.len(), 1);
+        assert_eq!(reassembler.accept(&fragments[0]), Some(message(10)));
+    }
+
+    #[test]
+    fn fragments_reassemble_in_any_order() {
+        // given a message split into several fragments
+        let mut fragmenter = Fragmenter::new(64);
+        let mut fragments = fragmenter.split(&message(200)).unwrap();
+        assert_eq!(fragments.len(), 4);
+        assert!(fragments.iter().all(|fragment| fragment.len() <= 64));
+
+        // when they arrive reversed, with a duplicate
+        fragments.reverse();
+        fragments.insert(1, fragments[0].clone());
+        let mut reassembler = Reassembler::new();
+        let results: Vec<Option<Vec<u8>>> =
+            fragments.iter().map(|fragment| reassembler.accept(fragment)).collect();
+
+        // then only the last one completes the message
+        assert!(results[..results.len() - 1].iter().all(Option::is_none));
+        assert_eq!(results.last().unwrap().as_deref(), Some(message(200).as_slice()));
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn interleaved_messages_stay_separate() {
+        // given two messages split by the same fragmenter
+        let mut fragmenter = Fragmenter::new(16);
+        let first = fragmenter.split(&[1; 20]).unwrap();
+        let second = fragmenter.split(&[2; 20]).unwrap();
+
+        // when their fragments interleave
+        let mut reassembler = Reassembler::new();
+        assert_eq!(reassembler.accept(&first[0]), None);
+        assert_eq!(reassembler.accept(&second[0]), None);
+
+        // then each completes on its own
+        assert_eq!(reassembler.accept(&second[1]), Some(vec![2; 20]));
+        assert_eq!(reassembler.accept(&first[1]), Some(vec![1; 20]));
+    }
+
+    #[test]
+    fn oldest_incomplete_message_is_evicted() {
+        // given more incomplete messages than the reassembler keeps
+        let mut fragmenter = Fragmenter::new(16);
+        let messages: Vec<Vec<Vec<u8>>> =
+            (0..=MAX_PENDING).map(|_| fragmenter.split(&[0; 20]).unwrap()).collect();
+        let mut reassembler = Reassembler::new();
+        for fragments in &messages {
+            reassembler.accept(&fragments[0]);
+        }
+
+        // when the first message's last fragment finally arrives
+        let late = reassembler.accept(&messages[0][1]);
+
+        // then it was already given up on
+        assert_eq!(late, None);
+        assert_eq!(reassembler.pending(), MAX_PENDING);
+    }
+
+    #[test]
+    fn oversized_message_is_refused() {
+        let mut fragmenter = Fragmenter::new(HEADER_LEN + 1);
+        assert!(matches!(
+            fragmenter.split(&message(300)),
+            Err(FragmentError::TooLarge { len: 300, .. })
+        ));
+    }
+
+    #[test]
+    fn malformed_fragment_is_ignored() {
+        let mut reassembler = Reassembler::new();
+        assert_eq!(reassembler.accept(&[0, 0]), None);
+        // index past count
+        assert_eq!(reassembler.accept(&[0, 0, 3, 2, 9]), None);
+    }
+}
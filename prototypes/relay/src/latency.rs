@@ -0,0 +1,124 @@
+//! Round-trip time estimates, measured from traffic the relay already sends.
+//!
+//! Every `TickInputs` broadcast is answered by an `AckTick`, so the relay
+//! gets an RTT sample per player per tick without any extra pings. Samples are only taken from broadcasts that
+//! were never retransmitted: an ack after a retransmit could be answering
+//! either copy (Karn's algorithm).
+//!
+//! A room's expected ping is the median of its players' smoothed RTTs, which
+//! a room browser can sort by without any geolocation service. How much each
+//! player's RTT swings from sample to sample is its jitter.
+
+use std::time::Duration;
+
+/// Weight of a new sample in the smoothed RTT, as in TCP (RFC 6298).
+const SMOOTHING: f64 = 1.0 / 8.0;
+/// Weight of a new sample's deviation in the jitter, also from RFC 6298.
+const JITTER_SMOOTHING: f64 = 1.0 / 4.0;
+
+/// One client's smoothed RTT and jitter.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RttEstimator {
+    smoothed: Option<Duration>,
+    jitter: Option<Duration>,
+    samples: u32,
+}
+
+impl RttEstimator {
+    pub fn sample(&mut self, rtt: Duration) {
+        match self.smoothed {
+            None => {
+                self.smoothed = Some(rtt);
+                self.jitter = Some(rtt / 2);
+            }
+            Some(smoothed) => {
+                let deviation = smoothed.abs_diff(rtt);
+                let jitter = self.jitter.unwrap_or_default();
+                self.jitter = Some(
+                    jitter.mul_f64(1.0 - JITTER_SMOOTHING) + deviation.mul_f64(JITTER_SMOOTHING),
+                );
+                self.smoothed = Some(smoothed.mul_f64(1.0 - SMOOTHING) + rtt.mul_f64(SMOOTHING));
+            }
+        }
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    /// `None` until the first sample.
+    pub fn smoothed(&self) -> Option<Duration> {
+        self.smoothed
+    }
+
+    /// The smoothed deviation of samples from the smoothed RTT (RTTVAR in
+    /// RFC 6298). `None` until the first sample.
+    pub fn jitter(&self) -> Option<Duration> {
+        self.jitter
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+}
+
+/// The median of `rtts`, or `None` if there are none. With an even count,
+/// the mean of the middle two.
+pub fn median(rtts: impl IntoIterator<Item = Duration>) -> Option<Duration> {
+    let mut rtts: Vec<Duration> = rtts.into_iter().collect();
+    rtts.sort_unstable();
+    let middle = rtts.len() / 2;
+    match rtts.len() {
+        0 => None,
+        len if len % 2 == 1 => Some(rtts[middle]),
+        _ => Some((rtts[middle - 1] + rtts[middle]) / 2),
+    }
+}
+
+/// Whole milliseconds, saturating, for putting on the wire.
+pub fn as_millis_u32(rtt: Duration) -> u32 {
+    u32::try_from(rtt.as_millis()).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn first_sample_is_taken_as_is_then_smoothed() {
+        // given an estimator with one 80 ms sample
+        let mut rtt = RttEstimator::default();
+        rtt.sample(ms(80));
+        assert_eq!(rtt.smoothed(), Some(ms(80)));
+
+        // when a 160 ms spike arrives
+        rtt.sample(ms(160));
+
+        // then it moves the estimate by an eighth of the difference
+        assert_eq!(rtt.smoothed(), Some(ms(90)));
+        assert_eq!(rtt.samples(), 2);
+    }
+
+    #[test]
+    fn jitter_follows_how_far_samples_stray() {
+        // given an estimator whose first 80 ms sample sets the jitter to half
+        let mut rtt = RttEstimator::default();
+        assert_eq!(rtt.jitter(), None);
+        rtt.sample(ms(80));
+        assert_eq!(rtt.jitter(), Some(ms(40)));
+
+        // when a sample lands 80 ms off the estimate
+        rtt.sample(ms(160));
+
+        // then the jitter moves a quarter of the way toward that deviation
+        assert_eq!(rtt.jitter(), Some(ms(50)));
+    }
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(median([]), None);
+        assert_eq!(median([ms(30), ms(10), ms(20)]), Some(ms(20)));
+        assert_eq!(median([ms(40), ms(10), ms(20), ms(30)]), Some(ms(25)));
+    }
+}
@@ -2,36 +2,709 @@
 //!
 //! Both the relay server and game clients depend on this crate.
 //! Messages are serialized with `postcard` (compact, serde-based, no framing
-//! needed since UDP is message-oriented).
+//! needed since UDP is message-oriented). The `compression` feature adds lz4
+//! compression for large messages.
+//!
+//! Messages are generic over the input payload. The relay uses the default,
+//! opaque `Vec<u8>`; game clients use [`Payload<I>`] for their own
+//! [`LockstepInput`] type. Both encode identically on the wire, so the relay
+//! never needs to know a game's input type.
+//!
+//! Messages too large for one datagram can be split with [`frag`]. Relays on
+//! the local network can be found with [`discovery`].
+
+pub mod auth;
+pub mod discovery;
+pub mod frag;
+pub mod latency;
+pub mod metrics;
+pub mod netsim;
+pub mod priority;
+pub mod ratelimit;
+pub mod replay;
+pub mod results;
+pub mod rooms;
+pub mod sequence;
+pub mod selftest;
+pub mod server;
+pub mod sockets;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+pub mod ticklog;
+pub mod tuning;
 
-use serde::{Deserialize, Serialize};
+#[cfg(test)]
+mod proptests;
+#[cfg(test)]
+mod state_machine_tests;
+
+use arcade_buildinfo::BuildInfo;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 pub type Tick = u32;
 pub type PlayerSlot = u8;
 
+/// Who a spectator is in messages that name a slot, such as
+/// [`RelayMessage::SnapshotRequested`]. Never a player's.
+pub const SPECTATOR_SLOT: PlayerSlot = PlayerSlot::MAX;
+
+/// The longest [`ClientMessage::Chat`] line the relay passes on, in
+/// characters; it cuts longer ones short.
+pub const MAX_CHAT_LEN: usize = 200;
+
+/// Bumped whenever a message changes in a way older peers cannot decode.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// This crate's part of a `--version` report: its own version, the wire and
+/// replay formats it speaks, and whether compression is compiled in.
+pub fn describe_build(info: BuildInfo) -> BuildInfo {
+    info.with_crate("prototype-relay", env!("CARGO_PKG_VERSION"))
+        .with_protocol("lockstep", PROTOCOL_VERSION)
+        .with_protocol("replay", replay::FORMAT_VERSION)
+        .with_feature("compression", cfg!(feature = "compression"))
+}
+
+// ---- Typed input payloads ---------------------------------------------------
+
+/// One player's input for one tick, as a game defines it.
+pub trait LockstepInput: Serialize + DeserializeOwned + Clone + Default + std::fmt::Debug {}
+
+/// A typed input carried where the relay sees opaque bytes. Serializes as the
+/// postcard bytes of `I`, length-prefixed exactly like a `Vec<u8>`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Payload<I>(pub I);
+
+impl<I: Serialize> Serialize for Payload<I> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes = postcard::to_allocvec(&self.0).map_err(serde::ser::Error::custom)?;
+        bytes.serialize(serializer)
+    }
+}
+
+/// No input encodes as zero bytes except `()`, whose default it is, so empty
+/// bytes are read as the default input: what the relay sends for a player
+/// that missed the tick deadline.
+impl<'de, I: DeserializeOwned + Default> Deserialize<'de> for Payload<I> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        if bytes.is_empty() {
+            return Ok(Payload(I::default()));
+        }
+        postcard::from_bytes(&bytes)
+            .map(Payload)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
 // ---- Client -> Relay --------------------------------------------------------
 
-#[derive(Debug, Serialize, Deserialize)]
-pub enum ClientMessage {
-    Hello,
-    Input { tick: Tick, payload: Vec<u8> },
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ClientMessage<P = Vec<u8>> {
+    /// `game_id` names the game the client is running (e.g. `"net_pong"`);
+    /// the relay only seats players running the same game together. `name`
+    /// is shown to the other players. `room` names a standing room to join;
+    /// `None` joins whatever unnamed match is forming.
+    Hello {
+        game_id: String,
+        name: String,
+        room: Option<String>,
+    },
+    Input { tick: Tick, payload: P },
+    /// The client received `TickInputs` for `tick`, and so every tick before
+    /// it. Until every player acks a tick, the relay keeps re-sending it.
+    AckTick { tick: Tick },
+    /// The client missed `TickInputs` for `tick`; the relay sends it again if
+    /// the tick is still in its history.
+    RequestTick { tick: Tick },
+    /// The client is leaving; the relay frees its slot right away.
+    Goodbye,
+    /// The client is ending the match and leaving, for `reason`. The other
+    /// players get a `MatchAborted` instead of a bare `PlayerLeft`.
+    Abort { reason: AbortReason },
+    /// Change a standing room. Answered with `RoomAdminResult`.
+    RoomAdmin {
+        room: String,
+        owner_secret: String,
+        command: rooms::RoomCommand,
+    },
+    /// Ask for the standing rooms. Answered with `RoomList`.
+    ListRooms,
+    /// Inputs for consecutive ticks starting at `first_tick`, in one datagram.
+    /// Clients on connections where per-packet overhead dominates send these
+    /// ahead of time instead of one `Input` per tick; the relay holds them
+    /// within its input window.
+    InputBatch { first_tick: Tick, payloads: Vec<P> },
+    /// Take back `slot` in the standing room `room` after the relay lost the
+    /// match (it restarted, or the room was reset), instead of starting over
+    /// from tick 0. `next_tick` is the first tick the client has not played.
+    /// Sent in place of `Hello`; once every slot is back, the relay answers
+    /// with `Resumed`.
+    Resume {
+        game_id: String,
+        name: String,
+        room: String,
+        slot: PlayerSlot,
+        next_tick: Tick,
+    },
+    /// The score by slot, as the client's simulation has it. Sent whenever it
+    /// changes and before leaving; the relay only keeps the latest, for its
+    /// results log.
+    Scores { scores: Vec<u32> },
+    /// A hash of the client's game state once `tick` has been played, sent
+    /// every so often during a match. The relay passes it on to the other
+    /// players as [`RelayMessage::StateHash`], who compare it with their own
+    /// to catch a desync.
+    StateHash { tick: Tick, hash: u64 },
+    /// The client's game state after `tick`, as text, sent once after its
+    /// hashes stopped matching. The relay passes it on as
+    /// [`RelayMessage::StateDump`] so the other players can write both states
+    /// side by side, and dumps the room's tick log.
+    StateDump { tick: Tick, state: String },
+    /// The client's simulation is stuck or out of sync, and it wants another
+    /// player's state to carry on from. The relay passes it on to the other
+    /// players as [`RelayMessage::SnapshotRequested`].
+    RequestSnapshot,
+    /// The client's whole game state at the start of `tick`, serialized by
+    /// the game, in answer to `SnapshotRequested`. The relay passes it on as
+    /// [`RelayMessage::Snapshot`].
+    Snapshot { tick: Tick, state: Vec<u8> },
+    /// Stop the match before `tick`, the first tick the client has not sent
+    /// input for, until a player sends `Unpause`. Answered to every player
+    /// with `Paused`; repeated until that arrives.
+    Pause { tick: Tick },
+    /// Carry on after a `Pause`. Repeated until `Unpaused` arrives.
+    Unpause,
+    /// The client's game is over and it wants another with the same
+    /// players. The others hear `RematchRequested`; once every player has
+    /// asked, the relay starts the lockstep again from tick 0 and sends
+    /// everyone `RematchStart`. Repeated until that arrives.
+    Rematch,
+    /// Watch a match without playing in it: the one in standing room
+    /// `room`, or with `None` the longest running match of `game_id`. The
+    /// relay answers with `Spectating`, the roster, and from then on every
+    /// `TickInputs` and `RematchStart`. A spectator starts from a snapshot,
+    /// which it asks the players for with `RequestSnapshot`, and fills gaps
+    /// with `RequestTick`. Repeated, like Hello, for as long as it watches;
+    /// it leaves with Goodbye.
+    Spectate { game_id: String, room: Option<String> },
+    /// A line of chat for everyone else in the match, spectators included.
+    /// The relay passes it on as [`RelayMessage::Chat`], cut to
+    /// [`MAX_CHAT_LEN`]. Sent once; a lost line stays lost.
+    Chat { text: String },
 }
 
 // ---- Relay -> Client --------------------------------------------------------
 
-#[derive(Debug, Serialize, Deserialize)]
-pub enum RelayMessage {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RelayMessage<P = Vec<u8>> {
     Welcome { player_slot: PlayerSlot },
     GameStart,
-    TickInputs { tick: Tick, inputs: Vec<Vec<u8>> },
+    /// Every player's input for `tick`, by slot. An empty input is one the
+    /// relay filled in after its tick deadline; [`Payload`] reads it as the
+    /// game's default input.
+    TickInputs { tick: Tick, inputs: Vec<P> },
+    /// The relay refused the client's Hello. Clients should stop retrying.
+    Rejected { reason: RejectReason },
+    /// Non-gameplay settings pushed by the relay mid-session. Later values
+    /// for the same key replace earlier ones. See [`config_keys`].
+    Config { entries: Vec<(String, ConfigValue)> },
+    /// Every slot is taken; the client is waiting in line. `position` is
+    /// 1-based and re-sent periodically until the client is admitted.
+    QueuePosition { position: u32 },
+    /// A player took `slot`. Sent to everyone seated, including the new
+    /// player, who also receives one for each player already seated.
+    PlayerJoined { slot: PlayerSlot, name: String },
+    /// The player in `slot` left. If the game had started, the session is
+    /// over and the relay has dropped every player.
+    PlayerLeft { slot: PlayerSlot },
+    /// The player in `by_slot` ended the match for `reason`. The session is
+    /// over and the relay has dropped every player.
+    MatchAborted { by_slot: PlayerSlot, reason: AbortReason },
+    /// Reply to `RoomAdmin`: `None` on success, otherwise why it failed.
+    RoomAdminResult { error: Option<String> },
+    /// Reply to `ListRooms`, in name order.
+    RoomList { rooms: Vec<rooms::RoomInfo> },
+    /// The relay is going away. Sent to every client it knows of on the way
+    /// out; nothing more will come.
+    ServerShutdown,
+    /// Every player of a lost match is back, and play continues from `tick`:
+    /// the earliest tick any of them still needs. Sent in place of
+    /// `GameStart` to clients that sent `Resume`. Each client re-sends its own
+    /// inputs from `tick`, the same ones it sent before, and ignores
+    /// broadcasts for ticks it has already played.
+    Resumed { tick: Tick },
+    /// How each seated player's connection looks from the relay, sent about
+    /// once a second while a match runs, so clients can show whose link is
+    /// holding things up.
+    NetStats { players: Vec<PlayerNetStats> },
+    /// The player in `slot` hashed its state after `tick` to `hash`. See
+    /// [`ClientMessage::StateHash`].
+    StateHash { slot: PlayerSlot, tick: Tick, hash: u64 },
+    /// The player in `slot` found a desync, and this was its state after
+    /// `tick`. See [`ClientMessage::StateDump`].
+    StateDump { slot: PlayerSlot, tick: Tick, state: String },
+    /// The player in `slot` wants a snapshot. See
+    /// [`ClientMessage::RequestSnapshot`].
+    SnapshotRequested { slot: PlayerSlot },
+    /// The player in `slot`'s game state at the start of `tick`. See
+    /// [`ClientMessage::Snapshot`].
+    Snapshot {
+        slot: PlayerSlot,
+        tick: Tick,
+        state: Vec<u8>,
+    },
+    /// The player in `by_slot` paused the match before `tick`: nobody plays
+    /// `tick` until `Unpaused`, because the player who paused sends no input
+    /// for it and the relay fills none in. `tick` is later than the one asked for if the relay had
+    /// already played it, and stays put while the match is paused.
+    Paused { by_slot: PlayerSlot, tick: Tick },
+    /// The player in `by_slot` carried on after a pause.
+    Unpaused { by_slot: PlayerSlot },
+    /// The player in `slot` wants a rematch. See [`ClientMessage::Rematch`].
+    RematchRequested { slot: PlayerSlot },
+    /// Every player asked for a rematch. The next `TickInputs` is for tick 0
+    /// of a new game with the same players; nothing from the old one will
+    /// be re-sent.
+    RematchStart,
+    /// You are watching a match that is on `tick`; broadcasts from there on
+    /// will reach you. See [`ClientMessage::Spectate`].
+    Spectating { tick: Tick },
+    /// The player in `slot` said `text`. See [`ClientMessage::Chat`].
+    Chat { slot: PlayerSlot, text: String },
+}
+
+/// One player's connection in a [`RelayMessage::NetStats`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerNetStats {
+    pub slot: PlayerSlot,
+    /// Datagrams the relay accepted from the player since it was seated.
+    pub packets_received: u32,
+    /// How long ago the relay last heard from the player.
+    pub last_seen_ms: u32,
+    /// Smoothed round-trip time; `None` until the first measurement.
+    pub rtt_ms: Option<u32>,
+    /// How much the round-trip time swings; `None` until the first measurement.
+    pub jitter_ms: Option<u32>,
+}
+
+/// A typed value in a [`RelayMessage::Config`] entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ConfigValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl ConfigValue {
+    /// Parse operator-typed text: `true`/`false`, then integer, then float,
+    /// falling back to text.
+    pub fn parse(text: &str) -> Self {
+        if let Ok(b) = text.parse::<bool>() {
+            ConfigValue::Bool(b)
+        } else if let Ok(i) = text.parse::<i64>() {
+            ConfigValue::Int(i)
+        } else if let Ok(f) = text.parse::<f64>() {
+            ConfigValue::Float(f)
+        } else {
+            ConfigValue::Text(text.to_string())
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigValue::Bool(b) => write!(f, "{b}"),
+            ConfigValue::Int(i) => write!(f, "{i}"),
+            ConfigValue::Float(x) => write!(f, "{x}"),
+            ConfigValue::Text(t) => f.write_str(t),
+        }
+    }
+}
+
+/// Config keys clients know about. Clients ignore keys they do not recognize.
+pub mod config_keys {
+    /// `Bool`: whether clients should collect and report metrics.
+    pub const METRICS_ENABLED: &str = "metrics.enabled";
+    /// `Int`: how long to wait before retransmitting, in milliseconds.
+    pub const RETRANSMIT_INTERVAL_MS: &str = "retransmit.interval_ms";
+    /// `Int`: the relay will shut down this many seconds after the message.
+    pub const SHUTDOWN_COUNTDOWN_SECS: &str = "shutdown.countdown_secs";
+}
+
+/// Why the relay refused a client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RejectReason {
+    /// Every player slot is taken and the wait queue is full too.
+    Full,
+    /// The client speaks a protocol version the relay does not.
+    BadVersion,
+    /// The requested room does not exist or cannot be joined.
+    BadRoom,
+    /// The client's address is banned from this relay.
+    Banned,
+    /// The session is already running a different game.
+    WrongGame,
+    /// The relay operator removed the client.
+    Kicked,
+    /// A spectator asked to watch, but no match it may watch is running.
+    NoMatch,
+    /// The relay reclaimed the client's room as idle.
+    Idle,
+}
+
+impl std::fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            RejectReason::Full => "game is full",
+            RejectReason::BadVersion => "incompatible game version",
+            RejectReason::BadRoom => "room not available",
+            RejectReason::Banned => "banned from this relay",
+            RejectReason::WrongGame => "relay is hosting a different game",
+            RejectReason::Kicked => "removed by the relay operator",
+            RejectReason::NoMatch => "no match to watch",
+            RejectReason::Idle => "room closed after going quiet",
+        };
+        f.write_str(text)
+    }
+}
+
+/// Why a player ended a match early, so the others (and the relay's log) can
+/// tell a rage quit from a crash rather than everything looking like a timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AbortReason {
+    /// The player quit mid-match.
+    RageQuit,
+    /// The player's controller disconnected and the game cannot continue.
+    ControllerDisconnected,
+    /// The player's simulation no longer matches the others'.
+    Desync,
+    /// The players turned out to be running different game versions.
+    VersionMismatch,
+}
+
+impl std::fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            AbortReason::RageQuit => "quit",
+            AbortReason::ControllerDisconnected => "controller disconnected",
+            AbortReason::Desync => "games out of sync",
+            AbortReason::VersionMismatch => "different game versions",
+        };
+        f.write_str(text)
+    }
 }
 
 // ---- Serialization helpers --------------------------------------------------
+//
+// Every datagram starts with a one-byte header saying whether the rest is
+// compressed. The header is always present so builds with and without the
+// `compression` feature can talk to each other; a build without the feature
+// simply never compresses and rejects compressed datagrams it cannot read.
+
+const HEADER_UNCOMPRESSED: u8 = 0;
+const HEADER_LZ4: u8 = 1;
+
+/// Serialized bodies smaller than this are sent as-is; compression would only
+/// add overhead.
+#[cfg(feature = "compression")]
+const COMPRESSION_THRESHOLD: usize = 128;
 
 pub fn serialize<T: Serialize>(value: &T) -> Vec<u8> {
-    postcard::to_allocvec(value).expect("serialization should not fail")
+    let body = postcard::to_allocvec(value).expect("serialization should not fail");
+    frame(body)
+}
+
+/// Whether `bytes` could be a serialized message at all: a known header and
+/// a body. Costs nothing next to decoding, so the relay checks it before
+/// spending any effort on a datagram.
+pub fn plausible_frame(bytes: &[u8]) -> bool {
+    matches!(bytes, [HEADER_UNCOMPRESSED | HEADER_LZ4, _, ..])
 }
 
 pub fn deserialize<T: for<'a> Deserialize<'a>>(bytes: &[u8]) -> Option<T> {
-    postcard::from_bytes(bytes).ok()
+    let (&header, body) = bytes.split_first()?;
+    match header {
+        HEADER_UNCOMPRESSED => postcard::from_bytes(body).ok(),
+        HEADER_LZ4 => postcard::from_bytes(&decompress(body)?).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "compression")]
+fn frame(body: Vec<u8>) -> Vec<u8> {
+    if body.len() >= COMPRESSION_THRESHOLD {
+        let compressed = lz4_flex::compress_prepend_size(&body);
+        if compressed.len() < body.len() {
+            return with_header(HEADER_LZ4, &compressed);
+        }
+    }
+    with_header(HEADER_UNCOMPRESSED, &body)
+}
+
+#[cfg(not(feature = "compression"))]
+fn frame(body: Vec<u8>) -> Vec<u8> {
+    with_header(HEADER_UNCOMPRESSED, &body)
+}
+
+fn with_header(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(body.len() + 1);
+    framed.push(header);
+    framed.extend_from_slice(body);
+    framed
+}
+
+/// No real message comes close; refusing larger claims stops a forged size
+/// prefix from making the receiver allocate gigabytes.
+#[cfg(feature = "compression")]
+const MAX_DECOMPRESSED_LEN: usize = 64 * 1024;
+
+#[cfg(feature = "compression")]
+fn decompress(body: &[u8]) -> Option<Vec<u8>> {
+    let size: [u8; 4] = body.get(..4)?.try_into().ok()?;
+    if u32::from_le_bytes(size) as usize > MAX_DECOMPRESSED_LEN {
+        return None;
+    }
+    lz4_flex::decompress_size_prepended(body).ok()
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(_body: &[u8]) -> Option<Vec<u8>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn forged_decompressed_size_is_rejected() {
+        let bytes = [HEADER_LZ4, 0xFF, 0xFF, 0xFF, 0xFF];
+        assert!(deserialize::<ClientMessage>(&bytes).is_none());
+    }
+
+    #[test]
+    fn round_trip_small_message() {
+        let msg: ClientMessage = ClientMessage::Input { tick: 7, payload: vec![1, 2, 3] };
+        let bytes = serialize(&msg);
+        assert_eq!(bytes[0], HEADER_UNCOMPRESSED);
+        let decoded: ClientMessage = deserialize(&bytes).unwrap();
+        assert!(matches!(decoded, ClientMessage::Input { tick: 7, ref payload } if payload == &[1, 2, 3]));
+    }
+
+    #[test]
+    fn round_trip_large_message() {
+        let msg: RelayMessage = RelayMessage::TickInputs { tick: 1, inputs: vec![vec![0; 600], vec![0; 600]] };
+        let bytes = serialize(&msg);
+        let decoded: RelayMessage = deserialize(&bytes).unwrap();
+        assert!(matches!(decoded, RelayMessage::TickInputs { tick: 1, ref inputs } if inputs[1].len() == 600));
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn large_repetitive_message_is_compressed() {
+        let msg: RelayMessage = RelayMessage::TickInputs { tick: 1, inputs: vec![vec![0; 600], vec![0; 600]] };
+        let bytes = serialize(&msg);
+        assert_eq!(bytes[0], HEADER_LZ4);
+        assert!(bytes.len() < 600);
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+    struct TestInput {
+        up: bool,
+        aim: i16,
+    }
+
+    impl LockstepInput for TestInput {}
+
+    #[test]
+    fn typed_payload_reads_as_opaque_bytes() {
+        // given a client message with a typed payload
+        let typed: ClientMessage<Payload<TestInput>> = ClientMessage::Input {
+            tick: 3,
+            payload: Payload(TestInput { up: true, aim: -5 }),
+        };
+
+        // when the relay decodes it with opaque payloads
+        let opaque: ClientMessage = deserialize(&serialize(&typed)).unwrap();
+
+        // then the bytes are the postcard encoding of the input
+        let ClientMessage::Input { tick, payload } = opaque else {
+            panic!("expected Input");
+        };
+        assert_eq!(tick, 3);
+        assert_eq!(
+            postcard::from_bytes::<TestInput>(&payload).unwrap(),
+            TestInput { up: true, aim: -5 }
+        );
+    }
+
+    #[test]
+    fn opaque_tick_inputs_read_as_typed() {
+        // given the relay's opaque broadcast of two inputs
+        let inputs = [TestInput { up: true, aim: 1 }, TestInput { up: false, aim: 2 }];
+        let opaque: RelayMessage = RelayMessage::TickInputs {
+            tick: 9,
+            inputs: inputs.iter().map(|i| postcard::to_allocvec(i).unwrap()).collect(),
+        };
+
+        // when a client decodes it with its input type
+        let typed: RelayMessage<Payload<TestInput>> = deserialize(&serialize(&opaque)).unwrap();
+
+        // then it gets the typed inputs back
+        let RelayMessage::TickInputs { tick, inputs: decoded } = typed else {
+            panic!("expected TickInputs");
+        };
+        assert_eq!(tick, 9);
+        assert_eq!(decoded, vec![Payload(inputs[0].clone()), Payload(inputs[1].clone())]);
+    }
+
+    #[test]
+    fn substituted_input_reads_as_the_default() {
+        // given a broadcast where the relay filled in slot 1's input
+        let played = TestInput { up: true, aim: 4 };
+        let opaque: RelayMessage = RelayMessage::TickInputs {
+            tick: 2,
+            inputs: vec![postcard::to_allocvec(&played).unwrap(), Vec::new()],
+        };
+
+        // when a client decodes it with its input type
+        let typed: RelayMessage<Payload<TestInput>> = deserialize(&serialize(&opaque)).unwrap();
+
+        // then the missing input is the game's default
+        let RelayMessage::TickInputs { inputs, .. } = typed else {
+            panic!("expected TickInputs");
+        };
+        assert_eq!(inputs, vec![Payload(played), Payload(TestInput::default())]);
+    }
+
+    // ---- Wire-format fixtures ----------------------------------------------
+
+    const HANDSHAKE_TRACE: &str = include_str!("../fixtures/handshake.trace");
+    const EXCHANGE_TRACE: &str = include_str!("../fixtures/exchange_100_ticks.trace");
+
+    /// Parse a trace into `(from_client, bytes)` datagrams.
+    fn parse_trace(trace: &str) -> Vec<(bool, Vec<u8>)> {
+        trace
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (direction, hex) = line.split_once(": ").expect("line has a direction");
+                let bytes = hex
+                    .split(' ')
+                    .map(|byte| u8::from_str_radix(byte, 16).expect("hex byte"))
+                    .collect();
+                (direction.starts_with("client"), bytes)
+            })
+            .collect()
+    }
+
+    fn assert_round_trips(trace: &str) {
+        for (line, (from_client, bytes)) in parse_trace(trace).into_iter().enumerate() {
+            let reencoded = if from_client {
+                let msg: sequence::Sequenced<ClientMessage> =
+                    deserialize(&bytes).unwrap_or_else(|| panic!("datagram {line} decodes"));
+                serialize(&msg)
+            } else {
+                let msg: sequence::Sequenced<RelayMessage> =
+                    deserialize(&bytes).unwrap_or_else(|| panic!("datagram {line} decodes"));
+                serialize(&msg)
+            };
+            assert_eq!(reencoded, bytes, "datagram {line} re-encodes byte-for-byte");
+        }
+    }
+
+    #[test]
+    fn handshake_fixture_round_trips() {
+        assert_round_trips(HANDSHAKE_TRACE);
+    }
+
+    #[test]
+    fn exchange_fixture_round_trips() {
+        assert_round_trips(EXCHANGE_TRACE);
+    }
+
+    #[test]
+    fn handshake_fixture_decodes_to_expected_messages() {
+        // given the handshake trace
+        let datagrams = parse_trace(HANDSHAKE_TRACE);
+
+        // when the relay's messages are decoded
+        let relay_messages: Vec<RelayMessage> = datagrams
+            .iter()
+            .filter(|(from_client, _)| !from_client)
+            .map(|(_, bytes)| deserialize::<sequence::Sequenced<RelayMessage>>(bytes).unwrap().message)
+            .collect();
+
+        // then both clients are welcomed into distinct slots, told about
+        // each other, then started
+        assert!(matches!(relay_messages[0], RelayMessage::Welcome { player_slot: 0 }));
+        assert!(matches!(relay_messages[2], RelayMessage::Welcome { player_slot: 1 }));
+        let joined = relay_messages
+            .iter()
+            .filter(|msg| matches!(msg, RelayMessage::PlayerJoined { .. }))
+            .count();
+        assert_eq!(joined, 4);
+        assert!(matches!(relay_messages[6], RelayMessage::GameStart));
+        assert!(matches!(relay_messages[7], RelayMessage::GameStart));
+
+        // and both clients asked for the same game
+        let (_, first_hello) = &datagrams[0];
+        let hello: sequence::Sequenced<ClientMessage> = deserialize(first_hello).unwrap();
+        assert!(matches!(
+            hello.message,
+            ClientMessage::Hello { ref game_id, ref name, room: None }
+                if game_id == "net_pong" && name == "alice"
+        ));
+    }
+
+    #[test]
+    fn exchange_fixture_covers_100_ticks() {
+        // given the exchange trace
+        let datagrams = parse_trace(EXCHANGE_TRACE);
+
+        // when the last relay broadcast is decoded as typed input
+        let (_, last) = datagrams
+            .iter()
+            .rev()
+            .find(|(from_client, _)| !from_client)
+            .unwrap();
+        let msg: sequence::Sequenced<RelayMessage<Payload<f32>>> = deserialize(last).unwrap();
+
+        // then it is tick 99 with one input per player
+        let RelayMessage::TickInputs { tick, inputs } = msg.message else {
+            panic!("expected TickInputs");
+        };
+        assert_eq!(tick, 99);
+        assert_eq!(inputs.len(), 2);
+        // each tick: two inputs, two broadcasts, two acks
+        assert_eq!(datagrams.len(), 600);
+    }
+
+    #[test]
+    fn config_value_parse() {
+        assert_eq!(ConfigValue::parse("true"), ConfigValue::Bool(true));
+        assert_eq!(ConfigValue::parse("250"), ConfigValue::Int(250));
+        assert_eq!(ConfigValue::parse("0.5"), ConfigValue::Float(0.5));
+        assert_eq!(ConfigValue::parse("soon"), ConfigValue::Text("soon".into()));
+    }
+
+    #[test]
+    fn empty_datagram_is_rejected() {
+        assert!(deserialize::<ClientMessage>(&[]).is_none());
+    }
+
+    #[test]
+    fn unknown_header_is_rejected() {
+        let mut bytes = serialize(&ClientMessage::<Vec<u8>>::Hello {
+            game_id: "test".into(),
+            name: "tester".into(),
+            room: None,
+        });
+        bytes[0] = 0xFF;
+        assert!(deserialize::<ClientMessage>(&bytes).is_none());
+    }
 }
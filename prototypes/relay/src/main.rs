@@ -1,153 +1,548 @@
 //! UDP relay server for deterministic lockstep multiplayer.
 //!
-//! Coordinates input exchange between two clients. The relay never interprets
-//! game-specific payload bytes — it only waits for both players to submit input
-//! for a tick, then broadcasts the combined inputs to both.
+//! Coordinates input exchange between clients. The relay never interprets
+//! game-specific payload bytes — it only waits for every player in a room to
+//! submit input for a tick, then broadcasts the combined inputs to them.
 //!
-//! Usage: `cargo run -p relay [bind_address]`
-//! Default bind address: `0.0.0.0:7700`
+//! One relay hosts many matches at once. Each match lives in its own room;
+//! a Hello joins a forming room for the same game, or opens a new room when
+//! every existing one is full. Clients only queue once `--room-capacity`
+//! rooms are open.
+//!
+//! The relay runs on tokio. This binary owns the sockets, stdin and the
+//! timers; everything it receives goes to a `prototype_relay::server::RelayCore`,
+//! which decides who sits where and runs each match as its own task.
+//!
+//! Usage: `cargo run -p relay -- [OPTIONS]`; `--help` lists them all. The
+//! `prototype_relay` module behind an option (`tuning` for `--config`,
+//! `results` for `--results-log`, and so on) says how it behaves. Operator
+//! commands are typed on stdin, one per line; `ConsoleCommand` lists them.
 
-use std::net::{SocketAddr, UdpSocket};
+use std::io::BufRead;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-use prototype_relay::{
-    ClientMessage, PlayerSlot, RelayMessage, Tick, deserialize, serialize,
+use prototype_relay::Tick;
+use prototype_relay::auth::SessionAuth;
+use prototype_relay::discovery::DISCOVERY_PORT;
+use prototype_relay::metrics::Metrics;
+use prototype_relay::netsim::{NetConditions, NetSim, SimulatedSocket};
+use prototype_relay::rooms::RoomDirectory;
+use prototype_relay::selftest::{self, SELF_TEST_TICKS};
+use prototype_relay::server::{
+    ConsoleCommand, QUEUE_UPDATE_INTERVAL, RelayCore, Settings, Socket, SystemClock,
+    parse_console_command,
 };
+use prototype_relay::sockets::UdpSockets;
+use prototype_relay::tuning::Tuning;
+#[cfg(feature = "websocket")]
+use prototype_relay::server::StreamEvent;
+use clap::Parser;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::mpsc;
+use tokio::time::{MissedTickBehavior, interval};
+use tracing::{info, warn};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{Registry, reload};
 
-const MAX_PLAYERS: usize = 2;
 const RECV_BUF_SIZE: usize = 1024;
+/// How often `--config` is checked for changes.
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
-struct RelayState {
-    players: [Option<SocketAddr>; MAX_PLAYERS],
-    game_started: bool,
-    current_tick: Tick,
-    tick_inputs: [Option<Vec<u8>>; MAX_PLAYERS],
-}
+/// Changes the log level of the running subscriber.
+type LogLevelHandle = reload::Handle<LevelFilter, Registry>;
 
-impl RelayState {
-    fn new() -> Self {
-        Self {
-            players: [None; MAX_PLAYERS],
-            game_started: false,
-            current_tick: 0,
-            tick_inputs: [None, None],
+/// Read operator commands from stdin on a background thread.
+fn spawn_console() -> mpsc::UnboundedReceiver<ConsoleCommand> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_console_command(&line) {
+                Ok(command) => {
+                    if sender.send(command).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => eprintln!("relay: {e}"),
+            }
         }
-    }
+    });
+    receiver
+}
 
-    fn find_player(&self, addr: &SocketAddr) -> Option<usize> {
-        self.players.iter().position(|slot| slot.as_ref() == Some(addr))
-    }
+/// When `path` was last written, if it can be read at all.
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
 
-    fn next_empty_slot(&self) -> Option<usize> {
-        self.players.iter().position(|slot| slot.is_none())
+/// Ask for a reload each time the config file's modification time changes,
+/// and on every SIGHUP where there is such a thing.
+fn watch_config(path: PathBuf) -> mpsc::UnboundedReceiver<()> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::hangup()) {
+            Ok(mut sighup) => {
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    while sighup.recv().await.is_some() && sender.send(()).is_ok() {}
+                });
+            }
+            Err(e) => warn!("cannot listen for SIGHUP: {e}"),
+        }
     }
+    tokio::spawn(async move {
+        let mut last = modified(&path);
+        let mut poll = interval(CONFIG_POLL_INTERVAL);
+        loop {
+            poll.tick().await;
+            let now = modified(&path);
+            if now != last {
+                last = now;
+                if sender.send(()).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    receiver
+}
 
-    fn all_slots_filled(&self) -> bool {
-        self.players.iter().all(|slot| slot.is_some())
+/// Read `path` again and apply it. A file that does not parse changes
+/// nothing.
+fn reload_config(path: &Path, core: &mut RelayCore, log_level: &LogLevelHandle) {
+    let tuning = match Tuning::load(path) {
+        Ok(tuning) => tuning,
+        Err(e) => {
+            warn!("not reloading {}: {e}", path.display());
+            return;
+        }
+    };
+    if let Some(level) = tuning.log_level() {
+        let _ = log_level.modify(|filter| *filter = LevelFilter::from_level(level));
     }
+    core.retune(&tuning);
+    info!("reloaded {}", path.display());
+}
 
-    fn all_inputs_received(&self) -> bool {
-        self.tick_inputs.iter().all(|input| input.is_some())
+/// Answer `GET /metrics` with the current metrics; anything else gets a 404.
+/// One request per connection, which is all a Prometheus scrape needs.
+async fn serve_metrics(listener: TcpListener, metrics: Arc<Metrics>) {
+    loop {
+        let Ok((mut stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let metrics = Arc::clone(&metrics);
+        tokio::spawn(async move {
+            let mut request = [0u8; 1024];
+            let Ok(len) = stream.read(&mut request).await else {
+                return;
+            };
+            let request = String::from_utf8_lossy(&request[..len]);
+            let response = if request.starts_with("GET /metrics ") {
+                let body = metrics.render();
+                format!(
+                    "HTTP/1.1 200 OK\r\n\
+                     Content-Type: text/plain; version=0.0.4\r\n\
+                     Content-Length: {}\r\n\
+                     Connection: close\r\n\r\n{body}",
+                    body.len()
+                )
+            } else {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_string()
+            };
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
     }
 }
 
-fn main() {
-    let bind_addr = std::env::args()
-        .nth(1)
-        .unwrap_or_else(|| "0.0.0.0:7700".into());
-
-    let socket = UdpSocket::bind(&bind_addr)
-        .unwrap_or_else(|e| panic!("failed to bind to {bind_addr}: {e}"));
-
-    println!("relay: listening on {bind_addr}");
-
-    let mut state = RelayState::new();
-    let mut buf = [0u8; RECV_BUF_SIZE];
+/// Accept WebSocket clients and run one task per connection, passing its
+/// binary messages to the dispatcher as datagrams and its replies back.
+///
+/// A WebSocket client speaks exactly what a UDP client does: each binary
+/// message carries one datagram, sequenced and sealed the same way. Rooms
+/// cannot tell the two apart; closing the connection counts as a Goodbye.
+#[cfg(feature = "websocket")]
+async fn serve_websocket(listener: TcpListener, events: mpsc::UnboundedSender<StreamEvent>) {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
 
     loop {
-        let (len, src) = match socket.recv_from(&mut buf) {
-            Ok(result) => result,
-            Err(e) => {
-                eprintln!("relay: recv error: {e}");
-                continue;
-            }
-        };
-
-        let Some(msg) = deserialize::<ClientMessage>(&buf[..len]) else {
-            eprintln!("relay: bad message from {src}");
+        let Ok((stream, addr)) = listener.accept().await else {
             continue;
         };
-
-        match msg {
-            ClientMessage::Hello => {
-                // Already connected? Re-send welcome.
-                if let Some(slot) = state.find_player(&src) {
-                    let welcome = serialize(&RelayMessage::Welcome {
-                        player_slot: slot as PlayerSlot,
-                    });
-                    let _ = socket.send_to(&welcome, src);
-                    if state.game_started {
-                        let start = serialize(&RelayMessage::GameStart);
-                        let _ = socket.send_to(&start, src);
+        let events = events.clone();
+        tokio::spawn(async move {
+            let socket = match tokio_tungstenite::accept_async(stream).await {
+                Ok(socket) => socket,
+                Err(e) => {
+                    warn!("WebSocket handshake with {addr} failed: {e}");
+                    return;
+                }
+            };
+            let (mut sink, mut incoming) = socket.split();
+            let (outgoing, mut replies) = mpsc::unbounded_channel();
+            if events.send(StreamEvent::Connected { addr, outgoing }).is_err() {
+                return;
+            }
+            loop {
+                tokio::select! {
+                    message = incoming.next() => match message {
+                        Some(Ok(Message::Binary(bytes))) => {
+                            let bytes = bytes.to_vec();
+                            let _ = events.send(StreamEvent::Datagram { addr, bytes });
+                        }
+                        // Text, ping and pong carry nothing for the relay.
+                        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                        Some(Ok(_)) => {}
+                    },
+                    Some(reply) = replies.recv() => {
+                        if sink.send(Message::binary(reply)).await.is_err() {
+                            break;
+                        }
                     }
-                    continue;
                 }
+            }
+            let _ = events.send(StreamEvent::Closed { addr });
+        });
+    }
+}
 
-                let Some(slot) = state.next_empty_slot() else {
-                    eprintln!("relay: rejected {src}, game is full");
-                    continue;
-                };
+/// Command-line options.
+#[derive(Parser, Debug)]
+#[command(name = "relay", about = "UDP relay server for deterministic lockstep multiplayer")]
+struct Args {
+    /// Address to listen on; repeat to listen on several. IPv6 addresses
+    /// also take IPv4 clients, so `[::]:7700` covers both.
+    #[arg(long, value_name = "ADDR", default_value = "0.0.0.0:7700")]
+    bind: Vec<SocketAddr>,
+    /// Players per match.
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u8).range(1..))]
+    players: u8,
+    /// Milliseconds to wait for a player to ack a tick before re-sending it.
+    #[arg(long, value_name = "MS", default_value_t = 50)]
+    tick_timeout: u64,
+    /// Milliseconds a tick waits on a missing player's input before playing
+    /// an empty one in its place.
+    #[arg(long, value_name = "MS")]
+    tick_deadline: Option<u64>,
+    /// Concurrent matches; beyond this, new clients wait in the queue.
+    #[arg(long, default_value_t = 64)]
+    room_capacity: usize,
+    /// Ticks past the current one a player may send input for; later input
+    /// is dropped.
+    #[arg(long, value_name = "TICKS", default_value_t = 3)]
+    input_window: Tick,
+    /// Broadcasts each room keeps so a client that missed one can ask again.
+    #[arg(long, value_name = "TICKS", default_value_t = 256)]
+    input_history: usize,
+    /// Datagrams per second each source may send; the excess is dropped.
+    #[arg(long, value_name = "N", default_value_t = 400)]
+    rate_limit: u32,
+    /// Datagrams a source may send at once before the rate limit applies.
+    #[arg(long, value_name = "N", default_value_t = 200)]
+    rate_burst: u32,
+    /// Most verbose messages to log: error, warn, info, debug, or trace.
+    #[arg(long, default_value = "info")]
+    log_level: tracing::Level,
+    /// Where standing rooms and tick logs are written.
+    #[arg(long, default_value = ".")]
+    data_dir: PathBuf,
+    /// Ticks per room to keep for post-mortems.
+    #[arg(long, value_name = "TICKS")]
+    tick_log: Option<usize>,
+    /// Seconds of silence before a seated player is dropped.
+    #[arg(long, value_name = "SECS", default_value_t = 10)]
+    player_timeout: u64,
+    /// Clients that may wait at once for a match to start, seated or
+    /// queued; more are turned away.
+    #[arg(long, value_name = "N", default_value_t = 64)]
+    max_pending: usize,
+    /// Seconds a room may go without hearing from any of its players before
+    /// it is closed.
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    room_ttl: u64,
+    /// Seconds a match waits for players before bots fill the empty seats.
+    #[arg(long, value_name = "SECS")]
+    bot_fill: Option<u64>,
+    /// Also accept clients over WebSocket on this address, e.g. `0.0.0.0:7702`.
+    #[cfg(feature = "websocket")]
+    #[arg(long, value_name = "ADDR")]
+    websocket: Option<String>,
+    /// Port to answer LAN discovery probes on.
+    #[arg(long, value_name = "PORT", default_value_t = DISCOVERY_PORT)]
+    discovery_port: u16,
+    /// Do not answer LAN discovery probes.
+    #[arg(long)]
+    no_discovery: bool,
+    /// Record every match's inputs as a replay file in this directory.
+    #[arg(long, value_name = "PATH")]
+    record_dir: Option<PathBuf>,
+    /// Append a line of JSON to this file for every match that ends.
+    #[arg(long, value_name = "PATH")]
+    results_log: Option<PathBuf>,
+    /// Serve Prometheus metrics over HTTP on this address, e.g. `127.0.0.1:9100`.
+    #[arg(long, value_name = "ADDR")]
+    metrics: Option<String>,
+    /// Delay and drop outgoing datagrams, e.g. `latency=80,jitter=20,loss=2%`.
+    #[arg(long, value_name = "CONDITIONS")]
+    simulate_net: Option<NetConditions>,
+    /// TOML file of settings to apply over these, reloaded when it changes
+    /// or on SIGHUP.
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Play a scripted match through a loopback relay with these settings,
+    /// and exit nonzero if it stalls or goes wrong.
+    #[arg(long)]
+    self_test: bool,
+    /// Print the build, protocol versions and features, and exit.
+    #[arg(long)]
+    version: bool,
+}
 
-                state.players[slot] = Some(src);
-                println!("relay: player {slot} connected from {src}");
+impl Args {
+    fn settings(&self) -> Settings {
+        Settings {
+            players: usize::from(self.players),
+            room_capacity: self.room_capacity,
+            tick_timeout: Duration::from_millis(self.tick_timeout.max(1)),
+            tick_deadline: self.tick_deadline.map(Duration::from_millis),
+            input_window: self.input_window,
+            input_history: self.input_history,
+            rate_limit: self.rate_limit,
+            rate_burst: self.rate_burst,
+            player_timeout: Duration::from_secs(self.player_timeout),
+            max_pending: self.max_pending,
+            room_ttl: Duration::from_secs(self.room_ttl),
+            bot_fill: self.bot_fill.map(Duration::from_secs),
+            tick_log: self.tick_log.map(|ticks| (ticks, self.data_dir.clone())),
+            record_dir: self.record_dir.clone(),
+            results_log: self.results_log.clone(),
+        }
+    }
+}
 
-                let welcome = serialize(&RelayMessage::Welcome {
-                    player_slot: slot as PlayerSlot,
-                });
-                let _ = socket.send_to(&welcome, src);
-
-                if state.all_slots_filled() && !state.game_started {
-                    state.game_started = true;
-                    println!("relay: all players connected, starting game");
-                    let start = serialize(&RelayMessage::GameStart);
-                    for addr in state.players.iter().flatten() {
-                        let _ = socket.send_to(&start, addr);
-                    }
-                }
+/// The next discovery probe, or never when discovery is off.
+async fn recv_probe(
+    socket: Option<&UdpSocket>,
+    buf: &mut [u8],
+) -> std::io::Result<(usize, SocketAddr)> {
+    match socket {
+        Some(socket) => socket.recv_from(buf).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM where there is such a thing. The relay
+/// then tells every client it knows of that it is shutting down, sends
+/// whatever is still queued, and exits.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("cannot listen for Ctrl-C: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                warn!("cannot listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
             }
-            ClientMessage::Input { tick, payload } => {
-                let Some(slot) = state.find_player(&src) else {
-                    eprintln!("relay: input from unknown client {src}");
-                    continue;
-                };
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        () = ctrl_c => {}
+        () = terminate => {}
+    }
+}
 
-                if tick != state.current_tick {
-                    // Ignore inputs for wrong tick (stale or future).
-                    continue;
-                }
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    if args.version {
+        println!("{}", prototype_relay::describe_build(arcade_buildinfo::build_info!()));
+        return;
+    }
+    let (level_filter, log_level) = reload::Layer::new(LevelFilter::from_level(args.log_level));
+    tracing_subscriber::registry()
+        .with(level_filter)
+        .with(tracing_subscriber::fmt::layer().with_target(false))
+        .init();
+    let directory_path = RoomDirectory::path_from_data_dir(&args.data_dir);
+    let mut settings = args.settings();
+    if let Some(path) = &args.config {
+        let tuning = Tuning::load(path)
+            .unwrap_or_else(|e| panic!("cannot load config {}: {e}", path.display()));
+        tuning.apply(&mut settings);
+        if let Some(level) = tuning.log_level() {
+            let _ = log_level.modify(|filter| *filter = LevelFilter::from_level(level));
+        }
+    }
+    if args.self_test {
+        match selftest::run(settings, SessionAuth::from_env(), SELF_TEST_TICKS).await {
+            Ok(report) => println!(
+                "relay: self-test passed: {} ticks in {:.2?}, slowest tick {:.2?}",
+                report.ticks, report.elapsed, report.slowest_tick
+            ),
+            Err(e) => {
+                eprintln!("relay: self-test failed: {e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let socket = UdpSockets::bind(&args.bind).unwrap_or_else(|e| {
+        let addrs: Vec<String> = args.bind.iter().map(ToString::to_string).collect();
+        panic!("failed to bind to {}: {e}", addrs.join(", "))
+    });
+    let socket = Arc::new(socket);
+    let bound: Vec<String> = socket.local_addrs().iter().map(ToString::to_string).collect();
+    let bind_addr = bound.join(", ");
+    // Discovery replies name one port; the first address is the one to find.
+    let relay_port = socket
+        .local_addrs()
+        .first()
+        .map(|addr| addr.port())
+        .unwrap_or_default();
 
-                state.tick_inputs[slot] = Some(payload);
+    // Another relay on this machine may already have the discovery port;
+    // this one still serves games, it just cannot be discovered.
+    let discovery = if args.no_discovery {
+        None
+    } else {
+        match UdpSocket::bind(("0.0.0.0", args.discovery_port)).await {
+            Ok(socket) => {
+                info!("answering LAN discovery on port {}", args.discovery_port);
+                Some(socket)
+            }
+            Err(e) => {
+                warn!("LAN discovery off: cannot bind port {}: {e}", args.discovery_port);
+                None
+            }
+        }
+    };
 
-                if state.all_inputs_received() {
-                    let inputs: Vec<Vec<u8>> = state
-                        .tick_inputs
-                        .iter()
-                        .map(|input| input.clone().unwrap())
-                        .collect();
+    let metrics = Arc::new(Metrics::new());
+    if let Some(metrics_addr) = &args.metrics {
+        let listener = TcpListener::bind(metrics_addr)
+            .await
+            .unwrap_or_else(|e| panic!("failed to bind metrics to {metrics_addr}: {e}"));
+        info!("serving metrics on http://{metrics_addr}/metrics");
+        tokio::spawn(serve_metrics(listener, Arc::clone(&metrics)));
+    }
 
-                    let msg = serialize(&RelayMessage::TickInputs {
-                        tick: state.current_tick,
-                        inputs,
-                    });
+    let (stream_events_tx, mut stream_events) = mpsc::unbounded_channel();
+    #[cfg(feature = "websocket")]
+    if let Some(ws_addr) = &args.websocket {
+        let listener = TcpListener::bind(ws_addr)
+            .await
+            .unwrap_or_else(|e| panic!("failed to bind WebSocket to {ws_addr}: {e}"));
+        info!("accepting WebSocket clients on {ws_addr}");
+        tokio::spawn(serve_websocket(listener, stream_events_tx.clone()));
+    }
+    drop(stream_events_tx);
 
-                    for addr in state.players.iter().flatten() {
-                        let _ = socket.send_to(&msg, addr);
-                    }
+    let auth = SessionAuth::from_env();
+    if auth.is_some() {
+        info!("listening on {bind_addr} (authenticated)");
+    } else {
+        info!("listening on {bind_addr}");
+    }
+    info!(
+        "{} players per match, up to {} matches",
+        settings.players, settings.room_capacity
+    );
+
+    let outgoing: Arc<dyn Socket> = match args.simulate_net {
+        Some(conditions) => {
+            warn!("simulating a bad network on the way out: {conditions}");
+            Arc::new(SimulatedSocket::new(socket.clone(), NetSim::new(conditions)))
+        }
+        None => socket.clone(),
+    };
+    let (mut core, mut dispatch_events) = RelayCore::new(
+        settings,
+        directory_path,
+        outgoing,
+        Arc::new(SystemClock),
+        auth,
+        metrics,
+    );
+    let mut console = spawn_console();
+    let mut config_changes = match &args.config {
+        Some(path) => watch_config(path.clone()),
+        None => mpsc::unbounded_channel().1,
+    };
+    let mut queue_timer = interval(QUEUE_UPDATE_INTERVAL);
+    queue_timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut buf = [0u8; RECV_BUF_SIZE];
+    let mut probe_buf = [0u8; RECV_BUF_SIZE];
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
 
-                    // Advance to next tick.
-                    state.current_tick += 1;
-                    state.tick_inputs = [None, None];
+    loop {
+        tokio::select! {
+            received = socket.recv_from(&mut buf) => match received {
+                Ok((len, src)) => core.handle_datagram(&buf[..len], src),
+                Err(e) => warn!("recv error: {e}"),
+            },
+            received = recv_probe(discovery.as_ref(), &mut probe_buf) => {
+                if let (Ok((len, src)), Some(discovery)) = (received, &discovery)
+                    && let Some(reply) = core.answer_probe(&probe_buf[..len], src, relay_port)
+                    && let Err(e) = discovery.try_send_to(&reply, src)
+                {
+                    warn!("cannot answer discovery probe from {src}: {e}");
+                }
+            }
+            Some(event) = stream_events.recv() => {
+                core.handle_stream_event(event);
+            }
+            Some(event) = dispatch_events.recv() => {
+                core.handle_dispatch_event(event);
+            }
+            Some(command) = console.recv() => {
+                core.run_console_command(command);
+            }
+            Some(()) = config_changes.recv() => {
+                if let Some(path) = &args.config {
+                    reload_config(path, &mut core, &log_level);
                 }
             }
+            _ = queue_timer.tick() => core.service_timers(),
+            Ok(()) = socket.writable(), if core.has_pending() => {}
+            () = &mut shutdown => break,
+        }
+        core.flush();
+        core.update_gauges();
+    }
+
+    // Tell every client the relay is going away, and wait until that and
+    // everything queued before it has gone out.
+    info!("shutting down");
+    core.queue_shutdown();
+    loop {
+        core.flush();
+        if !core.has_pending() || socket.writable().await.is_err() {
+            break;
         }
     }
 }
@@ -0,0 +1,213 @@
+//! Counters and gauges for monitoring a long-running relay.
+//!
+//! The relay serves these over HTTP in the Prometheus text format when
+//! started with `--metrics <addr>`. Everything is an atomic so room tasks can
+//! count ticks without going through the dispatcher; [`Metrics::render`] only
+//! reads them, so scraping never holds anything up.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Why an incoming or outgoing message went nowhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    /// Failed authentication against the session secret.
+    Unauthenticated,
+    /// Did not decode.
+    Malformed,
+    /// The handshake nonce on anything but a Hello or Resume.
+    HandshakeMisuse,
+    /// Duplicate or stale sequence number.
+    Sequence,
+    /// Input or ack from an address that is not seated.
+    UnknownClient,
+    /// Input for a tick already played or beyond the input window.
+    WrongTick,
+    /// Bulk message dropped because the destination's send queue was full.
+    SendQueueFull,
+    /// The source sent faster than its rate limit allows.
+    RateLimited,
+}
+
+impl DropReason {
+    pub const ALL: [DropReason; 8] = [
+        DropReason::Unauthenticated,
+        DropReason::Malformed,
+        DropReason::HandshakeMisuse,
+        DropReason::Sequence,
+        DropReason::UnknownClient,
+        DropReason::WrongTick,
+        DropReason::SendQueueFull,
+        DropReason::RateLimited,
+    ];
+
+    /// The `reason` label value.
+    pub fn label(self) -> &'static str {
+        match self {
+            DropReason::Unauthenticated => "unauthenticated",
+            DropReason::Malformed => "malformed",
+            DropReason::HandshakeMisuse => "handshake_misuse",
+            DropReason::Sequence => "sequence",
+            DropReason::UnknownClient => "unknown_client",
+            DropReason::WrongTick => "wrong_tick",
+            DropReason::SendQueueFull => "send_queue_full",
+            DropReason::RateLimited => "rate_limited",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    players: AtomicU64,
+    rooms: AtomicU64,
+    queued: AtomicU64,
+    ticks: AtomicU64,
+    substituted: AtomicU64,
+    rooms_opened: AtomicU64,
+    rooms_reclaimed: AtomicU64,
+    players_timed_out: AtomicU64,
+    packets_in: AtomicU64,
+    packets_out: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    dropped: [AtomicU64; DropReason::ALL.len()],
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_gauges(&self, players: usize, rooms: usize, queued: usize) {
+        self.players.store(players as u64, Ordering::Relaxed);
+        self.rooms.store(rooms as u64, Ordering::Relaxed);
+        self.queued.store(queued as u64, Ordering::Relaxed);
+    }
+
+    /// A room broadcast one tick's inputs.
+    pub fn tick_advanced(&self) {
+        self.ticks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A room filled in a player's input after the tick deadline.
+    pub fn input_substituted(&self) {
+        self.substituted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The dispatcher opened a room for a new match.
+    pub fn room_opened(&self) {
+        self.rooms_opened.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The idle-room sweep closed a room.
+    pub fn room_reclaimed(&self) {
+        self.rooms_reclaimed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A seated player was dropped for going silent.
+    pub fn player_timed_out(&self) {
+        self.players_timed_out.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn received(&self, bytes: usize) {
+        self.packets_in.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn sent(&self, bytes: usize) {
+        self.packets_out.fetch_add(1, Ordering::Relaxed);
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn dropped(&self, reason: DropReason) {
+        self.dropped[reason as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn dropped_count(&self, reason: DropReason) -> u64 {
+        self.dropped[reason as usize].load(Ordering::Relaxed)
+    }
+
+    /// The Prometheus text exposition of every metric.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let load = |value: &AtomicU64| value.load(Ordering::Relaxed);
+        let mut metric = |name: &str, kind: &str, help: &str, value: u64| {
+            let _ = writeln!(out, "# HELP relay_{name} {help}");
+            let _ = writeln!(out, "# TYPE relay_{name} {kind}");
+            let _ = writeln!(out, "relay_{name} {value}");
+        };
+        metric("connected_players", "gauge", "Players seated in a room.", load(&self.players));
+        metric("active_rooms", "gauge", "Rooms open, forming or playing.", load(&self.rooms));
+        metric("queued_clients", "gauge", "Clients waiting for a room.", load(&self.queued));
+        metric("ticks_advanced_total", "counter", "Ticks broadcast, all rooms.", load(&self.ticks));
+        metric(
+            "inputs_substituted_total",
+            "counter",
+            "Inputs filled in after the tick deadline.",
+            load(&self.substituted),
+        );
+        metric("rooms_opened_total", "counter", "Rooms opened.", load(&self.rooms_opened));
+        metric(
+            "rooms_reclaimed_total",
+            "counter",
+            "Rooms closed for going idle.",
+            load(&self.rooms_reclaimed),
+        );
+        metric(
+            "players_timed_out_total",
+            "counter",
+            "Players dropped for going silent.",
+            load(&self.players_timed_out),
+        );
+        metric("packets_received_total", "counter", "Datagrams received.", load(&self.packets_in));
+        metric("packets_sent_total", "counter", "Datagrams sent.", load(&self.packets_out));
+        metric("bytes_received_total", "counter", "Bytes received.", load(&self.bytes_in));
+        metric("bytes_sent_total", "counter", "Bytes sent.", load(&self.bytes_out));
+
+        let _ = writeln!(
+            out,
+            "# HELP relay_packets_dropped_total Messages dropped or ignored, by reason."
+        );
+        let _ = writeln!(out, "# TYPE relay_packets_dropped_total counter");
+        for reason in DropReason::ALL {
+            let _ = writeln!(
+                out,
+                "relay_packets_dropped_total{{reason=\"{}\"}} {}",
+                reason.label(),
+                self.dropped_count(reason)
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_prometheus_text() {
+        // given some traffic
+        let metrics = Metrics::new();
+        metrics.set_gauges(4, 2, 1);
+        metrics.received(100);
+        metrics.received(20);
+        metrics.tick_advanced();
+        metrics.room_opened();
+        metrics.dropped(DropReason::Malformed);
+
+        // when rendered
+        let text = metrics.render();
+
+        // then each metric appears with its type and value
+        assert!(text.contains("# TYPE relay_connected_players gauge\nrelay_connected_players 4\n"));
+        assert!(text.contains("relay_active_rooms 2\n"));
+        assert!(text.contains("relay_packets_received_total 2\n"));
+        assert!(text.contains("relay_bytes_received_total 120\n"));
+        assert!(text.contains("relay_ticks_advanced_total 1\n"));
+        assert!(text.contains("relay_rooms_opened_total 1\n"));
+        assert!(text.contains("relay_rooms_reclaimed_total 0\n"));
+        assert!(text.contains("relay_packets_dropped_total{reason=\"malformed\"} 1\n"));
+        assert!(text.contains("relay_packets_dropped_total{reason=\"sequence\"} 0\n"));
+    }
+}
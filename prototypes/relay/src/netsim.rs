@@ -0,0 +1,258 @@
+//! A bad network on demand, for testing lockstep without finding one.
+//!
+//! `--simulate-net latency=80,jitter=20,loss=2%`, on the relay or on a
+//! client, holds back every datagram that end sends by `latency`
+//! milliseconds, give or take up to `jitter`, and loses `loss` percent of
+//! them outright. Jitter reorders datagrams the way a real network does.
+//! Each end only impairs what it sends, so give the option to both ends to
+//! make both directions bad. Any key may be left out; it defaults to zero.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::server::Socket;
+
+/// How bad to make the network.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NetConditions {
+    pub latency: Duration,
+    /// The most a datagram's delay differs from `latency`, either way.
+    pub jitter: Duration,
+    /// The fraction of datagrams lost, from 0 to 1.
+    pub loss: f64,
+}
+
+impl FromStr for NetConditions {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, String> {
+        let mut conditions = NetConditions::default();
+        for entry in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("{entry:?}: expected key=value"))?;
+            let millis = || {
+                value
+                    .trim_end_matches("ms")
+                    .parse()
+                    .map(Duration::from_millis)
+                    .map_err(|_| format!("{key}: {value:?} is not a number of milliseconds"))
+            };
+            match key.trim() {
+                "latency" => conditions.latency = millis()?,
+                "jitter" => conditions.jitter = millis()?,
+                "loss" => {
+                    let percent: f64 = value
+                        .trim_end_matches('%')
+                        .parse()
+                        .map_err(|_| format!("loss: {value:?} is not a percentage"))?;
+                    if !(0.0..=100.0).contains(&percent) {
+                        return Err(format!("loss: {value:?} is not between 0% and 100%"));
+                    }
+                    conditions.loss = percent / 100.0;
+                }
+                other => return Err(format!("unknown condition {other:?}")),
+            }
+        }
+        Ok(conditions)
+    }
+}
+
+impl fmt::Display for NetConditions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "latency {} ms, jitter {} ms, loss {}%",
+            self.latency.as_millis(),
+            self.jitter.as_millis(),
+            self.loss * 100.0
+        )
+    }
+}
+
+/// Decides each datagram's fate under some [`NetConditions`].
+#[derive(Debug)]
+pub struct NetSim {
+    conditions: NetConditions,
+    /// xorshift64* state; never zero.
+    rng: u64,
+}
+
+impl NetSim {
+    /// Seeded from the clock, so two runs lose different datagrams.
+    pub fn new(conditions: NetConditions) -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or_default();
+        Self::with_seed(conditions, nanos)
+    }
+
+    /// The same seed loses and delays the same datagrams, for tests.
+    pub fn with_seed(conditions: NetConditions, seed: u64) -> Self {
+        Self {
+            conditions,
+            rng: seed | 1,
+        }
+    }
+
+    pub fn conditions(&self) -> NetConditions {
+        self.conditions
+    }
+
+    /// How long to hold the next datagram, or `None` to lose it.
+    pub fn delay(&mut self) -> Option<Duration> {
+        if self.next_unit() < self.conditions.loss {
+            return None;
+        }
+        let jitter = self.conditions.jitter.as_secs_f64() * (self.next_unit() * 2.0 - 1.0);
+        let delay = self.conditions.latency.as_secs_f64() + jitter;
+        Some(Duration::from_secs_f64(delay.max(0.0)))
+    }
+
+    /// A number in `[0, 1)`.
+    fn next_unit(&mut self) -> f64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let bits = self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11;
+        bits as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Datagrams held back by a [`NetSim`], for a caller that polls.
+#[derive(Debug)]
+pub struct DelayLine<T> {
+    sim: NetSim,
+    /// By when each is due, then by arrival, so equal times keep their order.
+    held: BTreeMap<(Instant, u64), T>,
+    arrivals: u64,
+}
+
+impl<T> DelayLine<T> {
+    pub fn new(sim: NetSim) -> Self {
+        Self {
+            sim,
+            held: BTreeMap::new(),
+            arrivals: 0,
+        }
+    }
+
+    /// Hold `item` for as long as the conditions say, or lose it.
+    pub fn push(&mut self, now: Instant, item: T) {
+        if let Some(delay) = self.sim.delay() {
+            self.held.insert((now + delay, self.arrivals), item);
+            self.arrivals += 1;
+        }
+    }
+
+    /// The next item due by `now`, if any.
+    pub fn pop_due(&mut self, now: Instant) -> Option<T> {
+        let entry = self.held.first_entry()?;
+        (entry.key().0 <= now).then(|| entry.remove())
+    }
+
+    /// Everything still held, in the order it is due, as when shutting down.
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        std::mem::take(&mut self.held).into_values()
+    }
+}
+
+/// A [`Socket`] that sends through another after a [`NetSim`] delay, on a
+/// tokio task per datagram. Lost datagrams still report success, as a real
+/// network would.
+pub struct SimulatedSocket<S> {
+    inner: Arc<S>,
+    sim: Mutex<NetSim>,
+}
+
+impl<S> SimulatedSocket<S> {
+    pub fn new(inner: Arc<S>, sim: NetSim) -> Self {
+        Self {
+            inner,
+            sim: Mutex::new(sim),
+        }
+    }
+}
+
+impl<S: Socket + 'static> Socket for SimulatedSocket<S> {
+    fn try_send_to(&self, datagram: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        let Some(delay) = self.sim.lock().unwrap().delay() else {
+            return Ok(datagram.len());
+        };
+        let inner = Arc::clone(&self.inner);
+        let len = datagram.len();
+        let datagram = datagram.to_vec();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let _ = inner.try_send_to(&datagram, addr);
+        });
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conditions_parse_with_or_without_units() {
+        let conditions: NetConditions = "latency=80,jitter=20ms, loss=2%".parse().unwrap();
+        assert_eq!(conditions.latency, Duration::from_millis(80));
+        assert_eq!(conditions.jitter, Duration::from_millis(20));
+        assert!((conditions.loss - 0.02).abs() < 1e-9);
+        assert_eq!(
+            "loss=5".parse::<NetConditions>().unwrap().latency,
+            Duration::ZERO
+        );
+        assert!("latency=fast".parse::<NetConditions>().is_err());
+        assert!("loss=120%".parse::<NetConditions>().is_err());
+        assert!("delay=80".parse::<NetConditions>().is_err());
+    }
+
+    #[test]
+    fn delays_stay_within_the_jitter() {
+        let conditions: NetConditions = "latency=80,jitter=20".parse().unwrap();
+        let mut sim = NetSim::with_seed(conditions, 7);
+        for _ in 0..1000 {
+            let delay = sim.delay().unwrap();
+            assert!(delay >= Duration::from_millis(60) && delay <= Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn loss_is_roughly_the_share_asked_for() {
+        let conditions: NetConditions = "loss=25%".parse().unwrap();
+        let mut sim = NetSim::with_seed(conditions, 7);
+        let lost = (0..10_000).filter(|_| sim.delay().is_none()).count();
+        assert!((2000..3000).contains(&lost), "lost {lost} of 10000");
+    }
+
+    #[test]
+    fn held_datagrams_come_out_when_due() {
+        // given a line with a fixed 50 ms delay
+        let conditions: NetConditions = "latency=50".parse().unwrap();
+        let mut line = DelayLine::new(NetSim::with_seed(conditions, 7));
+        let start = Instant::now();
+
+        // when two datagrams go in
+        line.push(start, "first");
+        line.push(start, "second");
+
+        // then nothing comes out early, and then both in order
+        assert_eq!(line.pop_due(start + Duration::from_millis(49)), None);
+        let due = start + Duration::from_millis(50);
+        assert_eq!(line.pop_due(due), Some("first"));
+        assert_eq!(line.pop_due(due), Some("second"));
+        assert_eq!(line.pop_due(due), None);
+    }
+}
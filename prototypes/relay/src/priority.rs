@@ -0,0 +1,271 @@
+//! Send-path priority, so bulk traffic never delays a tick.
+//!
+//! Every client's simulation stalls until `TickInputs` arrives, while a
+//! config push or queue update can wait a few milliseconds without anyone
+//! noticing. Outgoing messages wait in a [`SendQueue`], a small priority
+//! queue per destination. Each send takes the highest-priority message
+//! queued for any destination, taking turns between destinations at the
+//! same priority. However much bulk data is backed up, a `TickInputs`
+//! waits only behind other critical messages.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+
+use crate::RelayMessage;
+
+/// Bulk messages kept per destination; beyond this the oldest is dropped.
+pub const MAX_BULK_PER_DESTINATION: usize = 256;
+
+/// Highest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Holds up the simulation until delivered.
+    Critical,
+    /// Connection and roster changes.
+    Control,
+    /// Settings, queue updates, admin replies, room lists, connection
+    /// statistics, state hashes, chat, and anything large.
+    Bulk,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 3] = [Priority::Critical, Priority::Control, Priority::Bulk];
+
+    pub fn of<P>(msg: &RelayMessage<P>) -> Self {
+        match msg {
+            RelayMessage::TickInputs { .. }
+            | RelayMessage::GameStart
+            | RelayMessage::Resumed { .. }
+            | RelayMessage::SnapshotRequested { .. }
+            | RelayMessage::Snapshot { .. }
+            | RelayMessage::Paused { .. }
+            | RelayMessage::Unpaused { .. }
+            | RelayMessage::RematchRequested { .. }
+            | RelayMessage::RematchStart => Priority::Critical,
+            RelayMessage::Welcome { .. }
+            | RelayMessage::Spectating { .. }
+            | RelayMessage::Rejected { .. }
+            | RelayMessage::PlayerJoined { .. }
+            | RelayMessage::PlayerLeft { .. }
+            | RelayMessage::MatchAborted { .. }
+            | RelayMessage::ServerShutdown => Priority::Control,
+            RelayMessage::Config { .. }
+            | RelayMessage::QueuePosition { .. }
+            | RelayMessage::RoomAdminResult { .. }
+            | RelayMessage::RoomList { .. }
+            | RelayMessage::NetStats { .. }
+            | RelayMessage::StateHash { .. }
+            | RelayMessage::StateDump { .. }
+            | RelayMessage::Chat { .. } => Priority::Bulk,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Outgoing messages waiting for the socket, one priority queue per
+/// destination.
+#[derive(Debug)]
+pub struct SendQueue<T> {
+    queues: HashMap<SocketAddr, [VecDeque<T>; 3]>,
+    /// Destinations with anything queued; the front goes next among equals.
+    turns: VecDeque<SocketAddr>,
+    len: usize,
+}
+
+impl<T> Default for SendQueue<T> {
+    fn default() -> Self {
+        Self {
+            queues: HashMap::new(),
+            turns: VecDeque::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<T> SendQueue<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Queue `item` for `addr`. Returns a bulk item dropped to make room.
+    pub fn push(&mut self, addr: SocketAddr, priority: Priority, item: T) -> Option<T> {
+        let queues = self.queues.entry(addr).or_insert_with(|| {
+            self.turns.push_back(addr);
+            Default::default()
+        });
+        let queue = &mut queues[priority.index()];
+        queue.push_back(item);
+        if priority == Priority::Bulk && queue.len() > MAX_BULK_PER_DESTINATION {
+            return queue.pop_front();
+        }
+        self.len += 1;
+        None
+    }
+
+    /// Put back an item that could not be sent, to go first next time.
+    pub fn requeue(&mut self, addr: SocketAddr, priority: Priority, item: T) {
+        let queues = self.queues.entry(addr).or_insert_with(|| {
+            self.turns.push_front(addr);
+            Default::default()
+        });
+        queues[priority.index()].push_front(item);
+        self.len += 1;
+    }
+
+    /// The next item to send: the highest priority queued for anyone, from
+    /// the destination whose turn it is.
+    pub fn pop(&mut self) -> Option<(SocketAddr, Priority, T)> {
+        for priority in Priority::ALL {
+            let Some(turn) = self
+                .turns
+                .iter()
+                .position(|addr| !self.queues[addr][priority.index()].is_empty())
+            else {
+                continue;
+            };
+            let addr = self.turns.remove(turn)?;
+            let queues = self.queues.get_mut(&addr)?;
+            let item = queues[priority.index()].pop_front()?;
+            if queues.iter().all(VecDeque::is_empty) {
+                self.queues.remove(&addr);
+            } else {
+                self.turns.push_back(addr);
+            }
+            self.len -= 1;
+            return Some((addr, priority, item));
+        }
+        None
+    }
+
+    /// Remove everything queued for `addr`, highest priority first.
+    pub fn take_destination(&mut self, addr: &SocketAddr) -> Vec<(Priority, T)> {
+        let Some(queues) = self.queues.remove(addr) else {
+            return Vec::new();
+        };
+        self.turns.retain(|queued| queued != addr);
+        let items: Vec<(Priority, T)> = Priority::ALL
+            .into_iter()
+            .zip(queues)
+            .flat_map(|(priority, queue)| queue.into_iter().map(move |item| (priority, item)))
+            .collect();
+        self.len -= items.len();
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn tick_inputs_preempt_a_bulk_backlog() {
+        // given a destination with a full bulk backlog
+        let mut queue = SendQueue::new();
+        for i in 0..MAX_BULK_PER_DESTINATION {
+            queue.push(addr(1), Priority::Bulk, i);
+        }
+
+        // when a critical message is queued behind it
+        queue.push(addr(1), Priority::Critical, usize::MAX);
+
+        // then it goes out first
+        assert_eq!(queue.pop(), Some((addr(1), Priority::Critical, usize::MAX)));
+        assert_eq!(queue.pop(), Some((addr(1), Priority::Bulk, 0)));
+    }
+
+    #[test]
+    fn destinations_take_turns_within_a_priority() {
+        // given two destinations each with several control messages
+        let mut queue = SendQueue::new();
+        for i in 0..3 {
+            queue.push(addr(1), Priority::Control, ('a', i));
+            queue.push(addr(2), Priority::Control, ('b', i));
+        }
+
+        // when they are drained
+        let order: Vec<(char, i32)> = std::iter::from_fn(|| queue.pop())
+            .map(|(_, _, item)| item)
+            .collect();
+
+        // then they alternate, each in its own order
+        assert_eq!(
+            order,
+            vec![('a', 0), ('b', 0), ('a', 1), ('b', 1), ('a', 2), ('b', 2)]
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn worst_case_tick_latency_under_bulk_load() {
+        // given a link that sends 4 datagrams per millisecond, two players,
+        // a tick every 16 ms, and more bulk offered than the link can carry
+        const PER_MS: usize = 4;
+        const TICK_MS: u32 = 16;
+        let players = [addr(1), addr(2)];
+        let mut queue = SendQueue::new();
+        let mut worst_ms = 0;
+        let mut ticks_sent = 0;
+
+        // when it runs for ten seconds
+        for now in 0..10_000u32 {
+            for player in players {
+                for _ in 0..3 {
+                    queue.push(player, Priority::Bulk, now);
+                }
+                if now % TICK_MS == 0 {
+                    queue.push(player, Priority::Critical, now);
+                }
+            }
+            for _ in 0..PER_MS {
+                let Some((_, priority, queued_at)) = queue.pop() else {
+                    break;
+                };
+                if priority == Priority::Critical {
+                    worst_ms = worst_ms.max(now - queued_at);
+                    ticks_sent += 1;
+                }
+            }
+        }
+
+        // then every tick left in the millisecond it was queued, and the
+        // bulk backlog stayed bounded
+        assert_eq!(ticks_sent, 2 * 10_000u32.div_ceil(TICK_MS));
+        assert_eq!(worst_ms, 0);
+        assert!(queue.len() <= players.len() * MAX_BULK_PER_DESTINATION);
+    }
+
+    #[test]
+    fn bulk_beyond_the_limit_drops_the_oldest() {
+        let mut queue = SendQueue::new();
+        for i in 0..MAX_BULK_PER_DESTINATION {
+            assert_eq!(queue.push(addr(1), Priority::Bulk, i), None);
+        }
+        assert_eq!(queue.push(addr(1), Priority::Bulk, MAX_BULK_PER_DESTINATION), Some(0));
+        assert_eq!(queue.len(), MAX_BULK_PER_DESTINATION);
+    }
+
+    #[test]
+    fn requeued_item_goes_first() {
+        let mut queue = SendQueue::new();
+        queue.push(addr(1), Priority::Critical, 1);
+        queue.push(addr(1), Priority::Critical, 2);
+        let (to, priority, item) = queue.pop().unwrap();
+        queue.requeue(to, priority, item);
+        assert_eq!(queue.pop(), Some((addr(1), Priority::Critical, 1)));
+    }
+}
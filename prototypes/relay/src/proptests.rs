@@ -0,0 +1,257 @@
+//! Property tests for the wire format.
+//!
+//! Every message must survive a serialize/deserialize round trip unchanged,
+//! and no byte slice — truncated, garbled, or random — may panic the
+//! decoder, since the relay decodes whatever arrives on its socket.
+//!
+//! Messages do not implement `PartialEq` (`ConfigValue::Float` may be NaN),
+//! so a round trip is checked by re-serializing and comparing bytes.
+
+use proptest::collection::vec;
+use proptest::option;
+use proptest::prelude::*;
+
+use crate::frag::{Fragmenter, Reassembler};
+use crate::rooms::{RoomCommand, RoomInfo, RoomPrivacy};
+use crate::sequence::Sequenced;
+use crate::{
+    AbortReason, ClientMessage, ConfigValue, PlayerNetStats, RejectReason, RelayMessage,
+    deserialize, serialize,
+};
+
+fn text() -> impl Strategy<Value = String> {
+    ".{0,24}"
+}
+
+fn payload() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..64)
+}
+
+fn config_value() -> impl Strategy<Value = ConfigValue> {
+    prop_oneof![
+        any::<bool>().prop_map(ConfigValue::Bool),
+        any::<i64>().prop_map(ConfigValue::Int),
+        any::<f64>().prop_map(ConfigValue::Float),
+        text().prop_map(ConfigValue::Text),
+    ]
+}
+
+fn room_command() -> impl Strategy<Value = RoomCommand> {
+    prop_oneof![
+        (text(), config_value()).prop_map(|(key, value)| RoomCommand::SetSetting { key, value }),
+        text().prop_map(|key| RoomCommand::ClearSetting { key }),
+        Just(RoomCommand::ResetLeaderboard),
+        room_privacy().prop_map(|privacy| RoomCommand::SetPrivacy { privacy }),
+    ]
+}
+
+fn room_privacy() -> impl Strategy<Value = RoomPrivacy> {
+    any::<(bool, bool, bool)>().prop_map(|(no_spectators, hidden, no_history)| RoomPrivacy {
+        no_spectators,
+        hidden,
+        no_history,
+    })
+}
+
+fn room_info() -> impl Strategy<Value = RoomInfo> {
+    (
+        text(),
+        option::of(text()),
+        any::<u8>(),
+        option::of(any::<u32>()),
+        room_privacy(),
+    )
+        .prop_map(|(name, game_id, players, median_rtt_ms, privacy)| RoomInfo {
+            name,
+            game_id,
+            players,
+            median_rtt_ms,
+            privacy,
+        })
+}
+
+fn reject_reason() -> impl Strategy<Value = RejectReason> {
+    prop_oneof![
+        Just(RejectReason::Full),
+        Just(RejectReason::BadVersion),
+        Just(RejectReason::BadRoom),
+        Just(RejectReason::Banned),
+        Just(RejectReason::WrongGame),
+        Just(RejectReason::Kicked),
+        Just(RejectReason::NoMatch),
+        Just(RejectReason::Idle),
+    ]
+}
+
+fn abort_reason() -> impl Strategy<Value = AbortReason> {
+    prop_oneof![
+        Just(AbortReason::RageQuit),
+        Just(AbortReason::ControllerDisconnected),
+        Just(AbortReason::Desync),
+        Just(AbortReason::VersionMismatch),
+    ]
+}
+
+fn player_net_stats() -> impl Strategy<Value = PlayerNetStats> {
+    (
+        any::<u8>(),
+        any::<u32>(),
+        any::<u32>(),
+        option::of(any::<u32>()),
+        option::of(any::<u32>()),
+    )
+        .prop_map(|(slot, packets_received, last_seen_ms, rtt_ms, jitter_ms)| {
+            PlayerNetStats {
+                slot,
+                packets_received,
+                last_seen_ms,
+                rtt_ms,
+                jitter_ms,
+            }
+        })
+}
+
+fn client_message() -> impl Strategy<Value = ClientMessage> {
+    prop_oneof![
+        (text(), text(), option::of(text()))
+            .prop_map(|(game_id, name, room)| ClientMessage::Hello { game_id, name, room }),
+        (any::<u32>(), payload()).prop_map(|(tick, payload)| ClientMessage::Input { tick, payload }),
+        (any::<u32>(), vec(payload(), 0..4)).prop_map(|(first_tick, payloads)| {
+            ClientMessage::InputBatch { first_tick, payloads }
+        }),
+        any::<u32>().prop_map(|tick| ClientMessage::AckTick { tick }),
+        Just(ClientMessage::Goodbye),
+        Just(ClientMessage::ListRooms),
+        any::<u32>().prop_map(|tick| ClientMessage::RequestTick { tick }),
+        abort_reason().prop_map(|reason| ClientMessage::Abort { reason }),
+        (text(), text(), room_command()).prop_map(|(room, owner_secret, command)| {
+            ClientMessage::RoomAdmin {
+                room,
+                owner_secret,
+                command,
+            }
+        }),
+        (text(), text(), text(), any::<u8>(), any::<u32>()).prop_map(
+            |(game_id, name, room, slot, next_tick)| ClientMessage::Resume {
+                game_id,
+                name,
+                room,
+                slot,
+                next_tick,
+            }
+        ),
+        vec(any::<u32>(), 0..4).prop_map(|scores| ClientMessage::Scores { scores }),
+        (any::<u32>(), any::<u64>())
+            .prop_map(|(tick, hash)| ClientMessage::StateHash { tick, hash }),
+        (any::<u32>(), text()).prop_map(|(tick, state)| ClientMessage::StateDump { tick, state }),
+        Just(ClientMessage::RequestSnapshot),
+        (any::<u32>(), vec(any::<u8>(), 0..64))
+            .prop_map(|(tick, state)| ClientMessage::Snapshot { tick, state }),
+        any::<u32>().prop_map(|tick| ClientMessage::Pause { tick }),
+        Just(ClientMessage::Unpause),
+        Just(ClientMessage::Rematch),
+        (text(), option::of(text()))
+            .prop_map(|(game_id, room)| ClientMessage::Spectate { game_id, room }),
+        text().prop_map(|text| ClientMessage::Chat { text }),
+    ]
+}
+
+fn relay_message() -> impl Strategy<Value = RelayMessage> {
+    prop_oneof![
+        any::<u8>().prop_map(|player_slot| RelayMessage::Welcome { player_slot }),
+        Just(RelayMessage::GameStart),
+        (any::<u32>(), vec(payload(), 0..4))
+            .prop_map(|(tick, inputs)| RelayMessage::TickInputs { tick, inputs }),
+        reject_reason().prop_map(|reason| RelayMessage::Rejected { reason }),
+        vec((text(), config_value()), 0..4).prop_map(|entries| RelayMessage::Config { entries }),
+        any::<u32>().prop_map(|position| RelayMessage::QueuePosition { position }),
+        (any::<u8>(), text()).prop_map(|(slot, name)| RelayMessage::PlayerJoined { slot, name }),
+        any::<u8>().prop_map(|slot| RelayMessage::PlayerLeft { slot }),
+        (any::<u8>(), abort_reason())
+            .prop_map(|(by_slot, reason)| RelayMessage::MatchAborted { by_slot, reason }),
+        option::of(text()).prop_map(|error| RelayMessage::RoomAdminResult { error }),
+        vec(room_info(), 0..4).prop_map(|rooms| RelayMessage::RoomList { rooms }),
+        Just(RelayMessage::ServerShutdown),
+        any::<u32>().prop_map(|tick| RelayMessage::Resumed { tick }),
+        vec(player_net_stats(), 0..4).prop_map(|players| RelayMessage::NetStats { players }),
+        (any::<u8>(), any::<u32>(), any::<u64>())
+            .prop_map(|(slot, tick, hash)| RelayMessage::StateHash { slot, tick, hash }),
+        (any::<u8>(), any::<u32>(), text())
+            .prop_map(|(slot, tick, state)| RelayMessage::StateDump { slot, tick, state }),
+        any::<u8>().prop_map(|slot| RelayMessage::SnapshotRequested { slot }),
+        (any::<u8>(), any::<u32>(), vec(any::<u8>(), 0..64))
+            .prop_map(|(slot, tick, state)| RelayMessage::Snapshot { slot, tick, state }),
+        (any::<u8>(), any::<u32>())
+            .prop_map(|(by_slot, tick)| RelayMessage::Paused { by_slot, tick }),
+        any::<u8>().prop_map(|by_slot| RelayMessage::Unpaused { by_slot }),
+        any::<u8>().prop_map(|slot| RelayMessage::RematchRequested { slot }),
+        Just(RelayMessage::RematchStart),
+        any::<u32>().prop_map(|tick| RelayMessage::Spectating { tick }),
+        (any::<u8>(), text()).prop_map(|(slot, text)| RelayMessage::Chat { slot, text }),
+    ]
+}
+
+fn sequenced<T: std::fmt::Debug>(
+    message: impl Strategy<Value = T>,
+) -> impl Strategy<Value = Sequenced<T>> {
+    (any::<u32>(), message).prop_map(|(seq, message)| Sequenced { seq, message })
+}
+
+/// Serialize, decode, and serialize again; both encodings must match.
+fn assert_lossless<T>(value: &T)
+where
+    T: serde::Serialize + for<'a> serde::Deserialize<'a>,
+{
+    let bytes = serialize(value);
+    let decoded: T = deserialize(&bytes).expect("a serialized message must decode");
+    assert_eq!(serialize(&decoded), bytes);
+}
+
+proptest! {
+    #[test]
+    fn client_messages_round_trip(msg in sequenced(client_message())) {
+        assert_lossless(&msg);
+    }
+
+    #[test]
+    fn relay_messages_round_trip(msg in sequenced(relay_message())) {
+        assert_lossless(&msg);
+    }
+
+    #[test]
+    fn truncated_messages_never_panic(msg in sequenced(relay_message()), cut in any::<prop::sample::Index>()) {
+        let bytes = serialize(&msg);
+        let truncated = &bytes[..cut.index(bytes.len())];
+        let _ = deserialize::<Sequenced<RelayMessage>>(truncated);
+        let _ = deserialize::<Sequenced<ClientMessage>>(truncated);
+    }
+
+    #[test]
+    fn garbled_messages_never_panic(
+        msg in sequenced(client_message()),
+        flips in vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+    ) {
+        let mut bytes = serialize(&msg);
+        for (position, value) in flips {
+            let i = position.index(bytes.len());
+            bytes[i] ^= value;
+        }
+        let _ = deserialize::<Sequenced<ClientMessage>>(&bytes);
+        let _ = deserialize::<Sequenced<RelayMessage>>(&bytes);
+    }
+
+    #[test]
+    fn random_bytes_never_panic(bytes in vec(any::<u8>(), 0..256)) {
+        let _ = deserialize::<Sequenced<ClientMessage>>(&bytes);
+        let _ = deserialize::<Sequenced<RelayMessage>>(&bytes);
+        let _ = Reassembler::new().accept(&bytes);
+    }
+
+    #[test]
+    fn fragments_reassemble_to_the_original(message in vec(any::<u8>(), 0..2048), mtu in 16usize..512) {
+        let fragments = Fragmenter::new(mtu).split(&message).unwrap();
+        let mut reassembler = Reassembler::new();
+        let whole: Vec<Vec<u8>> = fragments.iter().filter_map(|f| reassembler.accept(f)).collect();
+        prop_assert_eq!(whole, vec![message]);
+    }
+}
@@ -0,0 +1,127 @@
+//! Per-source rate limiting for the relay's receive path.
+//!
+//! Every source address gets a token bucket: each datagram spends a token,
+//! and tokens refill at a steady rate up to a burst allowance. A host that
+//! floods the relay with Hello or Input packets runs dry and has the excess
+//! dropped before it is authenticated or decoded, so it costs the single
+//! receive loop as little as possible and cannot fill rooms with junk.
+//!
+//! Well-behaved clients send an input and an ack per tick plus the odd
+//! Hello, so the default rate leaves them plenty of headroom.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Buckets untouched this long are full again and can be forgotten.
+const IDLE_AFTER: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Tokens added per second.
+    rate: f64,
+    /// Most tokens a bucket holds, and what a new source starts with.
+    burst: f64,
+    buckets: HashMap<SocketAddr, TokenBucket>,
+}
+
+impl RateLimiter {
+    pub fn new(rate_per_sec: u32, burst: u32) -> Self {
+        Self {
+            rate: f64::from(rate_per_sec),
+            burst: f64::from(burst.max(1)),
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Change the rate and burst. Buckets already over the new burst are cut
+    /// down to it on their next datagram.
+    pub fn set_rates(&mut self, rate_per_sec: u32, burst: u32) {
+        self.rate = f64::from(rate_per_sec);
+        self.burst = f64::from(burst.max(1));
+    }
+
+    /// Spend a token for a datagram from `src`; `false` if it has none left
+    /// and the datagram should be dropped.
+    pub fn allow(&mut self, src: SocketAddr, now: Instant) -> bool {
+        let bucket = self.buckets.entry(src).or_insert(TokenBucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Forget sources that have been quiet long enough to be back at full
+    /// burst, so spoofed addresses cannot grow the table forever.
+    pub fn prune(&mut self, now: Instant) {
+        self.buckets
+            .retain(|_, bucket| now.saturating_duration_since(bucket.updated) < IDLE_AFTER);
+    }
+
+    pub fn tracked_sources(&self) -> usize {
+        self.buckets.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn flood_is_cut_off_after_the_burst() {
+        // given a limiter allowing bursts of 5
+        let mut limiter = RateLimiter::new(10, 5);
+        let now = Instant::now();
+
+        // when one source sends 8 datagrams at once
+        let allowed = (0..8).filter(|_| limiter.allow(addr(1), now)).count();
+
+        // then only the burst gets through, and other sources are unaffected
+        assert_eq!(allowed, 5);
+        assert!(limiter.allow(addr(2), now));
+    }
+
+    #[test]
+    fn tokens_refill_over_time() {
+        // given a source that used up its burst
+        let mut limiter = RateLimiter::new(10, 2);
+        let start = Instant::now();
+        assert!(limiter.allow(addr(1), start));
+        assert!(limiter.allow(addr(1), start));
+        assert!(!limiter.allow(addr(1), start));
+
+        // when a tenth of a second passes at 10 per second
+        let later = start + Duration::from_millis(100);
+
+        // then one more datagram is allowed
+        assert!(limiter.allow(addr(1), later));
+        assert!(!limiter.allow(addr(1), later));
+    }
+
+    #[test]
+    fn idle_sources_are_forgotten() {
+        let mut limiter = RateLimiter::new(10, 2);
+        let start = Instant::now();
+        limiter.allow(addr(1), start);
+        limiter.prune(start + IDLE_AFTER);
+        assert_eq!(limiter.tracked_sources(), 0);
+    }
+}
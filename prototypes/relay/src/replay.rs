@@ -0,0 +1,519 @@
+//! Versioned replay container.
+//!
+//! A lockstep session is fully described by its starting parameters and the
+//! inputs for every tick, so that is all a replay stores:
+//!
+//! ```text
+//! magic     b"ARPL"
+//! version   u16 LE
+//! header    u32 LE length, then postcard ReplayHeader
+//! chunk*    b"CHNK", then u32 LE first tick, tick count, body length and
+//!           body checksum, then the body: per tick, u32 LE length, then
+//!           postcard ReplayTick
+//! ```
+//!
+//! Inputs are the same opaque bytes the relay broadcasts, so the relay and
+//! clients record identical files and replaying one feeds a client exactly
+//! what it saw live. With `--record-dir`, the relay records every match
+//! this way as it is broadcast, for debugging a desync after the fact;
+//! rooms flagged no-history are not recorded.
+//!
+//! Sessions can run for hours, so neither side ever holds more than one
+//! chunk. The writer appends a chunk and flushes once it has
+//! [`ReplayWriter::with_chunk_ticks`] ticks or has been open for
+//! [`FLUSH_INTERVAL`], whichever comes first; the reader loads one chunk at a
+//! time and can skip whole chunks to reach a tick. Everything before the
+//! current chunk is on disk, so a crash loses at most the last
+//! [`FLUSH_INTERVAL`] of play. A chunk cut short by a crash reads as the end
+//! of the replay (see [`ReplayReader::truncated`]); a complete chunk whose
+//! checksum does not match is corrupt.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Tick;
+
+pub const MAGIC: [u8; 4] = *b"ARPL";
+pub const FORMAT_VERSION: u16 = 2;
+const CHUNK_MAGIC: [u8; 4] = *b"CHNK";
+const CHUNK_HEADER_LEN: usize = 20;
+
+/// Ticks per chunk unless [`ReplayWriter::with_chunk_ticks`] says otherwise:
+/// about four seconds at 64 Hz.
+pub const DEFAULT_CHUNK_TICKS: u32 = 256;
+/// The longest a tick waits in memory before it is written out.
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Refuse records larger than this rather than allocating whatever a corrupt
+/// length field asks for.
+const MAX_RECORD_LEN: u32 = 1 << 20;
+/// Same for chunk bodies.
+const MAX_CHUNK_LEN: u32 = 16 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    pub game_id: String,
+    pub player_count: u8,
+    /// Simulation ticks per second; 0 in relay recordings, where it is not
+    /// known.
+    pub tick_rate: u16,
+    /// Seed for any randomness in the simulation; 0 in relay recordings.
+    pub seed: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayTick {
+    pub tick: Tick,
+    /// One opaque input per player slot.
+    pub inputs: Vec<Vec<u8>>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u16),
+    /// A record or chunk is oversized, fails its checksum, or does not decode.
+    Corrupt(String),
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "replay I/O error: {e}"),
+            ReplayError::BadMagic => f.write_str("not a replay file"),
+            ReplayError::UnsupportedVersion(v) => {
+                write!(f, "replay format version {v} is not supported (expected {FORMAT_VERSION})")
+            }
+            ReplayError::Corrupt(reason) => write!(f, "corrupt replay: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> Self {
+        ReplayError::Io(e)
+    }
+}
+
+/// FNV-1a; catches torn and bit-flipped chunks, not tampering.
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0x811c_9dc5, |hash, byte| (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193))
+}
+
+struct ChunkHeader {
+    first_tick: Tick,
+    ticks: u32,
+    len: u32,
+    checksum: u32,
+}
+
+impl ChunkHeader {
+    fn to_bytes(&self) -> [u8; CHUNK_HEADER_LEN] {
+        let mut bytes = [0u8; CHUNK_HEADER_LEN];
+        bytes[..4].copy_from_slice(&CHUNK_MAGIC);
+        bytes[4..8].copy_from_slice(&self.first_tick.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.ticks.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.len.to_le_bytes());
+        bytes[16..].copy_from_slice(&self.checksum.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; CHUNK_HEADER_LEN]) -> Result<Self, ReplayError> {
+        if bytes[..4] != CHUNK_MAGIC {
+            return Err(ReplayError::Corrupt("missing chunk marker".into()));
+        }
+        let field = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let header = Self {
+            first_tick: field(4),
+            ticks: field(8),
+            len: field(12),
+            checksum: field(16),
+        };
+        if header.len > MAX_CHUNK_LEN {
+            return Err(ReplayError::Corrupt(format!("chunk of {} bytes", header.len)));
+        }
+        Ok(header)
+    }
+}
+
+pub struct ReplayWriter<W: Write> {
+    out: W,
+    chunk_ticks: u32,
+    /// Encoded ticks not yet written out.
+    chunk: Vec<u8>,
+    chunk_first_tick: Tick,
+    chunk_len: u32,
+    chunk_opened: Instant,
+}
+
+impl<W: Write> ReplayWriter<W> {
+    /// Write and flush the file header, so even a replay that crashes before
+    /// its first chunk opens cleanly.
+    pub fn new(mut out: W, header: &ReplayHeader) -> io::Result<Self> {
+        out.write_all(&MAGIC)?;
+        out.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        write_record(&mut out, header)?;
+        out.flush()?;
+        Ok(Self {
+            out,
+            chunk_ticks: DEFAULT_CHUNK_TICKS,
+            chunk: Vec::new(),
+            chunk_first_tick: 0,
+            chunk_len: 0,
+            chunk_opened: Instant::now(),
+        })
+    }
+
+    /// Close chunks after `ticks` ticks instead of [`DEFAULT_CHUNK_TICKS`].
+    pub fn with_chunk_ticks(mut self, ticks: u32) -> Self {
+        self.chunk_ticks = ticks.max(1);
+        self
+    }
+
+    pub fn write_tick(&mut self, tick: Tick, inputs: &[Vec<u8>]) -> io::Result<()> {
+        if self.chunk_len == 0 {
+            self.chunk_first_tick = tick;
+            self.chunk_opened = Instant::now();
+        }
+        write_record(
+            &mut self.chunk,
+            &ReplayTick {
+                tick,
+                inputs: inputs.to_vec(),
+            },
+        )?;
+        self.chunk_len += 1;
+        if self.chunk_len >= self.chunk_ticks || self.chunk_opened.elapsed() >= FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write out the ticks recorded so far as a chunk and flush the
+    /// underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.chunk_len == 0 {
+            return self.out.flush();
+        }
+        let header = ChunkHeader {
+            first_tick: self.chunk_first_tick,
+            ticks: self.chunk_len,
+            len: self.chunk.len() as u32,
+            checksum: checksum(&self.chunk),
+        };
+        self.out.write_all(&header.to_bytes())?;
+        self.out.write_all(&self.chunk)?;
+        self.out.flush()?;
+        self.chunk.clear();
+        self.chunk_len = 0;
+        Ok(())
+    }
+
+    /// Write out the last chunk, flush, and return the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush()?;
+        Ok(self.out)
+    }
+}
+
+fn write_record<T: Serialize>(out: &mut impl Write, value: &T) -> io::Result<()> {
+    let bytes = postcard::to_allocvec(value).map_err(io::Error::other)?;
+    out.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    out.write_all(&bytes)
+}
+
+pub struct ReplayReader<R: Read> {
+    input: R,
+    header: ReplayHeader,
+    /// The chunk being read, and how far into it we are.
+    chunk: Vec<u8>,
+    offset: usize,
+    truncated: bool,
+}
+
+impl<R: Read> ReplayReader<R> {
+    pub fn open(mut input: R) -> Result<Self, ReplayError> {
+        let mut magic = [0u8; 4];
+        input.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(ReplayError::BadMagic);
+        }
+        let mut version = [0u8; 2];
+        input.read_exact(&mut version)?;
+        let version = u16::from_le_bytes(version);
+        if version != FORMAT_VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+        let header = read_record(&mut input)?
+            .ok_or_else(|| ReplayError::Corrupt("missing header".into()))?;
+        Ok(Self {
+            input,
+            header,
+            chunk: Vec::new(),
+            offset: 0,
+            truncated: false,
+        })
+    }
+
+    pub fn header(&self) -> &ReplayHeader {
+        &self.header
+    }
+
+    /// Whether the replay ended in a chunk cut short, as a recording that
+    /// crashed does. Only meaningful once [`ReplayReader::next_tick`] has
+    /// returned `None`.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// The next tick, or `None` at the end of the replay.
+    pub fn next_tick(&mut self) -> Result<Option<ReplayTick>, ReplayError> {
+        while self.offset == self.chunk.len() {
+            let Some(header) = self.read_chunk_header()? else {
+                return Ok(None);
+            };
+            if !self.load_chunk(&header)? {
+                return Ok(None);
+            }
+        }
+        let mut rest = &self.chunk[self.offset..];
+        let tick = read_record(&mut rest)?
+            .ok_or_else(|| ReplayError::Corrupt("empty record".into()))?;
+        self.offset = self.chunk.len() - rest.len();
+        Ok(Some(tick))
+    }
+
+    /// The next chunk header, or `None` at the end of the replay.
+    fn read_chunk_header(&mut self) -> Result<Option<ChunkHeader>, ReplayError> {
+        let mut bytes = [0u8; CHUNK_HEADER_LEN];
+        match read_full(&mut self.input, &mut bytes)? {
+            0 => Ok(None),
+            CHUNK_HEADER_LEN => ChunkHeader::from_bytes(&bytes).map(Some),
+            _ => {
+                self.truncated = true;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Read the body `header` describes. `false` if it was cut short.
+    fn load_chunk(&mut self, header: &ChunkHeader) -> Result<bool, ReplayError> {
+        self.chunk.resize(header.len as usize, 0);
+        self.offset = 0;
+        if read_full(&mut self.input, &mut self.chunk)? < self.chunk.len() {
+            self.chunk.clear();
+            self.truncated = true;
+            return Ok(false);
+        }
+        if checksum(&self.chunk) != header.checksum {
+            self.chunk.clear();
+            return Err(ReplayError::Corrupt(format!(
+                "checksum mismatch in chunk at tick {}",
+                header.first_tick
+            )));
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read + Seek> ReplayReader<R> {
+    /// Position the reader so the next tick read is the first at or after
+    /// `tick`, skipping whole chunks without reading them. Only seeks forward.
+    pub fn skip_to(&mut self, tick: Tick) -> Result<(), ReplayError> {
+        loop {
+            while self.offset < self.chunk.len() {
+                let mut rest = &self.chunk[self.offset..];
+                let Some(next) = read_record::<ReplayTick>(&mut rest)? else {
+                    break;
+                };
+                if next.tick >= tick {
+                    return Ok(());
+                }
+                self.offset = self.chunk.len() - rest.len();
+            }
+            let Some(header) = self.read_chunk_header()? else {
+                return Ok(());
+            };
+            let last_tick = header.first_tick.saturating_add(header.ticks.saturating_sub(1));
+            if last_tick < tick {
+                self.chunk.clear();
+                self.offset = 0;
+                self.input.seek(SeekFrom::Current(i64::from(header.len)))?;
+            } else if !self.load_chunk(&header)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for ReplayReader<R> {
+    type Item = Result<ReplayTick, ReplayError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_tick().transpose()
+    }
+}
+
+/// Read until `buf` is full or the input ends; how many bytes were read.
+fn read_full(input: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match input.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn read_record<T: for<'a> Deserialize<'a>>(input: &mut impl Read) -> Result<Option<T>, ReplayError> {
+    let mut len = [0u8; 4];
+    match input.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_le_bytes(len);
+    if len > MAX_RECORD_LEN {
+        return Err(ReplayError::Corrupt(format!("record of {len} bytes")));
+    }
+    let mut bytes = vec![0u8; len as usize];
+    input.read_exact(&mut bytes).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => ReplayError::Corrupt("truncated record".into()),
+        _ => ReplayError::Io(e),
+    })?;
+    postcard::from_bytes(&bytes)
+        .map(Some)
+        .map_err(|e| ReplayError::Corrupt(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn header() -> ReplayHeader {
+        ReplayHeader {
+            game_id: "net_pong".into(),
+            player_count: 2,
+            tick_rate: 64,
+            seed: 42,
+        }
+    }
+
+    fn record_ticks(ticks: Tick, chunk_ticks: u32) -> Vec<u8> {
+        let mut writer = ReplayWriter::new(Vec::new(), &header())
+            .unwrap()
+            .with_chunk_ticks(chunk_ticks);
+        for tick in 0..ticks {
+            writer.write_tick(tick, &[vec![tick as u8], vec![0xFF]]).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    fn record_three_ticks() -> Vec<u8> {
+        record_ticks(3, DEFAULT_CHUNK_TICKS)
+    }
+
+    #[test]
+    fn write_then_read_round_trip() {
+        // given a recorded replay
+        let bytes = record_three_ticks();
+
+        // when it is read back
+        let reader = ReplayReader::open(bytes.as_slice()).unwrap();
+        let recorded_header = reader.header().clone();
+        let ticks: Vec<ReplayTick> = reader.map(Result::unwrap).collect();
+
+        // then the header and every tick match
+        assert_eq!(recorded_header, header());
+        assert_eq!(ticks.len(), 3);
+        assert_eq!(ticks[2], ReplayTick { tick: 2, inputs: vec![vec![2], vec![0xFF]] });
+    }
+
+    #[test]
+    fn recording_is_byte_for_byte_reproducible() {
+        assert_eq!(record_three_ticks(), record_three_ticks());
+    }
+
+    #[test]
+    fn ticks_span_chunks() {
+        let bytes = record_ticks(10, 3);
+        let ticks: Vec<Tick> = ReplayReader::open(bytes.as_slice())
+            .unwrap()
+            .map(|tick| tick.unwrap().tick)
+            .collect();
+        assert_eq!(ticks, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn wrong_magic_is_rejected() {
+        let mut bytes = record_three_ticks();
+        bytes[0] = b'X';
+        assert!(matches!(ReplayReader::open(bytes.as_slice()), Err(ReplayError::BadMagic)));
+    }
+
+    #[test]
+    fn future_version_is_rejected() {
+        let mut bytes = record_three_ticks();
+        bytes[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            ReplayReader::open(bytes.as_slice()),
+            Err(ReplayError::UnsupportedVersion(v)) if v == FORMAT_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn chunk_cut_short_ends_the_replay() {
+        // given a recording that crashed partway through writing its last chunk
+        let bytes = record_ticks(7, 3);
+        let truncated = &bytes[..bytes.len() - 1];
+
+        // when it is read
+        let mut reader = ReplayReader::open(truncated).unwrap();
+        let ticks: Vec<Tick> = reader.by_ref().map(|tick| tick.unwrap().tick).collect();
+
+        // then every complete chunk plays and the reader says it was cut short
+        assert_eq!(ticks, (0..6).collect::<Vec<_>>());
+        assert!(reader.truncated());
+    }
+
+    #[test]
+    fn damaged_chunk_is_corrupt() {
+        // given a replay with a flipped byte in its only chunk
+        let mut bytes = record_three_ticks();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+
+        // when it is read
+        let results: Vec<_> = ReplayReader::open(bytes.as_slice()).unwrap().collect();
+
+        // then the checksum catches it
+        assert!(matches!(results.last(), Some(Err(ReplayError::Corrupt(_)))));
+    }
+
+    #[test]
+    fn skip_to_lands_on_the_requested_tick() {
+        // given a long replay in small chunks
+        let bytes = record_ticks(100, 8);
+        let mut reader = ReplayReader::open(Cursor::new(bytes)).unwrap();
+
+        // when skipping ahead twice
+        reader.skip_to(42).unwrap();
+        let first = reader.next_tick().unwrap().unwrap().tick;
+        reader.skip_to(90).unwrap();
+        let second = reader.next_tick().unwrap().unwrap().tick;
+
+        // then each read resumes exactly there
+        assert_eq!((first, second), (42, 90));
+    }
+}
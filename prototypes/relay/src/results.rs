@@ -0,0 +1,125 @@
+//! The match results log.
+//!
+//! With `--results-log <path>`, every match that started gets one line of
+//! JSON appended to `path` when it ends, however it ends, so a relay that
+//! runs for weeks keeps its history in a file `jq` can read:
+//!
+//! ```text
+//! {"match_id":3,"game_id":"net_pong","room":"office-lounge","players":["alice","bob"],
+//!  "scores":[11,7],"end":"player_left","duration_ms":184023,"ticks":11041,
+//!  "finished_unix_ms":1760000000000}
+//! ```
+//!
+//! (one line in the file). The relay never looks inside inputs, so `scores`
+//! is whatever the players last reported with
+//! [`crate::ClientMessage::Scores`], and `null` if they never did.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// How a match came to end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchEnd {
+    /// A player said Goodbye, timed out or was kicked.
+    PlayerLeft,
+    /// A player ended it with `Abort`.
+    Aborted,
+    /// The operator closed it.
+    Closed,
+    /// Nobody had been heard from for the room TTL.
+    Idle,
+    /// The game was over and every player asked for another; the next one
+    /// gets a line of its own.
+    Rematch,
+}
+
+/// One line of the results log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchResult {
+    pub match_id: u32,
+    pub game_id: String,
+    /// The standing room it was played in, if any.
+    pub room: Option<String>,
+    /// Names by slot, as they were when the match started.
+    pub players: Vec<String>,
+    /// By slot, as last reported by a player.
+    pub scores: Option<Vec<u32>>,
+    pub end: MatchEnd,
+    pub duration_ms: u64,
+    /// Ticks broadcast, counting from tick 0 even for a resumed match.
+    pub ticks: u64,
+    pub finished_unix_ms: u64,
+}
+
+/// Milliseconds since the Unix epoch, for [`MatchResult::finished_unix_ms`].
+pub fn unix_ms_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Add `result` to the end of the log at `path`, creating it if need be.
+pub fn append(path: &Path, result: &MatchResult) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_string(result).map_err(io::Error::other)?;
+    line.push('\n');
+    // One write per line, so two relays sharing a file cannot interleave
+    // within a record.
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(match_id: u32, end: MatchEnd) -> MatchResult {
+        MatchResult {
+            match_id,
+            game_id: "net_pong".into(),
+            room: None,
+            players: vec!["alice".into(), "bob".into()],
+            scores: Some(vec![11, 7]),
+            end,
+            duration_ms: 60_000,
+            ticks: 3600,
+            finished_unix_ms: 1_760_000_000_000,
+        }
+    }
+
+    #[test]
+    fn each_match_is_one_line_appended() {
+        // given a log that does not exist yet
+        let path = std::env::temp_dir()
+            .join(format!("relay_results_{}", std::process::id()))
+            .join("results.jsonl");
+        let _ = std::fs::remove_file(&path);
+
+        // when two matches end
+        append(&path, &result(1, MatchEnd::PlayerLeft)).unwrap();
+        append(&path, &result(2, MatchEnd::Idle)).unwrap();
+
+        // then each is a line of JSON that reads back the same
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<MatchResult> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![result(1, MatchEnd::PlayerLeft), result(2, MatchEnd::Idle)]
+        );
+        assert!(contents.contains("\"end\":\"player_left\""));
+    }
+}
@@ -0,0 +1,394 @@
+//! Standing rooms: named rooms that outlive any one match.
+//!
+//! A standing room (e.g. `office-lounge`) is created once by the relay
+//! operator with an owner secret. Its settings are pushed to every player who
+//! joins it, and its leaderboard accumulates across matches. The directory is
+//! saved as TOML in the relay's data directory so rooms survive restarts.
+//!
+//! The owner changes a room remotely with [`crate::ClientMessage::RoomAdmin`],
+//! proving ownership with the secret given at creation. That includes its
+//! [`RoomPrivacy`]: not everyone wants their 11–0 loss kept or watched.
+//!
+//! A standing room has at most one match at a time; latecomers queue for it.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ConfigValue;
+
+pub const MIN_NAME_LEN: usize = 3;
+pub const MAX_NAME_LEN: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoomNameError {
+    TooShort,
+    TooLong,
+    /// Only lowercase ASCII letters, digits, and `-` are allowed.
+    InvalidCharacter(char),
+    /// Names may not start or end with `-`.
+    EdgeHyphen,
+}
+
+impl std::fmt::Display for RoomNameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoomNameError::TooShort => write!(f, "room names need at least {MIN_NAME_LEN} characters"),
+            RoomNameError::TooLong => write!(f, "room names are at most {MAX_NAME_LEN} characters"),
+            RoomNameError::InvalidCharacter(c) => {
+                write!(f, "{c:?} is not allowed; use a-z, 0-9 and -")
+            }
+            RoomNameError::EdgeHyphen => f.write_str("room names cannot start or end with -"),
+        }
+    }
+}
+
+/// Room names are lowercase so `Office-Lounge` and `office-lounge` cannot
+/// both exist and confuse the regulars.
+pub fn validate_room_name(name: &str) -> Result<(), RoomNameError> {
+    if name.len() < MIN_NAME_LEN {
+        return Err(RoomNameError::TooShort);
+    }
+    if name.len() > MAX_NAME_LEN {
+        return Err(RoomNameError::TooLong);
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '-'))
+    {
+        return Err(RoomNameError::InvalidCharacter(c));
+    }
+    if name.starts_with('-') || name.ends_with('-') {
+        return Err(RoomNameError::EdgeHyphen);
+    }
+    Ok(())
+}
+
+/// One player's record in a room's leaderboard.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub matches_played: u32,
+    pub ticks_played: u64,
+}
+
+/// What a room's owner keeps to the room. Everything is open by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomPrivacy {
+    /// Only the players may see matches here; no one joins to watch.
+    pub no_spectators: bool,
+    /// Left out of [`crate::RelayMessage::RoomList`]; joining takes the name.
+    pub hidden: bool,
+    /// Matches here are neither credited on the leaderboard nor written to
+    /// tick logs.
+    pub no_history: bool,
+}
+
+impl std::fmt::Display for RoomPrivacy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let flags: Vec<&str> = [
+            (self.no_spectators, "no spectators"),
+            (self.hidden, "hidden"),
+            (self.no_history, "no history"),
+        ]
+        .into_iter()
+        .filter_map(|(set, label)| set.then_some(label))
+        .collect();
+        if flags.is_empty() {
+            f.write_str("open")
+        } else {
+            f.write_str(&flags.join(", "))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StandingRoom {
+    pub owner_secret: String,
+    /// Config entries pushed to players on joining, stored as the text an
+    /// operator would type so the file stays hand-editable.
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
+    /// Keyed by player name.
+    #[serde(default)]
+    pub leaderboard: BTreeMap<String, LeaderboardEntry>,
+    #[serde(default)]
+    pub privacy: RoomPrivacy,
+}
+
+impl StandingRoom {
+    pub fn config_entries(&self) -> Vec<(String, ConfigValue)> {
+        self.settings
+            .iter()
+            .map(|(key, value)| (key.clone(), ConfigValue::parse(value)))
+            .collect()
+    }
+
+    /// Leaderboard sorted by matches played, most first.
+    pub fn standings(&self) -> Vec<(&str, &LeaderboardEntry)> {
+        let mut standings: Vec<(&str, &LeaderboardEntry)> = self
+            .leaderboard
+            .iter()
+            .map(|(name, entry)| (name.as_str(), entry))
+            .collect();
+        standings.sort_by(|a, b| b.1.matches_played.cmp(&a.1.matches_played).then(a.0.cmp(b.0)));
+        standings
+    }
+}
+
+/// One standing room as a room browser sees it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoomInfo {
+    pub name: String,
+    /// The game being played there, if a match is forming or running.
+    pub game_id: Option<String>,
+    pub players: u8,
+    /// Median round-trip time to the relay of the room's players, in
+    /// milliseconds: the ping a newcomer can expect. The last known value is
+    /// kept after a match ends; `None` if no match there has been measured.
+    pub median_rtt_ms: Option<u32>,
+    /// Never `hidden`: hidden rooms are not listed.
+    pub privacy: RoomPrivacy,
+}
+
+/// A change the owner of a standing room can make.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RoomCommand {
+    SetSetting { key: String, value: ConfigValue },
+    ClearSetting { key: String },
+    ResetLeaderboard,
+    SetPrivacy { privacy: RoomPrivacy },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoomError {
+    BadName(RoomNameError),
+    AlreadyExists,
+    NoSuchRoom,
+    WrongSecret,
+}
+
+impl std::fmt::Display for RoomError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoomError::BadName(e) => write!(f, "bad room name: {e}"),
+            RoomError::AlreadyExists => f.write_str("room already exists"),
+            RoomError::NoSuchRoom => f.write_str("no such room"),
+            RoomError::WrongSecret => f.write_str("wrong owner secret"),
+        }
+    }
+}
+
+/// Every standing room on this relay, keyed by name.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RoomDirectory {
+    #[serde(flatten)]
+    rooms: BTreeMap<String, StandingRoom>,
+}
+
+impl RoomDirectory {
+    pub fn load(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!("relay: ignoring unreadable {}: {e}", path.display());
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) {
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let contents = toml::to_string_pretty(&self).expect("failed to serialize rooms");
+        if let Err(e) = std::fs::write(path, contents) {
+            eprintln!("relay: failed to write {}: {e}", path.display());
+        }
+    }
+
+    pub fn path_from_data_dir(data_dir: &Path) -> PathBuf {
+        data_dir.join("rooms.toml")
+    }
+
+    pub fn get(&self, name: &str) -> Option<&StandingRoom> {
+        self.rooms.get(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.rooms.keys().map(String::as_str).collect()
+    }
+
+    pub fn create(&mut self, name: &str, owner_secret: &str) -> Result<(), RoomError> {
+        validate_room_name(name).map_err(RoomError::BadName)?;
+        if self.rooms.contains_key(name) {
+            return Err(RoomError::AlreadyExists);
+        }
+        self.rooms.insert(
+            name.to_string(),
+            StandingRoom {
+                owner_secret: owner_secret.to_string(),
+                ..StandingRoom::default()
+            },
+        );
+        Ok(())
+    }
+
+    /// Operator-only: no secret needed.
+    pub fn remove(&mut self, name: &str) -> Result<(), RoomError> {
+        self.rooms.remove(name).map(|_| ()).ok_or(RoomError::NoSuchRoom)
+    }
+
+    /// Apply an owner's command after checking their secret.
+    pub fn apply(&mut self, name: &str, owner_secret: &str, command: RoomCommand) -> Result<(), RoomError> {
+        let room = self.rooms.get_mut(name).ok_or(RoomError::NoSuchRoom)?;
+        if room.owner_secret != owner_secret {
+            return Err(RoomError::WrongSecret);
+        }
+        match command {
+            RoomCommand::SetSetting { key, value } => {
+                room.settings.insert(key, value.to_string());
+            }
+            RoomCommand::ClearSetting { key } => {
+                room.settings.remove(&key);
+            }
+            RoomCommand::ResetLeaderboard => room.leaderboard.clear(),
+            RoomCommand::SetPrivacy { privacy } => room.privacy = privacy,
+        }
+        Ok(())
+    }
+
+    /// Credit every player with a finished match of `ticks` ticks, unless
+    /// the room keeps no history.
+    pub fn record_match(&mut self, name: &str, players: &[String], ticks: u64) {
+        let Some(room) = self.rooms.get_mut(name) else {
+            return;
+        };
+        if room.privacy.no_history {
+            return;
+        }
+        for player in players {
+            let entry = room.leaderboard.entry(player.clone()).or_default();
+            entry.matches_played += 1;
+            entry.ticks_played += ticks;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn room_names_follow_the_rules() {
+        assert_eq!(validate_room_name("office-lounge"), Ok(()));
+        assert_eq!(validate_room_name("ab"), Err(RoomNameError::TooShort));
+        assert_eq!(validate_room_name(&"a".repeat(MAX_NAME_LEN + 1)), Err(RoomNameError::TooLong));
+        assert_eq!(validate_room_name("Office"), Err(RoomNameError::InvalidCharacter('O')));
+        assert_eq!(validate_room_name("-lounge"), Err(RoomNameError::EdgeHyphen));
+    }
+
+    #[test]
+    fn owner_commands_require_the_secret() {
+        // given a room owned by "s3cret"
+        let mut directory = RoomDirectory::default();
+        directory.create("office-lounge", "s3cret").unwrap();
+        let command = RoomCommand::SetSetting {
+            key: "metrics.enabled".into(),
+            value: ConfigValue::Bool(true),
+        };
+
+        // when someone without the secret changes it, then the owner does
+        let stranger = directory.apply("office-lounge", "guess", command.clone());
+        let owner = directory.apply("office-lounge", "s3cret", command);
+
+        // then only the owner's change lands
+        assert_eq!(stranger, Err(RoomError::WrongSecret));
+        assert_eq!(owner, Ok(()));
+        assert_eq!(
+            directory.get("office-lounge").unwrap().config_entries(),
+            vec![("metrics.enabled".to_string(), ConfigValue::Bool(true))]
+        );
+    }
+
+    #[test]
+    fn duplicate_room_is_refused() {
+        let mut directory = RoomDirectory::default();
+        directory.create("office-lounge", "a").unwrap();
+        assert_eq!(directory.create("office-lounge", "b"), Err(RoomError::AlreadyExists));
+    }
+
+    #[test]
+    fn leaderboard_accumulates_across_matches() {
+        // given a room
+        let mut directory = RoomDirectory::default();
+        directory.create("office-lounge", "s3cret").unwrap();
+        let players = ["alice".to_string(), "bob".to_string()];
+
+        // when two matches finish, alice playing both
+        directory.record_match("office-lounge", &players, 100);
+        directory.record_match("office-lounge", &players[..1], 50);
+
+        // then alice leads
+        let room = directory.get("office-lounge").unwrap();
+        let standings = room.standings();
+        assert_eq!(standings[0].0, "alice");
+        assert_eq!(
+            *standings[0].1,
+            LeaderboardEntry {
+                matches_played: 2,
+                ticks_played: 150
+            }
+        );
+    }
+
+    #[test]
+    fn private_room_keeps_no_history() {
+        // given a room whose owner turned history off
+        let mut directory = RoomDirectory::default();
+        directory.create("office-lounge", "s3cret").unwrap();
+        let privacy = RoomPrivacy {
+            no_history: true,
+            ..RoomPrivacy::default()
+        };
+        directory
+            .apply("office-lounge", "s3cret", RoomCommand::SetPrivacy { privacy })
+            .unwrap();
+
+        // when a match finishes there
+        directory.record_match("office-lounge", &["alice".to_string()], 100);
+
+        // then nobody is credited
+        assert!(directory.get("office-lounge").unwrap().leaderboard.is_empty());
+    }
+
+    #[test]
+    fn save_then_load_roundtrip_preserves_rooms() {
+        // given a directory with a configured room
+        let mut directory = RoomDirectory::default();
+        directory.create("office-lounge", "s3cret").unwrap();
+        directory
+            .apply(
+                "office-lounge",
+                "s3cret",
+                RoomCommand::SetSetting {
+                    key: "retransmit.interval_ms".into(),
+                    value: ConfigValue::Int(80),
+                },
+            )
+            .unwrap();
+        directory.record_match("office-lounge", &["alice".to_string()], 10);
+
+        // when it is saved and reloaded
+        let dir = std::env::temp_dir().join(format!("relay_rooms_test_{}", std::process::id()));
+        let path = RoomDirectory::path_from_data_dir(&dir);
+        directory.save(&path);
+        let loaded = RoomDirectory::load(&path);
+
+        // then the room, its settings and its leaderboard survive
+        assert_eq!(loaded.get("office-lounge"), directory.get("office-lounge"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
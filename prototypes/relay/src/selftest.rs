@@ -0,0 +1,302 @@
+//! `relay --self-test`: a smoke test for a deployment.
+//!
+//! Starts a relay core on a loopback socket, in-process, with the settings
+//! the command line gave, and plays one match through it with two scripted
+//! clients over real UDP. Each client sends a known input every tick and
+//! checks every broadcast against what both of them sent. A tick that does
+//! not complete within [`STALL_LIMIT`], or a broadcast that differs from the
+//! script, fails the test.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::net::UdpSocket;
+use tokio::time::{MissedTickBehavior, interval, timeout};
+
+use crate::auth::{HANDSHAKE_NONCE, SessionAuth};
+use crate::metrics::Metrics;
+use crate::sequence::{SequenceCounter, SequenceFilter, Sequenced};
+use crate::server::{QUEUE_UPDATE_INTERVAL, RelayCore, Settings, SystemClock};
+use crate::sockets::UdpSockets;
+use crate::{ClientMessage, PlayerSlot, RelayMessage, Tick, deserialize, serialize};
+
+/// Ticks a self-test plays unless told otherwise.
+pub const SELF_TEST_TICKS: Tick = 1000;
+/// The relay only pairs self-test clients with each other.
+const GAME_ID: &str = "relay-self-test";
+/// Longest a tick may take, from a client sending its input to the
+/// broadcast arriving, before the test calls it a stall.
+pub const STALL_LIMIT: Duration = Duration::from_secs(2);
+/// How often a client repeats a Hello or an input nothing has come of.
+const RESEND_INTERVAL: Duration = Duration::from_millis(100);
+const RECV_BUF_SIZE: usize = 2048;
+
+/// How a self-test that passed went.
+#[derive(Debug, Clone, Copy)]
+pub struct SelfTestReport {
+    pub ticks: Tick,
+    pub elapsed: Duration,
+    /// The slowest tick, from the client's input to the broadcast.
+    pub slowest_tick: Duration,
+}
+
+/// The input `slot` sends on `tick`, which both clients can work out.
+fn scripted_input(slot: PlayerSlot, tick: Tick) -> Vec<u8> {
+    let mut input = vec![slot];
+    input.extend_from_slice(&tick.to_le_bytes());
+    input
+}
+
+/// Play `ticks` ticks through a relay with `settings`, seated two to a
+/// match, and `auth` if datagrams are to be sealed. Fails with what went
+/// wrong.
+pub async fn run(
+    settings: Settings,
+    auth: Option<SessionAuth>,
+    ticks: Tick,
+) -> Result<SelfTestReport, String> {
+    let localhost: SocketAddr = ([127, 0, 0, 1], 0).into();
+    let socket = UdpSockets::bind(&[localhost]).map_err(|e| format!("cannot bind: {e}"))?;
+    let socket = Arc::new(socket);
+    let relay_addr = socket.local_addrs()[0];
+    let settings = Settings {
+        players: 2,
+        tick_log: None,
+        record_dir: None,
+        results_log: None,
+        ..settings
+    };
+    let relay = tokio::spawn(serve(socket, settings, auth.clone()));
+
+    let started = Instant::now();
+    let clients = (
+        tokio::spawn(play(relay_addr, "left", auth.clone(), ticks)),
+        tokio::spawn(play(relay_addr, "right", auth, ticks)),
+    );
+    let outcome = tokio::try_join!(clients.0, clients.1);
+    relay.abort();
+    let (left, right) = outcome.map_err(|e| format!("client failed: {e}"))?;
+    Ok(SelfTestReport {
+        ticks,
+        elapsed: started.elapsed(),
+        slowest_tick: left?.max(right?),
+    })
+}
+
+/// The relay's half: what the binary's main loop does, without the console,
+/// discovery or WebSocket clients.
+async fn serve(socket: Arc<UdpSockets>, settings: Settings, auth: Option<SessionAuth>) {
+    let directory = std::env::temp_dir()
+        .join(format!("relay-self-test-{}", std::process::id()))
+        .join("rooms.toml");
+    let (mut core, mut dispatch_events) = RelayCore::new(
+        settings,
+        directory,
+        socket.clone(),
+        Arc::new(SystemClock),
+        auth,
+        Arc::new(Metrics::new()),
+    );
+    let mut timers = interval(QUEUE_UPDATE_INTERVAL);
+    timers.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut buf = [0u8; RECV_BUF_SIZE];
+    loop {
+        tokio::select! {
+            received = socket.recv_from(&mut buf) => {
+                if let Ok((len, src)) = received {
+                    core.handle_datagram(&buf[..len], src);
+                }
+            }
+            Some(event) = dispatch_events.recv() => core.handle_dispatch_event(event),
+            _ = timers.tick() => core.service_timers(),
+            Ok(()) = socket.writable(), if core.has_pending() => {}
+        }
+        core.flush();
+    }
+}
+
+/// One scripted client's connection to the relay.
+struct Client {
+    socket: UdpSocket,
+    auth: Option<SessionAuth>,
+    session_nonce: u64,
+    outbound: SequenceCounter,
+    inbound: SequenceFilter,
+}
+
+impl Client {
+    async fn send(&mut self, msg: ClientMessage) -> Result<(), String> {
+        let bytes = serialize(&self.outbound.wrap(msg));
+        let datagram = match &self.auth {
+            Some(auth) => auth.seal(self.session_nonce, &bytes),
+            None => bytes,
+        };
+        self.socket
+            .send(&datagram)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("cannot send: {e}"))
+    }
+
+    /// The next message from the relay, or `None` if nothing usable came
+    /// within `wait`.
+    async fn receive(&mut self, wait: Duration) -> Option<RelayMessage> {
+        let mut buf = [0u8; RECV_BUF_SIZE];
+        let deadline = tokio::time::Instant::now() + wait;
+        loop {
+            let len = tokio::time::timeout_at(deadline, self.socket.recv(&mut buf))
+                .await
+                .ok()?
+                .ok()?;
+            let mut bytes = &buf[..len];
+            if let Some(auth) = &self.auth {
+                let Some((nonce, message)) = auth.open(bytes) else {
+                    continue;
+                };
+                if self.session_nonce == HANDSHAKE_NONCE {
+                    self.session_nonce = nonce;
+                }
+                if nonce != self.session_nonce {
+                    continue;
+                }
+                bytes = message;
+            }
+            let Some(sequenced) = deserialize::<Sequenced<RelayMessage>>(bytes) else {
+                continue;
+            };
+            if self.inbound.accept(sequenced.seq).accepted() {
+                return Some(sequenced.message);
+            }
+        }
+    }
+}
+
+/// Join and play `ticks` ticks, checking every broadcast. Returns the
+/// slowest tick.
+async fn play(
+    relay_addr: SocketAddr,
+    name: &str,
+    auth: Option<SessionAuth>,
+    ticks: Tick,
+) -> Result<Duration, String> {
+    let socket = UdpSocket::bind(("127.0.0.1", 0))
+        .await
+        .map_err(|e| format!("{name}: cannot bind: {e}"))?;
+    socket
+        .connect(relay_addr)
+        .await
+        .map_err(|e| format!("{name}: cannot reach the relay: {e}"))?;
+    let mut client = Client {
+        socket,
+        auth,
+        session_nonce: HANDSHAKE_NONCE,
+        outbound: SequenceCounter::default(),
+        inbound: SequenceFilter::new(),
+    };
+
+    let slot = timeout(STALL_LIMIT, join(&mut client, name))
+        .await
+        .map_err(|_| format!("{name}: the match never started"))??;
+
+    let mut slowest = Duration::ZERO;
+    for tick in 0..ticks {
+        let sent = Instant::now();
+        let payload = scripted_input(slot, tick);
+        let expected = vec![scripted_input(0, tick), scripted_input(1, tick)];
+        loop {
+            if sent.elapsed() > STALL_LIMIT {
+                return Err(format!("{name}: stalled on tick {tick}"));
+            }
+            let input = ClientMessage::Input {
+                tick,
+                payload: payload.clone(),
+            };
+            client.send(input).await?;
+            match client.receive(RESEND_INTERVAL).await {
+                Some(RelayMessage::TickInputs { tick: got, inputs }) if got == tick => {
+                    if inputs != expected {
+                        return Err(format!(
+                            "{name}: tick {tick} came back as {inputs:?}, not {expected:?}"
+                        ));
+                    }
+                    client.send(ClientMessage::AckTick { tick }).await?;
+                    break;
+                }
+                Some(RelayMessage::TickInputs { tick: got, .. }) if got > tick => {
+                    return Err(format!("{name}: got tick {got} while waiting on {tick}"));
+                }
+                Some(
+                    msg @ (RelayMessage::PlayerLeft { .. }
+                    | RelayMessage::MatchAborted { .. }
+                    | RelayMessage::Rejected { .. }
+                    | RelayMessage::ServerShutdown),
+                ) => return Err(format!("{name}: match ended on tick {tick}: {msg:?}")),
+                // Re-sent broadcasts for ticks already played, and the rest.
+                _ => {}
+            }
+        }
+        slowest = slowest.max(sent.elapsed());
+    }
+    // No Goodbye: it would end the match before the other client has
+    // necessarily seen the last tick. The relay goes away with the test.
+    Ok(slowest)
+}
+
+/// Say Hello until the match starts, which also keeps the seat while the
+/// other client joins; returns the slot given.
+async fn join(client: &mut Client, name: &str) -> Result<PlayerSlot, String> {
+    let mut slot = None;
+    loop {
+        let hello = ClientMessage::Hello {
+            game_id: GAME_ID.into(),
+            name: name.into(),
+            room: None,
+        };
+        client.send(hello).await?;
+        match client.receive(RESEND_INTERVAL).await {
+            Some(RelayMessage::Welcome { player_slot }) => slot = Some(player_slot),
+            Some(RelayMessage::GameStart) => {
+                if let Some(slot) = slot {
+                    return Ok(slot);
+                }
+            }
+            Some(RelayMessage::Rejected { reason }) => {
+                return Err(format!("{name}: rejected: {reason}"));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> Settings {
+        Settings {
+            players: 2,
+            room_capacity: 4,
+            tick_timeout: Duration::from_millis(50),
+            tick_deadline: None,
+            input_window: 3,
+            input_history: 256,
+            rate_limit: 1000,
+            rate_burst: 1000,
+            player_timeout: Duration::from_secs(10),
+            max_pending: 64,
+            room_ttl: Duration::from_secs(60),
+            bot_fill: None,
+            tick_log: None,
+            record_dir: None,
+            results_log: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn scripted_match_passes_through_the_loopback() {
+        let report = run(settings(), None, 100).await.unwrap();
+        assert_eq!(report.ticks, 100);
+        assert!(report.slowest_tick <= STALL_LIMIT);
+    }
+}
@@ -0,0 +1,206 @@
+//! Sequence numbers and a reorder/duplicate filter for UDP datagrams.
+//!
+//! UDP may deliver datagrams late, twice, or out of order. Every message on
+//! the wire is wrapped in [`Sequenced`], numbered per sender. The receiver runs
+//! each sequence number through a [`SequenceFilter`], which accepts each
+//! number at most once, drops anything older than its window, and counts
+//! what it saw so losses are visible instead of silent.
+
+use serde::{Deserialize, Serialize};
+
+/// How far behind the newest sequence number a late datagram may arrive and
+/// still be accepted.
+pub const WINDOW: u32 = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sequenced<T> {
+    pub seq: u32,
+    pub message: T,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Newer than anything seen so far.
+    InOrder,
+    /// Older than the newest seen, but not seen before. Accepted.
+    Reordered,
+    /// Already accepted once. Dropped.
+    Duplicate,
+    /// Too far behind the newest to track. Dropped.
+    Stale,
+}
+
+impl Verdict {
+    pub fn accepted(self) -> bool {
+        matches!(self, Verdict::InOrder | Verdict::Reordered)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SequenceStats {
+    pub in_order: u64,
+    pub reordered: u64,
+    pub duplicates: u64,
+    pub stale: u64,
+    /// Sequence numbers skipped over that have not (yet) arrived.
+    pub gaps: u64,
+}
+
+/// Tracks which of the last [`WINDOW`] sequence numbers have arrived.
+#[derive(Debug, Clone, Default)]
+pub struct SequenceFilter {
+    newest: Option<u32>,
+    /// Bit `n` set means `newest - n` has been received.
+    received: u64,
+    stats: SequenceStats,
+}
+
+impl SequenceFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stats(&self) -> SequenceStats {
+        self.stats
+    }
+
+    pub fn accept(&mut self, seq: u32) -> Verdict {
+        let Some(newest) = self.newest else {
+            self.newest = Some(seq);
+            self.received = 1;
+            self.stats.in_order += 1;
+            return Verdict::InOrder;
+        };
+
+        // Serial-number order: the counter wraps, so `seq` is newer when it is
+        // less than half the number space ahead.
+        let ahead = seq.wrapping_sub(newest) as i32;
+        if ahead > 0 {
+            let advance = ahead as u32;
+            self.received = if advance >= WINDOW { 0 } else { self.received << advance };
+            self.received |= 1;
+            self.newest = Some(seq);
+            self.stats.in_order += 1;
+            self.stats.gaps += u64::from(advance - 1);
+            return Verdict::InOrder;
+        }
+
+        let behind = newest.wrapping_sub(seq);
+        if behind >= WINDOW {
+            self.stats.stale += 1;
+            return Verdict::Stale;
+        }
+        let bit = 1u64 << behind;
+        if self.received & bit != 0 {
+            self.stats.duplicates += 1;
+            return Verdict::Duplicate;
+        }
+        self.received |= bit;
+        self.stats.reordered += 1;
+        self.stats.gaps = self.stats.gaps.saturating_sub(1);
+        Verdict::Reordered
+    }
+}
+
+/// Hands out increasing sequence numbers for one sender.
+#[derive(Debug, Clone, Default)]
+pub struct SequenceCounter {
+    next: u32,
+}
+
+impl SequenceCounter {
+    pub fn wrap<T>(&mut self, message: T) -> Sequenced<T> {
+        let seq = self.next;
+        self.next = self.next.wrapping_add(1);
+        Sequenced { seq, message }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_order_sequence_is_accepted() {
+        // given a fresh filter
+        let mut filter = SequenceFilter::new();
+
+        // when sequence numbers arrive in order
+        let verdicts: Vec<Verdict> = (0..3).map(|seq| filter.accept(seq)).collect();
+
+        // then all are in order
+        assert_eq!(verdicts, vec![Verdict::InOrder; 3]);
+        assert_eq!(filter.stats().in_order, 3);
+    }
+
+    #[test]
+    fn duplicate_is_dropped() {
+        // given a filter that has seen 0 and 1
+        let mut filter = SequenceFilter::new();
+        filter.accept(0);
+        filter.accept(1);
+
+        // when 1 arrives again
+        let verdict = filter.accept(1);
+
+        // then it is a duplicate
+        assert_eq!(verdict, Verdict::Duplicate);
+        assert!(!verdict.accepted());
+        assert_eq!(filter.stats().duplicates, 1);
+    }
+
+    #[test]
+    fn late_datagram_within_window_is_accepted_once() {
+        // given a filter that saw 0 and 2 but not 1
+        let mut filter = SequenceFilter::new();
+        filter.accept(0);
+        filter.accept(2);
+        assert_eq!(filter.stats().gaps, 1);
+
+        // when 1 arrives late, then again
+        let first = filter.accept(1);
+        let second = filter.accept(1);
+
+        // then it is accepted once and the gap closes
+        assert_eq!(first, Verdict::Reordered);
+        assert_eq!(second, Verdict::Duplicate);
+        assert_eq!(filter.stats().gaps, 0);
+    }
+
+    #[test]
+    fn datagram_older_than_window_is_stale() {
+        // given a filter that has moved well past 0
+        let mut filter = SequenceFilter::new();
+        filter.accept(0);
+        filter.accept(WINDOW + 10);
+
+        // when 5 arrives
+        let verdict = filter.accept(5);
+
+        // then it is stale
+        assert_eq!(verdict, Verdict::Stale);
+    }
+
+    #[test]
+    fn sequence_carries_on_across_the_wrap() {
+        // given a filter that has seen the last two numbers before the wrap
+        let mut filter = SequenceFilter::new();
+        filter.accept(u32::MAX - 1);
+        filter.accept(u32::MAX);
+
+        // when the counter wraps and numbering starts over
+        let verdicts: Vec<Verdict> = [0, 1].map(|seq| filter.accept(seq)).to_vec();
+
+        // then the new numbers are in order and the old ones still dedup
+        assert_eq!(verdicts, vec![Verdict::InOrder; 2]);
+        assert_eq!(filter.accept(u32::MAX), Verdict::Duplicate);
+        assert_eq!(filter.stats().gaps, 0);
+    }
+
+    #[test]
+    fn counter_numbers_messages_consecutively() {
+        let mut counter = SequenceCounter::default();
+        assert_eq!(counter.wrap("a").seq, 0);
+        assert_eq!(counter.wrap("b").seq, 1);
+    }
+}
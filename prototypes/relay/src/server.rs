@@ -0,0 +1,2967 @@
+//! The relay's state machine, apart from its sockets and its clock.
+//!
+//! The relay runs on tokio. A central dispatcher owns the socket, decides who
+//! sits where, and sends every outgoing datagram; each room runs as its own
+//! task that collects inputs and retransmits on its own timer. Nothing a room
+//! does can hold up the receive path.
+//!
+//! [`RelayCore`] is the dispatcher without the I/O: the binary reads the UDP
+//! socket, stdin and its timers and hands what arrives to the core, which
+//! pairs clients into matches, drives the queue and the timeouts, and sends
+//! through a [`Socket`]. The time comes from a [`Clock`]. Tests put fakes
+//! behind both and check what would have gone out.
+//!
+//! Nobody holds a seat by going quiet. A seated player silent for
+//! [`Settings::player_timeout`] is dropped as if it had said Goodbye: in a
+//! lobby its slot opens up for the next Hello; in a match the match ends. A
+//! client waiting for a match keeps saying Hello, and one that stops for 5
+//! seconds loses its seat, so Hellos from spoofed addresses hold nothing for
+//! long. Rooms are swept as a whole too: one that has heard from none of its
+//! players for [`Settings::room_ttl`], or whose task has died, is closed and
+//! its slots freed.
+
+use std::collections::{BTreeMap, HashMap, VecDeque, btree_map};
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::sleep_until;
+use tracing::{debug, info, warn};
+
+use crate::auth::{HANDSHAKE_NONCE, SEALED_OVERHEAD, SessionAuth, new_session_nonce};
+use crate::discovery::{self, DiscoveryProbe, DiscoveryReply};
+use crate::latency::{self, RttEstimator};
+use crate::metrics::{DropReason, Metrics};
+use crate::priority::{Priority, SendQueue};
+use crate::ratelimit::RateLimiter;
+use crate::replay::{ReplayHeader, ReplayWriter};
+use crate::results::{self, MatchEnd, MatchResult};
+use crate::rooms::{RoomCommand, RoomDirectory, RoomInfo, RoomPrivacy};
+use crate::sequence::{SequenceCounter, SequenceFilter, Sequenced};
+use crate::sockets::canonical;
+use crate::ticklog::TickLog;
+use crate::tuning::Tuning;
+use crate::{
+    AbortReason, ClientMessage, ConfigValue, MAX_CHAT_LEN, PlayerNetStats, PlayerSlot,
+    RejectReason, RelayMessage, SPECTATOR_SLOT, Tick, deserialize, plausible_frame, serialize,
+};
+
+/// Where the relay's datagrams go: the UDP socket in production, a recorder
+/// in tests.
+pub trait Socket: Send + Sync {
+    /// Send one datagram without waiting. `WouldBlock` means try again later.
+    fn try_send_to(&self, datagram: &[u8], addr: SocketAddr) -> io::Result<usize>;
+}
+
+impl Socket for tokio::net::UdpSocket {
+    fn try_send_to(&self, datagram: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        tokio::net::UdpSocket::try_send_to(self, datagram, addr)
+    }
+}
+
+/// What time the relay thinks it is.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clients beyond this many waiting are rejected as Full.
+const MAX_QUEUE_LEN: usize = 16;
+/// How often [`RelayCore::service_timers`] should run.
+pub const QUEUE_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+/// A queued client that stops sending Hello for this long has given up.
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+/// A client seated in a match that has not started keeps saying Hello; one
+/// that goes this long without is taken to have never been there, such as
+/// a Hello with a spoofed source address.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Sequence state for a peer that holds no seat and has been silent this
+/// long is dropped, so spoofed or turned-away sources do not pile up.
+const PEER_IDLE_AFTER: Duration = Duration::from_secs(30);
+/// What the other players see a `--bot-fill` bot called.
+const BOT_NAME: &str = "bot";
+/// Spectators per match. Beyond this, more are rejected as Full.
+const MAX_SPECTATORS: usize = 8;
+
+pub type RoomId = u32;
+
+/// How every room on this relay runs, fixed at startup.
+pub struct Settings {
+    /// Players per match.
+    pub players: usize,
+    /// Concurrent matches. Beyond this, new clients wait in the queue.
+    pub room_capacity: usize,
+    /// How long to wait for an AckTick before re-sending TickInputs.
+    pub tick_timeout: Duration,
+    /// How long a tick waits on a missing player once another player's input
+    /// for it has arrived, before playing it with an empty input in its
+    /// place. `None` waits as long as it takes.
+    pub tick_deadline: Option<Duration>,
+    /// Future ticks a player may send input for ahead of the room.
+    pub input_window: Tick,
+    /// Past broadcasts each room keeps for `RequestTick`.
+    pub input_history: usize,
+    /// Datagrams per second, and per burst, each source may send.
+    pub rate_limit: u32,
+    pub rate_burst: u32,
+    /// A seated player silent for this long has gone away.
+    pub player_timeout: Duration,
+    /// Most clients waiting at once, seated in matches that have not started
+    /// or queued. Past this, new Hellos are turned away as Full.
+    pub max_pending: usize,
+    /// A room none of whose players has been heard from for this long is
+    /// closed, whatever state it is in.
+    pub room_ttl: Duration,
+    /// How long a match may wait for players before the relay fills its
+    /// empty seats with bots and starts it. `None` waits for people. A bot
+    /// sends the game's default input every tick, so a single player can
+    /// exercise the whole network path.
+    pub bot_fill: Option<Duration>,
+    /// Ticks each room keeps for post-mortems, and where they are written.
+    pub tick_log: Option<(usize, PathBuf)>,
+    /// Where every match's inputs are recorded as a replay, if anywhere.
+    pub record_dir: Option<PathBuf>,
+    /// The file each finished match's result is appended to, if any.
+    pub results_log: Option<PathBuf>,
+}
+
+/// What a client asked for in its Hello or Resume.
+struct Joiner {
+    addr: SocketAddr,
+    game_id: String,
+    name: String,
+    room: Option<String>,
+    /// The slot and next tick of a lost match the client is picking up.
+    resume: Option<(usize, Tick)>,
+}
+
+/// A client waiting for a player slot to free up.
+struct QueuedClient {
+    joiner: Joiner,
+    last_hello: Instant,
+}
+
+/// A finished match in a standing room, to credit on its leaderboard.
+pub struct MatchRecord {
+    room: String,
+    players: Vec<String>,
+}
+
+/// From the dispatcher to a room task.
+enum RoomEvent {
+    Seat { slot: usize, addr: SocketAddr },
+    /// The relay plays `slot` itself.
+    SeatBot { slot: usize },
+    /// A player left before the game started.
+    Vacate { slot: usize },
+    Input { slot: usize, tick: Tick, payload: Vec<u8> },
+    Ack { slot: usize, tick: Tick },
+    /// The player or spectator at `addr` missed the broadcast for `tick`.
+    RequestTick { addr: SocketAddr, tick: Tick },
+    /// The player wants the match to stop before `tick`.
+    Pause { slot: usize, tick: Tick },
+    Unpause { slot: usize },
+    /// A spectator at `addr` asked to watch, for the first time or again.
+    Watch { addr: SocketAddr },
+    Unwatch { addr: SocketAddr },
+    /// Something went wrong with a player; write the tick log, if kept.
+    DumpTickLog { reason: String },
+    /// The operator wants to know which tick the match is on.
+    Status { reply: oneshot::Sender<Tick> },
+    /// Every player of a lost match is back; play continues from `tick`.
+    Resume { tick: Tick },
+    /// Every player asked for a rematch. The task reports the finished game
+    /// if there is a result to log, then starts again from tick 0.
+    Rematch { result: Option<MatchResult> },
+    /// The operator reloaded the relay's settings.
+    Retune {
+        tick_timeout: Duration,
+        tick_deadline: Option<Duration>,
+    },
+    /// The match is over. The task reports how far it got if there is a
+    /// leaderboard to credit or a result to log, then exits.
+    Close {
+        record: Option<MatchRecord>,
+        result: Option<MatchResult>,
+    },
+}
+
+/// From WebSocket connection tasks to the dispatcher. Only the `websocket`
+/// feature opens connections.
+pub enum StreamEvent {
+    Connected {
+        addr: SocketAddr,
+        outgoing: mpsc::UnboundedSender<Vec<u8>>,
+    },
+    Datagram { addr: SocketAddr, bytes: Vec<u8> },
+    Closed { addr: SocketAddr },
+}
+
+/// From room tasks to the dispatcher; hand each to
+/// [`RelayCore::handle_dispatch_event`].
+pub enum DispatchEvent {
+    Send { msg: RelayMessage, addr: SocketAddr },
+    /// How long `addr` took to ack a TickInputs sent once.
+    RttSample { addr: SocketAddr, rtt: Duration },
+    MatchFinished {
+        record: Option<MatchRecord>,
+        result: Option<MatchResult>,
+        ticks: u64,
+    },
+}
+
+// ---------------------------------------------------------------------------
+// Room task: the lockstep exchange for one match
+// ---------------------------------------------------------------------------
+
+/// A TickInputs broadcast, kept until every player acknowledges it. Several
+/// can be outstanding: with the input window, a tick can complete before
+/// every player has received the one before it.
+struct PendingBroadcast {
+    tick: Tick,
+    inputs: Vec<Vec<u8>>,
+    acked: Vec<bool>,
+    last_sent: Instant,
+    /// Acks after a retransmit make no RTT sample: they could answer either copy.
+    retransmitted: bool,
+}
+
+/// A match's broadcasts, written as a replay with `--record-dir`. The file
+/// is created with the first tick, so a lobby that never starts leaves none
+/// behind, and finished when the room task ends, however it ends.
+struct Recording {
+    id: RoomId,
+    path: PathBuf,
+    header: ReplayHeader,
+    writer: Option<ReplayWriter<BufWriter<File>>>,
+}
+
+impl Recording {
+    fn new(dir: &Path, id: RoomId, game_id: &str, players: usize) -> Self {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis())
+            .unwrap_or_default();
+        Self {
+            id,
+            path: dir.join(format!("{game_id}-room-{id}-{millis}.arpl")),
+            // The relay sees neither the tick rate nor the seed; readers
+            // take them from the game.
+            header: ReplayHeader {
+                game_id: game_id.to_string(),
+                player_count: players as u8,
+                tick_rate: 0,
+                seed: 0,
+            },
+            writer: None,
+        }
+    }
+
+    fn record(&mut self, tick: Tick, inputs: &[Vec<u8>]) -> std::io::Result<()> {
+        let writer = match self.writer.take() {
+            Some(writer) => writer,
+            None => {
+                if let Some(dir) = self.path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                let file = BufWriter::new(File::create(&self.path)?);
+                ReplayWriter::new(file, &self.header)?
+            }
+        };
+        self.writer.insert(writer).write_tick(tick, inputs)
+    }
+
+    /// A new file for the next game in the same room. This one is finished
+    /// when dropped.
+    fn for_rematch(&self) -> Self {
+        let dir = self.path.parent().unwrap_or(Path::new("."));
+        let players = usize::from(self.header.player_count);
+        Self::new(dir, self.id, &self.header.game_id, players)
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        if let Some(writer) = self.writer.take() {
+            match writer.finish() {
+                Ok(_) => info!("recorded {}", self.path.display()),
+                Err(e) => warn!("cannot finish {}: {e}", self.path.display()),
+            }
+        }
+    }
+}
+
+/// One match's lockstep progress, owned by its room task.
+struct Lockstep {
+    players: Vec<Option<SocketAddr>>,
+    /// Sent every broadcast, but never waited on.
+    spectators: Vec<SocketAddr>,
+    /// Slots the relay plays itself, with neutral input every tick.
+    bots: Vec<bool>,
+    current_tick: Tick,
+    /// Each player's inputs from `current_tick` up to `input_window` ticks
+    /// beyond it, waiting for the room to catch up.
+    tick_inputs: Vec<BTreeMap<Tick, Vec<u8>>>,
+    input_window: Tick,
+    /// Oldest first.
+    pending_broadcasts: VecDeque<PendingBroadcast>,
+    /// The last `history_len` ticks broadcast, oldest first, for players
+    /// that missed one.
+    history: VecDeque<(Tick, Vec<Vec<u8>>)>,
+    history_len: usize,
+    tick_timeout: Duration,
+    tick_deadline: Option<Duration>,
+    /// When the current tick got its first input, while it waits for the rest.
+    waiting_since: Option<Instant>,
+    /// Who paused the match, and the tick it stops before, while paused.
+    paused: Option<(usize, Tick)>,
+    metrics: Arc<Metrics>,
+    clock: Arc<dyn Clock>,
+    /// The inputs of recent ticks, when `--tick-log` is given.
+    tick_log: Option<TickLog<Vec<Vec<u8>>>>,
+    /// Every tick's inputs, when `--record-dir` is given.
+    recording: Option<Recording>,
+    outbox: mpsc::UnboundedSender<DispatchEvent>,
+}
+
+impl Lockstep {
+    fn new(
+        settings: &Settings,
+        metrics: Arc<Metrics>,
+        clock: Arc<dyn Clock>,
+        tick_log: Option<TickLog<Vec<Vec<u8>>>>,
+        recording: Option<Recording>,
+        outbox: mpsc::UnboundedSender<DispatchEvent>,
+    ) -> Self {
+        let players = settings.players;
+        Self {
+            players: vec![None; players],
+            spectators: Vec::new(),
+            bots: vec![false; players],
+            current_tick: 0,
+            tick_inputs: vec![BTreeMap::new(); players],
+            input_window: settings.input_window,
+            pending_broadcasts: VecDeque::new(),
+            history: VecDeque::with_capacity(settings.input_history),
+            history_len: settings.input_history,
+            tick_timeout: settings.tick_timeout,
+            tick_deadline: settings.tick_deadline,
+            waiting_since: None,
+            paused: None,
+            metrics,
+            clock,
+            tick_log,
+            recording,
+            outbox,
+        }
+    }
+
+    fn send(&self, msg: RelayMessage, addr: SocketAddr) {
+        let _ = self.outbox.send(DispatchEvent::Send { msg, addr });
+    }
+
+    /// Pick up a lost match at `tick`. Nothing has been broadcast yet, and
+    /// any input held so far was numbered before the players agreed where
+    /// to start, so it is thrown away.
+    fn resume_at(&mut self, tick: Tick) {
+        self.current_tick = tick;
+        self.waiting_since = None;
+        for inputs in &mut self.tick_inputs {
+            inputs.clear();
+        }
+    }
+
+    /// Start a new game from tick 0 with the same players, forgetting
+    /// everything about the old one, and tell them.
+    fn restart(&mut self) {
+        self.resume_at(0);
+        self.pending_broadcasts.clear();
+        self.history.clear();
+        self.paused = None;
+        if let Some(recording) = &mut self.recording {
+            *recording = recording.for_rematch();
+        }
+        for addr in self.players.iter().flatten().chain(&self.spectators) {
+            self.send(RelayMessage::RematchStart, *addr);
+        }
+    }
+
+    /// Send the spectator at `addr` every broadcast from now on, and tell it
+    /// which tick that is.
+    fn watch(&mut self, addr: SocketAddr) {
+        if !self.spectators.contains(&addr) {
+            self.spectators.push(addr);
+        }
+        let tick = self.current_tick;
+        self.send(RelayMessage::Spectating { tick }, addr);
+    }
+
+    /// Whether every player's input for the current tick is in. A bot's
+    /// always is.
+    fn all_inputs_received(&self) -> bool {
+        self.tick_inputs
+            .iter()
+            .zip(&self.bots)
+            .all(|(inputs, bot)| *bot || inputs.contains_key(&self.current_tick))
+    }
+
+    /// Whether `slot` has somebody in it, person or bot.
+    fn occupied(&self, slot: usize) -> bool {
+        self.players[slot].is_some() || self.bots[slot]
+    }
+
+    /// `slot` has received `tick` and, since clients take ticks in order,
+    /// every tick before it.
+    fn acknowledge(&mut self, slot: usize, tick: Tick) {
+        for pending in self.pending_broadcasts.iter_mut() {
+            if pending.tick > tick || pending.acked[slot] {
+                continue;
+            }
+            pending.acked[slot] = true;
+            if pending.tick == tick
+                && !pending.retransmitted
+                && let Some(addr) = self.players[slot]
+            {
+                let rtt = self.clock.now().saturating_duration_since(pending.last_sent);
+                let _ = self.outbox.send(DispatchEvent::RttSample { addr, rtt });
+            }
+        }
+        self.pending_broadcasts
+            .retain(|pending| !pending.acked.iter().all(|acked| *acked));
+    }
+
+    /// Record one player's input, and broadcast every tick that is now
+    /// complete. Input for a tick up to `input_window` ahead is held until
+    /// the room gets there, so a client running slightly ahead loses nothing.
+    /// Input acknowledges nothing: clients send it ahead by their input
+    /// delay, before the broadcasts in between have reached them. Only
+    /// `AckTick` stops a re-send.
+    fn receive_input(&mut self, slot: usize, tick: Tick, payload: Vec<u8>) {
+        let window_end = self.current_tick.saturating_add(self.input_window);
+        if tick < self.current_tick || tick > window_end {
+            // Ignore inputs for ticks already played or too far ahead.
+            self.metrics.dropped(DropReason::WrongTick);
+            return;
+        }
+
+        self.tick_inputs[slot].insert(tick, payload);
+        if tick == self.current_tick {
+            self.waiting_since.get_or_insert(self.clock.now());
+        }
+        while self.all_inputs_received() {
+            self.advance();
+        }
+    }
+
+    /// Broadcast the current tick's inputs and move on to the next tick.
+    fn advance(&mut self) {
+        // A bot's input is empty, which clients read as the default input.
+        let inputs: Vec<Vec<u8>> = self
+            .tick_inputs
+            .iter_mut()
+            .map(|inputs| inputs.remove(&self.current_tick).unwrap_or_default())
+            .collect();
+        if let Some(log) = &mut self.tick_log {
+            log.record(self.current_tick, inputs.clone());
+        }
+        if let Some(recording) = &mut self.recording
+            && let Err(e) = recording.record(self.current_tick, &inputs)
+        {
+            warn!("stopped recording {}: {e}", recording.path.display());
+            self.recording = None;
+        }
+        if self.history_len > 0 {
+            if self.history.len() == self.history_len {
+                self.history.pop_front();
+            }
+            self.history.push_back((self.current_tick, inputs.clone()));
+        }
+        for addr in self.players.iter().flatten().chain(&self.spectators) {
+            self.send(
+                RelayMessage::TickInputs {
+                    tick: self.current_tick,
+                    inputs: inputs.clone(),
+                },
+                *addr,
+            );
+        }
+        self.pending_broadcasts.push_back(PendingBroadcast {
+            tick: self.current_tick,
+            inputs,
+            // Bots have nothing to acknowledge.
+            acked: self.bots.clone(),
+            last_sent: self.clock.now(),
+            retransmitted: false,
+        });
+
+        // Advance to next tick.
+        self.current_tick += 1;
+        self.metrics.tick_advanced();
+        let started = self
+            .tick_inputs
+            .iter()
+            .any(|inputs| inputs.contains_key(&self.current_tick));
+        self.waiting_since = started.then(|| self.clock.now());
+    }
+
+    /// When the current tick's missing input is due to be filled in, if the
+    /// room has a deadline and is waiting on someone. Never at a pause.
+    fn substitute_at(&self) -> Option<Instant> {
+        if self.paused.is_some_and(|(_, tick)| self.current_tick >= tick) {
+            return None;
+        }
+        Some(self.waiting_since? + self.tick_deadline?)
+    }
+
+    /// Stop the match before `tick`, or the current tick if that has been
+    /// played, and tell everyone where. A pause already in place stays as it
+    /// is and is announced again, for anyone who missed it.
+    fn pause(&mut self, slot: usize, tick: Tick) {
+        let (by_slot, tick) = *self
+            .paused
+            .get_or_insert((slot, tick.max(self.current_tick)));
+        let msg = RelayMessage::Paused {
+            by_slot: by_slot as PlayerSlot,
+            tick,
+        };
+        for addr in self.players.iter().flatten() {
+            self.send(msg.clone(), *addr);
+        }
+    }
+
+    /// Carry on after a pause, and tell everyone. Answered even when not
+    /// paused, so a player repeating `Unpause` hears that it worked.
+    fn unpause(&mut self, slot: usize) {
+        if self.paused.take().is_some() && self.waiting_since.is_some() {
+            // The deadline runs from now, not from before the pause.
+            self.waiting_since = Some(self.clock.now());
+        }
+        let msg = RelayMessage::Unpaused {
+            by_slot: slot as PlayerSlot,
+        };
+        for addr in self.players.iter().flatten() {
+            self.send(msg.clone(), *addr);
+        }
+    }
+
+    /// Once the deadline has passed, play the current tick with an empty
+    /// input for every player it is still waiting on, so one laggy client
+    /// slows the match down instead of freezing it. Clients read an empty
+    /// input as their game's default. A room with an empty seat is left
+    /// alone: there is nobody to stand in for.
+    fn substitute_missing(&mut self, now: Instant) {
+        let due = self.substitute_at().is_some_and(|due| now >= due);
+        if !due || !(0..self.players.len()).all(|slot| self.occupied(slot)) {
+            return;
+        }
+        let tick = self.current_tick;
+        for inputs in &mut self.tick_inputs {
+            if let btree_map::Entry::Vacant(entry) = inputs.entry(tick) {
+                entry.insert(Vec::new());
+                self.metrics.input_substituted();
+            }
+        }
+        while self.all_inputs_received() {
+            self.advance();
+        }
+    }
+
+    /// Send `addr` the broadcast for `tick` again, if it is still in history.
+    fn resend_tick(&self, addr: SocketAddr, tick: Tick) {
+        let Some(oldest) = self.history.front().map(|(tick, _)| *tick) else {
+            return;
+        };
+        // History holds consecutive ticks, so the index is the offset.
+        let Some((_, inputs)) = tick
+            .checked_sub(oldest)
+            .and_then(|offset| self.history.get(offset as usize))
+        else {
+            return;
+        };
+        let msg = RelayMessage::TickInputs {
+            tick,
+            inputs: inputs.clone(),
+        };
+        self.send(msg, addr);
+    }
+
+    /// When the next outstanding broadcast is due to be re-sent, if any is
+    /// outstanding.
+    fn next_retransmit(&self) -> Option<Instant> {
+        self.pending_broadcasts
+            .iter()
+            .map(|pending| pending.last_sent + self.tick_timeout)
+            .min()
+    }
+
+    /// Re-send each outstanding TickInputs to every player that has not acked
+    /// it, every `tick_timeout` until they all have. A single lost broadcast
+    /// would otherwise stall the match: nobody can send the next tick's input
+    /// without it.
+    fn retransmit_unacked(&mut self, now: Instant) {
+        for pending in self.pending_broadcasts.iter_mut() {
+            if now.duration_since(pending.last_sent) < self.tick_timeout {
+                continue;
+            }
+            pending.last_sent = now;
+            pending.retransmitted = true;
+            for (slot, addr) in self.players.iter().enumerate() {
+                if let Some(addr) = addr
+                    && !pending.acked[slot]
+                {
+                    let msg = RelayMessage::TickInputs {
+                        tick: pending.tick,
+                        inputs: pending.inputs.clone(),
+                    };
+                    let _ = self.outbox.send(DispatchEvent::Send { msg, addr: *addr });
+                }
+            }
+        }
+    }
+}
+
+/// Run one room until the dispatcher closes it.
+async fn run_room(
+    mut events: mpsc::UnboundedReceiver<RoomEvent>,
+    lockstep: Lockstep,
+) {
+    let mut lockstep = lockstep;
+
+    loop {
+        // Sleep until the oldest unacked broadcast or the tick deadline is
+        // due, rather than on a fixed interval, so re-sends go out
+        // `tick_timeout` apart and not up to twice that.
+        let wake_at = [lockstep.next_retransmit(), lockstep.substitute_at()]
+            .into_iter()
+            .flatten()
+            .min();
+        let wake = sleep_until(wake_at.unwrap_or_else(Instant::now).into());
+        tokio::select! {
+            event = events.recv() => {
+                let Some(event) = event else {
+                    return;
+                };
+                match event {
+                    RoomEvent::Seat { slot, addr } => lockstep.players[slot] = Some(addr),
+                    RoomEvent::SeatBot { slot } => lockstep.bots[slot] = true,
+                    RoomEvent::Vacate { slot } => lockstep.players[slot] = None,
+                    RoomEvent::Input { slot, tick, payload } => {
+                        lockstep.receive_input(slot, tick, payload);
+                    }
+                    RoomEvent::Ack { slot, tick } => lockstep.acknowledge(slot, tick),
+                    RoomEvent::RequestTick { addr, tick } => lockstep.resend_tick(addr, tick),
+                    RoomEvent::Pause { slot, tick } => lockstep.pause(slot, tick),
+                    RoomEvent::Unpause { slot } => lockstep.unpause(slot),
+                    RoomEvent::Watch { addr } => lockstep.watch(addr),
+                    RoomEvent::Unwatch { addr } => lockstep.spectators.retain(|a| *a != addr),
+                    RoomEvent::DumpTickLog { reason } => {
+                        if let Some(log) = &mut lockstep.tick_log {
+                            log.dump(&reason);
+                        }
+                    }
+                    RoomEvent::Status { reply } => {
+                        let _ = reply.send(lockstep.current_tick);
+                    }
+                    RoomEvent::Resume { tick } => lockstep.resume_at(tick),
+                    RoomEvent::Rematch { result } => {
+                        if result.is_some() {
+                            let ticks = u64::from(lockstep.current_tick);
+                            let _ = lockstep.outbox.send(DispatchEvent::MatchFinished {
+                                record: None,
+                                result,
+                                ticks,
+                            });
+                        }
+                        lockstep.restart();
+                    }
+                    RoomEvent::Retune { tick_timeout, tick_deadline } => {
+                        lockstep.tick_timeout = tick_timeout;
+                        lockstep.tick_deadline = tick_deadline;
+                    }
+                    RoomEvent::Close { record, result } => {
+                        if record.is_some() || result.is_some() {
+                            let ticks = u64::from(lockstep.current_tick);
+                            let _ = lockstep.outbox.send(DispatchEvent::MatchFinished {
+                                record,
+                                result,
+                                ticks,
+                            });
+                        }
+                        return;
+                    }
+                }
+            }
+            _ = wake, if wake_at.is_some() => {
+                let now = lockstep.clock.now();
+                lockstep.retransmit_unacked(now);
+                lockstep.substitute_missing(now);
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Dispatcher: membership, queueing, and the socket
+// ---------------------------------------------------------------------------
+
+/// The dispatcher's view of one room: who sits where. The lockstep exchange
+/// itself runs in the room's task.
+struct RoomHandle {
+    game_id: String,
+    /// The standing room this match is in, if any.
+    room: Option<String>,
+    players: Vec<Option<SocketAddr>>,
+    /// Slots the relay fills itself with `--bot-fill`.
+    bots: Vec<bool>,
+    player_names: Vec<Option<String>>,
+    /// When the room opened, for `--bot-fill`.
+    opened: Instant,
+    /// When each slot's player was last heard from.
+    last_seen: Vec<Instant>,
+    /// Datagrams accepted from each slot's player since it was seated.
+    packets: Vec<u32>,
+    rtt: Vec<RttEstimator>,
+    /// The next tick each seated player asked to resume from; `None` for
+    /// players that said Hello. A room holds only one kind.
+    resume_ticks: Vec<Option<Tick>>,
+    /// Where a resumed match picked up, once every player was back.
+    resumed_at: Option<Tick>,
+    game_started: bool,
+    /// When the game started, and who was in it, for the results log.
+    started_at: Option<Instant>,
+    lineup: Vec<String>,
+    /// The score by slot, as a player last reported it.
+    scores: Option<Vec<u32>>,
+    /// Which players asked for a rematch since the game started.
+    rematch: Vec<bool>,
+    /// Who is watching, and when each was last heard from.
+    spectators: HashMap<SocketAddr, Instant>,
+    events: mpsc::UnboundedSender<RoomEvent>,
+}
+
+impl RoomHandle {
+    /// Whether `joiner` can take a seat in this match right now.
+    fn accepts(&self, joiner: &Joiner) -> bool {
+        let mixed = self
+            .players
+            .iter()
+            .zip(&self.resume_ticks)
+            .any(|(addr, tick)| addr.is_some() && tick.is_some() != joiner.resume.is_some());
+        !self.game_started
+            && self.game_id == joiner.game_id
+            && self.room == joiner.room
+            && !mixed
+            && self.slot_for(joiner).is_some()
+    }
+
+    /// The slot `joiner` would take: the one it held, when resuming, or
+    /// else the first empty one.
+    fn slot_for(&self, joiner: &Joiner) -> Option<usize> {
+        match joiner.resume {
+            Some((slot, _)) => self.players.get(slot)?.is_none().then_some(slot),
+            None => self.next_empty_slot(),
+        }
+    }
+
+    /// The slot of the player at `addr`. Every address reaching the core is
+    /// made [`canonical`] first, so an IPv4 client is found whether it came
+    /// in over IPv4 or through a dual-stack IPv6 socket.
+    fn find_player(&self, addr: &SocketAddr) -> Option<usize> {
+        self.players.iter().position(|slot| slot.as_ref() == Some(addr))
+    }
+
+    fn next_empty_slot(&self) -> Option<usize> {
+        (0..self.players.len()).find(|slot| self.players[*slot].is_none() && !self.bots[*slot])
+    }
+
+    fn all_slots_filled(&self) -> bool {
+        self.next_empty_slot().is_none()
+    }
+
+    /// How the game in room `id` went, if it started, for the results log.
+    fn result(&self, id: RoomId, end: MatchEnd, now: Instant) -> Option<MatchResult> {
+        let started_at = self.started_at?;
+        Some(MatchResult {
+            match_id: id,
+            game_id: self.game_id.clone(),
+            room: self.room.clone(),
+            players: self.lineup.clone(),
+            scores: self.scores.clone(),
+            end,
+            duration_ms: now.duration_since(started_at).as_millis() as u64,
+            // The room task knows; it fills this in.
+            ticks: 0,
+            finished_unix_ms: results::unix_ms_now(),
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.players.iter().all(Option::is_none)
+    }
+
+    /// How each seated player's connection looks, as of `now`.
+    fn net_stats(&self, now: Instant) -> Vec<PlayerNetStats> {
+        self.players
+            .iter()
+            .enumerate()
+            .filter(|(_, addr)| addr.is_some())
+            .map(|(slot, _)| PlayerNetStats {
+                slot: slot as PlayerSlot,
+                packets_received: self.packets[slot],
+                last_seen_ms: latency::as_millis_u32(
+                    now.saturating_duration_since(self.last_seen[slot]),
+                ),
+                rtt_ms: self.rtt[slot].smoothed().map(latency::as_millis_u32),
+                jitter_ms: self.rtt[slot].jitter().map(latency::as_millis_u32),
+            })
+            .collect()
+    }
+
+    /// What tells a player the match is under way.
+    fn start_message(&self) -> RelayMessage {
+        match self.resumed_at {
+            Some(tick) => RelayMessage::Resumed { tick },
+            None => RelayMessage::GameStart,
+        }
+    }
+
+    /// When the room last heard from any of its seated players, or when it
+    /// opened if it has none.
+    fn last_heard(&self) -> Instant {
+        self.players
+            .iter()
+            .zip(&self.last_seen)
+            .filter(|(addr, _)| addr.is_some())
+            .map(|(_, seen)| *seen)
+            .max()
+            .unwrap_or(self.opened)
+    }
+
+    /// The median RTT of the seated players that have been measured.
+    fn median_rtt(&self) -> Option<Duration> {
+        let measured = self.players.iter().zip(&self.rtt);
+        latency::median(measured.filter_map(|(addr, rtt)| addr.and(rtt.smoothed())))
+    }
+
+    /// A room task only stops after `Close`, which also drops this handle,
+    /// so a failed send cannot happen.
+    fn notify(&self, event: RoomEvent) {
+        let _ = self.events.send(event);
+    }
+}
+
+/// Every match on this relay, plus the clients waiting for one.
+struct Relay {
+    rooms: HashMap<RoomId, RoomHandle>,
+    next_room_id: RoomId,
+    /// Which room each seated player is in.
+    player_rooms: HashMap<SocketAddr, RoomId>,
+    /// Which room each spectator is watching.
+    spectator_rooms: HashMap<SocketAddr, RoomId>,
+    /// Clients waiting for a slot, first come first served.
+    queue: VecDeque<QueuedClient>,
+    directory: RoomDirectory,
+    directory_path: PathBuf,
+    /// Each standing room's median RTT when its last match closed.
+    room_latency: HashMap<String, Duration>,
+    settings: Settings,
+    metrics: Arc<Metrics>,
+    clock: Arc<dyn Clock>,
+    limiter: RateLimiter,
+    /// Handed to every room task for talking back to the dispatcher.
+    outbox: mpsc::UnboundedSender<DispatchEvent>,
+}
+
+impl Relay {
+    fn new(
+        directory_path: PathBuf,
+        settings: Settings,
+        metrics: Arc<Metrics>,
+        clock: Arc<dyn Clock>,
+        outbox: mpsc::UnboundedSender<DispatchEvent>,
+    ) -> Self {
+        Self {
+            rooms: HashMap::new(),
+            next_room_id: 0,
+            player_rooms: HashMap::new(),
+            spectator_rooms: HashMap::new(),
+            queue: VecDeque::new(),
+            directory: RoomDirectory::load(&directory_path),
+            directory_path,
+            room_latency: HashMap::new(),
+            limiter: RateLimiter::new(settings.rate_limit, settings.rate_burst),
+            settings,
+            metrics,
+            clock,
+            outbox,
+        }
+    }
+
+    fn update_gauges(&self) {
+        self.metrics
+            .set_gauges(self.player_rooms.len(), self.rooms.len(), self.queue.len());
+    }
+
+    /// Why `joiner` can never be seated, if it can't. A resuming client
+    /// cannot be seated in a slot the relay does not have, or in a room
+    /// whose match is already running: that match was not lost.
+    fn refusal(&self, joiner: &Joiner) -> Option<RejectReason> {
+        let name = joiner.room.as_deref()?;
+        if self.directory.get(name).is_none() {
+            return Some(RejectReason::BadRoom);
+        }
+        let active = self
+            .rooms
+            .values()
+            .find(|handle| handle.room.as_deref() == Some(name));
+        if active.is_some_and(|handle| handle.game_id != joiner.game_id) {
+            return Some(RejectReason::WrongGame);
+        }
+        let resumes_running = active.is_some_and(|handle| handle.game_started);
+        match joiner.resume {
+            Some((slot, _)) if slot >= self.settings.players || resumes_running => {
+                Some(RejectReason::BadRoom)
+            }
+            _ => None,
+        }
+    }
+
+    /// A room `joiner` can sit in now: a forming one that fits, or a new one
+    /// if there is capacity. `None` means the joiner has to wait.
+    fn room_for(&mut self, joiner: &Joiner) -> Option<RoomId> {
+        let mut forming: Vec<RoomId> = self
+            .rooms
+            .iter()
+            .filter(|(_, handle)| handle.accepts(joiner))
+            .map(|(id, _)| *id)
+            .collect();
+        // Oldest first, so rooms fill in the order they opened.
+        forming.sort_unstable();
+        if let Some(id) = forming.first() {
+            return Some(*id);
+        }
+
+        let standing_busy = joiner.room.as_ref().is_some_and(|name| {
+            self.rooms.values().any(|handle| handle.room.as_ref() == Some(name))
+        });
+        if standing_busy || self.rooms.len() >= self.settings.room_capacity {
+            return None;
+        }
+        let id = self.next_room_id;
+        self.next_room_id = self.next_room_id.wrapping_add(1);
+        let (events, receiver) = mpsc::unbounded_channel();
+        let players = self.settings.players;
+        let keeps_history = joiner
+            .room
+            .as_deref()
+            .and_then(|room| self.directory.get(room))
+            .is_none_or(|room| !room.privacy.no_history);
+        let tick_log = self
+            .settings
+            .tick_log
+            .as_ref()
+            .filter(|_| keeps_history)
+            .map(|(ticks, dir)| TickLog::new(format!("relay-room-{id}"), *ticks, dir));
+        let recording = self
+            .settings
+            .record_dir
+            .as_deref()
+            .filter(|_| keeps_history)
+            .map(|dir| Recording::new(dir, id, &joiner.game_id, players));
+        let lockstep = Lockstep::new(
+            &self.settings,
+            Arc::clone(&self.metrics),
+            Arc::clone(&self.clock),
+            tick_log,
+            recording,
+            self.outbox.clone(),
+        );
+        tokio::spawn(run_room(receiver, lockstep));
+        self.rooms.insert(
+            id,
+            RoomHandle {
+                game_id: joiner.game_id.clone(),
+                room: joiner.room.clone(),
+                players: vec![None; players],
+                bots: vec![false; players],
+                player_names: vec![None; players],
+                opened: self.clock.now(),
+                last_seen: vec![self.clock.now(); players],
+                packets: vec![0; players],
+                rtt: vec![RttEstimator::default(); players],
+                resume_ticks: vec![None; players],
+                resumed_at: None,
+                game_started: false,
+                started_at: None,
+                lineup: Vec::new(),
+                scores: None,
+                rematch: vec![false; players],
+                spectators: HashMap::new(),
+                events,
+            },
+        );
+        self.metrics.room_opened();
+        info!("room {id}: opened for {}", joiner.game_id);
+        Some(id)
+    }
+
+    /// The seated player at `addr`: its room id, room, and slot.
+    fn player(&mut self, addr: &SocketAddr) -> Option<(RoomId, &mut RoomHandle, usize)> {
+        let id = *self.player_rooms.get(addr)?;
+        let handle = self.rooms.get_mut(&id)?;
+        let slot = handle.find_player(addr)?;
+        Some((id, handle, slot))
+    }
+
+    /// The room the spectator at `addr` is watching.
+    fn spectator(&mut self, addr: &SocketAddr) -> Option<&mut RoomHandle> {
+        let id = self.spectator_rooms.get(addr)?;
+        self.rooms.get_mut(id)
+    }
+
+    /// The match a spectator of `game_id` gets to watch: the one in standing
+    /// room `room`, or without one the longest running. Never one whose
+    /// room's owner turned spectators away.
+    fn match_to_watch(&self, game_id: &str, room: Option<&str>) -> Option<RoomId> {
+        self.rooms
+            .iter()
+            .filter(|(_, handle)| handle.game_started && handle.game_id == game_id)
+            .filter(|(_, handle)| room.is_none_or(|room| handle.room.as_deref() == Some(room)))
+            .filter(|(_, handle)| {
+                let standing = handle
+                    .room
+                    .as_deref()
+                    .and_then(|name| self.directory.get(name));
+                !standing.is_some_and(|standing| standing.privacy.no_spectators)
+            })
+            .min_by_key(|(id, handle)| (handle.started_at, **id))
+            .map(|(id, _)| *id)
+    }
+
+    /// Clients waiting for a match to start: seated in one that has not, or
+    /// queued.
+    fn pending(&self) -> usize {
+        let seated: usize = self
+            .rooms
+            .values()
+            .filter(|handle| !handle.game_started)
+            .map(|handle| handle.players.iter().flatten().count())
+            .sum();
+        seated + self.queue.len()
+    }
+
+    /// Whether `addr` is seated, watching, or queued.
+    fn holds(&self, addr: &SocketAddr) -> bool {
+        self.player_rooms.contains_key(addr)
+            || self.spectator_rooms.contains_key(addr)
+            || self.queue_position(addr).is_some()
+    }
+
+    /// 1-based queue position of `addr`, if it is waiting.
+    fn queue_position(&self, addr: &SocketAddr) -> Option<usize> {
+        self.queue
+            .iter()
+            .position(|queued| queued.joiner.addr == *addr)
+            .map(|index| index + 1)
+    }
+
+    /// The standing rooms as a room browser sees them; hidden ones are left out.
+    fn room_list(&self) -> Vec<RoomInfo> {
+        self.room_infos()
+            .into_iter()
+            .filter(|info| !info.privacy.hidden)
+            .collect()
+    }
+
+    /// Every standing room, hidden or not.
+    fn room_infos(&self) -> Vec<RoomInfo> {
+        self.directory
+            .names()
+            .into_iter()
+            .filter_map(|name| Some((name, self.directory.get(name)?.privacy)))
+            .map(|(name, privacy)| {
+                let active = self
+                    .rooms
+                    .values()
+                    .find(|handle| handle.room.as_deref() == Some(name));
+                let median_rtt = active
+                    .and_then(RoomHandle::median_rtt)
+                    .or_else(|| self.room_latency.get(name).copied());
+                RoomInfo {
+                    name: name.to_string(),
+                    game_id: active.map(|handle| handle.game_id.clone()),
+                    players: active.map_or(0, |handle| {
+                        handle.players.iter().flatten().count() as u8
+                    }),
+                    median_rtt_ms: median_rtt.map(latency::as_millis_u32),
+                    privacy,
+                }
+            })
+            .collect()
+    }
+
+    /// Close room `id`, forgetting its players and stopping its task. A
+    /// match that had started is logged as ending by `end`.
+    fn close_room(
+        &mut self,
+        id: RoomId,
+        link: &mut Link,
+        record: Option<MatchRecord>,
+        end: MatchEnd,
+    ) {
+        if let Some(handle) = self.rooms.remove(&id) {
+            let result = handle
+                .result(id, end, self.clock.now())
+                .filter(|_| self.settings.results_log.is_some());
+            if let (Some(room), Some(rtt)) = (&handle.room, handle.median_rtt()) {
+                self.room_latency.insert(room.clone(), rtt);
+            }
+            for addr in handle.players.iter().flatten() {
+                self.player_rooms.remove(addr);
+                link.forget(addr);
+            }
+            for addr in handle.spectators.keys() {
+                self.spectator_rooms.remove(addr);
+                link.forget(addr);
+            }
+            handle.notify(RoomEvent::Close { record, result });
+            info!("room {id}: closed");
+        }
+    }
+}
+
+/// The relay's socket. Numbers outgoing messages and filters incoming ones
+/// per peer, and seals/opens datagrams when a shared secret is configured.
+/// Outgoing messages wait in a priority queue and are numbered as they go
+/// out, so a TickInputs that jumps the queue does not make the messages it
+/// passed look stale to the client.
+struct Link {
+    socket: Arc<dyn Socket>,
+    /// WebSocket clients, by peer address: each message for one goes to its
+    /// connection task instead of out the UDP socket.
+    streams: HashMap<SocketAddr, mpsc::UnboundedSender<Vec<u8>>>,
+    auth: Option<SessionAuth>,
+    session_nonce: u64,
+    peers: HashMap<SocketAddr, PeerSequence>,
+    outgoing: SendQueue<RelayMessage>,
+    metrics: Arc<Metrics>,
+}
+
+#[derive(Default)]
+struct PeerSequence {
+    outbound: SequenceCounter,
+    inbound: SequenceFilter,
+    /// When the peer's last datagram was decoded. `None` for peers only
+    /// ever sent to.
+    heard_at: Option<Instant>,
+}
+
+/// Why an incoming datagram was dropped.
+enum Dropped {
+    /// Too short, or not framed like any message; never opened or decoded.
+    Implausible,
+    Unauthenticated,
+    Malformed,
+    /// Handshake nonce on anything but a Hello or Resume.
+    HandshakeMisuse,
+    /// Duplicate or stale; already counted in the peer's stats.
+    Sequence,
+}
+
+impl From<&Dropped> for DropReason {
+    fn from(dropped: &Dropped) -> Self {
+        match dropped {
+            Dropped::Unauthenticated => DropReason::Unauthenticated,
+            Dropped::Implausible | Dropped::Malformed => DropReason::Malformed,
+            Dropped::HandshakeMisuse => DropReason::HandshakeMisuse,
+            Dropped::Sequence => DropReason::Sequence,
+        }
+    }
+}
+
+impl Link {
+    /// Queue `msg` for `addr`. It goes out on the next [`Link::flush`].
+    fn send(&mut self, msg: &RelayMessage, addr: SocketAddr) {
+        if let Some(dropped) = self.outgoing.push(addr, Priority::of(msg), msg.clone()) {
+            warn!("send queue to {addr} full, dropped {dropped:?}");
+            self.metrics.dropped(DropReason::SendQueueFull);
+        }
+    }
+
+    /// Send queued messages, most urgent first, until the socket would block.
+    fn flush(&mut self) {
+        while let Some((addr, priority, msg)) = self.outgoing.pop() {
+            if !self.transmit(&msg, addr) {
+                self.outgoing.requeue(addr, priority, msg);
+                return;
+            }
+        }
+    }
+
+    fn has_pending(&self) -> bool {
+        !self.outgoing.is_empty()
+    }
+
+    /// Number, seal, and send one message. `false` if the socket would block.
+    fn transmit(&mut self, msg: &RelayMessage, addr: SocketAddr) -> bool {
+        let peer = self.peers.entry(addr).or_default();
+        let bytes = serialize(&peer.outbound.wrap(msg));
+        let datagram = match &self.auth {
+            Some(auth) => auth.seal(self.session_nonce, &bytes),
+            None => bytes,
+        };
+        if let Some(stream) = self.streams.get(&addr) {
+            let len = datagram.len();
+            if stream.send(datagram).is_ok() {
+                self.metrics.sent(len);
+            }
+            return true;
+        }
+        match self.socket.try_send_to(&datagram, addr) {
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => false,
+            Ok(len) => {
+                self.metrics.sent(len);
+                true
+            }
+            // Any other failure loses the datagram like the network would.
+            Err(_) => true,
+        }
+    }
+
+    /// Unwrap an incoming datagram. Without a secret every datagram passes
+    /// through. With one, the tag must verify and the nonce must be this
+    /// session's — or the handshake nonce, for a client's first Hello or
+    /// Resume.
+    fn open<'a>(&self, datagram: &'a [u8]) -> Option<(&'a [u8], bool)> {
+        let Some(auth) = &self.auth else {
+            return Some((datagram, false));
+        };
+        let (nonce, message) = auth.open(datagram)?;
+        if nonce == self.session_nonce {
+            Some((message, false))
+        } else if nonce == HANDSHAKE_NONCE {
+            Some((message, true))
+        } else {
+            None
+        }
+    }
+
+    fn receive(
+        &mut self,
+        datagram: &[u8],
+        src: SocketAddr,
+        now: Instant,
+    ) -> Result<ClientMessage, Dropped> {
+        let frame = match self.auth {
+            Some(_) => datagram.get(SEALED_OVERHEAD..).unwrap_or_default(),
+            None => datagram,
+        };
+        if !plausible_frame(frame) {
+            return Err(Dropped::Implausible);
+        }
+        let (bytes, handshake) = self.open(datagram).ok_or(Dropped::Unauthenticated)?;
+        let sequenced =
+            deserialize::<Sequenced<ClientMessage>>(bytes).ok_or(Dropped::Malformed)?;
+        let greeting = matches!(
+            sequenced.message,
+            ClientMessage::Hello { .. } | ClientMessage::Resume { .. }
+        );
+        if handshake && !greeting {
+            return Err(Dropped::HandshakeMisuse);
+        }
+        let peer = self.peers.entry(src).or_default();
+        peer.heard_at = Some(now);
+        if !peer.inbound.accept(sequenced.seq).accepted() {
+            return Err(Dropped::Sequence);
+        }
+        Ok(sequenced.message)
+    }
+
+    /// Forget `addr`'s sequence state so it can connect again from seq 0.
+    /// Anything still queued for it goes out first, under the old numbering.
+    fn forget(&mut self, addr: &SocketAddr) {
+        for (_, msg) in self.outgoing.take_destination(addr) {
+            self.transmit(&msg, *addr);
+        }
+        self.peers.remove(addr);
+    }
+
+    /// Forget peers silent for [`PEER_IDLE_AFTER`], unless `keep` says the
+    /// relay still holds them somewhere or they are on a WebSocket.
+    fn prune(&mut self, now: Instant, keep: impl Fn(&SocketAddr) -> bool) {
+        let streams = &self.streams;
+        self.peers.retain(|addr, peer| {
+            keep(addr)
+                || streams.contains_key(addr)
+                || peer
+                    .heard_at
+                    .is_some_and(|at| now.saturating_duration_since(at) < PEER_IDLE_AFTER)
+        });
+    }
+
+    /// Queue a goodbye to every known peer, behind whatever else is queued.
+    fn queue_shutdown(&mut self) {
+        let peers: Vec<SocketAddr> = self.peers.keys().copied().collect();
+        for addr in peers {
+            self.send(&RelayMessage::ServerShutdown, addr);
+        }
+    }
+
+    fn print_stats(&self) {
+        if self.peers.is_empty() {
+            println!("relay: no peers");
+        }
+        for (addr, peer) in &self.peers {
+            let stats = peer.inbound.stats();
+            println!(
+                "relay: {addr}: in order {}, reordered {}, duplicates {}, stale {}, missing {}",
+                stats.in_order, stats.reordered, stats.duplicates, stats.stale, stats.gaps
+            );
+        }
+    }
+}
+
+/// Tell `addr` about every player already seated.
+fn send_roster(handle: &RoomHandle, link: &mut Link, addr: SocketAddr) {
+    for (slot, name) in handle.player_names.iter().enumerate() {
+        if let Some(name) = name {
+            link.send(
+                &RelayMessage::PlayerJoined {
+                    slot: slot as PlayerSlot,
+                    name: name.clone(),
+                },
+                addr,
+            );
+        }
+    }
+}
+
+/// Seat `joiner` in room `id`, welcome it, introduce it to everyone present,
+/// and start (or resume) the game once every slot is filled.
+fn seat_player(relay: &mut Relay, link: &mut Link, id: RoomId, joiner: Joiner) {
+    let Relay {
+        rooms,
+        player_rooms,
+        directory,
+        clock,
+        ..
+    } = relay;
+    let Some(handle) = rooms.get_mut(&id) else {
+        return;
+    };
+    let Some(slot) = handle.slot_for(&joiner) else {
+        return;
+    };
+    let Joiner {
+        addr,
+        game_id,
+        name,
+        room,
+        resume,
+    } = joiner;
+    let in_room = room.as_ref().map(|room| format!(", room {room}")).unwrap_or_default();
+    let resuming = resume
+        .map(|(_, tick)| format!(", resuming at tick {tick}"))
+        .unwrap_or_default();
+    info!(
+        "room {id}: player {slot} ({name}) connected from {addr} ({game_id}{in_room}{resuming})"
+    );
+
+    link.send(
+        &RelayMessage::Welcome {
+            player_slot: slot as PlayerSlot,
+        },
+        addr,
+    );
+    if let Some(standing) = room.as_ref().and_then(|room| directory.get(room)) {
+        let entries = standing.config_entries();
+        if !entries.is_empty() {
+            link.send(&RelayMessage::Config { entries }, addr);
+        }
+    }
+    send_roster(handle, link, addr);
+
+    handle.players[slot] = Some(addr);
+    handle.player_names[slot] = Some(name.clone());
+    handle.last_seen[slot] = clock.now();
+    handle.packets[slot] = 0;
+    handle.rtt[slot] = RttEstimator::default();
+    handle.resume_ticks[slot] = resume.map(|(_, tick)| tick);
+    handle.notify(RoomEvent::Seat { slot, addr });
+    player_rooms.insert(addr, id);
+    let joined = RelayMessage::PlayerJoined {
+        slot: slot as PlayerSlot,
+        name,
+    };
+    for addr in handle.players.iter().flatten() {
+        link.send(&joined, *addr);
+    }
+    start_when_full(id, handle, link, clock.now());
+}
+
+/// Start room `id`'s game, or resume it, if every slot is now filled.
+fn start_when_full(id: RoomId, handle: &mut RoomHandle, link: &mut Link, now: Instant) {
+    if handle.all_slots_filled() && !handle.game_started {
+        handle.game_started = true;
+        handle.started_at = Some(now);
+        handle.lineup = handle
+            .player_names
+            .iter()
+            .map(|name| name.clone().unwrap_or_default())
+            .collect();
+        // Every client has played up to its own resume tick, so the earliest
+        // one is the first tick somebody is still missing.
+        handle.resumed_at = handle.resume_ticks.iter().flatten().min().copied();
+        match handle.resumed_at {
+            Some(tick) => {
+                info!("room {id}: all players back, resuming at tick {tick}");
+                handle.notify(RoomEvent::Resume { tick });
+            }
+            None => info!("room {id}: all players connected, starting game"),
+        }
+        let start = handle.start_message();
+        for addr in handle.players.iter().flatten() {
+            link.send(&start, *addr);
+        }
+    }
+}
+
+/// Seat queued clients wherever a room now fits them.
+fn admit_queued(relay: &mut Relay, link: &mut Link) {
+    let mut waiting = VecDeque::with_capacity(relay.queue.len());
+    while let Some(queued) = relay.queue.pop_front() {
+        // The standing room the client queued for may have been deleted.
+        if let Some(reason) = relay.refusal(&queued.joiner) {
+            warn!("rejected queued {}, {reason}", queued.joiner.addr);
+            link.send(&RelayMessage::Rejected { reason }, queued.joiner.addr);
+            continue;
+        }
+        match relay.room_for(&queued.joiner) {
+            Some(id) => seat_player(relay, link, id, queued.joiner),
+            None => waiting.push_back(queued),
+        }
+    }
+    relay.queue = waiting;
+}
+
+/// Drop queued clients that gave up, admit any that fit, and tell the rest
+/// where they stand.
+fn service_queue(relay: &mut Relay, link: &mut Link, now: Instant) {
+    relay
+        .queue
+        .retain(|queued| now.duration_since(queued.last_hello) < QUEUE_TIMEOUT);
+    admit_queued(relay, link);
+    for (index, queued) in relay.queue.iter().enumerate() {
+        link.send(
+            &RelayMessage::QueuePosition {
+                position: index as u32 + 1,
+            },
+            queued.joiner.addr,
+        );
+    }
+}
+
+/// Fill the empty seats of every match that has waited `--bot-fill` for
+/// players with bots, and start it. Lost matches being resumed wait for
+/// their own players.
+fn fill_with_bots(relay: &mut Relay, link: &mut Link, now: Instant) {
+    let Some(wait) = relay.settings.bot_fill else {
+        return;
+    };
+    for (id, handle) in &mut relay.rooms {
+        let waited = now.saturating_duration_since(handle.opened) >= wait;
+        let resuming = handle.resume_ticks.iter().any(Option::is_some);
+        if handle.game_started || handle.is_empty() || resuming || !waited {
+            continue;
+        }
+        while let Some(slot) = handle.next_empty_slot() {
+            handle.bots[slot] = true;
+            handle.player_names[slot] = Some(BOT_NAME.to_string());
+            handle.notify(RoomEvent::SeatBot { slot });
+            info!("room {id}: bot seated in slot {slot}");
+            let joined = RelayMessage::PlayerJoined {
+                slot: slot as PlayerSlot,
+                name: BOT_NAME.to_string(),
+            };
+            for addr in handle.players.iter().flatten() {
+                link.send(&joined, *addr);
+            }
+        }
+        start_when_full(*id, handle, link, now);
+    }
+}
+
+/// Tell everyone in a running match how every player's connection looks, so
+/// they can see whose is causing stalls.
+fn send_net_stats(relay: &Relay, link: &mut Link, now: Instant) {
+    for handle in relay.rooms.values().filter(|handle| handle.game_started) {
+        let msg = RelayMessage::NetStats {
+            players: handle.net_stats(now),
+        };
+        for addr in handle.players.iter().flatten() {
+            link.send(&msg, *addr);
+        }
+    }
+}
+
+/// Free the slot of a player that said Goodbye or aborted, and tell the others.
+/// A match in progress cannot continue without it, so that closes the room.
+/// Others in a started match learn why from `MatchAborted` when the player
+/// gave a reason, and get a plain `PlayerLeft` otherwise.
+fn remove_player(
+    relay: &mut Relay,
+    link: &mut Link,
+    addr: SocketAddr,
+    abort: Option<AbortReason>,
+) {
+    let Some((id, handle, slot)) = relay.player(&addr) else {
+        return;
+    };
+    let started = handle.game_started;
+    // Bots are not on the leaderboard.
+    let people = handle.player_names.iter().zip(&handle.bots);
+    let record = handle.room.clone().filter(|_| started).map(|room| MatchRecord {
+        room,
+        players: people
+            .filter(|(_, bot)| !**bot)
+            .filter_map(|(name, _)| name.clone())
+            .collect(),
+    });
+    handle.players[slot] = None;
+    handle.resume_ticks[slot] = None;
+    let name = handle.player_names[slot].take().unwrap_or_default();
+    info!("room {id}: player {slot} ({name}) at {addr} left");
+
+    let abort = abort.filter(|_| started);
+    let left = match abort {
+        Some(reason) => RelayMessage::MatchAborted {
+            by_slot: slot as PlayerSlot,
+            reason,
+        },
+        None => RelayMessage::PlayerLeft {
+            slot: slot as PlayerSlot,
+        },
+    };
+    let watching = handle.spectators.keys();
+    for other in handle.players.iter().flatten().chain(watching) {
+        link.send(&left, *other);
+    }
+    if !started {
+        handle.notify(RoomEvent::Vacate { slot });
+    }
+    let empty = handle.is_empty();
+    relay.player_rooms.remove(&addr);
+    link.forget(&addr);
+
+    if started {
+        match abort {
+            Some(reason) => info!("room {id}: match aborted by player {slot} ({name}): {reason}"),
+            None => info!("room {id}: match ended"),
+        }
+        let end = match abort {
+            Some(_) => MatchEnd::Aborted,
+            None => MatchEnd::PlayerLeft,
+        };
+        relay.close_room(id, link, record, end);
+    } else if empty {
+        relay.close_room(id, link, None, MatchEnd::PlayerLeft);
+    }
+    admit_queued(relay, link);
+}
+
+/// Drop every seated player silent for longer than the player timeout, as if
+/// it had said Goodbye.
+fn reclaim_silent_players(relay: &mut Relay, link: &mut Link, now: Instant) {
+    let player_timeout = relay.settings.player_timeout;
+    let silent: Vec<(SocketAddr, Duration)> = relay
+        .rooms
+        .values()
+        .flat_map(|handle| {
+            let timeout = if handle.game_started {
+                player_timeout
+            } else {
+                player_timeout.min(HANDSHAKE_TIMEOUT)
+            };
+            let players = handle.players.iter().zip(&handle.last_seen);
+            players.filter_map(move |(addr, seen)| {
+                addr.filter(|_| now.duration_since(*seen) > timeout)
+                    .map(|addr| (addr, timeout))
+            })
+        })
+        .collect();
+    for (addr, timeout) in silent {
+        info!("{addr} timed out after {}s of silence", timeout.as_secs());
+        relay.metrics.player_timed_out();
+        remove_player(relay, link, addr, None);
+    }
+
+    let silent: Vec<SocketAddr> = relay
+        .rooms
+        .values()
+        .flat_map(|handle| &handle.spectators)
+        .filter(|(_, seen)| now.duration_since(**seen) > player_timeout)
+        .map(|(addr, _)| *addr)
+        .collect();
+    for addr in silent {
+        info!("spectator {addr} timed out");
+        remove_spectator(relay, link, addr);
+    }
+}
+
+/// Close rooms that have gone quiet: nobody seated has been heard from for
+/// the room TTL, or the room's task is gone. Catches what dropping players
+/// one by one leaves behind, such as a room with only bots in it.
+fn reclaim_idle_rooms(relay: &mut Relay, link: &mut Link, now: Instant) {
+    let ttl = relay.settings.room_ttl;
+    let idle: Vec<RoomId> = relay
+        .rooms
+        .iter()
+        .filter(|(_, handle)| {
+            handle.events.is_closed() || now.duration_since(handle.last_heard()) > ttl
+        })
+        .map(|(id, _)| *id)
+        .collect();
+    if idle.is_empty() {
+        return;
+    }
+    let reason = RejectReason::Idle;
+    for id in idle {
+        info!("room {id}: idle for {}s, reclaiming it", ttl.as_secs());
+        if let Some(handle) = relay.rooms.get(&id) {
+            for addr in handle.players.iter().flatten() {
+                link.send(&RelayMessage::Rejected { reason }, *addr);
+            }
+        }
+        relay.close_room(id, link, None, MatchEnd::Idle);
+        relay.metrics.room_reclaimed();
+    }
+    admit_queued(relay, link);
+}
+
+/// Apply an owner's change to a standing room and tell them how it went.
+/// Setting changes reach a match already in that room immediately.
+fn administer_room(
+    relay: &mut Relay,
+    link: &mut Link,
+    src: SocketAddr,
+    room: String,
+    owner_secret: String,
+    command: RoomCommand,
+) {
+    let pushed = match &command {
+        RoomCommand::SetSetting { key, value } => Some((key.clone(), value.clone())),
+        RoomCommand::ClearSetting { .. }
+        | RoomCommand::ResetLeaderboard
+        | RoomCommand::SetPrivacy { .. } => None,
+    };
+    let result = relay.directory.apply(&room, &owner_secret, command);
+    let error = match result {
+        Ok(()) => {
+            info!("room {room} changed by owner at {src}");
+            relay.directory.save(&relay.directory_path);
+            if let Some(entry) = pushed {
+                let msg = RelayMessage::Config {
+                    entries: vec![entry],
+                };
+                let active = relay
+                    .rooms
+                    .values()
+                    .filter(|handle| handle.room.as_deref() == Some(room.as_str()));
+                for addr in active.flat_map(|handle| handle.players.iter().flatten()) {
+                    link.send(&msg, *addr);
+                }
+            }
+            None
+        }
+        Err(e) => {
+            warn!("room command for {room} from {src} refused: {e}");
+            Some(e.to_string())
+        }
+    };
+    link.send(&RelayMessage::RoomAdminResult { error }, src);
+}
+
+/// Seat, queue, or refuse a client that said Hello or Resume.
+fn greet(relay: &mut Relay, link: &mut Link, joiner: Joiner) {
+    let src = joiner.addr;
+
+    // Already connected? Re-send welcome and who is here. Seated clients keep
+    // saying Hello until the game starts, so this is also their keep-alive.
+    if let Some((_, handle, slot)) = relay.player(&src) {
+        link.send(
+            &RelayMessage::Welcome {
+                player_slot: slot as PlayerSlot,
+            },
+            src,
+        );
+        send_roster(handle, link, src);
+        if handle.game_started {
+            link.send(&handle.start_message(), src);
+        }
+        return;
+    }
+
+    if let Some(reason) = relay.refusal(&joiner) {
+        warn!("rejected {src} ({}), {reason}", joiner.game_id);
+        link.send(&RelayMessage::Rejected { reason }, src);
+        return;
+    }
+
+    // Already queued? Refresh and re-send the position.
+    if let Some(position) = relay.queue_position(&src) {
+        relay.queue[position - 1].last_hello = relay.clock.now();
+        link.send(
+            &RelayMessage::QueuePosition {
+                position: position as u32,
+            },
+            src,
+        );
+        return;
+    }
+
+    // A flood of Hellos from made-up addresses would otherwise fill every
+    // forming match and the queue. Quietly: the flood is what would be logged.
+    if relay.pending() >= relay.settings.max_pending {
+        let reason = RejectReason::Full;
+        debug!("rejected {src}, too many clients waiting");
+        link.send(&RelayMessage::Rejected { reason }, src);
+        return;
+    }
+
+    if let Some(id) = relay.room_for(&joiner) {
+        seat_player(relay, link, id, joiner);
+        return;
+    }
+
+    if relay.queue.len() >= MAX_QUEUE_LEN {
+        let reason = RejectReason::Full;
+        warn!("rejected {src}, {reason}");
+        link.send(&RelayMessage::Rejected { reason }, src);
+        return;
+    }
+
+    relay.queue.push_back(QueuedClient {
+        joiner,
+        last_hello: relay.clock.now(),
+    });
+    let position = relay.queue.len() as u32;
+    info!("{src} queued at position {position}");
+    link.send(&RelayMessage::QueuePosition { position }, src);
+}
+
+/// A client at `src` is going away: free its seat, stop it watching, or
+/// take it out of the queue.
+fn leave(relay: &mut Relay, link: &mut Link, src: SocketAddr, abort: Option<AbortReason>) {
+    if relay.player_rooms.contains_key(&src) {
+        remove_player(relay, link, src, abort);
+    } else if relay.spectator_rooms.contains_key(&src) {
+        remove_spectator(relay, link, src);
+    } else if let Some(position) = relay.queue_position(&src) {
+        relay.queue.remove(position - 1);
+        link.forget(&src);
+        info!("{src} left the queue");
+    }
+}
+
+/// Let `src` watch a running match of `game_id`, in standing room `room` if
+/// given, or carry on watching the one it is. Spectators keep saying
+/// Spectate, so this is also their keep-alive.
+fn watch(
+    relay: &mut Relay,
+    link: &mut Link,
+    src: SocketAddr,
+    game_id: String,
+    room: Option<String>,
+) {
+    let now = relay.clock.now();
+    if let Some(handle) = relay.spectator(&src) {
+        handle.spectators.insert(src, now);
+        handle.notify(RoomEvent::Watch { addr: src });
+        return;
+    }
+    if relay.player_rooms.contains_key(&src) {
+        // Players see the match already.
+        return;
+    }
+    let Some(id) = relay.match_to_watch(&game_id, room.as_deref()) else {
+        debug!("{src} asked to watch {game_id}, but nothing is on");
+        let reason = RejectReason::NoMatch;
+        link.send(&RelayMessage::Rejected { reason }, src);
+        return;
+    };
+    let Some(handle) = relay.rooms.get_mut(&id) else {
+        return;
+    };
+    if handle.spectators.len() >= MAX_SPECTATORS {
+        let reason = RejectReason::Full;
+        link.send(&RelayMessage::Rejected { reason }, src);
+        return;
+    }
+    handle.spectators.insert(src, now);
+    relay.spectator_rooms.insert(src, id);
+    info!("room {id}: {src} is watching");
+    send_roster(handle, link, src);
+    handle.notify(RoomEvent::Watch { addr: src });
+}
+
+/// Stop sending the spectator at `addr` anything.
+fn remove_spectator(relay: &mut Relay, link: &mut Link, addr: SocketAddr) {
+    let Some(id) = relay.spectator_rooms.remove(&addr) else {
+        return;
+    };
+    if let Some(handle) = relay.rooms.get_mut(&id) {
+        handle.spectators.remove(&addr);
+        handle.notify(RoomEvent::Unwatch { addr });
+    }
+    link.forget(&addr);
+    info!("room {id}: {addr} stopped watching");
+}
+
+/// Act on a message from a spectator. They may only ask for what they need
+/// to follow the match, and leave.
+fn handle_spectator_message(
+    relay: &mut Relay,
+    link: &mut Link,
+    src: SocketAddr,
+    msg: ClientMessage,
+) {
+    let Some(handle) = relay.spectator(&src) else {
+        return;
+    };
+    match msg {
+        ClientMessage::Spectate { game_id, room } => watch(relay, link, src, game_id, room),
+        ClientMessage::RequestTick { tick } => {
+            handle.notify(RoomEvent::RequestTick { addr: src, tick });
+        }
+        ClientMessage::RequestSnapshot => {
+            // Any player's will do; the spectator loads the first.
+            let msg = RelayMessage::SnapshotRequested {
+                slot: SPECTATOR_SLOT,
+            };
+            for addr in handle.players.iter().flatten() {
+                link.send(&msg, *addr);
+            }
+        }
+        ClientMessage::Goodbye | ClientMessage::Abort { .. } => remove_spectator(relay, link, src),
+        _ => relay.metrics.dropped(DropReason::UnknownClient),
+    }
+}
+
+/// Have the room of the seated player at `src`, if any, dump its tick log.
+fn report_protocol_error(relay: &mut Relay, src: SocketAddr, what: &str) {
+    if let Some((_, handle, slot)) = relay.player(&src) {
+        handle.notify(RoomEvent::DumpTickLog {
+            reason: format!("{what} from player {slot} at {src}"),
+        });
+    }
+}
+
+/// Pass `msg` from the player in `slot` on to everyone else in the match.
+fn send_to_others(link: &mut Link, handle: &RoomHandle, slot: usize, msg: &RelayMessage) {
+    for (other, addr) in handle.players.iter().enumerate() {
+        if other != slot
+            && let Some(addr) = addr
+        {
+            link.send(msg, *addr);
+        }
+    }
+}
+
+/// Decode one datagram and act on it.
+fn handle_datagram(relay: &mut Relay, link: &mut Link, datagram: &[u8], src: SocketAddr) {
+    relay.metrics.received(datagram.len());
+    if !relay.limiter.allow(src, relay.clock.now()) {
+        relay.metrics.dropped(DropReason::RateLimited);
+        return;
+    }
+    let received = link.receive(datagram, src, relay.clock.now());
+    if let Err(dropped) = &received {
+        relay.metrics.dropped(dropped.into());
+    }
+    let msg = match received {
+        Ok(msg) => msg,
+        // Junk is the bulk of a flood; not worth a warning each.
+        Err(Dropped::Implausible) => {
+            debug!("implausible datagram from {src}");
+            return;
+        }
+        Err(Dropped::Unauthenticated) => {
+            warn!("unauthenticated datagram from {src}");
+            return;
+        }
+        Err(Dropped::Malformed) => {
+            warn!("bad message from {src}");
+            report_protocol_error(relay, src, "malformed message");
+            return;
+        }
+        Err(Dropped::HandshakeMisuse) => {
+            warn!("handshake nonce on non-Hello from {src}");
+            report_protocol_error(relay, src, "handshake nonce on non-Hello");
+            return;
+        }
+        Err(Dropped::Sequence) => return,
+    };
+    let now = relay.clock.now();
+    if let Some((_, handle, slot)) = relay.player(&src) {
+        handle.last_seen[slot] = now;
+        handle.packets[slot] = handle.packets[slot].saturating_add(1);
+    } else if let Some(handle) = relay.spectator(&src) {
+        handle.spectators.insert(src, now);
+        handle_spectator_message(relay, link, src, msg);
+        return;
+    }
+
+    match msg {
+        ClientMessage::Hello {
+            game_id,
+            name,
+            room,
+        } => {
+            let joiner = Joiner {
+                addr: src,
+                game_id,
+                name,
+                room,
+                resume: None,
+            };
+            greet(relay, link, joiner);
+        }
+        ClientMessage::Resume {
+            game_id,
+            name,
+            room,
+            slot,
+            next_tick,
+        } => {
+            let joiner = Joiner {
+                addr: src,
+                game_id,
+                name,
+                room: Some(room),
+                resume: Some((usize::from(slot), next_tick)),
+            };
+            greet(relay, link, joiner);
+        }
+        ClientMessage::Input { tick, payload } => match relay.player(&src) {
+            Some((_, handle, slot)) => handle.notify(RoomEvent::Input {
+                slot,
+                tick,
+                payload,
+            }),
+            None => {
+                relay.metrics.dropped(DropReason::UnknownClient);
+                warn!("input from unknown client {src}");
+            }
+        },
+        ClientMessage::InputBatch {
+            first_tick,
+            payloads,
+        } => match relay.player(&src) {
+            Some((_, handle, slot)) => {
+                for (tick, payload) in (first_tick..).zip(payloads) {
+                    handle.notify(RoomEvent::Input {
+                        slot,
+                        tick,
+                        payload,
+                    });
+                }
+            }
+            None => {
+                relay.metrics.dropped(DropReason::UnknownClient);
+                warn!("input from unknown client {src}");
+            }
+        },
+        ClientMessage::AckTick { tick } => match relay.player(&src) {
+            Some((_, handle, slot)) => handle.notify(RoomEvent::Ack { slot, tick }),
+            None => relay.metrics.dropped(DropReason::UnknownClient),
+        },
+        ClientMessage::RequestTick { tick } => match relay.player(&src) {
+            Some((_, handle, _)) => handle.notify(RoomEvent::RequestTick { addr: src, tick }),
+            None => relay.metrics.dropped(DropReason::UnknownClient),
+        },
+        ClientMessage::Goodbye => leave(relay, link, src, None),
+        ClientMessage::Abort { reason } => leave(relay, link, src, Some(reason)),
+        ClientMessage::Scores { mut scores } => match relay.player(&src) {
+            Some((_, handle, _)) => {
+                if handle.game_started {
+                    scores.truncate(handle.players.len());
+                    handle.scores = Some(scores);
+                }
+            }
+            None => relay.metrics.dropped(DropReason::UnknownClient),
+        },
+        ClientMessage::StateHash { tick, hash } => match relay.player(&src) {
+            Some((_, handle, slot)) => {
+                if handle.game_started {
+                    let slot_id = slot as PlayerSlot;
+                    let msg = RelayMessage::StateHash { slot: slot_id, tick, hash };
+                    send_to_others(link, handle, slot, &msg);
+                }
+            }
+            None => relay.metrics.dropped(DropReason::UnknownClient),
+        },
+        ClientMessage::StateDump { tick, state } => match relay.player(&src) {
+            Some((id, handle, slot)) => {
+                if handle.game_started {
+                    warn!("room {id}: player {slot} at {src} reports a desync at tick {tick}");
+                    handle.notify(RoomEvent::DumpTickLog {
+                        reason: format!("player {slot} reports a desync at tick {tick}"),
+                    });
+                    let slot_id = slot as PlayerSlot;
+                    let msg = RelayMessage::StateDump { slot: slot_id, tick, state };
+                    send_to_others(link, handle, slot, &msg);
+                }
+            }
+            None => relay.metrics.dropped(DropReason::UnknownClient),
+        },
+        ClientMessage::Pause { tick } => match relay.player(&src) {
+            Some((_, handle, slot)) => {
+                if handle.game_started {
+                    handle.notify(RoomEvent::Pause { slot, tick });
+                }
+            }
+            None => relay.metrics.dropped(DropReason::UnknownClient),
+        },
+        ClientMessage::Unpause => match relay.player(&src) {
+            Some((_, handle, slot)) => {
+                if handle.game_started {
+                    handle.notify(RoomEvent::Unpause { slot });
+                }
+            }
+            None => relay.metrics.dropped(DropReason::UnknownClient),
+        },
+        ClientMessage::Spectate { game_id, room } => watch(relay, link, src, game_id, room),
+        ClientMessage::Chat { text } => match relay.player(&src) {
+            Some((_, handle, slot)) => {
+                let msg = RelayMessage::Chat {
+                    slot: slot as PlayerSlot,
+                    text: text.chars().take(MAX_CHAT_LEN).collect(),
+                };
+                send_to_others(link, handle, slot, &msg);
+                for addr in handle.spectators.keys() {
+                    link.send(&msg, *addr);
+                }
+            }
+            None => relay.metrics.dropped(DropReason::UnknownClient),
+        },
+        ClientMessage::Rematch => {
+            let logging = relay.settings.results_log.is_some();
+            match relay.player(&src) {
+                Some((id, handle, slot)) => {
+                    if handle.game_started && !handle.rematch[slot] {
+                        handle.rematch[slot] = true;
+                        let msg = RelayMessage::RematchRequested {
+                            slot: slot as PlayerSlot,
+                        };
+                        send_to_others(link, handle, slot, &msg);
+                    }
+                    // Bots will play anyone again.
+                    let everyone = handle.game_started
+                        && (0..handle.players.len()).all(|slot| {
+                            handle.bots[slot]
+                                || (handle.players[slot].is_some() && handle.rematch[slot])
+                        });
+                    if everyone {
+                        let result = handle
+                            .result(id, MatchEnd::Rematch, now)
+                            .filter(|_| logging);
+                        handle.started_at = Some(now);
+                        handle.scores = None;
+                        handle.rematch.fill(false);
+                        info!("room {id}: rematch");
+                        handle.notify(RoomEvent::Rematch { result });
+                    }
+                }
+                None => relay.metrics.dropped(DropReason::UnknownClient),
+            }
+        }
+        ClientMessage::RequestSnapshot => match relay.player(&src) {
+            Some((_, handle, slot)) => {
+                if handle.game_started {
+                    let msg = RelayMessage::SnapshotRequested {
+                        slot: slot as PlayerSlot,
+                    };
+                    send_to_others(link, handle, slot, &msg);
+                }
+            }
+            None => relay.metrics.dropped(DropReason::UnknownClient),
+        },
+        ClientMessage::Snapshot { tick, state } => match relay.player(&src) {
+            Some((_, handle, slot)) => {
+                if handle.game_started {
+                    let slot_id = slot as PlayerSlot;
+                    let msg = RelayMessage::Snapshot { slot: slot_id, tick, state };
+                    send_to_others(link, handle, slot, &msg);
+                    for addr in handle.spectators.keys() {
+                        link.send(&msg, *addr);
+                    }
+                }
+            }
+            None => relay.metrics.dropped(DropReason::UnknownClient),
+        },
+        ClientMessage::RoomAdmin {
+            room,
+            owner_secret,
+            command,
+        } => {
+            administer_room(relay, link, src, room, owner_secret, command);
+        }
+        ClientMessage::ListRooms => {
+            let rooms = relay.room_list();
+            link.send(&RelayMessage::RoomList { rooms }, src);
+        }
+    }
+}
+
+/// Act on a report from a room task.
+fn handle_dispatch_event(relay: &mut Relay, link: &mut Link, event: DispatchEvent) {
+    match event {
+        DispatchEvent::Send { msg, addr } => link.send(&msg, addr),
+        DispatchEvent::RttSample { addr, rtt } => {
+            if let Some((_, handle, slot)) = relay.player(&addr) {
+                handle.rtt[slot].sample(rtt);
+            }
+        }
+        DispatchEvent::MatchFinished {
+            record,
+            result,
+            ticks,
+        } => {
+            if let Some(record) = record {
+                relay.directory.record_match(&record.room, &record.players, ticks);
+                relay.directory.save(&relay.directory_path);
+            }
+            if let (Some(mut result), Some(path)) = (result, &relay.settings.results_log) {
+                result.ticks = ticks;
+                if let Err(e) = results::append(path, &result) {
+                    warn!("cannot log match {} to {}: {e}", result.match_id, path.display());
+                }
+            }
+        }
+    }
+}
+
+/// A command typed by the operator on stdin.
+#[derive(Debug, PartialEq)]
+pub enum ConsoleCommand {
+    /// `config <key> <value>`: push a setting to every connected client.
+    Config { key: String, value: ConfigValue },
+    /// `stats`: per-client sequence statistics (reordered, duplicate, stale).
+    Stats,
+    /// `rooms`: standing rooms, who is in them, and their median ping.
+    Rooms,
+    /// `room create <name> <owner_secret>`: create a standing room.
+    RoomCreate { name: String, owner_secret: String },
+    /// `room delete <name>`: delete a standing room.
+    RoomDelete { name: String },
+    /// `room show <name>`: a room's settings and leaderboard.
+    RoomShow { name: String },
+    /// `matches`: live matches, their players' addresses, and the tick each
+    /// is on.
+    Matches,
+    /// `kick <addr>`: remove the player at `addr`, telling it it was kicked.
+    Kick { addr: SocketAddr },
+    /// `close <match>`: end a live match by its number, e.g. one that wedged.
+    Close { id: RoomId },
+}
+
+pub fn parse_console_command(line: &str) -> Result<ConsoleCommand, String> {
+    let mut words = line.split_whitespace();
+    match words.next() {
+        Some("config") => {
+            let (Some(key), Some(value)) = (words.next(), words.next()) else {
+                return Err("usage: config <key> <value>".into());
+            };
+            Ok(ConsoleCommand::Config {
+                key: key.to_string(),
+                value: ConfigValue::parse(value),
+            })
+        }
+        Some("stats") => Ok(ConsoleCommand::Stats),
+        Some("rooms") => Ok(ConsoleCommand::Rooms),
+        Some("matches") => Ok(ConsoleCommand::Matches),
+        Some("kick") => match words.next().map(str::parse) {
+            Some(Ok(addr)) => Ok(ConsoleCommand::Kick {
+                addr: canonical(addr),
+            }),
+            _ => Err("usage: kick <ip:port>".into()),
+        },
+        Some("close") => match words.next().map(str::parse) {
+            Some(Ok(id)) => Ok(ConsoleCommand::Close { id }),
+            _ => Err("usage: close <match number>".into()),
+        },
+        Some("room") => match (words.next(), words.next(), words.next()) {
+            (Some("create"), Some(name), Some(owner_secret)) => Ok(ConsoleCommand::RoomCreate {
+                name: name.to_string(),
+                owner_secret: owner_secret.to_string(),
+            }),
+            (Some("delete"), Some(name), None) => Ok(ConsoleCommand::RoomDelete {
+                name: name.to_string(),
+            }),
+            (Some("show"), Some(name), None) => Ok(ConsoleCommand::RoomShow {
+                name: name.to_string(),
+            }),
+            _ => Err("usage: room create <name> <owner_secret> | room delete <name> | room show <name>".into()),
+        },
+        Some(other) => Err(format!("unknown command: {other}")),
+        None => Err("empty command".into()),
+    }
+}
+
+/// Print every live match. The tick each is on comes from its room task, so
+/// the listing is printed once they have all answered, without holding up
+/// the dispatcher.
+fn print_matches(relay: &Relay) {
+    if relay.rooms.is_empty() {
+        println!("relay: no matches");
+        return;
+    }
+    let mut ids: Vec<RoomId> = relay.rooms.keys().copied().collect();
+    ids.sort_unstable();
+    let now = relay.clock.now();
+    let listings: Vec<(String, Vec<String>, oneshot::Receiver<Tick>)> = ids
+        .into_iter()
+        .map(|id| {
+            let handle = &relay.rooms[&id];
+            let (reply, tick) = oneshot::channel();
+            handle.notify(RoomEvent::Status { reply });
+            let room = handle
+                .room
+                .as_ref()
+                .map(|room| format!(" in room {room}"))
+                .unwrap_or_default();
+            let state = if handle.game_started { "playing" } else { "forming" };
+            let header = format!("match {id}: {}{room}, {state}", handle.game_id);
+            let players = (0..handle.players.len())
+                .filter_map(|slot| {
+                    if handle.bots[slot] {
+                        return Some(format!("slot {slot}: bot, played by the relay"));
+                    }
+                    let addr = handle.players[slot]?;
+                    let name = handle.player_names[slot].as_deref().unwrap_or("?");
+                    let silent = now.duration_since(handle.last_seen[slot]).as_secs();
+                    let rtt = handle.rtt[slot]
+                        .smoothed()
+                        .map(|rtt| format!(", rtt ~{} ms", rtt.as_millis()))
+                        .unwrap_or_default();
+                    Some(format!("slot {slot}: {name} at {addr}, heard {silent}s ago{rtt}"))
+                })
+                .collect();
+            (header, players, tick)
+        })
+        .collect();
+    tokio::spawn(async move {
+        for (header, players, tick) in listings {
+            match tick.await {
+                Ok(tick) => println!("relay: {header}, tick {tick}"),
+                Err(_) => println!("relay: {header}, closed"),
+            }
+            for player in players {
+                println!("relay:   {player}");
+            }
+        }
+    });
+}
+
+fn run_console_command(command: ConsoleCommand, relay: &mut Relay, link: &mut Link) {
+    match command {
+        ConsoleCommand::Config { key, value } => {
+            println!("relay: pushing config {key} = {value}");
+            let msg = RelayMessage::Config {
+                entries: vec![(key, value)],
+            };
+            for addr in relay.player_rooms.keys() {
+                link.send(&msg, *addr);
+            }
+        }
+        ConsoleCommand::Stats => {
+            println!(
+                "relay: {} rooms open, {} queued",
+                relay.rooms.len(),
+                relay.queue.len()
+            );
+            link.print_stats();
+        }
+        ConsoleCommand::Rooms => {
+            let rooms = relay.room_infos();
+            if rooms.is_empty() {
+                println!("relay: no standing rooms");
+            }
+            for room in rooms {
+                let active = match &room.game_id {
+                    Some(game_id) => format!(" ({game_id}, {} players)", room.players),
+                    None => String::new(),
+                };
+                let ping = room
+                    .median_rtt_ms
+                    .map(|ms| format!(", ~{ms} ms"))
+                    .unwrap_or_default();
+                let privacy = if room.privacy == RoomPrivacy::default() {
+                    String::new()
+                } else {
+                    format!(" [{}]", room.privacy)
+                };
+                println!("relay: room {}{active}{ping}{privacy}", room.name);
+            }
+        }
+        ConsoleCommand::RoomCreate { name, owner_secret } => {
+            match relay.directory.create(&name, &owner_secret) {
+                Ok(()) => {
+                    relay.directory.save(&relay.directory_path);
+                    println!("relay: created room {name}");
+                }
+                Err(e) => eprintln!("relay: cannot create room {name}: {e}"),
+            }
+        }
+        ConsoleCommand::RoomDelete { name } => match relay.directory.remove(&name) {
+            Ok(()) => {
+                relay.directory.save(&relay.directory_path);
+                println!("relay: deleted room {name}");
+            }
+            Err(e) => eprintln!("relay: cannot delete room {name}: {e}"),
+        },
+        ConsoleCommand::Matches => print_matches(relay),
+        ConsoleCommand::Kick { addr } => {
+            if relay.player(&addr).is_none() {
+                eprintln!("relay: no player at {addr}");
+                return;
+            }
+            println!("relay: kicking {addr}");
+            let reason = RejectReason::Kicked;
+            link.send(&RelayMessage::Rejected { reason }, addr);
+            remove_player(relay, link, addr, None);
+        }
+        ConsoleCommand::Close { id } => {
+            let Some(handle) = relay.rooms.get(&id) else {
+                eprintln!("relay: no match {id}");
+                return;
+            };
+            println!("relay: closing match {id}");
+            let reason = RejectReason::Kicked;
+            for addr in handle.players.iter().flatten() {
+                link.send(&RelayMessage::Rejected { reason }, *addr);
+            }
+            relay.close_room(id, link, None, MatchEnd::Closed);
+            admit_queued(relay, link);
+        }
+        ConsoleCommand::RoomShow { name } => {
+            let Some(room) = relay.directory.get(&name) else {
+                eprintln!("relay: no such room: {name}");
+                return;
+            };
+            println!("relay: {name}: privacy: {}", room.privacy);
+            for (key, value) in &room.settings {
+                println!("relay: {name}: {key} = {value}");
+            }
+            for (player, entry) in room.standings() {
+                println!(
+                    "relay: {name}: {player}: {} matches, {} ticks",
+                    entry.matches_played, entry.ticks_played
+                );
+            }
+        }
+    }
+}
+
+fn handle_stream_event(relay: &mut Relay, link: &mut Link, event: StreamEvent) {
+    match event {
+        StreamEvent::Connected { addr, outgoing } => {
+            let addr = canonical(addr);
+            info!("{addr} connected over WebSocket");
+            link.streams.insert(addr, outgoing);
+        }
+        StreamEvent::Datagram { addr, bytes } => {
+            handle_datagram(relay, link, &bytes, canonical(addr));
+        }
+        StreamEvent::Closed { addr } => {
+            let addr = canonical(addr);
+            leave(relay, link, addr, None);
+            link.forget(&addr);
+            link.streams.remove(&addr);
+        }
+    }
+}
+
+/// What this relay offers, for a LAN discovery probe.
+fn discovery_reply(relay: &Relay, relay_port: u16) -> DiscoveryReply {
+    DiscoveryReply {
+        relay_port,
+        players_per_match: relay.settings.players as u8,
+        rooms: relay.room_list(),
+        open_matches: relay
+            .rooms
+            .values()
+            .filter(|handle| {
+                !handle.game_started && handle.room.is_none() && handle.next_empty_slot().is_some()
+            })
+            .map(|handle| handle.game_id.clone())
+            .collect(),
+    }
+}
+
+/// The reply to a discovery probe from the local network. Anything else,
+/// and anything from outside it, gets none.
+fn answer_probe(
+    relay: &mut Relay,
+    relay_port: u16,
+    datagram: &[u8],
+    src: SocketAddr,
+) -> Option<Vec<u8>> {
+    if !discovery::is_lan(src.ip()) || !relay.limiter.allow(src, relay.clock.now()) {
+        return None;
+    }
+    let probe = deserialize::<DiscoveryProbe>(datagram)?;
+    if probe.protocol_version != crate::PROTOCOL_VERSION {
+        return None;
+    }
+    Some(serialize(&discovery_reply(relay, relay_port)))
+}
+
+
+// ---------------------------------------------------------------------------
+// The core as the binary drives it
+// ---------------------------------------------------------------------------
+
+/// The dispatcher's state and every decision it makes, fed by the binary's
+/// I/O loop. Rooms still run as tokio tasks, so a core must live inside a
+/// runtime.
+pub struct RelayCore {
+    relay: Relay,
+    link: Link,
+}
+
+impl RelayCore {
+    /// Returns the core and the channel its room tasks report on.
+    pub fn new(
+        settings: Settings,
+        directory_path: PathBuf,
+        socket: Arc<dyn Socket>,
+        clock: Arc<dyn Clock>,
+        auth: Option<SessionAuth>,
+        metrics: Arc<Metrics>,
+    ) -> (Self, mpsc::UnboundedReceiver<DispatchEvent>) {
+        let (outbox, dispatch_events) = mpsc::unbounded_channel();
+        let link = Link {
+            socket,
+            streams: HashMap::new(),
+            auth,
+            session_nonce: new_session_nonce(),
+            peers: HashMap::new(),
+            outgoing: SendQueue::new(),
+            metrics: Arc::clone(&metrics),
+        };
+        let relay = Relay::new(directory_path, settings, metrics, clock, outbox);
+        (Self { relay, link }, dispatch_events)
+    }
+
+    /// Act on a datagram from `src`.
+    pub fn handle_datagram(&mut self, datagram: &[u8], src: SocketAddr) {
+        handle_datagram(&mut self.relay, &mut self.link, datagram, canonical(src));
+    }
+
+    pub fn handle_dispatch_event(&mut self, event: DispatchEvent) {
+        handle_dispatch_event(&mut self.relay, &mut self.link, event);
+    }
+
+    pub fn handle_stream_event(&mut self, event: StreamEvent) {
+        handle_stream_event(&mut self.relay, &mut self.link, event);
+    }
+
+    pub fn run_console_command(&mut self, command: ConsoleCommand) {
+        run_console_command(command, &mut self.relay, &mut self.link);
+    }
+
+    /// Drop silent players, idle rooms and queued clients that gave up, seat
+    /// whoever now fits, tell the rest where they stand, fill seats with
+    /// bots where players are overdue, send every running match its
+    /// players' network statistics, and forget idle sources. Run every
+    /// [`QUEUE_UPDATE_INTERVAL`].
+    pub fn service_timers(&mut self) {
+        let now = self.relay.clock.now();
+        reclaim_silent_players(&mut self.relay, &mut self.link, now);
+        reclaim_idle_rooms(&mut self.relay, &mut self.link, now);
+        service_queue(&mut self.relay, &mut self.link, now);
+        fill_with_bots(&mut self.relay, &mut self.link, now);
+        send_net_stats(&self.relay, &mut self.link, now);
+        self.relay.limiter.prune(now);
+        let relay = &self.relay;
+        self.link.prune(now, |addr| relay.holds(addr));
+    }
+
+    /// Apply reloaded settings. New rooms open with them; rooms already
+    /// playing take the new tick timeout and deadline, and keep their
+    /// players. A raised room capacity seats whoever was queued.
+    pub fn retune(&mut self, tuning: &Tuning) {
+        let settings = &mut self.relay.settings;
+        tuning.apply(settings);
+        self.relay
+            .limiter
+            .set_rates(settings.rate_limit, settings.rate_burst);
+        for handle in self.relay.rooms.values() {
+            handle.notify(RoomEvent::Retune {
+                tick_timeout: settings.tick_timeout,
+                tick_deadline: settings.tick_deadline,
+            });
+        }
+        admit_queued(&mut self.relay, &mut self.link);
+    }
+
+    /// The reply to send back for a LAN discovery datagram, if it deserves one.
+    pub fn answer_probe(
+        &mut self,
+        datagram: &[u8],
+        src: SocketAddr,
+        relay_port: u16,
+    ) -> Option<Vec<u8>> {
+        answer_probe(&mut self.relay, relay_port, datagram, canonical(src))
+    }
+
+    /// Send what is queued until the socket would block.
+    pub fn flush(&mut self) {
+        self.link.flush();
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.link.has_pending()
+    }
+
+    pub fn update_gauges(&self) {
+        self.relay.update_gauges();
+    }
+
+    /// Queue `ServerShutdown` for every client the relay knows of. Keep
+    /// flushing until [`RelayCore::has_pending`] is false to get it out.
+    pub fn queue_shutdown(&mut self) {
+        self.link.queue_shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ManualClock, MemorySocket, addr, settings};
+
+    struct Harness {
+        core: RelayCore,
+        socket: Arc<MemorySocket>,
+        clock: Arc<ManualClock>,
+        counters: HashMap<SocketAddr, SequenceCounter>,
+    }
+
+    impl Harness {
+        fn new(players: usize) -> Self {
+            let socket = Arc::new(MemorySocket::default());
+            let clock = Arc::new(ManualClock::new());
+            let directory = std::env::temp_dir()
+                .join(format!("relay_core_test_{}", std::process::id()))
+                .join("rooms.toml");
+            let (core, _) = RelayCore::new(
+                settings(players),
+                directory,
+                socket.clone(),
+                clock.clone(),
+                None,
+                Arc::new(Metrics::new()),
+            );
+            Self {
+                core,
+                socket,
+                clock,
+                counters: HashMap::new(),
+            }
+        }
+
+        fn send(&mut self, from: SocketAddr, message: ClientMessage) {
+            let sequenced = self.counters.entry(from).or_default().wrap(message);
+            self.core.handle_datagram(&serialize(&sequenced), from);
+            self.core.flush();
+        }
+
+        fn hello(&mut self, from: SocketAddr, name: &str) {
+            let hello = ClientMessage::Hello {
+                game_id: "pong".into(),
+                name: name.into(),
+                room: None,
+            };
+            self.send(from, hello);
+        }
+    }
+
+    #[tokio::test]
+    async fn two_hellos_make_a_match() {
+        // given a relay for two-player matches
+        let mut harness = Harness::new(2);
+
+        // when two clients say Hello
+        harness.hello(addr(1), "alice");
+        harness.hello(addr(2), "bob");
+
+        // then both are seated in order and told the game has started
+        let alice = harness.socket.take(addr(1));
+        let bob = harness.socket.take(addr(2));
+        let welcomed = |messages: &[RelayMessage], slot: PlayerSlot| {
+            messages.iter().any(|msg| {
+                matches!(msg, RelayMessage::Welcome { player_slot } if *player_slot == slot)
+            })
+        };
+        let started = |messages: &[RelayMessage]| {
+            messages.iter().any(|msg| matches!(msg, RelayMessage::GameStart))
+        };
+        assert!(welcomed(&alice, 0) && started(&alice));
+        assert!(welcomed(&bob, 1) && started(&bob));
+    }
+
+    #[tokio::test]
+    async fn full_relay_queues_the_next_client() {
+        // given a relay with room for one single-player match, already taken
+        let mut harness = Harness::new(1);
+        harness.core.relay.settings.room_capacity = 1;
+        harness.hello(addr(1), "alice");
+
+        // when another client says Hello
+        harness.hello(addr(2), "bob");
+
+        // then it waits first in line
+        let bob = harness.socket.take(addr(2));
+        assert!(matches!(bob[..], [RelayMessage::QueuePosition { position: 1 }]));
+    }
+
+    #[tokio::test]
+    async fn raising_the_capacity_seats_the_queue_without_touching_the_match() {
+        // given a relay full with one single-player match and a client queued
+        let mut harness = Harness::new(1);
+        harness.core.relay.settings.room_capacity = 1;
+        harness.hello(addr(1), "alice");
+        harness.hello(addr(2), "bob");
+        harness.socket.take(addr(1));
+        harness.socket.take(addr(2));
+
+        // when a reload raises the capacity
+        let tuning = Tuning {
+            room_capacity: Some(2),
+            ..Tuning::default()
+        };
+        harness.core.retune(&tuning);
+        harness.core.flush();
+
+        // then the queued client is seated and the first match carries on
+        let bob = harness.socket.take(addr(2));
+        assert!(bob.iter().any(|msg| matches!(msg, RelayMessage::Welcome { .. })));
+        assert_eq!(harness.core.relay.rooms.len(), 2);
+        assert!(harness.socket.take(addr(1)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn hellos_past_the_pending_cap_are_turned_away() {
+        // given a relay for three-player matches that lets two clients wait
+        let mut harness = Harness::new(3);
+        harness.core.relay.settings.max_pending = 2;
+        harness.hello(addr(1), "alice");
+        harness.hello(addr(2), "bob");
+
+        // when a third client says Hello
+        harness.hello(addr(3), "carol");
+
+        // then it is told the relay is full, and holds no seat
+        let carol = harness.socket.take(addr(3));
+        assert!(matches!(
+            carol[..],
+            [RelayMessage::Rejected { reason: RejectReason::Full }]
+        ));
+        assert!(!harness.core.relay.player_rooms.contains_key(&addr(3)));
+    }
+
+    #[tokio::test]
+    async fn turned_away_sources_are_forgotten_once_idle() {
+        // given a running single-player match, and a Hello turned away as
+        // Full once the relay lets nobody else wait
+        let mut harness = Harness::new(1);
+        harness.core.relay.settings.player_timeout = Duration::from_secs(3600);
+        harness.hello(addr(1), "alice");
+        harness.core.relay.settings.max_pending = 0;
+        harness.hello(addr(2), "spoofed");
+        assert!(!harness.core.relay.holds(&addr(2)));
+        assert!(harness.core.link.peers.contains_key(&addr(2)));
+
+        // when neither is heard from for longer than a peer may idle
+        harness.clock.advance(PEER_IDLE_AFTER);
+        harness.core.service_timers();
+
+        // then the turned-away source is forgotten, and the seated one kept
+        assert!(!harness.core.link.peers.contains_key(&addr(2)));
+        assert!(harness.core.link.peers.contains_key(&addr(1)));
+    }
+
+    #[tokio::test]
+    async fn lobby_seat_is_freed_when_the_hellos_stop() {
+        // given a client seated in a match that has not started
+        let mut harness = Harness::new(2);
+        harness.hello(addr(1), "alice");
+
+        // when it stops saying Hello for longer than the handshake timeout,
+        // though not the player timeout
+        harness.clock.advance(HANDSHAKE_TIMEOUT + Duration::from_secs(1));
+        harness.core.service_timers();
+
+        // then its seat is freed
+        assert!(harness.core.relay.player_rooms.is_empty());
+    }
+
+    #[tokio::test]
+    async fn junk_is_dropped_without_being_decoded() {
+        // given a relay
+        let mut harness = Harness::new(2);
+
+        // when datagrams arrive that cannot be messages
+        harness.core.handle_datagram(&[], addr(1));
+        harness.core.handle_datagram(&[0], addr(1));
+        harness.core.handle_datagram(&[0xff, 1, 2, 3], addr(1));
+
+        // then each is counted as malformed and nobody is answered
+        let metrics = &harness.core.relay.metrics;
+        assert_eq!(metrics.dropped_count(DropReason::Malformed), 3);
+        harness.core.flush();
+        assert!(harness.socket.take(addr(1)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn silent_player_is_dropped_after_the_timeout() {
+        // given a seated player waiting for an opponent
+        let mut harness = Harness::new(2);
+        harness.hello(addr(1), "alice");
+
+        // when it says nothing for longer than the player timeout
+        harness.clock.advance(Duration::from_secs(11));
+        harness.core.service_timers();
+
+        // then its seat is freed and the empty room closed
+        assert!(harness.core.relay.player_rooms.is_empty());
+        assert!(harness.core.relay.rooms.is_empty());
+    }
+
+    #[tokio::test]
+    async fn room_nobody_speaks_in_is_reclaimed_after_the_ttl() {
+        // given a match whose players outlast the room TTL in silence, as
+        // when the player timeout is set longer than it
+        let mut harness = Harness::new(2);
+        harness.core.relay.settings.player_timeout = Duration::from_secs(3600);
+        harness.hello(addr(1), "alice");
+        harness.hello(addr(2), "bob");
+        harness.socket.take(addr(1));
+
+        // when nobody has been heard from for longer than the TTL
+        harness.clock.advance(Duration::from_secs(61));
+        harness.core.service_timers();
+        harness.core.flush();
+
+        // then the room is closed, its players told why, and the sweep
+        // counted
+        assert!(harness.core.relay.rooms.is_empty());
+        assert!(harness.core.relay.player_rooms.is_empty());
+        let alice = harness.socket.take(addr(1));
+        assert!(alice.iter().any(|msg| matches!(
+            msg,
+            RelayMessage::Rejected { reason: RejectReason::Idle }
+        )));
+        let metrics = harness.core.relay.metrics.render();
+        assert!(metrics.contains("relay_rooms_reclaimed_total 1\n"));
+        assert!(metrics.contains("relay_players_timed_out_total 0\n"));
+    }
+
+    #[tokio::test]
+    async fn lone_player_gets_a_bot_after_the_wait() {
+        // given a relay that fills seats with bots after 5 seconds, and one
+        // player waiting
+        let mut harness = Harness::new(2);
+        harness.core.relay.settings.bot_fill = Some(Duration::from_secs(5));
+        harness.hello(addr(1), "alice");
+        harness.socket.take(addr(1));
+
+        // when 4 seconds pass, nothing changes
+        harness.clock.advance(Duration::from_secs(4));
+        harness.core.service_timers();
+        harness.core.flush();
+        assert!(harness.socket.take(addr(1)).is_empty());
+
+        // when the fifth has passed
+        harness.clock.advance(Duration::from_secs(1));
+        harness.core.service_timers();
+        harness.core.flush();
+
+        // then a bot takes slot 1 and the game starts
+        let alice = harness.socket.take(addr(1));
+        assert!(alice.iter().any(|msg| matches!(
+            msg,
+            RelayMessage::PlayerJoined { slot: 1, name } if name == BOT_NAME
+        )));
+        assert!(alice.iter().any(|msg| matches!(msg, RelayMessage::GameStart)));
+    }
+
+    #[test]
+    fn bot_input_never_holds_up_a_tick() {
+        // given a two-player lockstep where the relay plays slot 1
+        let (outbox, mut reports) = mpsc::unbounded_channel();
+        let clock = Arc::new(ManualClock::new());
+        let metrics = Arc::new(Metrics::new());
+        let mut lockstep = Lockstep::new(&settings(2), metrics, clock, None, None, outbox);
+        lockstep.players = vec![Some(addr(1)), None];
+        lockstep.bots = vec![false, true];
+
+        // when the person sends input for tick 0
+        lockstep.receive_input(0, 0, vec![1]);
+
+        // then the tick is played with neutral input for the bot
+        let Ok(DispatchEvent::Send { msg, addr: to }) = reports.try_recv() else {
+            panic!("expected a broadcast");
+        };
+        assert_eq!(to, addr(1));
+        assert!(matches!(
+            msg,
+            RelayMessage::TickInputs { tick: 0, inputs } if inputs == vec![vec![1], vec![]]
+        ));
+        assert!(reports.try_recv().is_err());
+    }
+
+    #[test]
+    fn complete_tick_is_broadcast_and_the_room_advances() {
+        // given a two-player lockstep with both seats taken
+        let (outbox, mut reports) = mpsc::unbounded_channel();
+        let clock = Arc::new(ManualClock::new());
+        let metrics = Arc::new(Metrics::new());
+        let mut lockstep = Lockstep::new(&settings(2), metrics, clock, None, None, outbox);
+        lockstep.players = vec![Some(addr(1)), Some(addr(2))];
+
+        // when both players send input for tick 0
+        lockstep.receive_input(0, 0, vec![1]);
+        lockstep.receive_input(1, 0, vec![2]);
+
+        // then both get tick 0's inputs and the room is on tick 1
+        let mut sent = Vec::new();
+        while let Ok(DispatchEvent::Send { msg, addr }) = reports.try_recv() {
+            sent.push((addr, msg));
+        }
+        assert_eq!(sent.len(), 2);
+        for (_, msg) in &sent {
+            assert!(matches!(
+                msg,
+                RelayMessage::TickInputs { tick: 0, inputs } if *inputs == vec![vec![1], vec![2]]
+            ));
+        }
+        assert_eq!(lockstep.current_tick, 1);
+    }
+
+    #[test]
+    fn lost_broadcast_is_resent_though_input_ahead_of_it_arrived() {
+        // given a two-player lockstep whose player 0 sends input two ticks
+        // ahead, so its input for tick 3 goes out once it has played tick 0
+        let (outbox, mut reports) = mpsc::unbounded_channel();
+        let clock = Arc::new(ManualClock::new());
+        let metrics = Arc::new(Metrics::new());
+        let mut lockstep = Lockstep::new(&settings(2), metrics, clock.clone(), None, None, outbox);
+        lockstep.players = vec![Some(addr(1)), Some(addr(2))];
+
+        // when ticks 0 to 3 are played, player 1 acks them all, and player 0
+        // acks only tick 0 because tick 1's broadcast was lost
+        for tick in 0..4 {
+            lockstep.receive_input(0, tick, vec![1]);
+            lockstep.receive_input(1, tick, vec![2]);
+        }
+        lockstep.acknowledge(0, 0);
+        lockstep.acknowledge(1, 3);
+        while reports.try_recv().is_ok() {}
+        clock.advance(Duration::from_millis(50));
+        lockstep.retransmit_unacked(clock.now());
+
+        // then ticks 1 to 3 are sent to player 0 again, and nothing to player 1
+        let mut resent = Vec::new();
+        while let Ok(DispatchEvent::Send { msg, addr: to }) = reports.try_recv() {
+            assert_eq!(to, addr(1));
+            if let RelayMessage::TickInputs { tick, .. } = msg {
+                resent.push(tick);
+            }
+        }
+        assert_eq!(resent, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn missing_input_is_filled_in_after_the_deadline() {
+        // given a two-player lockstep with a 100 ms tick deadline, where only
+        // player 0 has sent input for tick 0
+        let (outbox, mut reports) = mpsc::unbounded_channel();
+        let clock = Arc::new(ManualClock::new());
+        let metrics = Arc::new(Metrics::new());
+        let settings = Settings {
+            tick_deadline: Some(Duration::from_millis(100)),
+            ..settings(2)
+        };
+        let mut lockstep = Lockstep::new(&settings, metrics, clock.clone(), None, None, outbox);
+        lockstep.players = vec![Some(addr(1)), Some(addr(2))];
+        lockstep.receive_input(0, 0, vec![1]);
+
+        // when the deadline has not yet passed, nothing happens
+        clock.advance(Duration::from_millis(99));
+        lockstep.substitute_missing(clock.now());
+        assert!(reports.try_recv().is_err());
+
+        // when it has
+        clock.advance(Duration::from_millis(1));
+        lockstep.substitute_missing(clock.now());
+
+        // then tick 0 is played with an empty input for player 1
+        let Ok(DispatchEvent::Send { msg, .. }) = reports.try_recv() else {
+            panic!("expected a broadcast");
+        };
+        assert!(matches!(
+            msg,
+            RelayMessage::TickInputs { tick: 0, inputs } if inputs == vec![vec![1], vec![]]
+        ));
+        assert_eq!(lockstep.current_tick, 1);
+        assert_eq!(lockstep.substitute_at(), None);
+    }
+
+    #[test]
+    fn nothing_is_filled_in_at_a_pause() {
+        // given a two-player lockstep with a 100 ms tick deadline, paused
+        // before tick 1 once tick 0 has been played
+        let (outbox, mut reports) = mpsc::unbounded_channel();
+        let clock = Arc::new(ManualClock::new());
+        let metrics = Arc::new(Metrics::new());
+        let settings = Settings {
+            tick_deadline: Some(Duration::from_millis(100)),
+            ..settings(2)
+        };
+        let mut lockstep = Lockstep::new(&settings, metrics, clock.clone(), None, None, outbox);
+        lockstep.players = vec![Some(addr(1)), Some(addr(2))];
+        lockstep.pause(1, 1);
+        lockstep.receive_input(0, 0, vec![1]);
+        lockstep.receive_input(1, 0, vec![2]);
+        lockstep.receive_input(0, 1, vec![3]);
+        while reports.try_recv().is_ok() {}
+
+        // when the deadline passes
+        clock.advance(Duration::from_millis(100));
+        lockstep.substitute_missing(clock.now());
+
+        // then tick 1 waits for player 1
+        assert!(reports.try_recv().is_err());
+        assert_eq!(lockstep.current_tick, 1);
+
+        // until the pause ends, when the deadline starts over
+        lockstep.unpause(1);
+        assert_eq!(
+            lockstep.substitute_at(),
+            Some(clock.now() + Duration::from_millis(100))
+        );
+    }
+}
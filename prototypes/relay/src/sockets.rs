@@ -0,0 +1,213 @@
+//! The relay's UDP sockets: one or more bound addresses acting as one.
+//!
+//! `--bind` may be given several times, say once for an IPv4 interface and
+//! once for IPv6. An IPv6 socket is always bound dual-stack, whatever the
+//! platform's default, so `[::]:7700` takes IPv4 clients as well. Those
+//! arrive with v4-mapped addresses (`[::ffff:a.b.c.d]:port`); [`canonical`]
+//! turns them back into plain IPv4, so a client is the same peer whichever
+//! socket it reached, and replies are mapped again on the way out.
+//!
+//! A reply leaves by the socket its peer was last heard on, so it comes from
+//! the address the client sent to.
+
+use std::collections::HashMap;
+use std::future::poll_fn;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::Poll;
+
+use socket2::{Domain, Protocol, Type};
+use tokio::io::ReadBuf;
+use tokio::net::UdpSocket;
+
+use crate::server::Socket;
+
+/// Peers remembered for routing replies. Past this the table starts over;
+/// a peer still playing is relearned from its next datagram.
+const MAX_ROUTES: usize = 4096;
+
+/// `addr` with a v4-mapped IPv6 address turned into plain IPv4.
+pub fn canonical(addr: SocketAddr) -> SocketAddr {
+    match addr {
+        SocketAddr::V6(v6) => match v6.ip().to_ipv4_mapped() {
+            Some(v4) => SocketAddr::from((v4, v6.port())),
+            None => addr,
+        },
+        SocketAddr::V4(_) => addr,
+    }
+}
+
+/// Bind one UDP socket. IPv6 sockets accept IPv4 clients too.
+pub fn bind_udp(addr: SocketAddr) -> io::Result<UdpSocket> {
+    let socket = socket2::Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(false)?;
+    }
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    UdpSocket::from_std(socket.into())
+}
+
+pub struct UdpSockets {
+    sockets: Vec<UdpSocket>,
+    /// Each socket's bound address, in the same order.
+    locals: Vec<SocketAddr>,
+    /// Which socket each peer was last heard on, when there is a choice.
+    routes: Mutex<HashMap<SocketAddr, usize>>,
+    /// Where the next receive starts looking, so one busy socket cannot
+    /// starve the others.
+    next: AtomicUsize,
+}
+
+impl UdpSockets {
+    /// Bind every address in `addrs`; fails if any of them cannot be bound.
+    pub fn bind(addrs: &[SocketAddr]) -> io::Result<Self> {
+        let sockets = addrs
+            .iter()
+            .map(|addr| bind_udp(*addr))
+            .collect::<io::Result<Vec<_>>>()?;
+        let locals = sockets
+            .iter()
+            .map(UdpSocket::local_addr)
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Self {
+            sockets,
+            locals,
+            routes: Mutex::new(HashMap::new()),
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Where each socket ended up, e.g. with the port filled in for `:0`.
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.locals
+    }
+
+    /// The next datagram on any socket, and who sent it, in canonical form.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let (index, len, src) = poll_fn(|cx| {
+            let count = self.sockets.len();
+            let start = self.next.fetch_add(1, Ordering::Relaxed);
+            for offset in 0..count {
+                let index = (start + offset) % count;
+                let mut read = ReadBuf::new(&mut *buf);
+                if let Poll::Ready(received) = self.sockets[index].poll_recv_from(cx, &mut read) {
+                    return Poll::Ready(received.map(|src| (index, read.filled().len(), src)));
+                }
+            }
+            Poll::Pending
+        })
+        .await?;
+        let src = canonical(src);
+        if self.sockets.len() > 1 {
+            let mut routes = self.routes.lock().unwrap();
+            if routes.len() >= MAX_ROUTES && !routes.contains_key(&src) {
+                routes.clear();
+            }
+            routes.insert(src, index);
+        }
+        Ok((len, src))
+    }
+
+    /// Resolves once every socket can take a datagram.
+    pub async fn writable(&self) -> io::Result<()> {
+        poll_fn(|cx| {
+            let mut ready = true;
+            for socket in &self.sockets {
+                match socket.poll_send_ready(cx) {
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Ready(Ok(())) => {}
+                    Poll::Pending => ready = false,
+                }
+            }
+            if ready {
+                Poll::Ready(Ok(()))
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// The socket to reach `addr` by, and `addr` as that socket needs it.
+    fn route(&self, addr: SocketAddr) -> Option<(&UdpSocket, SocketAddr)> {
+        let learned = self.routes.lock().unwrap().get(&addr).copied();
+        let index =
+            learned.or_else(|| self.locals.iter().position(|local| reaches(*local, addr)))?;
+        let to = match addr {
+            SocketAddr::V4(v4) if self.locals[index].is_ipv6() => {
+                SocketAddr::from((v4.ip().to_ipv6_mapped(), v4.port()))
+            }
+            _ => addr,
+        };
+        Some((&self.sockets[index], to))
+    }
+}
+
+/// Whether a socket bound to `local` can send to `addr`: the same family, or
+/// IPv4 through a dual-stack socket on the unspecified address.
+fn reaches(local: SocketAddr, addr: SocketAddr) -> bool {
+    local.is_ipv4() == addr.is_ipv4() || (local.is_ipv6() && local.ip().is_unspecified())
+}
+
+impl Socket for UdpSockets {
+    fn try_send_to(&self, datagram: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        match self.route(addr) {
+            Some((socket, to)) => socket.try_send_to(datagram, to),
+            None => Err(io::Error::new(
+                io::ErrorKind::AddrNotAvailable,
+                format!("no bound address reaches {addr}"),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v4_mapped_addresses_become_plain_v4() {
+        let mapped: SocketAddr = "[::ffff:192.168.1.20]:4000".parse().unwrap();
+        assert_eq!(canonical(mapped), "192.168.1.20:4000".parse().unwrap());
+        let v6: SocketAddr = "[2001:db8::1]:4000".parse().unwrap();
+        assert_eq!(canonical(v6), v6);
+        let v4: SocketAddr = "10.0.0.5:4000".parse().unwrap();
+        assert_eq!(canonical(v4), v4);
+    }
+
+    #[test]
+    fn only_dual_stack_sockets_reach_across_families() {
+        let v4 = "10.0.0.5:4000".parse().unwrap();
+        let v6 = "[2001:db8::1]:4000".parse().unwrap();
+        assert!(reaches("0.0.0.0:7700".parse().unwrap(), v4));
+        assert!(!reaches("0.0.0.0:7700".parse().unwrap(), v6));
+        assert!(reaches("[::]:7700".parse().unwrap(), v4));
+        assert!(reaches("[::]:7700".parse().unwrap(), v6));
+        assert!(!reaches("[::1]:7700".parse().unwrap(), v4));
+    }
+
+    #[tokio::test]
+    async fn reply_leaves_by_the_socket_the_peer_used() {
+        // given a relay bound to two addresses
+        let any: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let sockets = UdpSockets::bind(&[any, any]).unwrap();
+        let second = sockets.local_addrs()[1];
+
+        // when a client sends to the second one
+        let client = UdpSocket::bind(any).await.unwrap();
+        client.send_to(b"hello", second).await.unwrap();
+        let mut buf = [0u8; 16];
+        let (len, src) = sockets.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], b"hello");
+        assert_eq!(src, client.local_addr().unwrap());
+
+        // then the reply comes back from that same address
+        sockets.try_send_to(b"welcome", src).unwrap();
+        let (len, from) = client.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], b"welcome");
+        assert_eq!(from, second);
+    }
+}
@@ -0,0 +1,724 @@
+//! Protocol tests for the relay as a whole.
+//!
+//! Each test drives a [`RelayCore`] with client datagrams, lets its room
+//! tasks run, and checks exactly what each client was sent, in order. The
+//! socket and clock are fakes and retransmits are pushed out of reach, so
+//! every run sends the same messages.
+//!
+//! Messages do not implement `PartialEq` (`ConfigValue::Float` may be NaN),
+//! so sequences are compared by their serialized bytes.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+
+use crate::metrics::Metrics;
+use crate::results::{MatchEnd, MatchResult};
+use crate::sequence::SequenceCounter;
+use crate::server::{ConsoleCommand, DispatchEvent, RelayCore, Settings};
+use crate::test_support::{ManualClock, MemorySocket, addr, settings};
+use crate::{
+    ClientMessage, MAX_CHAT_LEN, PlayerNetStats, PlayerSlot, RejectReason, RelayMessage,
+    SPECTATOR_SLOT, Tick, serialize,
+};
+
+const ALICE: u16 = 1;
+const BOB: u16 = 2;
+const STRANGER: u16 = 9;
+
+struct Harness {
+    core: RelayCore,
+    reports: mpsc::UnboundedReceiver<DispatchEvent>,
+    socket: Arc<MemorySocket>,
+    /// Where finished matches are logged.
+    results: PathBuf,
+    counters: HashMap<SocketAddr, SequenceCounter>,
+}
+
+impl Harness {
+    fn new() -> Self {
+        // Each relay gets its own standing rooms and results log; tests run
+        // side by side.
+        static RELAYS: AtomicUsize = AtomicUsize::new(0);
+        let relay = RELAYS.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir()
+            .join(format!("relay_state_machine_{}_{relay}", std::process::id()));
+        let results = dir.join("results.jsonl");
+        let _ = std::fs::remove_file(&results);
+        let settings = Settings {
+            // Nothing is ever re-sent on a timer while a test runs.
+            tick_timeout: Duration::from_secs(3600),
+            results_log: Some(results.clone()),
+            ..settings(2)
+        };
+        let directory = dir.join("rooms.toml");
+        let socket = Arc::new(MemorySocket::default());
+        let (core, reports) = RelayCore::new(
+            settings,
+            directory,
+            socket.clone(),
+            Arc::new(ManualClock::new()),
+            None,
+            Arc::new(Metrics::new()),
+        );
+        Self {
+            core,
+            reports,
+            socket,
+            results,
+            counters: HashMap::new(),
+        }
+    }
+
+    /// The datagram `port` would send next for `message`.
+    fn datagram(&mut self, port: u16, message: ClientMessage) -> Vec<u8> {
+        let sequenced = self.counters.entry(addr(port)).or_default().wrap(message);
+        serialize(&sequenced)
+    }
+
+    /// Deliver a datagram and wait until everything it set off has been sent.
+    async fn deliver(&mut self, port: u16, datagram: &[u8]) {
+        self.core.handle_datagram(datagram, addr(port));
+        self.settle().await;
+    }
+
+    async fn send(&mut self, port: u16, message: ClientMessage) {
+        let datagram = self.datagram(port, message);
+        self.deliver(port, &datagram).await;
+    }
+
+    async fn hello(&mut self, port: u16, name: &str) {
+        let hello = ClientMessage::Hello {
+            game_id: "pong".into(),
+            name: name.into(),
+            room: None,
+        };
+        self.send(port, hello).await;
+    }
+
+    async fn resume(&mut self, port: u16, name: &str, slot: PlayerSlot, next_tick: Tick) {
+        let resume = ClientMessage::Resume {
+            game_id: "pong".into(),
+            name: name.into(),
+            room: "arena".into(),
+            slot,
+            next_tick,
+        };
+        self.send(port, resume).await;
+    }
+
+    async fn input(&mut self, port: u16, tick: Tick, byte: u8) {
+        let input = ClientMessage::Input {
+            tick,
+            payload: vec![byte],
+        };
+        self.send(port, input).await;
+    }
+
+    /// Let the room tasks run and pass on what they report, until quiet.
+    async fn settle(&mut self) {
+        for _ in 0..8 {
+            tokio::task::yield_now().await;
+            while let Ok(event) = self.reports.try_recv() {
+                self.core.handle_dispatch_event(event);
+            }
+        }
+        self.core.flush();
+    }
+
+    /// Everything sent to `port` since the last call.
+    fn take(&self, port: u16) -> Vec<RelayMessage> {
+        self.socket.take(addr(port))
+    }
+
+    /// A relay with the standing room `arena`.
+    fn with_arena() -> Self {
+        let mut harness = Self::new();
+        harness.core.run_console_command(ConsoleCommand::RoomCreate {
+            name: "arena".into(),
+            owner_secret: "secret".into(),
+        });
+        harness
+    }
+
+    /// A started match between alice in slot 0 and bob in slot 1, with what
+    /// it took to get there already taken.
+    async fn started() -> Self {
+        let mut harness = Self::new();
+        harness.hello(ALICE, "alice").await;
+        harness.hello(BOB, "bob").await;
+        harness.take(ALICE);
+        harness.take(BOB);
+        harness
+    }
+}
+
+#[track_caller]
+fn assert_sent(actual: Vec<RelayMessage>, expected: Vec<RelayMessage>) {
+    let bytes = |messages: &[RelayMessage]| messages.iter().map(serialize).collect::<Vec<_>>();
+    assert!(
+        bytes(&actual) == bytes(&expected),
+        "sent {actual:#?}\nexpected {expected:#?}"
+    );
+}
+
+fn joined(slot: u8, name: &str) -> RelayMessage {
+    RelayMessage::PlayerJoined {
+        slot,
+        name: name.into(),
+    }
+}
+
+fn tick_inputs(tick: Tick, inputs: &[u8]) -> RelayMessage {
+    RelayMessage::TickInputs {
+        tick,
+        inputs: inputs.iter().map(|byte| vec![*byte]).collect(),
+    }
+}
+
+#[tokio::test]
+async fn hellos_seat_players_and_start_the_match() {
+    // given a relay for two-player matches
+    let mut harness = Harness::new();
+
+    // when alice says Hello
+    harness.hello(ALICE, "alice").await;
+
+    // then she is welcomed to slot 0 and sees herself join
+    assert_sent(
+        harness.take(ALICE),
+        vec![RelayMessage::Welcome { player_slot: 0 }, joined(0, "alice")],
+    );
+
+    // when bob says Hello
+    harness.hello(BOB, "bob").await;
+
+    // then the match starts, ahead of the roster since it is more urgent
+    assert_sent(
+        harness.take(BOB),
+        vec![
+            RelayMessage::GameStart,
+            RelayMessage::Welcome { player_slot: 1 },
+            joined(0, "alice"),
+            joined(1, "bob"),
+        ],
+    );
+    assert_sent(
+        harness.take(ALICE),
+        vec![RelayMessage::GameStart, joined(1, "bob")],
+    );
+}
+
+#[tokio::test]
+async fn complete_ticks_are_broadcast_once() {
+    // given a started match
+    let mut harness = Harness::started().await;
+
+    // when alice sends input for tick 0
+    harness.input(ALICE, 0, 10).await;
+
+    // then nothing goes out until bob has too
+    assert_sent(harness.take(ALICE), vec![]);
+    assert_sent(harness.take(BOB), vec![]);
+
+    harness.input(BOB, 0, 20).await;
+    assert_sent(harness.take(ALICE), vec![tick_inputs(0, &[10, 20])]);
+    assert_sent(harness.take(BOB), vec![tick_inputs(0, &[10, 20])]);
+}
+
+#[tokio::test]
+async fn duplicate_datagrams_and_inputs_change_nothing() {
+    // given a started match where alice's input for tick 0 has arrived
+    let mut harness = Harness::started().await;
+    let datagram = harness.datagram(
+        ALICE,
+        ClientMessage::Input {
+            tick: 0,
+            payload: vec![10],
+        },
+    );
+    harness.deliver(ALICE, &datagram).await;
+
+    // when the network duplicates that datagram, and alice re-sends the
+    // input with a new sequence number and a different payload
+    harness.deliver(ALICE, &datagram).await;
+    harness.input(ALICE, 0, 99).await;
+
+    // then the tick still completes with her latest input for it
+    harness.input(BOB, 0, 20).await;
+    assert_sent(harness.take(ALICE), vec![tick_inputs(0, &[99, 20])]);
+    assert_sent(harness.take(BOB), vec![tick_inputs(0, &[99, 20])]);
+
+    // and input for a tick already played is not broadcast again
+    harness.input(ALICE, 0, 10).await;
+    harness.input(BOB, 0, 20).await;
+    assert_sent(harness.take(ALICE), vec![]);
+    assert_sent(harness.take(BOB), vec![]);
+}
+
+#[tokio::test]
+async fn input_outside_the_window_is_dropped() {
+    // given a started match on tick 0 with an input window of 3
+    let mut harness = Harness::started().await;
+
+    // when both players send input far ahead, then for tick 0
+    harness.input(ALICE, 10, 1).await;
+    harness.input(BOB, 10, 2).await;
+    harness.input(ALICE, 0, 10).await;
+    harness.input(BOB, 0, 20).await;
+
+    // then only tick 0 is played; tick 10 never completes
+    assert_sent(harness.take(ALICE), vec![tick_inputs(0, &[10, 20])]);
+
+    // when they send input just inside the window
+    harness.input(ALICE, 2, 12).await;
+    harness.input(BOB, 2, 22).await;
+    harness.input(ALICE, 1, 11).await;
+    harness.input(BOB, 1, 21).await;
+
+    // then it was held, and both ticks go out in order
+    assert_sent(
+        harness.take(ALICE),
+        vec![tick_inputs(1, &[11, 21]), tick_inputs(2, &[12, 22])],
+    );
+}
+
+#[tokio::test]
+async fn unknown_senders_are_ignored() {
+    // given a started match
+    let mut harness = Harness::started().await;
+
+    // when a client that never said Hello sends input and acks
+    harness.input(STRANGER, 0, 66).await;
+    harness.send(STRANGER, ClientMessage::AckTick { tick: 0 }).await;
+    harness.send(STRANGER, ClientMessage::Goodbye).await;
+
+    // then nobody hears anything, and the match plays on
+    assert_sent(harness.take(STRANGER), vec![]);
+    assert_sent(harness.take(ALICE), vec![]);
+    harness.input(ALICE, 0, 10).await;
+    harness.input(BOB, 0, 20).await;
+    assert_sent(harness.take(ALICE), vec![tick_inputs(0, &[10, 20])]);
+}
+
+#[tokio::test]
+async fn repeated_hello_resends_the_welcome() {
+    // given a started match
+    let mut harness = Harness::started().await;
+
+    // when bob's Hello is answered again, as after a lost reply
+    harness.hello(BOB, "bob").await;
+
+    // then he keeps his slot and hears the game has started
+    assert_sent(
+        harness.take(BOB),
+        vec![
+            RelayMessage::GameStart,
+            RelayMessage::Welcome { player_slot: 1 },
+            joined(0, "alice"),
+            joined(1, "bob"),
+        ],
+    );
+    assert_sent(harness.take(ALICE), vec![]);
+}
+
+#[tokio::test]
+async fn missed_tick_is_sent_again_on_request() {
+    // given a started match that has played tick 0
+    let mut harness = Harness::started().await;
+    harness.input(ALICE, 0, 10).await;
+    harness.input(BOB, 0, 20).await;
+    harness.take(ALICE);
+    harness.take(BOB);
+
+    // when alice says she missed it
+    harness.send(ALICE, ClientMessage::RequestTick { tick: 0 }).await;
+
+    // then only she gets it again
+    assert_sent(harness.take(ALICE), vec![tick_inputs(0, &[10, 20])]);
+    assert_sent(harness.take(BOB), vec![]);
+}
+
+#[tokio::test]
+async fn leaving_ends_the_match_and_the_player_can_come_back() {
+    // given a started match
+    let mut harness = Harness::started().await;
+
+    // when alice says Goodbye
+    harness.send(ALICE, ClientMessage::Goodbye).await;
+
+    // then bob is told and the match is over
+    assert_sent(harness.take(BOB), vec![RelayMessage::PlayerLeft { slot: 0 }]);
+    assert_sent(harness.take(ALICE), vec![]);
+
+    // when alice reconnects from the same address, numbering from zero again
+    harness.counters.remove(&addr(ALICE));
+    harness.hello(ALICE, "alice").await;
+
+    // then she is seated in a new match
+    assert_sent(
+        harness.take(ALICE),
+        vec![RelayMessage::Welcome { player_slot: 0 }, joined(0, "alice")],
+    );
+}
+
+#[tokio::test]
+async fn lost_match_resumes_at_the_earliest_tick_anyone_needs() {
+    // given a relay that lost the match in arena, where alice had played
+    // up to tick 41 and bob only up to tick 40
+    let mut harness = Harness::with_arena();
+
+    // when bob comes back to slot 1
+    harness.resume(BOB, "bob", 1, 41).await;
+
+    // then he gets his old slot and waits for alice
+    assert_sent(
+        harness.take(BOB),
+        vec![RelayMessage::Welcome { player_slot: 1 }, joined(1, "bob")],
+    );
+
+    // when alice comes back to slot 0
+    harness.resume(ALICE, "alice", 0, 42).await;
+
+    // then both are told the match resumes at tick 41, not 0
+    assert_sent(
+        harness.take(ALICE),
+        vec![
+            RelayMessage::Resumed { tick: 41 },
+            RelayMessage::Welcome { player_slot: 0 },
+            joined(1, "bob"),
+            joined(0, "alice"),
+        ],
+    );
+    assert_sent(
+        harness.take(BOB),
+        vec![RelayMessage::Resumed { tick: 41 }, joined(0, "alice")],
+    );
+
+    // and their re-sent inputs for tick 41 complete it
+    harness.input(ALICE, 41, 10).await;
+    harness.input(BOB, 41, 20).await;
+    assert_sent(harness.take(ALICE), vec![tick_inputs(41, &[10, 20])]);
+    assert_sent(harness.take(BOB), vec![tick_inputs(41, &[10, 20])]);
+}
+
+#[tokio::test]
+async fn resuming_a_match_that_is_still_running_is_refused() {
+    // given a resumed match running in arena
+    let mut harness = Harness::with_arena();
+    harness.resume(ALICE, "alice", 0, 5).await;
+    harness.resume(BOB, "bob", 1, 5).await;
+    harness.take(ALICE);
+    harness.take(BOB);
+
+    // when someone else claims slot 0
+    harness.resume(STRANGER, "mallory", 0, 5).await;
+
+    // then it is refused and the players hear nothing
+    assert_sent(
+        harness.take(STRANGER),
+        vec![RelayMessage::Rejected {
+            reason: RejectReason::BadRoom,
+        }],
+    );
+    assert_sent(harness.take(ALICE), vec![]);
+}
+
+#[tokio::test]
+async fn running_match_hears_every_players_connection_stats() {
+    // given a started match where only alice has sent anything since
+    let mut harness = Harness::started().await;
+    harness.input(ALICE, 0, 10).await;
+    harness.input(ALICE, 1, 11).await;
+
+    // when the relay's timers run
+    harness.core.service_timers();
+    harness.settle().await;
+
+    // then both players hear how many packets each seat has sent
+    let stats = |slot: PlayerSlot, packets_received: u32| PlayerNetStats {
+        slot,
+        packets_received,
+        last_seen_ms: 0,
+        rtt_ms: None,
+        jitter_ms: None,
+    };
+    let expected = || RelayMessage::NetStats {
+        players: vec![stats(0, 2), stats(1, 0)],
+    };
+    assert_sent(harness.take(ALICE), vec![expected()]);
+    assert_sent(harness.take(BOB), vec![expected()]);
+}
+
+#[tokio::test]
+async fn state_hashes_go_to_the_other_players_only() {
+    // given a started match
+    let mut harness = Harness::started().await;
+
+    // when alice reports her state's hash
+    let hash = ClientMessage::StateHash {
+        tick: 30,
+        hash: 0xfeed,
+    };
+    harness.send(ALICE, hash).await;
+
+    // then bob hears it from her slot, and she does not hear it back
+    let forwarded = RelayMessage::StateHash {
+        slot: 0,
+        tick: 30,
+        hash: 0xfeed,
+    };
+    assert_sent(harness.take(BOB), vec![forwarded]);
+    assert_sent(harness.take(ALICE), vec![]);
+}
+
+#[tokio::test]
+async fn a_snapshot_answers_a_request_through_the_relay() {
+    // given a started match where bob has asked for a snapshot
+    let mut harness = Harness::started().await;
+    harness.send(BOB, ClientMessage::RequestSnapshot).await;
+    assert_sent(
+        harness.take(ALICE),
+        vec![RelayMessage::SnapshotRequested { slot: 1 }],
+    );
+
+    // when alice answers
+    let snapshot = ClientMessage::Snapshot {
+        tick: 40,
+        state: vec![1, 2, 3],
+    };
+    harness.send(ALICE, snapshot).await;
+
+    // then bob gets her state, and neither hears their own message back
+    let forwarded = RelayMessage::Snapshot {
+        slot: 0,
+        tick: 40,
+        state: vec![1, 2, 3],
+    };
+    assert_sent(harness.take(BOB), vec![forwarded]);
+    assert_sent(harness.take(ALICE), vec![]);
+}
+
+#[tokio::test]
+async fn a_pause_is_announced_to_every_player_until_lifted() {
+    // given a started match that has played tick 0
+    let mut harness = Harness::started().await;
+    harness.input(ALICE, 0, 1).await;
+    harness.input(BOB, 0, 2).await;
+    harness.take(ALICE);
+    harness.take(BOB);
+
+    // when bob pauses before tick 1, then alice before tick 5
+    harness.send(BOB, ClientMessage::Pause { tick: 1 }).await;
+    harness.send(ALICE, ClientMessage::Pause { tick: 5 }).await;
+
+    // then both hear bob's pause twice: the first one holds
+    let paused = || RelayMessage::Paused { by_slot: 1, tick: 1 };
+    assert_sent(harness.take(ALICE), vec![paused(), paused()]);
+    assert_sent(harness.take(BOB), vec![paused(), paused()]);
+
+    // and when alice carries on, both hear that
+    harness.send(ALICE, ClientMessage::Unpause).await;
+    let unpaused = || RelayMessage::Unpaused { by_slot: 0 };
+    assert_sent(harness.take(ALICE), vec![unpaused()]);
+    assert_sent(harness.take(BOB), vec![unpaused()]);
+}
+
+#[tokio::test]
+async fn a_rematch_starts_over_from_tick_zero_once_everyone_asks() {
+    // given a match that has played tick 0, with the score reported
+    let mut harness = Harness::started().await;
+    harness.input(ALICE, 0, 1).await;
+    harness.input(BOB, 0, 2).await;
+    let scores = ClientMessage::Scores { scores: vec![3, 1] };
+    harness.send(ALICE, scores).await;
+    harness.take(ALICE);
+    harness.take(BOB);
+
+    // when alice asks for a rematch, twice
+    harness.send(ALICE, ClientMessage::Rematch).await;
+    harness.send(ALICE, ClientMessage::Rematch).await;
+
+    // then bob hears about it once, and nothing starts yet
+    assert_sent(harness.take(ALICE), vec![]);
+    assert_sent(
+        harness.take(BOB),
+        vec![RelayMessage::RematchRequested { slot: 0 }],
+    );
+
+    // when bob asks too
+    harness.send(BOB, ClientMessage::Rematch).await;
+
+    // then both start again, and the old game is one line of the log
+    assert_sent(
+        harness.take(ALICE),
+        vec![
+            RelayMessage::RematchRequested { slot: 1 },
+            RelayMessage::RematchStart,
+        ],
+    );
+    assert_sent(harness.take(BOB), vec![RelayMessage::RematchStart]);
+    let log = std::fs::read_to_string(&harness.results).unwrap();
+    let result: MatchResult = serde_json::from_str(log.trim()).unwrap();
+    assert_eq!(result.end, MatchEnd::Rematch);
+    assert_eq!(result.scores, Some(vec![3, 1]));
+    assert_eq!(result.ticks, 1);
+
+    // and the next tick broadcast is tick 0 of the new game
+    harness.input(ALICE, 0, 5).await;
+    harness.input(BOB, 0, 6).await;
+    assert_sent(harness.take(ALICE), vec![tick_inputs(0, &[5, 6])]);
+}
+
+#[tokio::test]
+async fn a_spectator_follows_the_match_without_holding_it_up() {
+    // given a match that has played tick 0
+    let mut harness = Harness::started().await;
+    harness.input(ALICE, 0, 1).await;
+    harness.input(BOB, 0, 2).await;
+    harness.take(ALICE);
+    harness.take(BOB);
+
+    // when a stranger asks to watch
+    let spectate = || ClientMessage::Spectate {
+        game_id: "pong".into(),
+        room: None,
+    };
+    harness.send(STRANGER, spectate()).await;
+
+    // then it hears who is playing and the tick broadcasts resume from
+    assert_sent(
+        harness.take(STRANGER),
+        vec![
+            joined(0, "alice"),
+            joined(1, "bob"),
+            RelayMessage::Spectating { tick: 1 },
+        ],
+    );
+
+    // and it gets the next tick, and a missed one when it asks
+    harness.input(ALICE, 1, 3).await;
+    harness.input(BOB, 1, 4).await;
+    let request = ClientMessage::RequestTick { tick: 0 };
+    harness.send(STRANGER, request).await;
+    assert_sent(
+        harness.take(STRANGER),
+        vec![tick_inputs(1, &[3, 4]), tick_inputs(0, &[1, 2])],
+    );
+
+    // when it asks for a snapshot and alice answers
+    harness.send(STRANGER, ClientMessage::RequestSnapshot).await;
+    let requested = || RelayMessage::SnapshotRequested {
+        slot: SPECTATOR_SLOT,
+    };
+    let tick_then_request = || vec![tick_inputs(1, &[3, 4]), requested()];
+    assert_sent(harness.take(ALICE), tick_then_request());
+    assert_sent(harness.take(BOB), tick_then_request());
+    let snapshot = ClientMessage::Snapshot {
+        tick: 2,
+        state: vec![7],
+    };
+    harness.send(ALICE, snapshot).await;
+
+    // then the spectator gets it
+    let snapshot = || RelayMessage::Snapshot {
+        slot: 0,
+        tick: 2,
+        state: vec![7],
+    };
+    assert_sent(harness.take(STRANGER), vec![snapshot()]);
+    assert_sent(harness.take(BOB), vec![snapshot()]);
+
+    // and once it says Goodbye it hears nothing more, and play goes on
+    harness.send(STRANGER, ClientMessage::Goodbye).await;
+    harness.input(ALICE, 2, 5).await;
+    harness.input(BOB, 2, 6).await;
+    assert_sent(harness.take(STRANGER), vec![]);
+    assert_sent(harness.take(ALICE), vec![tick_inputs(2, &[5, 6])]);
+}
+
+#[tokio::test]
+async fn chat_goes_to_everyone_else_cut_to_length() {
+    // given a started match with a spectator watching
+    let mut harness = Harness::started().await;
+    let spectate = ClientMessage::Spectate {
+        game_id: "pong".into(),
+        room: None,
+    };
+    harness.send(STRANGER, spectate).await;
+    harness.take(STRANGER);
+
+    // when bob says something far too long
+    let text = "a".repeat(MAX_CHAT_LEN + 10);
+    harness.send(BOB, ClientMessage::Chat { text }).await;
+
+    // then alice and the spectator hear the first MAX_CHAT_LEN characters
+    // from his slot, and he does not hear it back
+    let said = || RelayMessage::Chat {
+        slot: 1,
+        text: "a".repeat(MAX_CHAT_LEN),
+    };
+    assert_sent(harness.take(ALICE), vec![said()]);
+    assert_sent(harness.take(STRANGER), vec![said()]);
+    assert_sent(harness.take(BOB), vec![]);
+
+    // when the spectator tries to chat
+    let chat = ClientMessage::Chat { text: "hi".into() };
+    harness.send(STRANGER, chat).await;
+
+    // then nobody hears it
+    assert_sent(harness.take(ALICE), vec![]);
+    assert_sent(harness.take(BOB), vec![]);
+}
+
+#[tokio::test]
+async fn a_spectator_is_turned_away_when_nothing_is_on() {
+    // given a relay with nobody playing
+    let mut harness = Harness::new();
+
+    // when a stranger asks to watch
+    let spectate = ClientMessage::Spectate {
+        game_id: "pong".into(),
+        room: None,
+    };
+    harness.send(STRANGER, spectate).await;
+
+    // then it is told there is no match
+    let reason = RejectReason::NoMatch;
+    assert_sent(
+        harness.take(STRANGER),
+        vec![RelayMessage::Rejected { reason }],
+    );
+}
+
+#[tokio::test]
+async fn finished_match_is_appended_to_the_results_log() {
+    // given a started match that has played a tick, with the score reported
+    let mut harness = Harness::started().await;
+    harness.input(ALICE, 0, 1).await;
+    harness.input(BOB, 0, 2).await;
+    let scores = ClientMessage::Scores { scores: vec![3, 1] };
+    harness.send(ALICE, scores).await;
+
+    // when bob leaves
+    harness.send(BOB, ClientMessage::Goodbye).await;
+
+    // then the match is one line of the log
+    let log = std::fs::read_to_string(&harness.results).unwrap();
+    let results: Vec<MatchResult> = log
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(results.len(), 1);
+    let result = &results[0];
+    assert_eq!(result.game_id, "pong");
+    assert_eq!(result.players, vec!["alice".to_string(), "bob".to_string()]);
+    assert_eq!(result.scores, Some(vec![3, 1]));
+    assert_eq!(result.end, MatchEnd::PlayerLeft);
+    assert_eq!(result.ticks, 1);
+}
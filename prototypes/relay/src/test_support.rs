@@ -0,0 +1,102 @@
+//! Stand-ins for the network and the clock, for tests that drive a
+//! [`RelayCore`](crate::server::RelayCore) directly.
+//!
+//! Nothing here waits on a real socket or timer, so a test sees the same
+//! datagrams on every run. Tests outside this crate turn on the
+//! `test-support` feature to use it.
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::sequence::Sequenced;
+use crate::server::{Clock, Settings, Socket};
+use crate::{RelayMessage, deserialize};
+
+/// Keeps every datagram instead of sending it, until a test takes it.
+#[derive(Default)]
+pub struct MemorySocket {
+    sent: Mutex<Vec<(SocketAddr, Vec<u8>)>>,
+}
+
+impl MemorySocket {
+    /// Every datagram sent to `addr` since the last call, as sent.
+    pub fn take_datagrams(&self, addr: SocketAddr) -> Vec<Vec<u8>> {
+        let mut sent = self.sent.lock().unwrap();
+        let (to_addr, rest) = sent.drain(..).partition(|(to, _)| *to == addr);
+        *sent = rest;
+        to_addr.into_iter().map(|(_, bytes)| bytes).collect()
+    }
+
+    /// Everything sent to `addr` since the last call, decoded.
+    pub fn take(&self, addr: SocketAddr) -> Vec<RelayMessage> {
+        self.take_datagrams(addr)
+            .into_iter()
+            .map(|bytes| {
+                deserialize::<Sequenced<RelayMessage>>(&bytes)
+                    .unwrap()
+                    .message
+            })
+            .collect()
+    }
+}
+
+impl Socket for MemorySocket {
+    fn try_send_to(&self, datagram: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        self.sent.lock().unwrap().push((addr, datagram.to_vec()));
+        Ok(datagram.len())
+    }
+}
+
+/// A clock that only moves when told to.
+pub struct ManualClock(Mutex<Instant>);
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self(Mutex::new(Instant::now()))
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// Settings for `players`-player matches with limits no test runs into by
+/// accident. Tests that need something else override fields of this.
+pub fn settings(players: usize) -> Settings {
+    Settings {
+        players,
+        room_capacity: 4,
+        tick_timeout: Duration::from_millis(50),
+        tick_deadline: None,
+        input_window: 3,
+        input_history: 16,
+        rate_limit: 1000,
+        rate_burst: 1000,
+        player_timeout: Duration::from_secs(10),
+        max_pending: 64,
+        room_ttl: Duration::from_secs(60),
+        bot_fill: None,
+        tick_log: None,
+        record_dir: None,
+        results_log: None,
+    }
+}
+
+/// A client's address on this machine.
+pub fn addr(port: u16) -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], port))
+}
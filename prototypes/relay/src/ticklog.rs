@@ -0,0 +1,186 @@
+//! Post-mortem log of the last few ticks.
+//!
+//! A lockstep bug reported from the field usually arrives as "it froze around
+//! five minutes in". A [`TickLog`] keeps the last N ticks' inputs and whatever
+//! state the owner records alongside them in a ring buffer, and writes them to
+//! a file when something goes wrong: a protocol error, a desync, or a panic.
+//! The file is plain text, one tick per line, oldest first.
+//!
+//! Both the relay (per room) and game clients keep one when started with
+//! `--tick-log <ticks>`.
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::Tick;
+
+/// Repeated trouble (a client spamming garbage) writes one dump, not hundreds.
+pub const DUMP_COOLDOWN: Duration = Duration::from_secs(10);
+
+pub struct TickLog<T: Debug> {
+    /// Names the dump file, e.g. `relay-room-3` or `net_pong`.
+    label: String,
+    capacity: usize,
+    entries: VecDeque<(Tick, T)>,
+    dump_dir: PathBuf,
+    last_dump: Option<Instant>,
+}
+
+impl<T: Debug> TickLog<T> {
+    pub fn new(label: impl Into<String>, capacity: usize, dump_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            label: label.into(),
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+            dump_dir: dump_dir.into(),
+            last_dump: None,
+        }
+    }
+
+    /// Remember `entry` for `tick`, forgetting the oldest tick if full.
+    pub fn record(&mut self, tick: Tick, entry: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((tick, entry));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Recorded ticks, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = &(Tick, T)> {
+        self.entries.iter()
+    }
+
+    pub fn write_to(&self, out: &mut impl Write, reason: &str) -> io::Result<()> {
+        writeln!(out, "# {}: {reason}", self.label)?;
+        writeln!(out, "# last {} ticks, oldest first", self.entries.len())?;
+        for (tick, entry) in &self.entries {
+            writeln!(out, "{tick}: {entry:?}")?;
+        }
+        Ok(())
+    }
+
+    /// Write the log to a new file in the dump directory and return its path.
+    /// Returns `None` if nothing was written: the log is empty, a dump was
+    /// written within [`DUMP_COOLDOWN`], or the write failed (reported on
+    /// stderr).
+    pub fn dump(&mut self, reason: &str) -> Option<PathBuf> {
+        if self.entries.is_empty()
+            || self
+                .last_dump
+                .is_some_and(|last| last.elapsed() < DUMP_COOLDOWN)
+        {
+            return None;
+        }
+        self.last_dump = Some(Instant::now());
+
+        let millis = SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis())
+            .unwrap_or_default();
+        let path = self.dump_dir.join(format!("{}-{millis}.ticklog", self.label));
+        let result = std::fs::create_dir_all(&self.dump_dir)
+            .and_then(|()| std::fs::File::create(&path))
+            .and_then(|file| {
+                let mut out = io::BufWriter::new(file);
+                self.write_to(&mut out, reason)?;
+                out.flush()
+            });
+        match result {
+            Ok(()) => {
+                eprintln!("{}: {reason}; wrote tick log to {}", self.label, path.display());
+                Some(path)
+            }
+            Err(e) => {
+                eprintln!("{}: failed to write tick log {}: {e}", self.label, path.display());
+                None
+            }
+        }
+    }
+}
+
+/// A log dropped while its thread unwinds from a panic dumps itself, so a
+/// crash leaves the ticks that led up to it behind.
+impl<T: Debug> Drop for TickLog<T> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.dump("panic");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ticklog_test_{name}_{}", std::process::id()))
+    }
+
+    #[test]
+    fn keeps_only_the_most_recent_ticks() {
+        // given a log with room for three ticks
+        let mut log = TickLog::new("test", 3, temp_dir("ring"));
+
+        // when five are recorded
+        for tick in 0..5 {
+            log.record(tick, tick * 10);
+        }
+
+        // then the last three remain, oldest first
+        let ticks: Vec<Tick> = log.entries().map(|(tick, _)| *tick).collect();
+        assert_eq!(ticks, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn writes_one_line_per_tick() {
+        // given a log of two ticks
+        let mut log = TickLog::new("relay-room-0", 8, temp_dir("format"));
+        log.record(7, vec![1u8, 2]);
+        log.record(8, vec![3u8, 4]);
+
+        // when it is written out
+        let mut out = Vec::new();
+        log.write_to(&mut out, "malformed message").unwrap();
+
+        // then there is a header and each tick's entry
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "# relay-room-0: malformed message\n\
+             # last 2 ticks, oldest first\n\
+             7: [1, 2]\n\
+             8: [3, 4]\n"
+        );
+    }
+
+    #[test]
+    fn repeated_dumps_are_held_back() {
+        // given a log with something in it
+        let dir = temp_dir("cooldown");
+        let mut log = TickLog::new("test", 4, &dir);
+        log.record(0, "state");
+
+        // when two dumps are requested back to back
+        let first = log.dump("first");
+        let second = log.dump("second");
+
+        // then only the first is written
+        assert!(first.as_ref().is_some_and(|path| path.exists()));
+        assert_eq!(second, None);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,152 @@
+//! Relay settings that can change while it runs.
+//!
+//! With `--config <path>` the relay reads a TOML file of overrides at start
+//! and again whenever the file changes or the process gets SIGHUP. Every key
+//! is optional; one left out keeps whatever the command line or the last
+//! reload said. Rooms already playing pick up the new tick timeout and
+//! deadline, and nothing is dropped: a lower room capacity only stops new
+//! rooms from opening. A file that does not parse is reported and ignored.
+//!
+//! ```toml
+//! log_level = "debug"
+//! room_capacity = 128
+//! tick_timeout_ms = 80
+//! tick_deadline_ms = 0   # 0 turns the deadline off
+//! player_timeout_secs = 20
+//! ```
+
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::server::Settings;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tuning {
+    /// `error`, `warn`, `info`, `debug` or `trace`.
+    pub log_level: Option<String>,
+    pub room_capacity: Option<usize>,
+    pub tick_timeout_ms: Option<u64>,
+    /// 0 waits for every player, as without `--tick-deadline`.
+    pub tick_deadline_ms: Option<u64>,
+    pub player_timeout_secs: Option<u64>,
+    pub max_pending: Option<usize>,
+    pub room_ttl_secs: Option<u64>,
+    /// 0 waits for people, as without `--bot-fill`.
+    pub bot_fill_secs: Option<u64>,
+    pub rate_limit: Option<u32>,
+    pub rate_burst: Option<u32>,
+}
+
+impl Tuning {
+    /// Read and check the file at `path`. An error names what is wrong, and
+    /// leaves the relay running as it was.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let tuning: Self = toml::from_str(contents).map_err(|e| e.to_string())?;
+        if let Some(level) = &tuning.log_level {
+            level
+                .parse::<tracing::Level>()
+                .map_err(|_| format!("log_level: unknown level {level:?}"))?;
+        }
+        Ok(tuning)
+    }
+
+    /// The log level to switch to, if the file sets one.
+    pub fn log_level(&self) -> Option<tracing::Level> {
+        self.log_level
+            .as_deref()
+            .and_then(|level| level.parse().ok())
+    }
+
+    /// Overwrite whatever `settings` this file sets.
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(capacity) = self.room_capacity {
+            settings.room_capacity = capacity;
+        }
+        if let Some(ms) = self.tick_timeout_ms {
+            settings.tick_timeout = Duration::from_millis(ms.max(1));
+        }
+        if let Some(ms) = self.tick_deadline_ms {
+            settings.tick_deadline = (ms > 0).then(|| Duration::from_millis(ms));
+        }
+        if let Some(secs) = self.player_timeout_secs {
+            settings.player_timeout = Duration::from_secs(secs);
+        }
+        if let Some(pending) = self.max_pending {
+            settings.max_pending = pending;
+        }
+        if let Some(secs) = self.room_ttl_secs {
+            settings.room_ttl = Duration::from_secs(secs);
+        }
+        if let Some(secs) = self.bot_fill_secs {
+            settings.bot_fill = (secs > 0).then(|| Duration::from_secs(secs));
+        }
+        if let Some(rate) = self.rate_limit {
+            settings.rate_limit = rate;
+        }
+        if let Some(burst) = self.rate_burst {
+            settings.rate_burst = burst;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> Settings {
+        Settings {
+            players: 2,
+            room_capacity: 64,
+            tick_timeout: Duration::from_millis(50),
+            tick_deadline: Some(Duration::from_millis(100)),
+            input_window: 3,
+            input_history: 256,
+            rate_limit: 400,
+            rate_burst: 200,
+            player_timeout: Duration::from_secs(10),
+            max_pending: 64,
+            room_ttl: Duration::from_secs(60),
+            bot_fill: None,
+            tick_log: None,
+            record_dir: None,
+            results_log: None,
+        }
+    }
+
+    #[test]
+    fn only_the_keys_given_change() {
+        // given a file that sets a few keys
+        let tuning = Tuning::parse(
+            "log_level = \"debug\"\nroom_capacity = 8\ntick_deadline_ms = 0\nbot_fill_secs = 5\n",
+        )
+        .unwrap();
+
+        // when applied
+        let mut settings = settings();
+        tuning.apply(&mut settings);
+
+        // then those change and the rest are as they were
+        assert_eq!(tuning.log_level(), Some(tracing::Level::DEBUG));
+        assert_eq!(settings.room_capacity, 8);
+        assert_eq!(settings.tick_deadline, None);
+        assert_eq!(settings.bot_fill, Some(Duration::from_secs(5)));
+        assert_eq!(settings.tick_timeout, Duration::from_millis(50));
+        assert_eq!(settings.player_timeout, Duration::from_secs(10));
+    }
+
+    #[test]
+    fn mistakes_are_refused_rather_than_half_applied() {
+        assert!(Tuning::parse("room_capacty = 8\n").is_err());
+        assert!(Tuning::parse("log_level = \"loud\"\n").is_err());
+        assert!(Tuning::parse("room_capacity = -1\n").is_err());
+        assert_eq!(Tuning::parse(""), Ok(Tuning::default()));
+    }
+}
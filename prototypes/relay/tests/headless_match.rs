@@ -0,0 +1,261 @@
+//! A relay and two headless Pong clients in one process.
+//!
+//! The clients are net_pong without a window: they say Hello, send each
+//! tick's paddle input a little ahead of playing it, and step a
+//! [`PongState`] with whatever the relay broadcasts. Datagrams go through an
+//! in-memory socket instead of the network, so every run is the same. Were
+//! the relay ever to give the clients different inputs for a tick, their
+//! states would part, and the checksums with them.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use pong_sim::fixed::Fx;
+use pong_sim::{PADDLE_HEIGHT, PLAYER_COUNT, PongState};
+use prototype_relay::metrics::Metrics;
+use prototype_relay::sequence::{SequenceCounter, Sequenced};
+use prototype_relay::server::{DispatchEvent, RelayCore, Settings};
+use prototype_relay::test_support::{self, ManualClock, MemorySocket};
+use prototype_relay::{ClientMessage, Payload, RelayMessage, Tick, deserialize, serialize};
+use tokio::sync::mpsc;
+
+/// Ticks to play: long enough for the ball to cross the arena a few times.
+const TICKS: Tick = 600;
+/// Ticks between sending an input and playing it, as net_pong does.
+const INPUT_DELAY: Tick = 2;
+/// net_pong's default tick rate.
+const DT: Fx = Fx::from_f32(1.0 / 64.0);
+
+type PongClientMessage = ClientMessage<Payload<f32>>;
+type PongRelayMessage = RelayMessage<Payload<f32>>;
+
+/// A player with no window: the simulation, and the lockstep bookkeeping
+/// that feeds it.
+struct HeadlessClient {
+    addr: SocketAddr,
+    name: &'static str,
+    counter: SequenceCounter,
+    slot: Option<usize>,
+    started: bool,
+    state: PongState,
+    /// The next tick to play.
+    sim_tick: Tick,
+    /// The next tick to send input for.
+    next_input: Tick,
+    /// Broadcast inputs by tick, until they are played.
+    inputs: BTreeMap<Tick, [f32; PLAYER_COUNT]>,
+    /// A checksum of the state after each tick played.
+    checksums: Vec<u64>,
+    /// Paddle hits, wall bounces and points seen.
+    events: usize,
+}
+
+impl HeadlessClient {
+    fn new(port: u16, name: &'static str) -> Self {
+        Self {
+            addr: SocketAddr::from(([127, 0, 0, 1], port)),
+            name,
+            counter: SequenceCounter::default(),
+            slot: None,
+            started: false,
+            state: PongState::default(),
+            sim_tick: 0,
+            next_input: 0,
+            inputs: BTreeMap::new(),
+            checksums: Vec::new(),
+            events: 0,
+        }
+    }
+
+    fn datagram(&mut self, message: PongClientMessage) -> Vec<u8> {
+        serialize(&self.counter.wrap(message))
+    }
+
+    fn hello(&mut self) -> Vec<u8> {
+        let hello = ClientMessage::Hello {
+            game_id: "net_pong".into(),
+            name: self.name.into(),
+            room: None,
+        };
+        self.datagram(hello)
+    }
+
+    /// Whether to send input for another tick before playing the next one.
+    fn wants_input(&self) -> bool {
+        self.next_input < TICKS && self.next_input <= self.sim_tick + INPUT_DELAY
+    }
+
+    /// Input for the next tick that needs it, steering toward the ball as
+    /// net_pong's bot does.
+    fn next_input(&mut self) -> Vec<u8> {
+        let slot = self.slot.expect("seated before the match starts");
+        let tick = self.next_input;
+        self.next_input += 1;
+        let input = ClientMessage::Input {
+            tick,
+            payload: Payload(follow_ball(&self.state, slot)),
+        };
+        self.datagram(input)
+    }
+
+    /// Take in what the relay sent and play every tick whose inputs have
+    /// arrived. Returns the ack for them, if there were any.
+    fn receive(&mut self, datagrams: Vec<Vec<u8>>) -> Option<Vec<u8>> {
+        for bytes in datagrams {
+            let sequenced = deserialize::<Sequenced<PongRelayMessage>>(&bytes)
+                .expect("the relay sends messages we can read");
+            match sequenced.message {
+                RelayMessage::Welcome { player_slot } => self.slot = Some(usize::from(player_slot)),
+                RelayMessage::GameStart => self.started = true,
+                RelayMessage::TickInputs { tick, inputs } => {
+                    let mut movement = [0.0; PLAYER_COUNT];
+                    for (slot, Payload(input)) in inputs.into_iter().enumerate().take(PLAYER_COUNT)
+                    {
+                        movement[slot] = input;
+                    }
+                    self.inputs.insert(tick, movement);
+                }
+                _ => {}
+            }
+        }
+
+        let played = self.sim_tick;
+        while let Some(movement) = self.inputs.remove(&self.sim_tick) {
+            self.events += self.state.step(&movement, DT).len();
+            self.checksums.push(checksum(&self.state));
+            self.sim_tick += 1;
+        }
+        (self.sim_tick > played).then(|| {
+            let ack = ClientMessage::AckTick {
+                tick: self.sim_tick - 1,
+            };
+            self.datagram(ack)
+        })
+    }
+}
+
+/// Move toward the ball while it comes our way, and back to the middle
+/// while it goes away.
+fn follow_ball(state: &PongState, slot: usize) -> f32 {
+    let target = if state.ball_approaching(slot) {
+        state.ball_along_side(slot)
+    } else {
+        0.0
+    };
+    let offset = (target - state.paddle[slot].to_f32()) / (PADDLE_HEIGHT / 2.0);
+    offset.clamp(-1.0, 1.0)
+}
+
+/// FNV-1a over the state as it goes on the wire.
+fn checksum(state: &PongState) -> u64 {
+    serialize(state)
+        .into_iter()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+struct Harness {
+    core: RelayCore,
+    reports: mpsc::UnboundedReceiver<DispatchEvent>,
+    socket: Arc<MemorySocket>,
+    clients: [HeadlessClient; PLAYER_COUNT],
+}
+
+impl Harness {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("relay_headless_match_{}", std::process::id()));
+        let settings = Settings {
+            room_capacity: 1,
+            // Nothing is ever re-sent on a timer, and the clock never moves
+            // to refill the rate limit.
+            tick_timeout: Duration::from_secs(3600),
+            rate_limit: 100_000,
+            rate_burst: 100_000,
+            max_pending: 8,
+            ..test_support::settings(PLAYER_COUNT)
+        };
+        let socket = Arc::new(MemorySocket::default());
+        let (core, reports) = RelayCore::new(
+            settings,
+            dir.join("rooms.toml"),
+            socket.clone(),
+            Arc::new(ManualClock::new()),
+            None,
+            Arc::new(Metrics::new()),
+        );
+        Self {
+            core,
+            reports,
+            socket,
+            clients: [
+                HeadlessClient::new(1, "alice"),
+                HeadlessClient::new(2, "bob"),
+            ],
+        }
+    }
+
+    /// Deliver a datagram from client `index` and wait until everything it
+    /// set off has been sent.
+    async fn deliver(&mut self, index: usize, datagram: &[u8]) {
+        let from = self.clients[index].addr;
+        self.core.handle_datagram(datagram, from);
+        for _ in 0..8 {
+            tokio::task::yield_now().await;
+            while let Ok(event) = self.reports.try_recv() {
+                self.core.handle_dispatch_event(event);
+            }
+        }
+        self.core.flush();
+    }
+
+    /// Let every client read what it was sent, and ack what it played.
+    async fn receive(&mut self) {
+        for index in 0..PLAYER_COUNT {
+            let sent = self.socket.take_datagrams(self.clients[index].addr);
+            if let Some(ack) = self.clients[index].receive(sent) {
+                self.deliver(index, &ack).await;
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn both_clients_reach_the_same_state_on_every_tick() {
+    // given a relay and two headless clients in a started match
+    let mut harness = Harness::new();
+    for index in 0..PLAYER_COUNT {
+        let hello = harness.clients[index].hello();
+        harness.deliver(index, &hello).await;
+    }
+    harness.receive().await;
+    assert!(harness.clients.iter().all(|client| client.started));
+
+    // when they play several hundred ticks, each sending input
+    // `INPUT_DELAY` ticks ahead of the tick it plays
+    for _ in 0..TICKS {
+        for index in 0..PLAYER_COUNT {
+            while harness.clients[index].wants_input() {
+                let input = harness.clients[index].next_input();
+                harness.deliver(index, &input).await;
+            }
+        }
+        harness.receive().await;
+    }
+
+    // then both played every tick and agreed on the state after each one
+    let [alice, bob] = &harness.clients;
+    assert_eq!(alice.checksums.len(), TICKS as usize);
+    assert_eq!(bob.checksums.len(), TICKS as usize);
+    let parted = alice
+        .checksums
+        .iter()
+        .zip(&bob.checksums)
+        .position(|(a, b)| a != b);
+    assert_eq!(parted, None, "the clients' states parted on this tick");
+
+    // and the ball hit things along the way, so there was a game to agree on
+    assert!(alice.events > 0);
+}